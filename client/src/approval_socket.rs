@@ -0,0 +1,159 @@
+use crate::error::Result;
+use crate::session_socket::{parse_approval_message, poll_text, Socket};
+use chef_de_vibe::models::{ApprovalFrame, ApprovalResponseResult};
+use futures_util::{SinkExt, Stream, StreamExt};
+use serde_json::{json, Value};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio_tungstenite::tungstenite::Message;
+
+/// What the approval WebSocket sends.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApprovalEvent {
+    /// A request waiting for a decision; pending ones are sent right after connecting
+    Request(ApprovalFrame),
+    /// Outcome of responses this client sent
+    Results(Vec<ApprovalResponseResult>),
+}
+
+/// An approver's answer to an approval request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    /// Let Claude use the tool with the input it asked for
+    Allow,
+    /// Let Claude use the tool with this input instead
+    AllowWith(Value),
+    /// Refuse, telling Claude why
+    Deny(String),
+}
+
+impl Decision {
+    /// The `response` object the server expects for `frame`
+    #[must_use]
+    pub fn response(&self, frame: &ApprovalFrame) -> Value {
+        match self {
+            Self::Allow => json!({
+                "behavior": "allow",
+                "updatedInput": frame.request.get("input").cloned().unwrap_or_else(|| json!({})),
+            }),
+            Self::AllowWith(input) => json!({"behavior": "allow", "updatedInput": input}),
+            Self::Deny(message) => json!({"behavior": "deny", "message": message}),
+        }
+    }
+}
+
+/// Connection to a session's approval WebSocket. It is a [`Stream`] of
+/// [`ApprovalEvent`]s; a frame that cannot be read yields an error without ending the
+/// stream.
+#[derive(Debug)]
+pub struct ApprovalSocket {
+    socket: Socket,
+}
+
+impl ApprovalSocket {
+    pub(crate) const fn new(socket: Socket) -> Self {
+        Self { socket }
+    }
+
+    async fn send(&mut self, message: &Value) -> Result<()> {
+        self.socket.send(Message::Text(message.to_string())).await?;
+        Ok(())
+    }
+
+    /// Answers the approval request `frame`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection is closed.
+    pub async fn respond(&mut self, frame: &ApprovalFrame, decision: &Decision) -> Result<()> {
+        self.send(&json!({"id": frame.id, "response": decision.response(frame)}))
+            .await
+    }
+
+    /// Asks Claude about the approval request `id` before deciding; the answer comes
+    /// back in the request's `clarifications`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection is closed.
+    pub async fn ask_clarification(&mut self, id: &str, question: &str) -> Result<()> {
+        self.send(&json!({"action": "ask_clarification", "id": id, "question": question}))
+            .await
+    }
+
+    /// Answers every approval request with `responder` until the server closes the
+    /// connection. Requests `responder` returns `None` for are left to other approvers.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of the connection; frames that cannot be read are skipped.
+    pub async fn respond_with<F, Fut>(mut self, mut responder: F) -> Result<()>
+    where
+        F: FnMut(ApprovalFrame) -> Fut,
+        Fut: Future<Output = Option<Decision>>,
+    {
+        while let Some(event) = self.next().await {
+            let frame = match event {
+                Ok(ApprovalEvent::Request(frame)) => frame,
+                Ok(ApprovalEvent::Results(_)) | Err(crate::Error::Json(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            if let Some(decision) = responder(frame.clone()).await {
+                self.respond(&frame, &decision).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Closes the connection
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the close handshake fails.
+    pub async fn close(mut self) -> Result<()> {
+        self.socket.close(None).await?;
+        Ok(())
+    }
+}
+
+impl Stream for ApprovalSocket {
+    type Item = Result<ApprovalEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        poll_text(&mut self.socket, cx).map(|text| {
+            text.map(|text| {
+                text.and_then(|text| parse_approval_message(serde_json::from_str(&text)?))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decision_response() {
+        let frame: ApprovalFrame = serde_json::from_value(json!({
+            "id": "a1",
+            "request": {"subtype": "can_use_tool", "tool_name": "Bash", "input": {"command": "ls"}},
+            "risk": "read_only",
+            "created_at": 1,
+        }))
+        .unwrap();
+
+        assert_eq!(
+            Decision::Allow.response(&frame),
+            json!({"behavior": "allow", "updatedInput": {"command": "ls"}})
+        );
+        assert_eq!(
+            Decision::AllowWith(json!({"command": "ls -a"})).response(&frame),
+            json!({"behavior": "allow", "updatedInput": {"command": "ls -a"}})
+        );
+        assert_eq!(
+            Decision::Deny("No".to_string()).response(&frame),
+            json!({"behavior": "deny", "message": "No"})
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use reqwest::StatusCode;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The server answered with an error status
+    #[error("{message} ({status})")]
+    Api {
+        status: StatusCode,
+        /// Machine-readable error code such as `SESSION_NOT_FOUND`, when the server sent one
+        code: Option<String>,
+        message: String,
+    },
+
+    #[error("Invalid server URL: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+
+    #[error("Invalid header: {0}")]
+    InvalidHeader(String),
+
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("WebSocket error: {0}")]
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+
+    #[error("Unexpected message from server: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(error: tokio_tungstenite::tungstenite::Error) -> Self {
+        Self::WebSocket(Box::new(error))
+    }
+}
+
+impl Error {
+    /// The server's error code, e.g. `SESSION_NOT_FOUND`
+    #[must_use]
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            Self::Api { code, .. } => code.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
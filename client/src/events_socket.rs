@@ -0,0 +1,84 @@
+use crate::error::Result;
+use crate::session_socket::{poll_text, Socket};
+use chef_de_vibe::server_events::ServerEvent;
+use futures_util::Stream;
+use serde_json::Value;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// What the server events WebSocket sends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerUpdate {
+    /// Something happened to a session the caller may access
+    Event(ServerEvent),
+    /// The connection fell behind and `missed` events were dropped; sessions have to be
+    /// listed again to catch up
+    Lagged { missed: u64 },
+}
+
+impl ServerUpdate {
+    fn parse(text: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(text)?;
+        if value.get("event").and_then(Value::as_str) == Some("lagged") {
+            return Ok(Self::Lagged {
+                missed: serde_json::from_value(value["missed"].clone())?,
+            });
+        }
+        Ok(Self::Event(serde_json::from_value(value)?))
+    }
+}
+
+/// Connection to the server events WebSocket. It is a [`Stream`] of [`ServerUpdate`]s;
+/// a frame that cannot be read yields an error without ending the stream.
+#[derive(Debug)]
+pub struct EventsSocket {
+    socket: Socket,
+}
+
+impl EventsSocket {
+    pub(crate) const fn new(socket: Socket) -> Self {
+        Self { socket }
+    }
+
+    /// Closes the connection
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the close handshake fails.
+    pub async fn close(mut self) -> Result<()> {
+        self.socket.close(None).await?;
+        Ok(())
+    }
+}
+
+impl Stream for EventsSocket {
+    type Item = Result<ServerUpdate>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        poll_text(&mut self.socket, cx)
+            .map(|text| text.map(|text| text.and_then(|text| ServerUpdate::parse(&text))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chef_de_vibe::server_events::ServerEventKind;
+
+    #[test]
+    fn test_parse_server_update() {
+        let update = ServerUpdate::parse(
+            r#"{"session_id":"s1","timestamp":"2026-01-01T00:00:00Z","event":"pending_approvals","count":2}"#,
+        )
+        .unwrap();
+        let ServerUpdate::Event(event) = update else {
+            panic!("Expected an event, got {update:?}");
+        };
+        assert_eq!(event.session_id, "s1");
+        assert_eq!(event.kind, ServerEventKind::PendingApprovals { count: 2 });
+
+        let update = ServerUpdate::parse(r#"{"event":"lagged","missed":3}"#).unwrap();
+        assert_eq!(update, ServerUpdate::Lagged { missed: 3 });
+        assert!(ServerUpdate::parse(r#"{"event":"unknown"}"#).is_err());
+    }
+}
@@ -0,0 +1,992 @@
+//! Typed async client for the Chef de Vibe API.
+//!
+//! Requests and responses are the server's own types from [`models`], so the client is
+//! always in step with the server it is built with. Every REST endpoint is a method of
+//! [`Client`]; the session, approval and server events sockets are opened with
+//! [`Client::session_socket`], [`Client::approval_socket`] and [`Client::events_socket`].
+
+mod approval_socket;
+mod error;
+mod events_socket;
+mod session_socket;
+
+pub use approval_socket::{ApprovalEvent, ApprovalSocket, Decision};
+pub use chef_de_vibe::api::websocket::{WebSocketChannels, WebSocketParams};
+pub use chef_de_vibe::models;
+pub use chef_de_vibe::server_events::{ServerEvent, ServerEventKind};
+pub use chef_de_vibe::session_metadata::SessionMetadataPatch;
+pub use chef_de_vibe::slo::{Operation, OperationReport, SloReport};
+pub use chef_de_vibe::tool_catalog::ToolCatalog;
+pub use chef_de_vibe::transcript_export::{ExportFormat, ExportedSession};
+pub use chef_de_vibe::uploads::UploadStatus;
+pub use chef_de_vibe::usage::UsageTotals;
+pub use chef_de_vibe::web_push::PushSubscription;
+pub use error::{Error, Result};
+pub use events_socket::{EventsSocket, ServerUpdate};
+pub use session_socket::{SessionEvent, SessionSocket};
+
+use chef_de_vibe::error::ErrorResponse;
+use models::{
+    AdminStatusResponse, AppendUploadQuery, ApprovalHistoryQuery, ApprovalHistoryResponse,
+    ApprovalResponseResult, BulkApprovalResponse, CreateAnchorRequest, CreateSessionRequest,
+    CreateSessionResponse, CreateUploadRequest, DrainingState, ExportSessionQuery,
+    ForkSessionRequest, GetSessionQuery, GetSessionResponse, ImportSessionsQuery,
+    ImportSessionsResponse, ListSessionsQuery, ListSessionsResponse, PinnedSessionsResponse,
+    PreferencesResponse, PushSubscriptionsResponse, RescanResponse, ResolveHeldMessagesRequest,
+    ResolveHeldMessagesResponse, SendMessageQuery, SendMessageResponse, SessionAnchorsResponse,
+    SessionApprovalRulesResponse, SessionApprovalsResponse, SessionClientsResponse,
+    SessionContentQuery, SessionContentResponse, SessionContextResponse, SessionFilesQuery,
+    SessionFilesResponse, SessionMetadataResponse, SessionQueueResponse, SessionScratchResponse,
+    SessionWatchesResponse, SetSessionDebugRequest, SetSessionDebugResponse,
+    TelemetryPreviewResponse, TerminateSessionResponse, UnsubscribePushQuery, UnwatchSessionQuery,
+    UsageQuery, UsageResponse, WatchSessionRequest, WebPushKeyResponse,
+};
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::{Method, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use url::Url;
+
+/// An image or document stored with a session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Media {
+    pub content_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// Client of one Chef de Vibe server.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: Url,
+    identity: Option<(HeaderName, HeaderValue)>,
+    token: Option<HeaderValue>,
+}
+
+impl Client {
+    /// Client of the server at `base_url`, e.g. `http://localhost:3000`. A path in the
+    /// URL is kept, for servers behind a proxy under a prefix.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `base_url` is not a valid HTTP(S) URL.
+    pub fn new(base_url: &str) -> Result<Self> {
+        let base_url = Url::parse(base_url)?;
+        if base_url.cannot_be_a_base() {
+            return Err(Error::InvalidUrl(
+                url::ParseError::RelativeUrlWithCannotBeABaseBase,
+            ));
+        }
+        Ok(Self {
+            http: reqwest::Client::new(),
+            base_url,
+            identity: None,
+            token: None,
+        })
+    }
+
+    /// Sends `value` in `header` with every request, for servers configured with
+    /// `IDENTITY_HEADER`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `header` or `value` cannot be sent as an HTTP header.
+    pub fn with_identity(mut self, header: &str, value: &str) -> Result<Self> {
+        let name = HeaderName::from_bytes(header.as_bytes())
+            .map_err(|e| Error::InvalidHeader(e.to_string()))?;
+        let value =
+            HeaderValue::from_str(value).map_err(|e| Error::InvalidHeader(e.to_string()))?;
+        self.identity = Some((name, value));
+        Ok(self)
+    }
+
+    /// Sends `token` as a bearer token with every request and WebSocket upgrade, for
+    /// servers configured with `CHEF_DE_VIBE_API_TOKEN`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `token` cannot be sent as an HTTP header.
+    pub fn with_token(mut self, token: &str) -> Result<Self> {
+        let mut value = HeaderValue::from_str(&format!("Bearer {token}"))
+            .map_err(|e| Error::InvalidHeader(e.to_string()))?;
+        value.set_sensitive(true);
+        self.token = Some(value);
+        Ok(self)
+    }
+
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .expect("base URL was checked to be a base")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    fn request(&self, method: Method, segments: &[&str]) -> RequestBuilder {
+        let mut builder = self.http.request(method, self.url(segments));
+        if let Some((name, value)) = &self.identity {
+            builder = builder.header(name, value);
+        }
+        if let Some(token) = &self.token {
+            builder = builder.header(reqwest::header::AUTHORIZATION, token);
+        }
+        builder
+    }
+
+    async fn send(builder: RequestBuilder) -> Result<reqwest::Response> {
+        let response = builder.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response.text().await?;
+        Err(match serde_json::from_str::<ErrorResponse>(&body) {
+            Ok(error) => Error::Api {
+                status,
+                code: Some(error.code),
+                message: error.error,
+            },
+            Err(_) => Error::Api {
+                status,
+                code: None,
+                message: body,
+            },
+        })
+    }
+
+    async fn send_json<T: DeserializeOwned>(builder: RequestBuilder) -> Result<T> {
+        let body = Self::send(builder).await?.bytes().await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, segments: &[&str]) -> Result<T> {
+        Self::send_json(self.request(Method::GET, segments)).await
+    }
+
+    async fn get_with<T: DeserializeOwned, Q: Serialize>(
+        &self,
+        segments: &[&str],
+        query: &Q,
+    ) -> Result<T> {
+        Self::send_json(self.request(Method::GET, segments).query(query)).await
+    }
+
+    async fn get_media(&self, segments: &[&str]) -> Result<Media> {
+        let response = Self::send(self.request(Method::GET, segments)).await?;
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        Ok(Media {
+            content_type,
+            data: response.bytes().await?.to_vec(),
+        })
+    }
+
+    async fn call<T: DeserializeOwned, B: Serialize + ?Sized>(
+        &self,
+        method: Method,
+        segments: &[&str],
+        body: &B,
+    ) -> Result<T> {
+        Self::send_json(self.request(method, segments).json(body)).await
+    }
+
+    /// `GET /api/v1/sessions`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn list_sessions(&self, query: &ListSessionsQuery) -> Result<ListSessionsResponse> {
+        self.get_with(&["api", "v1", "sessions"], query).await
+    }
+
+    /// `POST /api/v1/sessions`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn create_session(
+        &self,
+        request: &CreateSessionRequest,
+    ) -> Result<CreateSessionResponse> {
+        self.call(Method::POST, &["api", "v1", "sessions"], request)
+            .await
+    }
+
+    /// `POST /api/v1/sessions/import` with an export of another tool as `export`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn import_sessions(
+        &self,
+        query: &ImportSessionsQuery,
+        export: &serde_json::Value,
+    ) -> Result<ImportSessionsResponse> {
+        let builder = self
+            .request(Method::POST, &["api", "v1", "sessions", "import"])
+            .query(query)
+            .json(export);
+        Self::send_json(builder).await
+    }
+
+    /// `GET /api/v1/sessions/:id`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn get_session(
+        &self,
+        session_id: &str,
+        query: &GetSessionQuery,
+    ) -> Result<GetSessionResponse> {
+        self.get_with(&["api", "v1", "sessions", session_id], query)
+            .await
+    }
+
+    /// `GET /api/v1/sessions/:id/content`, the journal entries after `after_line` or
+    /// `after_uuid`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn session_content(
+        &self,
+        session_id: &str,
+        query: &SessionContentQuery,
+    ) -> Result<SessionContentResponse> {
+        self.get_with(&["api", "v1", "sessions", session_id, "content"], query)
+            .await
+    }
+
+    /// `DELETE /api/v1/sessions/:id`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn delete_session(&self, session_id: &str) -> Result<TerminateSessionResponse> {
+        Self::send_json(self.request(Method::DELETE, &["api", "v1", "sessions", session_id])).await
+    }
+
+    /// `POST /api/v1/sessions/:id/fork`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn fork_session(
+        &self,
+        session_id: &str,
+        request: &ForkSessionRequest,
+    ) -> Result<CreateSessionResponse> {
+        self.call(
+            Method::POST,
+            &["api", "v1", "sessions", session_id, "fork"],
+            request,
+        )
+        .await
+    }
+
+    /// `POST /api/v1/uploads`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn create_upload(&self, request: &CreateUploadRequest) -> Result<UploadStatus> {
+        self.call(Method::POST, &["api", "v1", "uploads"], request)
+            .await
+    }
+
+    /// `PUT /api/v1/uploads/:id`, appending `chunk` at `offset`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn append_upload(
+        &self,
+        upload_id: &str,
+        offset: u64,
+        chunk: Vec<u8>,
+    ) -> Result<UploadStatus> {
+        let builder = self
+            .request(Method::PUT, &["api", "v1", "uploads", upload_id])
+            .query(&AppendUploadQuery { offset })
+            .body(chunk);
+        Self::send_json(builder).await
+    }
+
+    /// `GET /api/v1/uploads/:id`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn get_upload(&self, upload_id: &str) -> Result<UploadStatus> {
+        self.get(&["api", "v1", "uploads", upload_id]).await
+    }
+
+    /// `GET /api/v1/sessions/:id/files`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn session_files(
+        &self,
+        session_id: &str,
+        query: &SessionFilesQuery,
+    ) -> Result<SessionFilesResponse> {
+        self.get_with(&["api", "v1", "sessions", session_id, "files"], query)
+            .await
+    }
+
+    /// `GET /api/v1/sessions/:id/export`, the document as text. A [`ExportFormat::Json`]
+    /// export parses as [`ExportedSession`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn export_session(
+        &self,
+        session_id: &str,
+        query: &ExportSessionQuery,
+    ) -> Result<String> {
+        let builder = self
+            .request(
+                Method::GET,
+                &["api", "v1", "sessions", session_id, "export"],
+            )
+            .query(query);
+        Ok(Self::send(builder).await?.text().await?)
+    }
+
+    /// `GET /api/v1/sessions/:id/media/:media_id`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn session_media(&self, session_id: &str, media_id: &str) -> Result<Media> {
+        self.get_media(&["api", "v1", "sessions", session_id, "media", media_id])
+            .await
+    }
+
+    /// `GET /api/v1/sessions/:id/scratch`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn session_scratch(&self, session_id: &str) -> Result<SessionScratchResponse> {
+        self.get(&["api", "v1", "sessions", session_id, "scratch"])
+            .await
+    }
+
+    /// `GET /api/v1/sessions/:id/scratch/*path`, with `path` relative to the scratch
+    /// directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn scratch_file(&self, session_id: &str, path: &str) -> Result<Media> {
+        let mut segments = vec!["api", "v1", "sessions", session_id, "scratch"];
+        segments.extend(path.split('/'));
+        self.get_media(&segments).await
+    }
+
+    /// `GET /api/v1/sessions/:id/context`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn session_context(&self, session_id: &str) -> Result<SessionContextResponse> {
+        self.get(&["api", "v1", "sessions", session_id, "context"])
+            .await
+    }
+
+    /// `PUT /api/v1/sessions/:id/debug`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn set_session_debug(
+        &self,
+        session_id: &str,
+        request: &SetSessionDebugRequest,
+    ) -> Result<SetSessionDebugResponse> {
+        self.call(
+            Method::PUT,
+            &["api", "v1", "sessions", session_id, "debug"],
+            request,
+        )
+        .await
+    }
+
+    /// `GET /api/v1/sessions/:id/wirelog`, the lines relayed to and from Claude as
+    /// newline-delimited JSON
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn session_wirelog(&self, session_id: &str) -> Result<String> {
+        Ok(Self::send(self.request(
+            Method::GET,
+            &["api", "v1", "sessions", session_id, "wirelog"],
+        ))
+        .await?
+        .text()
+        .await?)
+    }
+
+    /// `GET /api/v1/sessions/:id/approvals`, the pending approval requests
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn session_approvals(&self, session_id: &str) -> Result<SessionApprovalsResponse> {
+        self.get(&["api", "v1", "sessions", session_id, "approvals"])
+            .await
+    }
+
+    /// `GET /api/v1/sessions/:id/approval_rules`, the remembered approval decisions
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn approval_rules(&self, session_id: &str) -> Result<SessionApprovalRulesResponse> {
+        self.get(&["api", "v1", "sessions", session_id, "approval_rules"])
+            .await
+    }
+
+    /// `DELETE /api/v1/sessions/:id/approval_rules/:rule_id`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn delete_approval_rule(
+        &self,
+        session_id: &str,
+        rule_id: &str,
+    ) -> Result<SessionApprovalRulesResponse> {
+        Self::send_json(self.request(
+            Method::DELETE,
+            &[
+                "api",
+                "v1",
+                "sessions",
+                session_id,
+                "approval_rules",
+                rule_id,
+            ],
+        ))
+        .await
+    }
+
+    /// `POST /api/v1/sessions/:id/approvals/:approval_id`, answering one approval.
+    /// `response` is what the approval WebSocket sends as `response`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn submit_approval(
+        &self,
+        session_id: &str,
+        approval_id: &str,
+        response: &serde_json::Value,
+    ) -> Result<ApprovalResponseResult> {
+        self.call(
+            Method::POST,
+            &[
+                "api",
+                "v1",
+                "sessions",
+                session_id,
+                "approvals",
+                approval_id,
+            ],
+            response,
+        )
+        .await
+    }
+
+    /// `POST /api/v1/sessions/:id/approvals`, answering several approvals at once.
+    /// Each response is `{"id", "response"}` like on the approval WebSocket.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn submit_approvals(
+        &self,
+        session_id: &str,
+        responses: &[serde_json::Value],
+    ) -> Result<BulkApprovalResponse> {
+        self.call(
+            Method::POST,
+            &["api", "v1", "sessions", session_id, "approvals"],
+            responses,
+        )
+        .await
+    }
+
+    /// `GET /api/v1/sessions/:id/queue`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn session_queue(&self, session_id: &str) -> Result<SessionQueueResponse> {
+        self.get(&["api", "v1", "sessions", session_id, "queue"])
+            .await
+    }
+
+    /// `GET /api/v1/sessions/:id/clients`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn session_clients(&self, session_id: &str) -> Result<SessionClientsResponse> {
+        self.get(&["api", "v1", "sessions", session_id, "clients"])
+            .await
+    }
+
+    /// `POST /api/v1/sessions/:id/queue/:client_id`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn resolve_held_messages(
+        &self,
+        session_id: &str,
+        client_id: &str,
+        request: &ResolveHeldMessagesRequest,
+    ) -> Result<ResolveHeldMessagesResponse> {
+        self.call(
+            Method::POST,
+            &["api", "v1", "sessions", session_id, "queue", client_id],
+            request,
+        )
+        .await
+    }
+
+    /// `POST /api/v1/sessions/:id/messages`: queues `message` for Claude as if it was
+    /// sent over the session socket.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn send_message(
+        &self,
+        session_id: &str,
+        query: &SendMessageQuery,
+        message: &serde_json::Value,
+    ) -> Result<SendMessageResponse> {
+        Self::send_json(
+            self.request(
+                Method::POST,
+                &["api", "v1", "sessions", session_id, "messages"],
+            )
+            .query(query)
+            .json(message),
+        )
+        .await
+    }
+
+    /// `POST /api/v1/sessions/:id/anchors`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn create_anchor(
+        &self,
+        session_id: &str,
+        request: &CreateAnchorRequest,
+    ) -> Result<SessionAnchorsResponse> {
+        self.call(
+            Method::POST,
+            &["api", "v1", "sessions", session_id, "anchors"],
+            request,
+        )
+        .await
+    }
+
+    /// `DELETE /api/v1/sessions/:id/anchors/:name`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn delete_anchor(
+        &self,
+        session_id: &str,
+        name: &str,
+    ) -> Result<SessionAnchorsResponse> {
+        Self::send_json(self.request(
+            Method::DELETE,
+            &["api", "v1", "sessions", session_id, "anchors", name],
+        ))
+        .await
+    }
+
+    /// `PATCH /api/v1/sessions/:id/metadata`, changing the session's tags and metadata.
+    /// Sessions are listed by tag with [`ListSessionsQuery::tag`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn update_session_metadata(
+        &self,
+        session_id: &str,
+        patch: &SessionMetadataPatch,
+    ) -> Result<SessionMetadataResponse> {
+        self.call(
+            Method::PATCH,
+            &["api", "v1", "sessions", session_id, "metadata"],
+            patch,
+        )
+        .await
+    }
+
+    /// `PUT /api/v1/sessions/:id/pin`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn pin_session(&self, session_id: &str) -> Result<PinnedSessionsResponse> {
+        Self::send_json(self.request(Method::PUT, &["api", "v1", "sessions", session_id, "pin"]))
+            .await
+    }
+
+    /// `DELETE /api/v1/sessions/:id/pin`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn unpin_session(&self, session_id: &str) -> Result<PinnedSessionsResponse> {
+        Self::send_json(self.request(
+            Method::DELETE,
+            &["api", "v1", "sessions", session_id, "pin"],
+        ))
+        .await
+    }
+
+    /// `GET /api/v1/sessions/:id/watch`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn session_watches(&self, session_id: &str) -> Result<SessionWatchesResponse> {
+        self.get(&["api", "v1", "sessions", session_id, "watch"])
+            .await
+    }
+
+    /// `PUT /api/v1/sessions/:id/watch`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn watch_session(
+        &self,
+        session_id: &str,
+        request: &WatchSessionRequest,
+    ) -> Result<SessionWatchesResponse> {
+        self.call(
+            Method::PUT,
+            &["api", "v1", "sessions", session_id, "watch"],
+            request,
+        )
+        .await
+    }
+
+    /// `DELETE /api/v1/sessions/:id/watch`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn unwatch_session(
+        &self,
+        session_id: &str,
+        query: &UnwatchSessionQuery,
+    ) -> Result<SessionWatchesResponse> {
+        let builder = self
+            .request(
+                Method::DELETE,
+                &["api", "v1", "sessions", session_id, "watch"],
+            )
+            .query(query);
+        Self::send_json(builder).await
+    }
+
+    /// `GET /api/v1/preferences`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn preferences(&self) -> Result<PreferencesResponse> {
+        self.get(&["api", "v1", "preferences"]).await
+    }
+
+    /// `PUT /api/v1/preferences`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn set_preferences(
+        &self,
+        preferences: &serde_json::Value,
+    ) -> Result<PreferencesResponse> {
+        self.call(Method::PUT, &["api", "v1", "preferences"], preferences)
+            .await
+    }
+
+    /// `GET /api/v1/push/vapid_public_key`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn web_push_key(&self) -> Result<WebPushKeyResponse> {
+        self.get(&["api", "v1", "push", "vapid_public_key"]).await
+    }
+
+    /// `GET /api/v1/push/subscriptions`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn push_subscriptions(&self) -> Result<PushSubscriptionsResponse> {
+        self.get(&["api", "v1", "push", "subscriptions"]).await
+    }
+
+    /// `POST /api/v1/push/subscriptions`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn subscribe_push(
+        &self,
+        subscription: &PushSubscription,
+    ) -> Result<PushSubscriptionsResponse> {
+        self.call(
+            Method::POST,
+            &["api", "v1", "push", "subscriptions"],
+            subscription,
+        )
+        .await
+    }
+
+    /// `DELETE /api/v1/push/subscriptions`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn unsubscribe_push(&self, endpoint: &str) -> Result<PushSubscriptionsResponse> {
+        let builder = self
+            .request(Method::DELETE, &["api", "v1", "push", "subscriptions"])
+            .query(&UnsubscribePushQuery {
+                endpoint: endpoint.to_string(),
+            });
+        Self::send_json(builder).await
+    }
+
+    /// `GET /api/v1/projects/:id/tools`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn project_tools(&self, project_id: &str) -> Result<ToolCatalog> {
+        self.get(&["api", "v1", "projects", project_id, "tools"])
+            .await
+    }
+
+    /// `GET /api/v1/approvals/history`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn approval_history(
+        &self,
+        query: &ApprovalHistoryQuery,
+    ) -> Result<ApprovalHistoryResponse> {
+        self.get_with(&["api", "v1", "approvals", "history"], query)
+            .await
+    }
+
+    /// `GET /api/v1/telemetry`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn telemetry_preview(&self) -> Result<TelemetryPreviewResponse> {
+        self.get(&["api", "v1", "telemetry"]).await
+    }
+
+    /// `GET /api/v1/usage`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn usage(&self, query: &UsageQuery) -> Result<UsageResponse> {
+        self.get_with(&["api", "v1", "usage"], query).await
+    }
+
+    /// `GET /metrics`, in the Prometheus text format
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn metrics(&self) -> Result<String> {
+        Ok(Self::send(self.request(Method::GET, &["metrics"]))
+            .await?
+            .text()
+            .await?)
+    }
+
+    /// `GET /api/v1/admin/slo`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn slo_report(&self) -> Result<SloReport> {
+        self.get(&["api", "v1", "admin", "slo"]).await
+    }
+
+    /// `GET /api/v1/admin/status`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn admin_status(&self) -> Result<AdminStatusResponse> {
+        self.get(&["api", "v1", "admin", "status"]).await
+    }
+
+    /// `PUT /api/v1/admin/draining`, starting or stopping draining mode
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn set_draining(&self, draining: bool) -> Result<DrainingState> {
+        self.call(
+            Method::PUT,
+            &["api", "v1", "admin", "draining"],
+            &DrainingState { draining },
+        )
+        .await
+    }
+
+    /// `POST /api/v1/admin/rescan`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn rescan_sessions(&self) -> Result<RescanResponse> {
+        Self::send_json(self.request(Method::POST, &["api", "v1", "admin", "rescan"])).await
+    }
+
+    /// `POST /api/v1/admin/sessions/:id/kill`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn kill_session(&self, session_id: &str) -> Result<TerminateSessionResponse> {
+        Self::send_json(self.request(
+            Method::POST,
+            &["api", "v1", "admin", "sessions", session_id, "kill"],
+        ))
+        .await
+    }
+
+    /// `POST /v1/chat/completions` without streaming. The body and the answer are in the
+    /// `OpenAI` chat completions format, so they are passed as they are.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the server rejects it.
+    pub async fn chat_completion(&self, request: &serde_json::Value) -> Result<serde_json::Value> {
+        self.call(Method::POST, &["v1", "chat", "completions"], request)
+            .await
+    }
+
+    fn websocket_request(
+        &self,
+        segments: &[&str],
+        query: Option<&WebSocketParams>,
+    ) -> Result<tokio_tungstenite::tungstenite::handshake::client::Request> {
+        let mut url = self.url(segments);
+        let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+        url.set_scheme(scheme)
+            .map_err(|()| Error::InvalidUrl(url::ParseError::InvalidDomainCharacter))?;
+        if let Some(serde_json::Value::Object(params)) =
+            query.map(serde_json::to_value).transpose()?
+        {
+            let mut pairs = url.query_pairs_mut();
+            for (key, value) in &params {
+                match value {
+                    serde_json::Value::String(value) => pairs.append_pair(key, value),
+                    value => pairs.append_pair(key, &value.to_string()),
+                };
+            }
+        }
+        let mut request = url.as_str().into_client_request()?;
+        if let Some((name, value)) = &self.identity {
+            let name = tokio_tungstenite::tungstenite::http::HeaderName::from_bytes(
+                name.as_str().as_bytes(),
+            )
+            .map_err(|e| Error::InvalidHeader(e.to_string()))?;
+            let value =
+                tokio_tungstenite::tungstenite::http::HeaderValue::from_bytes(value.as_bytes())
+                    .map_err(|e| Error::InvalidHeader(e.to_string()))?;
+            request.headers_mut().insert(name, value);
+        }
+        if let Some(token) = &self.token {
+            let value =
+                tokio_tungstenite::tungstenite::http::HeaderValue::from_bytes(token.as_bytes())
+                    .map_err(|e| Error::InvalidHeader(e.to_string()))?;
+            request.headers_mut().insert(
+                tokio_tungstenite::tungstenite::http::header::AUTHORIZATION,
+                value,
+            );
+        }
+        Ok(request)
+    }
+
+    /// Connects to the session's main WebSocket, `/api/v1/sessions/:id/claude_ws`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection cannot be opened.
+    pub async fn session_socket(
+        &self,
+        session_id: &str,
+        params: &WebSocketParams,
+    ) -> Result<SessionSocket> {
+        let request = self.websocket_request(
+            &["api", "v1", "sessions", session_id, "claude_ws"],
+            Some(params),
+        )?;
+        let (stream, _) = tokio_tungstenite::connect_async(request).await?;
+        Ok(SessionSocket::new(stream))
+    }
+
+    /// Connects to the session's approval WebSocket,
+    /// `/api/v1/sessions/:id/claude_approvals_ws`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection cannot be opened.
+    pub async fn approval_socket(&self, session_id: &str) -> Result<ApprovalSocket> {
+        let request = self.websocket_request(
+            &["api", "v1", "sessions", session_id, "claude_approvals_ws"],
+            None,
+        )?;
+        let (stream, _) = tokio_tungstenite::connect_async(request).await?;
+        Ok(ApprovalSocket::new(stream))
+    }
+
+    /// Connects to the server events WebSocket, `/api/v1/events_ws`, which reports
+    /// sessions of the caller coming and going.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection cannot be opened.
+    pub async fn events_socket(&self) -> Result<EventsSocket> {
+        let request = self.websocket_request(&["api", "v1", "events_ws"], None)?;
+        let (stream, _) = tokio_tungstenite::connect_async(request).await?;
+        Ok(EventsSocket::new(stream))
+    }
+}
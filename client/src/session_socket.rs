@@ -0,0 +1,227 @@
+use crate::error::{Error, Result};
+use chef_de_vibe::models::{ApprovalFrame, ApprovalResponseResult};
+use futures_util::{SinkExt, Stream, StreamExt};
+use serde_json::{json, Value};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+pub(crate) type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Something that happened in a session, as its main WebSocket tells it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionEvent {
+    /// A line of Claude's output, such as a `system`, `assistant`, `user` or `result`
+    /// message, or input another client sent. Fields the connection opted into, like
+    /// `seq`, `session_seq` or `server_sent_at`, are included.
+    Claude(Value),
+    /// Another client's presence state; `Null` when it left
+    Presence { client_id: String, state: Value },
+    /// Write pacing holds back a message this client sent
+    Queued {
+        position: usize,
+        estimated_wait_ms: u64,
+    },
+    /// A pending approval, on connections with `channels=all`
+    Approval(ApprovalFrame),
+    /// Outcome of approval responses this client sent, on connections with `channels=all`
+    ApprovalResults(Vec<ApprovalResponseResult>),
+    /// Messages after `after_seq` could not all be replayed on resuming; the transcript
+    /// has to be reloaded to catch up
+    ReplayGap { after_seq: u64 },
+}
+
+impl SessionEvent {
+    fn parse(text: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(text)?;
+        match value.get("type").and_then(Value::as_str) {
+            Some("presence") => Ok(Self::Presence {
+                client_id: serde_json::from_value(value["client_id"].clone())?,
+                state: value["state"].clone(),
+            }),
+            Some("queued") => Ok(Self::Queued {
+                position: serde_json::from_value(value["position"].clone())?,
+                estimated_wait_ms: serde_json::from_value(value["estimated_wait_ms"].clone())?,
+            }),
+            Some("replay_gap") => Ok(Self::ReplayGap {
+                after_seq: serde_json::from_value(value["after_seq"].clone())?,
+            }),
+            Some("approval") => {
+                parse_approval_message(value["message"].clone()).map(|event| match event {
+                    crate::ApprovalEvent::Request(frame) => Self::Approval(frame),
+                    crate::ApprovalEvent::Results(results) => Self::ApprovalResults(results),
+                })
+            }
+            _ => Ok(Self::Claude(value)),
+        }
+    }
+}
+
+/// Reads a frame of the approval channel: a pending request, or the outcome of responses.
+pub(crate) fn parse_approval_message(message: Value) -> Result<crate::ApprovalEvent> {
+    if message.get("type").and_then(Value::as_str) == Some("approval_results") {
+        let results = serde_json::from_value(message["results"].clone())?;
+        return Ok(crate::ApprovalEvent::Results(results));
+    }
+    Ok(crate::ApprovalEvent::Request(serde_json::from_value(
+        message,
+    )?))
+}
+
+/// Text frames of `socket`, skipping pings, pongs and binary frames; ends when the
+/// server closes the connection.
+pub(crate) fn poll_text(socket: &mut Socket, cx: &mut Context<'_>) -> Poll<Option<Result<String>>> {
+    loop {
+        return match socket.poll_next_unpin(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(None | Some(Ok(Message::Close(_)))) => Poll::Ready(None),
+            Poll::Ready(Some(Ok(Message::Text(text)))) => Poll::Ready(Some(Ok(text))),
+            Poll::Ready(Some(Ok(_))) => continue,
+            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(Error::from(e)))),
+        };
+    }
+}
+
+/// Connection to a session's main WebSocket. It is a [`Stream`] of [`SessionEvent`]s;
+/// a frame that cannot be read yields an error without ending the stream.
+#[derive(Debug)]
+pub struct SessionSocket {
+    socket: Socket,
+}
+
+impl SessionSocket {
+    pub(crate) const fn new(socket: Socket) -> Self {
+        Self { socket }
+    }
+
+    /// Sends a message to Claude as it is, e.g. a stream-json `user` message
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection is closed.
+    pub async fn send(&mut self, message: &Value) -> Result<()> {
+        self.socket.send(Message::Text(message.to_string())).await?;
+        Ok(())
+    }
+
+    /// Sends `text` to Claude as a user message
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection is closed.
+    pub async fn send_user_message(&mut self, text: &str) -> Result<()> {
+        self.send(&json!({
+            "type": "user",
+            "message": {"role": "user", "content": text},
+        }))
+        .await
+    }
+
+    /// Shares this client's presence state with the other clients of the session
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection is closed.
+    pub async fn set_presence(&mut self, state: Value) -> Result<()> {
+        self.send(&json!({"type": "presence", "state": state}))
+            .await
+    }
+
+    /// Acknowledges messages up to `seq`, on connections with `ack=true`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection is closed.
+    pub async fn ack(&mut self, seq: u64) -> Result<()> {
+        self.send(&json!({"ack": seq})).await
+    }
+
+    /// Answers a pending approval, on connections with `channels=all`. `response` is
+    /// what the approval WebSocket takes, e.g. `{"behavior": "deny", "message": ...}`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection is closed.
+    pub async fn respond_to_approval(&mut self, id: &str, response: Value) -> Result<()> {
+        self.send(&json!({
+            "type": "approval",
+            "message": {"id": id, "response": response},
+        }))
+        .await
+    }
+
+    /// Closes the connection
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the close handshake fails.
+    pub async fn close(mut self) -> Result<()> {
+        self.socket.close(None).await?;
+        Ok(())
+    }
+}
+
+impl Stream for SessionSocket {
+    type Item = Result<SessionEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        poll_text(&mut self.socket, cx)
+            .map(|text| text.map(|text| text.and_then(|text| SessionEvent::parse(&text))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_session_event() {
+        let event =
+            SessionEvent::parse(r#"{"type":"queued","position":2,"estimated_wait_ms":500}"#)
+                .unwrap();
+        assert_eq!(
+            event,
+            SessionEvent::Queued {
+                position: 2,
+                estimated_wait_ms: 500
+            }
+        );
+
+        let event =
+            SessionEvent::parse(r#"{"type":"presence","client_id":"c1","state":null}"#).unwrap();
+        assert_eq!(
+            event,
+            SessionEvent::Presence {
+                client_id: "c1".to_string(),
+                state: Value::Null
+            }
+        );
+
+        let event = SessionEvent::parse(
+            r#"{"type":"approval","message":{"id":"a1","request":{"tool_name":"Bash"},"risk":"destructive","priority":"high","created_at":1}}"#,
+        )
+        .unwrap();
+        let SessionEvent::Approval(frame) = event else {
+            panic!("Expected an approval, got {event:?}");
+        };
+        assert_eq!(frame.request["tool_name"], "Bash");
+
+        let event = SessionEvent::parse(
+            r#"{"type":"approval","message":{"type":"approval_results","results":[{"id":"a1","accepted":true}]}}"#,
+        )
+        .unwrap();
+        assert!(matches!(event, SessionEvent::ApprovalResults(results) if results[0].accepted));
+
+        let event = SessionEvent::parse(r#"{"type":"replay_gap","after_seq":7}"#).unwrap();
+        assert_eq!(event, SessionEvent::ReplayGap { after_seq: 7 });
+
+        let event = SessionEvent::parse(r#"{"type":"result","subtype":"success"}"#).unwrap();
+        assert_eq!(
+            event,
+            SessionEvent::Claude(json!({"type": "result", "subtype": "success"}))
+        );
+        assert!(SessionEvent::parse("not json").is_err());
+    }
+}
@@ -0,0 +1,477 @@
+#[path = "../../tests/helpers/mock_claude.rs"]
+mod mock_claude;
+
+use chef_de_vibe::{
+    api::handlers::AppState,
+    config::{ApiKey, ApiKeys, ApiToken, Config},
+    session_manager::SessionManager,
+};
+use chef_de_vibe_client::models::{
+    ApprovalHistoryQuery, CreateSessionRequest, CreateSessionResponse, GetSessionQuery,
+    ListSessionsQuery, SessionContentQuery,
+};
+use chef_de_vibe_client::{
+    Client, Decision, ServerEventKind, ServerUpdate, SessionEvent, SessionMetadataPatch,
+    WebSocketChannels, WebSocketParams,
+};
+use futures_util::StreamExt;
+use mock_claude::MockClaude;
+use reqwest::StatusCode;
+use serial_test::serial;
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::time::timeout;
+
+/// The routes the client is tested against.
+fn routes() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route(
+            "/api/v1/sessions",
+            axum::routing::get(chef_de_vibe::api::handlers::list_sessions)
+                .post(chef_de_vibe::api::handlers::create_session),
+        )
+        .route(
+            "/api/v1/sessions/:id",
+            axum::routing::get(chef_de_vibe::api::handlers::get_session),
+        )
+        .route(
+            "/api/v1/sessions/:id/approval_rules",
+            axum::routing::get(chef_de_vibe::api::handlers::get_approval_rules),
+        )
+        .route(
+            "/api/v1/sessions/:id/approval_rules/:rule_id",
+            axum::routing::delete(chef_de_vibe::api::handlers::delete_approval_rule),
+        )
+        .route(
+            "/api/v1/sessions/:id/content",
+            axum::routing::get(chef_de_vibe::api::handlers::get_session_content_since),
+        )
+        .route(
+            "/api/v1/sessions/:id/metadata",
+            axum::routing::patch(chef_de_vibe::api::handlers::update_session_metadata),
+        )
+        .route(
+            "/api/v1/sessions/:id/wirelog",
+            axum::routing::get(chef_de_vibe::api::handlers::get_session_wirelog),
+        )
+        .route(
+            "/api/v1/approvals/history",
+            axum::routing::get(chef_de_vibe::api::handlers::get_approval_history),
+        )
+        .route(
+            "/api/v1/admin/status",
+            axum::routing::get(chef_de_vibe::api::admin::get_admin_status),
+        )
+        .route(
+            "/api/v1/admin/draining",
+            axum::routing::put(chef_de_vibe::api::admin::set_draining),
+        )
+        .route(
+            "/api/v1/admin/sessions/:id/kill",
+            axum::routing::post(chef_de_vibe::api::admin::kill_session),
+        )
+        .route(
+            "/api/v1/sessions/:id/claude_ws",
+            axum::routing::get(chef_de_vibe::api::websocket::websocket_handler),
+        )
+        .route(
+            "/api/v1/events_ws",
+            axum::routing::get(chef_de_vibe::api::websocket::events_websocket_handler),
+        )
+        .route(
+            "/api/v1/sessions/:id/claude_approvals_ws",
+            axum::routing::get(chef_de_vibe::api::websocket::approval_websocket_handler),
+        )
+}
+
+struct TestServer {
+    base_url: String,
+    client: Client,
+    mock: MockClaude,
+    server_handle: tokio::task::JoinHandle<()>,
+    session_manager: Arc<SessionManager>,
+}
+
+impl TestServer {
+    async fn new() -> Self {
+        Self::start(None).await
+    }
+
+    /// Server requiring `token`, with a client that sends it.
+    async fn with_token(token: &str) -> Self {
+        Self::start(Some(token)).await
+    }
+
+    async fn start(token: Option<&str>) -> Self {
+        let mock = MockClaude::new();
+        mock.setup_env_vars();
+        let mut config = Config::from_env().expect("Failed to load config");
+        if let Some(token) = token {
+            config.api_keys = ApiKeys::new(vec![ApiKey {
+                token: ApiToken::new(token).unwrap(),
+                user: None,
+                admin: true,
+            }]);
+        }
+        let session_manager = Arc::new(SessionManager::new(config.clone()));
+        let api_keys = config.api_keys.clone();
+        let state = AppState {
+            session_manager: session_manager.clone(),
+            config: Arc::new(config),
+        };
+
+        let app = if api_keys.is_empty() {
+            routes()
+        } else {
+            routes().layer(axum::middleware::from_fn_with_state(
+                api_keys,
+                chef_de_vibe::api::auth::require_token,
+            ))
+        }
+        .with_state(state);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server_handle = tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let base_url = format!("http://127.0.0.1:{port}");
+        let mut client = Client::new(&base_url).unwrap();
+        if let Some(token) = token {
+            client = client.with_token(token).unwrap();
+        }
+
+        Self {
+            base_url,
+            client,
+            mock,
+            server_handle,
+            session_manager,
+        }
+    }
+
+    async fn create_session(&self, name: &str) -> CreateSessionResponse {
+        let working_dir = self.mock.temp_dir.path().join(name);
+        fs::create_dir_all(&working_dir).unwrap();
+        let session_id = format!("{name}-{}", std::process::id());
+
+        // The mock writes the session file Claude would create
+        let session_file = self.mock.projects_dir().join(format!("{session_id}.jsonl"));
+        let content = serde_json::json!({
+            "sessionId": session_id,
+            "cwd": working_dir,
+            "type": "start",
+        });
+        let bootstrap = serde_json::json!({
+            "control": "write_file",
+            "path": session_file,
+            "content": content.to_string(),
+        });
+
+        self.client
+            .create_session(&CreateSessionRequest {
+                session_id,
+                working_dir,
+                resume: false,
+                bootstrap_messages: vec![bootstrap.to_string()],
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server_handle.abort();
+        let session_manager = self.session_manager.clone();
+        std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(session_manager.shutdown());
+        });
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rest_calls() {
+    let server = TestServer::new().await;
+    let created = server.create_session("client-rest").await;
+
+    let sessions = server
+        .client
+        .list_sessions(&ListSessionsQuery::default())
+        .await
+        .unwrap();
+    assert!(sessions
+        .sessions
+        .iter()
+        .any(|session| session.session_id == created.session_id));
+
+    let session = server
+        .client
+        .get_session(&created.session_id, &GetSessionQuery::default())
+        .await
+        .unwrap();
+    assert_eq!(session.session_id, created.session_id);
+
+    let content = server
+        .client
+        .session_content(&created.session_id, &SessionContentQuery::default())
+        .await
+        .unwrap();
+    assert_eq!(content.session_id, created.session_id);
+    assert!(!content.reset);
+    let rest = server
+        .client
+        .session_content(
+            &created.session_id,
+            &SessionContentQuery {
+                after_line: Some(content.next_line),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert!(rest.content.is_empty());
+    assert_eq!(rest.next_line, content.next_line);
+
+    // Nothing was remembered yet, and unknown rules are simply not there
+    let rules = server
+        .client
+        .approval_rules(&created.session_id)
+        .await
+        .unwrap();
+    assert!(rules.rules.is_empty());
+    let rules = server
+        .client
+        .delete_approval_rule(&created.session_id, "no-such-rule")
+        .await
+        .unwrap();
+    assert_eq!(rules.session_id, created.session_id);
+    assert!(rules.rules.is_empty());
+
+    // Server errors keep their status and code
+    let error = server
+        .client
+        .get_session("no-such-session", &GetSessionQuery::default())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&error, chef_de_vibe_client::Error::Api { status, .. } if *status == StatusCode::NOT_FOUND),
+        "Unexpected error: {error:?}"
+    );
+    assert_eq!(error.code(), Some("SESSION_NOT_FOUND"));
+
+    // Wire logging is off unless WIRE_LOG_DIR is set
+    let error = server
+        .client
+        .session_wirelog(&created.session_id)
+        .await
+        .unwrap_err();
+    assert_eq!(error.code(), Some("INVALID_REQUEST"));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_session_tags() {
+    let server = TestServer::new().await;
+    let tagged = server.create_session("client-tagged").await;
+    server.create_session("client-untagged").await;
+
+    let updated = server
+        .client
+        .update_session_metadata(
+            &tagged.session_id,
+            &SessionMetadataPatch {
+                add_tags: vec!["release".to_string()],
+                metadata: [("ticket".to_string(), Some("CDV-1".to_string()))].into(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(updated.tags, ["release"]);
+    assert_eq!(updated.metadata["ticket"], "CDV-1");
+
+    let sessions = server
+        .client
+        .list_sessions(&ListSessionsQuery {
+            tag: Some("release".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let ids: Vec<&str> = sessions
+        .sessions
+        .iter()
+        .map(|session| session.session_id.as_str())
+        .collect();
+    assert_eq!(ids, [tagged.session_id.as_str()]);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_admin_calls() {
+    let server = TestServer::new().await;
+    let created = server.create_session("client-admin").await;
+
+    assert!(server.client.set_draining(true).await.unwrap().draining);
+    let status = server.client.admin_status().await.unwrap();
+    assert!(status.draining);
+    assert!(status
+        .sessions
+        .iter()
+        .any(|session| session.session_id == created.session_id));
+
+    let killed = server
+        .client
+        .kill_session(&created.session_id)
+        .await
+        .unwrap();
+    assert!(killed.process_terminated);
+    assert!(!server.client.set_draining(false).await.unwrap().draining);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_websockets_and_approval_responder() {
+    let server = TestServer::new().await;
+    let created = server.create_session("client-ws").await;
+
+    let approvals = server
+        .client
+        .approval_socket(&created.session_id)
+        .await
+        .unwrap();
+    let responder = tokio::spawn(approvals.respond_with(|frame| async move {
+        (frame.request["tool_name"] == "Read").then_some(Decision::Allow)
+    }));
+
+    let mut socket = server
+        .client
+        .session_socket(
+            &created.session_id,
+            &WebSocketParams {
+                channels: WebSocketChannels::Claude,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    // The mock echoes what it gets, so this comes back as Claude asking for approval
+    socket
+        .send(&serde_json::json!({
+            "type": "control_request",
+            "request_id": "client-read",
+            "request": {"subtype": "can_use_tool", "tool_name": "Read", "input": {"file_path": "a.txt"}},
+        }))
+        .await
+        .unwrap();
+
+    // The responder's answer reaches Claude, which echoes it too
+    let response = timeout(Duration::from_secs(5), async {
+        loop {
+            match socket.next().await.unwrap().unwrap() {
+                SessionEvent::Claude(message) if message["type"] == "control_response" => {
+                    return message;
+                }
+                _ => {}
+            }
+        }
+    })
+    .await
+    .expect("Expected the approval response to reach Claude");
+    assert_eq!(
+        response["response"]["response"]["behavior"], "allow",
+        "Unexpected response: {response}"
+    );
+    assert_eq!(
+        response["response"]["response"]["updatedInput"]["file_path"],
+        "a.txt"
+    );
+
+    let history = server
+        .client
+        .approval_history(&ApprovalHistoryQuery {
+            tool: Some("Read".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(!history.entries.is_empty());
+
+    socket.close().await.unwrap();
+    responder.abort();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_events_socket() {
+    let server = TestServer::new().await;
+    let mut events = server.client.events_socket().await.unwrap();
+    let created = server.create_session("client-events").await;
+
+    let event = timeout(Duration::from_secs(5), async {
+        loop {
+            if let ServerUpdate::Event(event) = events.next().await.unwrap().unwrap() {
+                if event.session_id == created.session_id {
+                    return event;
+                }
+            }
+        }
+    })
+    .await
+    .expect("Expected an event of the new session");
+    assert!(
+        matches!(event.kind, ServerEventKind::SessionCreated { .. }),
+        "Unexpected event: {event:?}"
+    );
+    events.close().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_token_authentication() {
+    let server = TestServer::with_token("client-token").await;
+    let created = server.create_session("client-token").await;
+
+    let anonymous = Client::new(&server.base_url).unwrap();
+    let refused = anonymous
+        .list_sessions(&ListSessionsQuery::default())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&refused, chef_de_vibe_client::Error::Api { status, code, .. } if *status == StatusCode::UNAUTHORIZED && code.as_deref() == Some("UNAUTHORIZED")),
+        "{refused:?}"
+    );
+    assert!(anonymous
+        .session_socket(&created.session_id, &WebSocketParams::default())
+        .await
+        .is_err());
+
+    // The token goes along with requests and WebSocket upgrades alike
+    server
+        .client
+        .list_sessions(&ListSessionsQuery::default())
+        .await
+        .unwrap();
+    server
+        .client
+        .session_socket(&created.session_id, &WebSocketParams::default())
+        .await
+        .unwrap();
+    server
+        .client
+        .approval_socket(&created.session_id)
+        .await
+        .unwrap();
+}
@@ -0,0 +1,247 @@
+use crate::error::{OrchestratorError, OrchestratorResult};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+
+const ANCHORS_FILE: &str = "anchors.json";
+
+/// Longest anchor name accepted, in characters.
+pub const MAX_ANCHOR_NAME_LEN: usize = 200;
+
+/// Most anchors a single session may have.
+pub const MAX_ANCHORS_PER_SESSION: usize = 500;
+
+/// A named position in a session's conversation, e.g. "design discussion".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Anchor {
+    pub name: String,
+    /// `uuid` of the transcript entry the anchor points at
+    pub message_uuid: String,
+    pub created_by: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Position of that entry in the session's content, for paging to it; filled in
+    /// when anchors are returned with the content and unset if the entry is gone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry_index: Option<usize>,
+}
+
+/// Anchors of every session, so long conversations can offer a table of contents.
+#[derive(Debug, Default)]
+pub struct AnchorStore {
+    /// Session ID -> its anchors, in creation order
+    anchors: DashMap<String, Vec<Anchor>>,
+    /// Where anchors are persisted, if a session state directory is configured
+    path: Option<PathBuf>,
+}
+
+impl AnchorStore {
+    /// Loads the anchors persisted in `state_dir`, or starts empty.
+    #[must_use]
+    pub fn load(state_dir: Option<&Path>) -> Self {
+        let path = state_dir.map(|dir| dir.join(ANCHORS_FILE));
+        let mut anchors = DashMap::new();
+
+        if let Some(path) = &path {
+            match std::fs::read(path) {
+                Ok(bytes) => match serde_json::from_slice::<HashMap<String, Vec<Anchor>>>(&bytes) {
+                    Ok(map) => anchors.extend(map),
+                    Err(e) => warn!(
+                        path = %path.display(),
+                        error = %e,
+                        "Ignoring unreadable anchors file"
+                    ),
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!(
+                    path = %path.display(),
+                    error = %e,
+                    "Failed to read anchors file"
+                ),
+            }
+        }
+
+        Self { anchors, path }
+    }
+
+    /// The anchors of a session, in creation order.
+    #[must_use]
+    pub fn list(&self, session_id: &str) -> Vec<Anchor> {
+        self.anchors
+            .get(session_id)
+            .map(|anchors| anchors.clone())
+            .unwrap_or_default()
+    }
+
+    /// Adds an anchor to a session, replacing any anchor of the same name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is empty or too long, or the session already has
+    /// [`MAX_ANCHORS_PER_SESSION`] anchors.
+    pub fn set(&self, session_id: &str, mut anchor: Anchor) -> OrchestratorResult<()> {
+        anchor.name = anchor.name.trim().to_string();
+        if anchor.name.is_empty() {
+            return Err(OrchestratorError::InvalidRequest(
+                "Anchor name must not be empty".to_string(),
+            ));
+        }
+        if anchor.name.chars().count() > MAX_ANCHOR_NAME_LEN {
+            return Err(OrchestratorError::InvalidRequest(format!(
+                "Anchor names are limited to {MAX_ANCHOR_NAME_LEN} characters"
+            )));
+        }
+        // Positions depend on the content the anchors are returned with
+        anchor.entry_index = None;
+
+        {
+            let mut anchors = self.anchors.entry(session_id.to_string()).or_default();
+            match anchors.iter().position(|a| a.name == anchor.name) {
+                Some(existing) => anchors[existing] = anchor,
+                None if anchors.len() >= MAX_ANCHORS_PER_SESSION => {
+                    return Err(OrchestratorError::InvalidRequest(format!(
+                        "Sessions are limited to {MAX_ANCHORS_PER_SESSION} anchors"
+                    )));
+                }
+                None => anchors.push(anchor),
+            }
+        }
+        debug!(session_id = %session_id, "Stored anchor");
+        self.persist();
+        Ok(())
+    }
+
+    /// Removes the anchor called `name`; returns whether there was one.
+    #[must_use]
+    pub fn remove(&self, session_id: &str, name: &str) -> bool {
+        let mut removed = false;
+        self.anchors.remove_if_mut(session_id, |_, anchors| {
+            let before = anchors.len();
+            anchors.retain(|anchor| anchor.name != name);
+            removed = anchors.len() != before;
+            anchors.is_empty()
+        });
+        if removed {
+            self.persist();
+        }
+        removed
+    }
+
+    /// Gives a session resumed under a new ID the anchors of the one it continues,
+    /// whose transcript it carries over. The original keeps its anchors.
+    pub fn copy_session(&self, old_session_id: &str, new_session_id: &str) {
+        let anchors = self.list(old_session_id);
+        if anchors.is_empty() {
+            return;
+        }
+        self.anchors.insert(new_session_id.to_string(), anchors);
+        self.persist();
+    }
+
+    fn persist(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let snapshot: HashMap<String, Vec<Anchor>> = self
+            .anchors
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        let tmp_path = path.with_extension("json.tmp");
+        let result = serde_json::to_vec(&snapshot)
+            .map_err(std::io::Error::from)
+            .and_then(|bytes| std::fs::write(&tmp_path, bytes))
+            .and_then(|()| std::fs::rename(&tmp_path, path));
+        if let Err(e) = result {
+            warn!(path = %path.display(), error = %e, "Failed to persist anchors");
+        }
+    }
+}
+
+/// Index of the transcript entry with `uuid` in `content`.
+#[must_use]
+pub fn entry_index(content: &[serde_json::Value], uuid: &str) -> Option<usize> {
+    content
+        .iter()
+        .position(|entry| entry.get("uuid").and_then(|u| u.as_str()) == Some(uuid))
+}
+
+/// Fills in where each anchor points in `content` and orders them as the conversation
+/// goes; anchors whose entry is not in `content` come last.
+#[must_use]
+pub fn locate(mut anchors: Vec<Anchor>, content: &[serde_json::Value]) -> Vec<Anchor> {
+    for anchor in &mut anchors {
+        anchor.entry_index = entry_index(content, &anchor.message_uuid);
+    }
+    anchors.sort_by_key(|anchor| anchor.entry_index.unwrap_or(usize::MAX));
+    anchors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn anchor(name: &str, message_uuid: &str) -> Anchor {
+        Anchor {
+            name: name.to_string(),
+            message_uuid: message_uuid.to_string(),
+            created_by: "alice".to_string(),
+            created_at: chrono::Utc::now(),
+            entry_index: None,
+        }
+    }
+
+    #[test]
+    fn test_anchors_round_trip() {
+        let state_dir = TempDir::new().unwrap();
+        let store = AnchorStore::load(Some(state_dir.path()));
+        store.set("s1", anchor("final patch", "u3")).unwrap();
+        store.set("s1", anchor(" design ", "u1")).unwrap();
+        store.set("s1", anchor("final patch", "u2")).unwrap();
+        assert!(store.set("s1", anchor("  ", "u1")).is_err());
+        assert!(store
+            .set("s1", anchor(&"x".repeat(MAX_ANCHOR_NAME_LEN + 1), "u1"))
+            .is_err());
+
+        let reloaded = AnchorStore::load(Some(state_dir.path()));
+        let anchors = reloaded.list("s1");
+        assert_eq!(anchors.len(), 2);
+        assert_eq!(anchors[0].name, "final patch");
+        assert_eq!(anchors[0].message_uuid, "u2");
+        assert_eq!(anchors[1].name, "design");
+
+        reloaded.copy_session("s1", "s2");
+        assert!(reloaded.remove("s1", "design"));
+        assert!(!reloaded.remove("s1", "design"));
+        assert_eq!(reloaded.list("s1").len(), 1);
+        assert_eq!(reloaded.list("s2").len(), 2);
+    }
+
+    #[test]
+    fn test_locate_orders_by_conversation() {
+        let content = vec![
+            serde_json::json!({"type": "summary"}),
+            serde_json::json!({"type": "user", "uuid": "u1"}),
+            serde_json::json!({"type": "assistant", "uuid": "u2"}),
+        ];
+        let anchors = locate(
+            vec![
+                anchor("gone", "u9"),
+                anchor("late", "u2"),
+                anchor("early", "u1"),
+            ],
+            &content,
+        );
+        let located: Vec<_> = anchors
+            .iter()
+            .map(|a| (a.name.as_str(), a.entry_index))
+            .collect();
+        assert_eq!(
+            located,
+            vec![("early", Some(1)), ("late", Some(2)), ("gone", None)]
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use crate::error::{ErrorResponse, PROBLEM_JSON};
+use crate::trace_export;
+use axum::{
+    body::{self, Body, HttpBody},
+    extract::{MatchedPath, Request},
+    http::{header, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::TryStreamExt;
+use std::sync::atomic::{AtomicU16, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{info, info_span, Instrument, Span};
+use uuid::Uuid;
+
+/// Header naming a request, taken over from the caller or assigned by the server.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest request ID taken over from a caller; longer ones are replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Largest error body the request ID is added to; problem documents are far smaller.
+const MAX_PROBLEM_BYTES: usize = 64 * 1024;
+
+/// Access log line of a request, written once its response body was sent or, for a
+/// WebSocket, once the connection closed. Handlers that keep a connection open hold on
+/// to a clone and count what they send with [`AccessLog::add_bytes`].
+#[derive(Debug, Clone)]
+pub struct AccessLog(Arc<Entry>);
+
+#[derive(Debug)]
+struct Entry {
+    request_id: String,
+    method: Method,
+    path: String,
+    started: Instant,
+    status: AtomicU16,
+    bytes: AtomicU64,
+    span: Span,
+}
+
+impl AccessLog {
+    /// Counts `bytes` more sent to the client.
+    pub fn add_bytes(&self, bytes: usize) {
+        self.0
+            .bytes
+            .fetch_add(u64::try_from(bytes).unwrap_or(u64::MAX), Ordering::Relaxed);
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        let _entered = self.span.enter();
+        info!(
+            request_id = %self.request_id,
+            method = %self.method,
+            path = %self.path,
+            status = self.status.load(Ordering::Relaxed),
+            bytes = self.bytes.load(Ordering::Relaxed),
+            duration_ms = u64::try_from(self.started.elapsed().as_millis()).unwrap_or(u64::MAX),
+            "Request completed"
+        );
+    }
+}
+
+/// The caller's request ID, if it is one that can be logged and echoed as is.
+fn caller_request_id(request: &Request) -> Option<&str> {
+    request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id.bytes().all(|byte| byte.is_ascii_graphic())
+        })
+}
+
+/// Middleware giving every request an ID, the caller's `X-Request-Id` or a new one,
+/// which is echoed in the response header, added to problem documents and carried by
+/// the `http_request` span everything the request logs is under. Writes one access log
+/// line per request.
+pub async fn log_requests(mut request: Request, next: Next) -> Response {
+    let request_id =
+        caller_request_id(&request).map_or_else(|| Uuid::new_v4().to_string(), str::to_string);
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| request.uri().path(), MatchedPath::as_str);
+    let span = info_span!(
+        "http_request",
+        method = %request.method(),
+        route = %route,
+        request_id = %request_id,
+        status = tracing::field::Empty,
+    );
+    trace_export::continue_trace(&span, request.headers());
+    // The query is left out: it may carry an `access_token`
+    let log = AccessLog(Arc::new(Entry {
+        request_id: request_id.clone(),
+        method: request.method().clone(),
+        path: request.uri().path().to_string(),
+        started: Instant::now(),
+        status: AtomicU16::new(0),
+        bytes: AtomicU64::new(0),
+        span: span.clone(),
+    }));
+    request.extensions_mut().insert(log.clone());
+
+    let response = next.run(request).instrument(span.clone()).await;
+    let status = response.status();
+    span.record("status", status.as_u16());
+    log.0.status.store(status.as_u16(), Ordering::Relaxed);
+
+    let mut response = if is_problem(&response) {
+        with_request_id(response, &request_id).await
+    } else {
+        response
+    };
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    let (parts, body) = response.into_parts();
+    if let Some(bytes) = body.size_hint().exact() {
+        log.add_bytes(usize::try_from(bytes).unwrap_or(usize::MAX));
+        return Response::from_parts(parts, body);
+    }
+    // Streamed bodies are logged once the last chunk went out
+    let body = body.into_data_stream().inspect_ok(move |chunk| {
+        log.add_bytes(chunk.len());
+    });
+    Response::from_parts(parts, Body::from_stream(body))
+}
+
+fn is_problem(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes() == PROBLEM_JSON.as_bytes())
+}
+
+/// Adds `request_id` to the problem document `response` carries, keeping its status and
+/// headers.
+async fn with_request_id(response: Response, request_id: &str) -> Response {
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = body::to_bytes(body, MAX_PROBLEM_BYTES).await else {
+        return (parts.status, "").into_response();
+    };
+    let body = match serde_json::from_slice::<ErrorResponse>(&bytes) {
+        Ok(mut problem) => {
+            problem.request_id = Some(request_id.to_string());
+            serde_json::to_vec(&problem).map_or(bytes, Into::into)
+        }
+        Err(_) => bytes,
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::OrchestratorError;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    fn app() -> axum::Router {
+        axum::Router::new()
+            .route("/ok", get(|| async { "hello" }))
+            .route(
+                "/missing",
+                get(|| async { OrchestratorError::SessionNotFound("s1".to_string()) }),
+            )
+            .layer(axum::middleware::from_fn(log_requests))
+    }
+
+    async fn send(uri: &str, request_id: Option<&str>) -> Response {
+        let mut request = Request::builder().uri(uri);
+        if let Some(request_id) = request_id {
+            request = request.header(REQUEST_ID_HEADER, request_id);
+        }
+        app()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_request_ids() {
+        let response = send("/ok", Some("req-42")).await;
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "req-42");
+        let body = body::to_bytes(response.into_body(), 1024).await.unwrap();
+        assert_eq!(&body[..], b"hello");
+
+        // IDs that cannot be echoed safely are replaced
+        for unusable in ["", "has space", &"x".repeat(MAX_REQUEST_ID_LEN + 1)] {
+            let response = send("/ok", Some(unusable)).await;
+            let assigned = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+            assert!(Uuid::parse_str(assigned).is_ok());
+        }
+
+        let response = send("/missing", Some("req-43")).await;
+        assert_eq!(response.status(), 404);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+        let body = body::to_bytes(response.into_body(), 1024).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["code"], "SESSION_NOT_FOUND");
+        assert_eq!(problem["request_id"], "req-43");
+    }
+}
@@ -0,0 +1,107 @@
+use crate::api::handlers::AppState;
+use crate::discovery::SessionDiscovery;
+use crate::error::OrchestratorResult;
+use crate::models::{AdminStatusResponse, DrainingState, RescanResponse, TerminateSessionResponse};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use tracing::{info, instrument, warn};
+
+/// Uptime, draining mode, and the processes, clients, backlogs and pending approvals of
+/// every session the orchestrator holds.
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/status",
+    tag = "admin",
+    responses(
+        (status = 200, body = AdminStatusResponse),
+        (status = 403, description = "`FORBIDDEN`: the caller is not an admin", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn get_admin_status(State(state): State<AppState>) -> Json<AdminStatusResponse> {
+    Json(state.session_manager.admin_status().await)
+}
+
+/// Kills a session's Claude process right away, without the grace period of
+/// `DELETE /api/v1/sessions/:id`.
+///
+/// # Errors
+///
+/// Returns an error if the session does not exist.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/sessions/{session_id}/kill",
+    tag = "admin",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    responses(
+        (status = 200, body = TerminateSessionResponse),
+        (status = 403, description = "`FORBIDDEN`: the caller is not an admin", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state), fields(session_id = %session_id))]
+pub async fn kill_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> OrchestratorResult<Json<TerminateSessionResponse>> {
+    warn!(session_id = %session_id, "Force-killing session through the admin API");
+    Ok(Json(state.session_manager.kill_session(&session_id).await?))
+}
+
+/// Compares every journal on disk with the session index and reads the changed ones again.
+///
+/// # Errors
+///
+/// Returns an error if the session index cannot be read or written.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/rescan",
+    tag = "admin",
+    responses(
+        (status = 200, body = RescanResponse),
+        (status = 403, description = "`FORBIDDEN`: the caller is not an admin", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn rescan_sessions(
+    State(state): State<AppState>,
+) -> OrchestratorResult<Json<RescanResponse>> {
+    let refreshed = SessionDiscovery::new(&state.config, &state.session_manager).rescan()?;
+    info!(
+        rescanned = refreshed.rescanned,
+        removed = refreshed.removed,
+        "Rescanned session journals"
+    );
+    Ok(Json(RescanResponse {
+        rescanned: refreshed.rescanned,
+        removed: refreshed.removed,
+    }))
+}
+
+/// Starts or stops refusing new sessions and WebSocket clients.
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/draining",
+    tag = "admin",
+    request_body = DrainingState,
+    responses(
+        (status = 200, body = DrainingState),
+        (status = 403, description = "`FORBIDDEN`: the caller is not an admin", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn set_draining(
+    State(state): State<AppState>,
+    Json(request): Json<DrainingState>,
+) -> Json<DrainingState> {
+    if request.draining {
+        state.session_manager.start_draining();
+    } else {
+        state.session_manager.stop_draining();
+    }
+    Json(DrainingState {
+        draining: state.session_manager.is_draining(),
+    })
+}
@@ -0,0 +1,278 @@
+use crate::api::handlers::AppState;
+use crate::config::{ApiKey, ApiKeys, Config};
+use crate::error::{OrchestratorError, OrchestratorResult};
+use crate::preferences::DEFAULT_IDENTITY;
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, MatchedPath, Path, Query, Request, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    RequestExt,
+};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use tracing::debug;
+
+/// Subprotocol a WebSocket client offers next to `bearer.<token>`; the server selects
+/// it, since browsers drop a connection whose offered subprotocols were all ignored.
+pub const WEBSOCKET_PROTOCOL: &str = "chef-de-vibe";
+
+/// Prefix of the subprotocol carrying the token of a WebSocket upgrade.
+const PROTOCOL_TOKEN_PREFIX: &str = "bearer.";
+
+/// Query parameter carrying the token of a WebSocket upgrade.
+const QUERY_TOKEN: &str = "access_token";
+
+/// Where a request presented the token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Credential {
+    Header,
+    Query,
+    Protocol,
+}
+
+/// Who presented the API key of a request.
+#[derive(Debug, Clone)]
+struct Principal {
+    user: Option<String>,
+    admin: bool,
+}
+
+/// Middleware refusing requests without one of the API keys with `401 UNAUTHORIZED`.
+///
+/// Requests present it as `Authorization: Bearer <token>`. Browsers cannot set headers
+/// on WebSocket upgrades, so those may instead carry it in the `access_token` query
+/// parameter or as the subprotocol `bearer.<token>`.
+pub async fn require_token(
+    State(keys): State<ApiKeys>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some((credential, key)) = authenticate(&keys, &request) else {
+        debug!(method = %request.method(), path = %request.uri().path(), "Refused request without valid API token");
+        let mut response =
+            OrchestratorError::Unauthorized("Missing or invalid API token".to_string())
+                .into_response();
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        return response;
+    };
+
+    let principal = Principal {
+        user: key.user.clone(),
+        admin: key.admin,
+    };
+    request.extensions_mut().insert(principal);
+
+    // Keep the token out of handlers and anything that logs the URI
+    if credential == Credential::Query {
+        *request.uri_mut() = without_query_token(request.uri());
+    }
+    let offered_protocol = offers_protocol(request.headers(), WEBSOCKET_PROTOCOL);
+
+    let mut response = next.run(request).await;
+    if credential == Credential::Protocol
+        && offered_protocol
+        && response.status() == StatusCode::SWITCHING_PROTOCOLS
+        && !response
+            .headers()
+            .contains_key(header::SEC_WEBSOCKET_PROTOCOL)
+    {
+        response.headers_mut().insert(
+            header::SEC_WEBSOCKET_PROTOCOL,
+            HeaderValue::from_static(WEBSOCKET_PROTOCOL),
+        );
+    }
+    response
+}
+
+fn authenticate<'k>(keys: &'k ApiKeys, request: &Request) -> Option<(Credential, &'k ApiKey)> {
+    let headers = request.headers();
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+        .and_then(|(_, presented)| keys.find(presented.trim()));
+    if let Some(key) = bearer {
+        return Some((Credential::Header, key));
+    }
+    if !is_websocket_upgrade(headers) {
+        return None;
+    }
+
+    let protocol_key = protocols(headers)
+        .find_map(|protocol| protocol.strip_prefix(PROTOCOL_TOKEN_PREFIX))
+        .and_then(|presented| keys.find(presented));
+    if let Some(key) = protocol_key {
+        return Some((Credential::Protocol, key));
+    }
+    Query::<HashMap<String, String>>::try_from_uri(request.uri())
+        .ok()
+        .and_then(|Query(mut params)| params.remove(QUERY_TOKEN))
+        .and_then(|presented| keys.find(&presented))
+        .map(|key| (Credential::Query, key))
+}
+
+fn is_websocket_upgrade(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"))
+}
+
+fn protocols(headers: &HeaderMap) -> impl Iterator<Item = &str> {
+    headers
+        .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+}
+
+fn offers_protocol(headers: &HeaderMap, protocol: &str) -> bool {
+    protocols(headers).any(|offered| offered == protocol)
+}
+
+/// Who is making a request: the user of their API key, or else the `IDENTITY_HEADER`
+/// identity.
+#[derive(Debug, Clone)]
+pub struct Caller {
+    /// The reason there is none if `IDENTITY_HEADER` is configured but missing
+    identity: Result<String, String>,
+    /// May access every session; everyone may unless API keys belong to users
+    pub admin: bool,
+}
+
+impl Caller {
+    /// # Errors
+    ///
+    /// Returns an error if the identity header is configured but missing from the request.
+    pub fn identity(&self) -> OrchestratorResult<String> {
+        self.identity
+            .clone()
+            .map_err(OrchestratorError::Unauthorized)
+    }
+
+    /// Whether the caller may use a session owned by `owner`. Sessions without an owner,
+    /// e.g. ones started outside the server, are only accessible to admins.
+    #[must_use]
+    pub fn can_access(&self, owner: Option<&str>) -> bool {
+        self.admin || owner.is_some_and(|owner| self.identity.as_deref() == Ok(owner))
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for Caller {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let principal = parts.extensions.get::<Principal>();
+        let identity = match principal.and_then(|principal| principal.user.clone()) {
+            Some(user) => Ok(user),
+            None => header_identity(&state.config, &parts.headers),
+        };
+        Ok(Self {
+            identity,
+            admin: !state.config.api_keys.has_users()
+                || principal.is_some_and(|principal| principal.admin),
+        })
+    }
+}
+
+/// Who is asking, as told by the authenticating proxy in `IDENTITY_HEADER`. Without
+/// that header configured everyone shares one identity.
+fn header_identity(config: &Config, headers: &HeaderMap) -> Result<String, String> {
+    let Some(header) = &config.identity_header else {
+        return Ok(DEFAULT_IDENTITY.to_string());
+    };
+    headers
+        .get(header.as_str())
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|identity| !identity.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| format!("Missing {header} header"))
+}
+
+/// Middleware hiding other users' sessions from callers that are not admins, as if they
+/// did not exist, and refusing them the admin endpoints. Must be added with
+/// `route_layer` so the route template is known.
+pub async fn require_session_access(
+    State(state): State<AppState>,
+    caller: Caller,
+    route: MatchedPath,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if caller.admin {
+        return next.run(request).await;
+    }
+    let route = route.as_str();
+    if route.starts_with("/api/v1/admin/") {
+        return OrchestratorError::Forbidden("Admin key required".to_string()).into_response();
+    }
+    if route.starts_with("/api/v1/sessions/:id") {
+        let session_id = match request
+            .extract_parts::<Path<HashMap<String, String>>>()
+            .await
+        {
+            Ok(Path(mut params)) => params.remove("id").unwrap_or_default(),
+            Err(rejection) => return rejection.into_response(),
+        };
+        let owner = state.session_manager.session_owner(&session_id);
+        if !caller.can_access(owner.as_deref()) {
+            debug!(session_id = %session_id, "Refused access to another user's session");
+            return OrchestratorError::SessionNotFound(session_id).into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// `uri` without the `access_token` query parameter.
+fn without_query_token(uri: &Uri) -> Uri {
+    let Some(query) = uri.query() else {
+        return uri.clone();
+    };
+    let query = query
+        .split('&')
+        .filter(|pair| pair.split('=').next() != Some(QUERY_TOKEN))
+        .collect::<Vec<_>>()
+        .join("&");
+    let path_and_query = if query.is_empty() {
+        uri.path().to_string()
+    } else {
+        format!("{}?{query}", uri.path())
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(
+        path_and_query
+            .parse()
+            .expect("a subset of a valid query is valid"),
+    );
+    Uri::from_parts(parts).expect("only the query changed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_without_query_token() {
+        let strip = |uri: &str| without_query_token(&uri.parse().unwrap()).to_string();
+        assert_eq!(
+            strip("/api/v1/sessions/s1/claude_ws?ack=true&access_token=secret&stream=true"),
+            "/api/v1/sessions/s1/claude_ws?ack=true&stream=true"
+        );
+        assert_eq!(
+            strip("/api/v1/sessions/s1/claude_ws?access_token=secret"),
+            "/api/v1/sessions/s1/claude_ws"
+        );
+        assert_eq!(strip("/api/v1/sessions"), "/api/v1/sessions");
+    }
+}
@@ -0,0 +1,88 @@
+use crate::config::BodyLimits;
+use crate::error::OrchestratorError;
+use axum::{
+    async_trait,
+    extract::{DefaultBodyLimit, FromRequest, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::MethodRouter,
+    Extension, Json,
+};
+use serde::de::DeserializeOwned;
+use std::convert::Infallible;
+
+/// Largest body a route accepts, and the setting that configures it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyLimit {
+    pub max_bytes: usize,
+    /// Environment variable named in the error, so callers know what to raise
+    pub setting: &'static str,
+}
+
+impl BodyLimit {
+    #[must_use]
+    pub fn create_session(limits: &BodyLimits) -> Self {
+        Self {
+            max_bytes: limits.create_session,
+            setting: "CREATE_SESSION_BODY_MAX_BYTES",
+        }
+    }
+
+    #[must_use]
+    pub fn message(limits: &BodyLimits) -> Self {
+        Self {
+            max_bytes: limits.message,
+            setting: "MESSAGE_BODY_MAX_BYTES",
+        }
+    }
+
+    #[must_use]
+    pub fn import(limits: &BodyLimits) -> Self {
+        Self {
+            max_bytes: limits.import,
+            setting: "IMPORT_BODY_MAX_BYTES",
+        }
+    }
+
+    /// Caps the body of `route` at this limit, replacing axum's default.
+    pub fn apply<S>(self, route: MethodRouter<S>) -> MethodRouter<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        route
+            .layer::<_, Infallible>(DefaultBodyLimit::max(self.max_bytes))
+            .layer(Extension(self))
+    }
+}
+
+/// A JSON body like [`Json`], except that a body over the route's [`BodyLimit`] is
+/// answered with a `PAYLOAD_TOO_LARGE` error saying which limit it ran into.
+#[derive(Debug)]
+pub struct LimitedJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for LimitedJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let limit = req.extensions().get::<BodyLimit>().copied();
+        match Json::<T>::from_request(req, state).await {
+            Ok(Json(value)) => Ok(Self(value)),
+            Err(rejection) if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                let message = match limit {
+                    Some(limit) => format!(
+                        "Request body is larger than {} bytes (set by {})",
+                        limit.max_bytes, limit.setting
+                    ),
+                    None => "Request body is too large".to_string(),
+                };
+                Err(OrchestratorError::PayloadTooLarge(message).into_response())
+            }
+            Err(rejection) => Err(rejection.into_response()),
+        }
+    }
+}
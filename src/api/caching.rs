@@ -0,0 +1,113 @@
+use axum::{
+    body::{self, Body, HttpBody},
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use tower_http::compression::CompressionLayer;
+use tracing::error;
+
+/// Compresses responses with gzip or brotli, whichever the client prefers. Bodies too
+/// small to gain anything, byte ranges and event streams are sent as they are.
+#[must_use]
+pub fn compression() -> CompressionLayer {
+    CompressionLayer::new().gzip(true).br(true)
+}
+
+/// Weak entity tag of a response body. Weak, because compression changes the bytes
+/// sent without changing what they mean.
+#[must_use]
+pub fn entity_tag(body: &[u8]) -> String {
+    digest_tag(ring::digest::digest(&ring::digest::SHA256, body).as_ref())
+}
+
+/// Weak entity tag from a SHA-256 digest of the content.
+#[must_use]
+pub fn digest_tag(digest: &[u8]) -> String {
+    let prefix = &digest[..digest.len().min(16)];
+    format!("W/\"{}\"", URL_SAFE_NO_PAD.encode(prefix))
+}
+
+/// Whether the `If-None-Match` header of a request names `etag`, i.e. the client's
+/// copy is current. Tags are compared weakly, as RFC 9110 asks for `If-None-Match`.
+#[must_use]
+pub fn is_fresh(request_headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+    request_headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || opaque(tag) == etag)
+}
+
+/// `304 Not Modified` for a client whose copy has tag `etag`.
+#[must_use]
+pub fn not_modified(etag: &HeaderValue) -> Response {
+    (StatusCode::NOT_MODIFIED, [(header::ETAG, etag.clone())]).into_response()
+}
+
+/// Middleware giving successful `GET` responses an entity tag, and answering requests
+/// whose `If-None-Match` names it with `304 Not Modified` instead of the body. Only
+/// bodies of a known size, which are in memory already like the JSON of a session, are
+/// tagged; streamed ones go out untouched.
+pub async fn conditional_get(request: Request, next: Next) -> Response {
+    let is_get = request.method() == Method::GET;
+    let request_headers = request.headers().clone();
+    let response = next.run(request).await;
+    if !is_get
+        || response.status() != StatusCode::OK
+        || response.body().size_hint().exact().is_none()
+    {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!(error = %e, "Failed to buffer response for its entity tag");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let etag = entity_tag(&bytes);
+    let Ok(etag_value) = HeaderValue::from_str(&etag) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    if is_fresh(&request_headers, &etag) {
+        return not_modified(&etag_value);
+    }
+    parts.headers.insert(header::ETAG, etag_value);
+    // Cached copies are checked with the server before every use
+    parts
+        .headers
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_fresh() {
+        let etag = entity_tag(b"content");
+        assert!(etag.starts_with("W/\""));
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(value).unwrap());
+            headers
+        };
+        assert!(!is_fresh(&HeaderMap::new(), &etag));
+        assert!(is_fresh(&headers(&etag), &etag));
+        // Weak comparison ignores the W/ prefix
+        assert!(is_fresh(&headers(etag.trim_start_matches("W/")), &etag));
+        assert!(is_fresh(&headers(&format!("\"other\", {etag}")), &etag));
+        assert!(is_fresh(&headers("*"), &etag));
+        assert!(!is_fresh(&headers(&entity_tag(b"changed")), &etag));
+    }
+}
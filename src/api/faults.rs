@@ -0,0 +1,57 @@
+use crate::api::handlers::AppState;
+use crate::error::{OrchestratorError, OrchestratorResult};
+use crate::fault_injection::{self, FaultsResponse, InjectFaultsRequest};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use tracing::{info, instrument};
+
+/// Injects faults into a session, and returns the ones still pending.
+///
+/// # Errors
+///
+/// Returns an error if the session does not exist, or `exit_process` is set and the
+/// session has no process that could be killed.
+#[instrument(skip(state, request), fields(session_id = %session_id))]
+pub async fn inject_faults(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    Json(request): Json<InjectFaultsRequest>,
+) -> OrchestratorResult<Json<FaultsResponse>> {
+    let session = state
+        .session_manager
+        .get_session(&session_id)
+        .ok_or_else(|| OrchestratorError::SessionNotFound(session_id.clone()))?;
+
+    session.faults.set(&request);
+    info!(session_id = %session_id, request = ?request, "Injected faults into session");
+
+    if request.exit_process {
+        let pid = session.get_process_id().await.ok_or_else(|| {
+            OrchestratorError::InvalidRequest("Session has no Claude process to kill".to_string())
+        })?;
+        fault_injection::kill_process(pid).map_err(|e| {
+            OrchestratorError::InternalError(format!("Failed to kill Claude process: {e}"))
+        })?;
+    }
+
+    Ok(Json(session.faults.status(session_id)))
+}
+
+/// Returns the faults a session still has pending.
+///
+/// # Errors
+///
+/// Returns an error if the session does not exist.
+#[instrument(skip(state), fields(session_id = %session_id))]
+pub async fn get_faults(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> OrchestratorResult<Json<FaultsResponse>> {
+    let session = state
+        .session_manager
+        .get_session(&session_id)
+        .ok_or_else(|| OrchestratorError::SessionNotFound(session_id.clone()))?;
+    Ok(Json(session.faults.status(session_id)))
+}
@@ -1,14 +1,51 @@
-use crate::discovery::SessionDiscovery;
-use crate::error::OrchestratorResult;
+use crate::anchors::{self, Anchor};
+use crate::api::auth::Caller;
+use crate::api::body_limit::LimitedJson;
+use crate::debug_capture::{DEFAULT_DEBUG_DURATION, MAX_DEBUG_DURATION};
+use crate::discovery::{self, SessionDiscovery};
+use crate::error::{OrchestratorError, OrchestratorResult};
+use crate::file_history;
+use crate::input_record;
 use crate::models::{
-    CreateSessionRequest, CreateSessionResponse, GetSessionResponse, ListSessionsResponse,
+    is_valid_session_id, AppendUploadQuery, ApprovalHistoryQuery, ApprovalHistoryResponse,
+    ApprovalRequest, ApprovalResponseResult, BulkApprovalResponse, ContentFormat, ContentPaging,
+    CreateAnchorRequest, CreateSessionRequest, CreateSessionResponse, CreateUploadRequest,
+    ExportSessionQuery, ForkSessionRequest, GetSessionQuery, GetSessionResponse,
+    HeldMessagesAction, ImportSessionsQuery, ImportSessionsResponse, ListSessionsQuery,
+    ListSessionsResponse, PinnedSessionsResponse, PreferencesResponse, PushSubscriptionsResponse,
+    ResolveHeldMessagesRequest, ResolveHeldMessagesResponse, SessionAnchorsResponse,
+    SessionApprovalRulesResponse, SessionApprovalsResponse, SessionClientsResponse,
+    SessionContentQuery, SessionContentResponse, SessionContextResponse, SessionFilesQuery,
+    SessionFilesResponse, SessionMetadataResponse, SessionOptions, SessionQueueResponse,
+    SessionScratchResponse, SessionWatchesResponse, SetSessionDebugRequest,
+    SetSessionDebugResponse, TelemetryPreviewResponse, TerminateSessionResponse,
+    UnsubscribePushQuery, UnwatchSessionQuery, UsageQuery, UsageResponse, WatchSessionRequest,
+    WebPushKeyResponse,
 };
+use crate::process_stats;
+use crate::scratch;
+use crate::session_context;
+use crate::session_fork;
+use crate::session_import;
 use crate::session_manager::SessionManager;
+use crate::session_metadata::SessionMetadataPatch;
+use crate::slo::SloReport;
+use crate::tool_catalog::{self, ToolCatalog};
+use crate::transcript_hook::{ExternalCommandHook, TranscriptHook};
+use crate::transcript_media;
+use crate::transcript_stream::{self, LineRange};
+use crate::uploads::UploadStatus;
+use crate::web_push::{PushSubscription, WebPush};
+use crate::wire_log;
 use axum::{
-    extract::{Path, State},
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
     Json,
 };
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::{debug, error, info, instrument, warn};
 
 #[derive(Clone)]
@@ -19,17 +56,46 @@ pub struct AppState {
 
 /// Lists all available sessions.
 ///
+/// Sessions that were resumed under a new ID are folded into their latest segment
+/// unless `expand_lineage` is set. Sessions the requesting user pinned are marked, and
+/// `sort` orders the list. `active`, `working_dir`, `since` and `until` narrow it down.
+///
 /// # Errors
 ///
-/// Returns an error if session discovery fails or if there's an I/O error accessing session files.
-#[instrument(skip(state), fields(sessions_count))]
+/// Returns an error if `since` is later than `until`, if session discovery fails or if
+/// there's an I/O error accessing session files.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions",
+    tag = "sessions",
+    params(ListSessionsQuery),
+    responses(
+        (status = 200, body = ListSessionsResponse),
+        (status = 400, description = "`INVALID_REQUEST`: `since` is later than `until`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, caller), fields(sessions_count, expand_lineage = query.expand_lineage, sort = ?query.sort))]
 pub async fn list_sessions(
     State(state): State<AppState>,
+    Query(query): Query<ListSessionsQuery>,
+    caller: Caller,
 ) -> OrchestratorResult<Json<ListSessionsResponse>> {
     info!("Listing all sessions");
 
+    let mut filter = query.filter();
+    if !caller.admin {
+        filter.owner = Some(caller.identity()?);
+    }
+    if let (Some(since), Some(until)) = (filter.since, filter.until) {
+        if since > until {
+            return Err(OrchestratorError::InvalidRequest(
+                "since must not be later than until".to_string(),
+            ));
+        }
+    }
+
     let discovery = SessionDiscovery::new(&state.config, &state.session_manager);
-    let sessions = match discovery.list_all_sessions().await {
+    let sessions = match discovery.list_all_sessions(&filter).await {
         Ok(sessions) => {
             info!(count = sessions.len(), "Successfully retrieved sessions");
             tracing::Span::current().record("sessions_count", sessions.len());
@@ -41,20 +107,107 @@ pub async fn list_sessions(
         }
     };
 
+    let mut sessions = if query.expand_lineage {
+        sessions
+    } else {
+        discovery.collapse_resumed_chains(sessions)
+    };
+
+    // Listing works without an identity too, there are just no pins then
+    let pinned_sessions = caller
+        .identity()
+        .map(|identity| {
+            state
+                .session_manager
+                .preferences()
+                .pinned_sessions(&identity)
+        })
+        .unwrap_or_default();
+    let pin_ranks = discovery.mark_pinned(&mut sessions, &pinned_sessions);
+    let usage = state.session_manager.usage();
+    for session in &mut sessions {
+        session.usage = usage.session_totals(&session.session_id);
+    }
+    if let Some(sort) = query.sort {
+        discovery::sort_sessions(&mut sessions, sort, &pin_ranks);
+    }
+
     debug!("Returning sessions response");
     Ok(Json(ListSessionsResponse { sessions }))
 }
 
+/// Imports conversations exported from another host as sessions of `working_dir`,
+/// which can then be listed and resumed like any other.
+///
+/// # Errors
+///
+/// Returns an error if the working directory is not allowed, the body is not an export
+/// in the requested format, or a session file cannot be written.
+#[utoipa::path(
+    post,
+    path = "/api/v1/sessions/import",
+    tag = "sessions",
+    params(ImportSessionsQuery),
+    request_body(content = Object, description = "The export, in the format named by `format`"),
+    responses(
+        (status = 200, body = ImportSessionsResponse),
+        (status = 400, description = "`INVALID_REQUEST`: not an export in `format`, or `WORKING_DIR_INVALID`", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 413, description = "`PAYLOAD_TOO_LARGE`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, caller, export), fields(format = ?query.format, working_dir = %query.working_dir.display()))]
+pub async fn import_sessions(
+    State(state): State<AppState>,
+    Query(query): Query<ImportSessionsQuery>,
+    caller: Caller,
+    LimitedJson(export): LimitedJson<serde_json::Value>,
+) -> OrchestratorResult<Json<ImportSessionsResponse>> {
+    state
+        .session_manager
+        .validate_working_dir(&query.working_dir)?;
+    let sessions = session_import::import_sessions(
+        &state.config.claude_projects_dir,
+        &query.working_dir,
+        query.format,
+        export,
+    )?;
+    // Imported sessions belong to whoever imported them, if known
+    if let Ok(owner) = caller.identity() {
+        for session in &sessions {
+            state
+                .session_manager
+                .record_session_owner(&session.session_id, &owner);
+        }
+    }
+    info!(imported = sessions.len(), "Imported sessions");
+    Ok(Json(ImportSessionsResponse { sessions }))
+}
+
 /// Creates a new session or resumes an existing one.
 ///
 /// # Errors
 ///
 /// Returns an error if the session ID is empty, if the session manager fails to create
 /// the session, or if there's an I/O error.
-#[instrument(skip(state), fields(session_id = %request.session_id, working_dir = %request.working_dir.display(), resume = request.resume))]
+#[utoipa::path(
+    post,
+    path = "/api/v1/sessions",
+    tag = "sessions",
+    request_body = CreateSessionRequest,
+    responses(
+        (status = 200, body = CreateSessionResponse),
+        (status = 400, description = "`INVALID_REQUEST`, or `WORKING_DIR_INVALID` for a missing working directory", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "`FORBIDDEN`: a caller who is not an admin chose a `backend`", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 429, description = "`SESSION_LIMIT_REACHED`: `MAX_RUNNING_SESSIONS` are running", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "`SPAWN_FAILED`: Claude failed to start", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 503, description = "`SHUTTING_DOWN`: the server is draining sessions", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, caller), fields(session_id = %request.session_id, working_dir = %request.working_dir.display(), resume = request.resume))]
 pub async fn create_session(
     State(state): State<AppState>,
-    Json(request): Json<CreateSessionRequest>,
+    caller: Caller,
+    LimitedJson(request): LimitedJson<CreateSessionRequest>,
 ) -> OrchestratorResult<Json<CreateSessionResponse>> {
     info!(
         session_id = %request.session_id,
@@ -63,19 +216,43 @@ pub async fn create_session(
         "Creating session"
     );
 
-    // Validate request
-    if request.session_id.is_empty() {
-        warn!("Rejecting session creation request: empty session_id");
-        return Err(crate::error::OrchestratorError::InvalidRequest(
-            "session_id cannot be empty".to_string(),
-        ));
+    let deprecation = request.version.deprecation();
+    if let Some(deprecation) = &deprecation {
+        warn!(
+            session_id = %request.session_id,
+            field = %deprecation.field,
+            "Session created with a deprecated request shape"
+        );
     }
 
-    if request.bootstrap_messages.is_empty() {
-        warn!("Rejecting session creation request: empty bootstrap_messages");
-        return Err(crate::error::OrchestratorError::InvalidRequest(
-            "bootstrap_messages cannot be empty".to_string(),
-        ));
+    validate_create_request(&request, &caller)?;
+
+    // Other users' sessions can be neither resumed nor joined, as if they did not exist
+    let owner = state.session_manager.session_owner(&request.session_id);
+    let existing = request.resume
+        || owner.is_some()
+        || state
+            .session_manager
+            .get_session(&request.session_id)
+            .is_some();
+    if existing && !caller.can_access(owner.as_deref()) {
+        warn!(session_id = %request.session_id, "Rejecting access to another user's session");
+        return Err(OrchestratorError::SessionNotFound(request.session_id));
+    }
+    let mut options = request.options.clone();
+    options.owner = caller.identity().ok();
+
+    let mut bootstrap_messages = request.bootstrap_messages.clone();
+    if let Some(upload_id) = &request.first_message_upload {
+        let text = state.session_manager.uploads().read_text(upload_id).await?;
+        debug!(upload_id = %upload_id, len = text.len(), "Appending uploaded first message");
+        bootstrap_messages.push(
+            serde_json::json!({
+                "type": "user",
+                "message": {"role": "user", "content": text},
+            })
+            .to_string(),
+        );
     }
 
     // Create or resume session
@@ -85,7 +262,8 @@ pub async fn create_session(
             request.session_id.clone(),
             &request.working_dir,
             request.resume,
-            request.bootstrap_messages.clone(),
+            bootstrap_messages,
+            options,
         )
         .await
     {
@@ -95,6 +273,10 @@ pub async fn create_session(
                 actual_id = %id,
                 "Session created successfully"
             );
+            // The upload was delivered, a retry with the same ID would resend it
+            if let Some(upload_id) = &request.first_message_upload {
+                state.session_manager.uploads().remove(upload_id).await;
+            }
             id
         }
         Err(e) => {
@@ -123,24 +305,255 @@ pub async fn create_session(
         session_id: actual_session_id,
         websocket_url,
         approval_websocket_url,
+        deprecation,
+    }))
+}
+
+/// Rejects session creation requests that are missing required fields, whose
+/// `session_id` cannot be used as a file name, or that choose a backend without the
+/// caller being an admin.
+fn validate_create_request(
+    request: &CreateSessionRequest,
+    caller: &Caller,
+) -> OrchestratorResult<()> {
+    if request.session_id.is_empty() {
+        warn!("Rejecting session creation request: empty session_id");
+        return Err(crate::error::OrchestratorError::InvalidRequest(
+            "session_id cannot be empty".to_string(),
+        ));
+    }
+    // It names the session's files, which must not end up outside their directories
+    if !is_valid_session_id(&request.session_id) {
+        warn!(session_id = %request.session_id, "Rejecting session creation request: session_id is not a file name");
+        return Err(OrchestratorError::InvalidRequest(format!(
+            "session_id {} must not contain path separators or be . or ..",
+            request.session_id
+        )));
+    }
+
+    if request.bootstrap_messages.is_empty() && request.first_message_upload.is_none() {
+        warn!("Rejecting session creation request: empty bootstrap_messages");
+        return Err(crate::error::OrchestratorError::InvalidRequest(
+            "bootstrap_messages cannot be empty".to_string(),
+        ));
+    }
+    check_backend_choice(&request.options, caller)
+}
+
+/// Rejects a backend choice by a caller who is not an admin: picking the CLI would run
+/// Claude on the host where sessions are meant to be contained.
+fn check_backend_choice(options: &SessionOptions, caller: &Caller) -> OrchestratorResult<()> {
+    if options.backend.is_some() && !caller.admin {
+        warn!("Rejecting a backend choice by a caller who is not an admin");
+        return Err(OrchestratorError::Forbidden(
+            "Only admins may choose a session backend".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Branches a session into a new one: copies its transcript up to the requested message
+/// and starts a Claude process resuming from there, leaving the source session as is.
+///
+/// # Errors
+///
+/// Returns an error if the session does not exist, the message is not in its transcript,
+/// no bootstrap messages are given, or the new session cannot be started.
+#[utoipa::path(
+    post,
+    path = "/api/v1/sessions/{session_id}/fork",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    request_body = ForkSessionRequest,
+    responses(
+        (status = 200, body = CreateSessionResponse),
+        (status = 403, description = "`FORBIDDEN`: a caller who is not an admin chose a `backend`", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, caller, request), fields(session_id = %session_id))]
+pub async fn fork_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    caller: Caller,
+    LimitedJson(request): LimitedJson<ForkSessionRequest>,
+) -> OrchestratorResult<Json<CreateSessionResponse>> {
+    if request.bootstrap_messages.is_empty() {
+        return Err(OrchestratorError::InvalidRequest(
+            "bootstrap_messages cannot be empty".to_string(),
+        ));
+    }
+    check_backend_choice(&request.options, &caller)?;
+
+    let discovery = SessionDiscovery::new(&state.config, &state.session_manager);
+    let (source, content) = discovery.get_session_content(&session_id).await?;
+    let fork_id = session_fork::fork_session(
+        &state.config.claude_projects_dir,
+        &source.working_directory,
+        &content,
+        request.message_uuid.as_deref(),
+    )?;
+
+    let mut options = request.options;
+    options.owner = caller.identity().ok();
+    // Resumed sessions keep the owner of their transcript, which the fork has none of yet
+    if let Some(owner) = &options.owner {
+        state.session_manager.record_session_owner(&fork_id, owner);
+    }
+    let actual_session_id = state
+        .session_manager
+        .create_session(
+            fork_id,
+            &source.working_directory,
+            true,
+            request.bootstrap_messages,
+            options,
+        )
+        .await?;
+    info!(
+        source_id = %session_id,
+        session_id = %actual_session_id,
+        message_uuid = ?request.message_uuid,
+        "Session forked"
+    );
+
+    Ok(Json(CreateSessionResponse {
+        websocket_url: format!("/api/v1/sessions/{actual_session_id}/claude_ws"),
+        approval_websocket_url: format!("/api/v1/sessions/{actual_session_id}/claude_approvals_ws"),
+        session_id: actual_session_id,
+        deprecation: None,
     }))
 }
 
+/// Starts a chunked upload of content too large to send inline with a request.
+///
+/// # Errors
+///
+/// Returns an error if the announced size is over the limit or the upload cannot be stored.
+#[utoipa::path(
+    post,
+    path = "/api/v1/uploads",
+    tag = "uploads",
+    request_body = CreateUploadRequest,
+    responses(
+        (status = 200, body = UploadStatus),
+        (status = 413, description = "`PAYLOAD_TOO_LARGE`: `total_size` is over the upload limit", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, request), fields(total_size = ?request.total_size))]
+pub async fn create_upload(
+    State(state): State<AppState>,
+    Json(request): Json<CreateUploadRequest>,
+) -> OrchestratorResult<Json<UploadStatus>> {
+    Ok(Json(
+        state
+            .session_manager
+            .uploads()
+            .create(request.total_size)
+            .await?,
+    ))
+}
+
+/// Appends the request body to an upload at `offset`.
+///
+/// # Errors
+///
+/// Returns an error if the upload is unknown, the offset does not match the bytes
+/// received so far, or the upload would grow past its size.
+#[utoipa::path(
+    put,
+    path = "/api/v1/uploads/{upload_id}",
+    tag = "uploads",
+    params(("upload_id" = String, Path, description = "ID of the upload"), AppendUploadQuery),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses(
+        (status = 200, body = UploadStatus),
+        (status = 400, description = "`INVALID_REQUEST`: `offset` is not the number of bytes received so far", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "`UPLOAD_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 413, description = "`PAYLOAD_TOO_LARGE`: the upload would grow past its size", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, chunk), fields(upload_id = %upload_id, offset = query.offset, chunk_len = chunk.len()))]
+pub async fn append_upload(
+    State(state): State<AppState>,
+    Path(upload_id): Path<String>,
+    Query(query): Query<AppendUploadQuery>,
+    chunk: Bytes,
+) -> OrchestratorResult<Json<UploadStatus>> {
+    Ok(Json(
+        state
+            .session_manager
+            .uploads()
+            .append(&upload_id, query.offset, &chunk)
+            .await?,
+    ))
+}
+
+/// Reports how much of an upload has arrived, so an interrupted client can resume.
+///
+/// # Errors
+///
+/// Returns an error if the upload is unknown.
+#[utoipa::path(
+    get,
+    path = "/api/v1/uploads/{upload_id}",
+    tag = "uploads",
+    params(("upload_id" = String, Path, description = "ID of the upload")),
+    responses(
+        (status = 200, body = UploadStatus),
+        (status = 404, description = "`UPLOAD_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state), fields(upload_id = %upload_id))]
+pub async fn get_upload(
+    State(state): State<AppState>,
+    Path(upload_id): Path<String>,
+) -> OrchestratorResult<Json<UploadStatus>> {
+    Ok(Json(
+        state.session_manager.uploads().status(&upload_id).await?,
+    ))
+}
+
 /// Gets information about a specific session including its content.
 ///
+/// When `hook` names a configured transcript hook, the content is passed through it first.
+/// With `format=ndjson` only the content is sent, streamed from the journal.
+///
 /// # Errors
 ///
-/// Returns an error if the session is not found or if there's an I/O error accessing
-/// the session data.
-#[instrument(skip(state), fields(session_id = %session_id))]
+/// Returns an error if the session is not found, if there's an I/O error accessing
+/// the session data, or if the requested hook is unknown or fails.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/{session_id}",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session"), GetSessionQuery),
+    responses(
+        (status = 200, content(
+            ("application/json" = GetSessionResponse),
+            ("application/x-ndjson" = String),
+        )),
+        (status = 400, description = "`INVALID_REQUEST`: unknown hook, or a hook with `format=ndjson`", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, query), fields(session_id = %session_id))]
+#[allow(clippy::too_many_lines)]
 pub async fn get_session(
     State(state): State<AppState>,
     Path(session_id): Path<String>,
-) -> OrchestratorResult<Json<GetSessionResponse>> {
-    info!(session_id = %session_id, "Getting session details");
+    Query(query): Query<GetSessionQuery>,
+) -> OrchestratorResult<Response> {
+    info!(session_id = %session_id, hook = ?query.hook, "Getting session details");
+
+    if query.format == Some(ContentFormat::Ndjson) {
+        return stream_session_content(&state, &session_id, &query).await;
+    }
+
+    let hook = requested_hook(&state, query.hook.as_deref())?;
 
     let discovery = SessionDiscovery::new(&state.config, &state.session_manager);
-    let (session_info, content) = match discovery.get_session_content(&session_id).await {
+    let (session_info, mut content) = match discovery.get_session_content(&session_id).await {
         Ok((info, content)) => {
             info!(
                 session_id = %session_id,
@@ -161,6 +574,47 @@ pub async fn get_session(
         }
     };
 
+    // Before the hook and paging, which then work on the slimmer content
+    if !query.inline_media {
+        transcript_media::rewrite(&session_id, &mut content);
+    }
+    let content = match hook {
+        Some(hook) => hook.transform(content).await?,
+        None => content,
+    };
+    // Located before paging so anchors on other pages still tell where to jump
+    let anchors = anchors::locate(state.session_manager.anchors().list(&session_id), &content);
+    let (content, paging) = page_content(content, &query, state.config.session_response_max_bytes);
+    if let Some(paging) = &paging {
+        debug!(
+            session_id = %session_id,
+            total_entries = paging.total_entries,
+            total_bytes = paging.total_bytes,
+            omitted = paging.omitted,
+            "Returning paged session content"
+        );
+    }
+
+    // Launch options and status only live with the in-memory session
+    let in_memory = state.session_manager.get_session(&session_id);
+    let options = in_memory
+        .as_ref()
+        .map(|session| session.options.clone())
+        .unwrap_or_default();
+    let (status, resources, resource_limit) = match &in_memory {
+        Some(session) => (
+            Some(session.get_status().await),
+            session.get_process_stats().await,
+            session.resource_limit(),
+        ),
+        None => (None, None, None),
+    };
+
+    let recorded_inputs = match &state.config.session_state_dir {
+        Some(dir) => input_record::read(dir, &session_id).await,
+        None => Vec::new(),
+    };
+
     let (websocket_url, approval_websocket_url) = if session_info.active {
         let ws_url = format!("/api/v1/sessions/{session_id}/claude_ws");
         let approval_url = format!("/api/v1/sessions/{session_id}/claude_approvals_ws");
@@ -177,10 +631,1678 @@ pub async fn get_session(
     };
 
     Ok(Json(GetSessionResponse {
+        usage: state
+            .session_manager
+            .usage()
+            .session_totals(&session_info.session_id),
         session_id: session_info.session_id,
         working_directory: session_info.working_directory,
         content,
         websocket_url,
         approval_websocket_url,
+        status,
+        resources,
+        resource_limit,
+        paging,
+        anchors,
+        recorded_inputs,
+        options,
+    })
+    .into_response())
+}
+
+/// The configured transcript hook a request named, if it named one.
+fn requested_hook(
+    state: &AppState,
+    name: Option<&str>,
+) -> OrchestratorResult<Option<ExternalCommandHook>> {
+    let Some(name) = name else {
+        return Ok(None);
+    };
+    let Some(argv) = state.config.transcript_hooks.get(name) else {
+        warn!(hook = %name, "Rejecting request for unknown transcript hook");
+        return Err(OrchestratorError::InvalidRequest(format!(
+            "Unknown transcript hook: {name}"
+        )));
+    };
+    Ok(Some(ExternalCommandHook::new(
+        name.to_string(),
+        argv.clone(),
+    )))
+}
+
+/// The content of a session as NDJSON, read from its journal while it is sent, so that
+/// transcripts of any size take little memory. `offset` and `limit` count lines.
+async fn stream_session_content(
+    state: &AppState,
+    session_id: &str,
+    query: &GetSessionQuery,
+) -> OrchestratorResult<Response> {
+    if query.hook.is_some() {
+        return Err(OrchestratorError::InvalidRequest(
+            "Transcript hooks need the whole content and cannot be used with format=ndjson"
+                .to_string(),
+        ));
+    }
+    let discovery = SessionDiscovery::new(&state.config, &state.session_manager);
+    let body = match discovery.session_journal(session_id)? {
+        Some(path) => {
+            let range = LineRange {
+                offset: query.offset.unwrap_or(0),
+                limit: query.limit,
+            };
+            transcript_stream::ndjson_body(&path, session_id.to_string(), range, query.inline_media)
+                .await
+                .map_err(|e| {
+                    error!(session_id = %session_id, path = %path.display(), error = %e, "Failed to open session journal");
+                    OrchestratorError::FileParseError(format!(
+                        "Failed to open file {}: {e}",
+                        path.display()
+                    ))
+                })?
+        }
+        None => Body::empty(),
+    };
+    debug!(session_id = %session_id, offset = ?query.offset, limit = ?query.limit, "Streaming session content");
+    Ok(([(header::CONTENT_TYPE, transcript_stream::NDJSON)], body).into_response())
+}
+
+/// Applies the requested page, or leaves out content that is over the size limit unless
+/// the whole of it was asked for.
+fn page_content(
+    content: Vec<serde_json::Value>,
+    query: &GetSessionQuery,
+    max_bytes: Option<usize>,
+) -> (Vec<serde_json::Value>, Option<ContentPaging>) {
+    let paged = query.offset.is_some() || query.limit.is_some();
+    let max_bytes = max_bytes.filter(|_| !query.full);
+    if !paged && max_bytes.is_none() {
+        return (content, None);
+    }
+
+    let total_entries = content.len();
+    let total_bytes: usize = content
+        .iter()
+        .map(|entry| serde_json::to_vec(entry).map_or(0, |bytes| bytes.len()))
+        .sum();
+    let page_size = match max_bytes {
+        Some(max_bytes) if total_bytes > 0 => {
+            (total_entries.saturating_mul(max_bytes) / total_bytes).clamp(1, total_entries.max(1))
+        }
+        _ => total_entries.max(1),
+    };
+
+    if !paged {
+        if max_bytes.is_some_and(|max_bytes| total_bytes <= max_bytes) {
+            return (content, None);
+        }
+        let paging = ContentPaging {
+            total_entries,
+            total_bytes,
+            omitted: true,
+            page_size,
+            next_offset: Some(0),
+        };
+        return (Vec::new(), Some(paging));
+    }
+
+    let offset = query.offset.unwrap_or(0);
+    let limit = query.limit.unwrap_or(page_size);
+    let end = offset.saturating_add(limit);
+    let page = content.into_iter().skip(offset).take(limit).collect();
+    let paging = ContentPaging {
+        total_entries,
+        total_bytes,
+        omitted: false,
+        page_size,
+        next_offset: (end < total_entries).then_some(end),
+    };
+    (page, Some(paging))
+}
+
+/// Turns debug capture on or off for an active session.
+///
+/// While on, the session's spans are logged at trace level and raw Claude stdin/stdout
+/// is written to a capture file. Capture switches itself off after `duration_secs`.
+///
+/// # Errors
+///
+/// Returns an error if the session is not active, the duration is zero, or the capture
+/// file cannot be created.
+#[utoipa::path(
+    put,
+    path = "/api/v1/sessions/{session_id}/debug",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    request_body = SetSessionDebugRequest,
+    responses(
+        (status = 200, body = SetSessionDebugResponse),
+        (status = 400, description = "`INVALID_REQUEST`: `duration_secs` is zero", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, request), fields(session_id = %session_id, enabled = request.enabled))]
+pub async fn set_session_debug(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    Json(request): Json<SetSessionDebugRequest>,
+) -> OrchestratorResult<Json<SetSessionDebugResponse>> {
+    let session = state
+        .session_manager
+        .get_session(&session_id)
+        .ok_or_else(|| OrchestratorError::SessionNotFound(session_id.clone()))?;
+
+    if !request.enabled {
+        let stopped = session.stop_debug_capture(None).await;
+        info!(session_id = %session_id, stopped, "Debug capture disabled");
+        return Ok(Json(SetSessionDebugResponse {
+            session_id,
+            enabled: false,
+            debug_file: None,
+            expires_in_secs: None,
+        }));
+    }
+
+    let duration = match request.duration_secs {
+        Some(0) => {
+            return Err(OrchestratorError::InvalidRequest(
+                "duration_secs must be greater than zero".to_string(),
+            ))
+        }
+        Some(secs) => Duration::from_secs(secs).min(MAX_DEBUG_DURATION),
+        None => DEFAULT_DEBUG_DURATION,
+    };
+    let dir = state
+        .config
+        .session_state_dir
+        .clone()
+        .unwrap_or_else(|| std::env::temp_dir().join("chef-de-vibe-debug"));
+
+    let (debug_file, generation) = session.start_debug_capture(&dir).await.map_err(|e| {
+        error!(session_id = %session_id, error = %e, "Failed to start debug capture");
+        OrchestratorError::InternalError(format!("Failed to create debug capture file: {e}"))
+    })?;
+    info!(
+        session_id = %session_id,
+        debug_file = %debug_file.display(),
+        duration_secs = duration.as_secs(),
+        "Debug capture enabled"
+    );
+
+    let expiring_session = session.clone();
+    let expiring_session_id = session_id.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(duration).await;
+        if expiring_session.stop_debug_capture(Some(generation)).await {
+            info!(session_id = %expiring_session_id, "Debug capture expired");
+        }
+    });
+
+    Ok(Json(SetSessionDebugResponse {
+        session_id,
+        enabled: true,
+        debug_file: Some(debug_file),
+        expires_in_secs: Some(duration.as_secs()),
+    }))
+}
+
+/// Downloads a session's wire log: every line relayed to and from Claude, oldest first,
+/// one JSON object per line.
+///
+/// # Errors
+///
+/// Returns an error if wire logging is disabled or the session has no wire log.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/{session_id}/wirelog",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    responses(
+        (status = 200, description = "One JSON object per relayed line, oldest first", body = String, content_type = "application/x-ndjson"),
+        (status = 400, description = "`INVALID_REQUEST`: wire logging is not enabled", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "`WIRE_LOG_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state), fields(session_id = %session_id))]
+pub async fn get_session_wirelog(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> OrchestratorResult<Response> {
+    let settings = state.config.wire_log.as_ref().ok_or_else(|| {
+        OrchestratorError::InvalidRequest("Wire logging is not enabled (WIRE_LOG_DIR)".to_string())
+    })?;
+    let contents = wire_log::read(settings, &session_id).await?;
+    debug!(session_id = %session_id, bytes = contents.len(), "Serving wire log");
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{session_id}.wire.jsonl\""),
+            ),
+        ],
+        contents,
+    )
+        .into_response())
+}
+
+/// Answers several pending tool approvals of an active session in one call.
+///
+/// The body is a list of `{id, response}` items, the same shape the approval WebSocket
+/// accepts one at a time. Every item gets its own result. Sessions running on another
+/// instance sharing `APPROVAL_SYNC_DIR` are answered through it.
+///
+/// # Errors
+///
+/// Returns an error if the session is not active or the identity is missing.
+#[utoipa::path(
+    post,
+    path = "/api/v1/sessions/{session_id}/approvals",
+    tag = "approvals",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    request_body = Vec<ApprovalAnswer>,
+    responses(
+        (status = 200, body = BulkApprovalResponse),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, caller, responses), fields(session_id = %session_id, count = responses.len()))]
+pub async fn submit_approvals(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    caller: Caller,
+    LimitedJson(responses): LimitedJson<Vec<serde_json::Value>>,
+) -> OrchestratorResult<Json<BulkApprovalResponse>> {
+    let identity = caller.identity()?;
+    let results = match state.session_manager.get_session(&session_id) {
+        Some(session) => {
+            session
+                .submit_approval_responses(responses, &identity)
+                .await
+        }
+        None => state
+            .session_manager
+            .submit_replicated_approval_responses(&session_id, responses, &identity)
+            .await
+            .ok_or_else(|| OrchestratorError::SessionNotFound(session_id.clone()))?,
+    };
+    info!(
+        session_id = %session_id,
+        accepted = results.iter().filter(|r| r.accepted).count(),
+        rejected = results.iter().filter(|r| !r.accepted).count(),
+        "Processed bulk approval response"
+    );
+
+    Ok(Json(BulkApprovalResponse { results }))
+}
+
+/// Lists the pending tool approvals of an active session, for clients that cannot hold
+/// the approval WebSocket open. Those of sessions running on another instance sharing
+/// `APPROVAL_SYNC_DIR` are listed as that instance published them.
+///
+/// # Errors
+///
+/// Returns an error if the session is not active.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/{session_id}/approvals",
+    tag = "approvals",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    responses(
+        (status = 200, body = SessionApprovalsResponse),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state), fields(session_id = %session_id))]
+pub async fn get_session_approvals(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> OrchestratorResult<Json<SessionApprovalsResponse>> {
+    let pending = match state.session_manager.get_session(&session_id) {
+        Some(session) => session.get_pending_approvals_by_priority().await,
+        None => {
+            state
+                .session_manager
+                .replicated_approvals(&session_id)
+                .await
+                .ok_or_else(|| OrchestratorError::SessionNotFound(session_id.clone()))?
+                .approvals
+        }
+    };
+
+    let approvals: Vec<_> = pending.iter().map(ApprovalRequest::frame).collect();
+    debug!(session_id = %session_id, pending = approvals.len(), "Returning pending approvals");
+
+    Ok(Json(SessionApprovalsResponse {
+        session_id,
+        approvals,
+    }))
+}
+
+/// Lists the allows remembered for an active session: those of the session itself and
+/// those of every session of its owner in its working directory.
+///
+/// # Errors
+///
+/// Returns an error if the session is not active.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/{session_id}/approval_rules",
+    tag = "approvals",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    responses(
+        (status = 200, body = SessionApprovalRulesResponse),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state), fields(session_id = %session_id))]
+pub async fn get_approval_rules(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> OrchestratorResult<Json<SessionApprovalRulesResponse>> {
+    let session = state
+        .session_manager
+        .get_session(&session_id)
+        .ok_or_else(|| OrchestratorError::SessionNotFound(session_id.clone()))?;
+
+    Ok(Json(SessionApprovalRulesResponse {
+        rules: state
+            .session_manager
+            .approval_rules()
+            .list(&session_id, &session.rules_project()),
+        session_id,
+    }))
+}
+
+/// Forgets a remembered allow of an active session, so identical requests are asked
+/// again. Unknown rules are simply not there.
+///
+/// # Errors
+///
+/// Returns an error if the session is not active.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/sessions/{session_id}/approval_rules/{rule_id}",
+    tag = "approvals",
+    params(
+        ("session_id" = String, Path, description = "ID of the session"),
+        ("rule_id" = String, Path, description = "ID of the rule"),
+    ),
+    responses(
+        (status = 200, description = "The rules left", body = SessionApprovalRulesResponse),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state), fields(session_id = %session_id, rule_id = %rule_id))]
+pub async fn delete_approval_rule(
+    State(state): State<AppState>,
+    Path((session_id, rule_id)): Path<(String, String)>,
+) -> OrchestratorResult<Json<SessionApprovalRulesResponse>> {
+    let session = state
+        .session_manager
+        .get_session(&session_id)
+        .ok_or_else(|| OrchestratorError::SessionNotFound(session_id.clone()))?;
+
+    let rules = state.session_manager.approval_rules();
+    if rules.remove(&session_id, &session.rules_project(), &rule_id) {
+        info!(session_id = %session_id, rule_id = %rule_id, "Approval rule removed");
+    }
+    Ok(Json(SessionApprovalRulesResponse {
+        rules: rules.list(&session_id, &session.rules_project()),
+        session_id,
+    }))
+}
+
+/// Answers one pending tool approval of an active session. The body is the `response`
+/// the approval WebSocket would carry, e.g. `{"behavior": "deny", "message": "..."}`.
+/// Sessions running on another instance sharing `APPROVAL_SYNC_DIR` are answered
+/// through it.
+///
+/// # Errors
+///
+/// Returns an error if the session is not active, the identity is missing, no such
+/// approval is pending, the identity may not allow it, or it was answered on another
+/// instance first.
+#[utoipa::path(
+    post,
+    path = "/api/v1/sessions/{session_id}/approvals/{approval_id}",
+    tag = "approvals",
+    params(("session_id" = String, Path, description = "ID of the session"), ("approval_id" = String, Path, description = "`id` of the pending approval")),
+    request_body(content = Object, description = "The `response` of an approval answer"),
+    responses(
+        (status = 200, body = ApprovalResponseResult),
+        (status = 403, description = "`FORBIDDEN`: the identity may not allow this request", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "`SESSION_NOT_FOUND` or `APPROVAL_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "`APPROVAL_ALREADY_DECIDED`: another instance sharing `APPROVAL_SYNC_DIR` recorded an answer first", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, caller, response), fields(session_id = %session_id, approval_id = %approval_id))]
+pub async fn submit_approval(
+    State(state): State<AppState>,
+    Path((session_id, approval_id)): Path<(String, String)>,
+    caller: Caller,
+    LimitedJson(response): LimitedJson<serde_json::Value>,
+) -> OrchestratorResult<Json<ApprovalResponseResult>> {
+    let identity = caller.identity()?;
+    if !response.is_object() {
+        return Err(OrchestratorError::InvalidRequest(
+            "expected the approval response as a JSON object".to_string(),
+        ));
+    }
+    match state.session_manager.get_session(&session_id) {
+        Some(session) => {
+            session
+                .submit_approval_response(&approval_id, response, &identity)
+                .await?;
+        }
+        None => {
+            state
+                .session_manager
+                .submit_replicated_approval_response(&session_id, &approval_id, response, &identity)
+                .await?;
+        }
+    }
+    info!(session_id = %session_id, approval_id = %approval_id, decided_by = %identity, "Processed approval response");
+
+    Ok(Json(ApprovalResponseResult {
+        id: Some(approval_id),
+        accepted: true,
+        error: None,
+    }))
+}
+
+/// Shows the messages of an active session that have not been written to Claude yet,
+/// grouped by the client that sent them.
+///
+/// # Errors
+///
+/// Returns an error if the session is not active.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/{session_id}/queue",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    responses(
+        (status = 200, body = SessionQueueResponse),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state), fields(session_id = %session_id))]
+pub async fn get_session_queue(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> OrchestratorResult<Json<SessionQueueResponse>> {
+    let session = state
+        .session_manager
+        .get_session(&session_id)
+        .ok_or_else(|| OrchestratorError::SessionNotFound(session_id.clone()))?;
+
+    let clients = session.get_client_queues().await;
+    debug!(session_id = %session_id, clients = clients.len(), "Returning session queue");
+
+    Ok(Json(SessionQueueResponse {
+        session_id,
+        disconnect_policy: state.config.disconnect_queue_policy,
+        clients,
+    }))
+}
+
+/// Lists the clients connected to an active session, with when each was last heard from.
+///
+/// # Errors
+///
+/// Returns an error if the session is not active.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/{session_id}/clients",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    responses(
+        (status = 200, body = SessionClientsResponse),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state), fields(session_id = %session_id))]
+pub async fn get_session_clients(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> OrchestratorResult<Json<SessionClientsResponse>> {
+    let session = state
+        .session_manager
+        .get_session(&session_id)
+        .ok_or_else(|| OrchestratorError::SessionNotFound(session_id.clone()))?;
+
+    let clients = session.connected_clients().await;
+    debug!(session_id = %session_id, clients = clients.len(), "Returning session clients");
+
+    Ok(Json(SessionClientsResponse {
+        session_id,
+        clients,
     }))
 }
+
+/// Releases or drops the messages held back when a client disconnected.
+///
+/// # Errors
+///
+/// Returns an error if the session is not active.
+#[utoipa::path(
+    post,
+    path = "/api/v1/sessions/{session_id}/queue/{client_id}",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session"), ("client_id" = String, Path, description = "Client whose held messages are resolved")),
+    request_body = ResolveHeldMessagesRequest,
+    responses(
+        (status = 200, body = ResolveHeldMessagesResponse),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, request), fields(session_id = %session_id, client_id = %client_id, action = ?request.action))]
+pub async fn resolve_held_messages(
+    State(state): State<AppState>,
+    Path((session_id, client_id)): Path<(String, String)>,
+    Json(request): Json<ResolveHeldMessagesRequest>,
+) -> OrchestratorResult<Json<ResolveHeldMessagesResponse>> {
+    let session = state
+        .session_manager
+        .get_session(&session_id)
+        .ok_or_else(|| OrchestratorError::SessionNotFound(session_id.clone()))?;
+
+    let count = session
+        .resolve_held_messages(&client_id, request.action == HeldMessagesAction::Release)
+        .await;
+    info!(
+        session_id = %session_id,
+        client_id = %client_id,
+        action = ?request.action,
+        count,
+        "Resolved held client messages"
+    );
+
+    Ok(Json(ResolveHeldMessagesResponse {
+        session_id,
+        client_id,
+        action: request.action,
+        count,
+    }))
+}
+
+/// Subscribes a notification target to events of a session.
+///
+/// The session may be active or only on disk; watches follow it when it is resumed
+/// under a new ID.
+///
+/// # Errors
+///
+/// Returns an error if the session does not exist or the target is not configured.
+#[utoipa::path(
+    put,
+    path = "/api/v1/sessions/{session_id}/watch",
+    tag = "notifications",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    request_body = WatchSessionRequest,
+    responses(
+        (status = 200, body = SessionWatchesResponse),
+        (status = 400, description = "`INVALID_REQUEST`: the target is not configured", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, request), fields(session_id = %session_id, target = %request.target))]
+pub async fn watch_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    Json(request): Json<WatchSessionRequest>,
+) -> OrchestratorResult<Json<SessionWatchesResponse>> {
+    if state.session_manager.get_session(&session_id).is_none() {
+        let discovery = SessionDiscovery::new(&state.config, &state.session_manager);
+        discovery.get_session_content(&session_id).await?;
+    }
+
+    let notifier = state.session_manager.notifier();
+    notifier.watch(&session_id, &request.target, &request.events)?;
+    info!(
+        session_id = %session_id,
+        target = %request.target,
+        events = ?request.events,
+        "Session watch added"
+    );
+
+    Ok(Json(SessionWatchesResponse {
+        watches: notifier.watches(&session_id),
+        session_id,
+    }))
+}
+
+/// Names a message of a session, replacing the anchor of the same name if there is one.
+///
+/// # Errors
+///
+/// Returns an error if the identity is missing, the session does not exist, it has no
+/// message with that UUID, or the anchor is invalid.
+#[utoipa::path(
+    post,
+    path = "/api/v1/sessions/{session_id}/anchors",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    request_body = CreateAnchorRequest,
+    responses(
+        (status = 200, body = SessionAnchorsResponse),
+        (status = 400, description = "`INVALID_REQUEST`: no message with `message_uuid`, or an invalid name", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "`UNAUTHORIZED`: the identity header is missing", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, caller, request), fields(session_id = %session_id, name = %request.name))]
+pub async fn create_anchor(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    caller: Caller,
+    Json(request): Json<CreateAnchorRequest>,
+) -> OrchestratorResult<Json<SessionAnchorsResponse>> {
+    let identity = caller.identity()?;
+    let discovery = SessionDiscovery::new(&state.config, &state.session_manager);
+    let (_, content) = discovery.get_session_content(&session_id).await?;
+    if anchors::entry_index(&content, &request.message_uuid).is_none() {
+        return Err(OrchestratorError::InvalidRequest(format!(
+            "No message with uuid {} in session {session_id}",
+            request.message_uuid
+        )));
+    }
+
+    let store = state.session_manager.anchors();
+    store.set(
+        &session_id,
+        Anchor {
+            name: request.name,
+            message_uuid: request.message_uuid,
+            created_by: identity,
+            created_at: chrono::Utc::now(),
+            entry_index: None,
+        },
+    )?;
+    info!(session_id = %session_id, "Anchor created");
+
+    Ok(Json(SessionAnchorsResponse {
+        anchors: anchors::locate(store.list(&session_id), &content),
+        session_id,
+    }))
+}
+
+/// Removes an anchor of a session. Unknown anchors are simply not there.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/sessions/{session_id}/anchors/{name}",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session"), ("name" = String, Path, description = "Name of the anchor")),
+    responses(
+        (status = 200, body = SessionAnchorsResponse),
+    )
+)]
+#[instrument(skip(state), fields(session_id = %session_id, name = %name))]
+pub async fn delete_anchor(
+    State(state): State<AppState>,
+    Path((session_id, name)): Path<(String, String)>,
+) -> Json<SessionAnchorsResponse> {
+    let store = state.session_manager.anchors();
+    if store.remove(&session_id, &name) {
+        info!(session_id = %session_id, name = %name, "Anchor removed");
+    }
+    Json(SessionAnchorsResponse {
+        anchors: store.list(&session_id),
+        session_id,
+    })
+}
+
+/// Changes the tags and metadata of a session, which may be active or only on disk.
+/// They follow the session when it is resumed under a new ID.
+///
+/// # Errors
+///
+/// Returns an error if the session does not exist or the patch is invalid.
+#[utoipa::path(
+    patch,
+    path = "/api/v1/sessions/{session_id}/metadata",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    request_body = SessionMetadataPatch,
+    responses(
+        (status = 200, body = SessionMetadataResponse),
+        (status = 400, description = "`INVALID_REQUEST`: an invalid tag or metadata key", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, patch), fields(session_id = %session_id))]
+pub async fn update_session_metadata(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    Json(patch): Json<SessionMetadataPatch>,
+) -> OrchestratorResult<Json<SessionMetadataResponse>> {
+    if state.session_manager.get_session(&session_id).is_none() {
+        let discovery = SessionDiscovery::new(&state.config, &state.session_manager);
+        discovery.session_journal(&session_id)?;
+    }
+
+    let updated = state
+        .session_manager
+        .session_metadata()
+        .update(&session_id, &patch)?;
+    info!(session_id = %session_id, tags = ?updated.tags, "Session metadata updated");
+
+    Ok(Json(SessionMetadataResponse {
+        session_id,
+        tags: updated.tags,
+        metadata: updated.metadata,
+    }))
+}
+
+/// Removes one target's watch on a session, or all of them.
+/// Unknown sessions simply have no watches.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/sessions/{session_id}/watch",
+    tag = "notifications",
+    params(("session_id" = String, Path, description = "ID of the session"), UnwatchSessionQuery),
+    responses(
+        (status = 200, body = SessionWatchesResponse),
+    )
+)]
+#[instrument(skip(state), fields(session_id = %session_id))]
+pub async fn unwatch_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    Query(query): Query<UnwatchSessionQuery>,
+) -> Json<SessionWatchesResponse> {
+    let notifier = state.session_manager.notifier();
+    notifier.unwatch(&session_id, query.target.as_deref());
+    info!(session_id = %session_id, target = ?query.target, "Session watch removed");
+
+    Json(SessionWatchesResponse {
+        watches: notifier.watches(&session_id),
+        session_id,
+    })
+}
+
+/// Lists the notification targets watching a session.
+/// Unknown sessions simply have no watches.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/{session_id}/watch",
+    tag = "notifications",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    responses(
+        (status = 200, body = SessionWatchesResponse),
+    )
+)]
+#[instrument(skip(state), fields(session_id = %session_id))]
+pub async fn get_session_watches(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> Json<SessionWatchesResponse> {
+    Json(SessionWatchesResponse {
+        watches: state.session_manager.notifier().watches(&session_id),
+        session_id,
+    })
+}
+
+/// Returns the entries appended to a session's journal after a position the client
+/// already has, so frontends can keep a transcript current without refetching all of it.
+///
+/// # Errors
+///
+/// Returns an error if the session is not found, both positions or an unknown
+/// `after_uuid` are given, or the journal cannot be read.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/{session_id}/content",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session"), SessionContentQuery),
+    responses(
+        (status = 200, body = SessionContentResponse),
+        (status = 400, description = "`INVALID_REQUEST`: both positions given, or no entry with `after_uuid`", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, query), fields(session_id = %session_id))]
+pub async fn get_session_content_since(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    Query(query): Query<SessionContentQuery>,
+) -> OrchestratorResult<Json<SessionContentResponse>> {
+    if query.after_line.is_some() && query.after_uuid.is_some() {
+        return Err(OrchestratorError::InvalidRequest(
+            "Give either after_line or after_uuid, not both".to_string(),
+        ));
+    }
+    let discovery = SessionDiscovery::new(&state.config, &state.session_manager);
+    let mut appended =
+        discovery.content_after(&session_id, query.after_line, query.after_uuid.as_deref())?;
+    if !query.inline_media {
+        transcript_media::rewrite(&session_id, &mut appended.content);
+    }
+    debug!(
+        session_id = %session_id,
+        first_line = appended.first_line,
+        entries = appended.content.len(),
+        total_lines = appended.total_lines,
+        reset = appended.reset,
+        "Served appended session content"
+    );
+    Ok(Json(SessionContentResponse {
+        session_id,
+        first_line: appended.first_line,
+        content: appended.content,
+        next_line: appended.total_lines,
+        reset: appended.reset,
+    }))
+}
+
+/// Shows the standing configuration Claude has in a session's working directory:
+/// `CLAUDE.md` instructions, settings permissions and MCP servers.
+///
+/// # Errors
+///
+/// Returns an error if the session cannot be found.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/{session_id}/context",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    responses(
+        (status = 200, body = SessionContextResponse),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state), fields(session_id = %session_id))]
+pub async fn get_session_context(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> OrchestratorResult<Json<SessionContextResponse>> {
+    let working_directory = if let Some(session) = state.session_manager.get_session(&session_id) {
+        session.working_dir.clone()
+    } else {
+        let discovery = SessionDiscovery::new(&state.config, &state.session_manager);
+        discovery
+            .get_session_content(&session_id)
+            .await?
+            .0
+            .working_directory
+    };
+
+    // The user's Claude directory is the one holding the projects directory
+    let user_dir = state
+        .config
+        .claude_projects_dir
+        .parent()
+        .unwrap_or(&state.config.claude_projects_dir);
+    let context = session_context::collect(user_dir, &working_directory).await;
+    info!(
+        session_id = %session_id,
+        instructions = context.instructions.len(),
+        settings = context.settings.len(),
+        mcp_servers = context.mcp_servers.len(),
+        "Served session context"
+    );
+
+    Ok(Json(SessionContextResponse {
+        session_id,
+        working_directory,
+        instructions: context.instructions,
+        settings: context.settings,
+        mcp_servers: context.mcp_servers,
+    }))
+}
+
+/// Returns the UI preferences stored for the requesting user.
+///
+/// # Errors
+///
+/// Returns an error if an identity header is configured but missing from the request.
+#[utoipa::path(
+    get,
+    path = "/api/v1/preferences",
+    tag = "preferences",
+    responses(
+        (status = 200, body = PreferencesResponse),
+        (status = 401, description = "`UNAUTHORIZED`: the identity header is missing", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, caller))]
+pub async fn get_preferences(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> OrchestratorResult<Json<PreferencesResponse>> {
+    let identity = caller.identity()?;
+    Ok(Json(PreferencesResponse {
+        preferences: state.session_manager.preferences().get(&identity),
+        identity,
+    }))
+}
+
+/// Replaces the UI preferences stored for the requesting user with the JSON object in
+/// the body.
+///
+/// # Errors
+///
+/// Returns an error if the identity is missing, the body is not a JSON object, or it is
+/// too large.
+#[utoipa::path(
+    put,
+    path = "/api/v1/preferences",
+    tag = "preferences",
+    request_body(content = Object, description = "All preferences of the user"),
+    responses(
+        (status = 200, body = PreferencesResponse),
+        (status = 400, description = "`INVALID_REQUEST`: the body is not a JSON object, or is too large", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "`UNAUTHORIZED`: the identity header is missing", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, caller, body))]
+pub async fn set_preferences(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(body): Json<serde_json::Value>,
+) -> OrchestratorResult<Json<PreferencesResponse>> {
+    let identity = caller.identity()?;
+    let serde_json::Value::Object(preferences) = body else {
+        return Err(OrchestratorError::InvalidRequest(
+            "Preferences must be a JSON object".to_string(),
+        ));
+    };
+    state
+        .session_manager
+        .preferences()
+        .set(&identity, preferences.clone())?;
+    info!(identity = %identity, keys = preferences.len(), "Preferences updated");
+    Ok(Json(PreferencesResponse {
+        identity,
+        preferences,
+    }))
+}
+
+/// Pins a session for the requesting user, after the sessions they pinned before.
+///
+/// # Errors
+///
+/// Returns an error if the identity is missing or the preferences would grow too large.
+#[utoipa::path(
+    put,
+    path = "/api/v1/sessions/{session_id}/pin",
+    tag = "preferences",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    responses(
+        (status = 200, body = PinnedSessionsResponse),
+        (status = 400, description = "`INVALID_REQUEST`: the preferences would grow too large", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "`UNAUTHORIZED`: the identity header is missing", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, caller), fields(session_id = %session_id))]
+pub async fn pin_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    caller: Caller,
+) -> OrchestratorResult<Json<PinnedSessionsResponse>> {
+    set_session_pinned(&state, &session_id, &caller, true)
+}
+
+/// Unpins a session for the requesting user.
+///
+/// # Errors
+///
+/// Returns an error if the identity is missing.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/sessions/{session_id}/pin",
+    tag = "preferences",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    responses(
+        (status = 200, body = PinnedSessionsResponse),
+        (status = 401, description = "`UNAUTHORIZED`: the identity header is missing", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, caller), fields(session_id = %session_id))]
+pub async fn unpin_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    caller: Caller,
+) -> OrchestratorResult<Json<PinnedSessionsResponse>> {
+    set_session_pinned(&state, &session_id, &caller, false)
+}
+
+fn set_session_pinned(
+    state: &AppState,
+    session_id: &str,
+    caller: &Caller,
+    pinned: bool,
+) -> OrchestratorResult<Json<PinnedSessionsResponse>> {
+    let identity = caller.identity()?;
+    let pinned_sessions = state
+        .session_manager
+        .preferences()
+        .set_pinned(&identity, session_id, pinned)?;
+    info!(identity = %identity, session_id = %session_id, pinned, "Session pin updated");
+    Ok(Json(PinnedSessionsResponse {
+        identity,
+        pinned_sessions,
+    }))
+}
+
+/// Web Push, or an error telling the client it is not set up.
+fn web_push(state: &AppState) -> OrchestratorResult<&Arc<WebPush>> {
+    state
+        .session_manager
+        .notifier()
+        .web_push()
+        .ok_or_else(|| OrchestratorError::InvalidRequest("Web Push is not configured".to_string()))
+}
+
+/// Returns the VAPID public key browsers subscribe to push notifications with.
+///
+/// # Errors
+///
+/// Returns an error if Web Push is not configured.
+#[utoipa::path(
+    get,
+    path = "/api/v1/push/vapid_public_key",
+    tag = "notifications",
+    responses(
+        (status = 200, body = WebPushKeyResponse),
+        (status = 400, description = "`INVALID_REQUEST`: Web Push is not configured", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn get_web_push_key(
+    State(state): State<AppState>,
+) -> OrchestratorResult<Json<WebPushKeyResponse>> {
+    Ok(Json(WebPushKeyResponse {
+        public_key: web_push(&state)?.public_key().to_string(),
+    }))
+}
+
+/// Returns the browsers the requesting user subscribed to push notifications.
+///
+/// # Errors
+///
+/// Returns an error if Web Push is not configured or the identity is missing.
+#[utoipa::path(
+    get,
+    path = "/api/v1/push/subscriptions",
+    tag = "notifications",
+    responses(
+        (status = 200, body = PushSubscriptionsResponse),
+        (status = 400, description = "`INVALID_REQUEST`: Web Push is not configured", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "`UNAUTHORIZED`: the identity header is missing", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, caller))]
+pub async fn list_push_subscriptions(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> OrchestratorResult<Json<PushSubscriptionsResponse>> {
+    let web_push = web_push(&state)?;
+    let identity = caller.identity()?;
+    Ok(Json(PushSubscriptionsResponse {
+        subscriptions: web_push.subscriptions(&identity),
+        identity,
+    }))
+}
+
+/// Subscribes a browser of the requesting user to push notifications. The body is the
+/// browser's `PushSubscription` as JSON, optionally limited by `events` and `session_ids`.
+///
+/// # Errors
+///
+/// Returns an error if Web Push is not configured, the identity is missing, the
+/// subscription is malformed, or it names a session the caller may not access.
+#[utoipa::path(
+    post,
+    path = "/api/v1/push/subscriptions",
+    tag = "notifications",
+    request_body = PushSubscription,
+    responses(
+        (status = 200, body = PushSubscriptionsResponse),
+        (status = 400, description = "`INVALID_REQUEST`: Web Push is not configured, or the subscription is malformed", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "`UNAUTHORIZED`: the identity header is missing", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "`SESSION_NOT_FOUND`: `session_ids` names a session the caller may not access", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, caller, subscription))]
+pub async fn subscribe_push(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(subscription): Json<PushSubscription>,
+) -> OrchestratorResult<Json<PushSubscriptionsResponse>> {
+    let web_push = web_push(&state)?;
+    let identity = caller.identity()?;
+    // Other users' sessions are not disclosed, as if they did not exist
+    if let Some(session_id) = subscription.session_ids.iter().find(|session_id| {
+        !caller.can_access(state.session_manager.session_owner(session_id).as_deref())
+    }) {
+        warn!(session_id = %session_id, "Rejecting push subscription to another user's session");
+        return Err(OrchestratorError::SessionNotFound(session_id.clone()));
+    }
+    web_push.subscribe(&identity, subscription)?;
+    info!(identity = %identity, "Browser subscribed to push notifications");
+    Ok(Json(PushSubscriptionsResponse {
+        subscriptions: web_push.subscriptions(&identity),
+        identity,
+    }))
+}
+
+/// Unsubscribes the browser with the given push endpoint.
+///
+/// # Errors
+///
+/// Returns an error if Web Push is not configured or the identity is missing.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/push/subscriptions",
+    tag = "notifications",
+    params(UnsubscribePushQuery),
+    responses(
+        (status = 200, body = PushSubscriptionsResponse),
+        (status = 400, description = "`INVALID_REQUEST`: Web Push is not configured", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "`UNAUTHORIZED`: the identity header is missing", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, caller, query))]
+pub async fn unsubscribe_push(
+    State(state): State<AppState>,
+    Query(query): Query<UnsubscribePushQuery>,
+    caller: Caller,
+) -> OrchestratorResult<Json<PushSubscriptionsResponse>> {
+    let web_push = web_push(&state)?;
+    let identity = caller.identity()?;
+    if web_push.unsubscribe(&identity, &query.endpoint) {
+        info!(identity = %identity, "Browser unsubscribed from push notifications");
+    }
+    Ok(Json(PushSubscriptionsResponse {
+        subscriptions: web_push.subscriptions(&identity),
+        identity,
+    }))
+}
+
+/// Searches the approvals answered across all sessions the caller may access, newest
+/// first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/approvals/history",
+    tag = "approvals",
+    params(ApprovalHistoryQuery),
+    responses(
+        (status = 200, body = ApprovalHistoryResponse),
+    )
+)]
+#[instrument(skip(state, caller))]
+pub async fn get_approval_history(
+    State(state): State<AppState>,
+    Query(query): Query<ApprovalHistoryQuery>,
+    caller: Caller,
+) -> Json<ApprovalHistoryResponse> {
+    let (entries, total) = state
+        .session_manager
+        .approval_audit()
+        .query(&query, |entry| {
+            caller.can_access(
+                state
+                    .session_manager
+                    .session_owner(&entry.session_id)
+                    .as_deref(),
+            )
+        });
+    let next_offset = Some(query.offset + entries.len()).filter(|&next| next < total);
+    Json(ApprovalHistoryResponse {
+        entries,
+        total,
+        next_offset,
+    })
+}
+
+/// Shows exactly what the next telemetry report contains, whether or not telemetry is
+/// enabled.
+#[utoipa::path(
+    get,
+    path = "/api/v1/telemetry",
+    tag = "monitoring",
+    responses(
+        (status = 200, body = TelemetryPreviewResponse),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn get_telemetry_preview(
+    State(state): State<AppState>,
+) -> Json<TelemetryPreviewResponse> {
+    let telemetry = state.session_manager.telemetry();
+    Json(TelemetryPreviewResponse {
+        enabled: telemetry.endpoint().is_some(),
+        endpoint: telemetry.endpoint().map(str::to_string),
+        report: telemetry.report(),
+    })
+}
+
+/// Latency percentiles, availability and target breaches of session spawns, transcript
+/// waits, approval round trips and broadcast fan-out over the SLO window.
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/slo",
+    tag = "admin",
+    responses(
+        (status = 200, body = SloReport),
+        (status = 403, description = "`FORBIDDEN`: the caller is not an admin", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn get_slo_report(State(state): State<AppState>) -> Json<SloReport> {
+    Json(state.session_manager.slo().report())
+}
+
+/// Tokens and cost of the turns sessions completed, in total, per day and per working
+/// directory. Callers who are not admins only see their own sessions.
+///
+/// # Errors
+///
+/// Returns an error if `since` is later than `until`, or the caller is not an admin and
+/// has no identity.
+#[utoipa::path(
+    get,
+    path = "/api/v1/usage",
+    tag = "usage",
+    params(UsageQuery),
+    responses(
+        (status = 200, body = UsageResponse),
+        (status = 400, description = "`INVALID_REQUEST`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, caller))]
+pub async fn get_usage(
+    State(state): State<AppState>,
+    Query(query): Query<UsageQuery>,
+    caller: Caller,
+) -> OrchestratorResult<Json<UsageResponse>> {
+    if let (Some(since), Some(until)) = (query.since, query.until) {
+        if since > until {
+            return Err(OrchestratorError::InvalidRequest(
+                "since must not be later than until".to_string(),
+            ));
+        }
+    }
+    let identity = if caller.admin {
+        None
+    } else {
+        Some(caller.identity()?)
+    };
+    Ok(Json(state.session_manager.usage().report(
+        query.since,
+        query.until,
+        |owner| identity.is_none() || owner == identity.as_deref(),
+    )))
+}
+
+/// Memory and CPU use of every running session's Claude process, for Prometheus.
+/// Callers who are not admins only see their own sessions.
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "monitoring",
+    responses(
+        (status = 200, description = "Prometheus text format", body = String, content_type = "text/plain; version=0.0.4"),
+    )
+)]
+#[instrument(skip(state, caller))]
+pub async fn get_metrics(State(state): State<AppState>, caller: Caller) -> impl IntoResponse {
+    let mut samples = Vec::new();
+    for session in state.session_manager.get_active_sessions().await {
+        if !caller.can_access(session.owner()) {
+            continue;
+        }
+        if let Some(stats) = session.get_process_stats().await {
+            samples.push((session.get_id().await, stats));
+        }
+    }
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        process_stats::prometheus_metrics(&samples),
+    )
+}
+
+/// Stops a running session's Claude process and disconnects its clients.
+///
+/// # Errors
+///
+/// Returns an error if there is no such running session.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/sessions/{session_id}",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    responses(
+        (status = 200, body = TerminateSessionResponse),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn delete_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> OrchestratorResult<Json<TerminateSessionResponse>> {
+    Ok(Json(
+        state.session_manager.terminate_session(&session_id).await?,
+    ))
+}
+
+/// Lists the files in a session's working directory as they were when a given
+/// transcript message was produced, using the directory's git history.
+///
+/// # Errors
+///
+/// Returns an error if the session or message cannot be found, the working directory
+/// is not a git repository, or the requested file did not exist at that point.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/{session_id}/files",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session"), SessionFilesQuery),
+    responses(
+        (status = 200, body = SessionFilesResponse),
+        (status = 400, description = "`INVALID_REQUEST`: the working directory is not a git repository, or `path` did not exist then", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state), fields(session_id = %session_id))]
+pub async fn get_session_files(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    Query(query): Query<SessionFilesQuery>,
+) -> OrchestratorResult<Json<SessionFilesResponse>> {
+    let discovery = SessionDiscovery::new(&state.config, &state.session_manager);
+    let (session_info, content) = discovery.get_session_content(&session_id).await?;
+
+    let at = match &query.at_uuid {
+        Some(uuid) => Some(
+            file_history::message_timestamp(&content, uuid).ok_or_else(|| {
+                OrchestratorError::InvalidRequest(format!(
+                    "No timestamped message with uuid {uuid} in session {session_id}"
+                ))
+            })?,
+        ),
+        None => None,
+    };
+
+    let snapshot = file_history::tree_at(&session_info.working_directory, at).await?;
+    let file_content = match (&query.path, &snapshot.commit) {
+        (Some(path), Some(commit)) => {
+            Some(file_history::file_at(&session_info.working_directory, commit, path).await?)
+        }
+        (Some(path), None) => {
+            return Err(OrchestratorError::InvalidRequest(format!(
+                "{path} did not exist yet: nothing was committed at that point"
+            )))
+        }
+        (None, _) => None,
+    };
+    info!(
+        session_id = %session_id,
+        at_uuid = ?query.at_uuid,
+        commit = ?snapshot.commit,
+        files = snapshot.files.len(),
+        "Served session file tree"
+    );
+
+    Ok(Json(SessionFilesResponse {
+        session_id: session_info.session_id,
+        working_directory: session_info.working_directory,
+        at_uuid: query.at_uuid,
+        commit: snapshot.commit,
+        files: snapshot.files,
+        path: query.path,
+        content: file_content,
+    }))
+}
+
+/// Serves an image or other binary file referenced by a session's transcript, under the
+/// id the transcript was rewritten with.
+///
+/// # Errors
+///
+/// Returns an error if the session or media does not exist, a referenced file is outside
+/// the session's working directory, or the media is over the size limit.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/{session_id}/media/{media_id}",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session"), ("media_id" = String, Path, description = "ID the transcript references the media under")),
+    responses(
+        (status = 200, description = "The media, with its own content type", body = Vec<u8>, content_type = "application/octet-stream"),
+        (status = 404, description = "`SESSION_NOT_FOUND` or `MEDIA_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 413, description = "`PAYLOAD_TOO_LARGE`: the media is over the size limit", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn get_session_media(
+    State(state): State<AppState>,
+    Path((session_id, media_id)): Path<(String, String)>,
+) -> OrchestratorResult<Response> {
+    let discovery = SessionDiscovery::new(&state.config, &state.session_manager);
+    let (session_info, content) = discovery.get_session_content(&session_id).await?;
+    let media =
+        transcript_media::resolve(&content, &session_info.working_directory, &media_id).await?;
+    debug!(
+        content_type = %media.content_type,
+        size = media.bytes.len(),
+        "Serving transcript media"
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, media.content_type),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            // Files from the working directory may be SVGs with scripts in them
+            (header::CONTENT_SECURITY_POLICY, "sandbox".to_string()),
+        ],
+        media.bytes,
+    )
+        .into_response())
+}
+
+/// Returns the transcript of a session as a Markdown, HTML or JSON document, for
+/// attaching a conversation elsewhere. When `hook` names a configured transcript hook,
+/// the content is passed through it before rendering.
+///
+/// # Errors
+///
+/// Returns an error if the session does not exist, its transcript cannot be read, or the
+/// requested hook is unknown or fails.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/{session_id}/export",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session"), ExportSessionQuery),
+    responses(
+        (status = 200, content(
+            ("text/markdown" = String),
+            ("text/html" = String),
+            ("application/json" = ExportedSession),
+        )),
+        (status = 400, description = "`INVALID_REQUEST`: unknown hook", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "`TRANSCRIPT_HOOK_FAILED`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state), fields(session_id = %session_id, format = ?query.format))]
+pub async fn export_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    Query(query): Query<ExportSessionQuery>,
+) -> OrchestratorResult<Response> {
+    let hook = requested_hook(&state, query.hook.as_deref())?;
+    let discovery = SessionDiscovery::new(&state.config, &state.session_manager);
+    let document = discovery
+        .export_session(
+            &session_id,
+            query.format,
+            hook.as_ref().map(|hook| hook as &dyn TranscriptHook),
+        )
+        .await?;
+    debug!(size = document.len(), "Serving session export");
+
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                query.format.content_type().to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "inline; filename=\"{session_id}.{}\"",
+                    query.format.extension()
+                ),
+            ),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            // Keeps the HTML export from running anything, should escaping ever miss
+            (header::CONTENT_SECURITY_POLICY, "sandbox".to_string()),
+        ],
+        document,
+    )
+        .into_response())
+}
+
+/// Scratch directory of `session_id`: the running session's, or the one named after
+/// the ID if it is still kept.
+fn session_scratch_dir(
+    state: &AppState,
+    session_id: &str,
+) -> OrchestratorResult<std::path::PathBuf> {
+    let scratch = state.session_manager.scratch().ok_or_else(|| {
+        OrchestratorError::InvalidRequest(
+            "Scratch directories are not enabled (SCRATCH_DIR)".to_string(),
+        )
+    })?;
+    let dir = match state
+        .session_manager
+        .get_session(session_id)
+        .and_then(|session| session.scratch_dir().map(std::path::Path::to_path_buf))
+    {
+        Some(dir) => dir,
+        None => scratch.path(session_id)?,
+    };
+    if !dir.is_dir() {
+        return Err(OrchestratorError::ScratchFileNotFound(format!(
+            "no scratch directory for session {session_id}"
+        )));
+    }
+    Ok(dir)
+}
+
+/// Lists the files in a session's scratch directory.
+///
+/// # Errors
+///
+/// Returns an error if scratch directories are disabled or the session has none.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/{session_id}/scratch",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session")),
+    responses(
+        (status = 200, body = SessionScratchResponse),
+        (status = 400, description = "`INVALID_REQUEST`: scratch directories are not enabled", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "`SCRATCH_FILE_NOT_FOUND`: the session has no scratch directory", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn get_session_scratch(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> OrchestratorResult<Json<SessionScratchResponse>> {
+    let dir = session_scratch_dir(&state, &session_id)?;
+    let list_dir = dir.clone();
+    let (files, truncated) = tokio::task::spawn_blocking(move || scratch::list(&list_dir))
+        .await
+        .map_err(|e| OrchestratorError::InternalError(format!("Scratch listing failed: {e}")))?;
+    debug!(files = files.len(), truncated, "Listed scratch directory");
+
+    Ok(Json(SessionScratchResponse {
+        session_id,
+        directory: dir,
+        files,
+        truncated,
+    }))
+}
+
+/// Serves a file from a session's scratch directory.
+///
+/// # Errors
+///
+/// Returns an error if scratch directories are disabled, there is no such file, the
+/// path leads outside the directory, or the file is over the size limit.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/{session_id}/scratch/{path}",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session"), ("path" = String, Path, description = "Path of the file in the scratch directory")),
+    responses(
+        (status = 200, description = "The file, with a content type guessed from its name", body = Vec<u8>, content_type = "application/octet-stream"),
+        (status = 400, description = "`INVALID_REQUEST`: scratch directories are not enabled, or the path leads outside the directory", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "`SCRATCH_FILE_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 413, description = "`PAYLOAD_TOO_LARGE`: the file is over the size limit", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn get_session_scratch_file(
+    State(state): State<AppState>,
+    Path((session_id, path)): Path<(String, String)>,
+) -> OrchestratorResult<Response> {
+    let dir = session_scratch_dir(&state, &session_id)?;
+    let bytes = scratch::read(&dir, &path).await?;
+    let content_type = mime_guess::from_path(&path)
+        .first_or_octet_stream()
+        .to_string();
+    debug!(content_type = %content_type, size = bytes.len(), "Serving scratch file");
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            // Claude may have generated HTML or SVGs with scripts in them
+            (header::CONTENT_SECURITY_POLICY, "sandbox".to_string()),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+/// Tools called across the sessions of a project the caller may access, to base
+/// approval policies on.
+///
+/// # Errors
+///
+/// Returns an error if the project ID is invalid or there is no such project.
+#[utoipa::path(
+    get,
+    path = "/api/v1/projects/{project_id}/tools",
+    tag = "sessions",
+    params(("project_id" = String, Path, description = "Directory name of the project under the Claude projects directory")),
+    responses(
+        (status = 200, body = ToolCatalog),
+        (status = 400, description = "`INVALID_REQUEST`: an invalid project ID", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "`PROJECT_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, caller))]
+pub async fn get_project_tools(
+    State(state): State<AppState>,
+    Path(project_id): Path<String>,
+    caller: Caller,
+) -> OrchestratorResult<Json<ToolCatalog>> {
+    let dir = tool_catalog::project_dir(&state.config.claude_projects_dir, &project_id)?;
+    let catalog = tool_catalog::scan(&project_id, &dir, |session_id| {
+        caller.can_access(state.session_manager.session_owner(session_id).as_deref())
+    });
+    debug!(
+        sessions = catalog.sessions,
+        calls = catalog.calls,
+        "Built tool catalog"
+    );
+    Ok(Json(catalog))
+}
@@ -1,3 +1,15 @@
+pub mod access_log;
+pub mod admin;
+pub mod auth;
+pub mod body_limit;
+pub mod caching;
+#[cfg(feature = "testing")]
+pub mod faults;
 pub mod handlers;
+pub mod openai;
+pub mod openapi;
+pub mod problem;
+pub mod slack;
+pub mod sse;
 pub mod static_files;
 pub mod websocket;
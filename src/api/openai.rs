@@ -0,0 +1,437 @@
+use crate::api::auth::Caller;
+use crate::api::body_limit::LimitedJson;
+use crate::api::handlers::AppState;
+use crate::error::{OrchestratorError, OrchestratorResult};
+use crate::models::{BroadcastEnvelope, BroadcastMessage, ClaudeMessage, SessionOptions};
+use axum::{
+    extract::State,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::path::PathBuf;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::StreamExt;
+use tracing::{debug, info, instrument, warn};
+use uuid::Uuid;
+
+/// Model name reported when the request doesn't name one.
+const DEFAULT_MODEL: &str = "claude-code";
+
+#[derive(Debug, Deserialize)]
+pub struct ChatCompletionRequest {
+    #[serde(default)]
+    pub model: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    #[serde(default)]
+    pub stream: bool,
+    /// Not part of the chat completions API: directory the session runs in, defaults to the
+    /// orchestrator's own working directory
+    #[serde(default)]
+    pub working_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    /// A string, or a list of content parts of which only `text` parts are used
+    #[serde(default)]
+    pub content: serde_json::Value,
+}
+
+impl ChatMessage {
+    fn text(&self) -> String {
+        match &self.content {
+            serde_json::Value::String(text) => text.clone(),
+            serde_json::Value::Array(parts) => parts
+                .iter()
+                .filter_map(|part| part.get("text")?.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Token counts in the shape the chat completions API reports them
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[allow(clippy::struct_field_names)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+/// What a run of Claude produced, one stdout message at a time.
+#[derive(Debug, PartialEq, Eq)]
+enum RunEvent {
+    Text(String),
+    Finished {
+        usage: Option<Usage>,
+        error: Option<String>,
+    },
+}
+
+/// Turns a chat completions conversation into session options and the single user turn sent to
+/// Claude. System messages become an appended system prompt; earlier turns are replayed
+/// as a transcript ahead of the final user message.
+fn build_prompt(messages: &[ChatMessage]) -> OrchestratorResult<(SessionOptions, String)> {
+    let Some((last, earlier)) = messages.split_last() else {
+        return Err(OrchestratorError::InvalidRequest(
+            "messages must not be empty".to_string(),
+        ));
+    };
+    if last.role != "user" {
+        return Err(OrchestratorError::InvalidRequest(
+            "the last message must have role 'user'".to_string(),
+        ));
+    }
+
+    let mut system = Vec::new();
+    let mut transcript = Vec::new();
+    for message in earlier {
+        match message.role.as_str() {
+            "system" | "developer" => system.push(message.text()),
+            "user" => transcript.push(format!("User: {}", message.text())),
+            "assistant" => transcript.push(format!("Assistant: {}", message.text())),
+            other => {
+                return Err(OrchestratorError::InvalidRequest(format!(
+                    "unsupported message role '{other}'"
+                )))
+            }
+        }
+    }
+
+    let prompt = if transcript.is_empty() {
+        last.text()
+    } else {
+        format!(
+            "Conversation so far:\n\n{}\n\nUser: {}",
+            transcript.join("\n\n"),
+            last.text()
+        )
+    };
+    let options = SessionOptions {
+        system_prompt: None,
+        append_system_prompt: (!system.is_empty()).then(|| system.join("\n\n")),
+        ..Default::default()
+    };
+    Ok((options, prompt))
+}
+
+/// Maps one line of Claude's stream-json output onto a run event; anything that is not
+/// assistant text or the final result is skipped.
+fn parse_output_line(line: &str) -> Option<RunEvent> {
+    match ClaudeMessage::parse(line).ok()? {
+        ClaudeMessage::Assistant(assistant) => {
+            let text: String = assistant.text_blocks().collect();
+            (!text.is_empty()).then_some(RunEvent::Text(text))
+        }
+        ClaudeMessage::Result(result) => {
+            let usage = result.usage.map(|usage| {
+                let prompt_tokens = usage.input_tokens
+                    + usage.cache_read_input_tokens
+                    + usage.cache_creation_input_tokens;
+                let completion_tokens = usage.output_tokens;
+                Usage {
+                    prompt_tokens,
+                    completion_tokens,
+                    total_tokens: prompt_tokens + completion_tokens,
+                }
+            });
+            let error = result.is_error.then(|| {
+                result
+                    .detail()
+                    .unwrap_or("Claude reported an error")
+                    .to_string()
+            });
+            Some(RunEvent::Finished { usage, error })
+        }
+        _ => None,
+    }
+}
+
+/// A Claude session started for a single completion.
+struct Run {
+    session_id: String,
+    model: String,
+    created: i64,
+    output: broadcast::Receiver<BroadcastEnvelope>,
+}
+
+impl Run {
+    async fn start(
+        state: &AppState,
+        caller: &Caller,
+        request: ChatCompletionRequest,
+    ) -> OrchestratorResult<Self> {
+        let (mut options, prompt) = build_prompt(&request.messages)?;
+        options.owner = caller.identity().ok();
+        let working_dir = match request.working_dir {
+            Some(dir) => dir,
+            None => std::env::current_dir()?,
+        };
+
+        let user_message = serde_json::json!({
+            "type": "user",
+            "message": {"role": "user", "content": prompt},
+        });
+        let (session_id, output) = state
+            .session_manager
+            .create_session_with_output(
+                Uuid::new_v4().to_string(),
+                &working_dir,
+                false,
+                vec![user_message.to_string()],
+                options,
+            )
+            .await?;
+        info!(
+            session_id = %session_id,
+            working_dir = %working_dir.display(),
+            "Started session for chat completion"
+        );
+
+        Ok(Self {
+            session_id,
+            model: request.model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+            created: chrono::Utc::now().timestamp(),
+            output,
+        })
+    }
+
+    fn completion_id(&self) -> String {
+        format!("chatcmpl-{}", self.session_id)
+    }
+
+    async fn next_event(&mut self) -> RunEvent {
+        loop {
+            match self.output.recv().await.map(|envelope| envelope.message) {
+                Ok(BroadcastMessage::ClaudeOutput { line, .. }) => {
+                    if let Some(event) = parse_output_line(&line) {
+                        return event;
+                    }
+                }
+                Ok(BroadcastMessage::Disconnect) | Err(broadcast::error::RecvError::Closed) => {
+                    return RunEvent::Finished {
+                        usage: None,
+                        error: Some("Claude exited before finishing".to_string()),
+                    }
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(session_id = %self.session_id, skipped, "Chat completion fell behind Claude output");
+                }
+            }
+        }
+    }
+
+    fn chunk(&self, delta: &serde_json::Value, finish_reason: Option<&str>) -> Event {
+        let chunk = serde_json::json!({
+            "id": self.completion_id(),
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}],
+        });
+        Event::default().data(chunk.to_string())
+    }
+
+    /// Forwards the run as SSE chunks until it finishes or the client goes away.
+    async fn stream(mut self, events: mpsc::Sender<Event>) {
+        let mut role_sent = false;
+        loop {
+            let event = match self.next_event().await {
+                RunEvent::Text(text) => {
+                    let delta = if role_sent {
+                        serde_json::json!({"content": text})
+                    } else {
+                        role_sent = true;
+                        serde_json::json!({"role": "assistant", "content": text})
+                    };
+                    self.chunk(&delta, None)
+                }
+                RunEvent::Finished {
+                    error: Some(error), ..
+                } => {
+                    let _ = events
+                        .send(Event::default().data(
+                            serde_json::json!({"error": {"message": error, "type": "server_error"}})
+                                .to_string(),
+                        ))
+                        .await;
+                    break;
+                }
+                RunEvent::Finished { error: None, .. } => {
+                    let _ = events
+                        .send(self.chunk(&serde_json::json!({}), Some("stop")))
+                        .await;
+                    let _ = events.send(Event::default().data("[DONE]")).await;
+                    break;
+                }
+            };
+            if events.send(event).await.is_err() {
+                debug!(session_id = %self.session_id, "Chat completion client went away");
+                break;
+            }
+        }
+    }
+
+    /// Collects the whole run into a single completion object.
+    async fn collect(mut self) -> OrchestratorResult<serde_json::Value> {
+        let mut content = String::new();
+        let usage = loop {
+            match self.next_event().await {
+                RunEvent::Text(text) => {
+                    if !content.is_empty() {
+                        content.push_str("\n\n");
+                    }
+                    content.push_str(&text);
+                }
+                RunEvent::Finished {
+                    error: Some(error), ..
+                } => {
+                    return Err(OrchestratorError::ProcessCommunicationError(error));
+                }
+                RunEvent::Finished { usage, error: None } => break usage.unwrap_or_default(),
+            }
+        };
+
+        Ok(serde_json::json!({
+            "id": self.completion_id(),
+            "object": "chat.completion",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": content},
+                "finish_reason": "stop",
+            }],
+            "usage": usage,
+        }))
+    }
+}
+
+/// OpenAI-compatible chat completions, each request served by a fresh session that is
+/// stopped once Claude has answered.
+///
+/// # Errors
+///
+/// Returns an error if the request is malformed, the session cannot be started, or
+/// (without streaming) Claude fails before producing a result.
+#[instrument(skip(state, caller, request), fields(stream = request.stream, messages = request.messages.len()))]
+pub async fn chat_completions(
+    State(state): State<AppState>,
+    caller: Caller,
+    LimitedJson(request): LimitedJson<ChatCompletionRequest>,
+) -> OrchestratorResult<Response> {
+    let stream = request.stream;
+    let run = Run::start(&state, &caller, request).await?;
+    let session_id = run.session_id.clone();
+    let session_manager = state.session_manager.clone();
+
+    // The run is driven from its own task so the session is stopped even if the
+    // client hangs up halfway
+    if !stream {
+        let completion = tokio::spawn(async move {
+            let result = run.collect().await;
+            session_manager.stop_session(&session_id).await;
+            result
+        })
+        .await
+        .map_err(|e| OrchestratorError::InternalError(format!("Chat completion failed: {e}")))??;
+        return Ok(Json(completion).into_response());
+    }
+
+    let (events_tx, events_rx) = mpsc::channel(16);
+    tokio::spawn(async move {
+        run.stream(events_tx).await;
+        session_manager.stop_session(&session_id).await;
+    });
+
+    let events = ReceiverStream::new(events_rx).map(Ok::<_, Infallible>);
+    Ok(Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: serde_json::Value) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content,
+        }
+    }
+
+    #[test]
+    fn test_build_prompt() {
+        let (options, prompt) = build_prompt(&[
+            message("system", "Be terse.".into()),
+            message("user", "Hi".into()),
+            message("assistant", "Hello".into()),
+            message(
+                "user",
+                serde_json::json!([{"type": "text", "text": "What is 2+2?"}]),
+            ),
+        ])
+        .unwrap();
+        assert_eq!(options.append_system_prompt.as_deref(), Some("Be terse."));
+        assert_eq!(
+            prompt,
+            "Conversation so far:\n\nUser: Hi\n\nAssistant: Hello\n\nUser: What is 2+2?"
+        );
+
+        let (options, prompt) = build_prompt(&[message("user", "Hi".into())]).unwrap();
+        assert!(options.append_system_prompt.is_none());
+        assert_eq!(prompt, "Hi");
+
+        assert!(build_prompt(&[]).is_err());
+        assert!(build_prompt(&[message("assistant", "Hello".into())]).is_err());
+        assert!(
+            build_prompt(&[message("tool", "x".into()), message("user", "Hi".into())]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_output_line() {
+        assert_eq!(
+            parse_output_line(
+                r#"{"type":"assistant","message":{"content":[{"type":"text","text":"4"},{"type":"tool_use","id":"t"}]}}"#
+            ),
+            Some(RunEvent::Text("4".to_string()))
+        );
+        assert_eq!(
+            parse_output_line(
+                r#"{"type":"result","subtype":"success","is_error":false,"result":"4","usage":{"input_tokens":3,"cache_read_input_tokens":7,"output_tokens":2}}"#
+            ),
+            Some(RunEvent::Finished {
+                usage: Some(Usage {
+                    prompt_tokens: 10,
+                    completion_tokens: 2,
+                    total_tokens: 12,
+                }),
+                error: None,
+            })
+        );
+        assert_eq!(
+            parse_output_line(r#"{"type":"result","subtype":"error_max_turns","is_error":true}"#),
+            Some(RunEvent::Finished {
+                usage: None,
+                error: Some("error_max_turns".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_output_line(r#"{"type":"system","subtype":"init"}"#),
+            None
+        );
+        assert_eq!(parse_output_line("not json"), None);
+    }
+}
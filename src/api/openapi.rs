@@ -0,0 +1,323 @@
+// The `OpenApi` derive expands to code clippy objects to
+#![allow(clippy::needless_for_each)]
+
+use crate::anchors::Anchor;
+use crate::approval_audit::ApprovalAuditEntry;
+use crate::approval_priority::Priority;
+use crate::approval_risk::RiskLevel;
+use crate::approval_rules::{ApprovalRule, RememberScope};
+use crate::config::DisconnectQueuePolicy;
+use crate::error::ErrorResponse;
+use crate::input_record::RecordedInput;
+use crate::models::{
+    AdminSessionState, AdminStatusResponse, ApprovalAnswer, ApprovalFrame, ApprovalHistoryResponse,
+    ApprovalResponseResult, BulkApprovalResponse, Clarification, ClientChannel, ClientQueue,
+    ConnectedClient, ContentFormat, ContentPaging, CreateAnchorRequest, CreateSessionRequest,
+    CreateSessionResponse, CreateUploadRequest, DailyUsage, Deprecation, DrainingState,
+    ForkSessionRequest, GetSessionResponse, HeldMessagesAction, ImportSessionsResponse,
+    ImportedSession, ListSessionsResponse, PinnedSessionsResponse, PreferencesResponse,
+    PushSubscriptionsResponse, QueuedMessage, RescanResponse, ResolveHeldMessagesRequest,
+    ResolveHeldMessagesResponse, SendMessageResponse, SessionAnchorsResponse,
+    SessionApprovalRulesResponse, SessionApprovalsResponse, SessionClientsResponse,
+    SessionContentResponse, SessionContextResponse, SessionFilesResponse, SessionInfo,
+    SessionMetadataResponse, SessionOptions, SessionQueueResponse, SessionScratchResponse,
+    SessionSort, SessionStatus, SessionWatch, SessionWatchesResponse, SetSessionDebugRequest,
+    SetSessionDebugResponse, TelemetryPreviewResponse, TerminateSessionResponse, UsageResponse,
+    WatchSessionRequest, WebPushKeyResponse, WorkingDirUsage,
+};
+use crate::notifications::WatchEvent;
+use crate::process_stats::ProcessStats;
+use crate::resource_limits::ResourceLimit;
+use crate::scratch::ScratchFile;
+use crate::session_backend::BackendKind;
+use crate::session_context::{ContextScope, InstructionFile, McpServer, SettingsFile};
+use crate::session_import::ImportFormat;
+use crate::session_metadata::SessionMetadataPatch;
+use crate::slo::{Operation, OperationReport, SloReport};
+use crate::telemetry::{RouteCounters, SessionEvent, TelemetryReport};
+use crate::tool_catalog::{DetailUsage, ToolCatalog, ToolUsage};
+use crate::transcript_export::{Block, ExportFormat, ExportedSession, Role, Turn};
+use crate::uploads::UploadStatus;
+use crate::usage::UsageTotals;
+use crate::web_push::{PushKeys, PushSubscription};
+use axum::Json;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+/// Where the generated specification is served
+pub const SPEC_PATH: &str = "/api/v1/openapi.json";
+
+/// Where Swagger UI is served when `SWAGGER_UI` is on
+pub const SWAGGER_UI_PATH: &str = "/api/v1/docs";
+
+/// The REST API, for generating clients in other languages. The WebSocket endpoints and
+/// the OpenAI-compatible `/v1/chat/completions` are described in the README only; the
+/// socket frames are listed as schemas.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Chef de Vibe",
+        description = "Orchestrates Claude Code sessions. The WebSocket endpoints and `/v1/chat/completions` are not part of this specification."
+    ),
+    paths(
+        crate::api::handlers::list_sessions,
+        crate::api::handlers::create_session,
+        crate::api::handlers::import_sessions,
+        crate::api::handlers::get_session,
+        crate::api::handlers::get_session_content_since,
+        crate::api::handlers::delete_session,
+        crate::api::handlers::fork_session,
+        crate::api::handlers::get_session_files,
+        crate::api::handlers::export_session,
+        crate::api::handlers::get_session_media,
+        crate::api::handlers::get_session_scratch,
+        crate::api::handlers::get_session_scratch_file,
+        crate::api::handlers::get_session_context,
+        crate::api::handlers::set_session_debug,
+        crate::api::handlers::get_session_wirelog,
+        crate::api::handlers::get_session_queue,
+        crate::api::handlers::get_session_clients,
+        crate::api::handlers::resolve_held_messages,
+        crate::api::handlers::create_anchor,
+        crate::api::handlers::delete_anchor,
+        crate::api::handlers::update_session_metadata,
+        crate::api::handlers::get_project_tools,
+        crate::api::sse::session_events,
+        crate::api::sse::send_message,
+        crate::api::handlers::get_session_approvals,
+        crate::api::handlers::get_approval_rules,
+        crate::api::handlers::delete_approval_rule,
+        crate::api::handlers::submit_approvals,
+        crate::api::handlers::submit_approval,
+        crate::api::handlers::get_approval_history,
+        crate::api::handlers::create_upload,
+        crate::api::handlers::get_upload,
+        crate::api::handlers::append_upload,
+        crate::api::handlers::get_session_watches,
+        crate::api::handlers::watch_session,
+        crate::api::handlers::unwatch_session,
+        crate::api::handlers::get_web_push_key,
+        crate::api::handlers::list_push_subscriptions,
+        crate::api::handlers::subscribe_push,
+        crate::api::handlers::unsubscribe_push,
+        crate::api::handlers::get_preferences,
+        crate::api::handlers::set_preferences,
+        crate::api::handlers::pin_session,
+        crate::api::handlers::unpin_session,
+        crate::api::handlers::get_usage,
+        crate::api::handlers::get_telemetry_preview,
+        crate::api::handlers::get_metrics,
+        crate::api::handlers::get_slo_report,
+        crate::api::admin::get_admin_status,
+        crate::api::admin::set_draining,
+        crate::api::admin::rescan_sessions,
+        crate::api::admin::kill_session,
+    ),
+    components(schemas(
+        AdminSessionState,
+        AdminStatusResponse,
+        Anchor,
+        ApprovalAnswer,
+        ApprovalAuditEntry,
+        ApprovalFrame,
+        ApprovalHistoryResponse,
+        ApprovalResponseResult,
+        ApprovalRule,
+        BackendKind,
+        Block,
+        BulkApprovalResponse,
+        Clarification,
+        ClientChannel,
+        ClientQueue,
+        ConnectedClient,
+        ContentFormat,
+        ContentPaging,
+        ContextScope,
+        CreateAnchorRequest,
+        CreateSessionRequest,
+        CreateSessionResponse,
+        CreateUploadRequest,
+        DailyUsage,
+        Deprecation,
+        DetailUsage,
+        DisconnectQueuePolicy,
+        DrainingState,
+        ErrorResponse,
+        ExportFormat,
+        ExportedSession,
+        ForkSessionRequest,
+        GetSessionResponse,
+        HeldMessagesAction,
+        ImportFormat,
+        ImportSessionsResponse,
+        ImportedSession,
+        InstructionFile,
+        ListSessionsResponse,
+        McpServer,
+        Operation,
+        OperationReport,
+        PinnedSessionsResponse,
+        PreferencesResponse,
+        Priority,
+        ProcessStats,
+        PushKeys,
+        PushSubscription,
+        PushSubscriptionsResponse,
+        QueuedMessage,
+        RecordedInput,
+        RememberScope,
+        RescanResponse,
+        ResolveHeldMessagesRequest,
+        ResolveHeldMessagesResponse,
+        ResourceLimit,
+        RiskLevel,
+        Role,
+        RouteCounters,
+        ScratchFile,
+        SendMessageResponse,
+        SessionAnchorsResponse,
+        SessionApprovalRulesResponse,
+        SessionApprovalsResponse,
+        SessionClientsResponse,
+        SessionContentResponse,
+        SessionContextResponse,
+        SessionEvent,
+        SessionFilesResponse,
+        SessionInfo,
+        SessionMetadataPatch,
+        SessionMetadataResponse,
+        SessionOptions,
+        SessionQueueResponse,
+        SessionScratchResponse,
+        SessionSort,
+        SessionStatus,
+        SessionWatch,
+        SessionWatchesResponse,
+        SetSessionDebugRequest,
+        SetSessionDebugResponse,
+        SettingsFile,
+        SloReport,
+        TelemetryPreviewResponse,
+        TelemetryReport,
+        TerminateSessionResponse,
+        ToolCatalog,
+        ToolUsage,
+        Turn,
+        UploadStatus,
+        UsageResponse,
+        UsageTotals,
+        WatchEvent,
+        WatchSessionRequest,
+        WebPushKeyResponse,
+        WorkingDirUsage,
+    )),
+    tags(
+        (name = "sessions", description = "Starting, inspecting and stopping sessions"),
+        (name = "approvals", description = "Answering Claude's tool approval requests"),
+        (name = "uploads", description = "Content uploaded in chunks ahead of a session"),
+        (name = "notifications", description = "Watches and browser push subscriptions"),
+        (name = "preferences", description = "Per-user UI preferences and pinned sessions"),
+        (name = "usage", description = "Tokens and cost of sessions"),
+        (name = "monitoring", description = "Metrics and telemetry"),
+        (name = "admin", description = "Operating the server; needs an admin key"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Serves the `OpenAPI` specification of the API.
+#[allow(clippy::unused_async)]
+pub async fn get_openapi_spec() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// Swagger UI for the specification at [`SPEC_PATH`].
+#[must_use]
+pub fn swagger_ui() -> SwaggerUi {
+    SwaggerUi::new(SWAGGER_UI_PATH).config(utoipa_swagger_ui::Config::new([SPEC_PATH]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_documents_sessions_and_approvals() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let paths = spec["paths"].as_object().unwrap();
+        assert!(paths["/api/v1/sessions"]["post"].is_object());
+        assert!(paths["/api/v1/sessions/{session_id}/approvals"]["post"].is_object());
+        let refused = &paths["/api/v1/sessions"]["post"]["responses"]["429"];
+        assert!(refused["content"]["application/problem+json"].is_object());
+
+        let schemas = &spec["components"]["schemas"];
+        // Session options are flattened into the request
+        let request = &schemas["CreateSessionRequest"]["allOf"];
+        assert_eq!(request[0]["$ref"], "#/components/schemas/SessionOptions");
+        let required: Vec<&str> = request[1]["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|field| field.as_str())
+            .collect();
+        assert!(required.contains(&"session_id"));
+        assert!(required.contains(&"working_dir"));
+        assert!(schemas["ApprovalFrame"]["properties"]["risk"].is_object());
+        assert!(schemas["ErrorResponse"]["properties"]["code"].is_object());
+    }
+
+    #[test]
+    fn test_spec_documents_admin_and_per_session_routes() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let paths = spec["paths"].as_object().unwrap();
+        for (path, method) in [
+            ("/api/v1/sessions/{session_id}/wirelog", "get"),
+            ("/api/v1/sessions/{session_id}/metadata", "patch"),
+            ("/api/v1/sessions/{session_id}/export", "get"),
+            ("/api/v1/uploads/{upload_id}", "put"),
+            ("/api/v1/push/subscriptions", "delete"),
+            ("/api/v1/admin/slo", "get"),
+            ("/api/v1/admin/draining", "put"),
+            ("/api/v1/admin/sessions/{session_id}/kill", "post"),
+            ("/metrics", "get"),
+        ] {
+            assert!(paths[path][method].is_object(), "{method} {path} missing");
+        }
+        let tag = &paths["/api/v1/sessions"]["get"]["parameters"];
+        assert!(tag
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|parameter| parameter["name"] == "tag"));
+    }
+
+    #[test]
+    fn test_spec_references_only_listed_schemas() {
+        fn collect_refs(value: &serde_json::Value, refs: &mut Vec<String>) {
+            match value {
+                serde_json::Value::Object(object) => {
+                    if let Some(serde_json::Value::String(target)) = object.get("$ref") {
+                        refs.push(target.clone());
+                    }
+                    object.values().for_each(|value| collect_refs(value, refs));
+                }
+                serde_json::Value::Array(items) => {
+                    items.iter().for_each(|value| collect_refs(value, refs));
+                }
+                _ => {}
+            }
+        }
+
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let mut refs = Vec::new();
+        collect_refs(&spec, &mut refs);
+        let schemas = spec["components"]["schemas"].as_object().unwrap();
+        let mut missing: Vec<&str> = refs
+            .iter()
+            .map(|target| target.trim_start_matches("#/components/schemas/"))
+            .filter(|name| !schemas.contains_key(*name))
+            .collect();
+        missing.sort_unstable();
+        missing.dedup();
+        assert!(missing.is_empty(), "schemas not listed: {missing:?}");
+    }
+}
@@ -0,0 +1,65 @@
+use crate::error::ErrorResponse;
+use axum::{
+    body::{self, Body},
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Largest rejection message kept; axum's are a line or two
+const MAX_REJECTION_BYTES: usize = 16 * 1024;
+
+/// Middleware turning the plain-text rejections of axum's extractors (a malformed
+/// query string, a JSON body of the wrong shape) into problem documents, so every
+/// API error carries a `code`. Errors the handlers return already are left alone.
+pub async fn problem_details(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error()) || !is_plain_text(&response) {
+        return response;
+    }
+
+    let (_, body) = response.into_parts();
+    let detail = match body::to_bytes(body, MAX_REJECTION_BYTES).await {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(_) => status.canonical_reason().unwrap_or_default().to_string(),
+    };
+    ErrorResponse::new(status, rejection_code(status), detail).into_response()
+}
+
+fn is_plain_text(response: &Response<Body>) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/plain"))
+}
+
+fn rejection_code(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::PAYLOAD_TOO_LARGE => "PAYLOAD_TOO_LARGE",
+        StatusCode::UNAUTHORIZED => "UNAUTHORIZED",
+        StatusCode::FORBIDDEN => "FORBIDDEN",
+        status if status.is_server_error() => "INTERNAL_ERROR",
+        _ => "INVALID_REQUEST",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejection_codes() {
+        assert_eq!(
+            rejection_code(StatusCode::UNPROCESSABLE_ENTITY),
+            "INVALID_REQUEST"
+        );
+        assert_eq!(rejection_code(StatusCode::BAD_REQUEST), "INVALID_REQUEST");
+        assert_eq!(
+            rejection_code(StatusCode::INTERNAL_SERVER_ERROR),
+            "INTERNAL_ERROR"
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use crate::api::handlers::AppState;
+use crate::error::{OrchestratorError, OrchestratorResult};
+use crate::slack::{self, ButtonPress, SlackApprovals};
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+};
+use std::time::SystemTime;
+use tracing::{info, instrument};
+
+/// Path Slack sends button presses to, set as the Request URL of the app's Interactivity.
+pub const INTERACTIONS_PATH: &str = "/api/v1/slack/interactions";
+
+/// Receives presses of the Allow and Deny buttons of approval requests posted to Slack,
+/// and answers the request as the approval WebSocket would. Slack signs every callback,
+/// so this is reachable without an API key.
+///
+/// Slack is always answered with `200 OK` once the signature checks out; a press that
+/// cannot be applied, e.g. because the request was answered already, is explained to
+/// the user who pressed it instead.
+///
+/// # Errors
+///
+/// Returns an error if Slack is not set up or the callback is not signed by it.
+#[instrument(skip_all)]
+pub async fn slack_interactions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> OrchestratorResult<StatusCode> {
+    let slack = state.session_manager.slack().ok_or_else(|| {
+        OrchestratorError::InvalidRequest("Slack integration is not configured".to_string())
+    })?;
+    if !slack.verify(&headers, &body, SystemTime::now()) {
+        return Err(OrchestratorError::Unauthorized(
+            "invalid Slack request signature".to_string(),
+        ));
+    }
+    // Other interactions with the app need no answer
+    let Some(press) = slack::parse_button_press(&body) else {
+        return Ok(StatusCode::OK);
+    };
+
+    if let Err(e) = apply(&state, &press).await {
+        info!(
+            session_id = %press.session_id,
+            approval_id = %press.approval_id,
+            decided_by = %press.decided_by,
+            error = %e,
+            "Slack approval response not applied"
+        );
+        reply(slack, &press, &e.to_string());
+    } else {
+        info!(
+            session_id = %press.session_id,
+            approval_id = %press.approval_id,
+            decided_by = %press.decided_by,
+            allow = press.allow,
+            "Processed Slack approval response"
+        );
+    }
+    Ok(StatusCode::OK)
+}
+
+async fn apply(state: &AppState, press: &ButtonPress) -> OrchestratorResult<()> {
+    let session = state
+        .session_manager
+        .get_session(&press.session_id)
+        .ok_or_else(|| OrchestratorError::SessionNotFound(press.session_id.clone()))?;
+    let request = session
+        .get_pending_approvals()
+        .await
+        .into_iter()
+        .find(|request| request.id == press.approval_id)
+        .ok_or_else(|| OrchestratorError::ApprovalNotFound(press.approval_id.clone()))?;
+    session
+        .submit_approval_response(
+            &press.approval_id,
+            slack::approval_response(press, &request),
+            &press.decided_by,
+        )
+        .await
+}
+
+/// Sends `error` to the user who pressed, without holding up the answer Slack waits for.
+fn reply(slack: &std::sync::Arc<SlackApprovals>, press: &ButtonPress, error: &str) {
+    let Some(response_url) = press.response_url.clone() else {
+        return;
+    };
+    let slack = slack.clone();
+    let text = format!("Could not answer this approval request: {error}");
+    tokio::spawn(async move { slack.reply_privately(&response_url, &text).await });
+}
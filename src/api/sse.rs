@@ -0,0 +1,283 @@
+use crate::api::body_limit::LimitedJson;
+use crate::api::handlers::AppState;
+use crate::api::websocket::{broadcast_frame, submit_input};
+use crate::config::DisconnectQueuePolicy;
+use crate::error::{OrchestratorError, OrchestratorResult};
+use crate::models::{
+    BroadcastEnvelope, BroadcastMessage, SendMessageQuery, SendMessageResponse, Session,
+    WebSocketClient,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::StreamExt;
+use tracing::{debug, info, instrument};
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+/// Header browsers send with the ID of the last event when they reconnect
+const LAST_EVENT_ID: &str = "last-event-id";
+
+/// Query parameters of the session event stream
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SessionEventsParams {
+    /// Opt into `server_received_at` on events
+    #[serde(default)]
+    pub timestamps: bool,
+    /// Opt into Claude's incremental `stream_event` messages
+    #[serde(default)]
+    pub stream: bool,
+    /// Resume after the event with this ID, like a `Last-Event-ID` header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after_seq: Option<u64>,
+}
+
+/// Keeps an event stream client registered with its session for as long as the
+/// response body is alive; dropping it (the client went away) removes the client and
+/// applies the disconnect policy to its unwritten input, as for a WebSocket client.
+struct EventStreamClient {
+    session: Arc<Session>,
+    client_id: String,
+    queue_policy: DisconnectQueuePolicy,
+    forward_task: JoinHandle<()>,
+}
+
+impl Drop for EventStreamClient {
+    fn drop(&mut self) {
+        self.forward_task.abort();
+        let session = self.session.clone();
+        let client_id = std::mem::take(&mut self.client_id);
+        let queue_policy = self.queue_policy;
+        tokio::spawn(async move {
+            session.remove_client(&client_id).await;
+            let affected = session
+                .handle_client_disconnect(&client_id, queue_policy)
+                .await;
+            info!(
+                client_id = %client_id,
+                policy = ?queue_policy,
+                messages = affected,
+                "Event stream client disconnected"
+            );
+        });
+    }
+}
+
+/// The event carrying a broadcast envelope, if the client gets one for it.
+fn envelope_event(
+    session: &Session,
+    envelope: &BroadcastEnvelope,
+    client_id: &str,
+    params: SessionEventsParams,
+) -> Option<Event> {
+    let frame = broadcast_frame(
+        session,
+        &envelope.message,
+        client_id,
+        params.timestamps,
+        params.stream,
+    )?;
+    Some(Event::default().id(envelope.seq.to_string()).data(frame))
+}
+
+/// Streams what the main WebSocket carries as Server-Sent Events: Claude's messages,
+/// every client's input and other clients' presence, one JSON frame per `data` event
+/// with the session-wide sequence number as its ID. The first event, `connected`,
+/// names the client ID to send input under, and a `disconnect` event ends the stream
+/// when the session goes away. A client reconnecting with `Last-Event-ID` (or
+/// `after_seq`) first gets the buffered events it missed.
+///
+/// # Errors
+///
+/// Returns an error if the session is not active.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/{session_id}/events",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session"), SessionEventsParams),
+    responses(
+        (status = 200, description = "Frames of the main WebSocket as Server-Sent Events", body = String, content_type = "text/event-stream"),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[allow(clippy::too_many_lines)]
+#[instrument(skip(state, headers), fields(session_id = %session_id, client_id))]
+pub async fn session_events(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    Query(params): Query<SessionEventsParams>,
+    headers: HeaderMap,
+) -> OrchestratorResult<Response> {
+    let session = state
+        .session_manager
+        .get_session(&session_id)
+        .ok_or_else(|| OrchestratorError::SessionNotFound(session_id.clone()))?;
+    if !session.is_active().await && !session.is_restarting() {
+        return Err(OrchestratorError::ProcessCommunicationError(
+            "Session is not active".into(),
+        ));
+    }
+
+    let client_id = Uuid::new_v4().to_string();
+    tracing::Span::current().record("client_id", &client_id);
+    let after_seq = params.after_seq.or_else(|| {
+        headers
+            .get(LAST_EVENT_ID)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+    });
+    // Subscribe before the client is announced, so no input sent under its ID is missed
+    let (mut broadcast_rx, replay) = match after_seq {
+        Some(after_seq) => {
+            let (broadcast_rx, replay) = session.resume_broadcasts(after_seq);
+            (broadcast_rx, Some((after_seq, replay)))
+        }
+        None => (session.subscribe_to_broadcasts(), None),
+    };
+    session
+        .add_client(WebSocketClient::new(
+            client_id.clone(),
+            "127.0.0.1".to_string(),
+            Some("SSE Client".to_string()),
+        ))
+        .await;
+    info!(session_id = %session_id, client_id = %client_id, "Event stream client connected");
+
+    let (events_tx, events_rx) = mpsc::channel(64);
+    let connected = Event::default()
+        .event("connected")
+        .data(serde_json::json!({ "client_id": client_id }).to_string());
+    let _ = events_tx.send(connected).await;
+    // Sent by the forwarder, since more of them may be waiting than the channel holds
+    let mut missed = Vec::new();
+    if let Some((after_seq, replay)) = replay {
+        info!(
+            client_id = %client_id,
+            after_seq,
+            missed = replay.missed.len(),
+            complete = replay.complete,
+            "Replaying missed events to resuming client"
+        );
+        if !replay.complete {
+            missed.push(
+                Event::default()
+                    .event("replay_gap")
+                    .data(serde_json::json!({ "after_seq": after_seq }).to_string()),
+            );
+        }
+        missed.extend(
+            replay
+                .missed
+                .iter()
+                .filter_map(|envelope| envelope_event(&session, envelope, &client_id, params)),
+        );
+    }
+
+    let forward_session = session.clone();
+    let forward_client_id = client_id.clone();
+    let forward_task = tokio::spawn(async move {
+        for event in missed {
+            if events_tx.send(event).await.is_err() {
+                return;
+            }
+        }
+        while let Ok(envelope) = broadcast_rx.recv().await {
+            if matches!(envelope.message, BroadcastMessage::Disconnect) {
+                let _ = events_tx
+                    .send(Event::default().event("disconnect").data("{}"))
+                    .await;
+                break;
+            }
+            let Some(event) =
+                envelope_event(&forward_session, &envelope, &forward_client_id, params)
+            else {
+                continue;
+            };
+            if events_tx.send(event).await.is_err() {
+                break;
+            }
+        }
+        debug!(client_id = %forward_client_id, "Event stream forwarder finished");
+    });
+
+    let client = EventStreamClient {
+        session,
+        client_id,
+        queue_policy: state.config.disconnect_queue_policy,
+        forward_task,
+    };
+    let events = ReceiverStream::new(events_rx).map(move |event| {
+        let _ = &client;
+        Ok::<_, Infallible>(event)
+    });
+    Ok(Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
+/// Queues a message for Claude like a frame sent over the main WebSocket, and
+/// broadcasts it to the session's clients. Input is attributed to `client_id`, as
+/// announced on the event stream, or to a fresh client ID without one.
+///
+/// # Errors
+///
+/// Returns an error if the session is not active or takes no more input.
+#[utoipa::path(
+    post,
+    path = "/api/v1/sessions/{session_id}/messages",
+    tag = "sessions",
+    params(("session_id" = String, Path, description = "ID of the session"), SendMessageQuery),
+    request_body(content = Object, description = "A message as the main WebSocket takes it"),
+    responses(
+        (status = 202, body = SendMessageResponse),
+        (status = 404, description = "`SESSION_NOT_FOUND`", body = ErrorResponse, content_type = "application/problem+json"),
+    )
+)]
+#[instrument(skip(state, message), fields(session_id = %session_id))]
+pub async fn send_message(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    Query(query): Query<SendMessageQuery>,
+    LimitedJson(message): LimitedJson<serde_json::Value>,
+) -> OrchestratorResult<(StatusCode, Json<SendMessageResponse>)> {
+    let received_at = chrono::Utc::now();
+    let session = state
+        .session_manager
+        .get_session(&session_id)
+        .ok_or_else(|| OrchestratorError::SessionNotFound(session_id.clone()))?;
+    let client_id = query
+        .client_id
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let paced = submit_input(
+        &session,
+        &client_id,
+        &session_id,
+        message.to_string(),
+        received_at,
+    )
+    .await?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(SendMessageResponse {
+            client_id,
+            position: paced.map(|paced| paced.position),
+            estimated_wait_ms: paced
+                .map(|paced| u64::try_from(paced.estimated_wait.as_millis()).unwrap_or(u64::MAX)),
+        }),
+    ))
+}
@@ -1,11 +1,19 @@
+use crate::api::caching;
+use crate::api::handlers::AppState;
+use crate::config::SecurityHeaders;
 use axum::{
     body::Body,
-    extract::Path,
-    http::{header, StatusCode},
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
     response::{IntoResponse, Response},
 };
-use mime_guess::mime;
 use rust_embed::RustEmbed;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tracing::{error, info, warn};
 
 #[derive(RustEmbed)]
 #[folder = "frontend/dist/"]
@@ -13,48 +21,571 @@ use rust_embed::RustEmbed;
 #[exclude = "*.map"]
 pub struct Assets;
 
-/// Serves static files from the embedded frontend assets.
+/// Default policy: everything from our own origin, plus the WebSocket endpoints on the
+/// host the page was loaded from (older browsers don't treat `ws:` as covered by `'self'`).
+fn default_content_security_policy(host: Option<&str>) -> String {
+    let ws_origins = host
+        .filter(|host| {
+            host.chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | ':' | '-' | '[' | ']'))
+        })
+        .map(|host| format!(" ws://{host} wss://{host}"))
+        .unwrap_or_default();
+
+    format!(
+        "default-src 'self'; script-src 'self'; style-src 'self' 'unsafe-inline'; \
+         img-src 'self' data:; font-src 'self' data:; connect-src 'self'{ws_origins}; \
+         object-src 'none'; base-uri 'self'; frame-ancestors 'none'"
+    )
+}
+
+fn apply_security_headers(
+    mut response: Response,
+    config: &SecurityHeaders,
+    request_headers: &HeaderMap,
+) -> Response {
+    let host = request_headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok());
+    let csp = if config.disable_content_security_policy {
+        None
+    } else {
+        Some(
+            config
+                .content_security_policy
+                .clone()
+                .unwrap_or_else(|| default_content_security_policy(host)),
+        )
+    };
+
+    let headers = response.headers_mut();
+    for (name, value) in [
+        (header::CONTENT_SECURITY_POLICY, csp.as_ref()),
+        (header::X_FRAME_OPTIONS, config.frame_options.as_ref()),
+        (header::REFERRER_POLICY, config.referrer_policy.as_ref()),
+        (
+            header::STRICT_TRANSPORT_SECURITY,
+            config.strict_transport_security.as_ref(),
+        ),
+    ] {
+        if let Some(value) = value.and_then(|v| HeaderValue::from_str(v).ok()) {
+            headers.insert(name, value);
+        }
+    }
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+
+    response
+}
+
+/// Every servable frontend asset, keyed by its path relative to the frontend root.
+/// Looking a request up here first means unknown paths never reach the filesystem.
+#[derive(Debug, Default)]
+pub struct StaticIndex {
+    /// Canonical frontend directory assets are read from; the embedded assets when unset
+    root: Option<PathBuf>,
+    entries: HashMap<String, IndexedAsset>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IndexedAsset {
+    mime: String,
+    len: u64,
+    /// Weak entity tag, from the content of embedded assets and from the size and
+    /// modification time of files in a frontend directory
+    etag: String,
+}
+
+impl IndexedAsset {
+    fn new(path: &str, len: u64, etag: String) -> Self {
+        Self {
+            mime: mime_guess::from_path(path)
+                .first_or_octet_stream()
+                .to_string(),
+            len,
+            etag,
+        }
+    }
+}
+
+/// `Cache-Control` of an asset. Vite puts the bundles under `assets/` with a content
+/// hash in their names, so those never change; everything else, `index.html` above all,
+/// is checked with the server before each use.
+fn cache_control(key: &str) -> &'static str {
+    if key.starts_with("assets/") {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    }
+}
+
+impl StaticIndex {
+    /// Indexes the assets compiled into the binary.
+    #[must_use]
+    pub fn embedded() -> Self {
+        let entries = Assets::iter()
+            .filter_map(|path| {
+                let asset = Assets::get(&path)?;
+                let len = asset.data.len() as u64;
+                let etag = caching::digest_tag(&asset.metadata.sha256_hash());
+                Some((path.to_string(), IndexedAsset::new(&path, len, etag)))
+            })
+            .collect();
+        Self {
+            root: None,
+            entries,
+        }
+    }
+
+    /// Indexes the files under `root`. Files that resolve outside of it, e.g. through a
+    /// symlink, are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `root` cannot be resolved.
+    pub fn from_dir(root: &std::path::Path) -> std::io::Result<Self> {
+        let root = std::fs::canonicalize(root)?;
+        let mut entries = HashMap::new();
+        for entry in walkdir::WalkDir::new(&root).follow_links(true) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!(error = %e, "Skipping unreadable frontend path");
+                    continue;
+                }
+            };
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(resolved) = std::fs::canonicalize(entry.path()) else {
+                continue;
+            };
+            if !resolved.starts_with(&root) {
+                warn!(
+                    path = %entry.path().display(),
+                    resolved = %resolved.display(),
+                    "Not serving frontend file that resolves outside the frontend directory"
+                );
+                continue;
+            }
+            let Ok(relative) = entry.path().strip_prefix(&root) else {
+                continue;
+            };
+            let Some(key) = relative
+                .iter()
+                .map(|part| part.to_str())
+                .collect::<Option<Vec<_>>>()
+                .map(|parts| parts.join("/"))
+            else {
+                continue;
+            };
+            let metadata = entry.metadata().ok();
+            let len = metadata.as_ref().map_or(0, std::fs::Metadata::len);
+            let modified = metadata
+                .and_then(|metadata| metadata.modified().ok())
+                .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+                .unwrap_or_default();
+            let etag = format!("W/\"{len:x}-{:x}\"", modified.as_nanos());
+            entries.insert(key.clone(), IndexedAsset::new(&key, len, etag));
+        }
+        Ok(Self {
+            root: Some(root),
+            entries,
+        })
+    }
+
+    fn get(&self, key: &str) -> Option<&IndexedAsset> {
+        self.entries.get(key)
+    }
+
+    /// Reads `range` of an indexed asset.
+    async fn read(&self, key: &str, range: Range<u64>) -> std::io::Result<Vec<u8>> {
+        let Some(root) = &self.root else {
+            let content = Assets::get(key).ok_or(std::io::ErrorKind::NotFound)?;
+            let range = usize::try_from(range.start).unwrap_or(usize::MAX)
+                ..usize::try_from(range.end).unwrap_or(usize::MAX);
+            return content
+                .data
+                .get(range)
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| std::io::ErrorKind::UnexpectedEof.into());
+        };
+
+        // Resolve again, in case the file was swapped for a symlink since indexing
+        let path = tokio::fs::canonicalize(root.join(key)).await?;
+        if !path.starts_with(root) {
+            return Err(std::io::ErrorKind::PermissionDenied.into());
+        }
+        let mut file = tokio::fs::File::open(path).await?;
+        file.seek(std::io::SeekFrom::Start(range.start)).await?;
+        let mut data = vec![0; usize::try_from(range.end - range.start).unwrap_or(0)];
+        file.read_exact(&mut data).await?;
+        Ok(data)
+    }
+}
+
+static INDEX: OnceLock<StaticIndex> = OnceLock::new();
+
+/// Builds the asset index, from `frontend_dir` when given and from the embedded assets
+/// otherwise. Without this the embedded assets are indexed on first request.
 ///
-/// # Panics
+/// # Errors
 ///
-/// Panics if the HTTP response builder fails to construct a response.
-/// This should only happen in extremely rare cases where the HTTP headers
-/// cannot be set properly.
-#[allow(clippy::unused_async)]
-pub async fn serve_static(Path(path): Path<String>) -> impl IntoResponse {
-    let path = if path.is_empty() || path == "/" {
-        "index.html"
-    } else {
-        &path
+/// Returns an error if `frontend_dir` cannot be resolved.
+pub fn init(frontend_dir: Option<&std::path::Path>) -> std::io::Result<()> {
+    let index = match frontend_dir {
+        Some(dir) => StaticIndex::from_dir(dir)?,
+        None => StaticIndex::embedded(),
     };
+    info!(
+        frontend_dir = ?index.root,
+        assets = index.entries.len(),
+        "Indexed frontend assets"
+    );
+    let _ = INDEX.set(index);
+    Ok(())
+}
 
-    match Assets::get(path) {
-        Some(content) => {
-            let mime = mime_guess::from_path(path).first_or_octet_stream();
+fn index() -> &'static StaticIndex {
+    INDEX.get_or_init(StaticIndex::embedded)
+}
 
-            Response::builder()
-                .status(StatusCode::OK)
-                .header(header::CONTENT_TYPE, mime.as_ref())
-                .body(Body::from(content.data.to_vec()))
-                .unwrap()
+/// The index key of a request path, or `None` for paths that try to leave the root.
+fn index_key(path: &str) -> Option<String> {
+    let path = path.trim_start_matches('/');
+    if path.is_empty() {
+        return Some("index.html".to_string());
+    }
+    let safe = path
+        .split('/')
+        .all(|segment| !matches!(segment, "" | "." | "..") && !segment.contains(['\\', '\0']));
+    safe.then(|| path.to_string())
+}
+
+/// What a `Range` header asks of an asset of `len` bytes. Only single byte ranges are
+/// honoured; anything else gets the whole asset.
+#[derive(Debug, PartialEq, Eq)]
+enum RangeRequest {
+    Full,
+    Partial(Range<u64>),
+    Unsatisfiable,
+}
+
+fn parse_range(header: Option<&HeaderValue>, len: u64) -> RangeRequest {
+    let Some(spec) = header
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().strip_prefix("bytes="))
+        .filter(|spec| !spec.contains(','))
+    else {
+        return RangeRequest::Full;
+    };
+    let Some((start, end)) = spec.split_once('-') else {
+        return RangeRequest::Full;
+    };
+    let range = match (start.trim(), end.trim()) {
+        ("", "") => return RangeRequest::Full,
+        // The last `suffix` bytes
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return RangeRequest::Unsatisfiable,
+            Ok(suffix) => len.saturating_sub(suffix)..len,
+            Err(_) => return RangeRequest::Full,
+        },
+        (start, end) => {
+            let Ok(start) = start.parse::<u64>() else {
+                return RangeRequest::Full;
+            };
+            let end = match end {
+                "" => len,
+                end => match end.parse::<u64>() {
+                    Ok(end) if end >= start => (end + 1).min(len),
+                    _ => return RangeRequest::Full,
+                },
+            };
+            start..end
         }
-        None => {
-            // Try to serve index.html for client-side routing
-            match Assets::get("index.html") {
-                Some(content) => Response::builder()
-                    .status(StatusCode::OK)
-                    .header(header::CONTENT_TYPE, mime::TEXT_HTML.as_ref())
-                    .body(Body::from(content.data.to_vec()))
-                    .unwrap(),
-                None => Response::builder()
-                    .status(StatusCode::NOT_FOUND)
-                    .body(Body::from("404 Not Found"))
-                    .unwrap(),
+    };
+    if range.start >= len {
+        RangeRequest::Unsatisfiable
+    } else {
+        RangeRequest::Partial(range)
+    }
+}
+
+fn not_found() -> Response {
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(Body::from("404 Not Found"))
+        .unwrap()
+}
+
+async fn asset_response(
+    index: &StaticIndex,
+    method: &Method,
+    path: &str,
+    request_headers: &HeaderMap,
+) -> Response {
+    let Some(key) = index_key(path) else {
+        warn!(path = %path, "Rejected static file path escaping the frontend root");
+        return not_found();
+    };
+    let (key, asset) = match index.get(&key) {
+        Some(asset) => (key, asset),
+        // Paths that don't name a file are client-side routes of the single page app
+        None if !key.rsplit('/').next().unwrap_or_default().contains('.') => {
+            match index.get("index.html") {
+                Some(asset) => ("index.html".to_string(), asset),
+                None => return not_found(),
             }
         }
+        None => return not_found(),
+    };
+
+    let Ok(etag) = HeaderValue::from_str(&asset.etag) else {
+        return not_found();
+    };
+    if caching::is_fresh(request_headers, &asset.etag) {
+        let mut response = caching::not_modified(&etag);
+        response.headers_mut().insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(cache_control(&key)),
+        );
+        return response;
+    }
+
+    let (status, range) = match parse_range(request_headers.get(header::RANGE), asset.len) {
+        RangeRequest::Full => (StatusCode::OK, 0..asset.len),
+        RangeRequest::Partial(range) => (StatusCode::PARTIAL_CONTENT, range),
+        RangeRequest::Unsatisfiable => {
+            return Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", asset.len))
+                .body(Body::empty())
+                .unwrap();
+        }
+    };
+
+    let mut response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, asset.mime.as_str())
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_LENGTH, range.end - range.start)
+        .header(header::ETAG, etag)
+        .header(header::CACHE_CONTROL, cache_control(&key));
+    if status == StatusCode::PARTIAL_CONTENT {
+        response = response.header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", range.start, range.end - 1, asset.len),
+        );
+    }
+    if method == Method::HEAD {
+        return response.body(Body::empty()).unwrap();
+    }
+
+    match index.read(&key, range).await {
+        Ok(data) => response.body(Body::from(data)).unwrap(),
+        Err(e) => {
+            error!(path = %key, error = %e, "Failed to read indexed frontend asset");
+            not_found()
+        }
     }
 }
 
-pub async fn serve_index() -> impl IntoResponse {
-    serve_static(Path(String::from("index.html"))).await
+/// Serves static files from the frontend asset index, with `HEAD`, single byte range
+/// and `If-None-Match` support.
+///
+/// # Panics
+///
+/// Panics if the HTTP response builder fails to construct a response.
+/// This should only happen in extremely rare cases where the HTTP headers
+/// cannot be set properly.
+pub async fn serve_static(
+    State(state): State<AppState>,
+    method: Method,
+    request_headers: HeaderMap,
+    Path(path): Path<String>,
+) -> impl IntoResponse {
+    apply_security_headers(
+        asset_response(index(), &method, &path, &request_headers).await,
+        &state.config.security_headers,
+        &request_headers,
+    )
+}
+
+pub async fn serve_index(
+    state: State<AppState>,
+    method: Method,
+    request_headers: HeaderMap,
+) -> impl IntoResponse {
+    serve_static(
+        state,
+        method,
+        request_headers,
+        Path(String::from("index.html")),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_headers(host: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_str(host).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_default_security_headers() {
+        let response = apply_security_headers(
+            Response::new(Body::empty()),
+            &SecurityHeaders::default(),
+            &request_headers("vibe.local:3000"),
+        );
+        let headers = response.headers();
+
+        let csp = headers[header::CONTENT_SECURITY_POLICY].to_str().unwrap();
+        assert!(csp.contains("connect-src 'self' ws://vibe.local:3000 wss://vibe.local:3000;"));
+        assert!(csp.contains("frame-ancestors 'none'"));
+        assert_eq!(headers[header::X_FRAME_OPTIONS], "DENY");
+        assert_eq!(headers[header::REFERRER_POLICY], "no-referrer");
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert!(!headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+    }
+
+    #[test]
+    fn test_configured_security_headers() {
+        let config = SecurityHeaders {
+            content_security_policy: Some("default-src 'none'".to_string()),
+            disable_content_security_policy: false,
+            frame_options: None,
+            referrer_policy: Some("same-origin".to_string()),
+            strict_transport_security: Some("max-age=31536000".to_string()),
+        };
+        let response = apply_security_headers(
+            Response::new(Body::empty()),
+            &config,
+            &request_headers("vibe.local"),
+        );
+        let headers = response.headers();
+
+        assert_eq!(
+            headers[header::CONTENT_SECURITY_POLICY],
+            "default-src 'none'"
+        );
+        assert!(!headers.contains_key(header::X_FRAME_OPTIONS));
+        assert_eq!(headers[header::REFERRER_POLICY], "same-origin");
+        assert_eq!(
+            headers[header::STRICT_TRANSPORT_SECURITY],
+            "max-age=31536000"
+        );
+    }
+
+    #[test]
+    fn test_untrusted_host_is_not_embedded_in_policy() {
+        let csp = default_content_security_policy(Some("evil; script-src *"));
+        assert!(csp.contains("connect-src 'self';"));
+        assert!(!csp.contains("evil"));
+    }
+
+    #[test]
+    fn test_index_key_rejects_escapes() {
+        assert_eq!(index_key("").as_deref(), Some("index.html"));
+        assert_eq!(
+            index_key("/assets/app.js").as_deref(),
+            Some("assets/app.js")
+        );
+        assert_eq!(index_key("../etc/passwd"), None);
+        assert_eq!(index_key("assets/../../secret"), None);
+        assert_eq!(index_key("assets//app.js"), None);
+        assert_eq!(index_key("assets\\..\\secret"), None);
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let range = |value: &str| parse_range(Some(&HeaderValue::from_str(value).unwrap()), 100);
+        assert_eq!(parse_range(None, 100), RangeRequest::Full);
+        assert_eq!(range("bytes=0-9"), RangeRequest::Partial(0..10));
+        assert_eq!(range("bytes=90-"), RangeRequest::Partial(90..100));
+        assert_eq!(range("bytes=-5"), RangeRequest::Partial(95..100));
+        assert_eq!(range("bytes=50-500"), RangeRequest::Partial(50..100));
+        assert_eq!(range("bytes=100-"), RangeRequest::Unsatisfiable);
+        assert_eq!(range("bytes=0-1,5-6"), RangeRequest::Full);
+        assert_eq!(range("items=0-1"), RangeRequest::Full);
+        assert_eq!(range("bytes=9-1"), RangeRequest::Full);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_directory_index() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path().join("dist");
+        std::fs::create_dir_all(root.join("assets")).unwrap();
+        std::fs::write(root.join("index.html"), "<html></html>").unwrap();
+        std::fs::write(root.join("assets/app.js.map"), "0123456789").unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("secret.txt"), root.join("leak.txt")).unwrap();
+
+        let index = StaticIndex::from_dir(&root).unwrap();
+        assert!(index.get("assets/app.js.map").is_some());
+        assert!(index.get("leak.txt").is_none());
+
+        let get = |path: &'static str, method: Method, range: Option<&'static str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(range) = range {
+                headers.insert(header::RANGE, HeaderValue::from_static(range));
+            }
+            let index = &index;
+            async move { asset_response(index, &method, path, &headers).await }
+        };
+        let body = |response: Response| async {
+            axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap()
+        };
+
+        let response = get("assets/app.js.map", Method::GET, Some("bytes=2-4")).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 2-4/10");
+        assert_eq!(body(response).await, "234");
+
+        let response = get("assets/app.js.map", Method::HEAD, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "10");
+        assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+        assert!(body(response).await.is_empty());
+
+        let response = get("assets/app.js.map", Method::GET, Some("bytes=10-")).await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+
+        // A client holding the current copy gets 304
+        let response = get("assets/app.js.map", Method::GET, None).await;
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "public, max-age=31536000, immutable"
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::IF_NONE_MATCH,
+            response.headers()[header::ETAG].clone(),
+        );
+        let response = asset_response(&index, &Method::GET, "assets/app.js.map", &headers).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(body(response).await.is_empty());
+        let response = get("index.html", Method::GET, None).await;
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+
+        // Client-side routes get the app, missing files and escapes do not
+        let response = get("sessions/abc", Method::GET, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body(response).await, "<html></html>");
+        for path in ["assets/missing.js", "leak.txt", "../secret.txt"] {
+            assert_eq!(
+                get(path, Method::GET, None).await.status(),
+                StatusCode::NOT_FOUND
+            );
+        }
+    }
 }
@@ -1,41 +1,236 @@
+use crate::api::access_log::AccessLog;
+use crate::api::auth::Caller;
 use crate::api::handlers::AppState;
+use crate::approval_priority::{Priority, PriorityLanes};
+use crate::config::{DisconnectQueuePolicy, Heartbeat};
+use crate::error::OrchestratorResult;
 use crate::models::{
-    ApprovalMessage, ApprovalWebSocketClient, BroadcastMessage, Session, WebSocketClient,
+    ApprovalMessage, ApprovalResponseResult, ApprovalWebSocketClient, BroadcastEnvelope,
+    BroadcastMessage, DeliveryTracker, Liveness, PacedPosition, Session, WebSocketClient,
     WriteMessage,
 };
+use crate::session_manager::SessionManager;
+use crate::slo::Operation;
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        Path, State,
+        Extension, Path, Query, State,
     },
-    response::Response,
+    response::{IntoResponse, Response},
 };
 use futures::{
     sink::SinkExt,
     stream::{SplitSink, SplitStream, StreamExt},
 };
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::{
     sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
     task::JoinHandle,
 };
-use tracing::{debug, error, info, instrument, warn};
+use tracing::{debug, error, info, instrument, warn, Instrument, Span};
 use uuid::Uuid;
 
-#[instrument(skip(ws, state), fields(session_id = %session_id))]
+/// Client frames with this `type` carry ephemeral UI state and are relayed to
+/// the other clients of the session instead of being written to Claude.
+const PRESENCE_MESSAGE_TYPE: &str = "presence";
+
+/// Frames with this `type` carry approval traffic on a main socket opened with
+/// `?channels=all`, as `{"type": "approval", "message": <approval frame>}`.
+const APPROVAL_MESSAGE_TYPE: &str = "approval";
+
+/// Approval client frames with this `action` ask Claude about a pending approval.
+const ASK_CLARIFICATION_ACTION: &str = "ask_clarification";
+
+/// Unacknowledged message count at which a lagging client is reported.
+const ACK_LAG_WARNING_THRESHOLD: u64 = 500;
+
+/// Query parameters of the main WebSocket
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[allow(clippy::struct_excessive_bools)] // Independent opt-ins
+pub struct WebSocketParams {
+    /// Opt into `seq`-tagged outbound messages and `{"ack": seq}` replies
+    #[serde(default)]
+    pub ack: bool,
+    /// Opt into `server_received_at`/`server_sent_at` on outbound messages
+    #[serde(default)]
+    pub timestamps: bool,
+    /// Opt into Claude's incremental `stream_event` messages
+    #[serde(default)]
+    pub stream: bool,
+    /// Which traffic the socket carries besides Claude's messages
+    #[serde(default)]
+    pub channels: WebSocketChannels,
+    /// Opt into `session_seq` on messages relayed from the session's broadcasts
+    #[serde(default)]
+    pub resumable: bool,
+    /// Resume after the message with this `session_seq`: the buffered messages after it
+    /// are sent first. Implies `resumable`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after_seq: Option<u64>,
+}
+
+impl WebSocketParams {
+    /// Whether relayed messages carry their `session_seq`
+    #[must_use]
+    pub const fn tags_session_seq(&self) -> bool {
+        self.resumable || self.after_seq.is_some()
+    }
+}
+
+/// Traffic carried by a main WebSocket connection.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebSocketChannels {
+    /// Claude's messages only; approvals need the approvals endpoint
+    #[default]
+    Claude,
+    /// Claude's messages plus approval requests and answers in approval envelopes
+    All,
+}
+
+/// The approval side of a main socket opened with `?channels=all`.
+struct ApprovalChannel {
+    /// Who answers made over this socket are attributed to
+    identity: String,
+    /// Approval frames written here reach the client wrapped in an approval envelope
+    tx: UnboundedSender<Message>,
+    forward_task: JoinHandle<()>,
+    broadcast_task: JoinHandle<()>,
+    messages_processed: u32,
+}
+
+fn approval_envelope(frame: &str) -> String {
+    // Approval frames are serialized JSON already, so they are embedded as they are
+    format!(r#"{{"type":"{APPROVAL_MESSAGE_TYPE}","message":{frame}}}"#)
+}
+
+/// Registers a main socket as an approval client of the session and starts relaying
+/// approval frames to it, pending ones first.
+async fn open_approval_channel(
+    session: &Arc<Session>,
+    session_id: &str,
+    client_id: &str,
+    identity: String,
+    main_tx: UnboundedSender<Message>,
+) -> ApprovalChannel {
+    session
+        .add_approval_client(ApprovalWebSocketClient::new(
+            client_id.to_string(),
+            "127.0.0.1".to_string(), // In real implementation, get from socket
+            Some("Multiplexed WebSocket Client".to_string()),
+        ))
+        .await;
+    info!(
+        session_id = %session_id,
+        client_id = %client_id,
+        "Multiplexed client added to session as an approval client"
+    );
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+    let forward_task = tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            let Message::Text(frame) = msg else {
+                continue;
+            };
+            if main_tx
+                .send(Message::Text(approval_envelope(&frame)))
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+    let broadcast_task =
+        spawn_approval_broadcast_handler(session.clone(), tx.clone(), client_id.to_string());
+
+    send_pending_approvals(session, &tx, session_id, client_id).await;
+
+    ApprovalChannel {
+        identity,
+        tx,
+        forward_task,
+        broadcast_task,
+        messages_processed: 0,
+    }
+}
+
+/// Timestamps on frames have microseconds, so relay latency within the server shows up.
+fn frame_timestamp(at: chrono::DateTime<chrono::Utc>) -> String {
+    at.to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+}
+
+fn presence_frame(client_id: &str, state: &serde_json::Value) -> String {
+    serde_json::json!({
+        "type": PRESENCE_MESSAGE_TYPE,
+        "client_id": client_id,
+        "state": state,
+    })
+    .to_string()
+}
+
+/// Payload size of `message`, as counted in the access log.
+fn message_len(message: &Message) -> usize {
+    match message {
+        Message::Text(text) => text.len(),
+        Message::Binary(data) | Message::Ping(data) | Message::Pong(data) => data.len(),
+        Message::Close(frame) => frame.as_ref().map_or(0, |frame| frame.reason.len()),
+    }
+}
+
+/// Tells a sender where its message waits while write pacing holds it back.
+fn queued_frame(paced: PacedPosition) -> String {
+    serde_json::json!({
+        "type": "queued",
+        "position": paced.position,
+        "estimated_wait_ms": u64::try_from(paced.estimated_wait.as_millis()).unwrap_or(u64::MAX),
+    })
+    .to_string()
+}
+
+#[instrument(skip(ws, state, caller, access_log), fields(session_id = %session_id, ack = params.ack, timestamps = params.timestamps, stream = params.stream, channels = ?params.channels))]
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
     Path(session_id): Path<String>,
+    Query(params): Query<WebSocketParams>,
     State(state): State<AppState>,
+    caller: Caller,
+    access_log: Option<Extension<AccessLog>>,
 ) -> Response {
     info!(session_id = %session_id, "WebSocket upgrade request");
-    ws.on_upgrade(move |socket| handle_websocket(socket, session_id, state))
+    if let Err(e) = state.session_manager.check_accepting() {
+        return e.into_response();
+    }
+    // Approval answers are attributed to whoever opened the connection
+    let approval_identity = match params.channels {
+        WebSocketChannels::Claude => None,
+        WebSocketChannels::All => match caller.identity() {
+            Ok(identity) => Some(identity),
+            Err(e) => return e.into_response(),
+        },
+    };
+    let access_log = access_log.map(|Extension(log)| log);
+    let span = Span::current();
+    ws.on_upgrade(move |socket| {
+        span.in_scope(|| {
+            handle_websocket(
+                socket,
+                session_id,
+                state,
+                params,
+                approval_identity,
+                access_log,
+            )
+        })
+    })
 }
 
 #[instrument(skip(_session), fields(session_id = %session_id, client_id))]
 fn setup_client_connection(
     session_id: &str,
     _session: &Arc<crate::models::Session>,
+    ack: bool,
 ) -> (String, WebSocketClient) {
     // Generate unique client ID
     let client_id = Uuid::new_v4().to_string();
@@ -53,6 +248,11 @@ fn setup_client_connection(
         "127.0.0.1".to_string(), // In real implementation, get from socket
         Some("WebSocket Client".to_string()),
     );
+    let client = if ack {
+        client.with_delivery_tracking()
+    } else {
+        client
+    };
 
     info!(
         session_id = %session_id,
@@ -64,20 +264,149 @@ fn setup_client_connection(
     (client_id, client)
 }
 
-#[instrument(skip(sender, rx), fields(client_id = %client_id))]
+/// Adds the next `seq` (with delivery tracking) and `server_sent_at` (with timestamps)
+/// to a JSON object frame; other payloads pass through untouched.
+fn tag_outgoing(
+    text: String,
+    delivery: Option<&DeliveryTracker>,
+    timestamps: bool,
+    client_id: &str,
+) -> String {
+    let Ok(serde_json::Value::Object(mut object)) = serde_json::from_str(&text) else {
+        return text;
+    };
+
+    if let Some(delivery) = delivery {
+        let seq = delivery.next_seq();
+        object.insert("seq".to_string(), seq.into());
+
+        if delivery.lag() == ACK_LAG_WARNING_THRESHOLD {
+            warn!(
+                client_id = %client_id,
+                lag = ACK_LAG_WARNING_THRESHOLD,
+                "Client is falling behind on message acknowledgements"
+            );
+        }
+    }
+    if timestamps {
+        object.insert(
+            "server_sent_at".to_string(),
+            frame_timestamp(chrono::Utc::now()).into(),
+        );
+    }
+
+    serde_json::Value::Object(object).to_string()
+}
+
+/// Pings the client every `interval` and returns once it has left `max_missed_pongs`
+/// pings in a row unanswered. Never returns with pings off.
+async fn heartbeat(
+    tx: UnboundedSender<Message>,
+    liveness: Arc<Liveness>,
+    config: Heartbeat,
+    client_id: String,
+) {
+    let Some(interval) = config.interval else {
+        return std::future::pending().await;
+    };
+    let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+    loop {
+        ticks.tick().await;
+        let unanswered = liveness.unanswered_pings();
+        if unanswered >= config.max_missed_pongs {
+            warn!(
+                client_id = %client_id,
+                unanswered,
+                last_seen = %liveness.last_seen(),
+                "Client stopped answering pings, disconnecting it"
+            );
+            return;
+        }
+        if tx.send(Message::Ping(Vec::new())).is_err() {
+            // The connection is going away by itself
+            return std::future::pending().await;
+        }
+        liveness.pinged();
+    }
+}
+
+/// Frames the client sends, each marking it as seen, until the heartbeat finds it gone.
+fn watch_liveness(
+    receiver: SplitStream<WebSocket>,
+    tx: UnboundedSender<Message>,
+    liveness: Arc<Liveness>,
+    config: Heartbeat,
+    client_id: &str,
+) -> impl futures::Stream<Item = Result<Message, axum::Error>> + Unpin {
+    let seen = liveness.clone();
+    receiver
+        .inspect(move |_| seen.seen())
+        .take_until(Box::pin(heartbeat(
+            tx,
+            liveness,
+            config,
+            client_id.to_string(),
+        )))
+}
+
+/// Adds the session-wide sequence number of a relayed message to a JSON object frame.
+fn stamp_session_seq(text: String, seq: u64) -> String {
+    let Ok(serde_json::Value::Object(mut object)) = serde_json::from_str(&text) else {
+        return text;
+    };
+    object.insert("session_seq".to_string(), seq.into());
+    serde_json::Value::Object(object).to_string()
+}
+
+/// Tells a resuming client that messages after `after_seq` are no longer buffered (or
+/// never existed), so it has to reload the transcript to be sure it has everything.
+fn replay_gap_frame(after_seq: u64) -> String {
+    serde_json::json!({
+        "type": "replay_gap",
+        "after_seq": after_seq,
+    })
+    .to_string()
+}
+
+/// Adds when the server got a relayed message to a JSON object frame.
+fn stamp_received(text: &str, received_at: chrono::DateTime<chrono::Utc>) -> String {
+    let Ok(serde_json::Value::Object(mut object)) = serde_json::from_str(text) else {
+        return text.to_string();
+    };
+    object.insert(
+        "server_received_at".to_string(),
+        frame_timestamp(received_at).into(),
+    );
+    serde_json::Value::Object(object).to_string()
+}
+
+#[instrument(skip(sender, rx, delivery, access_log), fields(client_id = %client_id))]
 fn spawn_outgoing_message_handler(
     mut sender: futures::stream::SplitSink<WebSocket, Message>,
     mut rx: tokio::sync::mpsc::UnboundedReceiver<Message>,
     client_id: String,
+    delivery: Option<Arc<DeliveryTracker>>,
+    timestamps: bool,
+    access_log: Option<AccessLog>,
 ) -> tokio::task::JoinHandle<()> {
     debug!(client_id = %client_id, "Spawning outgoing message handler");
 
     tokio::spawn(async move {
         let mut messages_sent = 0;
         while let Some(msg) = rx.recv().await {
+            let msg = match msg {
+                Message::Text(text) if delivery.is_some() || timestamps => Message::Text(
+                    tag_outgoing(text, delivery.as_deref(), timestamps, &client_id),
+                ),
+                msg => msg,
+            };
+            let bytes = message_len(&msg);
             match sender.send(msg).await {
                 Ok(()) => {
                     messages_sent += 1;
+                    if let Some(log) = &access_log {
+                        log.add_bytes(bytes);
+                    }
                     debug!(
                         client_id = %client_id,
                         messages_sent = messages_sent,
@@ -103,68 +432,128 @@ fn spawn_outgoing_message_handler(
     })
 }
 
+/// The frame a client of `session` gets for a broadcast, if any: Claude's messages
+/// (its `stream_event`s only with `stream`), every client's input and the presence of
+/// other clients. `Disconnect` has no frame; clients close on it.
+pub(crate) fn broadcast_frame(
+    session: &Session,
+    broadcast_msg: &BroadcastMessage,
+    client_id: &str,
+    timestamps: bool,
+    stream: bool,
+) -> Option<String> {
+    match broadcast_msg {
+        BroadcastMessage::ClaudeOutput { line, received_at } => {
+            debug!(
+                client_id = %client_id,
+                content_length = line.len(),
+                "Received Claude output to broadcast"
+            );
+            if let (Some(slo), Ok(latency)) =
+                (session.slo(), (chrono::Utc::now() - *received_at).to_std())
+            {
+                slo.record(Operation::BroadcastFanOut, latency);
+            }
+            Some(if timestamps {
+                stamp_received(line, *received_at)
+            } else {
+                line.to_string()
+            })
+        }
+        BroadcastMessage::StreamEvent { line, received_at } => stream.then(|| {
+            if timestamps {
+                stamp_received(line, *received_at)
+            } else {
+                line.to_string()
+            }
+        }),
+        BroadcastMessage::ClientInput {
+            content,
+            sender_client_id,
+            received_at,
+        } => {
+            // Send to ALL clients (including the sender)
+            debug!(
+                client_id = %client_id,
+                sender_client_id = %sender_client_id,
+                content_length = content.len(),
+                "Received client input to broadcast to all clients"
+            );
+            Some(if timestamps {
+                stamp_received(content, *received_at)
+            } else {
+                content.to_string()
+            })
+        }
+        BroadcastMessage::Presence {
+            client_id: sender_client_id,
+            state,
+        } => {
+            // Relay to everyone except the client it describes
+            if sender_client_id == client_id {
+                None
+            } else {
+                Some(presence_frame(sender_client_id, state))
+            }
+        }
+        BroadcastMessage::Disconnect => None,
+    }
+}
+
+/// The frame a client connected with `params` gets for a broadcast envelope, if any.
+fn client_frame(
+    session: &Session,
+    envelope: &BroadcastEnvelope,
+    client_id: &str,
+    params: WebSocketParams,
+) -> Option<String> {
+    let frame = broadcast_frame(
+        session,
+        &envelope.message,
+        client_id,
+        params.timestamps,
+        params.stream,
+    )?;
+    Some(if params.tags_session_seq() {
+        stamp_session_seq(frame, envelope.seq)
+    } else {
+        frame
+    })
+}
+
 fn spawn_broadcast_handler(
     session: Arc<crate::models::Session>,
+    mut broadcast_rx: tokio::sync::broadcast::Receiver<BroadcastEnvelope>,
     tx: tokio::sync::mpsc::UnboundedSender<Message>,
     client_id: String,
+    params: WebSocketParams,
 ) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
-        let mut broadcast_rx = session.subscribe_to_broadcasts();
         debug!(
             client_id = %client_id,
             "Started broadcast receiver for WebSocket client"
         );
 
-        while let Ok(broadcast_msg) = broadcast_rx.recv().await {
-            let should_send_message = match &broadcast_msg {
-                BroadcastMessage::ClaudeOutput(content) => {
-                    debug!(
-                        client_id = %client_id,
-                        content_length = content.len(),
-                        "Received Claude output to broadcast"
-                    );
-                    Some(content.clone())
-                }
-                BroadcastMessage::ClientInput {
-                    content,
-                    sender_client_id,
-                } => {
-                    // Send to ALL clients (including the sender)
-                    debug!(
-                        client_id = %client_id,
-                        sender_client_id = %sender_client_id,
-                        content_length = content.len(),
-                        "Received client input to broadcast to all clients"
-                    );
-                    Some(content.clone())
-                }
-                BroadcastMessage::Disconnect => {
-                    info!(
-                        client_id = %client_id,
-                        "Received disconnect signal, closing WebSocket"
-                    );
-                    // Send close message and break
-                    let _ = tx.send(Message::Close(None));
-                    break;
-                }
-            };
+        while let Ok(envelope) = broadcast_rx.recv().await {
+            if matches!(envelope.message, BroadcastMessage::Disconnect) {
+                info!(
+                    client_id = %client_id,
+                    "Received disconnect signal, closing WebSocket"
+                );
+                // Send close message and break
+                let _ = tx.send(Message::Close(None));
+                break;
+            }
+            let should_send_message = client_frame(&session, &envelope, &client_id, params);
 
+            // No membership check per message: cleanup aborts this task when the client
+            // leaves, and the send fails once its outgoing handler has stopped
             if let Some(message_content) = should_send_message {
-                // Check if this client is still connected to the session
-                let clients = session.get_clients().await;
-                if clients.iter().any(|c| c.id == client_id) {
-                    if let Err(e) = tx.send(Message::Text(message_content)) {
-                        warn!(
-                            client_id = %client_id,
-                            error = %e,
-                            "Failed to send message to WebSocket client, stopping broadcast handler"
-                        );
-                        break;
-                    }
-                } else {
-                    info!(
+                if let Err(e) = tx.send(Message::Text(message_content)) {
+                    warn!(
                         client_id = %client_id,
-                        "Client no longer connected to session, stopping broadcast handler"
+                        error = %e,
+                        "Failed to send message to WebSocket client, stopping broadcast handler"
                     );
                     break;
                 }
@@ -178,14 +567,18 @@ fn spawn_broadcast_handler(
     })
 }
 
-#[instrument(skip(session, state), fields(client_id = %client_id, session_id = %session_id, message_len = text.len()))]
+#[allow(clippy::too_many_lines, clippy::too_many_arguments)]
+#[instrument(skip(session, tx, delivery, approvals), fields(client_id = %client_id, session_id = %session_id, message_len = text.len()))]
 async fn handle_text_message(
     text: String,
     client_id: &str,
     session_id: &str,
     session: Arc<crate::models::Session>,
-    state: AppState,
+    tx: &mpsc::UnboundedSender<Message>,
+    delivery: Option<&DeliveryTracker>,
+    approvals: Option<&mut ApprovalChannel>,
 ) {
+    let received_at = chrono::Utc::now();
     debug!(
         client_id = %client_id,
         session_id = %session_id,
@@ -194,16 +587,19 @@ async fn handle_text_message(
     );
 
     // Validate JSON
-    if let Err(e) = serde_json::from_str::<serde_json::Value>(&text) {
-        error!(
-            client_id = %client_id,
-            session_id = %session_id,
-            message_content = %text,
-            error = %e,
-            "Received invalid JSON from WebSocket client"
-        );
-        return;
-    }
+    let value = match serde_json::from_str::<serde_json::Value>(&text) {
+        Ok(value) => value,
+        Err(e) => {
+            error!(
+                client_id = %client_id,
+                session_id = %session_id,
+                message_content = %text,
+                error = %e,
+                "Received invalid JSON from WebSocket client"
+            );
+            return;
+        }
+    };
 
     debug!(
         client_id = %client_id,
@@ -211,6 +607,63 @@ async fn handle_text_message(
         "JSON validation passed"
     );
 
+    if delivery.is_some_and(|delivery| handle_ack(&value, delivery, client_id, session_id)) {
+        return;
+    }
+
+    if value.get("type").and_then(serde_json::Value::as_str) == Some(PRESENCE_MESSAGE_TYPE) {
+        let state = value
+            .get("state")
+            .cloned()
+            .unwrap_or(serde_json::Value::Null);
+        handle_presence_update(client_id, session_id, &session, state).await;
+        return;
+    }
+
+    if let Some(approvals) = approvals {
+        if value.get("type").and_then(serde_json::Value::as_str) == Some(APPROVAL_MESSAGE_TYPE) {
+            let message = value
+                .get("message")
+                .cloned()
+                .unwrap_or(serde_json::Value::Null);
+            approvals.messages_processed += 1;
+            handle_approval_text_message(
+                message.to_string(),
+                client_id,
+                session_id,
+                session,
+                &approvals.identity,
+                &approvals.tx,
+            )
+            .await;
+            return;
+        }
+    }
+
+    let Ok(paced) = submit_input(&session, client_id, session_id, text, received_at).await else {
+        return;
+    };
+
+    // Tell the sender its message waits for write pacing, rather than leave it guessing
+    if let Some(paced) = paced {
+        let _ = tx.send(Message::Text(queued_frame(paced)));
+    }
+}
+
+/// Queues `text` from `client_id` for Claude and broadcasts it to the session's
+/// clients. Returns where it waits if write pacing holds it back.
+///
+/// # Errors
+///
+/// Returns an error if the session takes no input.
+#[instrument(skip(session, text), fields(client_id = %client_id, session_id = %session_id))]
+pub(crate) async fn submit_input(
+    session: &Session,
+    client_id: &str,
+    session_id: &str,
+    text: String,
+    received_at: chrono::DateTime<chrono::Utc>,
+) -> OrchestratorResult<Option<PacedPosition>> {
     // Create write message
     let write_msg = WriteMessage {
         content: text.clone(),
@@ -225,62 +678,113 @@ async fn handle_text_message(
     );
 
     // Enqueue message for Claude
-    if let Err(e) = state
-        .session_manager
-        .enqueue_message(session_id, write_msg)
-        .await
-    {
-        error!(
+    let paced = match SessionManager::enqueue_to(session, write_msg).await {
+        Ok(paced) => paced,
+        Err(e) => {
+            error!(
+                client_id = %client_id,
+                session_id = %session_id,
+                error = %e,
+                "Failed to enqueue message for Claude processing"
+            );
+            return Err(e);
+        }
+    };
+
+    info!(
+        client_id = %client_id,
+        session_id = %session_id,
+        "Message successfully enqueued for Claude"
+    );
+
+    if let Some(paced) = paced {
+        debug!(
+            client_id = %client_id,
+            session_id = %session_id,
+            position = paced.position,
+            "Message held back by write pacing"
+        );
+    }
+
+    // Broadcast to ALL clients (including the sender) using session broadcast; also
+    // when none is connected, so clients that resume later can replay it
+    let broadcast_msg = BroadcastMessage::ClientInput {
+        content: text.as_str().into(),
+        sender_client_id: client_id.to_string(),
+        received_at,
+    };
+    if let Err(e) = session.broadcast_message(broadcast_msg) {
+        debug!(
             client_id = %client_id,
             session_id = %session_id,
             error = %e,
-            "Failed to enqueue message for Claude processing"
+            "No clients to broadcast client input to"
+        );
+    } else {
+        debug!(
+            client_id = %client_id,
+            session_id = %session_id,
+            "Successfully broadcast client input to all clients"
         );
-        return;
     }
 
-    info!(
+    Ok(paced)
+}
+
+/// Consumes `{"ack": seq}` frames; returns false for anything else.
+fn handle_ack(
+    value: &serde_json::Value,
+    delivery: &DeliveryTracker,
+    client_id: &str,
+    session_id: &str,
+) -> bool {
+    let ack = value
+        .as_object()
+        .filter(|object| object.len() == 1)
+        .and_then(|object| object.get("ack"))
+        .and_then(serde_json::Value::as_u64);
+    let Some(seq) = ack else {
+        return false;
+    };
+
+    delivery.ack(seq);
+    debug!(
         client_id = %client_id,
         session_id = %session_id,
-        "Message successfully enqueued for Claude"
+        seq = seq,
+        lag = delivery.lag(),
+        "Client acknowledged messages"
     );
+    true
+}
 
-    // Broadcast to ALL clients (including the sender) using session broadcast
-    let clients = session.get_clients().await;
-    let other_clients_count = clients.len();
-
+async fn handle_presence_update(
+    client_id: &str,
+    session_id: &str,
+    session: &Session,
+    state: serde_json::Value,
+) {
     debug!(
         client_id = %client_id,
         session_id = %session_id,
-        total_clients = clients.len(),
-        "Broadcasting client input to all clients"
+        "Relaying client presence update"
     );
 
-    if other_clients_count > 0 {
-        let broadcast_msg = BroadcastMessage::ClientInput {
-            content: text.clone(),
-            sender_client_id: client_id.to_string(),
-        };
-
-        if let Err(e) = session.broadcast_message(broadcast_msg) {
-            warn!(
-                client_id = %client_id,
-                session_id = %session_id,
-                error = %e,
-                "Failed to broadcast client input to other clients"
-            );
-        } else {
-            debug!(
-                client_id = %client_id,
-                session_id = %session_id,
-                "Successfully broadcast client input to all clients"
-            );
-        }
+    if state.is_null() {
+        session.remove_presence(client_id).await;
     } else {
-        debug!(
+        session.set_presence(client_id, state.clone()).await;
+    }
+
+    if let Err(e) = session.broadcast_message(BroadcastMessage::Presence {
+        client_id: client_id.to_string(),
+        state,
+    }) {
+        warn!(
             client_id = %client_id,
             session_id = %session_id,
-            "No clients to broadcast to"
+            error = %e,
+            "Failed to broadcast presence update"
         );
     }
 }
@@ -291,6 +795,7 @@ fn cleanup_client_connection(
     session: Arc<crate::models::Session>,
     client_id: &str,
     session_id: &str,
+    queue_policy: DisconnectQueuePolicy,
     send_task: tokio::task::JoinHandle<()>,
     broadcast_task: tokio::task::JoinHandle<()>,
 ) {
@@ -311,6 +816,29 @@ fn cleanup_client_connection(
             "Removing client from session"
         );
         session_cleanup.remove_client(&client_id_cleanup).await;
+        let affected = session_cleanup
+            .handle_client_disconnect(&client_id_cleanup, queue_policy)
+            .await;
+        if affected > 0 {
+            info!(
+                client_id = %client_id_cleanup,
+                session_id = %session_id_cleanup,
+                policy = ?queue_policy,
+                messages = affected,
+                "Applied disconnect policy to unwritten client messages"
+            );
+        }
+        if session_cleanup
+            .remove_presence(&client_id_cleanup)
+            .await
+            .is_some()
+        {
+            // Let the remaining clients drop this client's cursor
+            let _ = session_cleanup.broadcast_message(BroadcastMessage::Presence {
+                client_id: client_id_cleanup.clone(),
+                state: serde_json::Value::Null,
+            });
+        }
         debug!(
             client_id = %client_id_cleanup,
             session_id = %session_id_cleanup,
@@ -335,8 +863,15 @@ fn cleanup_client_connection(
 }
 
 #[allow(clippy::too_many_lines)]
-#[instrument(skip(socket, state), fields(session_id = %session_id, client_id))]
-async fn handle_websocket(socket: WebSocket, session_id: String, state: AppState) {
+#[instrument(skip(socket, state, access_log), fields(session_id = %session_id, client_id))]
+async fn handle_websocket(
+    socket: WebSocket,
+    session_id: String,
+    state: AppState,
+    params: WebSocketParams,
+    approval_identity: Option<String>,
+    access_log: Option<AccessLog>,
+) {
     info!(session_id = %session_id, "Starting WebSocket connection handling");
 
     // Get session
@@ -349,8 +884,8 @@ async fn handle_websocket(socket: WebSocket, session_id: String, state: AppState
 
     debug!(session_id = %session_id, "Session found, checking if active");
 
-    // Check if session is active
-    if !session.is_active().await {
+    // Check if session is active; a restarting one comes back shortly
+    if !session.is_active().await && !session.is_restarting() {
         error!(session_id = %session_id, "WebSocket connection rejected: session not active");
         // Close the WebSocket connection immediately
         let _ = socket.close().await;
@@ -360,8 +895,10 @@ async fn handle_websocket(socket: WebSocket, session_id: String, state: AppState
     debug!(session_id = %session_id, "Session is active, proceeding with connection");
 
     // Setup client connection
-    let (client_id, client) = setup_client_connection(&session_id, &session);
+    let (client_id, client) = setup_client_connection(&session_id, &session, params.ack);
     tracing::Span::current().record("client_id", &client_id);
+    let delivery = client.delivery.clone();
+    let liveness = client.liveness.clone();
 
     session.add_client(client).await;
     info!(
@@ -371,7 +908,7 @@ async fn handle_websocket(socket: WebSocket, session_id: String, state: AppState
     );
 
     // Split socket into sender and receiver
-    let (sender, mut receiver) = socket.split();
+    let (sender, receiver) = socket.split();
     debug!(
         session_id = %session_id,
         client_id = %client_id,
@@ -386,9 +923,54 @@ async fn handle_websocket(socket: WebSocket, session_id: String, state: AppState
         "Communication channels created"
     );
 
+    let mut receiver = watch_liveness(
+        receiver,
+        tx.clone(),
+        liveness,
+        state.config.heartbeat,
+        &client_id,
+    );
+
     // Spawn background tasks
-    let send_task = spawn_outgoing_message_handler(sender, rx, client_id.clone());
-    let broadcast_task = spawn_broadcast_handler(session.clone(), tx.clone(), client_id.clone());
+    let send_task = spawn_outgoing_message_handler(
+        sender,
+        rx,
+        client_id.clone(),
+        delivery.clone(),
+        params.timestamps,
+        access_log,
+    );
+    // Messages the client missed go out before any new ones; none fall in between
+    let broadcast_rx = match params.after_seq {
+        Some(after_seq) => {
+            let (broadcast_rx, replay) = session.resume_broadcasts(after_seq);
+            info!(
+                session_id = %session_id,
+                client_id = %client_id,
+                after_seq,
+                missed = replay.missed.len(),
+                complete = replay.complete,
+                "Replaying missed messages to resuming client"
+            );
+            if !replay.complete {
+                let _ = tx.send(Message::Text(replay_gap_frame(after_seq)));
+            }
+            for envelope in &replay.missed {
+                if let Some(frame) = client_frame(&session, envelope, &client_id, params) {
+                    let _ = tx.send(Message::Text(frame));
+                }
+            }
+            broadcast_rx
+        }
+        None => session.subscribe_to_broadcasts(),
+    };
+    let broadcast_task = spawn_broadcast_handler(
+        session.clone(),
+        broadcast_rx,
+        tx.clone(),
+        client_id.clone(),
+        params,
+    );
 
     debug!(
         session_id = %session_id,
@@ -396,6 +978,18 @@ async fn handle_websocket(socket: WebSocket, session_id: String, state: AppState
         "Background tasks spawned"
     );
 
+    // Bring the new client up to date with everyone else's presence
+    for (other_client_id, state) in session.get_presence().await {
+        let _ = tx.send(Message::Text(presence_frame(&other_client_id, &state)));
+    }
+
+    let mut approvals = match approval_identity {
+        Some(identity) => Some(
+            open_approval_channel(&session, &session_id, &client_id, identity, tx.clone()).await,
+        ),
+        None => None,
+    };
+
     // Handle incoming messages from this WebSocket client
     let client_id_recv = client_id.clone();
     info!(
@@ -420,7 +1014,9 @@ async fn handle_websocket(socket: WebSocket, session_id: String, state: AppState
                     &client_id_recv,
                     &session_id,
                     session.clone(),
-                    state.clone(),
+                    &tx,
+                    delivery.as_deref(),
+                    approvals.as_mut(),
                 )
                 .await;
             }
@@ -485,18 +1081,52 @@ async fn handle_websocket(socket: WebSocket, session_id: String, state: AppState
         "Message processing loop ended"
     );
 
-    cleanup_client_connection(session, &client_id, &session_id, send_task, broadcast_task);
+    if let Some(approvals) = approvals {
+        cleanup_approval_connection(
+            state.session_manager.clone(),
+            session.clone(),
+            &client_id,
+            &session_id,
+            approvals.forward_task,
+            approvals.broadcast_task,
+            approvals.messages_processed,
+        )
+        .await;
+    }
+
+    cleanup_client_connection(
+        session,
+        &client_id,
+        &session_id,
+        state.config.disconnect_queue_policy,
+        send_task,
+        broadcast_task,
+    );
 }
 
 /// Approval WebSocket handler
-#[instrument(skip(ws, state), fields(session_id = %session_id))]
+#[instrument(skip(ws, state, caller, access_log), fields(session_id = %session_id))]
 pub async fn approval_websocket_handler(
     ws: WebSocketUpgrade,
     Path(session_id): Path<String>,
     State(state): State<AppState>,
+    caller: Caller,
+    access_log: Option<Extension<AccessLog>>,
 ) -> Response {
     info!(session_id = %session_id, "Approval WebSocket upgrade request");
-    ws.on_upgrade(move |socket| handle_approval_websocket(socket, session_id, state))
+    if let Err(e) = state.session_manager.check_accepting() {
+        return e.into_response();
+    }
+    // Answers are attributed to whoever opened the connection
+    let identity = match caller.identity() {
+        Ok(identity) => identity,
+        Err(e) => return e.into_response(),
+    };
+    let access_log = access_log.map(|Extension(log)| log);
+    let span = Span::current();
+    ws.on_upgrade(move |socket| {
+        span.in_scope(|| handle_approval_websocket(socket, session_id, state, identity, access_log))
+    })
 }
 
 #[instrument(skip(_session), fields(session_id = %session_id, client_id))]
@@ -531,20 +1161,25 @@ fn setup_approval_client_connection(
     (client_id, client)
 }
 
-#[instrument(skip(sender, rx), fields(client_id = %client_id))]
+#[instrument(skip(sender, rx, access_log), fields(client_id = %client_id))]
 fn spawn_approval_outgoing_message_handler(
     mut sender: futures::stream::SplitSink<WebSocket, Message>,
     mut rx: tokio::sync::mpsc::UnboundedReceiver<Message>,
     client_id: String,
+    access_log: Option<AccessLog>,
 ) -> tokio::task::JoinHandle<()> {
     debug!(client_id = %client_id, "Spawning approval outgoing message handler");
 
     tokio::spawn(async move {
         let mut messages_sent = 0;
         while let Some(msg) = rx.recv().await {
+            let bytes = message_len(&msg);
             match sender.send(msg).await {
                 Ok(()) => {
                     messages_sent += 1;
+                    if let Some(log) = &access_log {
+                        log.add_bytes(bytes);
+                    }
                     debug!(
                         client_id = %client_id,
                         messages_sent = messages_sent,
@@ -581,55 +1216,54 @@ fn spawn_approval_broadcast_handler(
             client_id = %client_id,
             "Started approval broadcast receiver for WebSocket client"
         );
-
-        while let Ok(broadcast_msg) = broadcast_rx.recv().await {
-            let message_json = match &broadcast_msg {
-                ApprovalMessage::ApprovalRequest(request) => {
-                    debug!(
-                        client_id = %client_id,
-                        approval_id = %request.id,
-                        "Received approval request to broadcast with new simplified format"
-                    );
-                    match serde_json::to_string(&serde_json::json!({
-                        "id": request.id,
-                        "request": request.request,  // Pass through raw Claude request
-                        "created_at": request.created_at.duration_since(std::time::UNIX_EPOCH)
-                            .unwrap_or_default().as_secs()
-                    })) {
-                        Ok(json) => json,
-                        Err(e) => {
-                            error!(
-                                client_id = %client_id,
-                                error = %e,
-                                "Failed to serialize approval request"
-                            );
-                            continue;
-                        }
+        let batch_window = session
+            .approval_priority()
+            .map(|priority| priority.batch_window)
+            .unwrap_or_default();
+        let mut lanes = PriorityLanes::new(batch_window);
+
+        loop {
+            let batch_due = lanes.due();
+            tokio::select! {
+                broadcast_msg = broadcast_rx.recv() => match broadcast_msg {
+                    Ok(ApprovalMessage::ApprovalRequest { request, frame }) => {
+                        debug!(
+                            client_id = %client_id,
+                            approval_id = %request.id,
+                            priority = ?request.priority,
+                            "Received approval request to broadcast with new simplified format"
+                        );
+                        lanes.push(request.priority, (request, frame), Instant::now());
                     }
-                }
-                ApprovalMessage::ApprovalResponse { .. } => {
                     // Approval responses are not broadcast to clients, only processed internally
+                    Ok(ApprovalMessage::ApprovalResponse { .. }) => continue,
+                    Err(_) => break,
+                },
+                () = tokio::time::sleep_until(batch_due.unwrap_or_else(Instant::now).into()),
+                    if batch_due.is_some() => {}
+            }
+
+            while let Some((request, frame)) = lanes.pop_ready(Instant::now()) {
+                // Held requests may have been answered over HTTP in the meantime
+                if request.priority == Priority::Normal
+                    && !batch_window.is_zero()
+                    && !session
+                        .pending_approvals
+                        .lock()
+                        .await
+                        .contains_key(&request.id)
+                {
                     continue;
                 }
-            };
-
-            // Check if this client is still connected to the session
-            let clients = session.get_approval_clients().await;
-            if clients.iter().any(|c| c.id == client_id) {
-                if let Err(e) = tx.send(Message::Text(message_json)) {
+                // Serialized once by the broadcaster; cleanup aborts this task when the client leaves
+                if let Err(e) = tx.send(Message::Text(frame.to_string())) {
                     warn!(
                         client_id = %client_id,
                         error = %e,
                         "Failed to send approval message to WebSocket client, stopping broadcast handler"
                     );
-                    break;
+                    return;
                 }
-            } else {
-                info!(
-                    client_id = %client_id,
-                    "Approval client no longer connected to session, stopping broadcast handler"
-                );
-                break;
             }
         }
 
@@ -640,13 +1274,14 @@ fn spawn_approval_broadcast_handler(
     })
 }
 
-#[instrument(skip(session, _state), fields(client_id = %client_id, session_id = %session_id))]
+#[instrument(skip(session, tx), fields(client_id = %client_id, session_id = %session_id))]
 async fn handle_approval_text_message(
     text: String,
     client_id: &str,
     session_id: &str,
     session: Arc<crate::models::Session>,
-    _state: AppState,
+    decided_by: &str,
+    tx: &tokio::sync::mpsc::UnboundedSender<Message>,
 ) {
     debug!(
         client_id = %client_id,
@@ -670,9 +1305,55 @@ async fn handle_approval_text_message(
         }
     };
 
+    // A batch of responses: [{id: "...", response: {...}}, ...], answered with per-item results
+    if let serde_json::Value::Array(responses) = parsed {
+        let count = responses.len();
+        let results = session
+            .submit_approval_responses(responses, decided_by)
+            .await;
+        info!(
+            client_id = %client_id,
+            session_id = %session_id,
+            count,
+            accepted = results.iter().filter(|r| r.accepted).count(),
+            "Processed bulk approval response"
+        );
+        send_approval_results(tx, client_id, &results);
+        return;
+    }
+
+    if parsed.get("action").and_then(serde_json::Value::as_str) == Some(ASK_CLARIFICATION_ACTION) {
+        let result = ask_clarification(&parsed, client_id, &session, decided_by).await;
+        send_approval_results(tx, client_id, &[result]);
+        return;
+    }
+
     // Check if this has the expected new format: {id: "...", response: {...}}
-    if parsed.get("id").is_some() && parsed.get("response").is_some() {
-        let approval_response = ApprovalMessage::ApprovalResponse(parsed.clone());
+    if let (Some(id), Some(response)) = (
+        parsed.get("id").and_then(serde_json::Value::as_str),
+        parsed.get("response"),
+    ) {
+        if let Err(error) = session
+            .check_approval_response(id, Some(response), decided_by)
+            .await
+        {
+            warn!(
+                client_id = %client_id,
+                session_id = %session_id,
+                wrapper_id = %id,
+                decided_by = %decided_by,
+                error = %error,
+                "Refused approval response from client"
+            );
+            let result = ApprovalResponseResult {
+                id: Some(id.to_string()),
+                accepted: false,
+                error: Some(error),
+            };
+            send_approval_results(tx, client_id, &[result]);
+            return;
+        }
+        let approval_response = ApprovalMessage::response(parsed.clone(), decided_by);
 
         debug!(
             client_id = %client_id,
@@ -708,8 +1389,111 @@ async fn handle_approval_text_message(
     }
 }
 
-#[instrument(skip(socket, state), fields(session_id = %session_id, client_id))]
-async fn handle_approval_websocket(socket: WebSocket, session_id: String, state: AppState) {
+/// Replies to an approval client with `{"type": "approval_results", "results": [...]}`.
+fn send_approval_results(
+    tx: &tokio::sync::mpsc::UnboundedSender<Message>,
+    client_id: &str,
+    results: &[ApprovalResponseResult],
+) {
+    let reply = serde_json::json!({"type": "approval_results", "results": results});
+    if let Err(e) = tx.send(Message::Text(reply.to_string())) {
+        warn!(
+            client_id = %client_id,
+            error = %e,
+            "Failed to send approval results to WebSocket client"
+        );
+    }
+}
+
+/// Puts an approver's `{"action": "ask_clarification", "id", "question"}` to Claude as a
+/// user message. The approval stays pending, and Claude's next reply is attached to it.
+async fn ask_clarification(
+    parsed: &serde_json::Value,
+    client_id: &str,
+    session: &Session,
+    asked_by: &str,
+) -> ApprovalResponseResult {
+    let id = parsed
+        .get("id")
+        .and_then(serde_json::Value::as_str)
+        .map(ToString::to_string);
+    let question = parsed
+        .get("question")
+        .and_then(serde_json::Value::as_str)
+        .map(str::trim)
+        .filter(|question| !question.is_empty());
+    let outcome = match (&id, question) {
+        (Some(id), Some(question)) => session
+            .ask_clarification(id, question, asked_by)
+            .await
+            .map(|request| (request, question)),
+        _ => Err("expected {action, id, question}".to_string()),
+    };
+
+    let error = match outcome {
+        Ok((request, question)) => {
+            let tool = request
+                .request
+                .get("tool_name")
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or("a tool");
+            let content = serde_json::json!({
+                "type": "user",
+                "message": {
+                    "role": "user",
+                    "content": format!(
+                        "Before deciding whether to let you use {tool}, the approver asks: {question}"
+                    ),
+                },
+            })
+            .to_string();
+            session
+                .enqueue_message(WriteMessage {
+                    content: content.clone(),
+                    sender_client_id: client_id.to_string(),
+                    timestamp: std::time::SystemTime::now(),
+                })
+                .await;
+            // Clients of the session see the question like any other input
+            let _ = session.broadcast_message(BroadcastMessage::ClientInput {
+                content: content.into(),
+                sender_client_id: client_id.to_string(),
+                received_at: chrono::Utc::now(),
+            });
+            let _ = session.broadcast_approval_message(ApprovalMessage::request(request));
+            info!(
+                client_id = %client_id,
+                approval_id = ?id,
+                "Asked Claude to clarify a pending approval"
+            );
+            None
+        }
+        Err(error) => {
+            warn!(
+                client_id = %client_id,
+                approval_id = ?id,
+                error = %error,
+                "Rejected approval clarification"
+            );
+            Some(error)
+        }
+    };
+
+    ApprovalResponseResult {
+        id,
+        accepted: error.is_none(),
+        error,
+    }
+}
+
+#[instrument(skip(socket, state, access_log), fields(session_id = %session_id, client_id))]
+async fn handle_approval_websocket(
+    socket: WebSocket,
+    session_id: String,
+    state: AppState,
+    identity: String,
+    access_log: Option<AccessLog>,
+) {
     info!(session_id = %session_id, "Starting approval WebSocket connection handling");
 
     let Some(session) = validate_approval_session(&session_id, &state).await else {
@@ -717,9 +1501,11 @@ async fn handle_approval_websocket(socket: WebSocket, session_id: String, state:
         return;
     };
 
-    let (client_id, tx, rx) = setup_approval_connection(socket, &session_id, &session).await;
+    let (client_id, tx, rx) =
+        setup_approval_connection(socket, &session_id, &session, state.config.heartbeat).await;
 
-    let send_task = spawn_approval_outgoing_message_handler(rx.0, rx.1, client_id.clone());
+    let send_task =
+        spawn_approval_outgoing_message_handler(rx.0, rx.1, client_id.clone(), access_log);
     let broadcast_task =
         spawn_approval_broadcast_handler(session.clone(), tx.clone(), client_id.clone());
 
@@ -730,11 +1516,13 @@ async fn handle_approval_websocket(socket: WebSocket, session_id: String, state:
         &client_id,
         &session_id,
         session.clone(),
-        state.clone(),
+        &identity,
+        &tx,
     )
     .await;
 
     cleanup_approval_connection(
+        state.session_manager.clone(),
         session,
         &client_id,
         &session_id,
@@ -753,7 +1541,7 @@ async fn validate_approval_session(session_id: &str, state: &AppState) -> Option
 
     debug!(session_id = %session_id, "Session found, checking if active");
 
-    if !session.is_active().await {
+    if !session.is_active().await && !session.is_restarting() {
         error!(session_id = %session_id, "Approval WebSocket connection rejected: session not active");
         return None;
     }
@@ -766,17 +1554,19 @@ async fn setup_approval_connection(
     socket: WebSocket,
     session_id: &str,
     session: &Arc<Session>,
+    heartbeat: Heartbeat,
 ) -> (
     String,
     UnboundedSender<Message>,
     (
         SplitSink<WebSocket, Message>,
         UnboundedReceiver<Message>,
-        SplitStream<WebSocket>,
+        impl futures::Stream<Item = Result<Message, axum::Error>> + Unpin,
     ),
 ) {
     let (client_id, client) = setup_approval_client_connection(session_id, session);
     tracing::Span::current().record("client_id", &client_id);
+    let liveness = client.liveness.clone();
 
     session.add_approval_client(client).await;
     info!(
@@ -799,6 +1589,8 @@ async fn setup_approval_connection(
         "Approval communication channels created"
     );
 
+    let receiver = watch_liveness(receiver, tx.clone(), liveness, heartbeat, &client_id);
+
     (client_id, tx, (sender, rx, receiver))
 }
 
@@ -808,7 +1600,7 @@ async fn send_pending_approvals(
     session_id: &str,
     client_id: &str,
 ) {
-    let pending_approvals = session.get_pending_approvals().await;
+    let pending_approvals = session.get_pending_approvals_by_priority().await;
     if pending_approvals.is_empty() {
         return;
     }
@@ -821,26 +1613,7 @@ async fn send_pending_approvals(
     );
 
     for approval_request in pending_approvals {
-        let approval_message = match serde_json::to_string(&serde_json::json!({
-            "id": approval_request.id,
-            "request": approval_request.request,
-            "created_at": approval_request.created_at.duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default().as_secs()
-        })) {
-            Ok(json) => json,
-            Err(e) => {
-                error!(
-                    session_id = %session_id,
-                    client_id = %client_id,
-                    approval_id = %approval_request.id,
-                    error = %e,
-                    "Failed to serialize pending approval request"
-                );
-                continue;
-            }
-        };
-
-        if let Err(e) = tx.send(Message::Text(approval_message)) {
+        if let Err(e) = tx.send(Message::Text(approval_request.client_frame())) {
             warn!(
                 session_id = %session_id,
                 client_id = %client_id,
@@ -854,11 +1627,12 @@ async fn send_pending_approvals(
 }
 
 async fn handle_approval_message_loop(
-    mut receiver: SplitStream<WebSocket>,
+    mut receiver: impl futures::Stream<Item = Result<Message, axum::Error>> + Unpin,
     client_id: &str,
     session_id: &str,
     session: Arc<Session>,
-    state: AppState,
+    identity: &str,
+    tx: &tokio::sync::mpsc::UnboundedSender<Message>,
 ) -> u32 {
     info!(
         session_id = %session_id,
@@ -875,7 +1649,8 @@ async fn handle_approval_message_loop(
             client_id,
             session_id,
             &session,
-            &state,
+            identity,
+            tx,
         )
         .await
         {
@@ -899,7 +1674,8 @@ async fn process_approval_message(
     client_id: &str,
     session_id: &str,
     session: &Arc<Session>,
-    state: &AppState,
+    identity: &str,
+    tx: &tokio::sync::mpsc::UnboundedSender<Message>,
 ) -> bool {
     match msg {
         Ok(Message::Text(text)) => {
@@ -914,7 +1690,8 @@ async fn process_approval_message(
                 client_id,
                 session_id,
                 session.clone(),
-                state.clone(),
+                identity,
+                tx,
             )
             .await;
             true
@@ -947,6 +1724,7 @@ async fn process_approval_message(
 
 #[allow(clippy::unused_async)]
 async fn cleanup_approval_connection(
+    session_manager: Arc<SessionManager>,
     session: Arc<Session>,
     client_id: &str,
     session_id: &str,
@@ -971,6 +1749,7 @@ async fn cleanup_approval_connection(
             session_id = %session_id_cleanup,
             "Approval client removed from session"
         );
+        session_manager.approval_client_left(&session_cleanup).await;
     });
 
     debug!(
@@ -989,3 +1768,90 @@ async fn cleanup_approval_connection(
         "Approval WebSocket client disconnected and cleanup completed"
     );
 }
+
+/// Frame telling an events client it fell behind and missed `missed` events.
+fn events_lagged_frame(missed: u64) -> String {
+    serde_json::json!({ "event": "lagged", "missed": missed }).to_string()
+}
+
+#[instrument(skip(ws, state, caller, access_log))]
+pub async fn events_websocket_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    caller: Caller,
+    access_log: Option<Extension<AccessLog>>,
+) -> Response {
+    info!("Events WebSocket upgrade request");
+    if let Err(e) = state.session_manager.check_accepting() {
+        return e.into_response();
+    }
+    let access_log = access_log.map(|Extension(log)| log);
+    let span = Span::current();
+    ws.on_upgrade(move |socket| {
+        handle_events_websocket(socket, state, caller, access_log).instrument(span)
+    })
+}
+
+/// Relays the lifecycle events of the sessions `caller` may access to the client until
+/// it leaves. The client only ever receives; what it sends merely counts as a sign of
+/// life.
+async fn handle_events_websocket(
+    socket: WebSocket,
+    state: AppState,
+    caller: Caller,
+    access_log: Option<AccessLog>,
+) {
+    let client_id = Uuid::new_v4().to_string();
+    let mut events = state.session_manager.server_events().subscribe();
+    info!(client_id = %client_id, "Events WebSocket client connected");
+
+    let (mut sender, receiver) = socket.split();
+    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+    let mut receiver = watch_liveness(
+        receiver,
+        tx.clone(),
+        Arc::new(Liveness::default()),
+        state.config.heartbeat,
+        &client_id,
+    );
+
+    loop {
+        let outgoing = tokio::select! {
+            event = events.recv() => match event {
+                Ok(event)
+                    if !caller.can_access(
+                        state.session_manager.session_owner(&event.session_id).as_deref(),
+                    ) =>
+                {
+                    continue;
+                }
+                Ok(event) => match serde_json::to_string(&event) {
+                    Ok(frame) => Message::Text(frame),
+                    Err(e) => {
+                        error!(client_id = %client_id, error = %e, "Failed to serialize server event");
+                        continue;
+                    }
+                },
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!(client_id = %client_id, missed, "Events client fell behind");
+                    Message::Text(events_lagged_frame(missed))
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            },
+            Some(message) = rx.recv() => message,
+            incoming = receiver.next() => match incoming {
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+        };
+        let bytes = message_len(&outgoing);
+        if sender.send(outgoing).await.is_err() {
+            break;
+        }
+        if let Some(log) = &access_log {
+            log.add_bytes(bytes);
+        }
+    }
+
+    info!(client_id = %client_id, "Events WebSocket client disconnected");
+}
@@ -0,0 +1,244 @@
+use crate::approval_risk::RiskLevel;
+use crate::models::{ApprovalHistoryQuery, ApprovalRequest};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tracing::warn;
+use utoipa::ToSchema;
+
+const AUDIT_FILE: &str = "approval_audit.jsonl";
+
+/// Page size of history queries that do not ask for one.
+pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// Largest page a history query may ask for.
+pub const MAX_HISTORY_LIMIT: usize = 1000;
+
+/// One answered approval.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ApprovalAuditEntry {
+    pub approval_id: String,
+    pub session_id: String,
+    /// Tool Claude asked to use; unset for control requests other than tool use
+    pub tool: Option<String>,
+    /// Input Claude wanted to pass to the tool
+    #[schema(value_type = Option<Object>)]
+    pub input: Option<serde_json::Value>,
+    /// `behavior` of the response, e.g. `allow` or `deny`
+    pub decision: String,
+    #[serde(default)]
+    pub risk: RiskLevel,
+    /// Identity of whoever answered
+    pub decided_by: String,
+    pub requested_at: DateTime<Utc>,
+    pub decided_at: DateTime<Utc>,
+}
+
+impl ApprovalAuditEntry {
+    #[must_use]
+    pub fn new(request: &ApprovalRequest, response: &serde_json::Value, decided_by: &str) -> Self {
+        Self {
+            approval_id: request.id.clone(),
+            session_id: request.session_id.clone(),
+            tool: request
+                .request
+                .get("tool_name")
+                .and_then(serde_json::Value::as_str)
+                .map(str::to_string),
+            input: request.request.get("input").cloned(),
+            decision: response
+                .get("behavior")
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or("unknown")
+                .to_string(),
+            risk: request.risk,
+            decided_by: decided_by.to_string(),
+            requested_at: request.created_at.into(),
+            decided_at: Utc::now(),
+        }
+    }
+
+    fn matches(&self, query: &ApprovalHistoryQuery) -> bool {
+        let matches = |filter: Option<&str>, value: Option<&str>| {
+            filter.is_none_or(|filter| value.is_some_and(|v| v.eq_ignore_ascii_case(filter)))
+        };
+        matches(query.tool.as_deref(), self.tool.as_deref())
+            && matches(query.decision.as_deref(), Some(&self.decision))
+            && query
+                .user
+                .as_ref()
+                .is_none_or(|user| *user == self.decided_by)
+            && query.risk.is_none_or(|risk| risk == self.risk)
+            && query.since.is_none_or(|since| self.decided_at >= since)
+    }
+}
+
+/// Every approval answered across all sessions, kept for answering "who allowed what".
+#[derive(Debug, Default)]
+pub struct ApprovalAuditLog {
+    /// Entries in the order they were decided
+    entries: RwLock<Vec<ApprovalAuditEntry>>,
+    /// Where entries are appended, if a session state directory is configured
+    path: Option<PathBuf>,
+}
+
+impl ApprovalAuditLog {
+    /// Loads the entries recorded in `state_dir`, or starts empty.
+    #[must_use]
+    pub fn load(state_dir: Option<&Path>) -> Self {
+        let path = state_dir.map(|dir| dir.join(AUDIT_FILE));
+        let mut entries = Vec::new();
+
+        if let Some(path) = &path {
+            match std::fs::File::open(path) {
+                Ok(file) => {
+                    for line in std::io::BufReader::new(file).lines() {
+                        match line
+                            .map_err(serde_json::Error::io)
+                            .and_then(|line| serde_json::from_str(&line))
+                        {
+                            Ok(entry) => entries.push(entry),
+                            Err(e) => warn!(
+                                path = %path.display(),
+                                error = %e,
+                                "Skipping unreadable approval audit entry"
+                            ),
+                        }
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!(
+                    path = %path.display(),
+                    error = %e,
+                    "Failed to read approval audit log"
+                ),
+            }
+        }
+
+        Self {
+            entries: RwLock::new(entries),
+            path,
+        }
+    }
+
+    /// Appends an answered approval.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entries lock is poisoned.
+    pub fn record(&self, entry: ApprovalAuditEntry) {
+        if let Some(path) = &self.path {
+            let result = serde_json::to_string(&entry)
+                .map_err(std::io::Error::from)
+                .and_then(|line| {
+                    std::fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(path)?
+                        .write_all(format!("{line}\n").as_bytes())
+                });
+            if let Err(e) = result {
+                warn!(path = %path.display(), error = %e, "Failed to persist approval audit entry");
+            }
+        }
+        self.entries.write().unwrap().push(entry);
+    }
+
+    /// The entries matching `query` among those `visible` lets through, newest first,
+    /// and how many match in total.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entries lock is poisoned.
+    #[must_use]
+    pub fn query(
+        &self,
+        query: &ApprovalHistoryQuery,
+        visible: impl Fn(&ApprovalAuditEntry) -> bool,
+    ) -> (Vec<ApprovalAuditEntry>, usize) {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_HISTORY_LIMIT)
+            .clamp(1, MAX_HISTORY_LIMIT);
+        let entries = self.entries.read().unwrap();
+        let matching: Vec<&ApprovalAuditEntry> = entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.matches(query) && visible(entry))
+            .collect();
+        let page = matching
+            .iter()
+            .skip(query.offset)
+            .take(limit)
+            .map(|entry| (*entry).clone())
+            .collect();
+        let total = matching.len();
+        (page, total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(tool: &str, decision: &str, decided_by: &str, age_days: i64) -> ApprovalAuditEntry {
+        ApprovalAuditEntry {
+            approval_id: uuid::Uuid::new_v4().to_string(),
+            session_id: "audited".to_string(),
+            tool: Some(tool.to_string()),
+            input: Some(serde_json::json!({"command": "ls"})),
+            decision: decision.to_string(),
+            risk: RiskLevel::ReadOnly,
+            decided_by: decided_by.to_string(),
+            requested_at: Utc::now() - chrono::Duration::days(age_days),
+            decided_at: Utc::now() - chrono::Duration::days(age_days),
+        }
+    }
+
+    #[test]
+    fn test_approval_history_filters() {
+        let log = ApprovalAuditLog::load(None);
+        log.record(entry("Bash", "allow", "alice", 10));
+        log.record(entry("Bash", "allow", "bob", 2));
+        log.record(entry("Bash", "deny", "alice", 1));
+        log.record(entry("Read", "allow", "alice", 1));
+
+        let query = ApprovalHistoryQuery {
+            tool: Some("bash".to_string()),
+            decision: Some("allow".to_string()),
+            since: Some(Utc::now() - chrono::Duration::days(7)),
+            ..Default::default()
+        };
+        let (entries, total) = log.query(&query, |_| true);
+        assert_eq!(total, 1);
+        assert_eq!(entries[0].decided_by, "bob");
+
+        let query = ApprovalHistoryQuery {
+            user: Some("alice".to_string()),
+            limit: Some(2),
+            offset: 1,
+            ..Default::default()
+        };
+        let (entries, total) = log.query(&query, |_| true);
+        assert_eq!(total, 3);
+        // Newest first
+        let decisions: Vec<_> = entries.iter().map(|e| e.decision.as_str()).collect();
+        assert_eq!(decisions, vec!["deny", "allow"]);
+    }
+
+    #[test]
+    fn test_approval_history_persistence() {
+        let state_dir = TempDir::new().unwrap();
+        let log = ApprovalAuditLog::load(Some(state_dir.path()));
+        let recorded = entry("Bash", "allow", "alice", 0);
+        log.record(recorded.clone());
+
+        let reloaded = ApprovalAuditLog::load(Some(state_dir.path()));
+        let (entries, total) = reloaded.query(&ApprovalHistoryQuery::default(), |_| true);
+        assert_eq!(total, 1);
+        assert_eq!(entries[0], recorded);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::approval_risk::RiskLevel;
+use crate::config::ApprovalPriority;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+/// Lane an approval request is delivered in.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// Held for the batch window, then delivered with the others that arrived meanwhile
+    #[default]
+    Normal,
+    /// Delivered right away, ahead of held requests
+    High,
+}
+
+/// Priority of a request of `risk`; everything is normal without priority lanes.
+#[must_use]
+pub fn classify(lanes: Option<ApprovalPriority>, risk: RiskLevel) -> Priority {
+    match lanes {
+        Some(lanes) if risk >= lanes.high_risk => Priority::High,
+        _ => Priority::Normal,
+    }
+}
+
+/// Two-lane queue of approval deliveries. High priority items are ready at once; normal
+/// ones become ready together, `window` after the first of them was queued.
+#[derive(Debug)]
+pub struct PriorityLanes<T> {
+    window: Duration,
+    high: VecDeque<T>,
+    normal: VecDeque<T>,
+    normal_due: Option<Instant>,
+}
+
+impl<T> PriorityLanes<T> {
+    #[must_use]
+    pub const fn new(window: Duration) -> Self {
+        Self {
+            window,
+            high: VecDeque::new(),
+            normal: VecDeque::new(),
+            normal_due: None,
+        }
+    }
+
+    pub fn push(&mut self, priority: Priority, item: T, now: Instant) {
+        match priority {
+            Priority::High => self.high.push_back(item),
+            Priority::Normal => {
+                if self.normal.is_empty() {
+                    self.normal_due = Some(now + self.window);
+                }
+                self.normal.push_back(item);
+            }
+        }
+    }
+
+    /// When the held normal items become ready, if any are held
+    #[must_use]
+    pub const fn due(&self) -> Option<Instant> {
+        self.normal_due
+    }
+
+    /// The next item ready at `now`, high priority ones first.
+    pub fn pop_ready(&mut self, now: Instant) -> Option<T> {
+        if let Some(item) = self.high.pop_front() {
+            return Some(item);
+        }
+        if self.normal_due.is_some_and(|due| due > now) {
+            return None;
+        }
+        let item = self.normal.pop_front();
+        if self.normal.is_empty() {
+            self.normal_due = None;
+        }
+        item
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let lanes = Some(ApprovalPriority {
+            high_risk: RiskLevel::Network,
+            batch_window: Duration::from_secs(2),
+        });
+        assert_eq!(classify(lanes, RiskLevel::Destructive), Priority::High);
+        assert_eq!(classify(lanes, RiskLevel::Network), Priority::High);
+        assert_eq!(classify(lanes, RiskLevel::SystemWrite), Priority::Normal);
+        assert_eq!(classify(None, RiskLevel::Destructive), Priority::Normal);
+    }
+
+    #[test]
+    fn test_priority_lanes() {
+        let start = Instant::now();
+        let mut lanes = PriorityLanes::new(Duration::from_secs(2));
+        lanes.push(Priority::Normal, "read", start);
+        lanes.push(Priority::High, "rm", start + Duration::from_secs(1));
+        lanes.push(Priority::Normal, "grep", start + Duration::from_secs(1));
+
+        // The high priority item overtakes the held ones
+        let now = start + Duration::from_secs(1);
+        assert_eq!(lanes.due(), Some(start + Duration::from_secs(2)));
+        assert_eq!(lanes.pop_ready(now), Some("rm"));
+        assert_eq!(lanes.pop_ready(now), None);
+
+        // The window runs from the first held item, then they all go together
+        let now = start + Duration::from_secs(2);
+        assert_eq!(lanes.pop_ready(now), Some("read"));
+        assert_eq!(lanes.pop_ready(now), Some("grep"));
+        assert_eq!(lanes.pop_ready(now), None);
+        assert_eq!(lanes.due(), None);
+
+        // Without a window nothing is held
+        let mut lanes = PriorityLanes::new(Duration::ZERO);
+        lanes.push(Priority::Normal, "read", start);
+        assert_eq!(lanes.pop_ready(start), Some("read"));
+    }
+}
@@ -0,0 +1,387 @@
+use crate::models::ApprovalRequest;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+
+const RULES_FILE: &str = "approval_rules.json";
+
+/// Most rules a session, or a project, keeps; the oldest one goes first beyond it.
+pub const MAX_RULES_PER_SCOPE: usize = 200;
+
+/// Field of an approval response naming how long an allow is remembered. It is taken
+/// out before the response is passed to Claude.
+pub const REMEMBER_FIELD: &str = "remember";
+
+/// Who requests allowed by a rule are attributed to, followed by the rule's ID.
+pub const RULE_IDENTITY_PREFIX: &str = "approval-rule:";
+
+/// How long an allow is remembered for identical requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RememberScope {
+    /// Only this request is allowed
+    #[default]
+    Once,
+    /// Identical requests of the same session are allowed
+    Session,
+    /// Identical requests of every session of the same owner in the same working
+    /// directory are allowed
+    Project,
+}
+
+/// The sessions project rules are shared by: those of one owner in one working
+/// directory, so one user's allows do not apply to another's sessions in the same
+/// checkout.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ProjectKey {
+    /// Sessions started outside the orchestrator have none
+    pub owner: Option<String>,
+    pub working_dir: PathBuf,
+}
+
+/// An allow remembered for requests identical to the one it was given for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ApprovalRule {
+    pub id: String,
+    /// `session` or `project`
+    pub scope: RememberScope,
+    pub tool_name: String,
+    /// Tool input a request must carry, exactly, to be allowed by the rule
+    #[schema(value_type = Object)]
+    pub input: serde_json::Value,
+    pub created_by: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl ApprovalRule {
+    fn matches(&self, request: &serde_json::Value) -> bool {
+        request.get("tool_name").and_then(|t| t.as_str()) == Some(self.tool_name.as_str())
+            && request.get("input").unwrap_or(&serde_json::Value::Null) == &self.input
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistedProject {
+    #[serde(flatten)]
+    key: ProjectKey,
+    rules: Vec<ApprovalRule>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct PersistedRules {
+    #[serde(default)]
+    sessions: HashMap<String, Vec<ApprovalRule>>,
+    #[serde(default)]
+    projects: Vec<PersistedProject>,
+}
+
+/// Remembered allows, so approvers are not asked the same thing again and again.
+#[derive(Debug, Default)]
+pub struct ApprovalRules {
+    /// Session ID -> rules only it uses, oldest first
+    sessions: DashMap<String, Vec<ApprovalRule>>,
+    /// Owner and working directory -> rules every session of theirs there uses, oldest
+    /// first
+    projects: DashMap<ProjectKey, Vec<ApprovalRule>>,
+    /// Where rules are persisted, if a session state directory is configured
+    path: Option<PathBuf>,
+}
+
+/// Takes the remember scope out of an approval response. Missing and unknown scopes
+/// are `once`, unknown ones with a warning.
+pub fn take_remember(response: &mut serde_json::Value) -> RememberScope {
+    let Some(scope) = response
+        .as_object_mut()
+        .and_then(|object| object.remove(REMEMBER_FIELD))
+    else {
+        return RememberScope::Once;
+    };
+    serde_json::from_value(scope.clone()).unwrap_or_else(|_| {
+        warn!(remember = %scope, "Ignoring unknown remember scope of approval response");
+        RememberScope::Once
+    })
+}
+
+impl ApprovalRules {
+    /// Loads the rules persisted in `state_dir`, or starts empty.
+    #[must_use]
+    pub fn load(state_dir: Option<&Path>) -> Self {
+        let path = state_dir.map(|dir| dir.join(RULES_FILE));
+        let mut rules = Self {
+            path,
+            ..Self::default()
+        };
+
+        if let Some(path) = &rules.path {
+            match std::fs::read(path) {
+                Ok(bytes) => match serde_json::from_slice::<PersistedRules>(&bytes) {
+                    Ok(persisted) => {
+                        rules.sessions.extend(persisted.sessions);
+                        rules.projects.extend(
+                            persisted
+                                .projects
+                                .into_iter()
+                                .map(|project| (project.key, project.rules)),
+                        );
+                    }
+                    Err(e) => warn!(
+                        path = %path.display(),
+                        error = %e,
+                        "Ignoring unreadable approval rules file"
+                    ),
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!(
+                    path = %path.display(),
+                    error = %e,
+                    "Failed to read approval rules file"
+                ),
+            }
+        }
+        rules
+    }
+
+    /// The rules that apply to a session of `project`: its own, then its project's.
+    #[must_use]
+    pub fn list(&self, session_id: &str, project: &ProjectKey) -> Vec<ApprovalRule> {
+        let session = self.sessions.get(session_id);
+        let project = self.projects.get(project);
+        session
+            .iter()
+            .flat_map(|rules| rules.iter())
+            .chain(project.iter().flat_map(|rules| rules.iter()))
+            .cloned()
+            .collect()
+    }
+
+    /// The rule allowing Claude's `request` in a session of `project`, if any.
+    #[must_use]
+    pub fn matching(
+        &self,
+        session_id: &str,
+        project: &ProjectKey,
+        request: &serde_json::Value,
+    ) -> Option<ApprovalRule> {
+        self.list(session_id, project)
+            .into_iter()
+            .find(|rule| rule.matches(request))
+    }
+
+    /// Remembers that `request` was allowed by `created_by` for `scope`, and returns the
+    /// rule. Nothing is remembered for `once`, or for requests without a tool name; an
+    /// identical rule already there is returned as it is.
+    pub fn remember(
+        &self,
+        session_id: &str,
+        project: &ProjectKey,
+        scope: RememberScope,
+        request: &ApprovalRequest,
+        created_by: &str,
+    ) -> Option<ApprovalRule> {
+        let tool_name = request.request.get("tool_name")?.as_str()?;
+        let rule = ApprovalRule {
+            id: uuid::Uuid::new_v4().to_string(),
+            scope,
+            tool_name: tool_name.to_string(),
+            input: request
+                .request
+                .get("input")
+                .cloned()
+                .unwrap_or(serde_json::Value::Null),
+            created_by: created_by.to_string(),
+            created_at: chrono::Utc::now(),
+        };
+        let rule = match scope {
+            RememberScope::Once => return None,
+            RememberScope::Session => add(
+                &mut self.sessions.entry(session_id.to_string()).or_default(),
+                rule,
+            ),
+            RememberScope::Project => {
+                add(&mut self.projects.entry(project.clone()).or_default(), rule)
+            }
+        };
+        debug!(session_id = %session_id, rule_id = %rule.id, scope = ?scope, "Remembered approval rule");
+        self.persist();
+        Some(rule)
+    }
+
+    /// Removes the rule `rule_id` from those applying to a session of `project`; returns
+    /// whether there was one.
+    #[must_use]
+    pub fn remove(&self, session_id: &str, project: &ProjectKey, rule_id: &str) -> bool {
+        let mut removed = false;
+        let mut retain = |rules: &mut Vec<ApprovalRule>| {
+            let before = rules.len();
+            rules.retain(|rule| rule.id != rule_id);
+            removed |= rules.len() != before;
+            rules.is_empty()
+        };
+        self.sessions
+            .remove_if_mut(session_id, |_, rules| retain(rules));
+        self.projects
+            .remove_if_mut(project, |_, rules| retain(rules));
+        if removed {
+            self.persist();
+        }
+        removed
+    }
+
+    /// Gives a session resumed under a new ID the rules of the one it continues.
+    pub fn copy_session(&self, old_session_id: &str, new_session_id: &str) {
+        let Some(rules) = self.sessions.get(old_session_id).map(|rules| rules.clone()) else {
+            return;
+        };
+        self.sessions.insert(new_session_id.to_string(), rules);
+        self.persist();
+    }
+
+    fn persist(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let snapshot = PersistedRules {
+            sessions: self
+                .sessions
+                .iter()
+                .map(|entry| (entry.key().clone(), entry.value().clone()))
+                .collect(),
+            projects: self
+                .projects
+                .iter()
+                .map(|entry| PersistedProject {
+                    key: entry.key().clone(),
+                    rules: entry.value().clone(),
+                })
+                .collect(),
+        };
+        let tmp_path = path.with_extension("json.tmp");
+        let result = serde_json::to_vec(&snapshot)
+            .map_err(std::io::Error::from)
+            .and_then(|bytes| std::fs::write(&tmp_path, bytes))
+            .and_then(|()| std::fs::rename(&tmp_path, path));
+        if let Err(e) = result {
+            warn!(path = %path.display(), error = %e, "Failed to persist approval rules");
+        }
+    }
+}
+
+/// Adds `rule` to `rules` unless an identical one is there, and returns the one kept.
+fn add(rules: &mut Vec<ApprovalRule>, rule: ApprovalRule) -> ApprovalRule {
+    if let Some(existing) = rules
+        .iter()
+        .find(|existing| existing.tool_name == rule.tool_name && existing.input == rule.input)
+    {
+        return existing.clone();
+    }
+    if rules.len() >= MAX_RULES_PER_SCOPE {
+        rules.remove(0);
+    }
+    rules.push(rule.clone());
+    rule
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn request(tool_name: &str, input: &serde_json::Value) -> ApprovalRequest {
+        ApprovalRequest {
+            id: "a1".to_string(),
+            session_id: "s1".to_string(),
+            claude_request_id: "c1".to_string(),
+            request: json!({"subtype": "can_use_tool", "tool_name": tool_name, "input": input}),
+            created_at: std::time::SystemTime::now(),
+            clarifications: Vec::new(),
+            risk: crate::approval_risk::RiskLevel::default(),
+            risk_reasons: Vec::new(),
+            priority: crate::approval_priority::Priority::default(),
+        }
+    }
+
+    #[test]
+    fn test_take_remember() {
+        let mut response = json!({"behavior": "allow", "updatedInput": {}, "remember": "project"});
+        assert_eq!(take_remember(&mut response), RememberScope::Project);
+        assert_eq!(response, json!({"behavior": "allow", "updatedInput": {}}));
+
+        let mut response = json!({"behavior": "allow", "remember": "forever"});
+        assert_eq!(take_remember(&mut response), RememberScope::Once);
+        assert_eq!(take_remember(&mut json!({})), RememberScope::Once);
+    }
+
+    fn project(owner: &str, working_dir: &str) -> ProjectKey {
+        ProjectKey {
+            owner: Some(owner.to_string()),
+            working_dir: PathBuf::from(working_dir),
+        }
+    }
+
+    #[test]
+    fn test_rules_match_identical_requests_in_scope() {
+        let state_dir = TempDir::new().unwrap();
+        let rules = ApprovalRules::load(Some(state_dir.path()));
+        let work = project("alice", "/work");
+        let test = request("Bash", &json!({"command": "cargo test"}));
+
+        assert!(rules
+            .remember("s1", &work, RememberScope::Once, &test, "alice")
+            .is_none());
+        let rule = rules
+            .remember("s1", &work, RememberScope::Session, &test, "alice")
+            .unwrap();
+        // Remembering the same again keeps the first rule
+        let again = rules
+            .remember("s1", &work, RememberScope::Session, &test, "bob")
+            .unwrap();
+        assert_eq!(again.id, rule.id);
+
+        assert!(rules.matching("s1", &work, &test.request).is_some());
+        assert!(rules.matching("s2", &work, &test.request).is_none());
+        let other = request("Bash", &json!({"command": "cargo test --release"}));
+        assert!(rules.matching("s1", &work, &other.request).is_none());
+
+        let fmt = request("Bash", &json!({"command": "cargo fmt"}));
+        rules
+            .remember("s1", &work, RememberScope::Project, &fmt, "alice")
+            .unwrap();
+        assert!(rules.matching("s2", &work, &fmt.request).is_some());
+        assert!(rules
+            .matching("s2", &project("alice", "/elsewhere"), &fmt.request)
+            .is_none());
+
+        // Rules outlive a restart and follow a resumed session
+        let reloaded = ApprovalRules::load(Some(state_dir.path()));
+        assert_eq!(reloaded.list("s1", &work).len(), 2);
+        reloaded.copy_session("s1", "s3");
+        assert!(reloaded.matching("s3", &work, &test.request).is_some());
+
+        assert!(reloaded.remove("s1", &work, &rule.id));
+        assert!(!reloaded.remove("s1", &work, &rule.id));
+        assert!(reloaded.matching("s1", &work, &test.request).is_none());
+        assert_eq!(reloaded.list("s1", &work).len(), 1);
+    }
+
+    #[test]
+    fn test_project_rules_are_per_owner() {
+        let rules = ApprovalRules::load(None);
+        let alices = project("alice", "/work");
+        let bobs = project("bob", "/work");
+        let push = request("Bash", &json!({"command": "git push --force"}));
+
+        let rule = rules
+            .remember("s1", &alices, RememberScope::Project, &push, "alice")
+            .unwrap();
+        assert!(rules.matching("s2", &alices, &push.request).is_some());
+        // Bob's sessions in the same checkout still ask
+        assert!(rules.matching("s3", &bobs, &push.request).is_none());
+        assert!(rules.list("s3", &bobs).is_empty());
+        assert!(!rules.remove("s3", &bobs, &rule.id));
+        assert_eq!(rules.list("s2", &alices).len(), 1);
+    }
+}
@@ -0,0 +1,416 @@
+use crate::models::{is_valid_session_id, ApprovalMessage, ApprovalRequest, Session};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+const PENDING_DIR: &str = "pending";
+const DECISIONS_DIR: &str = "decisions";
+
+/// How often decisions taken on other instances are looked for
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long decisions are kept for instances that were not polling when they were taken
+const DECISION_RETENTION: Duration = Duration::from_secs(60 * 60);
+
+/// Marks an approval response as applying a decision already recorded in the shared
+/// directory, so it is not claimed again.
+pub const SYNCED_FIELD: &str = "synced";
+
+/// The pending approvals of a session, as published by the instance running it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicatedApprovals {
+    pub session_id: String,
+    pub owner: Option<String>,
+    pub approvals: Vec<ApprovalRequest>,
+}
+
+/// The answer an approval got, on whichever instance it was given.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Decision {
+    pub session_id: String,
+    pub approval_id: String,
+    /// The `response` of the approval answer, passed to Claude as is
+    pub response: serde_json::Value,
+    pub decided_by: String,
+    pub decided_at: DateTime<Utc>,
+}
+
+impl Decision {
+    #[must_use]
+    pub fn new(
+        session_id: &str,
+        approval_id: &str,
+        response: serde_json::Value,
+        decided_by: &str,
+    ) -> Self {
+        Self {
+            session_id: session_id.to_string(),
+            approval_id: approval_id.to_string(),
+            response,
+            decided_by: decided_by.to_string(),
+            decided_at: Utc::now(),
+        }
+    }
+
+    /// The approval response applying this decision on the instance running the session
+    #[must_use]
+    pub fn message(&self) -> ApprovalMessage {
+        ApprovalMessage::response(
+            serde_json::json!({
+                "id": self.approval_id,
+                "response": self.response,
+                SYNCED_FIELD: true,
+            }),
+            &self.decided_by,
+        )
+    }
+}
+
+/// Whether `response` applies a decision recorded in the shared directory
+#[must_use]
+pub fn is_synced(response: &serde_json::Value) -> bool {
+    response
+        .get(SYNCED_FIELD)
+        .and_then(serde_json::Value::as_bool)
+        == Some(true)
+}
+
+/// Replicates pending approvals and their decisions between the instances sharing
+/// `APPROVAL_SYNC_DIR`, so an approver connected to any of them can answer.
+///
+/// The instance running a session publishes its pending approvals to
+/// `pending/{session_id}.json`. Every decision, wherever it is taken, is recorded in
+/// `decisions/{approval_id}.json`, which only the first one gets to create: opposing
+/// decisions taken at the same time on two instances are resolved in favor of the one
+/// recorded first, and the other is refused. The running instance polls the decisions
+/// and passes those taken elsewhere on to Claude.
+#[derive(Debug)]
+pub struct ApprovalSync {
+    dir: PathBuf,
+}
+
+impl ApprovalSync {
+    /// Shares approvals through `dir`, creating its layout if missing.
+    #[must_use]
+    pub fn new(dir: PathBuf) -> Self {
+        for sub_dir in [PENDING_DIR, DECISIONS_DIR] {
+            if let Err(e) = std::fs::create_dir_all(dir.join(sub_dir)) {
+                warn!(
+                    path = %dir.join(sub_dir).display(),
+                    error = %e,
+                    "Failed to create approval sync directory"
+                );
+            }
+        }
+        Self { dir }
+    }
+
+    /// Where the pending approvals of `session_id` are published; nowhere for IDs that
+    /// are not file names.
+    fn pending_file(&self, session_id: &str) -> Option<PathBuf> {
+        is_valid_session_id(session_id).then(|| {
+            self.dir
+                .join(PENDING_DIR)
+                .join(format!("{session_id}.json"))
+        })
+    }
+
+    fn decision_file(&self, approval_id: &str) -> Option<PathBuf> {
+        is_valid_session_id(approval_id).then(|| {
+            self.dir
+                .join(DECISIONS_DIR)
+                .join(format!("{approval_id}.json"))
+        })
+    }
+
+    /// Publishes the pending approvals of `session_id`, removing them once none are left.
+    pub async fn publish(
+        &self,
+        session_id: &str,
+        owner: Option<&str>,
+        approvals: Vec<ApprovalRequest>,
+    ) {
+        let Some(path) = self.pending_file(session_id) else {
+            return;
+        };
+
+        let result = if approvals.is_empty() {
+            match tokio::fs::remove_file(&path).await {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                other => other,
+            }
+        } else {
+            let published = ReplicatedApprovals {
+                session_id: session_id.to_string(),
+                owner: owner.map(str::to_string),
+                approvals,
+            };
+            let tmp_path = path.with_extension("json.tmp");
+            match serde_json::to_vec(&published) {
+                Ok(bytes) => match tokio::fs::write(&tmp_path, bytes).await {
+                    Ok(()) => tokio::fs::rename(&tmp_path, &path).await,
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e.into()),
+            }
+        };
+
+        if let Err(e) = result {
+            warn!(
+                path = %path.display(),
+                error = %e,
+                "Failed to publish pending approvals"
+            );
+        }
+    }
+
+    /// The pending approvals another instance published for `session_id`, including
+    /// those decided since that the instance did not pass on to Claude yet.
+    pub async fn pending(&self, session_id: &str) -> Option<ReplicatedApprovals> {
+        let path = self.pending_file(session_id)?;
+        let bytes = tokio::fs::read(&path).await.ok()?;
+        serde_json::from_slice(&bytes)
+            .inspect_err(|e| {
+                warn!(path = %path.display(), error = %e, "Ignoring unreadable replicated approvals");
+            })
+            .ok()
+    }
+
+    /// The owner of a session whose approvals another instance published, if it has one
+    #[must_use]
+    pub fn owner_of(&self, session_id: &str) -> Option<String> {
+        let bytes = std::fs::read(self.pending_file(session_id)?).ok()?;
+        serde_json::from_slice::<ReplicatedApprovals>(&bytes)
+            .ok()?
+            .owner
+    }
+
+    /// Whether an answer to `approval_id` was recorded already
+    #[must_use]
+    pub fn is_decided(&self, approval_id: &str) -> bool {
+        self.decision_file(approval_id)
+            .is_some_and(|path| path.exists())
+    }
+
+    /// Records `decision` unless the approval was decided already, in which case the
+    /// earlier decision is returned and this one must be dropped. A decision that
+    /// cannot be recorded is let through, so approvals keep working on the instance
+    /// running the session when the shared directory is unavailable.
+    pub async fn claim(&self, decision: &Decision) -> Option<Decision> {
+        let path = self.decision_file(&decision.approval_id)?;
+        let tmp_path = path.with_extension(format!("{}.tmp", Uuid::new_v4()));
+
+        let bytes = match serde_json::to_vec(decision) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!(error = %e, "Failed to serialize approval decision");
+                return None;
+            }
+        };
+        if let Err(e) = tokio::fs::write(&tmp_path, bytes).await {
+            warn!(path = %tmp_path.display(), error = %e, "Failed to record approval decision");
+            return None;
+        }
+        // Linking fails if the decision exists, so only the first one is recorded, and
+        // never half written
+        let linked = tokio::fs::hard_link(&tmp_path, &path).await;
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        match linked {
+            Ok(()) => None,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                let earlier = read_decision(&path).await;
+                debug!(
+                    approval_id = %decision.approval_id,
+                    decided_by = ?earlier.as_ref().map(|earlier| &earlier.decided_by),
+                    "Approval was decided already"
+                );
+                earlier.or_else(|| Some(decision.clone()))
+            }
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Failed to record approval decision");
+                None
+            }
+        }
+    }
+
+    async fn decisions(&self) -> Vec<(PathBuf, Decision)> {
+        let mut entries = match tokio::fs::read_dir(self.dir.join(DECISIONS_DIR)).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!(error = %e, "Failed to read approval decisions");
+                return Vec::new();
+            }
+        };
+        let mut decisions = Vec::new();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                if let Some(decision) = read_decision(&path).await {
+                    decisions.push((path, decision));
+                }
+            }
+        }
+        decisions
+    }
+
+    /// Passes decisions taken on other instances on to the sessions running here whose
+    /// approvals they answer, every `POLL_INTERVAL`.
+    pub fn start(self: &Arc<Self>, sessions: Arc<DashMap<String, Arc<Session>>>) -> JoinHandle<()> {
+        info!(dir = %self.dir.display(), "Sharing approvals with other instances");
+        let sync = self.clone();
+        tokio::spawn(async move {
+            let mut applied = HashSet::new();
+            let mut interval = tokio::time::interval(POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                sync.apply_decisions(&sessions, &mut applied).await;
+            }
+        })
+    }
+
+    /// Applies the decisions not seen yet; `applied` holds the approval IDs seen before.
+    async fn apply_decisions(
+        &self,
+        sessions: &DashMap<String, Arc<Session>>,
+        applied: &mut HashSet<String>,
+    ) {
+        let decisions = self.decisions().await;
+        applied.retain(|approval_id| {
+            decisions
+                .iter()
+                .any(|(_, decision)| &decision.approval_id == approval_id)
+        });
+
+        for (path, decision) in decisions {
+            let expired = (Utc::now() - decision.decided_at)
+                .to_std()
+                .is_ok_and(|age| age > DECISION_RETENTION);
+            if expired {
+                let _ = tokio::fs::remove_file(&path).await;
+                continue;
+            }
+            if applied.contains(&decision.approval_id) {
+                continue;
+            }
+            // Map guards are not held across the awaits below
+            let Some(session) = sessions
+                .get(&decision.session_id)
+                .map(|session| session.clone())
+            else {
+                continue;
+            };
+            applied.insert(decision.approval_id.clone());
+            if !session.is_pending_approval(&decision.approval_id).await {
+                continue;
+            }
+            info!(
+                session_id = %decision.session_id,
+                approval_id = %decision.approval_id,
+                decided_by = %decision.decided_by,
+                "Applying approval decided on another instance"
+            );
+            if let Err(e) = session.broadcast_approval_message(decision.message()) {
+                warn!(
+                    session_id = %decision.session_id,
+                    approval_id = %decision.approval_id,
+                    error = %e,
+                    "Failed to apply approval decided on another instance"
+                );
+            }
+        }
+    }
+}
+
+async fn read_decision(path: &Path) -> Option<Decision> {
+    let bytes = tokio::fs::read(path).await.ok()?;
+    serde_json::from_slice(&bytes)
+        .inspect_err(|e| {
+            warn!(path = %path.display(), error = %e, "Ignoring unreadable approval decision");
+        })
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::approval_priority::Priority;
+    use crate::approval_risk::RiskLevel;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_first_decision_wins() {
+        let dir = TempDir::new().unwrap();
+        let replica_a = ApprovalSync::new(dir.path().to_path_buf());
+        let replica_b = ApprovalSync::new(dir.path().to_path_buf());
+
+        let allow = Decision::new(
+            "s1",
+            "a1",
+            serde_json::json!({"behavior": "allow"}),
+            "alice",
+        );
+        let deny = Decision::new("s1", "a1", serde_json::json!({"behavior": "deny"}), "bob");
+        assert!(!replica_b.is_decided("a1"));
+        assert_eq!(replica_a.claim(&allow).await, None);
+        assert!(replica_b.is_decided("a1"));
+        assert_eq!(replica_b.claim(&deny).await, Some(allow.clone()));
+        assert_eq!(replica_a.claim(&deny).await, Some(allow));
+
+        // Only the decision itself is left behind
+        let files: Vec<_> = std::fs::read_dir(dir.path().join(DECISIONS_DIR))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["a1.json"]);
+    }
+
+    #[tokio::test]
+    async fn test_published_approvals() {
+        let dir = TempDir::new().unwrap();
+        let replica_a = ApprovalSync::new(dir.path().to_path_buf());
+        let replica_b = ApprovalSync::new(dir.path().to_path_buf());
+        let request = |id: &str| ApprovalRequest {
+            id: id.to_string(),
+            session_id: "s1".to_string(),
+            claude_request_id: format!("claude-{id}"),
+            request: serde_json::json!({"tool_name": "Bash"}),
+            created_at: std::time::SystemTime::now(),
+            clarifications: Vec::new(),
+            risk: RiskLevel::default(),
+            risk_reasons: Vec::new(),
+            priority: Priority::default(),
+        };
+
+        replica_a
+            .publish("s1", Some("alice"), vec![request("a1"), request("a2")])
+            .await;
+        assert_eq!(replica_b.owner_of("s1").as_deref(), Some("alice"));
+        assert_eq!(replica_b.owner_of("s2"), None);
+
+        replica_b
+            .claim(&Decision::new("s1", "a1", serde_json::json!({}), "bob"))
+            .await;
+        let pending = replica_b.pending("s1").await.unwrap();
+        let undecided: Vec<&str> = pending
+            .approvals
+            .iter()
+            .map(|request| request.id.as_str())
+            .filter(|id| !replica_b.is_decided(id))
+            .collect();
+        assert_eq!(undecided, ["a2"]);
+
+        replica_a.publish("s1", Some("alice"), Vec::new()).await;
+        assert!(replica_b.pending("s1").await.is_none());
+        assert!(replica_b.pending("../s1").await.is_none());
+    }
+}
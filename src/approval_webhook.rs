@@ -0,0 +1,339 @@
+use crate::config::ApprovalWebhookSettings;
+use crate::models::{ApprovalMessage, DECIDED_BY_FIELD};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, RwLock};
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+/// Attempts at delivering one event, the first included.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Wait before the first retry; doubled for every retry after it.
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// How long a single attempt may take.
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Events waiting while an earlier one is retried; newer events are dropped once it is full.
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Header with the HMAC-SHA256 of the body, as `sha256=<hex>`, when a secret is set.
+pub const SIGNATURE_HEADER: &str = "X-Chef-Signature";
+
+/// Header naming the event, e.g. `approval.requested`.
+pub const EVENT_HEADER: &str = "X-Chef-Event";
+
+/// Header with an ID that stays the same across the retries of one delivery.
+pub const DELIVERY_HEADER: &str = "X-Chef-Delivery";
+
+/// What happened to an approval request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalEvent {
+    Requested,
+    Resolved,
+    /// Still undecided after the configured time; the request stays pending
+    TimedOut,
+}
+
+impl ApprovalEvent {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Requested => "approval.requested",
+            Self::Resolved => "approval.resolved",
+            Self::TimedOut => "approval.timed_out",
+        }
+    }
+}
+
+/// Body of a webhook delivery; `data` depends on the event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalHookEvent {
+    pub event: ApprovalEvent,
+    pub session_id: String,
+    pub working_directory: PathBuf,
+    pub approval_id: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub data: serde_json::Value,
+}
+
+/// POSTs approval events to the configured URL, one at a time and in order, retrying
+/// failed deliveries with exponential backoff.
+pub struct ApprovalWebhook {
+    queue: mpsc::Sender<ApprovalHookEvent>,
+    timeout: Option<Duration>,
+}
+
+impl ApprovalWebhook {
+    /// Starts the delivery task; must be called within a Tokio runtime.
+    #[must_use]
+    pub fn new(settings: &ApprovalWebhookSettings) -> Self {
+        let (queue, events) = mpsc::channel(QUEUE_CAPACITY);
+        tokio::spawn(deliver_all(
+            reqwest::Client::new(),
+            settings.clone(),
+            FIRST_RETRY_DELAY,
+            events,
+        ));
+        Self {
+            queue,
+            timeout: settings.timeout,
+        }
+    }
+
+    /// Queues an event for delivery. Delivery happens in the background and failures
+    /// are only logged.
+    pub fn send(&self, event: ApprovalHookEvent) {
+        if let Err(mpsc::error::TrySendError::Full(event)) = self.queue.try_send(event) {
+            warn!(
+                session_id = %event.session_id,
+                approval_id = %event.approval_id,
+                event = event.event.as_str(),
+                "Approval webhook is falling behind, dropping event"
+            );
+        }
+    }
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `body` under `secret`.
+fn signature(secret: &str, body: &[u8]) -> String {
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = ring::hmac::sign(&key, body);
+    tag.as_ref()
+        .iter()
+        .fold(String::from("sha256="), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+async fn deliver_all(
+    client: reqwest::Client,
+    settings: ApprovalWebhookSettings,
+    first_retry_delay: Duration,
+    mut events: mpsc::Receiver<ApprovalHookEvent>,
+) {
+    while let Some(event) = events.recv().await {
+        let body = match serde_json::to_vec(&event) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!(error = %e, "Failed to serialize approval webhook event");
+                continue;
+            }
+        };
+        let delivery_id = uuid::Uuid::new_v4().to_string();
+        let mut delay = first_retry_delay;
+        for attempt in 1..=MAX_ATTEMPTS {
+            let mut request = client
+                .post(&settings.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(EVENT_HEADER, event.event.as_str())
+                .header(DELIVERY_HEADER, &delivery_id)
+                .timeout(ATTEMPT_TIMEOUT)
+                .body(body.clone());
+            if let Some(secret) = &settings.secret {
+                request = request.header(SIGNATURE_HEADER, signature(secret, &body));
+            }
+            match request
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+            {
+                Ok(_) => {
+                    debug!(
+                        session_id = %event.session_id,
+                        approval_id = %event.approval_id,
+                        event = event.event.as_str(),
+                        attempt,
+                        "Delivered approval webhook"
+                    );
+                    break;
+                }
+                Err(e) if attempt < MAX_ATTEMPTS => {
+                    debug!(
+                        approval_id = %event.approval_id,
+                        attempt,
+                        error = %e,
+                        "Approval webhook failed, retrying"
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(e) => warn!(
+                    url = %settings.url,
+                    session_id = %event.session_id,
+                    approval_id = %event.approval_id,
+                    event = event.event.as_str(),
+                    error = %e,
+                    "Giving up on approval webhook after {MAX_ATTEMPTS} attempts"
+                ),
+            }
+        }
+    }
+}
+
+/// Turns a session's approval broadcasts into webhook events, and sends `timed_out`
+/// for requests still undecided after the configured time.
+///
+/// Runs until the session's approval channel closes. The session ID is read on every
+/// event since it changes when a resumed session settles on its new ID.
+pub async fn follow_session(
+    webhook: Arc<ApprovalWebhook>,
+    session_id: Arc<RwLock<String>>,
+    working_directory: PathBuf,
+    mut approvals: broadcast::Receiver<ApprovalMessage>,
+) {
+    // Undecided requests by ID, and when they time out
+    let mut pending: HashMap<String, (Instant, serde_json::Value)> = HashMap::new();
+
+    loop {
+        let next_timeout = pending.values().map(|(deadline, _)| *deadline).min();
+        let (event, approval_id, data) = tokio::select! {
+            message = approvals.recv() => match message {
+                Ok(ApprovalMessage::ApprovalRequest { request, .. }) => {
+                    // Restored approvals are broadcast again, they only need announcing once
+                    if pending.contains_key(&request.id) {
+                        continue;
+                    }
+                    let data = serde_json::json!({
+                        "tool_name": request.request.get("tool_name"),
+                        "input": request.request.get("input"),
+                        "risk": request.risk,
+                        "risk_reasons": request.risk_reasons,
+                    });
+                    if let Some(timeout) = webhook.timeout {
+                        pending.insert(request.id.clone(), (Instant::now() + timeout, data.clone()));
+                    }
+                    (ApprovalEvent::Requested, request.id.clone(), data)
+                }
+                Ok(ApprovalMessage::ApprovalResponse(response)) => {
+                    let Some(approval_id) = response.get("id").and_then(|id| id.as_str()) else {
+                        continue;
+                    };
+                    pending.remove(approval_id);
+                    (
+                        ApprovalEvent::Resolved,
+                        approval_id.to_string(),
+                        serde_json::json!({
+                            "behavior": response.get("response").and_then(|r| r.get("behavior")),
+                            "decided_by": response.get(DECIDED_BY_FIELD),
+                        }),
+                    )
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            () = tokio::time::sleep_until(next_timeout.unwrap_or_else(Instant::now)),
+                if next_timeout.is_some() =>
+            {
+                let now = Instant::now();
+                let Some(approval_id) = pending
+                    .iter()
+                    .find(|(_, (deadline, _))| *deadline <= now)
+                    .map(|(id, _)| id.clone())
+                else {
+                    continue;
+                };
+                let Some((_, data)) = pending.remove(&approval_id) else {
+                    continue;
+                };
+                info!(approval_id = %approval_id, "Approval request timed out");
+                (ApprovalEvent::TimedOut, approval_id, data)
+            }
+        };
+
+        webhook.send(ApprovalHookEvent {
+            event,
+            session_id: session_id.read().await.clone(),
+            working_directory: working_directory.clone(),
+            approval_id,
+            timestamp: chrono::Utc::now(),
+            data,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderMap, StatusCode};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_signature() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_deliveries_are_retried() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let app = axum::Router::new().route(
+            "/hook",
+            axum::routing::post({
+                let attempts = attempts.clone();
+                let received = received.clone();
+                move |headers: HeaderMap, body: String| async move {
+                    // The first attempt fails
+                    if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                        return StatusCode::SERVICE_UNAVAILABLE;
+                    }
+                    received.lock().unwrap().push((headers, body));
+                    StatusCode::OK
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let settings = ApprovalWebhookSettings {
+            url,
+            secret: Some("s3cret".to_string()),
+            timeout: None,
+        };
+        let (queue, events) = mpsc::channel(QUEUE_CAPACITY);
+        let delivery = tokio::spawn(deliver_all(
+            reqwest::Client::new(),
+            settings,
+            Duration::from_millis(10),
+            events,
+        ));
+        queue
+            .send(ApprovalHookEvent {
+                event: ApprovalEvent::Requested,
+                session_id: "s1".to_string(),
+                working_directory: PathBuf::from("/work"),
+                approval_id: "a1".to_string(),
+                timestamp: chrono::Utc::now(),
+                data: serde_json::json!({"tool_name": "Bash"}),
+            })
+            .await
+            .unwrap();
+        drop(queue);
+        delivery.await.unwrap();
+
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        let received = received.lock().unwrap();
+        let (headers, body) = &received[0];
+        assert_eq!(headers[EVENT_HEADER], "approval.requested");
+        assert_eq!(
+            headers[SIGNATURE_HEADER].to_str().unwrap(),
+            signature("s3cret", body.as_bytes())
+        );
+        let event: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(event["event"], "requested");
+        assert_eq!(event["approval_id"], "a1");
+    }
+}
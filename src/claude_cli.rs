@@ -0,0 +1,378 @@
+use crate::models::SessionOptions;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+/// How long `claude --version` may take before we give up and assume the latest CLI.
+const VERSION_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CliVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl CliVersion {
+    #[must_use]
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Extracts the first `x.y.z` token from `claude --version` output,
+    /// e.g. `1.0.43 (Claude Code)`.
+    #[must_use]
+    pub fn parse(output: &str) -> Option<Self> {
+        output.split_whitespace().find_map(|token| {
+            let mut parts = token.trim_start_matches('v').splitn(3, '.');
+            let major = parts.next()?.parse().ok()?;
+            let minor = parts.next()?.parse().ok()?;
+            let patch = parts
+                .next()?
+                .split(|c: char| !c.is_ascii_digit())
+                .next()?
+                .parse()
+                .ok()?;
+            Some(Self::new(major, minor, patch))
+        })
+    }
+}
+
+impl std::fmt::Display for CliVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Flags and stream formats a given CLI release understands.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// `--input-format stream-json`
+    pub stream_json_input: bool,
+    /// `--permission-prompt-tool stdio`
+    pub permission_prompt_tool: bool,
+    /// `--session-id` for choosing the ID of a new session
+    pub session_id_flag: bool,
+    /// `--system-prompt`
+    pub system_prompt_flag: bool,
+    /// `--append-system-prompt`
+    pub append_system_prompt_flag: bool,
+    /// `--include-partial-messages` for `stream_event` output while a reply is generated
+    pub include_partial_messages: bool,
+}
+
+/// Capabilities by the first release that has them, oldest first.
+const CAPABILITY_TABLE: &[(CliVersion, Capabilities)] = &[
+    (
+        CliVersion::new(0, 0, 0),
+        Capabilities {
+            stream_json_input: false,
+            permission_prompt_tool: false,
+            session_id_flag: false,
+            system_prompt_flag: false,
+            append_system_prompt_flag: true,
+            include_partial_messages: false,
+        },
+    ),
+    (
+        CliVersion::new(1, 0, 0),
+        Capabilities {
+            stream_json_input: true,
+            permission_prompt_tool: true,
+            session_id_flag: true,
+            system_prompt_flag: true,
+            append_system_prompt_flag: true,
+            include_partial_messages: false,
+        },
+    ),
+    (
+        CliVersion::new(1, 0, 86),
+        Capabilities {
+            stream_json_input: true,
+            permission_prompt_tool: true,
+            session_id_flag: true,
+            system_prompt_flag: true,
+            append_system_prompt_flag: true,
+            include_partial_messages: true,
+        },
+    ),
+];
+
+impl Capabilities {
+    /// Looks up the capabilities for `version`. An unknown version is assumed
+    /// to be a current release.
+    #[must_use]
+    pub fn for_version(version: Option<CliVersion>) -> Self {
+        let latest = CAPABILITY_TABLE[CAPABILITY_TABLE.len() - 1].1;
+        let Some(version) = version else {
+            return latest;
+        };
+        CAPABILITY_TABLE
+            .iter()
+            .rev()
+            .find(|(since, _)| *since <= version)
+            .map_or(latest, |(_, capabilities)| *capabilities)
+    }
+}
+
+fn version_cache() -> &'static Mutex<HashMap<PathBuf, Option<CliVersion>>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, Option<CliVersion>>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Runs `<binary> --version` once per binary and returns the parsed version, if any.
+///
+/// # Panics
+///
+/// Panics if the version cache lock is poisoned.
+pub async fn detect_version(binary: &Path) -> Option<CliVersion> {
+    if let Some(cached) = version_cache()
+        .lock()
+        .expect("version cache poisoned")
+        .get(binary)
+    {
+        return *cached;
+    }
+
+    let probe = Command::new(binary)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+
+    let version = match tokio::time::timeout(VERSION_PROBE_TIMEOUT, probe).await {
+        Ok(Ok(output)) => CliVersion::parse(&String::from_utf8_lossy(&output.stdout)),
+        Ok(Err(e)) => {
+            warn!(claude_binary = %binary.display(), error = %e, "Failed to run Claude version probe");
+            None
+        }
+        Err(_) => {
+            warn!(claude_binary = %binary.display(), "Claude version probe timed out");
+            None
+        }
+    };
+
+    if let Some(version) = version {
+        info!(claude_binary = %binary.display(), %version, "Detected Claude CLI version");
+    } else {
+        info!(
+            claude_binary = %binary.display(),
+            "Could not detect Claude CLI version, assuming latest capabilities"
+        );
+    }
+
+    version_cache()
+        .lock()
+        .expect("version cache poisoned")
+        .insert(binary.to_path_buf(), version);
+    version
+}
+
+/// Builds the argument list for a Claude process, leaving out flags the CLI lacks.
+#[derive(Debug)]
+pub struct SpawnArgs<'a> {
+    capabilities: Capabilities,
+    session_id: &'a str,
+    resume: bool,
+    options: Option<&'a SessionOptions>,
+    partial_messages: bool,
+}
+
+impl<'a> SpawnArgs<'a> {
+    #[must_use]
+    pub fn new(capabilities: Capabilities, session_id: &'a str) -> Self {
+        Self {
+            capabilities,
+            session_id,
+            resume: false,
+            options: None,
+            partial_messages: false,
+        }
+    }
+
+    #[must_use]
+    pub fn resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    #[must_use]
+    pub fn options(mut self, options: &'a SessionOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    #[must_use]
+    pub fn partial_messages(mut self, partial_messages: bool) -> Self {
+        self.partial_messages = partial_messages;
+        self
+    }
+
+    #[must_use]
+    pub fn build(&self) -> Vec<String> {
+        let caps = self.capabilities;
+        let mut args: Vec<String> = vec!["--output-format".into(), "stream-json".into()];
+
+        if caps.stream_json_input {
+            args.extend(["--input-format".into(), "stream-json".into()]);
+        } else {
+            warn!("Claude CLI does not support --input-format stream-json");
+        }
+
+        args.extend(["--verbose".into(), "--print".into()]);
+
+        if caps.permission_prompt_tool {
+            args.extend(["--permission-prompt-tool".into(), "stdio".into()]);
+        } else {
+            warn!(
+                "Claude CLI does not support --permission-prompt-tool, tool approvals are disabled"
+            );
+        }
+
+        if self.partial_messages {
+            if caps.include_partial_messages {
+                args.push("--include-partial-messages".into());
+            } else {
+                warn!("Claude CLI does not support --include-partial-messages, replies will not stream");
+            }
+        }
+
+        if self.resume {
+            args.extend(["--resume".into(), self.session_id.to_string()]);
+            debug!(session_id = %self.session_id, "Using resume mode");
+        } else if caps.session_id_flag {
+            args.extend(["--session-id".into(), self.session_id.to_string()]);
+            debug!(session_id = %self.session_id, "Using new session mode");
+        } else {
+            warn!(
+                session_id = %self.session_id,
+                "Claude CLI does not support --session-id, the CLI will pick its own ID"
+            );
+        }
+
+        if let Some(options) = self.options {
+            if let Some(system_prompt) = &options.system_prompt {
+                if caps.system_prompt_flag {
+                    args.extend(["--system-prompt".into(), system_prompt.clone()]);
+                } else {
+                    warn!("Claude CLI does not support --system-prompt, ignoring it");
+                }
+            }
+            if let Some(append_system_prompt) = &options.append_system_prompt {
+                if caps.append_system_prompt_flag {
+                    args.extend([
+                        "--append-system-prompt".into(),
+                        append_system_prompt.clone(),
+                    ]);
+                } else {
+                    warn!("Claude CLI does not support --append-system-prompt, ignoring it");
+                }
+            }
+            for (flag, value) in &options.cli_flags {
+                args.extend([format!("--{flag}"), value.clone()]);
+            }
+        }
+
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            CliVersion::parse("1.0.43 (Claude Code)\n"),
+            Some(CliVersion::new(1, 0, 43))
+        );
+        assert_eq!(
+            CliVersion::parse("claude v2.1.0-beta"),
+            Some(CliVersion::new(2, 1, 0))
+        );
+        assert_eq!(CliVersion::parse(""), None);
+        assert_eq!(CliVersion::parse("{\"args\": []}"), None);
+    }
+
+    #[test]
+    fn test_capabilities_for_version() {
+        let legacy = Capabilities::for_version(Some(CliVersion::new(0, 2, 9)));
+        assert!(!legacy.stream_json_input);
+        assert!(!legacy.session_id_flag);
+
+        let current = Capabilities::for_version(Some(CliVersion::new(1, 0, 43)));
+        assert!(current.stream_json_input && current.permission_prompt_tool);
+        assert!(!current.include_partial_messages);
+
+        let latest = Capabilities::for_version(Some(CliVersion::new(2, 0, 0)));
+        assert!(latest.include_partial_messages);
+        assert_eq!(Capabilities::for_version(None), latest);
+    }
+
+    #[test]
+    fn test_spawn_args_degrade_for_legacy_cli() {
+        let options = SessionOptions {
+            system_prompt: Some("terse".to_string()),
+            append_system_prompt: Some("no force push".to_string()),
+            cli_flags: [("model".to_string(), "opus".to_string())].into(),
+            ..Default::default()
+        };
+
+        let current = SpawnArgs::new(Capabilities::for_version(None), "abc")
+            .options(&options)
+            .build();
+        assert!(current.windows(2).any(|w| w == ["--session-id", "abc"]));
+        assert!(current
+            .windows(2)
+            .any(|w| w == ["--system-prompt", "terse"]));
+        assert!(current.windows(2).any(|w| w == ["--model", "opus"]));
+        assert!(current.contains(&"--permission-prompt-tool".to_string()));
+
+        let legacy = SpawnArgs::new(
+            Capabilities::for_version(Some(CliVersion::new(0, 2, 0))),
+            "abc",
+        )
+        .options(&options)
+        .build();
+        assert!(!legacy.contains(&"--session-id".to_string()));
+        assert!(!legacy.contains(&"--system-prompt".to_string()));
+        assert!(!legacy.contains(&"--input-format".to_string()));
+        assert!(legacy
+            .windows(2)
+            .any(|w| w == ["--append-system-prompt", "no force push"]));
+
+        let resumed = SpawnArgs::new(Capabilities::for_version(None), "abc")
+            .resume(true)
+            .build();
+        assert!(resumed.windows(2).any(|w| w == ["--resume", "abc"]));
+        assert!(!resumed.contains(&"--session-id".to_string()));
+    }
+
+    #[test]
+    fn test_spawn_args_partial_messages() {
+        let flag = "--include-partial-messages".to_string();
+        let current = SpawnArgs::new(Capabilities::for_version(None), "abc");
+        assert!(!current.build().contains(&flag));
+        assert!(current.partial_messages(true).build().contains(&flag));
+
+        let older = SpawnArgs::new(
+            Capabilities::for_version(Some(CliVersion::new(1, 0, 43))),
+            "abc",
+        )
+        .partial_messages(true)
+        .build();
+        assert!(!older.contains(&flag));
+    }
+}
@@ -1,6 +1,9 @@
+use crate::claude_cli::{detect_version, Capabilities, SpawnArgs};
 use crate::config::Config;
 use crate::error::{OrchestratorError, OrchestratorResult};
-use crate::models::Session;
+use crate::models::{Session, SessionOptions};
+use crate::resource_limits::{self, SessionCgroup};
+use crate::scratch::SCRATCH_DIR_ENV;
 use anyhow::Result;
 use std::path::Path;
 use std::process::Stdio;
@@ -11,7 +14,7 @@ use tracing::{debug, error, info, instrument, warn};
 
 /// Compacts JSON message to a single line for Claude stdin.
 /// Claude expects each JSON message to be on a single line.
-fn compact_json_message(message: &str, context: &str) -> OrchestratorResult<String> {
+pub(crate) fn compact_json_message(message: &str, context: &str) -> OrchestratorResult<String> {
     match serde_json::from_str::<serde_json::Value>(message) {
         Ok(parsed) => match serde_json::to_string(&parsed) {
             Ok(compacted) => {
@@ -52,6 +55,8 @@ pub struct ClaudeProcess {
     pub child: Child,
     pub stdin_tx: mpsc::Sender<String>,
     pub stdout_rx: mpsc::Receiver<String>,
+    /// Cgroup Claude and its tools run in, if `CLAUDE_CGROUP_ROOT` is set
+    pub cgroup: Option<SessionCgroup>,
 }
 
 impl ClaudeProcess {
@@ -64,7 +69,6 @@ impl ClaudeProcess {
     /// - Process communication channels cannot be established  
     /// - Initial process setup or communication fails
     /// - Working directory is invalid or inaccessible
-    #[allow(clippy::too_many_lines)]
     #[instrument(skip(config), fields(
         session_id = %session_id,
         working_dir = %working_dir.display(),
@@ -78,6 +82,8 @@ impl ClaudeProcess {
         working_dir: &Path,
         resume: bool,
         bootstrap_messages: &[String],
+        options: &SessionOptions,
+        scratch_dir: Option<&Path>,
     ) -> OrchestratorResult<(Self, String)> {
         info!(
             session_id = %session_id,
@@ -89,28 +95,19 @@ impl ClaudeProcess {
 
         // Build the command
         let mut cmd = Command::new(&config.claude_binary_path);
-        cmd.current_dir(working_dir)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::inherit());
-
-        cmd.arg("--output-format");
-        cmd.arg("stream-json");
-        cmd.arg("--input-format");
-        cmd.arg("stream-json");
-        cmd.arg("--verbose");
-        cmd.arg("--print");
-        cmd.arg("--permission-prompt-tool");
-        cmd.arg("stdio");
-
-        if resume {
-            cmd.arg("--resume");
-            cmd.arg(session_id);
-            debug!(session_id = %session_id, "Using resume mode");
-        } else {
-            cmd.arg("--session-id");
-            cmd.arg(session_id);
-            debug!(session_id = %session_id, "Using new session mode");
+        cmd.current_dir(working_dir);
+
+        let capabilities =
+            Capabilities::for_version(detect_version(&config.claude_binary_path).await);
+        cmd.args(
+            SpawnArgs::new(capabilities, session_id)
+                .resume(resume)
+                .options(options)
+                .partial_messages(config.partial_messages)
+                .build(),
+        );
+        if let Some(scratch_dir) = scratch_dir {
+            cmd.env(SCRATCH_DIR_ENV, scratch_dir);
         }
 
         debug!(
@@ -138,12 +135,47 @@ impl ClaudeProcess {
             "Claude binary exists, attempting to spawn process"
         );
 
+        let cgroup = resource_limits::apply(&config.resource_limits, &mut cmd, session_id)?;
+        match Self::spawn_command(cmd, session_id, resume, bootstrap_messages).await {
+            Ok((mut process, session_id)) => {
+                process.cgroup = cgroup;
+                Ok((process, session_id))
+            }
+            Err(e) => {
+                if let Some(cgroup) = cgroup {
+                    cgroup.remove().await;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Spawns an already built command that runs Claude in stream-json mode, sends the
+    /// bootstrap messages and wires up its stdio. On resume, waits for the session ID
+    /// Claude actually uses.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the command cannot be spawned, exits straight away, or does
+    /// not report a session ID when resuming.
+    #[allow(clippy::too_many_lines)]
+    pub(crate) async fn spawn_command(
+        mut cmd: Command,
+        session_id: &str,
+        resume: bool,
+        bootstrap_messages: &[String],
+    ) -> OrchestratorResult<(Self, String)> {
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            // Only dropped without being waited on when the session start is abandoned
+            .kill_on_drop(true);
+
         // Spawn the process
         let mut child = cmd.spawn().map_err(|e| {
             error!(
                 session_id = %session_id,
-                working_dir = %working_dir.display(),
-                claude_binary = %config.claude_binary_path.display(),
+                command = ?cmd.as_std(),
                 error = %e,
                 "Failed to spawn Claude process"
             );
@@ -591,6 +623,7 @@ impl ClaudeProcess {
                 child,
                 stdin_tx,
                 stdout_rx,
+                cgroup: None,
             },
             actual_session_id,
         ))
@@ -781,6 +814,7 @@ mod tests {
     use tempfile::TempDir;
 
     #[tokio::test]
+    #[allow(clippy::too_many_lines)]
     async fn test_spawn_claude_process() {
         let temp_dir = TempDir::new().unwrap();
         let working_dir = temp_dir.path().join("work");
@@ -807,6 +841,53 @@ mod tests {
             http_listen_address: "127.0.0.1:8080".to_string(),
             claude_projects_dir: projects_dir.clone(),
             shutdown_timeout: std::time::Duration::from_secs(30),
+            terminate_timeout: std::time::Duration::from_secs(30),
+            shutdown_policies: crate::config::ShutdownPolicies::default(),
+            stdout_mode: crate::config::StdoutMode::Strict,
+            session_state_dir: None,
+            approval_sync_dir: None,
+            mdns_instance_name: None,
+            transcript_hooks: std::collections::HashMap::new(),
+            security_headers: crate::config::SecurityHeaders::default(),
+            claude_sdk_socket: None,
+            disconnect_queue_policy: crate::config::DisconnectQueuePolicy::default(),
+            notification_targets: std::collections::HashMap::new(),
+            dead_session_grace_period: std::time::Duration::from_secs(60),
+            telemetry_endpoint: None,
+            working_dir_roots: Vec::new(),
+            identity_header: None,
+            api_keys: crate::config::ApiKeys::default(),
+            control_routes: crate::config::ControlRoutes::default(),
+            session_limits: crate::config::SessionLimits::default(),
+            session_capacity: None,
+            partial_messages: false,
+            process_monitoring: crate::config::ProcessMonitoring::default(),
+            resource_limits: crate::config::ResourceLimits::default(),
+            restart_policy: None,
+            cli_flag_allowlist: crate::config::CliFlagAllowlist::default(),
+            session_response_max_bytes: None,
+            container: None,
+            session_backend: None,
+            risk_approvers: crate::approval_risk::RiskApprovers::default(),
+            risk_patterns: crate::approval_risk::RiskPatterns::default(),
+            working_dir_sandbox: crate::working_dir_sandbox::SandboxMode::default(),
+            frontend_dir: None,
+            event_sinks: Vec::new(),
+            body_limits: crate::config::BodyLimits::default(),
+            record_client_inputs: false,
+            web_push: None,
+            tls: None,
+            write_pacing: None,
+            approval_priority: None,
+            approval_webhook: None,
+            slack: None,
+            push: None,
+            scratch: None,
+            wire_log: None,
+            slo: crate::config::SloSettings::default(),
+            heartbeat: crate::config::Heartbeat::default(),
+            swagger_ui: false,
+            cors_allowed_origins: Vec::new(),
         };
 
         // Create session file first using control command
@@ -831,6 +912,8 @@ mod tests {
                 create_file_command,
                 r#"{"role": "user", "content": "Hello Claude"}"#.to_string(),
             ],
+            &SessionOptions::default(),
+            None,
         )
         .await
         .unwrap();
@@ -898,6 +981,53 @@ done
             http_listen_address: "127.0.0.1:8080".to_string(),
             claude_projects_dir: temp_dir.path().to_path_buf(),
             shutdown_timeout: std::time::Duration::from_secs(30),
+            terminate_timeout: std::time::Duration::from_secs(30),
+            shutdown_policies: crate::config::ShutdownPolicies::default(),
+            stdout_mode: crate::config::StdoutMode::Strict,
+            session_state_dir: None,
+            approval_sync_dir: None,
+            mdns_instance_name: None,
+            transcript_hooks: std::collections::HashMap::new(),
+            security_headers: crate::config::SecurityHeaders::default(),
+            claude_sdk_socket: None,
+            disconnect_queue_policy: crate::config::DisconnectQueuePolicy::default(),
+            notification_targets: std::collections::HashMap::new(),
+            dead_session_grace_period: std::time::Duration::from_secs(60),
+            telemetry_endpoint: None,
+            working_dir_roots: Vec::new(),
+            identity_header: None,
+            api_keys: crate::config::ApiKeys::default(),
+            control_routes: crate::config::ControlRoutes::default(),
+            session_limits: crate::config::SessionLimits::default(),
+            session_capacity: None,
+            partial_messages: false,
+            process_monitoring: crate::config::ProcessMonitoring::default(),
+            resource_limits: crate::config::ResourceLimits::default(),
+            restart_policy: None,
+            cli_flag_allowlist: crate::config::CliFlagAllowlist::default(),
+            session_response_max_bytes: None,
+            container: None,
+            session_backend: None,
+            risk_approvers: crate::approval_risk::RiskApprovers::default(),
+            risk_patterns: crate::approval_risk::RiskPatterns::default(),
+            working_dir_sandbox: crate::working_dir_sandbox::SandboxMode::default(),
+            frontend_dir: None,
+            event_sinks: Vec::new(),
+            body_limits: crate::config::BodyLimits::default(),
+            record_client_inputs: false,
+            web_push: None,
+            tls: None,
+            write_pacing: None,
+            approval_priority: None,
+            approval_webhook: None,
+            slack: None,
+            push: None,
+            scratch: None,
+            wire_log: None,
+            slo: crate::config::SloSettings::default(),
+            heartbeat: crate::config::Heartbeat::default(),
+            swagger_ui: false,
+            cors_allowed_origins: Vec::new(),
         };
 
         let (process, actual_session_id) = ClaudeProcess::spawn(
@@ -906,6 +1036,8 @@ done
             &working_dir,
             true,
             &[r#"{"role": "user", "content": "Resume session"}"#.to_string()],
+            &SessionOptions::default(),
+            None,
         )
         .await
         .unwrap();
@@ -916,6 +1048,7 @@ done
     }
 
     #[tokio::test]
+    #[allow(clippy::too_many_lines)]
     async fn test_spawn_claude_process_with_multiline_bootstrap_messages() {
         let temp_dir = TempDir::new().unwrap();
         let working_dir = temp_dir.path().join("work");
@@ -942,6 +1075,53 @@ done
             http_listen_address: "127.0.0.1:8080".to_string(),
             claude_projects_dir: projects_dir.clone(),
             shutdown_timeout: std::time::Duration::from_secs(30),
+            terminate_timeout: std::time::Duration::from_secs(30),
+            shutdown_policies: crate::config::ShutdownPolicies::default(),
+            stdout_mode: crate::config::StdoutMode::Strict,
+            session_state_dir: None,
+            approval_sync_dir: None,
+            mdns_instance_name: None,
+            transcript_hooks: std::collections::HashMap::new(),
+            security_headers: crate::config::SecurityHeaders::default(),
+            claude_sdk_socket: None,
+            disconnect_queue_policy: crate::config::DisconnectQueuePolicy::default(),
+            notification_targets: std::collections::HashMap::new(),
+            dead_session_grace_period: std::time::Duration::from_secs(60),
+            telemetry_endpoint: None,
+            working_dir_roots: Vec::new(),
+            identity_header: None,
+            api_keys: crate::config::ApiKeys::default(),
+            control_routes: crate::config::ControlRoutes::default(),
+            session_limits: crate::config::SessionLimits::default(),
+            session_capacity: None,
+            partial_messages: false,
+            process_monitoring: crate::config::ProcessMonitoring::default(),
+            resource_limits: crate::config::ResourceLimits::default(),
+            restart_policy: None,
+            cli_flag_allowlist: crate::config::CliFlagAllowlist::default(),
+            session_response_max_bytes: None,
+            container: None,
+            session_backend: None,
+            risk_approvers: crate::approval_risk::RiskApprovers::default(),
+            risk_patterns: crate::approval_risk::RiskPatterns::default(),
+            working_dir_sandbox: crate::working_dir_sandbox::SandboxMode::default(),
+            frontend_dir: None,
+            event_sinks: Vec::new(),
+            body_limits: crate::config::BodyLimits::default(),
+            record_client_inputs: false,
+            web_push: None,
+            tls: None,
+            write_pacing: None,
+            approval_priority: None,
+            approval_webhook: None,
+            slack: None,
+            push: None,
+            scratch: None,
+            wire_log: None,
+            slo: crate::config::SloSettings::default(),
+            heartbeat: crate::config::Heartbeat::default(),
+            swagger_ui: false,
+            cors_allowed_origins: Vec::new(),
         };
 
         // Test with multi-line first message - use serde_json for proper JSON formatting
@@ -964,10 +1144,17 @@ done
             r#"{"role": "user", "content": "Second message"}"#.to_string(),
         ];
 
-        let (mut process, session_id) =
-            ClaudeProcess::spawn(&config, "test-session", &working_dir, false, &messages)
-                .await
-                .unwrap();
+        let (mut process, session_id) = ClaudeProcess::spawn(
+            &config,
+            "test-session",
+            &working_dir,
+            false,
+            &messages,
+            &SessionOptions::default(),
+            None,
+        )
+        .await
+        .unwrap();
 
         assert_eq!(session_id, "test-session");
 
@@ -1015,6 +1202,53 @@ done
             http_listen_address: "127.0.0.1:8080".to_string(),
             claude_projects_dir: projects_dir.clone(),
             shutdown_timeout: std::time::Duration::from_secs(30),
+            terminate_timeout: std::time::Duration::from_secs(30),
+            shutdown_policies: crate::config::ShutdownPolicies::default(),
+            stdout_mode: crate::config::StdoutMode::Strict,
+            session_state_dir: None,
+            approval_sync_dir: None,
+            mdns_instance_name: None,
+            transcript_hooks: std::collections::HashMap::new(),
+            security_headers: crate::config::SecurityHeaders::default(),
+            claude_sdk_socket: None,
+            disconnect_queue_policy: crate::config::DisconnectQueuePolicy::default(),
+            notification_targets: std::collections::HashMap::new(),
+            dead_session_grace_period: std::time::Duration::from_secs(60),
+            telemetry_endpoint: None,
+            working_dir_roots: Vec::new(),
+            identity_header: None,
+            api_keys: crate::config::ApiKeys::default(),
+            control_routes: crate::config::ControlRoutes::default(),
+            session_limits: crate::config::SessionLimits::default(),
+            session_capacity: None,
+            partial_messages: false,
+            process_monitoring: crate::config::ProcessMonitoring::default(),
+            resource_limits: crate::config::ResourceLimits::default(),
+            restart_policy: None,
+            cli_flag_allowlist: crate::config::CliFlagAllowlist::default(),
+            session_response_max_bytes: None,
+            container: None,
+            session_backend: None,
+            risk_approvers: crate::approval_risk::RiskApprovers::default(),
+            risk_patterns: crate::approval_risk::RiskPatterns::default(),
+            working_dir_sandbox: crate::working_dir_sandbox::SandboxMode::default(),
+            frontend_dir: None,
+            event_sinks: Vec::new(),
+            body_limits: crate::config::BodyLimits::default(),
+            record_client_inputs: false,
+            web_push: None,
+            tls: None,
+            write_pacing: None,
+            approval_priority: None,
+            approval_webhook: None,
+            slack: None,
+            push: None,
+            scratch: None,
+            wire_log: None,
+            slo: crate::config::SloSettings::default(),
+            heartbeat: crate::config::Heartbeat::default(),
+            swagger_ui: false,
+            cors_allowed_origins: Vec::new(),
         };
 
         // Test with empty lines that should be skipped - use serde_json for proper JSON formatting
@@ -1045,6 +1279,8 @@ done
             &working_dir,
             false,
             &messages_with_empty_lines,
+            &SessionOptions::default(),
+            None,
         )
         .await
         .unwrap();
@@ -1053,4 +1289,121 @@ done
 
         process.kill().await.unwrap();
     }
+
+    #[tokio::test]
+    #[allow(clippy::too_many_lines)]
+    async fn test_spawn_passes_system_prompt_flags() {
+        let temp_dir = TempDir::new().unwrap();
+
+        // Mock that reports its own argv as the first stdout line
+        let script_path = temp_dir.path().join("mock_claude_args");
+        let script_content = r#"#!/usr/bin/env python3
+import json, sys
+print(json.dumps({"type": "args", "args": sys.argv[1:]}), flush=True)
+for line in sys.stdin:
+    pass
+"#;
+        fs::write(&script_path, script_content).unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mut perms = fs::metadata(&script_path).unwrap().permissions();
+            perms.set_mode(0o755);
+            fs::set_permissions(&script_path, perms).unwrap();
+        }
+
+        let working_dir = temp_dir.path().join("work");
+        fs::create_dir_all(&working_dir).unwrap();
+
+        let config = Config {
+            claude_binary_path: script_path,
+            http_listen_address: "127.0.0.1:8080".to_string(),
+            claude_projects_dir: temp_dir.path().to_path_buf(),
+            shutdown_timeout: std::time::Duration::from_secs(30),
+            terminate_timeout: std::time::Duration::from_secs(30),
+            shutdown_policies: crate::config::ShutdownPolicies::default(),
+            stdout_mode: crate::config::StdoutMode::Strict,
+            session_state_dir: None,
+            approval_sync_dir: None,
+            mdns_instance_name: None,
+            transcript_hooks: std::collections::HashMap::new(),
+            security_headers: crate::config::SecurityHeaders::default(),
+            claude_sdk_socket: None,
+            disconnect_queue_policy: crate::config::DisconnectQueuePolicy::default(),
+            notification_targets: std::collections::HashMap::new(),
+            dead_session_grace_period: std::time::Duration::from_secs(60),
+            telemetry_endpoint: None,
+            working_dir_roots: Vec::new(),
+            identity_header: None,
+            api_keys: crate::config::ApiKeys::default(),
+            control_routes: crate::config::ControlRoutes::default(),
+            session_limits: crate::config::SessionLimits::default(),
+            session_capacity: None,
+            partial_messages: false,
+            process_monitoring: crate::config::ProcessMonitoring::default(),
+            resource_limits: crate::config::ResourceLimits::default(),
+            restart_policy: None,
+            cli_flag_allowlist: crate::config::CliFlagAllowlist::default(),
+            session_response_max_bytes: None,
+            container: None,
+            session_backend: None,
+            risk_approvers: crate::approval_risk::RiskApprovers::default(),
+            risk_patterns: crate::approval_risk::RiskPatterns::default(),
+            working_dir_sandbox: crate::working_dir_sandbox::SandboxMode::default(),
+            frontend_dir: None,
+            event_sinks: Vec::new(),
+            body_limits: crate::config::BodyLimits::default(),
+            record_client_inputs: false,
+            web_push: None,
+            tls: None,
+            write_pacing: None,
+            approval_priority: None,
+            approval_webhook: None,
+            slack: None,
+            push: None,
+            scratch: None,
+            wire_log: None,
+            slo: crate::config::SloSettings::default(),
+            heartbeat: crate::config::Heartbeat::default(),
+            swagger_ui: false,
+            cors_allowed_origins: Vec::new(),
+        };
+
+        let options = SessionOptions {
+            system_prompt: Some("You are terse".to_string()),
+            append_system_prompt: Some("Never push to main".to_string()),
+            ..Default::default()
+        };
+
+        let (mut process, _) = ClaudeProcess::spawn(
+            &config,
+            "prompt-session",
+            &working_dir,
+            false,
+            &[r#"{"role": "user", "content": "Hello"}"#.to_string()],
+            &options,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let line = process.read().await.unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&line).unwrap();
+        let args: Vec<&str> = parsed["args"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|v| v.as_str())
+            .collect();
+
+        let position = |flag: &str| args.iter().position(|a| *a == flag).unwrap();
+        assert_eq!(args[position("--system-prompt") + 1], "You are terse");
+        assert_eq!(
+            args[position("--append-system-prompt") + 1],
+            "Never push to main"
+        );
+
+        process.kill().await.unwrap();
+    }
 }
@@ -0,0 +1,139 @@
+use crate::config::{Config, Settings};
+use crate::{api, config_file, demo, state_migrations, tls};
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+/// Command line of `chef-de-vibe`. Options take precedence over the environment
+/// variables they stand for, which take precedence over the config file; every other
+/// setting comes from the environment (or the config file) as described in the README.
+#[derive(Debug, Parser)]
+#[command(
+    name = "chef-de-vibe",
+    version,
+    about = "Orchestrates Claude Code sessions"
+)]
+pub struct Cli {
+    #[command(flatten)]
+    pub settings: SettingsArgs,
+
+    /// Print what starting up would do to `SESSION_STATE_DIR`, then exit
+    #[arg(long)]
+    pub check_migrations: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Subcommand)]
+pub enum Command {
+    /// Run the server (the default)
+    Serve,
+    /// Run the server in a throwaway environment with example sessions and a fake Claude
+    #[command(name = demo::DEMO_COMMAND)]
+    Demo,
+    /// Load and validate the configuration without starting the server
+    CheckConfig,
+    /// Print the version
+    Version,
+}
+
+#[derive(Debug, Args)]
+pub struct SettingsArgs {
+    /// Address to listen on
+    #[arg(long, global = true, value_name = "ADDR", env = "HTTP_LISTEN_ADDRESS")]
+    pub listen: Option<String>,
+
+    /// The Claude CLI; found in `PATH` when not given
+    #[arg(long, global = true, value_name = "PATH", env = "CLAUDE_BINARY_PATH")]
+    pub claude_binary: Option<PathBuf>,
+
+    /// Where Claude keeps its session journals
+    #[arg(long, global = true, value_name = "DIR", env = "CLAUDE_PROJECTS_DIR")]
+    pub projects_dir: Option<PathBuf>,
+
+    /// TOML or YAML file of settings named like their environment variables; reloaded
+    /// when it changes
+    #[arg(long, global = true, value_name = "FILE", env = "CHEF_DE_VIBE_CONFIG")]
+    pub config: Option<PathBuf>,
+}
+
+impl SettingsArgs {
+    /// The settings these options and the config file give.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the config file cannot be read.
+    pub fn settings(&self) -> Result<Settings> {
+        let mut settings = Settings::default();
+        if let Some(path) = &self.config {
+            settings = settings.with_file(config_file::read(path)?);
+        }
+        if let Some(listen) = &self.listen {
+            settings = settings.with_override("HTTP_LISTEN_ADDRESS", listen);
+        }
+        if let Some(claude_binary) = &self.claude_binary {
+            settings =
+                settings.with_override("CLAUDE_BINARY_PATH", claude_binary.to_string_lossy());
+        }
+        if let Some(projects_dir) = &self.projects_dir {
+            settings =
+                settings.with_override("CLAUDE_PROJECTS_DIR", projects_dir.to_string_lossy());
+        }
+        Ok(settings)
+    }
+}
+
+/// Runs the startup checks that do not change anything: the configuration itself, the
+/// state directory, the frontend assets and the TLS certificate.
+///
+/// # Errors
+///
+/// Returns an error describing the first check that fails.
+pub async fn check_config(config: &Config) -> Result<()> {
+    println!(
+        "{}",
+        state_migrations::check(config.session_state_dir.as_deref())?
+    );
+    api::static_files::init(config.frontend_dir.as_deref())
+        .context("Failed to index frontend directory")?;
+    if let Some(settings) = &config.tls {
+        tls::load(settings).await?;
+    }
+    println!("Claude binary: {}", config.claude_binary_path.display());
+    println!(
+        "Projects directory: {}",
+        config.claude_projects_dir.display()
+    );
+    println!("Listen address: {}", config.http_listen_address);
+    println!("Configuration is valid");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_apply_to_every_subcommand() {
+        let cli = Cli::try_parse_from([
+            "chef-de-vibe",
+            "check-config",
+            "--listen",
+            "0.0.0.0:8080",
+            "--config",
+            "chef.toml",
+        ])
+        .unwrap();
+        assert_eq!(cli.command, Some(Command::CheckConfig));
+        assert_eq!(cli.settings.listen.as_deref(), Some("0.0.0.0:8080"));
+        assert_eq!(cli.settings.config, Some(PathBuf::from("chef.toml")));
+    }
+
+    #[test]
+    fn test_serve_is_the_default() {
+        let cli = Cli::try_parse_from(["chef-de-vibe", "--check-migrations"]).unwrap();
+        assert_eq!(cli.command, None);
+        assert!(cli.check_migrations);
+    }
+}
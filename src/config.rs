@@ -1,14 +1,1747 @@
+use crate::approval_risk::{RiskApprovers, RiskLevel, RiskPatterns};
+use crate::notifications::WatchEvent;
+use crate::session_backend::BackendKind;
+use crate::slo::Operation;
+use crate::working_dir_sandbox::SandboxMode;
 use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::env;
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 
+/// How lines on Claude's stdout that are not valid JSON are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StdoutMode {
+    /// Any invalid line disconnects the session's clients.
+    #[default]
+    Strict,
+    /// Invalid lines are wrapped as `{"type":"raw","text":...}` events and the stream continues.
+    Tolerant,
+}
+
+impl FromStr for StdoutMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "strict" => Ok(Self::Strict),
+            "tolerant" => Ok(Self::Tolerant),
+            other => anyhow::bail!("expected 'strict' or 'tolerant', got '{other}'"),
+        }
+    }
+}
+
+/// What happens to a client's unwritten messages when that client disconnects.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    utoipa::ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum DisconnectQueuePolicy {
+    /// Messages stay queued and are written to Claude as usual.
+    #[default]
+    Keep,
+    /// Messages are removed from the queue.
+    Drop,
+    /// Messages are set aside until released or dropped through the API.
+    Hold,
+}
+
+impl FromStr for DisconnectQueuePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "keep" => Ok(Self::Keep),
+            "drop" => Ok(Self::Drop),
+            "hold" => Ok(Self::Hold),
+            other => anyhow::bail!("expected 'keep', 'drop' or 'hold', got '{other}'"),
+        }
+    }
+}
+
+/// How a `control_request` from Claude is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlRoute {
+    /// Answered right away with an error response, so Claude does not wait on it.
+    Auto,
+    /// Held as a pending approval and sent to the approval clients.
+    Approvals,
+    /// Relayed to the regular clients, which answer with a `control_response`.
+    Main,
+}
+
+impl FromStr for ControlRoute {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "approvals" => Ok(Self::Approvals),
+            "main" => Ok(Self::Main),
+            other => anyhow::bail!("expected 'auto', 'approvals' or 'main', got '{other}'"),
+        }
+    }
+}
+
+/// Routes of `control_request` subtypes, with a fallback for subtypes not listed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlRoutes {
+    routes: HashMap<String, ControlRoute>,
+    fallback: ControlRoute,
+}
+
+impl Default for ControlRoutes {
+    fn default() -> Self {
+        Self {
+            routes: HashMap::from([("can_use_tool".to_string(), ControlRoute::Approvals)]),
+            fallback: ControlRoute::Auto,
+        }
+    }
+}
+
+impl ControlRoutes {
+    #[must_use]
+    pub fn route(&self, subtype: &str) -> ControlRoute {
+        self.routes.get(subtype).copied().unwrap_or(self.fallback)
+    }
+}
+
+/// What happens to a session's Claude process when the orchestrator shuts down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShutdownBehavior {
+    /// SIGTERM, then SIGKILL if Claude is still there after `SHUTDOWN_TIMEOUT`.
+    #[default]
+    Terminate,
+    /// SIGKILL right away.
+    Kill,
+    /// Shutdown waits until Claude has written its queued input and finished its turn,
+    /// or until `deadline`, before terminating it.
+    Protected { deadline: Duration },
+}
+
+impl ShutdownBehavior {
+    /// Orders behaviors by how much they let a session finish, for sessions whose tags
+    /// name several.
+    fn patience(self) -> (u8, Duration) {
+        match self {
+            Self::Kill => (0, Duration::ZERO),
+            Self::Terminate => (1, Duration::ZERO),
+            Self::Protected { deadline } => (2, deadline),
+        }
+    }
+}
+
+impl FromStr for ShutdownBehavior {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.to_ascii_lowercase();
+        match s.split_once(':') {
+            None if s == "terminate" => Ok(Self::Terminate),
+            None if s == "kill" => Ok(Self::Kill),
+            Some(("protected", secs)) => Ok(Self::Protected {
+                deadline: Duration::from_secs(secs.trim().parse().with_context(|| {
+                    format!("expected the deadline in seconds after 'protected:', got '{secs}'")
+                })?),
+            }),
+            _ => anyhow::bail!("expected 'terminate', 'kill' or 'protected:SECONDS', got '{s}'"),
+        }
+    }
+}
+
+/// Shutdown behavior of sessions by tag, with a fallback for sessions whose tags are
+/// not listed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownPolicies {
+    by_tag: HashMap<String, ShutdownBehavior>,
+    fallback: ShutdownBehavior,
+}
+
+impl ShutdownPolicies {
+    /// The behavior for a session with `tags`: the most patient one any of them names.
+    #[must_use]
+    pub fn behavior(&self, tags: &[String]) -> ShutdownBehavior {
+        tags.iter()
+            .filter_map(|tag| self.by_tag.get(tag).copied())
+            .max_by_key(|behavior| behavior.patience())
+            .unwrap_or(self.fallback)
+    }
+}
+
+/// Parses `tag=behavior;other=behavior`; `*` sets the behavior of untagged sessions
+/// and of tags not listed.
+impl FromStr for ShutdownPolicies {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut policies = Self::default();
+        for entry in s.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let Some((tag, behavior)) = entry.split_once('=') else {
+                anyhow::bail!("expected 'tag=behavior', got '{entry}'");
+            };
+            let behavior = behavior.trim().parse()?;
+            match tag.trim() {
+                "" => anyhow::bail!("expected 'tag=behavior', got '{entry}'"),
+                "*" => policies.fallback = behavior,
+                tag => {
+                    policies.by_tag.insert(tag.to_string(), behavior);
+                }
+            }
+        }
+        Ok(policies)
+    }
+}
+
+/// What happens to a session once it reaches one of its limits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LimitAction {
+    /// Claude keeps running but no new input is accepted.
+    #[default]
+    RejectInput,
+    /// Claude is asked to exit.
+    Terminate,
+}
+
+impl FromStr for LimitAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "reject_input" => Ok(Self::RejectInput),
+            "terminate" => Ok(Self::Terminate),
+            other => anyhow::bail!("expected 'reject_input' or 'terminate', got '{other}'"),
+        }
+    }
+}
+
+/// Wall-clock and turn limits of a session. `None` leaves a limit off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionLimits {
+    pub max_duration: Option<Duration>,
+    /// Turns are counted by the `result` messages Claude emits
+    pub max_turns: Option<u32>,
+    pub action: LimitAction,
+}
+
+impl SessionLimits {
+    fn from_settings(settings: &Settings) -> Result<Self> {
+        Ok(Self {
+            max_duration: settings
+                .var("SESSION_MAX_DURATION")
+                .ok()
+                .map(|secs| secs.parse::<u64>().map(Duration::from_secs))
+                .transpose()
+                .context("Invalid SESSION_MAX_DURATION value")?,
+            max_turns: settings
+                .var("SESSION_MAX_TURNS")
+                .ok()
+                .map(|turns| turns.parse::<u32>())
+                .transpose()
+                .context("Invalid SESSION_MAX_TURNS value")?,
+            action: settings
+                .var("SESSION_LIMIT_ACTION")
+                .map_or(Ok(LimitAction::default()), |action| action.parse())
+                .context("Invalid SESSION_LIMIT_ACTION value")?,
+        })
+    }
+
+    /// These limits tightened by a session's own, keeping the lower of each.
+    #[must_use]
+    pub fn narrowed(self, max_duration: Option<Duration>, max_turns: Option<u32>) -> Self {
+        fn lower<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            }
+        }
+        Self {
+            max_duration: lower(self.max_duration, max_duration),
+            max_turns: lower(self.max_turns, max_turns),
+            action: self.action,
+        }
+    }
+}
+
+/// Cap on Claude processes running at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionCapacity {
+    pub max_running: usize,
+    /// How long a start waits for a slot when all are taken; it is refused at once when
+    /// unset.
+    pub queue_timeout: Option<Duration>,
+}
+
+impl SessionCapacity {
+    /// The cap, if `MAX_RUNNING_SESSIONS` sets one.
+    fn from_settings(settings: &Settings) -> Result<Option<Self>> {
+        let Some(max_running) = settings
+            .var("MAX_RUNNING_SESSIONS")
+            .ok()
+            .filter(|max| !max.is_empty())
+        else {
+            return Ok(None);
+        };
+        let max_running = max_running
+            .parse::<usize>()
+            .ok()
+            .filter(|&max| max > 0)
+            .context("Invalid MAX_RUNNING_SESSIONS value: expected a positive number")?;
+        let queue_timeout = settings
+            .var("SESSION_QUEUE_TIMEOUT")
+            .ok()
+            .map(|secs| secs.parse::<u64>().map(Duration::from_secs))
+            .transpose()
+            .context("Invalid SESSION_QUEUE_TIMEOUT value")?;
+        Ok(Some(Self {
+            max_running,
+            queue_timeout,
+        }))
+    }
+}
+
+/// Flags the orchestrator sets itself, which sessions can never override.
+const RESERVED_CLI_FLAGS: &[&str] = &[
+    "output-format",
+    "input-format",
+    "verbose",
+    "print",
+    "permission-prompt-tool",
+    "include-partial-messages",
+    "resume",
+    "continue",
+    "session-id",
+    "system-prompt",
+    "append-system-prompt",
+];
+
+/// Claude CLI flags a session may set for itself when it is created.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CliFlagAllowlist(Vec<String>);
+
+impl CliFlagAllowlist {
+    /// The flags `CLAUDE_CLI_FLAG_ALLOWLIST` lists; none when unset.
+    fn from_settings(settings: &Settings) -> Result<Self> {
+        let Ok(value) = settings.var("CLAUDE_CLI_FLAG_ALLOWLIST") else {
+            return Ok(Self::default());
+        };
+        value
+            .split(',')
+            .map(|flag| flag.trim().trim_start_matches("--"))
+            .filter(|flag| !flag.is_empty())
+            .map(|flag| {
+                if !flag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                    anyhow::bail!("{flag:?} is not a flag name");
+                }
+                if RESERVED_CLI_FLAGS.contains(&flag) {
+                    anyhow::bail!("--{flag} is set by the orchestrator");
+                }
+                Ok(flag.to_string())
+            })
+            .collect::<Result<_>>()
+            .map(Self)
+            .context("Invalid CLAUDE_CLI_FLAG_ALLOWLIST value")
+    }
+
+    /// Checks flags a session asks for, by name without dashes.
+    ///
+    /// # Errors
+    ///
+    /// Returns why the first flag that may not be set is refused. Values may not start
+    /// with `-`, so they cannot pass for another flag.
+    pub fn check<'a>(
+        &self,
+        flags: impl IntoIterator<Item = (&'a String, &'a String)>,
+    ) -> Result<(), String> {
+        for (flag, value) in flags {
+            if !self.0.contains(flag) {
+                return Err(format!(
+                    "CLI flag --{flag} is not in CLAUDE_CLI_FLAG_ALLOWLIST"
+                ));
+            }
+            if value.starts_with('-') {
+                return Err(format!("Value of CLI flag --{flag} may not start with '-'"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Respawning of Claude processes that crash while their session is in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// Restarts a session gets over its lifetime before it is given up
+    pub max_restarts: u32,
+    /// Wait before the first restart, doubled for each further one
+    pub backoff: Duration,
+}
+
+impl RestartPolicy {
+    /// Longest wait between restarts, however many came before.
+    pub const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+    /// The policy, if `CLAUDE_RESTART_MAX` enables restarts.
+    fn from_settings(settings: &Settings) -> Result<Option<Self>> {
+        let max_restarts = settings
+            .var("CLAUDE_RESTART_MAX")
+            .map_or(Ok(0), |max| max.parse::<u32>())
+            .context("Invalid CLAUDE_RESTART_MAX value")?;
+        if max_restarts == 0 {
+            return Ok(None);
+        }
+        let backoff = settings
+            .var("CLAUDE_RESTART_BACKOFF_MS")
+            .map_or(Ok(1000), |ms| ms.parse::<u64>())
+            .context("Invalid CLAUDE_RESTART_BACKOFF_MS value")?;
+        Ok(Some(Self {
+            max_restarts,
+            backoff: Duration::from_millis(backoff),
+        }))
+    }
+
+    /// How long to wait before restart number `attempt`, counting from 1.
+    #[must_use]
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.backoff.saturating_mul(factor).min(Self::MAX_BACKOFF)
+    }
+}
+
+/// Sampling of Claude processes' memory and CPU use, and when to warn about it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProcessMonitoring {
+    /// How often each process is sampled; sampling is off when unset.
+    pub sample_interval: Option<Duration>,
+    pub rss_warning_bytes: Option<u64>,
+    /// CPU use, as a percentage of one core, above which sessions are warned
+    pub cpu_warning_percent: Option<f64>,
+}
+
+impl Default for ProcessMonitoring {
+    fn default() -> Self {
+        Self {
+            sample_interval: Some(Duration::from_secs(10)),
+            rss_warning_bytes: None,
+            cpu_warning_percent: None,
+        }
+    }
+}
+
+impl ProcessMonitoring {
+    fn from_settings(settings: &Settings) -> Result<Self> {
+        let sample_interval = match settings.var("PROCESS_SAMPLE_INTERVAL") {
+            Ok(secs) => Some(secs.parse::<u64>())
+                .transpose()
+                .context("Invalid PROCESS_SAMPLE_INTERVAL value")?
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+            Err(_) => Self::default().sample_interval,
+        };
+        Ok(Self {
+            sample_interval,
+            rss_warning_bytes: settings
+                .var("PROCESS_RSS_WARNING_MB")
+                .ok()
+                .map(|mb| mb.parse::<u64>().map(|mb| mb * 1024 * 1024))
+                .transpose()
+                .context("Invalid PROCESS_RSS_WARNING_MB value")?,
+            cpu_warning_percent: settings
+                .var("PROCESS_CPU_WARNING_PERCENT")
+                .ok()
+                .map(|percent| percent.parse::<f64>())
+                .transpose()
+                .context("Invalid PROCESS_CPU_WARNING_PERCENT value")?,
+        })
+    }
+}
+
+/// Limits on each session's Claude and every process it starts, so a runaway tool
+/// cannot take the machine down. Nothing is limited by default.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceLimits {
+    /// Memory of a session's whole process tree with a cgroup, of each process otherwise
+    pub memory_bytes: Option<u64>,
+    /// CPU time each process may use before it is killed
+    pub cpu_secs: Option<u64>,
+    /// CPU cores a session's processes may keep busy together; needs a cgroup
+    pub cpu_cores: Option<f64>,
+    /// Processes a session may run at once with a cgroup; those of the user otherwise
+    pub max_processes: Option<u64>,
+    /// Delegated cgroup v2 directory every session gets a cgroup of its own under
+    pub cgroup_root: Option<PathBuf>,
+}
+
+impl ResourceLimits {
+    fn from_settings(settings: &Settings) -> Result<Self> {
+        let number = |name: &str| {
+            settings
+                .var(name)
+                .ok()
+                .map(|value| value.parse::<u64>())
+                .transpose()
+                .with_context(|| format!("Invalid {name} value"))
+        };
+        let limits = Self {
+            memory_bytes: number("CLAUDE_MEMORY_LIMIT_MB")?.map(|mb| mb * 1024 * 1024),
+            cpu_secs: number("CLAUDE_CPU_LIMIT_SECS")?,
+            cpu_cores: settings
+                .var("CLAUDE_CPU_LIMIT_CORES")
+                .ok()
+                .map(|cores| cores.parse::<f64>())
+                .transpose()
+                .context("Invalid CLAUDE_CPU_LIMIT_CORES value")?
+                .filter(|cores| *cores > 0.0),
+            max_processes: number("CLAUDE_PROCESS_LIMIT")?,
+            cgroup_root: settings
+                .var("CLAUDE_CGROUP_ROOT")
+                .ok()
+                .filter(|root| !root.is_empty())
+                .map(PathBuf::from),
+        };
+        if limits.cpu_cores.is_some() && limits.cgroup_root.is_none() {
+            anyhow::bail!("CLAUDE_CPU_LIMIT_CORES needs CLAUDE_CGROUP_ROOT");
+        }
+        Ok(limits)
+    }
+
+    /// Whether Claude runs without any limit.
+    #[must_use]
+    pub fn is_unlimited(&self) -> bool {
+        self.memory_bytes.is_none()
+            && self.cpu_secs.is_none()
+            && self.cpu_cores.is_none()
+            && self.max_processes.is_none()
+    }
+}
+
+/// Pings the server sends on WebSocket connections, so clients that vanished without
+/// closing their connection (behind NAT or a proxy) are dropped instead of lingering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Heartbeat {
+    /// How often each connection is pinged; pings are off when unset.
+    pub interval: Option<Duration>,
+    /// Pings in a row a client may leave unanswered before it is disconnected
+    pub max_missed_pongs: u32,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self {
+            interval: Some(Duration::from_secs(30)),
+            max_missed_pongs: 3,
+        }
+    }
+}
+
+impl Heartbeat {
+    fn from_settings(settings: &Settings) -> Result<Self> {
+        let interval = match settings.var("WEBSOCKET_PING_INTERVAL") {
+            Ok(secs) => Some(secs.parse::<u64>())
+                .transpose()
+                .context("Invalid WEBSOCKET_PING_INTERVAL value")?
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+            Err(_) => Self::default().interval,
+        };
+        let max_missed_pongs = match settings.var("WEBSOCKET_MAX_MISSED_PONGS") {
+            Ok(count) => count
+                .parse::<u32>()
+                .ok()
+                .filter(|count| *count > 0)
+                .context("WEBSOCKET_MAX_MISSED_PONGS must be a positive number")?,
+            Err(_) => Self::default().max_missed_pongs,
+        };
+        Ok(Self {
+            interval,
+            max_missed_pongs,
+        })
+    }
+}
+
+/// Rate at which queued messages are written to each session's Claude, so a client
+/// sending in a tight loop cannot flood its stdin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WritePacing {
+    pub messages_per_second: f64,
+    /// Messages that may be written back to back before the rate applies
+    pub burst: u32,
+}
+
+impl WritePacing {
+    /// Pacing, if `STDIN_MESSAGES_PER_SECOND` turns it on.
+    fn from_settings(settings: &Settings) -> Result<Option<Self>> {
+        let messages_per_second = settings
+            .var("STDIN_MESSAGES_PER_SECOND")
+            .ok()
+            .map(|rate| rate.parse::<f64>())
+            .transpose()
+            .context("Invalid STDIN_MESSAGES_PER_SECOND value")?;
+        let Some(messages_per_second) = messages_per_second.filter(|rate| *rate != 0.0) else {
+            return Ok(None);
+        };
+        if !messages_per_second.is_finite() || messages_per_second < 0.0 {
+            anyhow::bail!(
+                "Invalid STDIN_MESSAGES_PER_SECOND value: {messages_per_second} is not a positive rate"
+            );
+        }
+        let burst = settings
+            .var("STDIN_BURST")
+            .map_or(Ok(1), |burst| burst.parse::<u32>())
+            .context("Invalid STDIN_BURST value")?;
+        if burst == 0 {
+            anyhow::bail!("Invalid STDIN_BURST value: at least one message must fit");
+        }
+        Ok(Some(Self {
+            messages_per_second,
+            burst,
+        }))
+    }
+}
+
+/// Which approval requests jump ahead of the others, which are held briefly so that bursts
+/// of them reach approval clients and watchers together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApprovalPriority {
+    /// Requests of this risk level or above are high priority
+    pub high_risk: RiskLevel,
+    /// How long other requests are held before they are delivered
+    pub batch_window: Duration,
+}
+
+impl ApprovalPriority {
+    /// Priority lanes, if `APPROVAL_HIGH_PRIORITY_RISK` turns them on.
+    fn from_settings(settings: &Settings) -> Result<Option<Self>> {
+        let Ok(high_risk) = settings.var("APPROVAL_HIGH_PRIORITY_RISK") else {
+            return Ok(None);
+        };
+        let high_risk = high_risk
+            .parse()
+            .context("Invalid APPROVAL_HIGH_PRIORITY_RISK value")?;
+        let batch_window = settings
+            .var("APPROVAL_BATCH_WINDOW_MS")
+            .map_or(Ok(2000), |ms| ms.parse::<u64>())
+            .context("Invalid APPROVAL_BATCH_WINDOW_MS value")?;
+        Ok(Some(Self {
+            high_risk,
+            batch_window: Duration::from_millis(batch_window),
+        }))
+    }
+}
+
+/// Where approval requests are announced as they are created, resolved and time out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApprovalWebhookSettings {
+    /// Every event is sent here in a `POST`
+    pub url: String,
+    /// Signs every body with HMAC-SHA256 when set
+    pub secret: Option<String>,
+    /// How long a request may wait for a decision before `timed_out` is sent; never when unset
+    pub timeout: Option<Duration>,
+}
+
+impl ApprovalWebhookSettings {
+    /// The webhook, if `APPROVAL_WEBHOOK_URL` turns it on.
+    fn from_settings(settings: &Settings) -> Result<Option<Self>> {
+        let Some(url) = settings
+            .var("APPROVAL_WEBHOOK_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+        else {
+            return Ok(None);
+        };
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            anyhow::bail!("Invalid APPROVAL_WEBHOOK_URL value: {url} is not an HTTP(S) URL");
+        }
+        let timeout = settings
+            .var("APPROVAL_WEBHOOK_TIMEOUT_SECS")
+            .map_or(Ok(300), |secs| secs.parse::<u64>())
+            .context("Invalid APPROVAL_WEBHOOK_TIMEOUT_SECS value")?;
+        Ok(Some(Self {
+            url,
+            secret: settings
+                .var("APPROVAL_WEBHOOK_SECRET")
+                .ok()
+                .filter(|secret| !secret.is_empty()),
+            timeout: (timeout > 0).then(|| Duration::from_secs(timeout)),
+        }))
+    }
+}
+
+/// Slack app that approval requests are posted to, to be answered with its buttons.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SlackSettings {
+    /// Bot token (`xoxb-...`) with the `chat:write` scope
+    pub bot_token: String,
+    /// Channel requests are posted to, by ID or name
+    pub channel: String,
+    /// Verifies that button callbacks come from Slack
+    pub signing_secret: String,
+}
+
+impl std::fmt::Debug for SlackSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SlackSettings")
+            .field("bot_token", &"<redacted>")
+            .field("channel", &self.channel)
+            .field("signing_secret", &"<redacted>")
+            .finish()
+    }
+}
+
+impl SlackSettings {
+    /// The Slack app, if `SLACK_BOT_TOKEN` turns it on.
+    fn from_settings(settings: &Settings) -> Result<Option<Self>> {
+        let Some(bot_token) = settings
+            .var("SLACK_BOT_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty())
+        else {
+            return Ok(None);
+        };
+        let required = |name: &str| {
+            settings
+                .var(name)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .with_context(|| format!("{name} must be set along with SLACK_BOT_TOKEN"))
+        };
+        Ok(Some(Self {
+            bot_token,
+            channel: required("SLACK_CHANNEL")?,
+            signing_secret: required("SLACK_SIGNING_SECRET")?,
+        }))
+    }
+}
+
+/// Where sessions get a scratch directory of their own, and how long it outlives them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScratchSettings {
+    /// Each session's directory is named after its ID under here
+    pub root: PathBuf,
+    /// How long a directory is kept after its session ended
+    pub retention: Duration,
+}
+
+impl ScratchSettings {
+    /// Scratch directories, if `SCRATCH_DIR` turns them on.
+    fn from_settings(settings: &Settings) -> Result<Option<Self>> {
+        let Some(root) = settings
+            .var_os("SCRATCH_DIR")
+            .filter(|root| !root.is_empty())
+        else {
+            return Ok(None);
+        };
+        let root = PathBuf::from(root);
+        if !root.is_absolute() {
+            anyhow::bail!(
+                "Invalid SCRATCH_DIR value: {} is not an absolute path",
+                root.display()
+            );
+        }
+        let retention = settings
+            .var("SCRATCH_RETENTION_SECS")
+            .map_or(Ok(0), |secs| secs.parse::<u64>())
+            .context("Invalid SCRATCH_RETENTION_SECS value")?;
+        Ok(Some(Self {
+            root,
+            retention: Duration::from_secs(retention),
+        }))
+    }
+}
+
+/// Where each session's relayed Claude traffic is logged, and how much of it is kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WireLogSettings {
+    /// Each session's files are named after its ID under here
+    pub dir: PathBuf,
+    /// Size a file grows to before it is rotated
+    pub max_file_bytes: u64,
+    /// Files kept per session, the one being written included
+    pub max_files: usize,
+}
+
+impl WireLogSettings {
+    /// Wire logging, if `WIRE_LOG_DIR` turns it on.
+    fn from_settings(settings: &Settings) -> Result<Option<Self>> {
+        let Some(dir) = settings
+            .var_os("WIRE_LOG_DIR")
+            .filter(|dir| !dir.is_empty())
+        else {
+            return Ok(None);
+        };
+        let max_file_mb = match settings.var("WIRE_LOG_MAX_FILE_MB") {
+            Ok(mb) => match mb.parse::<u64>() {
+                Ok(0) => {
+                    anyhow::bail!("Invalid WIRE_LOG_MAX_FILE_MB value: must be greater than 0")
+                }
+                Ok(mb) => mb,
+                Err(e) => return Err(e).context("Invalid WIRE_LOG_MAX_FILE_MB value"),
+            },
+            Err(_) => 10,
+        };
+        let max_files = match settings.var("WIRE_LOG_MAX_FILES") {
+            Ok(files) => match files.parse::<usize>() {
+                Ok(0) => anyhow::bail!("Invalid WIRE_LOG_MAX_FILES value: must be greater than 0"),
+                Ok(files) => files,
+                Err(e) => return Err(e).context("Invalid WIRE_LOG_MAX_FILES value"),
+            },
+            Err(_) => 5,
+        };
+        Ok(Some(Self {
+            dir: PathBuf::from(dir),
+            max_file_bytes: max_file_mb.saturating_mul(1024 * 1024),
+            max_files,
+        }))
+    }
+}
+
+/// What the SLO report (`GET /api/v1/admin/slo`) covers and checks against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SloSettings {
+    /// How far back the report looks
+    pub window: Duration,
+    /// Latency each operation should stay under; operations without one never breach
+    pub targets: HashMap<Operation, Duration>,
+}
+
+impl Default for SloSettings {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(60 * 60),
+            targets: HashMap::new(),
+        }
+    }
+}
+
+impl SloSettings {
+    fn from_settings(settings: &Settings) -> Result<Self> {
+        let window = match settings.var("SLO_WINDOW_SECS") {
+            Ok(secs) => match secs.parse::<u64>() {
+                Ok(0) => anyhow::bail!("Invalid SLO_WINDOW_SECS value: must be greater than 0"),
+                Ok(secs) => Duration::from_secs(secs),
+                Err(e) => return Err(e).context("Invalid SLO_WINDOW_SECS value"),
+            },
+            Err(_) => Self::default().window,
+        };
+        let targets = settings
+            .var("SLO_TARGETS_MS")
+            .map_or_else(
+                |_| Ok(HashMap::new()),
+                |targets| parse_slo_targets(&targets),
+            )
+            .context("Invalid SLO_TARGETS_MS value")?;
+        Ok(Self { window, targets })
+    }
+}
+
+/// Parses `operation=millis;operation=millis`.
+fn parse_slo_targets(targets: &str) -> Result<HashMap<Operation, Duration>> {
+    targets
+        .split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let Some((operation, millis)) = entry.split_once('=') else {
+                anyhow::bail!("expected 'operation=millis', got '{entry}'");
+            };
+            let millis = millis
+                .trim()
+                .parse()
+                .with_context(|| format!("expected 'operation=millis', got '{entry}'"))?;
+            Ok((operation.trim().parse()?, Duration::from_millis(millis)))
+        })
+        .collect()
+}
+
+/// Largest request bodies accepted, in bytes, per kind of request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyLimits {
+    /// `POST /api/v1/sessions`, whose bootstrap messages may carry whole files
+    pub create_session: usize,
+    /// Message-sized requests: approval responses and chat completions
+    pub message: usize,
+    /// `POST /api/v1/sessions/import`
+    pub import: usize,
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self {
+            create_session: 16 * 1024 * 1024,
+            message: 2 * 1024 * 1024,
+            import: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl BodyLimits {
+    fn from_settings(settings: &Settings) -> Result<Self> {
+        let defaults = Self::default();
+        let limit = |name: &str, default: usize| -> Result<usize> {
+            let Ok(bytes) = settings.var(name) else {
+                return Ok(default);
+            };
+            match bytes.parse::<usize>() {
+                Ok(0) => anyhow::bail!("Invalid {name} value: must be greater than 0"),
+                Ok(bytes) => Ok(bytes),
+                Err(e) => Err(e).with_context(|| format!("Invalid {name} value")),
+            }
+        };
+        Ok(Self {
+            create_session: limit("CREATE_SESSION_BODY_MAX_BYTES", defaults.create_session)?,
+            message: limit("MESSAGE_BODY_MAX_BYTES", defaults.message)?,
+            import: limit("IMPORT_BODY_MAX_BYTES", defaults.import)?,
+        })
+    }
+}
+
+/// Runs each session's Claude inside its own container instead of on the host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerSettings {
+    /// Container runtime CLI, e.g. `docker` or `podman`
+    pub runtime: PathBuf,
+    pub image: String,
+    /// Claude executable inside the image
+    pub claude_binary: String,
+    /// Where the host's Claude config directory (the parent of the projects directory)
+    /// is mounted, so credentials are shared and transcripts land on the host.
+    pub config_mount: PathBuf,
+    /// Memory limit in the runtime's syntax, e.g. `2g`
+    pub memory: Option<String>,
+    /// CPU limit in the runtime's syntax, e.g. `1.5`
+    pub cpus: Option<String>,
+    /// Further `run` arguments, e.g. `--network=none`
+    pub extra_args: Vec<String>,
+}
+
+impl ContainerSettings {
+    /// Container settings, if `CONTAINER_IMAGE` selects the container backend.
+    fn from_settings(settings: &Settings) -> Option<Self> {
+        let image = settings
+            .var("CONTAINER_IMAGE")
+            .ok()
+            .filter(|image| !image.is_empty())?;
+        let non_empty = |name: &str| settings.var(name).ok().filter(|value| !value.is_empty());
+        Some(Self {
+            runtime: non_empty("CONTAINER_RUNTIME")
+                .map_or_else(|| PathBuf::from("docker"), PathBuf::from),
+            image,
+            claude_binary: non_empty("CONTAINER_CLAUDE_BINARY")
+                .unwrap_or_else(|| "claude".to_string()),
+            config_mount: non_empty("CONTAINER_CLAUDE_CONFIG_DIR")
+                .map_or_else(|| PathBuf::from("/root/.claude"), PathBuf::from),
+            memory: non_empty("CONTAINER_MEMORY"),
+            cpus: non_empty("CONTAINER_CPUS"),
+            extra_args: settings
+                .var("CONTAINER_RUN_ARGS")
+                .map(|args| args.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// Bearer token clients authenticate with. Only its SHA-256 digest is kept, so
+/// comparing a presented token takes the same time wherever it differs.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ApiToken([u8; 32]);
+
+impl std::fmt::Debug for ApiToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ApiToken(<redacted>)")
+    }
+}
+
+impl ApiToken {
+    /// Token clients must present as `token`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `token` is empty or contains characters that cannot be sent
+    /// in a WebSocket subprotocol.
+    pub fn new(token: &str) -> Result<Self> {
+        // RFC 7230 token characters, so the token fits in `Sec-WebSocket-Protocol`
+        let valid = !token.is_empty()
+            && token
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+        if !valid {
+            anyhow::bail!(
+                "API token must be non-empty and consist of letters, digits and !#$%&'*+-.^_`|~"
+            );
+        }
+        Ok(Self(Self::digest(token)))
+    }
+
+    fn digest(token: &str) -> [u8; 32] {
+        let digest = ring::digest::digest(&ring::digest::SHA256, token.as_bytes());
+        digest
+            .as_ref()
+            .try_into()
+            .expect("SHA-256 digests are 32 bytes")
+    }
+}
+
+/// A token the API accepts, and who presents it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKey {
+    pub token: ApiToken,
+    /// User the key belongs to, which sessions created with it are owned by; requests
+    /// with a shared key fall back to `IDENTITY_HEADER`.
+    pub user: Option<String>,
+    /// May access every user's sessions and the admin endpoints
+    pub admin: bool,
+}
+
+/// Keys the API accepts; the API is open to anyone who can reach it when there are none.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApiKeys(Arc<Vec<ApiKey>>);
+
+impl ApiKeys {
+    #[must_use]
+    pub fn new(keys: Vec<ApiKey>) -> Self {
+        Self(Arc::new(keys))
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether some key belongs to a user, so sessions are only accessible to their owners
+    /// and admins.
+    #[must_use]
+    pub fn has_users(&self) -> bool {
+        self.0.iter().any(|key| key.user.is_some())
+    }
+
+    /// Whether `user` has an admin key.
+    #[must_use]
+    pub fn is_admin(&self, user: &str) -> bool {
+        self.0
+            .iter()
+            .any(|key| key.admin && key.user.as_deref() == Some(user))
+    }
+
+    /// The key `candidate` is, if any.
+    #[must_use]
+    pub fn find(&self, candidate: &str) -> Option<&ApiKey> {
+        let digest = ApiToken::digest(candidate);
+        self.0.iter().find(|key| key.token.0 == digest)
+    }
+
+    /// The shared admin token from `CHEF_DE_VIBE_API_TOKEN` and the per-user keys from
+    /// `CHEF_DE_VIBE_API_KEYS`.
+    fn from_settings(settings: &Settings) -> Result<Self> {
+        let mut keys = settings
+            .var("CHEF_DE_VIBE_API_KEYS")
+            .map_or_else(|_| Ok(Vec::new()), |keys| parse_api_keys(&keys))
+            .context("Invalid CHEF_DE_VIBE_API_KEYS value")?;
+        if let Some(token) = settings
+            .var("CHEF_DE_VIBE_API_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty())
+        {
+            let token = ApiToken::new(&token).context("Invalid CHEF_DE_VIBE_API_TOKEN value")?;
+            if keys.iter().any(|key| key.token == token) {
+                anyhow::bail!("CHEF_DE_VIBE_API_TOKEN is also one of CHEF_DE_VIBE_API_KEYS");
+            }
+            keys.push(ApiKey {
+                token,
+                user: None,
+                admin: true,
+            });
+        }
+        Ok(Self::new(keys))
+    }
+}
+
+/// Parses `user=token` entries separated by `;`, where `user=token:admin` marks an admin.
+fn parse_api_keys(value: &str) -> Result<Vec<ApiKey>> {
+    let mut keys: Vec<ApiKey> = Vec::new();
+    for entry in value.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let Some((user, token)) = entry.split_once('=') else {
+            anyhow::bail!("expected 'user=token', got an entry without '='");
+        };
+        let user = user.trim();
+        if user.is_empty() {
+            anyhow::bail!("expected 'user=token', got an entry without a user");
+        }
+        let (token, admin) = match token.trim().rsplit_once(':') {
+            Some((token, "admin")) => (token, true),
+            Some((_, role)) => anyhow::bail!("unknown role '{role}' for user '{user}'"),
+            None => (token.trim(), false),
+        };
+        let token = ApiToken::new(token).with_context(|| format!("key of user '{user}'"))?;
+        if keys.iter().any(|key| key.token == token) {
+            anyhow::bail!("key of user '{user}' is used more than once");
+        }
+        keys.push(ApiKey {
+            token,
+            user: Some(user.to_string()),
+            admin,
+        });
+    }
+    Ok(keys)
+}
+
+/// VAPID credentials the server signs Web Push requests with.
+#[derive(Clone, PartialEq, Eq)]
+pub struct WebPushSettings {
+    /// Uncompressed P-256 public key, base64url; browsers subscribe with it as the
+    /// `applicationServerKey`.
+    pub public_key: String,
+    /// Raw P-256 private key scalar, base64url
+    pub private_key: String,
+    /// `mailto:` or `https:` contact push services can reach the operator at
+    pub subject: String,
+}
+
+impl std::fmt::Debug for WebPushSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebPushSettings")
+            .field("public_key", &self.public_key)
+            .field("private_key", &"<redacted>")
+            .field("subject", &self.subject)
+            .finish()
+    }
+}
+
+impl WebPushSettings {
+    /// Web Push settings, if `WEB_PUSH_VAPID_PUBLIC_KEY` enables it.
+    fn from_settings(settings: &Settings) -> Result<Option<Self>> {
+        let Some(public_key) = settings
+            .var("WEB_PUSH_VAPID_PUBLIC_KEY")
+            .ok()
+            .filter(|key| !key.is_empty())
+        else {
+            return Ok(None);
+        };
+        let private_key = settings.var("WEB_PUSH_VAPID_PRIVATE_KEY").context(
+            "WEB_PUSH_VAPID_PRIVATE_KEY must be set along with WEB_PUSH_VAPID_PUBLIC_KEY",
+        )?;
+        let subject = settings
+            .var("WEB_PUSH_SUBJECT")
+            .context("WEB_PUSH_SUBJECT must be set along with WEB_PUSH_VAPID_PUBLIC_KEY")?;
+        let settings = Self {
+            public_key,
+            private_key,
+            subject,
+        };
+        settings.validate()?;
+        Ok(Some(settings))
+    }
+
+    fn validate(&self) -> Result<()> {
+        crate::web_push::VapidKey::new(self)?;
+        if !self.subject.starts_with("mailto:") && !self.subject.starts_with("https://") {
+            anyhow::bail!(
+                "Invalid WEB_PUSH_SUBJECT value: {} is not a mailto: or https: URL",
+                self.subject
+            );
+        }
+        Ok(())
+    }
+}
+
+/// OpenTelemetry collector the server's tracing spans are exported to over OTLP/HTTP.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OtlpSettings {
+    /// Base URL of the collector, e.g. `http://localhost:4318`
+    pub endpoint: String,
+    pub service_name: String,
+    /// Share of traces exported, from 0 to 1, unless the caller already sampled its trace
+    pub sample_ratio: f64,
+}
+
+impl OtlpSettings {
+    /// Span export settings, if `OTLP_ENDPOINT` enables it. Read on their own, since
+    /// logging is set up before the rest of the configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `OTLP_SAMPLE_RATIO` is not a number from 0 to 1.
+    pub fn from_settings(settings: &Settings) -> Result<Option<Self>> {
+        let Some(endpoint) = settings
+            .var("OTLP_ENDPOINT")
+            .ok()
+            .filter(|endpoint| !endpoint.is_empty())
+        else {
+            return Ok(None);
+        };
+        let sample_ratio = match settings.var("OTLP_SAMPLE_RATIO") {
+            Ok(ratio) => match ratio.parse::<f64>() {
+                Ok(ratio) if (0.0..=1.0).contains(&ratio) => ratio,
+                Ok(_) => anyhow::bail!("Invalid OTLP_SAMPLE_RATIO value: must be from 0 to 1"),
+                Err(e) => return Err(e).context("Invalid OTLP_SAMPLE_RATIO value"),
+            },
+            Err(_) => 1.0,
+        };
+        Ok(Some(Self {
+            endpoint,
+            service_name: settings
+                .var("OTLP_SERVICE_NAME")
+                .unwrap_or_else(|_| "chef-de-vibe".to_string()),
+            sample_ratio,
+        }))
+    }
+}
+
+/// Certificate and key the HTTP listener terminates TLS with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsSettings {
+    /// PEM certificate chain, leaf first
+    pub cert_path: PathBuf,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1)
+    pub key_path: PathBuf,
+}
+
+impl TlsSettings {
+    /// TLS settings, if `TLS_CERT_PATH` enables it.
+    fn from_settings(settings: &Settings) -> Result<Option<Self>> {
+        let cert_path = settings
+            .var("TLS_CERT_PATH")
+            .ok()
+            .filter(|path| !path.is_empty());
+        let key_path = settings
+            .var("TLS_KEY_PATH")
+            .ok()
+            .filter(|path| !path.is_empty());
+        match (cert_path, key_path) {
+            (Some(cert_path), Some(key_path)) => Ok(Some(Self {
+                cert_path: PathBuf::from(cert_path),
+                key_path: PathBuf::from(key_path),
+            })),
+            (None, None) => Ok(None),
+            _ => anyhow::bail!("TLS_CERT_PATH and TLS_KEY_PATH must be set together"),
+        }
+    }
+}
+
+/// Where notifications for watched sessions are delivered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotificationTarget {
+    /// JSON `POST` of the notification to a URL.
+    Webhook(String),
+    /// Slack incoming-webhook URL, sent a `{"text": ...}` message.
+    Slack(String),
+    /// Address mailed through the local `sendmail`.
+    Email(String),
+}
+
+impl FromStr for NotificationTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((kind, destination)) = s.split_once(':') else {
+            anyhow::bail!("expected 'kind:destination', got '{s}'");
+        };
+        let destination = destination.trim();
+        if destination.is_empty() {
+            anyhow::bail!("missing destination in '{s}'");
+        }
+        match kind.trim().to_ascii_lowercase().as_str() {
+            "webhook" => Ok(Self::Webhook(destination.to_string())),
+            "slack" => Ok(Self::Slack(destination.to_string())),
+            "email" => Ok(Self::Email(destination.to_string())),
+            other => anyhow::bail!("expected 'webhook', 'slack' or 'email', got '{other}'"),
+        }
+    }
+}
+
+/// Service that session notifications are pushed to phones and desktops through.
+#[derive(Clone, PartialEq, Eq)]
+pub enum PushBackend {
+    /// ntfy topic, e.g. `https://ntfy.sh/my-topic`, with an access token for protected ones
+    Ntfy {
+        topic_url: String,
+        token: Option<String>,
+    },
+    /// Gotify server, posted to with the token of one of its applications
+    Gotify {
+        server_url: String,
+        app_token: String,
+    },
+}
+
+impl PushBackend {
+    #[must_use]
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::Ntfy { .. } => "ntfy",
+            Self::Gotify { .. } => "gotify",
+        }
+    }
+}
+
+impl std::fmt::Debug for PushBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ntfy { topic_url, token } => f
+                .debug_struct("Ntfy")
+                .field("topic_url", topic_url)
+                .field("token", &token.as_ref().map(|_| "<redacted>"))
+                .finish(),
+            Self::Gotify { server_url, .. } => f
+                .debug_struct("Gotify")
+                .field("server_url", server_url)
+                .field("app_token", &"<redacted>")
+                .finish(),
+        }
+    }
+}
+
+/// Where notifications of every session are pushed, and for which events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PushSettings {
+    pub backends: Vec<PushBackend>,
+    pub events: BTreeSet<WatchEvent>,
+}
+
+impl PushSettings {
+    /// Push notifications, if `NTFY_TOPIC_URL` or `GOTIFY_URL` turns them on.
+    fn from_settings(settings: &Settings) -> Result<Option<Self>> {
+        let var = |name: &str| settings.var(name).ok().filter(|value| !value.is_empty());
+        let http_url = |name: &str, url: String| {
+            if url.starts_with("http://") || url.starts_with("https://") {
+                Ok(url)
+            } else {
+                Err(anyhow::anyhow!(
+                    "Invalid {name} value: {url} is not an HTTP(S) URL"
+                ))
+            }
+        };
+
+        let mut backends = Vec::new();
+        if let Some(topic_url) = var("NTFY_TOPIC_URL") {
+            let topic_url = http_url("NTFY_TOPIC_URL", topic_url)?;
+            if topic_url.trim_end_matches('/').matches('/').count() < 3 {
+                anyhow::bail!("Invalid NTFY_TOPIC_URL value: {topic_url} names no topic");
+            }
+            backends.push(PushBackend::Ntfy {
+                topic_url,
+                token: var("NTFY_TOKEN"),
+            });
+        }
+        if let Some(server_url) = var("GOTIFY_URL") {
+            backends.push(PushBackend::Gotify {
+                server_url: http_url("GOTIFY_URL", server_url)?,
+                app_token: var("GOTIFY_APP_TOKEN")
+                    .context("GOTIFY_APP_TOKEN must be set along with GOTIFY_URL")?,
+            });
+        }
+        if backends.is_empty() {
+            return Ok(None);
+        }
+
+        let mut events = BTreeSet::new();
+        for (name, event) in [
+            ("PUSH_NOTIFY_APPROVAL_NEEDED", WatchEvent::ApprovalNeeded),
+            ("PUSH_NOTIFY_COMPLETED", WatchEvent::Completed),
+            ("PUSH_NOTIFY_FAILED", WatchEvent::Failed),
+            ("PUSH_NOTIFY_CRASHED", WatchEvent::Crashed),
+        ] {
+            let enabled = settings
+                .var(name)
+                .map_or(Ok(true), |enabled| enabled.parse())
+                .with_context(|| format!("Invalid {name} value"))?;
+            if enabled {
+                events.insert(event);
+            }
+        }
+        Ok(Some(Self { backends, events }))
+    }
+}
+
+/// Message queue that session lifecycle and approval events are published to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventSink {
+    /// NATS server, published to on `<subject_prefix>.<event>` subjects.
+    Nats {
+        address: String,
+        subject_prefix: String,
+    },
+    /// Kafka topic, produced to through a Kafka REST proxy.
+    KafkaRest { proxy_url: String, topic: String },
+}
+
+impl FromStr for EventSink {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((kind, destination)) = s.split_once(':') else {
+            anyhow::bail!("expected 'kind:destination', got '{s}'");
+        };
+        let destination = destination.trim();
+        match kind.trim().to_ascii_lowercase().as_str() {
+            "nats" => {
+                let (address, subject_prefix) =
+                    destination.split_once('/').unwrap_or((destination, ""));
+                if address.is_empty() {
+                    anyhow::bail!("missing NATS address in '{s}'");
+                }
+                let subject_prefix = match subject_prefix.trim_matches('.') {
+                    "" => "chef_de_vibe",
+                    prefix => prefix,
+                };
+                Ok(Self::Nats {
+                    address: address.to_string(),
+                    subject_prefix: subject_prefix.to_string(),
+                })
+            }
+            "kafka" => {
+                let Some((proxy_url, topic)) = destination
+                    .rsplit_once('/')
+                    .filter(|(proxy, topic)| proxy.contains("://") && !topic.is_empty())
+                else {
+                    anyhow::bail!("expected 'kafka:<proxy url>/<topic>', got '{s}'");
+                };
+                Ok(Self::KafkaRest {
+                    proxy_url: proxy_url.trim_end_matches('/').to_string(),
+                    topic: topic.to_string(),
+                })
+            }
+            other => anyhow::bail!("expected 'nats' or 'kafka', got '{other}'"),
+        }
+    }
+}
+
+/// Hardening headers attached to frontend responses. `None` leaves a header out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecurityHeaders {
+    /// Custom policy; when unset a default is built that allows the page's own WebSocket origin.
+    pub content_security_policy: Option<String>,
+    pub disable_content_security_policy: bool,
+    pub frame_options: Option<String>,
+    pub referrer_policy: Option<String>,
+    /// Only meaningful when the UI is served over TLS (e.g. behind a terminating proxy).
+    pub strict_transport_security: Option<String>,
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        Self {
+            content_security_policy: None,
+            disable_content_security_policy: false,
+            frame_options: Some("DENY".to_string()),
+            referrer_policy: Some("no-referrer".to_string()),
+            strict_transport_security: None,
+        }
+    }
+}
+
+impl SecurityHeaders {
+    fn from_settings(settings: &Settings) -> Self {
+        // Unset keeps the default, an empty value turns the header off
+        let header = |name: &str, default: Option<String>| match settings.var(name) {
+            Ok(value) if value.trim().is_empty() => None,
+            Ok(value) => Some(value),
+            Err(_) => default,
+        };
+        let defaults = Self::default();
+        let csp = settings.var("CONTENT_SECURITY_POLICY").ok();
+
+        Self {
+            disable_content_security_policy: csp.as_deref().is_some_and(|v| v.trim().is_empty()),
+            content_security_policy: csp.filter(|v| !v.trim().is_empty()),
+            frame_options: header("X_FRAME_OPTIONS", defaults.frame_options),
+            referrer_policy: header("REFERRER_POLICY", defaults.referrer_policy),
+            strict_transport_security: header(
+                "STRICT_TRANSPORT_SECURITY",
+                defaults.strict_transport_security,
+            ),
+        }
+    }
+}
+
+/// Transcripts larger than this are paged by default.
+const DEFAULT_SESSION_RESPONSE_MAX_BYTES: usize = 8 * 1024 * 1024;
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub claude_binary_path: PathBuf,
     pub http_listen_address: String,
     pub claude_projects_dir: PathBuf,
     pub shutdown_timeout: Duration,
+    /// How long `DELETE /api/v1/sessions/:id` waits for Claude to exit after SIGTERM
+    /// before killing it.
+    pub terminate_timeout: Duration,
+    /// How sessions are shut down, by their tags.
+    pub shutdown_policies: ShutdownPolicies,
+    pub stdout_mode: StdoutMode,
+    /// Where per-session state (e.g. pending approvals) is persisted; disabled when unset.
+    pub session_state_dir: Option<PathBuf>,
+    /// Directory shared with other instances to replicate pending approvals and their
+    /// decisions through; approvals stay on the instance running the session when unset.
+    pub approval_sync_dir: Option<PathBuf>,
+    /// Instance name to advertise via mDNS; advertising is disabled when unset.
+    pub mdns_instance_name: Option<String>,
+    /// Named transcript post-processing commands, selectable per request.
+    pub transcript_hooks: HashMap<String, Vec<String>>,
+    pub security_headers: SecurityHeaders,
+    /// Unix socket of an Agent SDK bridge; when set, sessions are started through it
+    /// instead of spawning the CLI.
+    pub claude_sdk_socket: Option<PathBuf>,
+    pub disconnect_queue_policy: DisconnectQueuePolicy,
+    /// Named destinations that session watchers can subscribe.
+    pub notification_targets: HashMap<String, NotificationTarget>,
+    /// How long a session whose Claude exited stays in memory before only its
+    /// transcript on disk is left.
+    pub dead_session_grace_period: Duration,
+    /// Where anonymous usage counters are reported; telemetry is off when unset.
+    pub telemetry_endpoint: Option<String>,
+    /// Canonical directories sessions may be created under; any directory when empty.
+    pub working_dir_roots: Vec<PathBuf>,
+    /// Request header carrying the user name set by an authenticating proxy; everyone
+    /// shares one identity when unset.
+    pub identity_header: Option<String>,
+    /// Keys API requests must present; the API is open to anyone who can reach it when
+    /// there are none.
+    pub api_keys: ApiKeys,
+    /// How each `control_request` subtype from Claude is handled.
+    pub control_routes: ControlRoutes,
+    /// Limits applied to every session, on top of those it was created with.
+    pub session_limits: SessionLimits,
+    /// Caps how many sessions run at once when set.
+    pub session_capacity: Option<SessionCapacity>,
+    /// Runs Claude with `--include-partial-messages`, so clients that opt in can render
+    /// replies as they stream.
+    pub partial_messages: bool,
+    pub process_monitoring: ProcessMonitoring,
+    /// Memory, CPU and process limits of each session's Claude and the tools it runs.
+    pub resource_limits: ResourceLimits,
+    /// Respawns crashed Claude processes with `--resume` when set.
+    pub restart_policy: Option<RestartPolicy>,
+    /// Claude CLI flags sessions may add when they are created.
+    pub cli_flag_allowlist: CliFlagAllowlist,
+    /// Size of the serialized transcript above which `GET /api/v1/sessions/:id` returns a
+    /// paging descriptor instead of the content; unlimited when unset.
+    pub session_response_max_bytes: Option<usize>,
+    /// Lets sessions run in containers when set; they do unless `session_backend` or the
+    /// session picks the CLI, or `claude_sdk_socket` is set.
+    pub container: Option<ContainerSettings>,
+    /// Backend sessions that do not pick one run on, unless `claude_sdk_socket` is set.
+    pub session_backend: Option<BackendKind>,
+    /// Who may allow approvals of each risk level; anyone when a level is not listed.
+    pub risk_approvers: RiskApprovers,
+    /// Patterns raising the risk level of approval requests they match.
+    pub risk_patterns: RiskPatterns,
+    /// What happens to tool requests for paths outside the session's working directory.
+    pub working_dir_sandbox: SandboxMode,
+    /// Directory the frontend is served from instead of the assets built into the binary.
+    pub frontend_dir: Option<PathBuf>,
+    /// Message queues that session events are published to; nothing is published when empty.
+    pub event_sinks: Vec<EventSink>,
+    pub body_limits: BodyLimits,
+    /// Appends client inputs to a sidecar transcript in `session_state_dir` before they
+    /// are written to Claude, so they outlive a crash before Claude records them.
+    pub record_client_inputs: bool,
+    /// Sends session notifications to subscribed browsers when set.
+    pub web_push: Option<WebPushSettings>,
+    /// Serves HTTPS and `wss://` with this certificate when set, plain HTTP otherwise.
+    pub tls: Option<TlsSettings>,
+    /// Paces writes to Claude's stdin when set; queued messages are written at once otherwise.
+    pub write_pacing: Option<WritePacing>,
+    /// Delivers risky approval requests first and batches the others when set.
+    pub approval_priority: Option<ApprovalPriority>,
+    /// POSTs approval lifecycle events to a URL when set.
+    pub approval_webhook: Option<ApprovalWebhookSettings>,
+    /// Posts approval requests to Slack, to be answered there, when set.
+    pub slack: Option<SlackSettings>,
+    /// Pushes notifications of every session through ntfy or Gotify when set.
+    pub push: Option<PushSettings>,
+    /// Gives every session a scratch directory when set.
+    pub scratch: Option<ScratchSettings>,
+    /// Logs every line written to and read from Claude when set.
+    pub wire_log: Option<WireLogSettings>,
+    pub slo: SloSettings,
+    pub heartbeat: Heartbeat,
+    /// Serves Swagger UI for the API specification when set.
+    pub swagger_ui: bool,
+    /// Origins browsers may call the API from; any origin when empty.
+    pub cors_allowed_origins: Vec<String>,
+}
+
+/// Every setting, by the name of its environment variable. A config file may set these
+/// and nothing else.
+pub const SETTING_NAMES: &[&str] = &[
+    "APPROVAL_BATCH_WINDOW_MS",
+    "APPROVAL_HIGH_PRIORITY_RISK",
+    "APPROVAL_RISK_APPROVERS",
+    "APPROVAL_RISK_PATTERNS",
+    "APPROVAL_SYNC_DIR",
+    "APPROVAL_WEBHOOK_SECRET",
+    "APPROVAL_WEBHOOK_TIMEOUT_SECS",
+    "APPROVAL_WEBHOOK_URL",
+    "CHEF_DE_VIBE_API_KEYS",
+    "CHEF_DE_VIBE_API_TOKEN",
+    "CLAUDE_BINARY_PATH",
+    "CLAUDE_CGROUP_ROOT",
+    "CLAUDE_CLI_FLAG_ALLOWLIST",
+    "CLAUDE_CPU_LIMIT_CORES",
+    "CLAUDE_CPU_LIMIT_SECS",
+    "CLAUDE_MEMORY_LIMIT_MB",
+    "CLAUDE_PARTIAL_MESSAGES",
+    "CLAUDE_PROCESS_LIMIT",
+    "CLAUDE_PROJECTS_DIR",
+    "CLAUDE_RESTART_BACKOFF_MS",
+    "CLAUDE_RESTART_MAX",
+    "CLAUDE_SDK_SOCKET",
+    "CLAUDE_STDOUT_MODE",
+    "CONTAINER_CLAUDE_BINARY",
+    "CONTAINER_CLAUDE_CONFIG_DIR",
+    "CONTAINER_CPUS",
+    "CONTAINER_IMAGE",
+    "CONTAINER_MEMORY",
+    "CONTAINER_RUNTIME",
+    "CONTAINER_RUN_ARGS",
+    "CONTENT_SECURITY_POLICY",
+    "CONTROL_REQUEST_ROUTES",
+    "CORS_ALLOWED_ORIGINS",
+    "CREATE_SESSION_BODY_MAX_BYTES",
+    "DEAD_SESSION_GRACE_PERIOD",
+    "DISCONNECT_QUEUE_POLICY",
+    "EVENT_SINKS",
+    "FRONTEND_DIR",
+    "GOTIFY_APP_TOKEN",
+    "GOTIFY_URL",
+    "HTTP_LISTEN_ADDRESS",
+    "IDENTITY_HEADER",
+    "IMPORT_BODY_MAX_BYTES",
+    "MAX_RUNNING_SESSIONS",
+    "MDNS_INSTANCE_NAME",
+    "MESSAGE_BODY_MAX_BYTES",
+    "NOTIFICATION_TARGETS",
+    "NTFY_TOKEN",
+    "NTFY_TOPIC_URL",
+    "OTLP_ENDPOINT",
+    "OTLP_SAMPLE_RATIO",
+    "OTLP_SERVICE_NAME",
+    "PROCESS_CPU_WARNING_PERCENT",
+    "PROCESS_RSS_WARNING_MB",
+    "PROCESS_SAMPLE_INTERVAL",
+    "PUSH_NOTIFY_APPROVAL_NEEDED",
+    "PUSH_NOTIFY_COMPLETED",
+    "PUSH_NOTIFY_CRASHED",
+    "PUSH_NOTIFY_FAILED",
+    "RECORD_CLIENT_INPUTS",
+    "REFERRER_POLICY",
+    "SCRATCH_DIR",
+    "SCRATCH_RETENTION_SECS",
+    "SESSION_BACKEND",
+    "SESSION_LIMIT_ACTION",
+    "SESSION_MAX_DURATION",
+    "SESSION_MAX_TURNS",
+    "SESSION_QUEUE_TIMEOUT",
+    "SESSION_RESPONSE_MAX_BYTES",
+    "SESSION_STATE_DIR",
+    "SESSION_TERMINATE_TIMEOUT",
+    "SHUTDOWN_POLICIES",
+    "SHUTDOWN_TIMEOUT",
+    "SLACK_BOT_TOKEN",
+    "SLACK_CHANNEL",
+    "SLACK_SIGNING_SECRET",
+    "SLO_TARGETS_MS",
+    "SLO_WINDOW_SECS",
+    "STDIN_BURST",
+    "STDIN_MESSAGES_PER_SECOND",
+    "STRICT_TRANSPORT_SECURITY",
+    "SWAGGER_UI",
+    "TELEMETRY_ENDPOINT",
+    "TLS_CERT_PATH",
+    "TLS_KEY_PATH",
+    "TRANSCRIPT_HOOKS",
+    "WEBSOCKET_MAX_MISSED_PONGS",
+    "WEBSOCKET_PING_INTERVAL",
+    "WEB_PUSH_SUBJECT",
+    "WEB_PUSH_VAPID_PRIVATE_KEY",
+    "WEB_PUSH_VAPID_PUBLIC_KEY",
+    "WIRE_LOG_DIR",
+    "WIRE_LOG_MAX_FILES",
+    "WIRE_LOG_MAX_FILE_MB",
+    "WORKING_DIR_ROOTS",
+    "WORKING_DIR_SANDBOX",
+    "X_FRAME_OPTIONS",
+];
+
+/// Settings a changed config file takes effect for while the server runs. Sessions
+/// already running keep what they were started with.
+pub const RELOADABLE_SETTINGS: &[&str] = &[
+    "APPROVAL_RISK_APPROVERS",
+    "APPROVAL_RISK_PATTERNS",
+    "CLAUDE_CLI_FLAG_ALLOWLIST",
+    "CONTROL_REQUEST_ROUTES",
+    "CORS_ALLOWED_ORIGINS",
+    "SESSION_LIMIT_ACTION",
+    "SESSION_MAX_DURATION",
+    "SESSION_MAX_TURNS",
+    "SHUTDOWN_POLICIES",
+    "WORKING_DIR_ROOTS",
+    "WORKING_DIR_SANDBOX",
+];
+
+/// Where the configuration is read from. Each setting is named like its environment
+/// variable; a value given on the command line wins over the environment, which wins
+/// over the config file.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    overrides: HashMap<String, String>,
+    file: BTreeMap<String, String>,
+}
+
+impl Settings {
+    /// Sets `name` regardless of the environment and the config file.
+    #[must_use]
+    pub fn with_override(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.overrides.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Falls back to these settings of a config file for names the environment lacks.
+    #[must_use]
+    pub fn with_file(mut self, file: BTreeMap<String, String>) -> Self {
+        self.file = file;
+        self
+    }
+
+    /// The settings of the config file.
+    #[must_use]
+    pub fn file(&self) -> &BTreeMap<String, String> {
+        &self.file
+    }
+
+    fn var(&self, name: &str) -> Result<String, env::VarError> {
+        if let Some(value) = self.overrides.get(name) {
+            return Ok(value.clone());
+        }
+        match env::var(name) {
+            Err(env::VarError::NotPresent) => self
+                .file
+                .get(name)
+                .cloned()
+                .ok_or(env::VarError::NotPresent),
+            value => value,
+        }
+    }
+
+    /// The raw value of `name`, like [`env::var_os`].
+    #[must_use]
+    pub fn var_os(&self, name: &str) -> Option<OsString> {
+        self.overrides
+            .get(name)
+            .map(OsString::from)
+            .or_else(|| env::var_os(name))
+            .or_else(|| self.file.get(name).map(OsString::from))
+    }
 }
 
 impl Config {
@@ -23,8 +1756,25 @@ impl Config {
     ///
     /// Panics if the home directory cannot be determined when `CLAUDE_PROJECTS_DIR`
     /// is not set.
+    #[allow(dead_code)] // Used by tests and library users; the binary reads `Settings`
     pub fn from_env() -> Result<Self> {
-        let claude_binary_path = match env::var("CLAUDE_BINARY_PATH") {
+        Self::from_settings(&Settings::default())
+    }
+
+    /// Creates a new configuration from `settings`, see [`Config::from_env`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if required settings are missing or invalid, or if the
+    /// configuration validation fails.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the home directory cannot be determined when `CLAUDE_PROJECTS_DIR`
+    /// is not set.
+    #[allow(clippy::too_many_lines)]
+    pub fn from_settings(settings: &Settings) -> Result<Self> {
+        let claude_binary_path = match settings.var("CLAUDE_BINARY_PATH") {
             Ok(path) => {
                 let path = PathBuf::from(path);
                 if path.is_relative() {
@@ -39,10 +1789,11 @@ impl Config {
                 .context("CLAUDE_BINARY_PATH not set and 'claude' not found in PATH")?,
         };
 
-        let http_listen_address =
-            env::var("HTTP_LISTEN_ADDRESS").unwrap_or_else(|_| "127.0.0.1:3000".to_string());
+        let http_listen_address = settings
+            .var("HTTP_LISTEN_ADDRESS")
+            .unwrap_or_else(|_| "127.0.0.1:3000".to_string());
 
-        let claude_projects_dir = env::var("CLAUDE_PROJECTS_DIR").map_or_else(
+        let claude_projects_dir = settings.var("CLAUDE_PROJECTS_DIR").map_or_else(
             |_| {
                 dirs::home_dir()
                     .expect("Could not determine home directory")
@@ -52,17 +1803,226 @@ impl Config {
             PathBuf::from,
         );
 
-        let shutdown_timeout = env::var("SHUTDOWN_TIMEOUT")
-            .unwrap_or_else(|_| "30".to_string())
-            .parse::<u64>()
-            .context("Invalid SHUTDOWN_TIMEOUT value")?;
-        let shutdown_timeout = Duration::from_secs(shutdown_timeout);
+        let shutdown_timeout = settings
+            .var("SHUTDOWN_TIMEOUT")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse::<u64>()
+            .context("Invalid SHUTDOWN_TIMEOUT value")?;
+        let shutdown_timeout = Duration::from_secs(shutdown_timeout);
+
+        let terminate_timeout = settings
+            .var("SESSION_TERMINATE_TIMEOUT")
+            .map_or(Ok(10), |secs| secs.parse::<u64>())
+            .context("Invalid SESSION_TERMINATE_TIMEOUT value")?;
+        let terminate_timeout = Duration::from_secs(terminate_timeout);
+
+        let shutdown_policies = settings
+            .var("SHUTDOWN_POLICIES")
+            .map_or(Ok(ShutdownPolicies::default()), |policies| policies.parse())
+            .context("Invalid SHUTDOWN_POLICIES value")?;
+
+        let dead_session_grace_period = settings
+            .var("DEAD_SESSION_GRACE_PERIOD")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse::<u64>()
+            .context("Invalid DEAD_SESSION_GRACE_PERIOD value")?;
+        let dead_session_grace_period = Duration::from_secs(dead_session_grace_period);
+
+        let stdout_mode = settings
+            .var("CLAUDE_STDOUT_MODE")
+            .map_or(Ok(StdoutMode::default()), |mode| mode.parse())
+            .context("Invalid CLAUDE_STDOUT_MODE value")?;
+
+        let session_state_dir = settings.var("SESSION_STATE_DIR").ok().map(PathBuf::from);
+        let approval_sync_dir = settings
+            .var_os("APPROVAL_SYNC_DIR")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from);
+
+        let mdns_instance_name = settings
+            .var("MDNS_INSTANCE_NAME")
+            .ok()
+            .filter(|name| !name.is_empty());
+
+        let transcript_hooks = settings
+            .var("TRANSCRIPT_HOOKS")
+            .map_or_else(
+                |_| Ok(HashMap::new()),
+                |hooks| parse_transcript_hooks(&hooks),
+            )
+            .context("Invalid TRANSCRIPT_HOOKS value")?;
+
+        let claude_sdk_socket = settings
+            .var("CLAUDE_SDK_SOCKET")
+            .ok()
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from);
+
+        let disconnect_queue_policy = settings
+            .var("DISCONNECT_QUEUE_POLICY")
+            .map_or(Ok(DisconnectQueuePolicy::default()), |policy| {
+                policy.parse()
+            })
+            .context("Invalid DISCONNECT_QUEUE_POLICY value")?;
+
+        let notification_targets = settings
+            .var("NOTIFICATION_TARGETS")
+            .map_or_else(
+                |_| Ok(HashMap::new()),
+                |targets| parse_notification_targets(&targets),
+            )
+            .context("Invalid NOTIFICATION_TARGETS value")?;
+
+        let telemetry_endpoint = settings
+            .var("TELEMETRY_ENDPOINT")
+            .ok()
+            .filter(|endpoint| !endpoint.is_empty());
+
+        let working_dir_roots = settings
+            .var_os("WORKING_DIR_ROOTS")
+            .map_or_else(|| Ok(Vec::new()), |roots| parse_working_dir_roots(&roots))
+            .context("Invalid WORKING_DIR_ROOTS value")?;
+
+        let identity_header = settings
+            .var("IDENTITY_HEADER")
+            .ok()
+            .filter(|header| !header.is_empty());
+
+        let control_routes = settings
+            .var("CONTROL_REQUEST_ROUTES")
+            .map_or_else(
+                |_| Ok(ControlRoutes::default()),
+                |routes| parse_control_routes(&routes),
+            )
+            .context("Invalid CONTROL_REQUEST_ROUTES value")?;
+
+        let partial_messages = settings
+            .var("CLAUDE_PARTIAL_MESSAGES")
+            .map_or(Ok(false), |enabled| enabled.parse())
+            .context("Invalid CLAUDE_PARTIAL_MESSAGES value")?;
+
+        let record_client_inputs = settings
+            .var("RECORD_CLIENT_INPUTS")
+            .map_or(Ok(false), |enabled| enabled.parse())
+            .context("Invalid RECORD_CLIENT_INPUTS value")?;
+
+        let swagger_ui = settings
+            .var("SWAGGER_UI")
+            .map_or(Ok(false), |enabled| enabled.parse())
+            .context("Invalid SWAGGER_UI value")?;
+
+        let cors_allowed_origins = settings
+            .var("CORS_ALLOWED_ORIGINS")
+            .map(|origins| {
+                origins
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|origin| !origin.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let session_response_max_bytes = settings
+            .var("SESSION_RESPONSE_MAX_BYTES")
+            .map_or(Ok(DEFAULT_SESSION_RESPONSE_MAX_BYTES), |bytes| {
+                bytes.parse()
+            })
+            .context("Invalid SESSION_RESPONSE_MAX_BYTES value")?;
+        let session_response_max_bytes =
+            Some(session_response_max_bytes).filter(|&bytes| bytes > 0);
+
+        let risk_approvers = settings
+            .var("APPROVAL_RISK_APPROVERS")
+            .map_or(Ok(RiskApprovers::default()), |approvers| approvers.parse())
+            .context("Invalid APPROVAL_RISK_APPROVERS value")?;
+
+        let risk_patterns = settings
+            .var("APPROVAL_RISK_PATTERNS")
+            .map_or(Ok(RiskPatterns::default()), |patterns| patterns.parse())
+            .context("Invalid APPROVAL_RISK_PATTERNS value")?;
+
+        let working_dir_sandbox = settings
+            .var("WORKING_DIR_SANDBOX")
+            .map_or(Ok(SandboxMode::default()), |mode| mode.parse())
+            .context("Invalid WORKING_DIR_SANDBOX value")?;
+
+        let event_sinks = settings
+            .var("EVENT_SINKS")
+            .map_or_else(
+                |_| Ok(Vec::new()),
+                |sinks| {
+                    sinks
+                        .split(';')
+                        .map(str::trim)
+                        .filter(|sink| !sink.is_empty())
+                        .map(str::parse)
+                        .collect()
+                },
+            )
+            .context("Invalid EVENT_SINKS value")?;
 
         let config = Self {
             claude_binary_path,
             http_listen_address,
             claude_projects_dir,
             shutdown_timeout,
+            terminate_timeout,
+            shutdown_policies,
+            stdout_mode,
+            session_state_dir,
+            approval_sync_dir,
+            mdns_instance_name,
+            transcript_hooks,
+            security_headers: SecurityHeaders::from_settings(settings),
+            claude_sdk_socket,
+            disconnect_queue_policy,
+            notification_targets,
+            dead_session_grace_period,
+            telemetry_endpoint,
+            working_dir_roots,
+            identity_header,
+            api_keys: ApiKeys::from_settings(settings)?,
+            control_routes,
+            session_limits: SessionLimits::from_settings(settings)?,
+            session_capacity: SessionCapacity::from_settings(settings)?,
+            partial_messages,
+            process_monitoring: ProcessMonitoring::from_settings(settings)?,
+            resource_limits: ResourceLimits::from_settings(settings)?,
+            restart_policy: RestartPolicy::from_settings(settings)?,
+            cli_flag_allowlist: CliFlagAllowlist::from_settings(settings)?,
+            session_response_max_bytes,
+            container: ContainerSettings::from_settings(settings),
+            session_backend: settings
+                .var("SESSION_BACKEND")
+                .ok()
+                .filter(|backend| !backend.is_empty())
+                .map(|backend| backend.parse())
+                .transpose()
+                .context("Invalid SESSION_BACKEND value")?,
+            risk_approvers,
+            risk_patterns,
+            working_dir_sandbox,
+            frontend_dir: settings
+                .var_os("FRONTEND_DIR")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+            event_sinks,
+            body_limits: BodyLimits::from_settings(settings)?,
+            record_client_inputs,
+            web_push: WebPushSettings::from_settings(settings)?,
+            tls: TlsSettings::from_settings(settings)?,
+            write_pacing: WritePacing::from_settings(settings)?,
+            approval_priority: ApprovalPriority::from_settings(settings)?,
+            approval_webhook: ApprovalWebhookSettings::from_settings(settings)?,
+            slack: SlackSettings::from_settings(settings)?,
+            push: PushSettings::from_settings(settings)?,
+            scratch: ScratchSettings::from_settings(settings)?,
+            wire_log: WireLogSettings::from_settings(settings)?,
+            slo: SloSettings::from_settings(settings)?,
+            heartbeat: Heartbeat::from_settings(settings)?,
+            swagger_ui,
+            cors_allowed_origins,
         };
 
         config.validate()?;
@@ -70,7 +2030,55 @@ impl Config {
         Ok(config)
     }
 
+    /// Takes the settings of [`RELOADABLE_SETTINGS`] from `reloaded`, keeping the rest.
+    pub fn take_reloadable(&mut self, reloaded: &Self) {
+        self.risk_approvers = reloaded.risk_approvers.clone();
+        self.risk_patterns = reloaded.risk_patterns.clone();
+        self.cli_flag_allowlist = reloaded.cli_flag_allowlist.clone();
+        self.control_routes = reloaded.control_routes.clone();
+        self.cors_allowed_origins
+            .clone_from(&reloaded.cors_allowed_origins);
+        self.session_limits = reloaded.session_limits;
+        self.shutdown_policies = reloaded.shutdown_policies.clone();
+        self.working_dir_roots
+            .clone_from(&reloaded.working_dir_roots);
+        self.working_dir_sandbox = reloaded.working_dir_sandbox;
+    }
+
+    /// Whether browsers on `origin` may call the API.
+    #[must_use]
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.cors_allowed_origins.is_empty()
+            || self
+                .cors_allowed_origins
+                .iter()
+                .any(|allowed| allowed == origin)
+    }
+
     fn validate(&self) -> Result<()> {
+        if let Some(origin) = self
+            .cors_allowed_origins
+            .iter()
+            .find(|origin| !origin.starts_with("http://") && !origin.starts_with("https://"))
+        {
+            anyhow::bail!("Invalid CORS_ALLOWED_ORIGINS value: {origin} is not an http(s) origin");
+        }
+
+        if let Some(endpoint) = &self.telemetry_endpoint {
+            if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                anyhow::bail!("Invalid TELEMETRY_ENDPOINT value: {endpoint} is not an http(s) URL");
+            }
+        }
+
+        if let Some(header) = &self.identity_header {
+            axum::http::HeaderName::try_from(header.as_str())
+                .with_context(|| format!("Invalid IDENTITY_HEADER value: {header}"))?;
+        }
+
+        if self.session_backend == Some(BackendKind::Container) && self.container.is_none() {
+            anyhow::bail!("SESSION_BACKEND=container needs CONTAINER_IMAGE");
+        }
+
         // Validate claude binary exists and is executable
         if !self.claude_binary_path.exists() {
             anyhow::bail!(
@@ -115,6 +2123,37 @@ impl Config {
             )
         })?;
 
+        if let Some(frontend_dir) = &self.frontend_dir {
+            if !frontend_dir.is_dir() {
+                anyhow::bail!(
+                    "Frontend directory is not a directory: {}",
+                    frontend_dir.display()
+                );
+            }
+        }
+
+        if self.record_client_inputs && self.session_state_dir.is_none() {
+            anyhow::bail!("RECORD_CLIENT_INPUTS needs SESSION_STATE_DIR to write inputs to");
+        }
+
+        if let Some(wire_log) = &self.wire_log {
+            std::fs::create_dir_all(&wire_log.dir).with_context(|| {
+                format!(
+                    "Cannot create wire log directory: {}",
+                    wire_log.dir.display()
+                )
+            })?;
+        }
+
+        if let Some(state_dir) = &self.session_state_dir {
+            std::fs::create_dir_all(state_dir).with_context(|| {
+                format!(
+                    "Cannot create session state directory: {}",
+                    state_dir.display()
+                )
+            })?;
+        }
+
         Ok(())
     }
 
@@ -152,6 +2191,72 @@ impl Config {
     }
 }
 
+/// Parses `name=command args;other=command` into named argument vectors.
+fn parse_transcript_hooks(value: &str) -> Result<HashMap<String, Vec<String>>> {
+    let mut hooks = HashMap::new();
+    for entry in value.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let Some((name, command)) = entry.split_once('=') else {
+            anyhow::bail!("expected 'name=command', got '{entry}'");
+        };
+        let argv: Vec<String> = command.split_whitespace().map(String::from).collect();
+        if name.trim().is_empty() || argv.is_empty() {
+            anyhow::bail!("expected 'name=command', got '{entry}'");
+        }
+        hooks.insert(name.trim().to_string(), argv);
+    }
+    Ok(hooks)
+}
+
+/// Parses a `PATH`-style list of directories, resolved so symlinks cannot be used to
+/// step outside of them.
+fn parse_working_dir_roots(value: &std::ffi::OsStr) -> Result<Vec<PathBuf>> {
+    env::split_paths(value)
+        .filter(|root| !root.as_os_str().is_empty())
+        .map(|root| {
+            if root.is_relative() {
+                anyhow::bail!("root must be absolute, got '{}'", root.display());
+            }
+            std::fs::canonicalize(&root)
+                .with_context(|| format!("Cannot resolve root '{}'", root.display()))
+        })
+        .collect()
+}
+
+/// Parses `name=kind:destination;other=kind:destination` into named notification targets.
+fn parse_notification_targets(value: &str) -> Result<HashMap<String, NotificationTarget>> {
+    let mut targets = HashMap::new();
+    for entry in value.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let Some((name, target)) = entry.split_once('=') else {
+            anyhow::bail!("expected 'name=kind:destination', got '{entry}'");
+        };
+        if name.trim().is_empty() {
+            anyhow::bail!("expected 'name=kind:destination', got '{entry}'");
+        }
+        targets.insert(name.trim().to_string(), target.parse()?);
+    }
+    Ok(targets)
+}
+
+/// Parses `subtype=route;other=route` on top of the default routes; `*` sets the
+/// route of unlisted subtypes.
+fn parse_control_routes(value: &str) -> Result<ControlRoutes> {
+    let mut routes = ControlRoutes::default();
+    for entry in value.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let Some((subtype, route)) = entry.split_once('=') else {
+            anyhow::bail!("expected 'subtype=route', got '{entry}'");
+        };
+        let route = route.trim().parse()?;
+        match subtype.trim() {
+            "" => anyhow::bail!("expected 'subtype=route', got '{entry}'"),
+            "*" => routes.fallback = route,
+            subtype => {
+                routes.routes.insert(subtype.to_string(), route);
+            }
+        }
+    }
+    Ok(routes)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -175,6 +2280,418 @@ mod tests {
         );
     }
 
+    #[test]
+    #[serial]
+    fn test_slo_settings_from_env() {
+        env::remove_var("SLO_WINDOW_SECS");
+        env::set_var(
+            "SLO_TARGETS_MS",
+            "session_spawn=5000; approval_round_trip = 60000",
+        );
+        let slo = SloSettings::from_settings(&Settings::default()).unwrap();
+        assert_eq!(slo.window, Duration::from_secs(60 * 60));
+        assert_eq!(
+            slo.targets,
+            HashMap::from([
+                (Operation::SessionSpawn, Duration::from_secs(5)),
+                (Operation::ApprovalRoundTrip, Duration::from_secs(60)),
+            ])
+        );
+
+        env::set_var("SLO_WINDOW_SECS", "0");
+        assert!(SloSettings::from_settings(&Settings::default()).is_err());
+        env::set_var("SLO_WINDOW_SECS", "600");
+        env::set_var("SLO_TARGETS_MS", "spawn=5000");
+        assert!(SloSettings::from_settings(&Settings::default()).is_err());
+        env::set_var("SLO_TARGETS_MS", "session_spawn=5s");
+        assert!(SloSettings::from_settings(&Settings::default()).is_err());
+        env::remove_var("SLO_TARGETS_MS");
+        assert_eq!(
+            SloSettings::from_settings(&Settings::default())
+                .unwrap()
+                .window,
+            Duration::from_secs(10 * 60)
+        );
+        env::remove_var("SLO_WINDOW_SECS");
+    }
+
+    #[test]
+    fn test_setting_names_cover_every_setting() {
+        let source = include_str!("config.rs");
+        let source = &source[..source.find("#[cfg(test)]").unwrap()];
+        for literal in source.split('"').skip(1).step_by(2) {
+            let is_name = literal.contains('_')
+                && literal.starts_with(|c: char| c.is_ascii_uppercase())
+                && literal
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+            if is_name {
+                assert!(SETTING_NAMES.contains(&literal), "{literal} is not listed");
+            }
+        }
+        assert!(RELOADABLE_SETTINGS
+            .iter()
+            .all(|name| SETTING_NAMES.contains(name)));
+    }
+
+    #[test]
+    #[serial]
+    fn test_settings_precedence() {
+        env::set_var("SETTINGS_PRECEDENCE_TEST", "env");
+        let file = BTreeMap::from([
+            ("SETTINGS_PRECEDENCE_TEST".to_string(), "file".to_string()),
+            (
+                "SETTINGS_PRECEDENCE_FILE_ONLY".to_string(),
+                "file".to_string(),
+            ),
+        ]);
+        let settings = Settings::default().with_file(file);
+        assert_eq!(settings.var("SETTINGS_PRECEDENCE_TEST").unwrap(), "env");
+        assert_eq!(
+            settings.var("SETTINGS_PRECEDENCE_FILE_ONLY").unwrap(),
+            "file"
+        );
+
+        let settings = settings.with_override("SETTINGS_PRECEDENCE_TEST", "cli");
+        assert_eq!(settings.var("SETTINGS_PRECEDENCE_TEST").unwrap(), "cli");
+        assert_eq!(
+            settings.var_os("SETTINGS_PRECEDENCE_TEST"),
+            Some(OsString::from("cli"))
+        );
+        env::remove_var("SETTINGS_PRECEDENCE_TEST");
+    }
+
+    #[test]
+    #[serial]
+    fn test_heartbeat_from_env() {
+        env::remove_var("WEBSOCKET_PING_INTERVAL");
+        env::remove_var("WEBSOCKET_MAX_MISSED_PONGS");
+        assert_eq!(
+            Heartbeat::from_settings(&Settings::default()).unwrap(),
+            Heartbeat::default()
+        );
+
+        env::set_var("WEBSOCKET_PING_INTERVAL", "0");
+        env::set_var("WEBSOCKET_MAX_MISSED_PONGS", "5");
+        let heartbeat = Heartbeat::from_settings(&Settings::default()).unwrap();
+        assert_eq!(heartbeat.interval, None);
+        assert_eq!(heartbeat.max_missed_pongs, 5);
+
+        env::set_var("WEBSOCKET_MAX_MISSED_PONGS", "0");
+        assert!(Heartbeat::from_settings(&Settings::default()).is_err());
+        env::set_var("WEBSOCKET_MAX_MISSED_PONGS", "3");
+        env::set_var("WEBSOCKET_PING_INTERVAL", "30s");
+        assert!(Heartbeat::from_settings(&Settings::default()).is_err());
+        env::remove_var("WEBSOCKET_PING_INTERVAL");
+        env::remove_var("WEBSOCKET_MAX_MISSED_PONGS");
+    }
+
+    #[test]
+    #[serial]
+    fn test_body_limits_from_env() {
+        env::set_var("CREATE_SESSION_BODY_MAX_BYTES", "1024");
+        env::remove_var("MESSAGE_BODY_MAX_BYTES");
+        env::remove_var("IMPORT_BODY_MAX_BYTES");
+        let limits = BodyLimits::from_settings(&Settings::default()).unwrap();
+        assert_eq!(limits.create_session, 1024);
+        assert_eq!(limits.message, BodyLimits::default().message);
+
+        env::set_var("CREATE_SESSION_BODY_MAX_BYTES", "0");
+        assert!(BodyLimits::from_settings(&Settings::default()).is_err());
+        env::set_var("CREATE_SESSION_BODY_MAX_BYTES", "1MB");
+        assert!(BodyLimits::from_settings(&Settings::default()).is_err());
+        env::remove_var("CREATE_SESSION_BODY_MAX_BYTES");
+    }
+
+    #[test]
+    #[serial]
+    fn test_cli_flag_allowlist() {
+        env::remove_var("CLAUDE_CLI_FLAG_ALLOWLIST");
+        let none = CliFlagAllowlist::from_settings(&Settings::default()).unwrap();
+        let model = ("model".to_string(), "opus".to_string());
+        assert!(none.check([(&model.0, &model.1)]).is_err());
+
+        env::set_var("CLAUDE_CLI_FLAG_ALLOWLIST", "model, --max-turns");
+        let allowlist = CliFlagAllowlist::from_settings(&Settings::default()).unwrap();
+        let turns = ("max-turns".to_string(), "5".to_string());
+        assert!(allowlist
+            .check([(&model.0, &model.1), (&turns.0, &turns.1)])
+            .is_ok());
+        let sneaky = (
+            "model".to_string(),
+            "--dangerously-skip-permissions".to_string(),
+        );
+        assert!(allowlist.check([(&sneaky.0, &sneaky.1)]).is_err());
+        let mode = ("permission-mode".to_string(), "plan".to_string());
+        assert!(allowlist.check([(&mode.0, &mode.1)]).is_err());
+
+        env::set_var("CLAUDE_CLI_FLAG_ALLOWLIST", "model,resume");
+        assert!(CliFlagAllowlist::from_settings(&Settings::default()).is_err());
+        env::set_var("CLAUDE_CLI_FLAG_ALLOWLIST", "model opus");
+        assert!(CliFlagAllowlist::from_settings(&Settings::default()).is_err());
+        env::remove_var("CLAUDE_CLI_FLAG_ALLOWLIST");
+    }
+
+    #[test]
+    #[serial]
+    fn test_restart_policy() {
+        env::remove_var("CLAUDE_RESTART_MAX");
+        env::set_var("CLAUDE_RESTART_BACKOFF_MS", "500");
+        assert_eq!(
+            RestartPolicy::from_settings(&Settings::default()).unwrap(),
+            None
+        );
+
+        env::set_var("CLAUDE_RESTART_MAX", "3");
+        let policy = RestartPolicy::from_settings(&Settings::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(policy.max_restarts, 3);
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(2));
+        assert_eq!(policy.backoff(40), RestartPolicy::MAX_BACKOFF);
+
+        env::set_var("CLAUDE_RESTART_MAX", "0");
+        assert_eq!(
+            RestartPolicy::from_settings(&Settings::default()).unwrap(),
+            None
+        );
+        env::set_var("CLAUDE_RESTART_MAX", "-1");
+        assert!(RestartPolicy::from_settings(&Settings::default()).is_err());
+        env::remove_var("CLAUDE_RESTART_MAX");
+        env::remove_var("CLAUDE_RESTART_BACKOFF_MS");
+    }
+
+    #[test]
+    #[serial]
+    fn test_resource_limits_from_env() {
+        let limits = ResourceLimits::from_settings(&Settings::default()).unwrap();
+        assert!(limits.is_unlimited());
+
+        env::set_var("CLAUDE_MEMORY_LIMIT_MB", "2048");
+        env::set_var("CLAUDE_CPU_LIMIT_CORES", "1.5");
+        assert!(ResourceLimits::from_settings(&Settings::default()).is_err());
+        env::set_var("CLAUDE_CGROUP_ROOT", "/sys/fs/cgroup/chef-de-vibe");
+        assert_eq!(
+            ResourceLimits::from_settings(&Settings::default()).unwrap(),
+            ResourceLimits {
+                memory_bytes: Some(2048 * 1024 * 1024),
+                cpu_secs: None,
+                cpu_cores: Some(1.5),
+                max_processes: None,
+                cgroup_root: Some(PathBuf::from("/sys/fs/cgroup/chef-de-vibe")),
+            }
+        );
+        env::set_var("CLAUDE_PROCESS_LIMIT", "lots");
+        assert!(ResourceLimits::from_settings(&Settings::default()).is_err());
+        env::remove_var("CLAUDE_MEMORY_LIMIT_MB");
+        env::remove_var("CLAUDE_CPU_LIMIT_CORES");
+        env::remove_var("CLAUDE_CGROUP_ROOT");
+        env::remove_var("CLAUDE_PROCESS_LIMIT");
+    }
+
+    #[test]
+    #[serial]
+    fn test_otlp_settings_from_env() {
+        env::remove_var("OTLP_ENDPOINT");
+        env::set_var("OTLP_SAMPLE_RATIO", "0.25");
+        assert_eq!(
+            OtlpSettings::from_settings(&Settings::default()).unwrap(),
+            None
+        );
+
+        env::set_var("OTLP_ENDPOINT", "http://tempo:4318");
+        assert_eq!(
+            OtlpSettings::from_settings(&Settings::default()).unwrap(),
+            Some(OtlpSettings {
+                endpoint: "http://tempo:4318".to_string(),
+                service_name: "chef-de-vibe".to_string(),
+                sample_ratio: 0.25,
+            })
+        );
+        env::set_var("OTLP_SAMPLE_RATIO", "1.5");
+        assert!(OtlpSettings::from_settings(&Settings::default()).is_err());
+        env::set_var("OTLP_SAMPLE_RATIO", "half");
+        assert!(OtlpSettings::from_settings(&Settings::default()).is_err());
+        env::remove_var("OTLP_ENDPOINT");
+        env::remove_var("OTLP_SAMPLE_RATIO");
+    }
+
+    #[test]
+    #[serial]
+    fn test_wire_log_settings_from_env() {
+        env::remove_var("WIRE_LOG_DIR");
+        env::set_var("WIRE_LOG_MAX_FILES", "3");
+        assert_eq!(
+            WireLogSettings::from_settings(&Settings::default()).unwrap(),
+            None
+        );
+
+        env::set_var("WIRE_LOG_DIR", "/var/log/chef-de-vibe/wire");
+        assert_eq!(
+            WireLogSettings::from_settings(&Settings::default()).unwrap(),
+            Some(WireLogSettings {
+                dir: PathBuf::from("/var/log/chef-de-vibe/wire"),
+                max_file_bytes: 10 * 1024 * 1024,
+                max_files: 3,
+            })
+        );
+        env::set_var("WIRE_LOG_MAX_FILE_MB", "0");
+        assert!(WireLogSettings::from_settings(&Settings::default()).is_err());
+        env::remove_var("WIRE_LOG_DIR");
+        env::remove_var("WIRE_LOG_MAX_FILES");
+        env::remove_var("WIRE_LOG_MAX_FILE_MB");
+    }
+
+    #[test]
+    #[serial]
+    fn test_session_capacity_from_env() {
+        env::remove_var("MAX_RUNNING_SESSIONS");
+        env::set_var("SESSION_QUEUE_TIMEOUT", "30");
+        assert_eq!(
+            SessionCapacity::from_settings(&Settings::default()).unwrap(),
+            None
+        );
+
+        env::set_var("MAX_RUNNING_SESSIONS", "4");
+        assert_eq!(
+            SessionCapacity::from_settings(&Settings::default()).unwrap(),
+            Some(SessionCapacity {
+                max_running: 4,
+                queue_timeout: Some(Duration::from_secs(30)),
+            })
+        );
+        env::remove_var("SESSION_QUEUE_TIMEOUT");
+        assert_eq!(
+            SessionCapacity::from_settings(&Settings::default())
+                .unwrap()
+                .unwrap()
+                .queue_timeout,
+            None
+        );
+
+        env::set_var("MAX_RUNNING_SESSIONS", "0");
+        assert!(SessionCapacity::from_settings(&Settings::default()).is_err());
+        env::set_var("MAX_RUNNING_SESSIONS", "many");
+        assert!(SessionCapacity::from_settings(&Settings::default()).is_err());
+        env::remove_var("MAX_RUNNING_SESSIONS");
+    }
+
+    #[test]
+    #[serial]
+    fn test_tls_settings_from_env() {
+        env::remove_var("TLS_CERT_PATH");
+        env::remove_var("TLS_KEY_PATH");
+        assert_eq!(
+            TlsSettings::from_settings(&Settings::default()).unwrap(),
+            None
+        );
+
+        env::set_var("TLS_CERT_PATH", "/etc/chef/cert.pem");
+        assert!(TlsSettings::from_settings(&Settings::default()).is_err());
+        env::set_var("TLS_KEY_PATH", "/etc/chef/key.pem");
+        assert_eq!(
+            TlsSettings::from_settings(&Settings::default()).unwrap(),
+            Some(TlsSettings {
+                cert_path: PathBuf::from("/etc/chef/cert.pem"),
+                key_path: PathBuf::from("/etc/chef/key.pem"),
+            })
+        );
+        env::remove_var("TLS_CERT_PATH");
+        env::remove_var("TLS_KEY_PATH");
+    }
+
+    #[test]
+    fn test_approval_webhook_settings() {
+        let settings = Settings::default().with_override("APPROVAL_WEBHOOK_URL", "");
+        assert_eq!(
+            ApprovalWebhookSettings::from_settings(&settings).unwrap(),
+            None
+        );
+
+        let settings = Settings::default().with_override("APPROVAL_WEBHOOK_URL", "hooks.example");
+        assert!(ApprovalWebhookSettings::from_settings(&settings).is_err());
+
+        let settings = Settings::default()
+            .with_override("APPROVAL_WEBHOOK_URL", "https://hooks.example/approvals")
+            .with_override("APPROVAL_WEBHOOK_SECRET", "s3cret")
+            .with_override("APPROVAL_WEBHOOK_TIMEOUT_SECS", "0");
+        assert_eq!(
+            ApprovalWebhookSettings::from_settings(&settings).unwrap(),
+            Some(ApprovalWebhookSettings {
+                url: "https://hooks.example/approvals".to_string(),
+                secret: Some("s3cret".to_string()),
+                timeout: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_push_settings() {
+        let settings = Settings::default()
+            .with_override("NTFY_TOPIC_URL", "")
+            .with_override("GOTIFY_URL", "");
+        assert_eq!(PushSettings::from_settings(&settings).unwrap(), None);
+
+        // ntfy needs a topic, Gotify its application token
+        let no_topic = settings
+            .clone()
+            .with_override("NTFY_TOPIC_URL", "https://ntfy.sh");
+        assert!(PushSettings::from_settings(&no_topic).is_err());
+        let no_token = settings
+            .clone()
+            .with_override("GOTIFY_URL", "https://gotify.example");
+        assert!(PushSettings::from_settings(&no_token).is_err());
+
+        let settings = settings
+            .with_override("NTFY_TOPIC_URL", "https://ntfy.sh/chef")
+            .with_override("GOTIFY_URL", "https://gotify.example")
+            .with_override("GOTIFY_APP_TOKEN", "app-token")
+            .with_override("PUSH_NOTIFY_COMPLETED", "false");
+        let push = PushSettings::from_settings(&settings).unwrap().unwrap();
+        assert_eq!(
+            push.backends,
+            vec![
+                PushBackend::Ntfy {
+                    topic_url: "https://ntfy.sh/chef".to_string(),
+                    token: None,
+                },
+                PushBackend::Gotify {
+                    server_url: "https://gotify.example".to_string(),
+                    app_token: "app-token".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            push.events,
+            BTreeSet::from([
+                WatchEvent::Failed,
+                WatchEvent::ApprovalNeeded,
+                WatchEvent::Crashed
+            ])
+        );
+        assert!(!format!("{push:?}").contains("app-token"));
+    }
+
+    #[test]
+    fn test_slack_settings() {
+        let settings = Settings::default().with_override("SLACK_BOT_TOKEN", "");
+        assert_eq!(SlackSettings::from_settings(&settings).unwrap(), None);
+
+        // Callbacks cannot be verified without the signing secret
+        let settings = Settings::default()
+            .with_override("SLACK_BOT_TOKEN", "xoxb-1")
+            .with_override("SLACK_CHANNEL", "#approvals")
+            .with_override("SLACK_SIGNING_SECRET", "");
+        assert!(SlackSettings::from_settings(&settings).is_err());
+
+        let settings = settings.with_override("SLACK_SIGNING_SECRET", "signing");
+        let slack = SlackSettings::from_settings(&settings).unwrap().unwrap();
+        assert_eq!(slack.channel, "#approvals");
+        assert!(!format!("{slack:?}").contains("xoxb-1"));
+    }
+
     #[test]
     #[serial]
     fn test_config_validation() {
@@ -204,6 +2721,239 @@ mod tests {
         let config = Config::from_env().unwrap();
         assert_eq!(config.http_listen_address, "127.0.0.1:8080");
         assert_eq!(config.shutdown_timeout, Duration::from_secs(30));
+        assert_eq!(config.stdout_mode, StdoutMode::Strict);
+        assert_eq!(config.dead_session_grace_period, Duration::from_secs(60));
+        assert!(config.telemetry_endpoint.is_none());
+        assert!(config.working_dir_roots.is_empty());
+        assert!(config.identity_header.is_none());
+        assert_eq!(config.session_backend, None);
+
+        env::set_var("SESSION_BACKEND", "container");
+        assert!(Config::from_env().is_err());
+        env::set_var("CONTAINER_IMAGE", "claude-sandbox:latest");
+        assert_eq!(
+            Config::from_env().unwrap().session_backend,
+            Some(BackendKind::Container)
+        );
+        env::set_var("SESSION_BACKEND", "vm");
+        assert!(Config::from_env().is_err());
+        env::remove_var("SESSION_BACKEND");
+        env::remove_var("CONTAINER_IMAGE");
+    }
+
+    #[test]
+    fn test_stdout_mode_from_str() {
+        assert_eq!("strict".parse::<StdoutMode>().unwrap(), StdoutMode::Strict);
+        assert_eq!(
+            "Tolerant".parse::<StdoutMode>().unwrap(),
+            StdoutMode::Tolerant
+        );
+        assert!("lenient".parse::<StdoutMode>().is_err());
+    }
+
+    #[test]
+    fn test_disconnect_queue_policy_from_str() {
+        assert_eq!(
+            "HOLD".parse::<DisconnectQueuePolicy>().unwrap(),
+            DisconnectQueuePolicy::Hold
+        );
+        assert_eq!(
+            "drop".parse::<DisconnectQueuePolicy>().unwrap(),
+            DisconnectQueuePolicy::Drop
+        );
+        assert!("flush".parse::<DisconnectQueuePolicy>().is_err());
+    }
+
+    #[test]
+    fn test_parse_transcript_hooks() {
+        let hooks =
+            parse_transcript_hooks("translate=/usr/bin/translate --to de; scrub=scrub-pii;")
+                .unwrap();
+        assert_eq!(hooks.len(), 2);
+        assert_eq!(hooks["translate"], vec!["/usr/bin/translate", "--to", "de"]);
+        assert_eq!(hooks["scrub"], vec!["scrub-pii"]);
+
+        assert!(parse_transcript_hooks("no-command").is_err());
+        assert!(parse_transcript_hooks("empty=").is_err());
+    }
+
+    #[test]
+    fn test_parse_notification_targets() {
+        let targets = parse_notification_targets(
+            "me=webhook:https://example.com/hook; team=Slack:https://hooks.slack.com/services/T/B/x;oncall=email:ops@example.com",
+        )
+        .unwrap();
+        assert_eq!(targets.len(), 3);
+        assert_eq!(
+            targets["me"],
+            NotificationTarget::Webhook("https://example.com/hook".to_string())
+        );
+        assert_eq!(
+            targets["team"],
+            NotificationTarget::Slack("https://hooks.slack.com/services/T/B/x".to_string())
+        );
+        assert_eq!(
+            targets["oncall"],
+            NotificationTarget::Email("ops@example.com".to_string())
+        );
+
+        assert!(parse_notification_targets("me=https://example.com").is_err());
+        assert!(parse_notification_targets("me=pager:123").is_err());
+        assert!(parse_notification_targets("me=webhook:").is_err());
+        assert!(parse_notification_targets("=webhook:https://example.com").is_err());
+    }
+
+    #[test]
+    fn test_parse_event_sinks() {
+        assert_eq!(
+            "nats:127.0.0.1:4222/ci.sessions"
+                .parse::<EventSink>()
+                .unwrap(),
+            EventSink::Nats {
+                address: "127.0.0.1:4222".to_string(),
+                subject_prefix: "ci.sessions".to_string(),
+            }
+        );
+        assert_eq!(
+            "NATS:nats.internal:4222".parse::<EventSink>().unwrap(),
+            EventSink::Nats {
+                address: "nats.internal:4222".to_string(),
+                subject_prefix: "chef_de_vibe".to_string(),
+            }
+        );
+        assert_eq!(
+            "kafka:http://proxy:8082/session-events"
+                .parse::<EventSink>()
+                .unwrap(),
+            EventSink::KafkaRest {
+                proxy_url: "http://proxy:8082".to_string(),
+                topic: "session-events".to_string(),
+            }
+        );
+
+        assert!("kafka:http://proxy:8082/".parse::<EventSink>().is_err());
+        assert!("kafka:session-events".parse::<EventSink>().is_err());
+        assert!("nats:".parse::<EventSink>().is_err());
+        assert!("amqp:localhost:5672".parse::<EventSink>().is_err());
+    }
+
+    #[test]
+    fn test_parse_working_dir_roots() {
+        let temp_dir = TempDir::new().unwrap();
+        let projects = temp_dir.path().join("projects");
+        fs::create_dir_all(&projects).unwrap();
+        let canonical = fs::canonicalize(&projects).unwrap();
+
+        let value = env::join_paths([projects.join("."), PathBuf::new()]).unwrap();
+        assert_eq!(parse_working_dir_roots(&value).unwrap(), vec![canonical]);
+
+        assert!(parse_working_dir_roots(std::ffi::OsStr::new("relative/dir")).is_err());
+        assert!(parse_working_dir_roots(temp_dir.path().join("missing").as_os_str()).is_err());
+    }
+
+    #[test]
+    fn test_session_limits_narrowed() {
+        let global = SessionLimits {
+            max_duration: Some(Duration::from_secs(90)),
+            max_turns: None,
+            action: LimitAction::Terminate,
+        };
+        let limits = global.narrowed(Some(Duration::from_secs(150)), Some(5));
+        assert_eq!(limits.max_duration, Some(Duration::from_secs(90)));
+        assert_eq!(limits.max_turns, Some(5));
+        assert_eq!(limits.action, LimitAction::Terminate);
+        assert_eq!(global.narrowed(None, None), global);
+
+        assert_eq!(
+            "Reject_Input".parse::<LimitAction>().unwrap(),
+            LimitAction::RejectInput
+        );
+        assert!("pause".parse::<LimitAction>().is_err());
+    }
+
+    #[test]
+    fn test_parse_control_routes() {
+        let routes = parse_control_routes("hook_callback=main; *=Approvals").unwrap();
+        assert_eq!(routes.route("hook_callback"), ControlRoute::Main);
+        assert_eq!(routes.route("can_use_tool"), ControlRoute::Approvals);
+        assert_eq!(routes.route("mcp_message"), ControlRoute::Approvals);
+
+        let defaults = ControlRoutes::default();
+        assert_eq!(defaults.route("can_use_tool"), ControlRoute::Approvals);
+        assert_eq!(defaults.route("hook_callback"), ControlRoute::Auto);
+
+        assert!(parse_control_routes("hook_callback").is_err());
+        assert!(parse_control_routes("=main").is_err());
+        assert!(parse_control_routes("hook_callback=ignore").is_err());
+    }
+
+    #[test]
+    fn test_shutdown_policies() {
+        let policies = "ci=kill; migration=protected:7200"
+            .parse::<ShutdownPolicies>()
+            .unwrap();
+        let tags = |tags: &[&str]| tags.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(policies.behavior(&tags(&["ci"])), ShutdownBehavior::Kill);
+        assert_eq!(policies.behavior(&[]), ShutdownBehavior::Terminate);
+        assert_eq!(
+            policies.behavior(&tags(&["other"])),
+            ShutdownBehavior::Terminate
+        );
+        // A session tagged for both is given the more patient treatment
+        assert_eq!(
+            policies.behavior(&tags(&["ci", "migration"])),
+            ShutdownBehavior::Protected {
+                deadline: Duration::from_secs(2 * 60 * 60)
+            }
+        );
+
+        let policies = "*=kill".parse::<ShutdownPolicies>().unwrap();
+        assert_eq!(policies.behavior(&tags(&["ci"])), ShutdownBehavior::Kill);
+
+        assert!("ci".parse::<ShutdownPolicies>().is_err());
+        assert!("=kill".parse::<ShutdownPolicies>().is_err());
+        assert!("ci=protected".parse::<ShutdownPolicies>().is_err());
+        assert!("ci=protected:soon".parse::<ShutdownPolicies>().is_err());
+        assert!("ci=pause".parse::<ShutdownPolicies>().is_err());
+    }
+
+    #[test]
+    fn test_api_token() {
+        let token = ApiToken::new("s3cr3t-token_1").unwrap();
+        let keys = ApiKeys::new(vec![ApiKey {
+            token: token.clone(),
+            user: None,
+            admin: true,
+        }]);
+        assert!(keys.find("s3cr3t-token_1").is_some());
+        assert!(keys.find("s3cr3t-token_2").is_none());
+        assert!(keys.find("").is_none());
+        assert!(!keys.has_users());
+        assert_eq!(format!("{token:?}"), "ApiToken(<redacted>)");
+
+        assert!(ApiToken::new("").is_err());
+        assert!(ApiToken::new("with space").is_err());
+        assert!(ApiToken::new("base64/with=").is_err());
+    }
+
+    #[test]
+    fn test_parse_api_keys() {
+        let keys =
+            ApiKeys::new(parse_api_keys("alice=tok-a; bob = tok-b ;ops=tok-o:admin").unwrap());
+        assert!(keys.has_users());
+        let alice = keys.find("tok-a").unwrap();
+        assert_eq!(alice.user.as_deref(), Some("alice"));
+        assert!(!alice.admin);
+        assert_eq!(keys.find("tok-b").unwrap().user.as_deref(), Some("bob"));
+        assert!(keys.find("tok-o").unwrap().admin);
+        assert!(keys.find("tok-x").is_none());
+
+        assert!(parse_api_keys("").unwrap().is_empty());
+        assert!(parse_api_keys("alice").is_err());
+        assert!(parse_api_keys("=tok").is_err());
+        assert!(parse_api_keys("alice=tok:root").is_err());
+        assert!(parse_api_keys("alice=tok; bob=tok").is_err());
+        assert!(parse_api_keys("alice=bad token").is_err());
     }
 
     #[test]
@@ -213,6 +2963,53 @@ mod tests {
             http_listen_address: "127.0.0.1:8080".to_string(),
             claude_projects_dir: PathBuf::from("/home/user/.claude/projects"),
             shutdown_timeout: Duration::from_secs(30),
+            terminate_timeout: Duration::from_secs(30),
+            shutdown_policies: ShutdownPolicies::default(),
+            stdout_mode: StdoutMode::Strict,
+            session_state_dir: None,
+            approval_sync_dir: None,
+            mdns_instance_name: None,
+            transcript_hooks: HashMap::new(),
+            security_headers: SecurityHeaders::default(),
+            claude_sdk_socket: None,
+            disconnect_queue_policy: DisconnectQueuePolicy::Keep,
+            notification_targets: HashMap::new(),
+            dead_session_grace_period: Duration::from_secs(60),
+            telemetry_endpoint: None,
+            working_dir_roots: Vec::new(),
+            identity_header: None,
+            api_keys: ApiKeys::default(),
+            control_routes: ControlRoutes::default(),
+            session_limits: SessionLimits::default(),
+            session_capacity: None,
+            partial_messages: false,
+            process_monitoring: ProcessMonitoring::default(),
+            resource_limits: ResourceLimits::default(),
+            restart_policy: None,
+            cli_flag_allowlist: CliFlagAllowlist::default(),
+            session_response_max_bytes: None,
+            container: None,
+            session_backend: None,
+            risk_approvers: RiskApprovers::default(),
+            risk_patterns: RiskPatterns::default(),
+            working_dir_sandbox: SandboxMode::default(),
+            frontend_dir: None,
+            event_sinks: Vec::new(),
+            body_limits: BodyLimits::default(),
+            record_client_inputs: false,
+            web_push: None,
+            tls: None,
+            write_pacing: None,
+            approval_priority: None,
+            approval_webhook: None,
+            slack: None,
+            push: None,
+            scratch: None,
+            wire_log: None,
+            slo: SloSettings::default(),
+            heartbeat: Heartbeat::default(),
+            swagger_ui: false,
+            cors_allowed_origins: Vec::new(),
         };
 
         let working_dir = Path::new("/home/user/my-project");
@@ -0,0 +1,112 @@
+use crate::config::SETTING_NAMES;
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Reads the settings of a config file given with `--config` or `CHEF_DE_VIBE_CONFIG`:
+/// a TOML table, or a YAML mapping for `.yaml` and `.yml` files, whose keys are the
+/// names of the environment variables they stand for, in any case
+/// (`max_running_sessions = 4`).
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or parsed, names a setting that does not
+/// exist, or has a value that is not a string, number or boolean.
+pub fn read(path: &Path) -> Result<BTreeMap<String, String>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    let is_yaml = path
+        .extension()
+        .is_some_and(|extension| extension == "yaml" || extension == "yml");
+    let settings = if is_yaml {
+        parse_yaml(&text)
+    } else {
+        parse_toml(&text)
+    };
+    settings.with_context(|| format!("Invalid config file {}", path.display()))
+}
+
+fn parse_toml(text: &str) -> Result<BTreeMap<String, String>> {
+    let table: toml::Table = text.parse()?;
+    table
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                toml::Value::String(value) => value,
+                toml::Value::Integer(value) => value.to_string(),
+                toml::Value::Float(value) => value.to_string(),
+                toml::Value::Boolean(value) => value.to_string(),
+                other => bail!(
+                    "{key} must be a string, number or boolean, not {}",
+                    other.type_str()
+                ),
+            };
+            setting(&key, value)
+        })
+        .collect()
+}
+
+fn parse_yaml(text: &str) -> Result<BTreeMap<String, String>> {
+    let mapping: serde_yaml::Mapping = serde_yaml::from_str(text)?;
+    mapping
+        .into_iter()
+        .map(|(key, value)| {
+            let Some(key) = key.as_str() else {
+                bail!("setting names must be strings, got {key:?}");
+            };
+            let value = match value {
+                serde_yaml::Value::String(value) => value,
+                serde_yaml::Value::Number(value) => value.to_string(),
+                serde_yaml::Value::Bool(value) => value.to_string(),
+                _ => bail!("{key} must be a string, number or boolean"),
+            };
+            setting(key, value)
+        })
+        .collect()
+}
+
+fn setting(key: &str, value: String) -> Result<(String, String)> {
+    let name = key.to_ascii_uppercase();
+    if !SETTING_NAMES.contains(&name.as_str()) {
+        bail!("{key} is not a setting");
+    }
+    Ok((name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_names_settings_like_variables() {
+        let settings = parse_toml(
+            "http_listen_address = \"0.0.0.0:8080\"\nMAX_RUNNING_SESSIONS = 4\nswagger_ui = true\n",
+        )
+        .unwrap();
+        assert_eq!(settings["HTTP_LISTEN_ADDRESS"], "0.0.0.0:8080");
+        assert_eq!(settings["MAX_RUNNING_SESSIONS"], "4");
+        assert_eq!(settings["SWAGGER_UI"], "true");
+    }
+
+    #[test]
+    fn test_parse_rejects_tables() {
+        let error = parse_toml("[limits]\nmax = 1\n").unwrap_err();
+        assert!(error.to_string().contains("limits"));
+    }
+
+    #[test]
+    fn test_parse_yaml() {
+        let settings =
+            parse_yaml("session_max_turns: 20\ncontrol_request_routes: \"*=deny\"\n").unwrap();
+        assert_eq!(settings["SESSION_MAX_TURNS"], "20");
+        assert_eq!(settings["CONTROL_REQUEST_ROUTES"], "*=deny");
+        assert!(parse_yaml("limits:\n  max: 1\n").is_err());
+    }
+
+    #[test]
+    fn test_unknown_settings_are_rejected() {
+        let error = parse_toml("max_sessions = 4\n").unwrap_err();
+        assert_eq!(error.to_string(), "max_sessions is not a setting");
+        assert!(parse_yaml("http_listen_adress: x\n").is_err());
+    }
+}
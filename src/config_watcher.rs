@@ -0,0 +1,158 @@
+use crate::config::{Config, Settings, RELOADABLE_SETTINGS};
+use crate::config_file;
+use crate::session_manager::SessionManager;
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Pause after a change before the file is read, so an editor's save is read once and
+/// in full.
+const SETTLE_DELAY: Duration = Duration::from_millis(250);
+
+/// Rereads the config file whenever it changes and applies the settings of
+/// [`RELOADABLE_SETTINGS`] to the session manager. Stops when dropped.
+pub struct ConfigWatcher {
+    _watcher: RecommendedWatcher,
+    task: JoinHandle<()>,
+}
+
+impl ConfigWatcher {
+    /// Starts watching `path`, whose settings at startup are those of `settings`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory of `path` cannot be watched.
+    pub fn start(path: PathBuf, settings: Settings, manager: Arc<SessionManager>) -> Result<Self> {
+        let changed = Arc::new(Notify::new());
+        // Editors replace the file rather than write to it, so its directory is watched
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let mut watcher = {
+            let changed = changed.clone();
+            let file_name = path.file_name().map(ToOwned::to_owned);
+            notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+                Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+                Ok(event) => {
+                    if event
+                        .paths
+                        .iter()
+                        .any(|changed| changed.file_name() == file_name.as_deref())
+                    {
+                        changed.notify_one();
+                    }
+                }
+                Err(e) => warn!(error = %e, "Config file watcher lost track of changes"),
+            })
+        }
+        .context("Failed to create config file watcher")?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch {}", dir.display()))?;
+        info!(path = %path.display(), "Watching config file for changes");
+
+        let task = tokio::spawn(async move {
+            let mut settings = settings;
+            loop {
+                changed.notified().await;
+                tokio::time::sleep(SETTLE_DELAY).await;
+                settings = reload(&path, settings, &manager);
+            }
+        });
+
+        Ok(Self {
+            _watcher: watcher,
+            task,
+        })
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Applies the config file at `path` on top of `settings` and returns the settings now
+/// in effect; a file that does not load leaves everything as it was.
+fn reload(path: &Path, settings: Settings, manager: &SessionManager) -> Settings {
+    let file = match config_file::read(path) {
+        Ok(file) => file,
+        Err(e) => {
+            warn!(
+                error = format!("{e:#}"),
+                "Ignoring config file that does not load"
+            );
+            return settings;
+        }
+    };
+    if &file == settings.file() {
+        return settings;
+    }
+
+    let changed = changed_settings(settings.file(), &file);
+    let reloaded = settings.clone().with_file(file);
+    let config = match Config::from_settings(&reloaded) {
+        Ok(config) => config,
+        Err(e) => {
+            warn!(error = format!("{e:#}"), "Ignoring invalid config file");
+            return settings;
+        }
+    };
+    let needs_restart: Vec<&String> = changed
+        .iter()
+        .filter(|name| !RELOADABLE_SETTINGS.contains(&name.as_str()))
+        .collect();
+    if !needs_restart.is_empty() {
+        warn!(
+            settings = ?needs_restart,
+            "Config file changed settings that only take effect after a restart"
+        );
+    }
+    manager.reload_config(&config);
+    info!(path = %path.display(), settings = ?changed, "Reloaded config file");
+    reloaded
+}
+
+/// Names of the settings `new` adds, removes or changes compared to `old`.
+fn changed_settings(old: &BTreeMap<String, String>, new: &BTreeMap<String, String>) -> Vec<String> {
+    let mut changed: Vec<String> = old
+        .keys()
+        .chain(new.keys())
+        .filter(|name| old.get(*name) != new.get(*name))
+        .cloned()
+        .collect();
+    changed.sort_unstable();
+    changed.dedup();
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_settings() {
+        let old = BTreeMap::from([
+            ("SESSION_MAX_TURNS".to_string(), "10".to_string()),
+            ("SWAGGER_UI".to_string(), "true".to_string()),
+            ("SHUTDOWN_TIMEOUT".to_string(), "30".to_string()),
+        ]);
+        let new = BTreeMap::from([
+            ("SESSION_MAX_TURNS".to_string(), "20".to_string()),
+            ("SHUTDOWN_TIMEOUT".to_string(), "30".to_string()),
+            ("WORKING_DIR_ROOTS".to_string(), "/work".to_string()),
+        ]);
+        assert_eq!(
+            changed_settings(&old, &new),
+            vec!["SESSION_MAX_TURNS", "SWAGGER_UI", "WORKING_DIR_ROOTS"]
+        );
+    }
+}
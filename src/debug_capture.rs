@@ -0,0 +1,230 @@
+use dashmap::DashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Filter, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Default and maximum time a debug capture stays on before it switches itself off.
+pub const DEFAULT_DEBUG_DURATION: Duration = Duration::from_secs(10 * 60);
+pub const MAX_DEBUG_DURATION: Duration = Duration::from_secs(60 * 60);
+
+fn debug_sessions() -> &'static DashSet<String> {
+    static SESSIONS: OnceLock<DashSet<String>> = OnceLock::new();
+    SESSIONS.get_or_init(DashSet::new)
+}
+
+/// Turns on trace-level logging for spans tagged with `session_id`.
+pub fn enable_session_tracing(session_id: &str) {
+    debug_sessions().insert(session_id.to_string());
+}
+
+pub fn disable_session_tracing(session_id: &str) {
+    debug_sessions().remove(session_id);
+}
+
+/// The `session_id` field of a span, kept in its extensions for [`SessionDebugFilter`].
+struct SessionTag(String);
+
+struct SessionIdVisitor(Option<String>);
+
+impl Visit for SessionIdVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "session_id" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "session_id" {
+            self.0 = Some(format!("{value:?}").trim_matches('"').to_string());
+        }
+    }
+}
+
+/// Remembers which session each span belongs to.
+pub struct SessionTagLayer;
+
+impl SessionTagLayer {
+    fn tag<S>(values: &Record<'_>, id: &Id, ctx: &Context<'_, S>)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let mut visitor = SessionIdVisitor(None);
+        values.record(&mut visitor);
+        if let (Some(session_id), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().replace(SessionTag(session_id));
+        }
+    }
+}
+
+impl<S> Layer<S> for SessionTagLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        Self::tag(&Record::new(attrs.values()), id, &ctx);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        Self::tag(values, id, &ctx);
+    }
+}
+
+fn is_session_span(metadata: &Metadata<'_>) -> bool {
+    metadata.fields().field("session_id").is_some()
+}
+
+/// Lets everything through for sessions with debug capture on; combine with the
+/// global filter via `FilterExt::or`.
+pub struct SessionDebugFilter;
+
+impl<S> Filter<S> for SessionDebugFilter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn enabled(&self, metadata: &Metadata<'_>, cx: &Context<'_, S>) -> bool {
+        // Session spans must always be let through, otherwise they are invisible to
+        // this filter's context when debugging is switched on later
+        if metadata.is_span() {
+            return is_session_span(metadata);
+        }
+        if debug_sessions().is_empty() {
+            return false;
+        }
+        cx.lookup_current().is_some_and(|span| {
+            span.scope().any(|span| {
+                span.extensions()
+                    .get::<SessionTag>()
+                    .is_some_and(|tag| debug_sessions().contains(&tag.0))
+            })
+        })
+    }
+
+    fn callsite_enabled(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if metadata.is_span() {
+            if is_session_span(metadata) {
+                Interest::always()
+            } else {
+                Interest::never()
+            }
+        } else {
+            // Depends on the current span, so it has to be decided per event
+            Interest::sometimes()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Direction {
+    Stdin,
+    Stdout,
+}
+
+/// Raw stdin/stdout capture for a single session.
+#[derive(Debug)]
+pub struct DebugCapture {
+    pub path: PathBuf,
+    file: File,
+}
+
+impl DebugCapture {
+    /// Creates `<dir>/<session_id>-<unix time>.debug.log`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory or file cannot be created.
+    pub fn create(dir: &Path, session_id: &str) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let started = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let path = dir.join(format!("{session_id}-{started}.debug.log"));
+        let file = File::create(&path)?;
+        Ok(Self { path, file })
+    }
+
+    /// Appends one line of traffic, prefixed with its direction and a timestamp.
+    pub fn record(&mut self, direction: Direction, line: &str) {
+        let prefix = match direction {
+            Direction::Stdin => ">>",
+            Direction::Stdout => "<<",
+        };
+        let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        if let Err(e) = writeln!(self.file, "{timestamp} {prefix} {line}") {
+            tracing::warn!(path = %self.path.display(), error = %e, "Failed to write debug capture");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_debug_capture_records_both_directions() {
+        let dir = TempDir::new().unwrap();
+        let mut capture = DebugCapture::create(dir.path(), "debug-session").unwrap();
+        capture.record(Direction::Stdin, r#"{"role":"user"}"#);
+        capture.record(Direction::Stdout, r#"{"type":"assistant"}"#);
+
+        let contents = std::fs::read_to_string(&capture.path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(r#">> {"role":"user"}"#));
+        assert!(lines[1].ends_with(r#"<< {"type":"assistant"}"#));
+        assert!(capture
+            .path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("debug-session-"));
+    }
+
+    #[test]
+    fn test_session_tracing_filter() {
+        use std::sync::{Arc, Mutex};
+        use tracing_subscriber::layer::SubscriberExt;
+
+        #[derive(Clone, Default)]
+        struct Collected(Arc<Mutex<Vec<String>>>);
+
+        impl<S: Subscriber> Layer<S> for Collected {
+            fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(event.metadata().target().to_string());
+            }
+        }
+
+        let collected = Collected::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(SessionTagLayer)
+            .with(collected.clone().with_filter(SessionDebugFilter));
+
+        tracing::subscriber::with_default(subscriber, || {
+            enable_session_tracing("traced");
+
+            let traced = tracing::info_span!("work", session_id = %"traced");
+            traced.in_scope(|| tracing::trace!(target: "traced_event", "captured"));
+
+            let quiet = tracing::info_span!("work", session_id = %"quiet");
+            quiet.in_scope(|| tracing::trace!(target: "quiet_event", "dropped"));
+
+            disable_session_tracing("traced");
+            traced.in_scope(|| tracing::trace!(target: "after_disable", "dropped"));
+        });
+
+        assert_eq!(*collected.0.lock().unwrap(), vec!["traced_event"]);
+    }
+}
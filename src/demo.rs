@@ -0,0 +1,496 @@
+use crate::session_import::{self, ImportFormat};
+use anyhow::Context;
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Subcommand that starts the server in demo mode.
+pub const DEMO_COMMAND: &str = "demo";
+
+/// Hidden subcommand the demo runs in place of the Claude CLI.
+pub const BACKEND_COMMAND: &str = "demo-backend";
+
+/// What `--version` of the demo backend reports; recent enough for every CLI feature.
+const BACKEND_VERSION: &str = "1.0.86 (Chef de Vibe demo backend)";
+
+/// Throwaway environment of `chef-de-vibe demo`: a projects directory with example
+/// sessions, an example project to work in, and a fake Claude CLI.
+#[derive(Debug)]
+pub struct Demo {
+    pub dir: PathBuf,
+    pub projects_dir: PathBuf,
+    pub working_dir: PathBuf,
+    pub backend: PathBuf,
+}
+
+impl Demo {
+    /// Creates the demo environment under the temp directory and points
+    /// `CLAUDE_BINARY_PATH` and `CLAUDE_PROJECTS_DIR` at it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory, the backend wrapper or the example sessions
+    /// cannot be written.
+    pub fn prepare() -> anyhow::Result<Self> {
+        let dir = std::env::temp_dir().join(format!("chef-de-vibe-demo-{}", std::process::id()));
+        let demo = Self::create(&dir, &std::env::current_exe()?)?;
+        std::env::set_var("CLAUDE_BINARY_PATH", &demo.backend);
+        std::env::set_var("CLAUDE_PROJECTS_DIR", &demo.projects_dir);
+        info!(
+            demo_dir = %demo.dir.display(),
+            working_dir = %demo.working_dir.display(),
+            "Prepared demo environment"
+        );
+        Ok(demo)
+    }
+
+    fn create(dir: &Path, executable: &Path) -> anyhow::Result<Self> {
+        let projects_dir = dir.join("projects");
+        let working_dir = dir.join("example-project");
+        std::fs::create_dir_all(&projects_dir)?;
+        std::fs::create_dir_all(working_dir.join("src"))?;
+        std::fs::write(
+            working_dir.join("README.md"),
+            "# Example project\n\nA tiny project to try Chef de Vibe on.\n",
+        )?;
+        std::fs::write(
+            working_dir.join("src").join("main.rs"),
+            "fn main() {\n    println!(\"Hello from the demo\");\n}\n",
+        )?;
+
+        // The server runs whatever CLAUDE_BINARY_PATH names, so the backend gets a wrapper
+        let backend = dir.join("claude");
+        let quoted = executable.display().to_string().replace('\'', r"'\''");
+        std::fs::write(
+            &backend,
+            format!("#!/bin/sh\nexec '{quoted}' {BACKEND_COMMAND} \"$@\"\n"),
+        )?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&backend, std::fs::Permissions::from_mode(0o755))?;
+        }
+
+        session_import::import_sessions(
+            &projects_dir,
+            &working_dir,
+            ImportFormat::ClaudeDesktop,
+            example_conversations(),
+        )
+        .context("Failed to seed example sessions")?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            projects_dir,
+            working_dir,
+            backend,
+        })
+    }
+
+    /// Removes the demo environment, sessions created during the demo included.
+    pub fn cleanup(&self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.dir) {
+            warn!(demo_dir = %self.dir.display(), error = %e, "Failed to remove demo environment");
+        }
+    }
+}
+
+/// Example sessions, in the Claude Desktop export format the importer reads.
+fn example_conversations() -> Value {
+    let conversation = |name: &str, started: &str, messages: &[(&str, &str)]| {
+        json!({
+            "name": name,
+            "chat_messages": messages
+                .iter()
+                .map(|(sender, text)| json!({"sender": sender, "text": text, "created_at": started}))
+                .collect::<Vec<_>>(),
+        })
+    };
+    json!([
+        conversation(
+            "Add a greeting to the example project",
+            "2024-05-01T09:00:00Z",
+            &[
+                ("human", "Can you make main.rs greet the user by name?"),
+                ("assistant", "Sure. I read the name from the first argument and fall back to \"world\" when there is none."),
+                ("human", "Looks good, thanks!"),
+            ],
+        ),
+        conversation(
+            "Why is the build slow?",
+            "2024-05-02T14:30:00Z",
+            &[
+                ("human", "cargo build takes ages after every change. Any ideas?"),
+                ("assistant", "Incremental builds are slowed down most by heavy proc-macro dependencies. Splitting them into their own crate keeps them from being rebuilt."),
+            ],
+        ),
+        conversation(
+            "Write a README",
+            "2024-05-03T11:15:00Z",
+            &[
+                ("human", "Please write a short README for this project."),
+                ("assistant", "Done: README.md now explains what the project is for and how to run it."),
+            ],
+        ),
+    ])
+}
+
+/// Opens `url` in the default browser, if there is one to open it in.
+pub fn open_browser(url: &str) {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    match std::process::Command::new(opener)
+        .arg(url)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+    {
+        Ok(_) => info!(url, "Opened the demo in the browser"),
+        Err(e) => info!(url, error = %e, "Could not open a browser, open the demo URL by hand"),
+    }
+}
+
+/// The fake Claude CLI of the demo. It speaks the stream-json protocol on stdin and
+/// stdout and keeps a transcript under `CLAUDE_PROJECTS_DIR` like Claude does, but
+/// answers with canned replies. Asking it about files shows a tool approval.
+///
+/// # Errors
+///
+/// Returns an error if stdout cannot be written or the transcript cannot be resumed.
+pub fn run_backend(args: &[String]) -> anyhow::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    if args.iter().any(|arg| arg == "--version") {
+        writeln!(stdout, "{BACKEND_VERSION}")?;
+        return Ok(());
+    }
+
+    let flag = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|i| args.get(i + 1))
+    };
+    let cwd = std::env::current_dir()?;
+    let projects_dir = std::env::var_os("CLAUDE_PROJECTS_DIR").map(PathBuf::from);
+    let mut backend = match flag("--resume") {
+        Some(resumed) => Backend::resume(resumed, cwd, projects_dir.as_deref())?,
+        None => Backend::new(
+            flag("--session-id").map_or_else(|| Uuid::new_v4().to_string(), Clone::clone),
+            cwd,
+            projects_dir.as_deref(),
+        ),
+    };
+
+    emit(&mut stdout, &backend.init())?;
+    for line in std::io::stdin().lock().lines() {
+        let Ok(input) = serde_json::from_str::<Value>(&line?) else {
+            continue;
+        };
+        for output in backend.handle(&input) {
+            emit(&mut stdout, &output)?;
+        }
+    }
+    Ok(())
+}
+
+fn emit(stdout: &mut impl Write, value: &Value) -> std::io::Result<()> {
+    writeln!(stdout, "{value}")?;
+    stdout.flush()
+}
+
+/// Conversation state of the demo backend.
+struct Backend {
+    session_id: String,
+    cwd: PathBuf,
+    transcript: Option<PathBuf>,
+    parent_uuid: Option<String>,
+    /// Claude's request ID of the tool call awaiting approval
+    pending_tool: Option<String>,
+}
+
+impl Backend {
+    fn new(session_id: String, cwd: PathBuf, projects_dir: Option<&Path>) -> Self {
+        let transcript = projects_dir.map(|dir| {
+            dir.join(session_import::project_dir_name(&cwd))
+                .join(format!("{session_id}.jsonl"))
+        });
+        Self {
+            session_id,
+            cwd,
+            transcript,
+            parent_uuid: None,
+            pending_tool: None,
+        }
+    }
+
+    /// Continues `resumed` under a new session ID, copying its history like Claude does.
+    fn resume(resumed: &str, cwd: PathBuf, projects_dir: Option<&Path>) -> anyhow::Result<Self> {
+        let previous = Self::new(resumed.to_string(), cwd.clone(), projects_dir);
+        let mut backend = Self::new(Uuid::new_v4().to_string(), cwd, projects_dir);
+        let history = previous
+            .transcript
+            .as_deref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .unwrap_or_default();
+        for mut entry in history
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        {
+            if entry.get("sessionId").is_some() {
+                entry["sessionId"] = backend.session_id.clone().into();
+            }
+            if let Some(uuid) = entry.get("uuid").and_then(Value::as_str) {
+                backend.parent_uuid = Some(uuid.to_string());
+            }
+            backend.append(&entry)?;
+        }
+        Ok(backend)
+    }
+
+    fn init(&self) -> Value {
+        json!({
+            "type": "system",
+            "subtype": "init",
+            "session_id": self.session_id,
+            "cwd": self.cwd,
+            "tools": ["Bash"],
+            "model": "demo",
+        })
+    }
+
+    fn append(&self, entry: &Value) -> std::io::Result<()> {
+        let Some(path) = &self.transcript else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        writeln!(file, "{entry}")
+    }
+
+    /// Records `message` in the transcript and returns it as Claude's output line.
+    fn record(&mut self, message: &Value) -> Value {
+        let role = message["role"].clone();
+        let uuid = Uuid::new_v4().to_string();
+        let entry = json!({
+            "parentUuid": self.parent_uuid,
+            "isSidechain": false,
+            "userType": "external",
+            "cwd": self.cwd,
+            "sessionId": self.session_id,
+            "type": role,
+            "message": message,
+            "uuid": uuid,
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+        });
+        if let Err(e) = self.append(&entry) {
+            // Nothing may go to stdout but the protocol
+            eprintln!("Failed to write the demo transcript: {e}");
+        }
+        self.parent_uuid = Some(uuid);
+        json!({
+            "type": role,
+            "message": entry["message"],
+            "session_id": self.session_id,
+            "uuid": entry["uuid"],
+        })
+    }
+
+    fn assistant_text(&mut self, text: &str) -> Vec<Value> {
+        let message = self.record(&json!({
+            "id": format!("msg_{}", Uuid::new_v4().simple()),
+            "type": "message",
+            "role": "assistant",
+            "model": "demo",
+            "content": [{"type": "text", "text": text}],
+        }));
+        let result = json!({
+            "type": "result",
+            "subtype": "success",
+            "is_error": false,
+            "result": text,
+            "session_id": self.session_id,
+            "num_turns": 1,
+            "total_cost_usd": 0,
+            "usage": {"input_tokens": 0, "output_tokens": text.split_whitespace().count()},
+        });
+        vec![message, result]
+    }
+
+    fn handle(&mut self, input: &Value) -> Vec<Value> {
+        match input.get("type").and_then(Value::as_str) {
+            Some("user") => {
+                let text = message_text(&input["message"]["content"]);
+                self.record(&json!({"role": "user", "content": text}));
+                if text.to_lowercase().contains("file") {
+                    self.request_listing()
+                } else {
+                    self.assistant_text(&format!(
+                        "This is the Chef de Vibe demo, so no model is answering: you said \"{text}\". \
+                         Ask me to list the files of the project to see how tool approvals work."
+                    ))
+                }
+            }
+            Some("control_response") => {
+                let response = &input["response"];
+                let request_id = response["request_id"].as_str();
+                let Some(tool_use_id) = self
+                    .pending_tool
+                    .take_if(|pending| request_id == Some(pending.as_str()))
+                else {
+                    return Vec::new();
+                };
+                let allowed = response["response"]["behavior"] == "allow";
+                self.finish_listing(&tool_use_id, allowed)
+            }
+            // Interrupts and other requests of the server need no work here
+            Some("control_request") => vec![json!({
+                "type": "control_response",
+                "response": {"subtype": "success", "request_id": input["request_id"]},
+            })],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Announces an `ls` and asks for permission to run it, as Claude would.
+    fn request_listing(&mut self) -> Vec<Value> {
+        let request_id = Uuid::new_v4().to_string();
+        self.pending_tool = Some(request_id.clone());
+        let input = json!({"command": "ls", "description": "List the files of the project"});
+        let tool_use = self.record(&json!({
+            "id": format!("msg_{}", Uuid::new_v4().simple()),
+            "type": "message",
+            "role": "assistant",
+            "model": "demo",
+            "content": [{"type": "tool_use", "id": request_id, "name": "Bash", "input": input}],
+        }));
+        vec![
+            tool_use,
+            json!({
+                "type": "control_request",
+                "request_id": request_id,
+                "request": {"subtype": "can_use_tool", "tool_name": "Bash", "input": input},
+            }),
+        ]
+    }
+
+    fn finish_listing(&mut self, tool_use_id: &str, allowed: bool) -> Vec<Value> {
+        let (output, reply) = if allowed {
+            let mut names: Vec<String> = std::fs::read_dir(&self.cwd)
+                .map(|entries| {
+                    entries
+                        .filter_map(Result::ok)
+                        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                        .collect()
+                })
+                .unwrap_or_default();
+            names.sort();
+            let listing = names.join("\n");
+            let reply = format!("The project contains: {}.", names.join(", "));
+            (listing, reply)
+        } else {
+            (
+                "The user doesn't want to proceed with this tool use.".to_string(),
+                "Okay, I won't list the files.".to_string(),
+            )
+        };
+        let mut outputs = vec![self.record(&json!({
+            "role": "user",
+            "content": [{
+                "type": "tool_result",
+                "tool_use_id": tool_use_id,
+                "content": output,
+                "is_error": !allowed,
+            }],
+        }))];
+        outputs.extend(self.assistant_text(&reply));
+        outputs
+    }
+}
+
+/// Text of a user message's `content`, which is a string or a list of blocks.
+fn message_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter_map(|block| block.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_create_seeds_example_sessions() {
+        let temp = TempDir::new().unwrap();
+        let demo = Demo::create(temp.path(), Path::new("/opt/chef de vibe's/bin")).unwrap();
+
+        let wrapper = std::fs::read_to_string(&demo.backend).unwrap();
+        assert!(wrapper.contains(r"exec '/opt/chef de vibe'\''s/bin' demo-backend"));
+        let sessions = std::fs::read_dir(
+            demo.projects_dir
+                .join(session_import::project_dir_name(&demo.working_dir)),
+        )
+        .unwrap()
+        .count();
+        assert_eq!(sessions, 3);
+        assert!(demo.working_dir.join("src/main.rs").exists());
+    }
+
+    #[test]
+    fn test_backend_tool_approval() {
+        let temp = TempDir::new().unwrap();
+        let cwd = temp.path().join("work");
+        std::fs::create_dir_all(&cwd).unwrap();
+        std::fs::write(cwd.join("notes.txt"), "").unwrap();
+        let projects = temp.path().join("projects");
+        let mut backend = Backend::new("demo-1".to_string(), cwd.clone(), Some(&projects));
+
+        let reply =
+            backend.handle(&json!({"type": "user", "message": {"role": "user", "content": "hi"}}));
+        assert_eq!(reply[0]["type"], "assistant");
+        assert_eq!(reply[1]["type"], "result");
+
+        let request = backend.handle(&json!({
+            "type": "user",
+            "message": {"role": "user", "content": [{"type": "text", "text": "Which files are there?"}]}
+        }));
+        assert_eq!(request[1]["type"], "control_request");
+        assert_eq!(request[1]["request"]["tool_name"], "Bash");
+        let request_id = request[1]["request_id"].clone();
+
+        // Responses to other requests are ignored
+        let ignored = backend.handle(&json!({
+            "type": "control_response",
+            "response": {"subtype": "success", "request_id": "other", "response": {"behavior": "allow"}}
+        }));
+        assert!(ignored.is_empty());
+        let done = backend.handle(&json!({
+            "type": "control_response",
+            "response": {"subtype": "success", "request_id": request_id, "response": {"behavior": "allow"}}
+        }));
+        assert_eq!(done[0]["message"]["content"][0]["content"], "notes.txt");
+        assert_eq!(done[2]["result"], "The project contains: notes.txt.");
+
+        // The history carries over to the resumed session
+        let resumed = Backend::resume("demo-1", cwd, Some(&projects)).unwrap();
+        assert_ne!(resumed.session_id, "demo-1");
+        let transcript = std::fs::read_to_string(resumed.transcript.unwrap()).unwrap();
+        assert_eq!(transcript.lines().count(), 6);
+        assert!(transcript.contains(&resumed.session_id));
+    }
+}
@@ -1,16 +1,141 @@
 use crate::config::Config;
 use crate::error::{OrchestratorError, OrchestratorResult};
-use crate::models::{SessionFileLine, SessionInfo};
+use crate::index::{FileScan, FileSession, RefreshStats};
+use crate::models::{SessionFileLine, SessionFilter, SessionInfo, SessionSort};
 use crate::session_manager::SessionManager;
-use rayon::prelude::*;
-use std::collections::HashMap;
+use crate::transcript_export::{self, ExportFormat};
+use crate::transcript_hook::TranscriptHook;
+use memmap2::Mmap;
+use serde::de::{self, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
 use tracing::{error, instrument, warn};
 use walkdir::WalkDir;
 
+/// Subset of a journal line that listing cares about. Everything else in the line is skipped
+/// by serde without being materialized.
+#[derive(Debug, Default, Deserialize)]
+struct ScanLine {
+    #[serde(rename = "type")]
+    entry_type: Option<String>,
+    #[serde(rename = "sessionId")]
+    session_id: Option<String>,
+    cwd: Option<String>,
+    uuid: Option<String>,
+    #[serde(rename = "leafUuid")]
+    leaf_uuid: Option<String>,
+    summary: Option<String>,
+    timestamp: Option<String>,
+    message: Option<ScanMessage>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ScanMessage {
+    #[serde(default)]
+    content: TextContent,
+}
+
+/// `message.content` when it is a plain string. Structured content (tool results, images)
+/// is consumed without allocating.
+#[derive(Debug, Default)]
+struct TextContent(Option<String>);
+
+impl<'de> Deserialize<'de> for TextContent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TextContentVisitor;
+
+        impl<'de> Visitor<'de> for TextContentVisitor {
+            type Value = TextContent;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("any JSON value")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(TextContent(Some(v.to_string())))
+            }
+
+            fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+                Ok(TextContent(Some(v)))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(TextContent(None))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+                Ok(TextContent(None))
+            }
+
+            fn visit_bool<E: de::Error>(self, _: bool) -> Result<Self::Value, E> {
+                Ok(TextContent(None))
+            }
+
+            fn visit_i64<E: de::Error>(self, _: i64) -> Result<Self::Value, E> {
+                Ok(TextContent(None))
+            }
+
+            fn visit_u64<E: de::Error>(self, _: u64) -> Result<Self::Value, E> {
+                Ok(TextContent(None))
+            }
+
+            fn visit_f64<E: de::Error>(self, _: f64) -> Result<Self::Value, E> {
+                Ok(TextContent(None))
+            }
+
+            fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+                Ok(TextContent(None))
+            }
+        }
+
+        deserializer.deserialize_any(TextContentVisitor)
+    }
+}
+
+impl ScanLine {
+    /// Markers that any line relevant to listing must contain. Lines without them are
+    /// skipped without parsing.
+    const MARKERS: [&'static [u8]; 2] = [b"\"sessionId\"", b"\"summary\""];
+
+    fn from_bytes(raw: &[u8]) -> Option<Self> {
+        if !Self::MARKERS
+            .iter()
+            .any(|marker| raw.windows(marker.len()).any(|w| w == *marker))
+        {
+            return None;
+        }
+
+        serde_json::from_slice::<Self>(raw).ok().or_else(|| {
+            serde_json::from_slice::<serde_json::Value>(raw)
+                .ok()
+                .map(|v| Self::from_value(&v))
+        })
+    }
+
+    /// Full-parse fallback for lines whose fields do not have the expected shape.
+    fn from_value(value: &serde_json::Value) -> Self {
+        let string_field = |key: &str| value.get(key).and_then(|v| v.as_str()).map(String::from);
+        Self {
+            entry_type: string_field("type"),
+            session_id: string_field("sessionId"),
+            cwd: string_field("cwd"),
+            uuid: string_field("uuid"),
+            leaf_uuid: string_field("leafUuid"),
+            summary: string_field("summary"),
+            timestamp: string_field("timestamp"),
+            message: value.get("message").map(|m| ScanMessage {
+                content: TextContent(m.get("content").and_then(|c| c.as_str()).map(String::from)),
+            }),
+        }
+    }
+}
+
 pub struct SessionDiscovery<'a> {
     config: &'a Config,
     session_manager: &'a SessionManager,
@@ -25,14 +150,17 @@ impl<'a> SessionDiscovery<'a> {
         }
     }
 
-    /// Lists all available sessions, both active and inactive.
+    /// Lists the available sessions, both active and inactive, that pass `filter`.
     ///
     /// # Errors
     ///
     /// Returns an error if there's an I/O error scanning the disk for session files
     /// or if session files cannot be parsed.
     #[instrument(skip(self))]
-    pub async fn list_all_sessions(&self) -> OrchestratorResult<Vec<SessionInfo>> {
+    pub async fn list_all_sessions(
+        &self,
+        filter: &SessionFilter,
+    ) -> OrchestratorResult<Vec<SessionInfo>> {
         let mut sessions = Vec::new();
 
         // Get active sessions from session manager
@@ -45,12 +173,16 @@ impl<'a> SessionDiscovery<'a> {
         }
 
         // Scan disk for all sessions and prepare fallback summaries
-        let (disk_sessions, session_fallbacks) = self.scan_disk_for_sessions();
+        let (disk_sessions, session_fallbacks) = self.scan_disk_for_sessions()?;
 
         // Add all sessions found on disk (both with and without summaries)
+        let mut on_disk = HashSet::new();
         for mut session in disk_sessions {
+            on_disk.insert(session.session_id.clone());
             // Mark as active if it's in the active list
             session.active = active_session_ids.contains(&session.session_id);
+            session.owner = self.session_manager.session_owner(&session.session_id);
+            self.attach_metadata(&mut session);
 
             // If no summary, try to use fallback
             if session.summary.is_none() {
@@ -59,13 +191,15 @@ impl<'a> SessionDiscovery<'a> {
                 }
             }
 
-            sessions.push(session);
+            if filter.matches(&session) {
+                sessions.push(session);
+            }
         }
 
         // Add active sessions that weren't found with summaries
         for active_session in active_sessions {
             let session_id = active_session.get_id().await;
-            if !sessions.iter().any(|s| s.session_id == session_id) {
+            if !on_disk.contains(&session_id) {
                 // Try to get the first user message as fallback from our scan
                 let fallback_summary = session_fallbacks.get(&session_id).cloned();
 
@@ -77,20 +211,90 @@ impl<'a> SessionDiscovery<'a> {
                     );
                 }
 
-                sessions.push(SessionInfo {
+                let mut session = SessionInfo {
                     session_id: session_id.clone(),
                     working_directory: active_session.working_dir.clone(),
                     active: true,
                     summary: fallback_summary,
                     earliest_message_date: None,
                     latest_message_date: None,
-                });
+                    resumed_from: None,
+                    pinned: false,
+                    owner: active_session.owner().map(str::to_string),
+                    usage: None,
+                    tags: Vec::new(),
+                    metadata: BTreeMap::new(),
+                };
+                self.attach_metadata(&mut session);
+                if filter.matches(&session) {
+                    sessions.push(session);
+                }
             }
         }
 
+        for session in &mut sessions {
+            session.resumed_from = self.session_manager.resumed_from(&session.session_id);
+        }
+
         Ok(sessions)
     }
 
+    /// Hides sessions that were later resumed under a new ID, so each resumed chain is
+    /// listed once through its latest segment (which links back via `resumed_from`).
+    #[must_use]
+    pub fn collapse_resumed_chains(&self, sessions: Vec<SessionInfo>) -> Vec<SessionInfo> {
+        let mut superseded = HashSet::new();
+        for session in &sessions {
+            let mut current = session.session_id.clone();
+            while let Some(parent) = self.session_manager.resumed_from(&current) {
+                if !superseded.insert(parent.clone()) {
+                    break;
+                }
+                current = parent;
+            }
+        }
+
+        sessions
+            .into_iter()
+            .filter(|session| !superseded.contains(&session.session_id))
+            .collect()
+    }
+
+    /// Marks the sessions `pinned_sessions` names, following each back through the sessions
+    /// it was resumed from, since resuming gives a pinned conversation a new ID. Returns
+    /// the position in `pinned_sessions` of each pinned session, for ordering them.
+    pub fn mark_pinned(
+        &self,
+        sessions: &mut [SessionInfo],
+        pinned_sessions: &[String],
+    ) -> HashMap<String, usize> {
+        let mut ranks = HashMap::new();
+        if pinned_sessions.is_empty() {
+            return ranks;
+        }
+        for session in sessions {
+            let mut current = session.session_id.clone();
+            let mut visited = HashSet::new();
+            let rank = loop {
+                if let Some(rank) = pinned_sessions.iter().position(|id| *id == current) {
+                    break Some(rank);
+                }
+                if !visited.insert(current.clone()) {
+                    break None;
+                }
+                match self.session_manager.resumed_from(&current) {
+                    Some(parent) => current = parent,
+                    None => break None,
+                }
+            };
+            if let Some(rank) = rank {
+                session.pinned = true;
+                ranks.insert(session.session_id.clone(), rank);
+            }
+        }
+        ranks
+    }
+
     /// Gets detailed information and content for a specific session.
     ///
     /// # Errors
@@ -109,7 +313,7 @@ impl<'a> SessionDiscovery<'a> {
                 .ok()
                 .map(|(info, _)| info);
 
-            let session_info = SessionInfo {
+            let mut session_info = SessionInfo {
                 session_id: session.get_id().await,
                 working_directory: session.working_dir.clone(),
                 active: session.is_active().await,
@@ -122,7 +326,14 @@ impl<'a> SessionDiscovery<'a> {
                 latest_message_date: disk_session_info
                     .as_ref()
                     .and_then(|info| info.latest_message_date.clone()),
+                resumed_from: None,
+                pinned: false,
+                owner: session.owner().map(str::to_string),
+                usage: None,
+                tags: Vec::new(),
+                metadata: BTreeMap::new(),
             };
+            self.attach_metadata(&mut session_info);
 
             // Try to read content from disk
             let content = self.read_session_content_from_disk(session_id)?;
@@ -131,285 +342,239 @@ impl<'a> SessionDiscovery<'a> {
         }
 
         // Not active, search on disk
-        self.find_session_on_disk(session_id)
+        let (mut session_info, content) = self.find_session_on_disk(session_id)?;
+        session_info.owner = self.session_manager.session_owner(session_id);
+        self.attach_metadata(&mut session_info);
+        Ok((session_info, content))
     }
 
-    #[allow(clippy::too_many_lines)]
-    fn scan_disk_for_sessions(&self) -> (Vec<SessionInfo>, HashMap<String, String>) {
-        struct FileData {
-            summaries: Vec<(String, String)>, // leafUuid -> summary text
-            lines: Vec<serde_json::Value>,
+    /// Fills in the tags and metadata users attached to `session`.
+    fn attach_metadata(&self, session: &mut SessionInfo) {
+        let attached = self
+            .session_manager
+            .session_metadata()
+            .get(&session.session_id);
+        session.tags = attached.tags;
+        session.metadata = attached.metadata;
+    }
+
+    /// Journal holding the content of `session_id`, for reading it line by line instead
+    /// of all at once. `None` for a running session that has not written one yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session is neither running nor on disk.
+    pub fn session_journal(&self, session_id: &str) -> OrchestratorResult<Option<PathBuf>> {
+        if let Some(path) = self.indexed_journal(session_id) {
+            return Ok(Some(path));
         }
 
-        // Phase 1: Collect all .jsonl file paths
-        let jsonl_files: Vec<PathBuf> = WalkDir::new(&self.config.claude_projects_dir)
+        let filename = format!("{session_id}.jsonl");
+        let journal = WalkDir::new(&self.config.claude_projects_dir)
             .into_iter()
             .filter_map(std::result::Result::ok)
-            .filter(|entry| entry.path().extension().and_then(|s| s.to_str()) == Some("jsonl"))
-            .map(|entry| entry.path().to_path_buf())
-            .collect();
-
-        // Phase 2: Process files in parallel batches to extract data
-
-        let file_data: Vec<FileData> = jsonl_files
-            .par_iter()
-            .filter_map(|path| {
-                Self::read_jsonl_file(path).ok().map(|lines| {
-                    let mut summaries = Vec::new();
-
-                    // Extract summaries from this file
-                    for line in &lines {
-                        if let Some(entry_type) = line.get("type").and_then(|v| v.as_str()) {
-                            if entry_type == "summary" {
-                                if let (Some(summary_text), Some(leaf_uuid)) = (
-                                    line.get("summary").and_then(|v| v.as_str()),
-                                    line.get("leafUuid").and_then(|v| v.as_str()),
-                                ) {
-                                    summaries
-                                        .push((leaf_uuid.to_string(), summary_text.to_string()));
-                                }
-                            }
-                        }
-                    }
+            .find(|entry| entry.file_name().to_str() == Some(filename.as_str()))
+            .map(walkdir::DirEntry::into_path);
+        match journal {
+            Some(path) => Ok(Some(path)),
+            None if self.session_manager.get_session(session_id).is_some() => Ok(None),
+            None => Err(OrchestratorError::SessionNotFound(session_id.to_string())),
+        }
+    }
 
-                    FileData { summaries, lines }
-                })
-            })
-            .collect();
+    /// Entries of the journal of `session_id` after its first `after_line` lines, or
+    /// after the entry with `after_uuid`. The session index remembers where the lines of
+    /// the journal start, so only the requested lines are read, and of the rest only what
+    /// was appended since the last request.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session is not found, `after_uuid` names no entry of it,
+    /// or its journal cannot be read or has a line that is not JSON.
+    pub fn content_after(
+        &self,
+        session_id: &str,
+        after_line: Option<usize>,
+        after_uuid: Option<&str>,
+    ) -> OrchestratorResult<AppendedContent> {
+        let Some(path) = self.session_journal(session_id)? else {
+            return Ok(AppendedContent {
+                first_line: 0,
+                content: Vec::new(),
+                total_lines: 0,
+                reset: after_line.is_some_and(|line| line > 0) || after_uuid.is_some(),
+            });
+        };
+        let read_error = |e: std::io::Error| {
+            error!(file_path = %path.display(), error = %e, "Failed to read session journal");
+            OrchestratorError::FileParseError(format!(
+                "Failed to read file {}: {e}",
+                path.display()
+            ))
+        };
 
-        // Combine all summaries into a single map
-        let mut all_summaries: HashMap<String, String> = HashMap::new();
-        for data in &file_data {
-            for (uuid, summary) in &data.summaries {
-                all_summaries.insert(uuid.clone(), summary.clone());
+        let offsets = self.session_manager.session_index().offsets();
+        let after_line = match after_uuid {
+            Some(uuid) => {
+                // Catches up with the journal without reading any of its lines
+                offsets.lines_after(&path, usize::MAX).map_err(read_error)?;
+                offsets
+                    .line_of(&path, uuid)
+                    .map(|line| line + 1)
+                    .ok_or_else(|| {
+                        OrchestratorError::InvalidRequest(format!(
+                            "Session {session_id} has no entry {uuid}"
+                        ))
+                    })?
             }
-        }
+            None => after_line.unwrap_or(0),
+        };
+        let appended = offsets.lines_after(&path, after_line).map_err(read_error)?;
+        let content = appended
+            .lines
+            .iter()
+            .map(|line| serde_json::from_str(line))
+            .collect::<Result<Vec<serde_json::Value>, _>>()?;
+        Ok(AppendedContent {
+            first_line: after_line.min(appended.total_lines),
+            content,
+            total_lines: appended.total_lines,
+            reset: after_line > appended.total_lines,
+        })
+    }
 
-        // Phase 3: Process all lines in parallel to build session information
-        let summaries = Arc::new(all_summaries);
-
-        // Process each file's data in parallel
-        let processed_data: Vec<_> = file_data
-            .par_iter()
-            .map(|data| {
-                let mut local_sessions: HashMap<String, SessionInfo> = HashMap::new();
-                let mut local_first_messages: HashMap<String, (String, Option<String>)> =
-                    HashMap::new();
-
-                for line in &data.lines {
-                    // Check if this entry has a UUID that matches a leafUuid
-                    if let Some(uuid) = line.get("uuid").and_then(|v| v.as_str()) {
-                        if let Some(summary_text) = summaries.get(uuid) {
-                            // Found a match! Extract session information
-                            if let Some(session_id) = line.get("sessionId").and_then(|v| v.as_str())
-                            {
-                                // Update or create session entry with summary
-                                local_sessions
-                                    .entry(session_id.to_string())
-                                    .and_modify(|s| {
-                                        if s.summary.is_none() {
-                                            s.summary = Some(summary_text.clone());
-                                        }
-                                    })
-                                    .or_insert_with(|| {
-                                        let mut info = SessionInfo {
-                                            session_id: session_id.to_string(),
-                                            working_directory: PathBuf::new(),
-                                            active: false,
-                                            summary: Some(summary_text.clone()),
-                                            earliest_message_date: None,
-                                            latest_message_date: None,
-                                        };
-
-                                        // Try to get working directory from cwd field
-                                        if let Some(cwd) = line.get("cwd").and_then(|v| v.as_str())
-                                        {
-                                            info.working_directory = PathBuf::from(cwd);
-                                        }
-
-                                        info
-                                    });
-                            }
-                        }
-                    }
+    /// Transcript of `session_id` rendered as a document in `format`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session is not found or its transcript cannot be read.
+    pub async fn export_session(
+        &self,
+        session_id: &str,
+        format: ExportFormat,
+        hook: Option<&dyn TranscriptHook>,
+    ) -> OrchestratorResult<String> {
+        let (session_info, content) = self.get_session_content(session_id).await?;
+        let content = match hook {
+            Some(hook) => hook.transform(content).await?,
+            None => content,
+        };
+        Ok(transcript_export::render(&session_info, &content, format))
+    }
 
-                    // Collect first user message for all sessions (as fallback for active sessions without summaries)
-                    if let Some(entry_type) = line.get("type").and_then(|v| v.as_str()) {
-                        if entry_type == "user" {
-                            if let Some(session_id) = line.get("sessionId").and_then(|v| v.as_str())
-                            {
-                                // Always collect first user messages for all sessions
-                                let timestamp = line
-                                    .get("timestamp")
-                                    .and_then(|v| v.as_str())
-                                    .map(String::from);
-
-                                // Extract user message content
-                                let message_content = line
-                                    .get("message")
-                                    .and_then(|m| m.get("content"))
-                                    .and_then(|c| c.as_str())
-                                    .map_or_else(|| "No content".to_string(), String::from);
-
-                                local_first_messages
-                                    .entry(session_id.to_string())
-                                    .and_modify(|(existing_msg, existing_ts)| {
-                                        // Only update if we have timestamps to compare
-                                        if let (Some(new_ts), Some(old_ts)) =
-                                            (&timestamp, existing_ts.as_ref())
-                                        {
-                                            // Replace if new timestamp is earlier
-                                            if new_ts < old_ts {
-                                                existing_msg.clone_from(&message_content);
-                                                existing_ts.clone_from(&timestamp);
-                                            }
-                                        } else if existing_ts.is_none() && timestamp.is_some() {
-                                            // Replace if we didn't have a timestamp but now we do
-                                            existing_msg.clone_from(&message_content);
-                                            existing_ts.clone_from(&timestamp);
-                                        }
-                                        // If both are None or existing has timestamp but new doesn't,
-                                        // keep the existing (first encountered) message
-                                    })
-                                    .or_insert((message_content, timestamp));
-                            }
-                        }
-                    }
+    /// Sessions on disk that have a working directory, and the first user message of every
+    /// session, for those without a summary. Served from the session index after it
+    /// rescanned the journals that changed since the last sync.
+    fn scan_disk_for_sessions(
+        &self,
+    ) -> OrchestratorResult<(Vec<SessionInfo>, HashMap<String, String>)> {
+        let index = self.session_manager.session_index();
+        index.sync(&self.config.claude_projects_dir, Self::index_jsonl_file)?;
+        index.sessions()
+    }
 
-                    // Also collect sessions from sessionId and cwd fields
-                    if let (Some(session_id), Some(cwd)) = (
-                        line.get("sessionId").and_then(|v| v.as_str()),
-                        line.get("cwd").and_then(|v| v.as_str()),
-                    ) {
-                        local_sessions
-                            .entry(session_id.to_string())
-                            .and_modify(|s| {
-                                if s.working_directory == PathBuf::new() {
-                                    s.working_directory = PathBuf::from(cwd);
-                                }
-                            })
-                            .or_insert_with(|| SessionInfo {
-                                session_id: session_id.to_string(),
-                                working_directory: PathBuf::from(cwd),
-                                active: false,
-                                summary: None,
-                                earliest_message_date: None,
-                                latest_message_date: None,
-                            });
-                    }
+    /// Compares every journal under the projects directory with the session index, not
+    /// only those a watcher reported, and reads the ones that changed again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index database cannot be read or written.
+    pub fn rescan(&self) -> OrchestratorResult<RefreshStats> {
+        let index = self.session_manager.session_index();
+        index.mark_all_changed();
+        index.sync(&self.config.claude_projects_dir, Self::index_jsonl_file)
+    }
 
-                    // Collect timestamps for sessions
-                    if let Some(session_id) = line.get("sessionId").and_then(|v| v.as_str()) {
-                        if let Some(timestamp) = line.get("timestamp").and_then(|v| v.as_str()) {
-                            if let Some(session) = local_sessions.get_mut(session_id) {
-                                let timestamp_str = timestamp.to_string();
-
-                                if session.earliest_message_date.is_none()
-                                    || Some(&timestamp_str) < session.earliest_message_date.as_ref()
-                                {
-                                    session.earliest_message_date = Some(timestamp_str.clone());
-                                }
-
-                                if session.latest_message_date.is_none()
-                                    || Some(&timestamp_str) > session.latest_message_date.as_ref()
-                                {
-                                    session.latest_message_date = Some(timestamp_str);
-                                }
-                            }
-                        }
-                    }
+    /// Reduces a journal to what the session index keeps of it.
+    #[must_use]
+    pub fn index_jsonl_file(path: &Path) -> Option<FileScan> {
+        let lines = Self::scan_jsonl_file(path).ok()?;
+        let mut scan = FileScan::default();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+
+        for line in lines {
+            if line.entry_type.as_deref() == Some("summary") {
+                if let (Some(summary), Some(leaf_uuid)) = (&line.summary, &line.leaf_uuid) {
+                    scan.summaries.push((leaf_uuid.clone(), summary.clone()));
                 }
-
-                (local_sessions, local_first_messages)
-            })
-            .collect();
-
-        // Merge all results
-        let mut sessions: HashMap<String, SessionInfo> = HashMap::new();
-        let mut first_user_messages: HashMap<String, (String, Option<String>)> = HashMap::new();
-
-        for (local_sessions, local_messages) in processed_data {
-            // Merge sessions
-            for (session_id, session_info) in local_sessions {
-                sessions
-                    .entry(session_id)
-                    .and_modify(|existing| {
-                        // Merge data, preferring non-empty values
-                        if existing.summary.is_none() && session_info.summary.is_some() {
-                            existing.summary.clone_from(&session_info.summary);
-                        }
-                        if existing.working_directory == PathBuf::new()
-                            && session_info.working_directory != PathBuf::new()
-                        {
-                            existing
-                                .working_directory
-                                .clone_from(&session_info.working_directory);
-                        }
-                        // Update timestamps to get earliest/latest
-                        if let Some(ref new_earliest) = session_info.earliest_message_date {
-                            if existing.earliest_message_date.is_none()
-                                || existing.earliest_message_date.as_ref() > Some(new_earliest)
-                            {
-                                existing.earliest_message_date = Some(new_earliest.clone());
-                            }
-                        }
-                        if let Some(ref new_latest) = session_info.latest_message_date {
-                            if existing.latest_message_date.is_none()
-                                || existing.latest_message_date.as_ref() < Some(new_latest)
-                            {
-                                existing.latest_message_date = Some(new_latest.clone());
-                            }
-                        }
-                    })
-                    .or_insert(session_info);
             }
 
-            // Merge first user messages for active sessions
-            for (session_id, (msg, timestamp)) in local_messages {
-                first_user_messages
-                    .entry(session_id)
-                    .and_modify(|(existing_msg, existing_ts)| {
-                        // Keep the earliest message based on timestamps
-                        if let (Some(new_ts), Some(old_ts)) = (&timestamp, existing_ts.as_ref()) {
-                            if new_ts < old_ts {
-                                existing_msg.clone_from(&msg);
-                                existing_ts.clone_from(&timestamp);
-                            }
-                        } else if existing_ts.is_none() && timestamp.is_some() {
-                            // Replace if we didn't have a timestamp but now we do
-                            existing_msg.clone_from(&msg);
-                            existing_ts.clone_from(&timestamp);
-                        }
-                        // If both are None or existing has timestamp but new doesn't,
-                        // keep the existing (first encountered) message
-                    })
-                    .or_insert((msg, timestamp));
+            let Some(session_id) = line.session_id else {
+                continue;
+            };
+            if let Some(uuid) = line.uuid {
+                scan.messages.push((uuid, session_id.clone()));
             }
-        }
+            let position = *positions.entry(session_id.clone()).or_insert_with(|| {
+                scan.sessions.push(FileSession {
+                    session_id,
+                    ..FileSession::default()
+                });
+                scan.sessions.len() - 1
+            });
+            let session = &mut scan.sessions[position];
 
-        // Prepare fallback summaries for all sessions
-        let mut session_fallbacks: HashMap<String, String> = HashMap::new();
-        for (session_id, (first_msg, _)) in first_user_messages {
-            session_fallbacks.insert(session_id.clone(), first_msg);
+            if session.cwd.is_none() {
+                session.cwd = line.cwd;
+            }
+            if let Some(timestamp) = &line.timestamp {
+                if session
+                    .earliest
+                    .as_ref()
+                    .is_none_or(|earliest| timestamp < earliest)
+                {
+                    session.earliest = Some(timestamp.clone());
+                }
+                if session
+                    .latest
+                    .as_ref()
+                    .is_none_or(|latest| timestamp > latest)
+                {
+                    session.latest = Some(timestamp.clone());
+                }
+            }
+            if line.entry_type.as_deref() == Some("user") {
+                let message = line
+                    .message
+                    .and_then(|m| m.content.0)
+                    .unwrap_or_else(|| "No content".to_string());
+                session.offer_first_message(message, line.timestamp);
+            }
         }
 
-        // Return all sessions found on disk (with valid working directories)
-        let mut sessions_to_return: Vec<SessionInfo> = Vec::new();
+        Some(scan)
+    }
 
-        // Add all sessions with valid working directories
-        for (_session_id, session_info) in sessions {
-            if session_info.working_directory != PathBuf::new() {
-                sessions_to_return.push(session_info);
-            }
+    /// Journal of `session_id` as the session index knows it, while a watcher keeps the
+    /// index current. Callers walk the projects directory when this finds nothing, which
+    /// covers journals the watcher has not reported yet.
+    fn indexed_journal(&self, session_id: &str) -> Option<PathBuf> {
+        let index = self.session_manager.session_index();
+        if !index.is_watched() {
+            return None;
         }
-
-        (sessions_to_return, session_fallbacks)
+        index
+            .sync(&self.config.claude_projects_dir, Self::index_jsonl_file)
+            .and_then(|_| index.journal_path(session_id))
+            .unwrap_or_else(|e| {
+                warn!(session_id = %session_id, error = %e, "Failed to look up session in index");
+                None
+            })
     }
 
     fn find_session_on_disk(
         &self,
         session_id: &str,
     ) -> OrchestratorResult<(SessionInfo, Vec<serde_json::Value>)> {
+        if let Some(path) = self.indexed_journal(session_id) {
+            if let Ok(Some(session_info)) = Self::parse_session_file(&path) {
+                if session_info.session_id == session_id {
+                    let content = Self::read_session_content(&path)?;
+                    return Ok((session_info, content));
+                }
+            }
+        }
+
         let filename = format!("{session_id}.jsonl");
 
         for entry in WalkDir::new(&self.config.claude_projects_dir)
@@ -443,6 +608,10 @@ impl<'a> SessionDiscovery<'a> {
         &self,
         session_id: &str,
     ) -> OrchestratorResult<Vec<serde_json::Value>> {
+        if let Some(path) = self.indexed_journal(session_id) {
+            return Self::read_session_content(&path);
+        }
+
         let filename = format!("{session_id}.jsonl");
 
         for entry in WalkDir::new(&self.config.claude_projects_dir)
@@ -459,7 +628,12 @@ impl<'a> SessionDiscovery<'a> {
         Ok(Vec::new())
     }
 
-    fn read_jsonl_file(path: &Path) -> OrchestratorResult<Vec<serde_json::Value>> {
+    /// Memory-maps a JSONL file and extracts the fields needed for listing.
+    ///
+    /// Only lines containing a `"sessionId"` or `"summary"` marker are deserialized, and
+    /// those go through [`ScanLine`] rather than a full `serde_json::Value`. Lines the lean
+    /// parser rejects fall back to a full parse so odd-but-valid entries are not lost.
+    fn scan_jsonl_file(path: &Path) -> OrchestratorResult<Vec<ScanLine>> {
         let file = File::open(path).map_err(|e| {
             error!(
                 file_path = %path.display(),
@@ -472,31 +646,29 @@ impl<'a> SessionDiscovery<'a> {
             ))
         })?;
 
-        let reader = BufReader::new(file);
-        let mut lines = Vec::new();
-
-        for (line_number, line) in reader.lines().enumerate() {
-            let line = line.map_err(|e| {
-                error!(
-                    file_path = %path.display(),
-                    line_number = line_number + 1,
-                    error = %e,
-                    "Failed to read line from file"
-                );
-                OrchestratorError::FileParseError(format!(
-                    "Failed to read line {} from {}: {e}",
-                    line_number + 1,
-                    path.display()
-                ))
-            })?;
-
-            // Try to parse as JSON, skip invalid lines
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&line) {
-                lines.push(json);
-            }
+        let file_len = file.metadata().map(|m| m.len()).unwrap_or_default();
+        if file_len == 0 {
+            return Ok(Vec::new());
         }
 
-        Ok(lines)
+        // SAFETY: the map is read-only and dropped before returning. Claude may append to
+        // the journal concurrently, which only extends the file past the mapped length.
+        let mmap = unsafe { Mmap::map(&file) }.map_err(|e| {
+            error!(
+                file_path = %path.display(),
+                error = %e,
+                "Failed to memory-map file"
+            );
+            OrchestratorError::FileParseError(format!(
+                "Failed to memory-map file {}: {e}",
+                path.display()
+            ))
+        })?;
+
+        Ok(mmap
+            .split(|&b| b == b'\n')
+            .filter_map(ScanLine::from_bytes)
+            .collect())
     }
 
     #[allow(clippy::too_many_lines)]
@@ -621,6 +793,12 @@ impl<'a> SessionDiscovery<'a> {
                     summary,
                     earliest_message_date: earliest_timestamp,
                     latest_message_date: latest_timestamp,
+                    resumed_from: None,
+                    pinned: false,
+                    owner: None,
+                    usage: None,
+                    tags: Vec::new(),
+                    metadata: BTreeMap::new(),
                 }))
             }
             (None, _) => Err(OrchestratorError::FileParseError(format!(
@@ -688,6 +866,48 @@ impl<'a> SessionDiscovery<'a> {
     }
 }
 
+/// Lines appended to a journal, read by [`SessionDiscovery::content_after`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppendedContent {
+    /// Journal line of the first entry of `content`
+    pub first_line: usize,
+    pub content: Vec<serde_json::Value>,
+    /// Complete lines in the journal
+    pub total_lines: usize,
+    /// The requested position was past the end of the journal
+    pub reset: bool,
+}
+
+/// Orders sessions for listing; `pin_ranks` is what [`SessionDiscovery::mark_pinned`]
+/// returned. Sessions without the date an order uses come last.
+pub fn sort_sessions<S: std::hash::BuildHasher>(
+    sessions: &mut [SessionInfo],
+    sort: SessionSort,
+    pin_ranks: &HashMap<String, usize, S>,
+) {
+    let recent = |a: &SessionInfo, b: &SessionInfo| {
+        b.latest_message_date
+            .cmp(&a.latest_message_date)
+            .then_with(|| a.session_id.cmp(&b.session_id))
+    };
+    match sort {
+        SessionSort::PinnedFirst => sessions.sort_by(|a, b| {
+            match (pin_ranks.get(&a.session_id), pin_ranks.get(&b.session_id)) {
+                (Some(a_rank), Some(b_rank)) => a_rank.cmp(b_rank).then_with(|| recent(a, b)),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => recent(a, b),
+            }
+        }),
+        SessionSort::Recent => sessions.sort_by(recent),
+        SessionSort::Created => sessions.sort_by(|a, b| {
+            b.earliest_message_date
+                .cmp(&a.earliest_message_date)
+                .then_with(|| a.session_id.cmp(&b.session_id))
+        }),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -740,13 +960,63 @@ mod tests {
             http_listen_address: "127.0.0.1:8080".to_string(),
             claude_projects_dir: projects_dir,
             shutdown_timeout: std::time::Duration::from_secs(30),
+            terminate_timeout: std::time::Duration::from_secs(30),
+            shutdown_policies: crate::config::ShutdownPolicies::default(),
+            stdout_mode: crate::config::StdoutMode::Strict,
+            session_state_dir: None,
+            approval_sync_dir: None,
+            mdns_instance_name: None,
+            transcript_hooks: std::collections::HashMap::new(),
+            security_headers: crate::config::SecurityHeaders::default(),
+            claude_sdk_socket: None,
+            disconnect_queue_policy: crate::config::DisconnectQueuePolicy::default(),
+            notification_targets: std::collections::HashMap::new(),
+            dead_session_grace_period: std::time::Duration::from_secs(60),
+            telemetry_endpoint: None,
+            working_dir_roots: Vec::new(),
+            identity_header: None,
+            api_keys: crate::config::ApiKeys::default(),
+            control_routes: crate::config::ControlRoutes::default(),
+            session_limits: crate::config::SessionLimits::default(),
+            session_capacity: None,
+            partial_messages: false,
+            process_monitoring: crate::config::ProcessMonitoring::default(),
+            resource_limits: crate::config::ResourceLimits::default(),
+            restart_policy: None,
+            cli_flag_allowlist: crate::config::CliFlagAllowlist::default(),
+            session_response_max_bytes: None,
+            container: None,
+            session_backend: None,
+            risk_approvers: crate::approval_risk::RiskApprovers::default(),
+            risk_patterns: crate::approval_risk::RiskPatterns::default(),
+            working_dir_sandbox: crate::working_dir_sandbox::SandboxMode::default(),
+            frontend_dir: None,
+            event_sinks: Vec::new(),
+            body_limits: crate::config::BodyLimits::default(),
+            record_client_inputs: false,
+            web_push: None,
+            tls: None,
+            write_pacing: None,
+            approval_priority: None,
+            approval_webhook: None,
+            slack: None,
+            push: None,
+            scratch: None,
+            wire_log: None,
+            slo: crate::config::SloSettings::default(),
+            heartbeat: crate::config::Heartbeat::default(),
+            swagger_ui: false,
+            cors_allowed_origins: Vec::new(),
         };
 
         let manager = SessionManager::new(config.clone());
         let discovery = SessionDiscovery::new(&config, &manager);
 
         // Sessions without summaries should now be returned with fallback summaries
-        let sessions = discovery.list_all_sessions().await.unwrap();
+        let sessions = discovery
+            .list_all_sessions(&SessionFilter::default())
+            .await
+            .unwrap();
         assert_eq!(
             sessions.len(),
             2,
@@ -787,6 +1057,53 @@ mod tests {
             http_listen_address: "127.0.0.1:8080".to_string(),
             claude_projects_dir: projects_dir,
             shutdown_timeout: std::time::Duration::from_secs(30),
+            terminate_timeout: std::time::Duration::from_secs(30),
+            shutdown_policies: crate::config::ShutdownPolicies::default(),
+            stdout_mode: crate::config::StdoutMode::Strict,
+            session_state_dir: None,
+            approval_sync_dir: None,
+            mdns_instance_name: None,
+            transcript_hooks: std::collections::HashMap::new(),
+            security_headers: crate::config::SecurityHeaders::default(),
+            claude_sdk_socket: None,
+            disconnect_queue_policy: crate::config::DisconnectQueuePolicy::default(),
+            notification_targets: std::collections::HashMap::new(),
+            dead_session_grace_period: std::time::Duration::from_secs(60),
+            telemetry_endpoint: None,
+            working_dir_roots: Vec::new(),
+            identity_header: None,
+            api_keys: crate::config::ApiKeys::default(),
+            control_routes: crate::config::ControlRoutes::default(),
+            session_limits: crate::config::SessionLimits::default(),
+            session_capacity: None,
+            partial_messages: false,
+            process_monitoring: crate::config::ProcessMonitoring::default(),
+            resource_limits: crate::config::ResourceLimits::default(),
+            restart_policy: None,
+            cli_flag_allowlist: crate::config::CliFlagAllowlist::default(),
+            session_response_max_bytes: None,
+            container: None,
+            session_backend: None,
+            risk_approvers: crate::approval_risk::RiskApprovers::default(),
+            risk_patterns: crate::approval_risk::RiskPatterns::default(),
+            working_dir_sandbox: crate::working_dir_sandbox::SandboxMode::default(),
+            frontend_dir: None,
+            event_sinks: Vec::new(),
+            body_limits: crate::config::BodyLimits::default(),
+            record_client_inputs: false,
+            web_push: None,
+            tls: None,
+            write_pacing: None,
+            approval_priority: None,
+            approval_webhook: None,
+            slack: None,
+            push: None,
+            scratch: None,
+            wire_log: None,
+            slo: crate::config::SloSettings::default(),
+            heartbeat: crate::config::Heartbeat::default(),
+            swagger_ui: false,
+            cors_allowed_origins: Vec::new(),
         };
 
         let manager = SessionManager::new(config.clone());
@@ -850,13 +1167,60 @@ mod tests {
             http_listen_address: "127.0.0.1:8080".to_string(),
             claude_projects_dir: projects_dir,
             shutdown_timeout: std::time::Duration::from_secs(30),
+            terminate_timeout: std::time::Duration::from_secs(30),
+            shutdown_policies: crate::config::ShutdownPolicies::default(),
+            stdout_mode: crate::config::StdoutMode::Strict,
+            session_state_dir: None,
+            approval_sync_dir: None,
+            mdns_instance_name: None,
+            transcript_hooks: std::collections::HashMap::new(),
+            security_headers: crate::config::SecurityHeaders::default(),
+            claude_sdk_socket: None,
+            disconnect_queue_policy: crate::config::DisconnectQueuePolicy::default(),
+            notification_targets: std::collections::HashMap::new(),
+            dead_session_grace_period: std::time::Duration::from_secs(60),
+            telemetry_endpoint: None,
+            working_dir_roots: Vec::new(),
+            identity_header: None,
+            api_keys: crate::config::ApiKeys::default(),
+            control_routes: crate::config::ControlRoutes::default(),
+            session_limits: crate::config::SessionLimits::default(),
+            session_capacity: None,
+            partial_messages: false,
+            process_monitoring: crate::config::ProcessMonitoring::default(),
+            resource_limits: crate::config::ResourceLimits::default(),
+            restart_policy: None,
+            cli_flag_allowlist: crate::config::CliFlagAllowlist::default(),
+            session_response_max_bytes: None,
+            container: None,
+            session_backend: None,
+            risk_approvers: crate::approval_risk::RiskApprovers::default(),
+            risk_patterns: crate::approval_risk::RiskPatterns::default(),
+            working_dir_sandbox: crate::working_dir_sandbox::SandboxMode::default(),
+            frontend_dir: None,
+            event_sinks: Vec::new(),
+            body_limits: crate::config::BodyLimits::default(),
+            record_client_inputs: false,
+            web_push: None,
+            tls: None,
+            write_pacing: None,
+            approval_priority: None,
+            approval_webhook: None,
+            slack: None,
+            push: None,
+            scratch: None,
+            wire_log: None,
+            slo: crate::config::SloSettings::default(),
+            heartbeat: crate::config::Heartbeat::default(),
+            swagger_ui: false,
+            cors_allowed_origins: Vec::new(),
         };
 
         let manager = SessionManager::new(config.clone());
         let discovery = SessionDiscovery::new(&config, &manager);
 
         // Should find the session because it has a summary
-        let result = discovery.list_all_sessions().await;
+        let result = discovery.list_all_sessions(&SessionFilter::default()).await;
         assert!(result.is_ok());
         let sessions = result.unwrap();
 
@@ -889,13 +1253,63 @@ mod tests {
             http_listen_address: "127.0.0.1:8080".to_string(),
             claude_projects_dir: projects_dir,
             shutdown_timeout: std::time::Duration::from_secs(30),
+            terminate_timeout: std::time::Duration::from_secs(30),
+            shutdown_policies: crate::config::ShutdownPolicies::default(),
+            stdout_mode: crate::config::StdoutMode::Strict,
+            session_state_dir: None,
+            approval_sync_dir: None,
+            mdns_instance_name: None,
+            transcript_hooks: std::collections::HashMap::new(),
+            security_headers: crate::config::SecurityHeaders::default(),
+            claude_sdk_socket: None,
+            disconnect_queue_policy: crate::config::DisconnectQueuePolicy::default(),
+            notification_targets: std::collections::HashMap::new(),
+            dead_session_grace_period: std::time::Duration::from_secs(60),
+            telemetry_endpoint: None,
+            working_dir_roots: Vec::new(),
+            identity_header: None,
+            api_keys: crate::config::ApiKeys::default(),
+            control_routes: crate::config::ControlRoutes::default(),
+            session_limits: crate::config::SessionLimits::default(),
+            session_capacity: None,
+            partial_messages: false,
+            process_monitoring: crate::config::ProcessMonitoring::default(),
+            resource_limits: crate::config::ResourceLimits::default(),
+            restart_policy: None,
+            cli_flag_allowlist: crate::config::CliFlagAllowlist::default(),
+            session_response_max_bytes: None,
+            container: None,
+            session_backend: None,
+            risk_approvers: crate::approval_risk::RiskApprovers::default(),
+            risk_patterns: crate::approval_risk::RiskPatterns::default(),
+            working_dir_sandbox: crate::working_dir_sandbox::SandboxMode::default(),
+            frontend_dir: None,
+            event_sinks: Vec::new(),
+            body_limits: crate::config::BodyLimits::default(),
+            record_client_inputs: false,
+            web_push: None,
+            tls: None,
+            write_pacing: None,
+            approval_priority: None,
+            approval_webhook: None,
+            slack: None,
+            push: None,
+            scratch: None,
+            wire_log: None,
+            slo: crate::config::SloSettings::default(),
+            heartbeat: crate::config::Heartbeat::default(),
+            swagger_ui: false,
+            cors_allowed_origins: Vec::new(),
         };
 
         let manager = SessionManager::new(config.clone());
         let discovery = SessionDiscovery::new(&config, &manager);
 
         // Sessions without summaries should now be listed with fallback summaries
-        let sessions = discovery.list_all_sessions().await.unwrap();
+        let sessions = discovery
+            .list_all_sessions(&SessionFilter::default())
+            .await
+            .unwrap();
         assert_eq!(
             sessions.len(),
             1,
@@ -909,7 +1323,7 @@ mod tests {
         );
 
         // Test scan_disk_for_sessions directly
-        let (sessions_found, fallbacks) = discovery.scan_disk_for_sessions();
+        let (sessions_found, fallbacks) = discovery.scan_disk_for_sessions().unwrap();
 
         // Should find the session
         assert_eq!(
@@ -945,16 +1359,167 @@ mod tests {
             http_listen_address: "127.0.0.1:8080".to_string(),
             claude_projects_dir: projects_dir,
             shutdown_timeout: std::time::Duration::from_secs(30),
+            terminate_timeout: std::time::Duration::from_secs(30),
+            shutdown_policies: crate::config::ShutdownPolicies::default(),
+            stdout_mode: crate::config::StdoutMode::Strict,
+            session_state_dir: None,
+            approval_sync_dir: None,
+            mdns_instance_name: None,
+            transcript_hooks: std::collections::HashMap::new(),
+            security_headers: crate::config::SecurityHeaders::default(),
+            claude_sdk_socket: None,
+            disconnect_queue_policy: crate::config::DisconnectQueuePolicy::default(),
+            notification_targets: std::collections::HashMap::new(),
+            dead_session_grace_period: std::time::Duration::from_secs(60),
+            telemetry_endpoint: None,
+            working_dir_roots: Vec::new(),
+            identity_header: None,
+            api_keys: crate::config::ApiKeys::default(),
+            control_routes: crate::config::ControlRoutes::default(),
+            session_limits: crate::config::SessionLimits::default(),
+            session_capacity: None,
+            partial_messages: false,
+            process_monitoring: crate::config::ProcessMonitoring::default(),
+            resource_limits: crate::config::ResourceLimits::default(),
+            restart_policy: None,
+            cli_flag_allowlist: crate::config::CliFlagAllowlist::default(),
+            session_response_max_bytes: None,
+            container: None,
+            session_backend: None,
+            risk_approvers: crate::approval_risk::RiskApprovers::default(),
+            risk_patterns: crate::approval_risk::RiskPatterns::default(),
+            working_dir_sandbox: crate::working_dir_sandbox::SandboxMode::default(),
+            frontend_dir: None,
+            event_sinks: Vec::new(),
+            body_limits: crate::config::BodyLimits::default(),
+            record_client_inputs: false,
+            web_push: None,
+            tls: None,
+            write_pacing: None,
+            approval_priority: None,
+            approval_webhook: None,
+            slack: None,
+            push: None,
+            scratch: None,
+            wire_log: None,
+            slo: crate::config::SloSettings::default(),
+            heartbeat: crate::config::Heartbeat::default(),
+            swagger_ui: false,
+            cors_allowed_origins: Vec::new(),
         };
 
         let manager = SessionManager::new(config.clone());
         let discovery = SessionDiscovery::new(&config, &manager);
 
         // Files with missing required fields should now be ignored, not cause errors
-        let result = discovery.list_all_sessions().await;
+        let result = discovery.list_all_sessions(&SessionFilter::default()).await;
         assert!(result.is_ok());
         let sessions = result.unwrap();
         // The malformed file should be ignored, so no sessions should be found
         assert!(sessions.is_empty());
     }
+
+    #[test]
+    fn test_scan_jsonl_file_skips_lines_without_markers() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("session.jsonl");
+        let content = r#"{"type": "noise", "payload": [1, 2, 3]}
+{"sessionId": "s1", "cwd": "/work", "type": "user", "message": {"role": "user", "content": "Hi"}, "timestamp": "2025-01-01T00:00:00Z"}
+not json at all
+{"type": "summary", "summary": "A summary", "leafUuid": "leaf-1"}
+"#;
+        fs::write(&path, content).unwrap();
+
+        let lines = SessionDiscovery::scan_jsonl_file(&path).unwrap();
+        assert_eq!(lines.len(), 2);
+
+        assert_eq!(lines[0].session_id.as_deref(), Some("s1"));
+        assert_eq!(lines[0].cwd.as_deref(), Some("/work"));
+        assert_eq!(
+            lines[0].message.as_ref().unwrap().content.0.as_deref(),
+            Some("Hi")
+        );
+
+        assert_eq!(lines[1].entry_type.as_deref(), Some("summary"));
+        assert_eq!(lines[1].summary.as_deref(), Some("A summary"));
+        assert_eq!(lines[1].leaf_uuid.as_deref(), Some("leaf-1"));
+    }
+
+    #[test]
+    fn test_scan_jsonl_file_structured_content_and_fallback() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("session.jsonl");
+        // The second line has a non-string cwd, which the lean parser rejects; the full
+        // parse fallback must still recover the other fields.
+        let content = r#"{"sessionId": "s1", "type": "user", "message": {"content": [{"type": "tool_result", "content": "x"}]}}
+{"sessionId": "s1", "cwd": 42, "type": "user", "message": {"content": "Escaped \"quote\""}}"#;
+        fs::write(&path, content).unwrap();
+
+        let lines = SessionDiscovery::scan_jsonl_file(&path).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].message.as_ref().unwrap().content.0, None);
+
+        assert_eq!(lines[1].session_id.as_deref(), Some("s1"));
+        assert_eq!(lines[1].cwd, None);
+        assert_eq!(
+            lines[1].message.as_ref().unwrap().content.0.as_deref(),
+            Some("Escaped \"quote\"")
+        );
+    }
+
+    #[test]
+    fn test_scan_jsonl_file_empty_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("empty.jsonl");
+        fs::write(&path, "").unwrap();
+
+        assert!(SessionDiscovery::scan_jsonl_file(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sort_sessions() {
+        let session = |id: &str, earliest: Option<&str>, latest: Option<&str>| SessionInfo {
+            session_id: id.to_string(),
+            working_directory: PathBuf::from("/tmp"),
+            active: false,
+            summary: None,
+            earliest_message_date: earliest.map(str::to_string),
+            latest_message_date: latest.map(str::to_string),
+            resumed_from: None,
+            pinned: false,
+            owner: None,
+            usage: None,
+            tags: Vec::new(),
+            metadata: BTreeMap::new(),
+        };
+        let mut sessions = vec![
+            session(
+                "old",
+                Some("2025-01-01T00:00:00Z"),
+                Some("2025-03-01T00:00:00Z"),
+            ),
+            session("undated", None, None),
+            session(
+                "new",
+                Some("2025-02-01T00:00:00Z"),
+                Some("2025-02-02T00:00:00Z"),
+            ),
+        ];
+        let ids = |sessions: &[SessionInfo]| {
+            sessions
+                .iter()
+                .map(|s| s.session_id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let no_pins = HashMap::new();
+        sort_sessions(&mut sessions, SessionSort::Recent, &no_pins);
+        assert_eq!(ids(&sessions), ["old", "new", "undated"]);
+        sort_sessions(&mut sessions, SessionSort::Created, &no_pins);
+        assert_eq!(ids(&sessions), ["new", "old", "undated"]);
+
+        let pin_ranks = HashMap::from([("undated".to_string(), 1), ("new".to_string(), 0)]);
+        sort_sessions(&mut sessions, SessionSort::PinnedFirst, &pin_ranks);
+        assert_eq!(ids(&sessions), ["new", "undated", "old"]);
+    }
 }
@@ -1,10 +1,11 @@
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use utoipa::ToSchema;
 
 #[derive(Error, Debug)]
 pub enum OrchestratorError {
@@ -36,12 +37,101 @@ pub enum OrchestratorError {
 
     #[error("Process communication error: {0}")]
     ProcessCommunicationError(String),
+
+    #[error("Transcript hook failed: {0}")]
+    TranscriptHookFailed(String),
+
+    #[error("Upload not found: {0}")]
+    UploadNotFound(String),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
+    #[error("Media not found: {0}")]
+    MediaNotFound(String),
+
+    #[error("Project not found: {0}")]
+    ProjectNotFound(String),
+
+    #[error("Scratch file not found: {0}")]
+    ScratchFileNotFound(String),
+
+    #[error("Approval not found: {0}")]
+    ApprovalNotFound(String),
+
+    #[error("Approval already decided: {0}")]
+    ApprovalAlreadyDecided(String),
+
+    #[error("No wire log for session: {0}")]
+    WireLogNotFound(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Too many sessions: {0}")]
+    TooManySessions(String),
+
+    #[error("Shutting down: {0}")]
+    ShuttingDown(String),
+}
+
+/// Seconds clients refused for lack of capacity or during shutdown are told to wait
+/// before retrying.
+const RETRY_AFTER_SECS: u64 = 10;
+
+/// Media type of every error response
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Body of every error response, an RFC 9457 problem document extended with a stable
+/// machine-readable `code`. `error` repeats `detail` for clients written before the
+/// problem members existed.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ErrorResponse {
+    /// `urn:chef-de-vibe:` followed by the code
+    #[serde(rename = "type", default)]
+    pub problem_type: String,
+    /// Reason phrase of the status
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub status: u16,
+    #[serde(default)]
+    pub detail: String,
+    /// Stable code such as `SESSION_NOT_FOUND`; see the README for the full list
+    pub code: String,
+    pub error: String,
+    /// `X-Request-Id` of the request that failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl ErrorResponse {
+    #[must_use]
+    pub fn new(status: StatusCode, code: &str, detail: String) -> Self {
+        Self {
+            problem_type: format!("urn:chef-de-vibe:{code}"),
+            title: status.canonical_reason().unwrap_or_default().to_string(),
+            status: status.as_u16(),
+            detail: detail.clone(),
+            code: code.to_string(),
+            error: detail,
+            request_id: None,
+        }
+    }
 }
 
-#[derive(Serialize)]
-struct ErrorResponse {
-    error: String,
-    code: String,
+impl IntoResponse for ErrorResponse {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = (status, Json(self)).into_response();
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+        response
+    }
 }
 
 impl OrchestratorError {
@@ -49,13 +139,26 @@ impl OrchestratorError {
         match self {
             Self::InvalidRequest(_) => "INVALID_REQUEST",
             Self::WorkingDirInvalid(_) => "WORKING_DIR_INVALID",
-            Self::ClaudeSpawnFailed(_) => "CLAUDE_SPAWN_FAILED",
+            Self::ClaudeSpawnFailed(_) => "SPAWN_FAILED",
             Self::SessionNotFound(_) => "SESSION_NOT_FOUND",
             Self::DirectoryReadError(_) => "DIRECTORY_READ_ERROR",
             Self::FileParseError(_) => "FILE_PARSE_ERROR",
             Self::InternalError(_) => "INTERNAL_ERROR",
             Self::WebSocketError(_) => "WEBSOCKET_ERROR",
             Self::ProcessCommunicationError(_) => "PROCESS_COMMUNICATION_ERROR",
+            Self::TranscriptHookFailed(_) => "TRANSCRIPT_HOOK_FAILED",
+            Self::UploadNotFound(_) => "UPLOAD_NOT_FOUND",
+            Self::Unauthorized(_) => "UNAUTHORIZED",
+            Self::PayloadTooLarge(_) => "PAYLOAD_TOO_LARGE",
+            Self::MediaNotFound(_) => "MEDIA_NOT_FOUND",
+            Self::ProjectNotFound(_) => "PROJECT_NOT_FOUND",
+            Self::ScratchFileNotFound(_) => "SCRATCH_FILE_NOT_FOUND",
+            Self::ApprovalNotFound(_) => "APPROVAL_NOT_FOUND",
+            Self::ApprovalAlreadyDecided(_) => "APPROVAL_ALREADY_DECIDED",
+            Self::WireLogNotFound(_) => "WIRE_LOG_NOT_FOUND",
+            Self::Forbidden(_) => "FORBIDDEN",
+            Self::TooManySessions(_) => "SESSION_LIMIT_REACHED",
+            Self::ShuttingDown(_) => "SHUTTING_DOWN",
         }
     }
 
@@ -64,24 +167,40 @@ impl OrchestratorError {
             Self::InvalidRequest(_) | Self::WorkingDirInvalid(_) | Self::FileParseError(_) => {
                 StatusCode::BAD_REQUEST
             }
-            Self::SessionNotFound(_) => StatusCode::NOT_FOUND,
+            Self::SessionNotFound(_)
+            | Self::UploadNotFound(_)
+            | Self::MediaNotFound(_)
+            | Self::ProjectNotFound(_)
+            | Self::ScratchFileNotFound(_)
+            | Self::ApprovalNotFound(_)
+            | Self::WireLogNotFound(_) => StatusCode::NOT_FOUND,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::ApprovalAlreadyDecided(_) => StatusCode::CONFLICT,
+            Self::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::TooManySessions(_) => StatusCode::TOO_MANY_REQUESTS,
+            Self::ShuttingDown(_) => StatusCode::SERVICE_UNAVAILABLE,
             Self::ClaudeSpawnFailed(_)
             | Self::DirectoryReadError(_)
             | Self::InternalError(_)
             | Self::WebSocketError(_)
-            | Self::ProcessCommunicationError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            | Self::ProcessCommunicationError(_)
+            | Self::TranscriptHookFailed(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
 
 impl IntoResponse for OrchestratorError {
     fn into_response(self) -> Response {
-        let error_response = ErrorResponse {
-            error: self.to_string(),
-            code: self.error_code().to_string(),
-        };
-
-        (self.status_code(), Json(error_response)).into_response()
+        let mut response =
+            ErrorResponse::new(self.status_code(), self.error_code(), self.to_string())
+                .into_response();
+        if matches!(self, Self::TooManySessions(_) | Self::ShuttingDown(_)) {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(RETRY_AFTER_SECS));
+        }
+        response
     }
 }
 
@@ -125,6 +244,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_limit_and_spawn_codes() {
+        assert_eq!(
+            OrchestratorError::TooManySessions("test".to_string()).error_code(),
+            "SESSION_LIMIT_REACHED"
+        );
+        assert_eq!(
+            OrchestratorError::ClaudeSpawnFailed("test".to_string()).error_code(),
+            "SPAWN_FAILED"
+        );
+    }
+
+    #[test]
+    fn test_error_response_is_problem_document() {
+        let response = OrchestratorError::SessionNotFound("abc".to_string()).into_response();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+
+        let body = ErrorResponse::new(StatusCode::NOT_FOUND, "SESSION_NOT_FOUND", "gone".into());
+        let body = serde_json::to_value(body).unwrap();
+        assert_eq!(body["type"], "urn:chef-de-vibe:SESSION_NOT_FOUND");
+        assert_eq!(body["title"], "Not Found");
+        assert_eq!(body["status"], 404);
+        assert_eq!(body["detail"], "gone");
+        assert_eq!(body["error"], "gone");
+    }
+
     #[test]
     fn test_status_codes() {
         assert_eq!(
@@ -139,5 +284,13 @@ mod tests {
             OrchestratorError::InternalError("test".to_string()).status_code(),
             StatusCode::INTERNAL_SERVER_ERROR
         );
+        assert_eq!(
+            OrchestratorError::TooManySessions("test".to_string()).status_code(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            OrchestratorError::ShuttingDown("test".to_string()).status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
     }
 }
@@ -0,0 +1,491 @@
+use crate::config::EventSink;
+use crate::models::{ApprovalMessage, BroadcastEnvelope, BroadcastMessage, DECIDED_BY_FIELD};
+use crate::notifications::{TurnTracker, WatchEvent};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, mpsc, RwLock};
+use tracing::{debug, info, warn};
+
+/// How long connecting to a sink or publishing one event may take before the event is dropped.
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Events waiting for a slow sink; newer events are dropped once it is full.
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Session lifecycle and approval events published to message queues.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportEvent {
+    SessionCreated,
+    TurnCompleted,
+    TurnFailed,
+    ApprovalRequested,
+    ApprovalDecided,
+    /// Claude exited, or the session was shut down
+    SessionExited,
+}
+
+impl ExportEvent {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SessionCreated => "session_created",
+            Self::TurnCompleted => "turn_completed",
+            Self::TurnFailed => "turn_failed",
+            Self::ApprovalRequested => "approval_requested",
+            Self::ApprovalDecided => "approval_decided",
+            Self::SessionExited => "session_exited",
+        }
+    }
+}
+
+/// One published event; `data` depends on the event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedEvent {
+    pub event: ExportEvent,
+    pub session_id: String,
+    pub working_directory: PathBuf,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub data: serde_json::Value,
+}
+
+/// Fans session events out to the configured message queues.
+///
+/// Every sink has its own queue and publishing task, so a sink that is down neither
+/// delays the others nor reorders their events.
+pub struct EventExporter {
+    queues: Vec<(String, mpsc::Sender<Arc<ExportedEvent>>)>,
+}
+
+impl EventExporter {
+    /// Starts a publishing task per sink; must be called within a Tokio runtime when
+    /// `sinks` is not empty.
+    #[must_use]
+    pub fn new(sinks: &[EventSink]) -> Self {
+        let client = reqwest::Client::new();
+        let queues = sinks
+            .iter()
+            .map(|sink| {
+                let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+                let name = match sink {
+                    EventSink::Nats {
+                        address,
+                        subject_prefix,
+                    } => {
+                        tokio::spawn(publish_to_nats(address.clone(), subject_prefix.clone(), rx));
+                        format!("nats:{address}")
+                    }
+                    EventSink::KafkaRest { proxy_url, topic } => {
+                        tokio::spawn(publish_to_kafka(
+                            client.clone(),
+                            proxy_url.clone(),
+                            topic.clone(),
+                            rx,
+                        ));
+                        format!("kafka:{proxy_url}/{topic}")
+                    }
+                };
+                (name, tx)
+            })
+            .collect();
+        Self { queues }
+    }
+
+    /// Whether any sink is configured, i.e. whether sessions need following at all
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        !self.queues.is_empty()
+    }
+
+    /// Queues an event for every sink. Publishing happens in the background and
+    /// failures are only logged.
+    pub fn publish(&self, event: ExportedEvent) {
+        let event = Arc::new(event);
+        for (sink, queue) in &self.queues {
+            if let Err(mpsc::error::TrySendError::Full(event)) = queue.try_send(event.clone()) {
+                warn!(
+                    sink = %sink,
+                    session_id = %event.session_id,
+                    event = event.event.as_str(),
+                    "Event sink is falling behind, dropping event"
+                );
+            }
+        }
+    }
+}
+
+/// A connection to a NATS server that only publishes.
+struct NatsConnection {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+}
+
+impl NatsConnection {
+    async fn connect(address: &str) -> io::Result<Self> {
+        let (reader, mut writer) = TcpStream::connect(address).await?.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        // The server greets with INFO before it accepts CONNECT
+        match lines.next_line().await? {
+            Some(line) if line.starts_with("INFO") => {}
+            other => {
+                return Err(io::Error::other(format!(
+                    "expected INFO from server, got {other:?}"
+                )))
+            }
+        }
+        writer
+            .write_all(
+                b"CONNECT {\"verbose\":false,\"pedantic\":false,\"name\":\"chef-de-vibe\"}\r\n",
+            )
+            .await?;
+        Ok(Self { lines, writer })
+    }
+
+    async fn publish(&mut self, subject: &str, payload: &[u8]) -> io::Result<()> {
+        let mut frame = format!("PUB {subject} {}\r\n", payload.len()).into_bytes();
+        frame.extend_from_slice(payload);
+        frame.extend_from_slice(b"\r\n");
+        self.writer.write_all(&frame).await
+    }
+
+    /// Handles one line from the server. Returns `false` once the connection is unusable.
+    async fn handle_server_line(&mut self, line: io::Result<Option<String>>) -> bool {
+        match line {
+            Ok(Some(line)) if line == "PING" => self.writer.write_all(b"PONG\r\n").await.is_ok(),
+            Ok(Some(line)) if line.starts_with("-ERR") => {
+                warn!(error = %line, "NATS server reported an error");
+                true
+            }
+            Ok(Some(_)) => true,
+            Ok(None) | Err(_) => false,
+        }
+    }
+}
+
+enum NatsWake {
+    Event(Option<Arc<ExportedEvent>>),
+    ServerLine(io::Result<Option<String>>),
+}
+
+/// Publishes queued events to `<subject_prefix>.<event>`, connecting lazily and
+/// reconnecting after the server goes away.
+async fn publish_to_nats(
+    address: String,
+    subject_prefix: String,
+    mut events: mpsc::Receiver<Arc<ExportedEvent>>,
+) {
+    let mut connection: Option<NatsConnection> = None;
+    loop {
+        let wake = match connection.as_mut() {
+            Some(conn) => tokio::select! {
+                event = events.recv() => NatsWake::Event(event),
+                line = conn.lines.next_line() => NatsWake::ServerLine(line),
+            },
+            None => NatsWake::Event(events.recv().await),
+        };
+        let event = match wake {
+            NatsWake::Event(Some(event)) => event,
+            NatsWake::Event(None) => break,
+            NatsWake::ServerLine(line) => {
+                if let Some(conn) = connection.as_mut() {
+                    if !conn.handle_server_line(line).await {
+                        debug!(address = %address, "NATS connection closed");
+                        connection = None;
+                    }
+                }
+                continue;
+            }
+        };
+
+        let subject = format!("{subject_prefix}.{}", event.event.as_str());
+        let payload = match serde_json::to_vec(&*event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!(error = %e, "Failed to serialize session event");
+                continue;
+            }
+        };
+        // A connection the server dropped while idle is only noticed on write, so a
+        // failed publish is retried once on a fresh connection
+        let mut result = Err(io::Error::other("not attempted"));
+        for _ in 0..2 {
+            result = tokio::time::timeout(PUBLISH_TIMEOUT, async {
+                if connection.is_none() {
+                    connection = Some(NatsConnection::connect(&address).await?);
+                }
+                let conn = connection.as_mut().expect("connected above");
+                conn.publish(&subject, &payload).await
+            })
+            .await
+            .unwrap_or_else(|_| Err(io::Error::from(io::ErrorKind::TimedOut)));
+            if result.is_ok() {
+                break;
+            }
+            connection = None;
+        }
+        match result {
+            Ok(()) => debug!(
+                subject = %subject,
+                session_id = %event.session_id,
+                "Published session event to NATS"
+            ),
+            Err(e) => warn!(
+                address = %address,
+                subject = %subject,
+                session_id = %event.session_id,
+                error = %e,
+                "Failed to publish session event to NATS"
+            ),
+        }
+    }
+}
+
+/// Produces queued events to a Kafka topic through the REST proxy's v2 API, keyed by
+/// session ID so a session's events stay in one partition.
+async fn publish_to_kafka(
+    client: reqwest::Client,
+    proxy_url: String,
+    topic: String,
+    mut events: mpsc::Receiver<Arc<ExportedEvent>>,
+) {
+    let url = format!("{proxy_url}/topics/{topic}");
+    while let Some(event) = events.recv().await {
+        let body = serde_json::json!({
+            "records": [{ "key": event.session_id, "value": &*event }],
+        });
+        let result = client
+            .post(&url)
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                "application/vnd.kafka.json.v2+json",
+            )
+            .body(body.to_string())
+            .timeout(PUBLISH_TIMEOUT)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        match result {
+            Ok(_) => debug!(
+                topic = %topic,
+                session_id = %event.session_id,
+                "Produced session event to Kafka"
+            ),
+            Err(e) => warn!(
+                url = %url,
+                session_id = %event.session_id,
+                error = %e,
+                "Failed to produce session event to Kafka"
+            ),
+        }
+    }
+}
+
+/// Turns a session's output and approvals into exported events.
+///
+/// Runs until Claude exits or the session is dropped. The session ID is read on every
+/// event since it changes when a resumed session settles on its new ID.
+pub async fn follow_session(
+    exporter: Arc<EventExporter>,
+    session_id: Arc<RwLock<String>>,
+    working_directory: PathBuf,
+    mut output: broadcast::Receiver<BroadcastEnvelope>,
+    mut approvals: broadcast::Receiver<ApprovalMessage>,
+) {
+    let publish = |event, session_id: String, data| {
+        exporter.publish(ExportedEvent {
+            event,
+            session_id,
+            working_directory: working_directory.clone(),
+            timestamp: chrono::Utc::now(),
+            data,
+        });
+    };
+    publish(
+        ExportEvent::SessionCreated,
+        session_id.read().await.clone(),
+        serde_json::Value::Null,
+    );
+
+    let mut turn = TurnTracker::default();
+    // Restored approvals are broadcast again, they only need announcing once
+    let mut announced_approvals = HashSet::new();
+
+    loop {
+        let (event, data) = tokio::select! {
+            message = output.recv() => match message.map(|envelope| envelope.message) {
+                Ok(BroadcastMessage::ClaudeOutput { line, .. }) => match turn.observe(&line) {
+                    Some((WatchEvent::Failed, detail)) => {
+                        (ExportEvent::TurnFailed, serde_json::json!({ "detail": detail }))
+                    }
+                    Some((_, detail)) => {
+                        (ExportEvent::TurnCompleted, serde_json::json!({ "detail": detail }))
+                    }
+                    None => continue,
+                },
+                Ok(BroadcastMessage::Disconnect) | Err(broadcast::error::RecvError::Closed) => {
+                    let session_id = session_id.read().await.clone();
+                    info!(session_id = %session_id, "Session exited, stopping event export");
+                    publish(
+                        ExportEvent::SessionExited,
+                        session_id,
+                        serde_json::json!({ "in_turn": turn.in_turn }),
+                    );
+                    break;
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    debug!(skipped, "Session event export fell behind Claude output");
+                    continue;
+                }
+            },
+            message = approvals.recv() => match message {
+                Ok(ApprovalMessage::ApprovalRequest { request, .. }) => {
+                    if !announced_approvals.insert(request.id.clone()) {
+                        continue;
+                    }
+                    (
+                        ExportEvent::ApprovalRequested,
+                        serde_json::json!({
+                            "approval_id": request.id,
+                            "tool_name": request.request.get("tool_name"),
+                            "risk": request.risk,
+                        }),
+                    )
+                }
+                Ok(ApprovalMessage::ApprovalResponse(response)) => (
+                    ExportEvent::ApprovalDecided,
+                    serde_json::json!({
+                        "approval_id": response.get("id"),
+                        "behavior": response.get("response").and_then(|r| r.get("behavior")),
+                        "decided_by": response.get(DECIDED_BY_FIELD),
+                    }),
+                ),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        };
+
+        publish(event, session_id.read().await.clone(), data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_publish_to_nats() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let exporter = EventExporter::new(&[EventSink::Nats {
+            address,
+            subject_prefix: "ci".to_string(),
+        }]);
+        exporter.publish(ExportedEvent {
+            event: ExportEvent::TurnCompleted,
+            session_id: "s1".to_string(),
+            working_directory: PathBuf::from("/work"),
+            timestamp: chrono::Utc::now(),
+            data: serde_json::json!({ "detail": "Done" }),
+        });
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        writer.write_all(b"INFO {}\r\n").await.unwrap();
+        assert!(lines
+            .next_line()
+            .await
+            .unwrap()
+            .unwrap()
+            .starts_with("CONNECT {"));
+
+        let publish = lines.next_line().await.unwrap().unwrap();
+        let payload = lines.next_line().await.unwrap().unwrap();
+        assert_eq!(publish, format!("PUB ci.turn_completed {}", payload.len()));
+        let event: ExportedEvent = serde_json::from_str(&payload).unwrap();
+        assert_eq!(event.event, ExportEvent::TurnCompleted);
+        assert_eq!(event.session_id, "s1");
+        assert_eq!(event.data["detail"], "Done");
+
+        // Keep-alive pings are answered
+        writer.write_all(b"PING\r\n").await.unwrap();
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "PONG");
+    }
+
+    #[tokio::test]
+    async fn test_follow_session_events() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let exporter = Arc::new(EventExporter::new(&[EventSink::Nats {
+            address: listener.local_addr().unwrap().to_string(),
+            subject_prefix: "ci".to_string(),
+        }]));
+        let (output_tx, output_rx) = broadcast::channel(16);
+        let (approval_tx, approval_rx) = broadcast::channel(16);
+        let follower = tokio::spawn(follow_session(
+            exporter,
+            Arc::new(RwLock::new("s1".to_string())),
+            PathBuf::from("/work"),
+            output_rx,
+            approval_rx,
+        ));
+
+        approval_tx
+            .send(ApprovalMessage::response(
+                serde_json::json!({ "id": "a1", "response": { "behavior": "allow" } }),
+                "alice",
+            ))
+            .unwrap();
+        // Let the approval be handled before Claude exits, both receivers are raced
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        output_tx
+            .send(BroadcastEnvelope {
+                seq: 1,
+                message: BroadcastMessage::ClaudeOutput {
+                    line: r#"{"type":"assistant","message":{"content":[]}}"#.into(),
+                    received_at: chrono::Utc::now(),
+                },
+            })
+            .unwrap();
+        output_tx
+            .send(BroadcastEnvelope {
+                seq: 2,
+                message: BroadcastMessage::Disconnect,
+            })
+            .unwrap();
+        follower.await.unwrap();
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        writer.write_all(b"INFO {}\r\n").await.unwrap();
+        lines.next_line().await.unwrap();
+
+        let mut events = Vec::new();
+        for _ in 0..3 {
+            lines.next_line().await.unwrap();
+            let payload = lines.next_line().await.unwrap().unwrap();
+            events.push(serde_json::from_str::<ExportedEvent>(&payload).unwrap());
+        }
+        let kinds: Vec<_> = events.iter().map(|e| e.event).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ExportEvent::SessionCreated,
+                ExportEvent::ApprovalDecided,
+                ExportEvent::SessionExited,
+            ]
+        );
+        assert_eq!(events[1].data["decided_by"], "alice");
+        assert_eq!(events[1].data["behavior"], "allow");
+        assert_eq!(events[2].data["in_turn"], true);
+    }
+}
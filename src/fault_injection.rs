@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Faults injected into a session through the admin API, so tests can exercise
+/// failure paths deterministically. Only compiled with the `testing` feature.
+#[derive(Debug, Default)]
+pub struct Faults {
+    stdin_delay_ms: AtomicU64,
+    drop_broadcasts: AtomicU64,
+    corrupt_stdout_lines: AtomicU64,
+}
+
+/// Faults to inject into a session; fields left out keep their current setting.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct InjectFaultsRequest {
+    /// Delay before each queued message is written to Claude's stdin; 0 removes it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdin_delay_ms: Option<u64>,
+    /// How many of the next broadcast messages are dropped instead of sent to clients
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drop_broadcasts: Option<u64>,
+    /// How many of the next stdout lines are cut in half, as if Claude wrote them torn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub corrupt_stdout_lines: Option<u64>,
+    /// Kill the Claude process, as if it had crashed
+    #[serde(default)]
+    pub exit_process: bool,
+}
+
+/// Faults a session still has pending.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FaultsResponse {
+    pub session_id: String,
+    pub stdin_delay_ms: u64,
+    pub drop_broadcasts: u64,
+    pub corrupt_stdout_lines: u64,
+}
+
+impl Faults {
+    /// Applies the settings of `request`; killing the process is up to the caller.
+    pub fn set(&self, request: &InjectFaultsRequest) {
+        if let Some(ms) = request.stdin_delay_ms {
+            self.stdin_delay_ms.store(ms, Ordering::Relaxed);
+        }
+        if let Some(count) = request.drop_broadcasts {
+            self.drop_broadcasts.store(count, Ordering::Relaxed);
+        }
+        if let Some(count) = request.corrupt_stdout_lines {
+            self.corrupt_stdout_lines.store(count, Ordering::Relaxed);
+        }
+    }
+
+    #[must_use]
+    pub fn status(&self, session_id: String) -> FaultsResponse {
+        FaultsResponse {
+            session_id,
+            stdin_delay_ms: self.stdin_delay_ms.load(Ordering::Relaxed),
+            drop_broadcasts: self.drop_broadcasts.load(Ordering::Relaxed),
+            corrupt_stdout_lines: self.corrupt_stdout_lines.load(Ordering::Relaxed),
+        }
+    }
+
+    /// How long to hold back the next stdin write, if at all.
+    #[must_use]
+    pub fn stdin_delay(&self) -> Option<Duration> {
+        match self.stdin_delay_ms.load(Ordering::Relaxed) {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
+    }
+
+    /// Whether the broadcast about to be sent should be dropped; counts it if so.
+    #[must_use]
+    pub fn drop_broadcast(&self) -> bool {
+        take_one(&self.drop_broadcasts)
+    }
+
+    /// The stdout line to process instead of `line`.
+    #[must_use]
+    pub fn corrupt_stdout_line(&self, line: String) -> String {
+        if !take_one(&self.corrupt_stdout_lines) {
+            return line;
+        }
+        let half = line.chars().count() / 2;
+        let corrupted: String = line.chars().take(half).collect();
+        warn!(
+            original_length = line.len(),
+            corrupted_length = corrupted.len(),
+            "Injected fault: corrupted stdout line"
+        );
+        corrupted
+    }
+}
+
+fn take_one(counter: &AtomicU64) -> bool {
+    counter
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+        .is_ok()
+}
+
+/// Sends SIGKILL to a Claude process, so its session goes through the crash path.
+///
+/// # Errors
+///
+/// Returns an error if the signal cannot be sent.
+pub fn kill_process(pid: u32) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        use nix::sys::signal::{kill, Signal};
+        use nix::unistd::Pid;
+        let pid_i32 = i32::try_from(pid).map_err(|e| e.to_string())?;
+        kill(Pid::from_raw(pid_i32), Signal::SIGKILL).map_err(|e| e.to_string())?;
+        info!(process_id = pid, "Injected fault: killed Claude process");
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        Err(format!(
+            "Killing process {pid} is not implemented for non-Unix systems"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_faults_count_down() {
+        let faults = Faults::default();
+        assert!(!faults.drop_broadcast());
+        assert_eq!(faults.stdin_delay(), None);
+
+        faults.set(&InjectFaultsRequest {
+            stdin_delay_ms: Some(250),
+            drop_broadcasts: Some(2),
+            corrupt_stdout_lines: Some(1),
+            exit_process: false,
+        });
+        assert_eq!(faults.stdin_delay(), Some(Duration::from_millis(250)));
+        assert!(faults.drop_broadcast());
+        assert!(faults.drop_broadcast());
+        assert!(!faults.drop_broadcast());
+
+        let line = r#"{"type":"assistant"}"#.to_string();
+        let corrupted = faults.corrupt_stdout_line(line.clone());
+        assert!(serde_json::from_str::<serde_json::Value>(&corrupted).is_err());
+        assert_eq!(faults.corrupt_stdout_line(line.clone()), line);
+
+        // Left-out settings are kept
+        faults.set(&InjectFaultsRequest {
+            stdin_delay_ms: Some(0),
+            ..Default::default()
+        });
+        assert_eq!(
+            faults.status("s1".to_string()),
+            FaultsResponse {
+                session_id: "s1".to_string(),
+                stdin_delay_ms: 0,
+                drop_broadcasts: 0,
+                corrupt_stdout_lines: 0,
+            }
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use crate::error::{OrchestratorError, OrchestratorResult};
+use chrono::{DateTime, Utc};
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{debug, instrument};
+
+/// How long a single git invocation may take.
+const GIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The working directory's tracked files as of some commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeSnapshot {
+    /// Commit the tree was read from; `None` if the repository had no commits yet
+    pub commit: Option<String>,
+    pub files: Vec<String>,
+}
+
+async fn git(working_dir: &Path, args: &[&str]) -> OrchestratorResult<Vec<u8>> {
+    let output = tokio::time::timeout(
+        GIT_TIMEOUT,
+        Command::new("git")
+            .arg("-C")
+            .arg(working_dir)
+            .args(args)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| OrchestratorError::InternalError(format!("git {} timed out", args[0])))?
+    .map_err(|e| OrchestratorError::InternalError(format!("Failed to run git: {e}")))?;
+
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(OrchestratorError::InvalidRequest(format!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Last commit on `HEAD` made at or before `at`, or the current `HEAD` when `at` is `None`.
+async fn commit_at(
+    working_dir: &Path,
+    at: Option<DateTime<Utc>>,
+) -> OrchestratorResult<Option<String>> {
+    let before = at.map(|at| format!("--before={}", at.to_rfc3339()));
+    let mut args = vec!["rev-list", "-1"];
+    if let Some(before) = &before {
+        args.push(before);
+    }
+    args.push("HEAD");
+
+    match git(working_dir, &args).await {
+        Ok(stdout) => {
+            let commit = String::from_utf8_lossy(&stdout).trim().to_string();
+            Ok((!commit.is_empty()).then_some(commit))
+        }
+        // An unborn HEAD has nothing to show yet
+        Err(OrchestratorError::InvalidRequest(message))
+            if message.contains("unknown revision") || message.contains("bad revision") =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Lists the files tracked in `working_dir` as they were at `at`.
+///
+/// Only committed state is visible: changes Claude made but nobody committed yet
+/// show up at the first commit that contains them.
+///
+/// # Errors
+///
+/// Returns an error if `working_dir` is not a git repository or git fails.
+#[instrument(fields(working_dir = %working_dir.display()))]
+pub async fn tree_at(
+    working_dir: &Path,
+    at: Option<DateTime<Utc>>,
+) -> OrchestratorResult<TreeSnapshot> {
+    git(working_dir, &["rev-parse", "--git-dir"])
+        .await
+        .map_err(|_| {
+            OrchestratorError::InvalidRequest(format!(
+                "Working directory is not a git repository: {}",
+                working_dir.display()
+            ))
+        })?;
+
+    let Some(commit) = commit_at(working_dir, at).await? else {
+        return Ok(TreeSnapshot {
+            commit: None,
+            files: Vec::new(),
+        });
+    };
+
+    let listing = git(
+        working_dir,
+        &["ls-tree", "-r", "-z", "--name-only", &commit],
+    )
+    .await?;
+    let files: Vec<String> = listing
+        .split(|b| *b == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect();
+    debug!(commit = %commit, files = files.len(), "Read file tree from git history");
+
+    Ok(TreeSnapshot {
+        commit: Some(commit),
+        files,
+    })
+}
+
+/// Reads one file's content at `commit`.
+///
+/// # Errors
+///
+/// Returns an error if the file does not exist at that commit.
+pub async fn file_at(working_dir: &Path, commit: &str, path: &str) -> OrchestratorResult<String> {
+    let content = git(working_dir, &["show", &format!("{commit}:{path}")]).await?;
+    Ok(String::from_utf8_lossy(&content).into_owned())
+}
+
+/// Timestamp of the transcript entry with the given `uuid`.
+#[must_use]
+pub fn message_timestamp(content: &[serde_json::Value], uuid: &str) -> Option<DateTime<Utc>> {
+    content
+        .iter()
+        .find(|entry| entry.get("uuid").and_then(|u| u.as_str()) == Some(uuid))
+        .and_then(|entry| entry.get("timestamp")?.as_str())
+        .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn run_git(dir: &Path, args: &[&str], date: &str) {
+        let status = std::process::Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .env("GIT_AUTHOR_DATE", date)
+            .env("GIT_COMMITTER_DATE", date)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[tokio::test]
+    async fn test_tree_at_follows_history() {
+        let repo = TempDir::new().unwrap();
+        run_git(repo.path(), &["init", "-q"], "2025-01-01T00:00:00Z");
+
+        // Nothing committed yet
+        let empty = tree_at(repo.path(), None).await.unwrap();
+        assert_eq!(empty.commit, None);
+
+        std::fs::write(repo.path().join("a.rs"), "fn a() {}").unwrap();
+        run_git(repo.path(), &["add", "."], "2025-01-01T10:00:00Z");
+        run_git(repo.path(), &["commit", "-qm", "a"], "2025-01-01T10:00:00Z");
+        std::fs::write(repo.path().join("b.rs"), "fn b() {}").unwrap();
+        std::fs::write(repo.path().join("a.rs"), "fn a() { b() }").unwrap();
+        run_git(repo.path(), &["add", "."], "2025-01-01T12:00:00Z");
+        run_git(repo.path(), &["commit", "-qm", "b"], "2025-01-01T12:00:00Z");
+
+        let at = |s: &str| Some(DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc));
+
+        let before_any = tree_at(repo.path(), at("2025-01-01T09:00:00Z"))
+            .await
+            .unwrap();
+        assert_eq!(before_any.commit, None);
+
+        let first = tree_at(repo.path(), at("2025-01-01T11:00:00Z"))
+            .await
+            .unwrap();
+        assert_eq!(first.files, vec!["a.rs"]);
+        let first_commit = first.commit.unwrap();
+        assert_eq!(
+            file_at(repo.path(), &first_commit, "a.rs").await.unwrap(),
+            "fn a() {}"
+        );
+        assert!(file_at(repo.path(), &first_commit, "b.rs").await.is_err());
+
+        let latest = tree_at(repo.path(), None).await.unwrap();
+        assert_eq!(latest.files, vec!["a.rs", "b.rs"]);
+    }
+
+    #[tokio::test]
+    async fn test_tree_at_requires_git_repository() {
+        let dir = TempDir::new().unwrap();
+        assert!(matches!(
+            tree_at(dir.path(), None).await,
+            Err(OrchestratorError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_message_timestamp() {
+        let content = vec![
+            serde_json::json!({"uuid": "u1", "timestamp": "2025-01-01T10:00:00.000Z"}),
+            serde_json::json!({"uuid": "u2"}),
+        ];
+        assert_eq!(
+            message_timestamp(&content, "u1").map(|t| t.to_rfc3339()),
+            Some("2025-01-01T10:00:00+00:00".to_string())
+        );
+        assert_eq!(message_timestamp(&content, "u2"), None);
+        assert_eq!(message_timestamp(&content, "missing"), None);
+    }
+}
@@ -0,0 +1,732 @@
+use crate::error::{OrchestratorError, OrchestratorResult};
+use crate::journal_offsets::JournalOffsets;
+use crate::models::SessionInfo;
+use rayon::prelude::*;
+use rusqlite::{params, Connection};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use tracing::{debug, warn};
+use walkdir::WalkDir;
+
+/// Index database in the state directory.
+const INDEX_FILE: &str = "session_index.sqlite3";
+
+/// Bumped whenever the tables change; an index of another version is rebuilt from scratch.
+const SCHEMA_VERSION: i64 = 2;
+
+const SCHEMA: &str = "
+    CREATE TABLE files (
+        path TEXT PRIMARY KEY,
+        modified_ns INTEGER NOT NULL,
+        size INTEGER NOT NULL
+    );
+    CREATE TABLE sessions (
+        path TEXT NOT NULL,
+        ordinal INTEGER NOT NULL,
+        session_id TEXT NOT NULL,
+        cwd TEXT,
+        earliest TEXT,
+        latest TEXT,
+        first_message TEXT,
+        first_message_at TEXT
+    );
+    CREATE INDEX sessions_path ON sessions (path);
+    CREATE INDEX sessions_session_id ON sessions (session_id);
+    CREATE TABLE messages (
+        path TEXT NOT NULL,
+        ordinal INTEGER NOT NULL,
+        uuid TEXT NOT NULL,
+        session_id TEXT NOT NULL
+    );
+    CREATE INDEX messages_path ON messages (path);
+    CREATE INDEX messages_uuid ON messages (uuid);
+    CREATE TABLE summaries (
+        path TEXT NOT NULL,
+        leaf_uuid TEXT NOT NULL,
+        summary TEXT NOT NULL
+    );
+    CREATE INDEX summaries_path ON summaries (path);
+";
+
+/// What one journal file contributes to the session list.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileScan {
+    /// `(leafUuid, summary)` of the summary entries
+    pub summaries: Vec<(String, String)>,
+    /// `(uuid, sessionId)` of the entries a summary can point at, in file order
+    pub messages: Vec<(String, String)>,
+    /// Sessions with entries in the file, in order of their first entry
+    pub sessions: Vec<FileSession>,
+}
+
+/// A session as seen in one journal file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileSession {
+    pub session_id: String,
+    /// First `cwd` of the session's entries
+    pub cwd: Option<String>,
+    pub earliest: Option<String>,
+    pub latest: Option<String>,
+    /// Earliest user message, listed when there is no summary
+    pub first_message: Option<String>,
+    pub first_message_at: Option<String>,
+}
+
+impl FileSession {
+    /// Keeps `message` if it is earlier than the one kept so far. Messages without a
+    /// timestamp only win over nothing.
+    pub fn offer_first_message(&mut self, message: String, timestamp: Option<String>) {
+        let earlier = match (&timestamp, &self.first_message_at) {
+            (Some(new), Some(old)) => new < old,
+            (Some(_), None) => true,
+            (None, _) => self.first_message.is_none(),
+        };
+        if earlier {
+            self.first_message = Some(message);
+            self.first_message_at = timestamp;
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        if self.cwd.is_none() {
+            self.cwd = other.cwd;
+        }
+        if other.earliest.is_some() && (self.earliest.is_none() || other.earliest < self.earliest) {
+            self.earliest = other.earliest;
+        }
+        if other.latest > self.latest {
+            self.latest = other.latest;
+        }
+        if let Some(message) = other.first_message {
+            self.offer_first_message(message, other.first_message_at);
+        }
+    }
+}
+
+/// Files a sync had to read or drop.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RefreshStats {
+    pub rescanned: usize,
+    pub removed: usize,
+}
+
+/// Sessions on disk with a working directory, and the first user message of every session
+type Catalog = (Vec<SessionInfo>, HashMap<String, String>);
+
+/// Journals that may have changed since the index was last synced.
+enum Pending {
+    /// Anything under the projects directory
+    Everything,
+    Paths(HashSet<PathBuf>),
+}
+
+/// Session metadata of every journal file under the projects directory, kept in a database
+/// so listing only re-reads the files that changed since the last sync. Stored in the
+/// state directory when one is configured, otherwise in memory.
+///
+/// Without a watcher every sync walks the projects directory to find changed files; while
+/// one is attached (see [`crate::journal_watcher`]) only the paths it reported are looked at.
+pub struct SessionIndex {
+    conn: Mutex<Connection>,
+    watched: AtomicBool,
+    pending: Mutex<Pending>,
+    /// Listing built from the database, until the next change
+    catalog: Mutex<Option<Catalog>>,
+    /// Line offsets of the journals whose content was read incrementally
+    offsets: JournalOffsets,
+}
+
+impl SessionIndex {
+    /// Opens the index in `state_dir`, or in memory without one or when the file cannot
+    /// be opened.
+    ///
+    /// # Panics
+    ///
+    /// Panics if not even an in-memory database can be created.
+    #[must_use]
+    pub fn open(state_dir: Option<&Path>) -> Self {
+        if let Some(path) = state_dir.map(|dir| dir.join(INDEX_FILE)) {
+            match Connection::open(&path).and_then(Self::prepare) {
+                Ok(conn) => return Self::with_connection(conn),
+                Err(e) => warn!(
+                    path = %path.display(),
+                    error = %e,
+                    "Failed to open session index, keeping it in memory"
+                ),
+            }
+        }
+        let conn = Connection::open_in_memory()
+            .and_then(Self::prepare)
+            .expect("in-memory SQLite database");
+        Self::with_connection(conn)
+    }
+
+    fn with_connection(conn: Connection) -> Self {
+        Self {
+            conn: Mutex::new(conn),
+            watched: AtomicBool::new(false),
+            pending: Mutex::new(Pending::Everything),
+            catalog: Mutex::new(None),
+            offsets: JournalOffsets::default(),
+        }
+    }
+
+    /// Creates the tables, dropping those of an index written with another schema.
+    fn prepare(conn: Connection) -> rusqlite::Result<Connection> {
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version != SCHEMA_VERSION {
+            conn.execute_batch(
+                "DROP TABLE IF EXISTS files;
+                 DROP TABLE IF EXISTS sessions;
+                 DROP TABLE IF EXISTS messages;
+                 DROP TABLE IF EXISTS summaries;",
+            )?;
+            conn.execute_batch(SCHEMA)?;
+            conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        }
+        Ok(conn)
+    }
+
+    /// Where the lines of journals start, for reading what was appended to them.
+    #[must_use]
+    pub const fn offsets(&self) -> &JournalOffsets {
+        &self.offsets
+    }
+
+    /// Whether a watcher reports changes, so syncs only look at the paths it reported.
+    #[must_use]
+    pub fn is_watched(&self) -> bool {
+        self.watched.load(Ordering::Acquire)
+    }
+
+    /// Attaches or detaches a watcher. Either way the next sync compares everything, since
+    /// changes may have gone unreported in between.
+    pub fn set_watched(&self, watched: bool) {
+        self.mark_all_changed();
+        self.watched.store(watched, Ordering::Release);
+    }
+
+    /// Records that files or directories at `paths` were created, modified or removed.
+    pub fn mark_changed(&self, paths: impl IntoIterator<Item = PathBuf>) {
+        let mut pending = self
+            .pending
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if let Pending::Paths(pending) = &mut *pending {
+            pending.extend(paths);
+        }
+    }
+
+    /// Records that anything under the projects directory may have changed.
+    pub fn mark_all_changed(&self) {
+        *self
+            .pending
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Pending::Everything;
+    }
+
+    /// Brings the index up to date with the `.jsonl` files under `root`, passing new and
+    /// modified ones to `scan`. Files `scan` cannot read are left out and tried again on
+    /// the next sync.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be read or written.
+    pub fn sync<F>(&self, root: &Path, scan: F) -> OrchestratorResult<RefreshStats>
+    where
+        F: Fn(&Path) -> Option<FileScan> + Sync,
+    {
+        let mut conn = self
+            .conn
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let pending = if self.is_watched() {
+            std::mem::replace(
+                &mut *self
+                    .pending
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner),
+                Pending::Paths(HashSet::new()),
+            )
+        } else {
+            Pending::Everything
+        };
+        let roots: Vec<PathBuf> = match pending {
+            Pending::Everything => vec![root.to_path_buf()],
+            Pending::Paths(paths) if paths.is_empty() => return Ok(RefreshStats::default()),
+            Pending::Paths(paths) => paths.into_iter().collect(),
+        };
+        let result = self.refresh(&mut conn, &roots, scan);
+        if result.is_err() {
+            self.mark_all_changed();
+        }
+        result
+    }
+
+    /// Compares the index with the `.jsonl` files at or under `roots`.
+    fn refresh<F>(
+        &self,
+        conn: &mut Connection,
+        roots: &[PathBuf],
+        scan: F,
+    ) -> OrchestratorResult<RefreshStats>
+    where
+        F: Fn(&Path) -> Option<FileScan> + Sync,
+    {
+        let on_disk: HashMap<PathBuf, (i64, i64)> = roots
+            .iter()
+            .flat_map(|root| WalkDir::new(root).into_iter())
+            .filter_map(std::result::Result::ok)
+            .filter(|entry| entry.path().extension().and_then(|s| s.to_str()) == Some("jsonl"))
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                if !metadata.is_file() {
+                    return None;
+                }
+                let modified_ns = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |since| {
+                        i64::try_from(since.as_nanos()).unwrap_or(i64::MAX)
+                    });
+                let size = i64::try_from(metadata.len()).unwrap_or(i64::MAX);
+                Some((entry.into_path(), (modified_ns, size)))
+            })
+            .collect();
+
+        let mut known: HashMap<String, (i64, i64)> = HashMap::new();
+        {
+            let mut statement = conn
+                .prepare(
+                    "SELECT path, modified_ns, size FROM files
+                     WHERE path = ?1 OR substr(path, 1, length(?1) + 1) = ?1 || '/'",
+                )
+                .map_err(db_error)?;
+            for root in roots {
+                let rows = statement
+                    .query_map([root.to_string_lossy()], |row| {
+                        Ok((row.get(0)?, (row.get(1)?, row.get(2)?)))
+                    })
+                    .map_err(db_error)?;
+                for row in rows {
+                    let (path, file) = row.map_err(db_error)?;
+                    known.insert(path, file);
+                }
+            }
+        }
+
+        let present: HashSet<String> = on_disk
+            .keys()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        let stale: Vec<(&PathBuf, &(i64, i64))> = on_disk
+            .iter()
+            .filter(|(path, file)| known.get(path.to_string_lossy().as_ref()) != Some(*file))
+            .collect();
+        let removed: Vec<&String> = known
+            .keys()
+            .filter(|path| !present.contains(*path))
+            .collect();
+
+        let scans: Vec<_> = stale
+            .par_iter()
+            .map(|(path, (modified_ns, size))| (*path, *modified_ns, *size, scan(path)))
+            .collect();
+
+        let tx = conn.transaction().map_err(db_error)?;
+        for path in &removed {
+            forget(&tx, path).map_err(db_error)?;
+            self.offsets.forget(Path::new(path.as_str()));
+        }
+        for (path, modified_ns, size, file_scan) in scans {
+            let path = path.to_string_lossy();
+            forget(&tx, &path).map_err(db_error)?;
+            if let Some(file_scan) = file_scan {
+                store(&tx, &path, modified_ns, size, &file_scan).map_err(db_error)?;
+            }
+        }
+        tx.commit().map_err(db_error)?;
+
+        let stats = RefreshStats {
+            rescanned: stale.len(),
+            removed: removed.len(),
+        };
+        if stats != RefreshStats::default() {
+            *self
+                .catalog
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner) = None;
+        }
+        debug!(
+            files = on_disk.len(),
+            rescanned = stats.rescanned,
+            removed = stats.removed,
+            "Synced session index"
+        );
+        Ok(stats)
+    }
+
+    /// Journal named after `session_id` that has entries of it, as of the last sync.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be read.
+    pub fn journal_path(&self, session_id: &str) -> OrchestratorResult<Option<PathBuf>> {
+        let conn = self
+            .conn
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let mut statement = conn
+            .prepare("SELECT DISTINCT path FROM sessions WHERE session_id = ?1 ORDER BY path")
+            .map_err(db_error)?;
+        let rows = statement
+            .query_map([session_id], |row| row.get::<_, String>(0))
+            .map_err(db_error)?;
+        for row in rows {
+            let path = PathBuf::from(row.map_err(db_error)?);
+            if path.file_stem().and_then(|stem| stem.to_str()) == Some(session_id) {
+                return Ok(Some(path));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Indexed sessions that have a working directory, and the first user message of
+    /// every indexed session by ID, for sessions without a summary.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be read.
+    pub fn sessions(&self) -> OrchestratorResult<Catalog> {
+        let conn = self
+            .conn
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let mut catalog = self
+            .catalog
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if let Some(catalog) = &*catalog {
+            return Ok(catalog.clone());
+        }
+
+        let mut merged: HashMap<String, FileSession> = HashMap::new();
+        let mut statement = conn
+            .prepare(
+                "SELECT session_id, cwd, earliest, latest, first_message, first_message_at
+                 FROM sessions ORDER BY path, ordinal",
+            )
+            .map_err(db_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok(FileSession {
+                    session_id: row.get(0)?,
+                    cwd: row.get(1)?,
+                    earliest: row.get(2)?,
+                    latest: row.get(3)?,
+                    first_message: row.get(4)?,
+                    first_message_at: row.get(5)?,
+                })
+            })
+            .map_err(db_error)?;
+        for row in rows {
+            let session = row.map_err(db_error)?;
+            match merged.get_mut(&session.session_id) {
+                Some(existing) => existing.merge(session),
+                None => {
+                    merged.insert(session.session_id.clone(), session);
+                }
+            }
+        }
+
+        // A summary belongs to the session of the entry its leafUuid points at; of several
+        // summaries of one entry, the last one written wins
+        let mut summaries: HashMap<String, String> = HashMap::new();
+        let mut statement = conn
+            .prepare(
+                "SELECT m.session_id, s.summary FROM messages m
+                 JOIN summaries s ON s.leaf_uuid = m.uuid
+                 ORDER BY m.path, m.ordinal, s.rowid DESC",
+            )
+            .map_err(db_error)?;
+        let rows = statement
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))
+            .map_err(db_error)?;
+        for row in rows {
+            let (session_id, summary) = row.map_err(db_error)?;
+            summaries.entry(session_id).or_insert(summary);
+        }
+
+        let mut sessions = Vec::new();
+        let mut fallbacks = HashMap::new();
+        for (session_id, session) in merged {
+            if let Some(message) = session.first_message {
+                fallbacks.insert(session_id.clone(), message);
+            }
+            let Some(cwd) = session.cwd.filter(|cwd| !cwd.is_empty()) else {
+                continue;
+            };
+            sessions.push(SessionInfo {
+                summary: summaries.remove(&session_id),
+                session_id,
+                working_directory: PathBuf::from(cwd),
+                active: false,
+                earliest_message_date: session.earliest,
+                latest_message_date: session.latest,
+                resumed_from: None,
+                pinned: false,
+                owner: None,
+                usage: None,
+                tags: Vec::new(),
+                metadata: BTreeMap::new(),
+            });
+        }
+        *catalog = Some((sessions.clone(), fallbacks.clone()));
+        Ok((sessions, fallbacks))
+    }
+
+    /// Size `path` was indexed at, for tests of the sync logic.
+    #[cfg(test)]
+    fn indexed_size(&self, path: &Path) -> Option<i64> {
+        use rusqlite::OptionalExtension;
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT size FROM files WHERE path = ?1",
+                [path.to_string_lossy()],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap()
+    }
+}
+
+fn forget(conn: &Connection, path: &str) -> rusqlite::Result<()> {
+    for table in ["files", "sessions", "messages", "summaries"] {
+        conn.execute(&format!("DELETE FROM {table} WHERE path = ?1"), [path])?;
+    }
+    Ok(())
+}
+
+fn store(
+    conn: &Connection,
+    path: &str,
+    modified_ns: i64,
+    size: i64,
+    scan: &FileScan,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO files (path, modified_ns, size) VALUES (?1, ?2, ?3)",
+        params![path, modified_ns, size],
+    )?;
+    let mut insert = conn.prepare_cached(
+        "INSERT INTO sessions
+         (path, ordinal, session_id, cwd, earliest, latest, first_message, first_message_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    )?;
+    for (ordinal, session) in scan.sessions.iter().enumerate() {
+        insert.execute(params![
+            path,
+            ordinal,
+            session.session_id,
+            session.cwd,
+            session.earliest,
+            session.latest,
+            session.first_message,
+            session.first_message_at,
+        ])?;
+    }
+    let mut insert = conn.prepare_cached(
+        "INSERT INTO messages (path, ordinal, uuid, session_id) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    for (ordinal, (uuid, session_id)) in scan.messages.iter().enumerate() {
+        insert.execute(params![path, ordinal, uuid, session_id])?;
+    }
+    let mut insert = conn
+        .prepare_cached("INSERT INTO summaries (path, leaf_uuid, summary) VALUES (?1, ?2, ?3)")?;
+    for (leaf_uuid, summary) in &scan.summaries {
+        insert.execute(params![path, leaf_uuid, summary])?;
+    }
+    Ok(())
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn db_error(e: rusqlite::Error) -> OrchestratorError {
+    OrchestratorError::InternalError(format!("Session index failed: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Treats each file as a single session named after it, working in the file's content.
+    fn scan(path: &Path) -> Option<FileScan> {
+        let cwd = std::fs::read_to_string(path).ok()?;
+        let session_id = path.file_stem()?.to_string_lossy().into_owned();
+        Some(FileScan {
+            summaries: Vec::new(),
+            messages: vec![(format!("{session_id}-leaf"), session_id.clone())],
+            sessions: vec![FileSession {
+                session_id,
+                cwd: Some(cwd),
+                earliest: Some("2024-01-01T00:00:00Z".to_string()),
+                latest: Some("2024-01-02T00:00:00Z".to_string()),
+                first_message: Some("hello".to_string()),
+                first_message_at: None,
+            }],
+        })
+    }
+
+    #[test]
+    fn test_sync_is_incremental() {
+        let projects = TempDir::new().unwrap();
+        let state_dir = TempDir::new().unwrap();
+        let a = projects.path().join("a.jsonl");
+        let b = projects.path().join("b.jsonl");
+        std::fs::write(&a, "/work/a").unwrap();
+        std::fs::write(&b, "/work/b").unwrap();
+        std::fs::write(projects.path().join("notes.txt"), "ignored").unwrap();
+
+        let index = SessionIndex::open(Some(state_dir.path()));
+        let stats = index.sync(projects.path(), scan).unwrap();
+        assert_eq!(
+            stats,
+            RefreshStats {
+                rescanned: 2,
+                removed: 0
+            }
+        );
+        assert_eq!(index.sync(projects.path(), scan).unwrap().rescanned, 0);
+
+        std::fs::write(&a, "/work/a-moved").unwrap();
+        std::fs::remove_file(&b).unwrap();
+        let stats = index.sync(projects.path(), scan).unwrap();
+        assert_eq!(
+            stats,
+            RefreshStats {
+                rescanned: 1,
+                removed: 1
+            }
+        );
+        assert_eq!(index.indexed_size(&a), Some(13));
+        assert_eq!(index.indexed_size(&b), None);
+
+        let (sessions, fallbacks) = index.sessions().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(
+            sessions[0].working_directory,
+            PathBuf::from("/work/a-moved")
+        );
+        assert_eq!(fallbacks["a"], "hello");
+
+        // The index outlives the process
+        drop(index);
+        let index = SessionIndex::open(Some(state_dir.path()));
+        assert_eq!(index.sync(projects.path(), scan).unwrap().rescanned, 0);
+    }
+
+    #[test]
+    fn test_sessions_merge_across_files() {
+        let projects = TempDir::new().unwrap();
+        std::fs::write(projects.path().join("one.jsonl"), "").unwrap();
+        std::fs::write(projects.path().join("two.jsonl"), "").unwrap();
+
+        let index = SessionIndex::open(None);
+        index
+            .sync(projects.path(), |path| {
+                let first = path.ends_with("one.jsonl");
+                Some(if first {
+                    FileScan {
+                        summaries: Vec::new(),
+                        messages: vec![("m1".to_string(), "s".to_string())],
+                        sessions: vec![FileSession {
+                            session_id: "s".to_string(),
+                            cwd: None,
+                            earliest: Some("2024-01-02".to_string()),
+                            latest: Some("2024-01-03".to_string()),
+                            first_message: Some("later".to_string()),
+                            first_message_at: Some("2024-01-02".to_string()),
+                        }],
+                    }
+                } else {
+                    // A summary pointing into the other file
+                    FileScan {
+                        summaries: vec![("m1".to_string(), "Refactor".to_string())],
+                        messages: Vec::new(),
+                        sessions: vec![FileSession {
+                            session_id: "s".to_string(),
+                            cwd: Some("/work".to_string()),
+                            earliest: Some("2024-01-01".to_string()),
+                            latest: Some("2024-01-02".to_string()),
+                            first_message: Some("earlier".to_string()),
+                            first_message_at: Some("2024-01-01".to_string()),
+                        }],
+                    }
+                })
+            })
+            .unwrap();
+
+        let (sessions, fallbacks) = index.sessions().unwrap();
+        assert_eq!(sessions.len(), 1);
+        let session = &sessions[0];
+        assert_eq!(session.working_directory, PathBuf::from("/work"));
+        assert_eq!(session.summary.as_deref(), Some("Refactor"));
+        assert_eq!(session.earliest_message_date.as_deref(), Some("2024-01-01"));
+        assert_eq!(session.latest_message_date.as_deref(), Some("2024-01-03"));
+        assert_eq!(fallbacks["s"], "earlier");
+    }
+
+    #[test]
+    fn test_unreadable_files_are_retried() {
+        let projects = TempDir::new().unwrap();
+        std::fs::write(projects.path().join("a.jsonl"), "/work").unwrap();
+        let index = SessionIndex::open(None);
+        assert_eq!(index.sync(projects.path(), |_| None).unwrap().rescanned, 1);
+        assert!(index.sessions().unwrap().0.is_empty());
+        assert_eq!(index.sync(projects.path(), scan).unwrap().rescanned, 1);
+        assert_eq!(index.sessions().unwrap().0.len(), 1);
+    }
+
+    #[test]
+    fn test_watched_sync_only_looks_at_reported_paths() {
+        let projects = TempDir::new().unwrap();
+        let nested = projects.path().join("project");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(projects.path().join("a.jsonl"), "/work/a").unwrap();
+
+        let index = SessionIndex::open(None);
+        index.set_watched(true);
+        assert!(index.is_watched());
+        // Attaching a watcher compares everything once
+        assert_eq!(index.sync(projects.path(), scan).unwrap().rescanned, 1);
+        assert_eq!(
+            index.sync(projects.path(), scan).unwrap(),
+            RefreshStats::default()
+        );
+
+        // Unreported files stay unknown
+        std::fs::write(nested.join("b.jsonl"), "/work/b").unwrap();
+        assert_eq!(index.sync(projects.path(), scan).unwrap().rescanned, 0);
+        assert_eq!(index.journal_path("b").unwrap(), None);
+
+        index.mark_changed([nested.clone()]);
+        assert_eq!(index.sync(projects.path(), scan).unwrap().rescanned, 1);
+        assert_eq!(
+            index.journal_path("b").unwrap(),
+            Some(nested.join("b.jsonl"))
+        );
+        assert_eq!(index.sessions().unwrap().0.len(), 2);
+
+        // A removed directory takes its journals along
+        std::fs::remove_dir_all(&nested).unwrap();
+        index.mark_changed([nested.clone()]);
+        assert_eq!(index.sync(projects.path(), scan).unwrap().removed, 1);
+        assert_eq!(index.sessions().unwrap().0.len(), 1);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::models::is_valid_session_id;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+use utoipa::ToSchema;
+
+/// Entry type of recorded inputs, so they cannot be mistaken for Claude's own entries.
+pub const INPUT_ENTRY_TYPE: &str = "orchestrator_input";
+
+/// A client message as the orchestrator received it, written before it reaches Claude.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RecordedInput {
+    #[serde(rename = "type")]
+    pub entry_type: String,
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub sender_client_id: String,
+    /// The message as sent: parsed when it is JSON, a string otherwise
+    pub content: serde_json::Value,
+}
+
+impl RecordedInput {
+    #[must_use]
+    pub fn new(
+        session_id: String,
+        sender_client_id: String,
+        timestamp: chrono::DateTime<chrono::Utc>,
+        content: &str,
+    ) -> Self {
+        Self {
+            entry_type: INPUT_ENTRY_TYPE.to_string(),
+            session_id,
+            timestamp,
+            sender_client_id,
+            content: serde_json::from_str(content)
+                .unwrap_or_else(|_| serde_json::Value::String(content.to_string())),
+        }
+    }
+}
+
+/// Sidecar transcript of a session's inputs, next to its other state files. Only for IDs
+/// [`is_valid_session_id`] accepts, which keeps the path inside `state_dir`.
+#[must_use]
+pub fn sidecar_path(state_dir: &Path, session_id: &str) -> PathBuf {
+    state_dir.join(format!("{session_id}.inputs.jsonl"))
+}
+
+/// Appends `input` to the session's sidecar transcript. Failures are logged, the message
+/// still goes to Claude.
+pub async fn append(state_dir: &Path, input: &RecordedInput) {
+    if !is_valid_session_id(&input.session_id) {
+        warn!(session_id = %input.session_id, "Session ID cannot name a file, not recording input");
+        return;
+    }
+    let path = sidecar_path(state_dir, &input.session_id);
+    let result = async {
+        let mut line = serde_json::to_vec(input)?;
+        line.push(b'\n');
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+        // One write per entry keeps concurrent appends from interleaving
+        file.write_all(&line).await?;
+        file.flush().await
+    }
+    .await;
+    if let Err(e) = result {
+        warn!(path = %path.display(), error = %e, "Failed to record client input");
+    }
+}
+
+/// Reads a session's recorded inputs; lines torn by a crash are skipped.
+pub async fn read(state_dir: &Path, session_id: &str) -> Vec<RecordedInput> {
+    if !is_valid_session_id(session_id) {
+        return Vec::new();
+    }
+    let path = sidecar_path(state_dir, session_id);
+    match tokio::fs::read_to_string(&path).await {
+        Ok(content) => content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "Failed to read recorded inputs");
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_append_and_read() {
+        let state_dir = TempDir::new().unwrap();
+        let now = chrono::Utc::now();
+        let json = RecordedInput::new(
+            "s1".to_string(),
+            "c1".to_string(),
+            now,
+            r#"{"role": "user", "content": "hi"}"#,
+        );
+        let text = RecordedInput::new("s1".to_string(), "c2".to_string(), now, "plain text");
+        append(state_dir.path(), &json).await;
+        append(state_dir.path(), &text).await;
+
+        // IDs that are not file names would write outside the state directory
+        let nested = state_dir.path().join("nested");
+        std::fs::create_dir(&nested).unwrap();
+        let escaping = RecordedInput::new("../escaped".to_string(), "c1".to_string(), now, "hi");
+        append(&nested, &escaping).await;
+        assert!(!state_dir.path().join("escaped.inputs.jsonl").exists());
+
+        // A line torn by a crash does not hide the others
+        let path = sidecar_path(state_dir.path(), "s1");
+        let mut content = std::fs::read_to_string(&path).unwrap();
+        content.push_str(r#"{"type": "orchestrator_in"#);
+        std::fs::write(&path, content).unwrap();
+
+        let recorded = read(state_dir.path(), "s1").await;
+        assert_eq!(recorded, vec![json, text]);
+        assert_eq!(recorded[0].content["content"], "hi");
+        assert_eq!(recorded[1].content, "plain text");
+        assert!(read(state_dir.path(), "s2").await.is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Where each line of a journal starts, as far as it has been read.
+#[derive(Debug, Default)]
+struct LineOffsets {
+    /// Byte offset of every complete line
+    starts: Vec<u64>,
+    /// Byte offset just past the last complete line, where the next read begins
+    end: u64,
+    /// Line of every entry with a `uuid`
+    uuids: HashMap<String, usize>,
+}
+
+/// Lines a journal gained since some point, read by [`JournalOffsets::lines_after`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppendedLines {
+    /// The complete lines after the requested one, without their newline
+    pub lines: Vec<String>,
+    /// Complete lines in the journal; where the next read should start
+    pub total_lines: usize,
+}
+
+#[derive(Deserialize)]
+struct EntryId {
+    uuid: Option<String>,
+}
+
+/// Remembers where the lines of journals start, so reading what was appended since a
+/// line reads only those bytes. Journals are only ever appended to; one that shrank is
+/// read again from the start. A line still being written (without its newline yet) is
+/// left for the next read.
+#[derive(Debug, Default)]
+pub struct JournalOffsets {
+    journals: Mutex<HashMap<PathBuf, LineOffsets>>,
+}
+
+impl JournalOffsets {
+    /// The complete lines of the journal at `path` after its first `after_line` lines.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the journal cannot be read.
+    pub fn lines_after(&self, path: &Path, after_line: usize) -> std::io::Result<AppendedLines> {
+        let mut journals = self
+            .journals
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let offsets = journals.entry(path.to_path_buf()).or_default();
+        let mut file = File::open(path)?;
+        Self::catch_up(offsets, &mut file)?;
+
+        let total_lines = offsets.starts.len();
+        let Some(&start) = offsets.starts.get(after_line) else {
+            return Ok(AppendedLines {
+                lines: Vec::new(),
+                total_lines,
+            });
+        };
+        let mut bytes = Vec::new();
+        file.seek(SeekFrom::Start(start))?;
+        file.take(offsets.end - start).read_to_end(&mut bytes)?;
+        let lines = bytes
+            .split(|byte| *byte == b'\n')
+            .take(total_lines - after_line)
+            .map(|line| String::from_utf8_lossy(line).into_owned())
+            .collect();
+        Ok(AppendedLines { lines, total_lines })
+    }
+
+    /// The line of the entry of the journal at `path` with `uuid`, as of the last read.
+    #[must_use]
+    pub fn line_of(&self, path: &Path, uuid: &str) -> Option<usize> {
+        self.journals
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .get(path)
+            .and_then(|offsets| offsets.uuids.get(uuid).copied())
+    }
+
+    /// Drops what is known about a journal that was removed.
+    pub fn forget(&self, path: &Path) {
+        self.journals
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(path);
+    }
+
+    /// Records the lines appended to `file` since `offsets` were last brought up to date.
+    fn catch_up(offsets: &mut LineOffsets, file: &mut File) -> std::io::Result<()> {
+        let len = file.metadata()?.len();
+        if len < offsets.end {
+            *offsets = LineOffsets::default();
+        }
+        if len == offsets.end {
+            return Ok(());
+        }
+
+        let mut appended = Vec::new();
+        file.seek(SeekFrom::Start(offsets.end))?;
+        file.take(len - offsets.end).read_to_end(&mut appended)?;
+        let mut line_start = 0;
+        for (i, byte) in appended.iter().enumerate() {
+            if *byte != b'\n' {
+                continue;
+            }
+            let line = &appended[line_start..i];
+            if let Ok(EntryId { uuid: Some(uuid) }) = serde_json::from_slice(line) {
+                offsets.uuids.insert(uuid, offsets.starts.len());
+            }
+            offsets.starts.push(offsets.end + line_start as u64);
+            line_start = i + 1;
+        }
+        offsets.end += line_start as u64;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_lines_after_reads_only_appended_lines() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("session.jsonl");
+        std::fs::write(&path, "{\"uuid\": \"a\"}\n{\"uuid\": \"b\"}\n{\"partial").unwrap();
+
+        let offsets = JournalOffsets::default();
+        let read = offsets.lines_after(&path, 0).unwrap();
+        assert_eq!(read.lines, vec!["{\"uuid\": \"a\"}", "{\"uuid\": \"b\"}"]);
+        assert_eq!(read.total_lines, 2);
+        assert_eq!(offsets.line_of(&path, "b"), Some(1));
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"\": 1, \"uuid\": \"c\"}\n{\"uuid\": \"d\"}\n")
+            .unwrap();
+        let read = offsets.lines_after(&path, 2).unwrap();
+        assert_eq!(
+            read.lines,
+            vec!["{\"partial\": 1, \"uuid\": \"c\"}", "{\"uuid\": \"d\"}"]
+        );
+        assert_eq!(read.total_lines, 4);
+        assert_eq!(offsets.line_of(&path, "d"), Some(3));
+
+        // Nothing new, or a position past the end
+        assert!(offsets.lines_after(&path, 4).unwrap().lines.is_empty());
+        assert_eq!(offsets.lines_after(&path, 9).unwrap().total_lines, 4);
+    }
+
+    #[test]
+    fn test_rewritten_journal_is_read_again() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("session.jsonl");
+        std::fs::write(&path, "{\"n\": 1}\n{\"n\": 2}\n").unwrap();
+        let offsets = JournalOffsets::default();
+        assert_eq!(offsets.lines_after(&path, 0).unwrap().total_lines, 2);
+
+        std::fs::write(&path, "{\"n\": 3}\n").unwrap();
+        let read = offsets.lines_after(&path, 0).unwrap();
+        assert_eq!(read.lines, vec!["{\"n\": 3}"]);
+        assert_eq!(read.total_lines, 1);
+    }
+}
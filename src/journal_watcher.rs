@@ -0,0 +1,148 @@
+use crate::discovery::SessionDiscovery;
+use crate::index::SessionIndex;
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Pause after a change before the index is synced, so a burst of appends to a journal
+/// costs one rescan.
+const SETTLE_DELAY: Duration = Duration::from_millis(250);
+
+/// Reports changes under the projects directory to the session index and syncs it in the
+/// background, so listing neither walks the directory nor usually reads a journal itself.
+/// Stops when dropped.
+pub struct JournalWatcher {
+    _watcher: RecommendedWatcher,
+    index: Arc<SessionIndex>,
+    task: JoinHandle<()>,
+}
+
+impl JournalWatcher {
+    /// Starts watching `root` for `index`, which is synced right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `root` cannot be watched.
+    pub fn start(index: Arc<SessionIndex>, root: PathBuf) -> Result<Self> {
+        let changed = Arc::new(Notify::new());
+        let mut watcher = {
+            let index = index.clone();
+            let changed = changed.clone();
+            notify::recommended_watcher(move |event: notify::Result<Event>| {
+                match event {
+                    // Reading a journal changes nothing
+                    Ok(event) if matches!(event.kind, EventKind::Access(_)) => return,
+                    Ok(event) if event.need_rescan() => index.mark_all_changed(),
+                    Ok(event) => index.mark_changed(event.paths),
+                    Err(e) => {
+                        warn!(error = %e, "Journal watcher lost track of changes");
+                        index.mark_all_changed();
+                    }
+                }
+                changed.notify_one();
+            })
+        }
+        .context("Failed to create journal watcher")?;
+        watcher
+            .watch(&root, RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch {}", root.display()))?;
+        index.set_watched(true);
+        changed.notify_one();
+        info!(path = %root.display(), "Watching projects directory for journal changes");
+
+        let task = tokio::spawn({
+            let index = index.clone();
+            async move {
+                loop {
+                    changed.notified().await;
+                    tokio::time::sleep(SETTLE_DELAY).await;
+                    let index = index.clone();
+                    let root = root.clone();
+                    let synced = tokio::task::spawn_blocking(move || {
+                        index.sync(&root, SessionDiscovery::index_jsonl_file)
+                    })
+                    .await;
+                    match synced {
+                        Ok(Ok(stats)) => debug!(
+                            rescanned = stats.rescanned,
+                            removed = stats.removed,
+                            "Synced session index after journal changes"
+                        ),
+                        Ok(Err(e)) => warn!(error = %e, "Failed to sync session index"),
+                        Err(e) => warn!(error = %e, "Session index sync panicked"),
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            _watcher: watcher,
+            index,
+            task,
+        })
+    }
+}
+
+impl Drop for JournalWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+        self.index.set_watched(false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn wait_for_sessions(index: &SessionIndex, count: usize) {
+        for _ in 0..100 {
+            if index.sessions().unwrap().0.len() == count {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("index never listed {count} sessions");
+    }
+
+    #[tokio::test]
+    async fn test_watcher_keeps_index_current() {
+        let projects = TempDir::new().unwrap();
+        let project = projects.path().join("-work");
+        std::fs::create_dir_all(&project).unwrap();
+        let journal = project.join("s1.jsonl");
+        std::fs::write(
+            &journal,
+            r#"{"type":"user","sessionId":"s1","cwd":"/work","uuid":"u1","message":{"content":"hi"}}"#,
+        )
+        .unwrap();
+
+        let index = Arc::new(SessionIndex::open(None));
+        let watcher = JournalWatcher::start(index.clone(), projects.path().to_path_buf()).unwrap();
+        assert!(index.is_watched());
+        // Synced in the background, without anybody asking
+        wait_for_sessions(&index, 1).await;
+
+        std::fs::write(
+            project.join("s2.jsonl"),
+            r#"{"type":"user","sessionId":"s2","cwd":"/work","uuid":"u2","message":{"content":"hey"}}"#,
+        )
+        .unwrap();
+        wait_for_sessions(&index, 2).await;
+        assert_eq!(
+            index.journal_path("s2").unwrap(),
+            Some(project.join("s2.jsonl"))
+        );
+
+        std::fs::remove_file(&journal).unwrap();
+        wait_for_sessions(&index, 1).await;
+
+        drop(watcher);
+        assert!(!index.is_watched());
+    }
+}
@@ -1,7 +1,60 @@
+pub mod anchors;
 pub mod api;
+pub mod approval_audit;
+pub mod approval_priority;
+pub mod approval_risk;
+pub mod approval_rules;
+pub mod approval_sync;
+pub mod approval_webhook;
+pub mod claude_cli;
 pub mod claude_process;
+pub mod cli;
 pub mod config;
+pub mod config_file;
+pub mod config_watcher;
+pub mod debug_capture;
+pub mod demo;
 pub mod discovery;
 pub mod error;
+pub mod event_export;
+#[cfg(feature = "testing")]
+pub mod fault_injection;
+pub mod file_history;
+pub mod index;
+pub mod input_record;
+pub mod journal_offsets;
+pub mod journal_watcher;
+pub mod lineage;
+pub mod mdns;
 pub mod models;
+pub mod notifications;
+pub mod preferences;
+pub mod process_stats;
+pub mod push_notify;
+pub mod resource_limits;
+pub mod scratch;
+pub mod server_events;
+pub mod session_backend;
+pub mod session_context;
+pub mod session_fork;
+pub mod session_import;
 pub mod session_manager;
+pub mod session_metadata;
+pub mod session_owners;
+pub mod slack;
+pub mod slo;
+pub mod state_migrations;
+pub mod telemetry;
+pub mod tls;
+pub mod tool_catalog;
+pub mod trace_export;
+pub mod transcript_export;
+pub mod transcript_hook;
+pub mod transcript_media;
+pub mod transcript_stream;
+pub mod uploads;
+pub mod usage;
+pub mod web_push;
+pub mod wire_log;
+pub mod working_dir_sandbox;
+pub mod write_pacing;
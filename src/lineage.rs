@@ -0,0 +1,110 @@
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+const LINEAGE_FILE: &str = "lineage.json";
+
+/// Records which session a resumed session was started from, since `--resume`
+/// hands back a new session ID and leaves the old transcript on disk.
+#[derive(Debug, Default)]
+pub struct ResumeLineage {
+    /// Resumed session ID -> the session it was resumed from
+    parents: DashMap<String, String>,
+    /// Where the mapping is persisted, if a session state directory is configured
+    path: Option<PathBuf>,
+}
+
+impl ResumeLineage {
+    /// Loads the lineage persisted in `state_dir`, or starts empty.
+    #[must_use]
+    pub fn load(state_dir: Option<&Path>) -> Self {
+        let path = state_dir.map(|dir| dir.join(LINEAGE_FILE));
+        let mut parents = DashMap::new();
+
+        if let Some(path) = &path {
+            match std::fs::read(path) {
+                Ok(bytes) => match serde_json::from_slice::<HashMap<String, String>>(&bytes) {
+                    Ok(map) => parents.extend(map),
+                    Err(e) => warn!(
+                        path = %path.display(),
+                        error = %e,
+                        "Ignoring unreadable resume lineage file"
+                    ),
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!(
+                    path = %path.display(),
+                    error = %e,
+                    "Failed to read resume lineage file"
+                ),
+            }
+        }
+
+        Self { parents, path }
+    }
+
+    /// Remembers that `resumed_id` continues `previous_id`.
+    pub fn record(&self, previous_id: &str, resumed_id: &str) {
+        if previous_id == resumed_id {
+            return;
+        }
+        self.parents
+            .insert(resumed_id.to_string(), previous_id.to_string());
+        debug!(previous_id = %previous_id, resumed_id = %resumed_id, "Recorded resume lineage");
+        self.persist();
+    }
+
+    /// The session `session_id` was resumed from, if any.
+    #[must_use]
+    pub fn parent_of(&self, session_id: &str) -> Option<String> {
+        self.parents.get(session_id).map(|parent| parent.clone())
+    }
+
+    fn persist(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let snapshot: HashMap<String, String> = self
+            .parents
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        let tmp_path = path.with_extension("json.tmp");
+        let result = serde_json::to_vec(&snapshot)
+            .map_err(std::io::Error::from)
+            .and_then(|bytes| std::fs::write(&tmp_path, bytes))
+            .and_then(|()| std::fs::rename(&tmp_path, path));
+        if let Err(e) = result {
+            warn!(path = %path.display(), error = %e, "Failed to persist resume lineage");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_lineage_round_trip() {
+        let state_dir = TempDir::new().unwrap();
+
+        let lineage = ResumeLineage::load(Some(state_dir.path()));
+        lineage.record("a", "b");
+        lineage.record("b", "c");
+        lineage.record("c", "c");
+
+        let reloaded = ResumeLineage::load(Some(state_dir.path()));
+        assert_eq!(reloaded.parent_of("c").as_deref(), Some("b"));
+        assert_eq!(reloaded.parent_of("b").as_deref(), Some("a"));
+        assert_eq!(reloaded.parent_of("a"), None);
+    }
+
+    #[test]
+    fn test_lineage_without_state_dir() {
+        let lineage = ResumeLineage::load(None);
+        lineage.record("a", "b");
+        assert_eq!(lineage.parent_of("b").as_deref(), Some("a"));
+    }
+}
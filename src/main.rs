@@ -1,41 +1,171 @@
+mod anchors;
 mod api;
+mod approval_audit;
+mod approval_priority;
+mod approval_risk;
+mod approval_rules;
+mod approval_sync;
+mod approval_webhook;
+mod claude_cli;
 mod claude_process;
+mod cli;
 mod config;
+mod config_file;
+mod config_watcher;
+mod debug_capture;
+mod demo;
 mod discovery;
 mod error;
+mod event_export;
+#[cfg(feature = "testing")]
+mod fault_injection;
+mod file_history;
+mod index;
+mod input_record;
+mod journal_offsets;
+mod journal_watcher;
+mod lineage;
+mod mdns;
 mod models;
+mod notifications;
+mod preferences;
+mod process_stats;
+mod push_notify;
+mod resource_limits;
+mod scratch;
+mod server_events;
+mod session_backend;
+mod session_context;
+mod session_fork;
+mod session_import;
 mod session_manager;
+mod session_metadata;
+mod session_owners;
+mod slack;
+mod slo;
+mod state_migrations;
+mod telemetry;
+mod tls;
+mod tool_catalog;
+mod trace_export;
+mod transcript_export;
+mod transcript_hook;
+mod transcript_media;
+mod transcript_stream;
+mod uploads;
+mod usage;
+mod web_push;
+mod wire_log;
+mod working_dir_sandbox;
+mod write_pacing;
 
-use crate::api::handlers::{create_session, get_session, list_sessions, AppState};
+use crate::api::body_limit::BodyLimit;
+use crate::api::handlers::{
+    append_upload, create_anchor, create_session, create_upload, delete_anchor,
+    delete_approval_rule, delete_session, export_session, fork_session, get_approval_history,
+    get_approval_rules, get_metrics, get_preferences, get_project_tools, get_session,
+    get_session_approvals, get_session_clients, get_session_content_since, get_session_context,
+    get_session_files, get_session_media, get_session_queue, get_session_scratch,
+    get_session_scratch_file, get_session_watches, get_session_wirelog, get_slo_report,
+    get_telemetry_preview, get_upload, get_usage, get_web_push_key, import_sessions,
+    list_push_subscriptions, list_sessions, pin_session, resolve_held_messages, set_preferences,
+    set_session_debug, submit_approval, submit_approvals, subscribe_push, unpin_session,
+    unsubscribe_push, unwatch_session, update_session_metadata, watch_session, AppState,
+};
+use crate::api::openai::chat_completions;
+use crate::api::sse::{send_message, session_events};
 use crate::api::static_files::{serve_index, serve_static};
-use crate::api::websocket::{approval_websocket_handler, websocket_handler};
-use crate::config::Config;
+use crate::api::websocket::{
+    approval_websocket_handler, events_websocket_handler, websocket_handler,
+};
+use crate::cli::{Cli, Command};
+use crate::config::{Config, OtlpSettings};
+use crate::debug_capture::{SessionDebugFilter, SessionTagLayer};
 use crate::session_manager::SessionManager;
+use anyhow::Context;
 use axum::{
-    routing::{get, post},
+    routing::{delete, get, patch, post, put},
     Router,
 };
+use clap::Parser;
 use std::sync::Arc;
-use tower_http::cors::CorsLayer;
-use tracing::{error, info};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::{error, info, warn};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{filter::FilterExt, Layer};
 
 #[tokio::main]
+#[allow(clippy::too_many_lines)]
 async fn main() -> anyhow::Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "chef_de_vibe=debug,info".into()),
-        )
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // The demo's stand-in for the Claude CLI; its stdout carries the protocol, so it must
+    // not log there
+    if args.first().map(String::as_str) == Some(demo::BACKEND_COMMAND) {
+        return demo::run_backend(&args[1..]);
+    }
+
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Serve);
+    if command == Command::Version {
+        println!("chef-de-vibe {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+
+    // Command line options and the config file, on top of the environment
+    let settings = cli.settings.settings()?;
+
+    // Initialize tracing; sessions with debug capture on are logged at every level, and
+    // spans go to an OTLP collector if one is configured
+    let (otlp_layer, span_export) = match OtlpSettings::from_settings(&settings)? {
+        Some(otlp) => {
+            let (layer, export) = trace_export::layer(&otlp)?;
+            (Some(layer), Some(export))
+        }
+        None => (None, None),
+    };
+    let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| "chef_de_vibe=debug,info".into());
+    tracing_subscriber::registry()
+        .with(SessionTagLayer)
+        .with(tracing_subscriber::fmt::layer().with_filter(env_filter.or(SessionDebugFilter)))
+        .with(otlp_layer)
         .init();
 
+    // Only report what starting up would do to the state directory
+    if cli.check_migrations {
+        let state_dir = settings
+            .var_os("SESSION_STATE_DIR")
+            .map(std::path::PathBuf::from);
+        println!("{}", state_migrations::check(state_dir.as_deref())?);
+        return Ok(());
+    }
+
+    // Example sessions and a fake Claude, to try everything out without the real CLI
+    let demo = if command == Command::Demo {
+        Some(demo::Demo::prepare().context("Failed to prepare the demo")?)
+    } else {
+        None
+    };
+
     // Load configuration
-    let config = Config::from_env()?;
+    let config = Config::from_settings(&settings)?;
+    if command == Command::CheckConfig {
+        return cli::check_config(&config).await;
+    }
     info!("Starting Chef de Vibe Service");
     info!(claude_binary = %config.claude_binary_path.display(), "Claude binary path");
     info!(projects_dir = %config.claude_projects_dir.display(), "Projects directory");
     info!(listen_address = %config.http_listen_address, "Listen address");
 
+    // Index the frontend up front so requests never have to discover files
+    api::static_files::init(config.frontend_dir.as_deref())
+        .context("Failed to index frontend directory")?;
+
+    // Bring persisted state up to date before anything reads it
+    state_migrations::migrate(config.session_state_dir.as_deref())
+        .context("Failed to migrate SESSION_STATE_DIR")?;
+
     // Create session manager
     let session_manager = Arc::new(SessionManager::new(config.clone()));
 
@@ -49,26 +179,276 @@ async fn main() -> anyhow::Result<()> {
     let app = Router::new()
         // API routes
         .route("/api/v1/sessions", get(list_sessions))
-        .route("/api/v1/sessions", post(create_session))
-        .route("/api/v1/sessions/:id", get(get_session))
+        .route(
+            "/api/v1/sessions",
+            BodyLimit::create_session(&config.body_limits).apply(post(create_session)),
+        )
+        .route(
+            "/api/v1/sessions/import",
+            BodyLimit::import(&config.body_limits).apply(post(import_sessions)),
+        )
+        .route(
+            "/api/v1/sessions/:id",
+            // Transcripts run to megabytes of JSON and are refetched often
+            get(get_session)
+                .route_layer(axum::middleware::from_fn(api::caching::conditional_get))
+                .route_layer(api::caching::compression())
+                .delete(delete_session),
+        )
+        .route("/api/v1/uploads", post(create_upload))
+        .route("/api/v1/uploads/:id", get(get_upload).put(append_upload))
+        .route("/api/v1/sessions/:id/files", get(get_session_files))
+        .route("/api/v1/sessions/:id/export", get(export_session))
+        .route(
+            "/api/v1/sessions/:id/media/:media_id",
+            get(get_session_media),
+        )
+        .route("/api/v1/sessions/:id/scratch", get(get_session_scratch))
+        .route(
+            "/api/v1/sessions/:id/scratch/*path",
+            get(get_session_scratch_file),
+        )
+        .route(
+            "/api/v1/sessions/:id/content",
+            get(get_session_content_since),
+        )
+        .route("/api/v1/sessions/:id/context", get(get_session_context))
+        .route("/api/v1/sessions/:id/debug", put(set_session_debug))
+        .route("/api/v1/sessions/:id/wirelog", get(get_session_wirelog))
+        .route("/api/v1/sessions/:id/approvals", get(get_session_approvals))
+        .route(
+            "/api/v1/sessions/:id/approvals",
+            BodyLimit::message(&config.body_limits).apply(post(submit_approvals)),
+        )
+        .route(
+            "/api/v1/sessions/:id/approvals/:approval_id",
+            BodyLimit::message(&config.body_limits).apply(post(submit_approval)),
+        )
+        .route(
+            "/api/v1/sessions/:id/approval_rules",
+            get(get_approval_rules),
+        )
+        .route(
+            "/api/v1/sessions/:id/approval_rules/:rule_id",
+            delete(delete_approval_rule),
+        )
+        .route("/api/v1/sessions/:id/queue", get(get_session_queue))
+        .route("/api/v1/sessions/:id/clients", get(get_session_clients))
+        .route(
+            "/api/v1/sessions/:id/fork",
+            BodyLimit::create_session(&config.body_limits).apply(post(fork_session)),
+        )
+        .route("/api/v1/sessions/:id/anchors", post(create_anchor))
+        .route("/api/v1/sessions/:id/anchors/:name", delete(delete_anchor))
+        .route(
+            "/api/v1/sessions/:id/metadata",
+            patch(update_session_metadata),
+        )
+        .route(
+            "/api/v1/sessions/:id/pin",
+            put(pin_session).delete(unpin_session),
+        )
+        .route(
+            "/api/v1/sessions/:id/watch",
+            get(get_session_watches)
+                .put(watch_session)
+                .delete(unwatch_session),
+        )
+        .route(
+            "/api/v1/sessions/:id/queue/:client_id",
+            post(resolve_held_messages),
+        )
         .route("/api/v1/sessions/:id/claude_ws", get(websocket_handler))
+        .route("/api/v1/sessions/:id/events", get(session_events))
+        .route(
+            "/api/v1/sessions/:id/messages",
+            BodyLimit::message(&config.body_limits).apply(post(send_message)),
+        )
         .route(
             "/api/v1/sessions/:id/claude_approvals_ws",
             get(approval_websocket_handler),
         )
+        .route(
+            "/api/v1/preferences",
+            get(get_preferences).put(set_preferences),
+        )
+        .route("/api/v1/push/vapid_public_key", get(get_web_push_key))
+        .route(
+            "/api/v1/push/subscriptions",
+            get(list_push_subscriptions)
+                .post(subscribe_push)
+                .delete(unsubscribe_push),
+        )
+        .route("/api/v1/events_ws", get(events_websocket_handler))
+        .route("/api/v1/projects/:id/tools", get(get_project_tools))
+        .route("/api/v1/approvals/history", get(get_approval_history))
+        .route("/api/v1/telemetry", get(get_telemetry_preview))
+        .route("/api/v1/usage", get(get_usage))
+        .route("/metrics", get(get_metrics))
+        .route("/api/v1/admin/slo", get(get_slo_report))
+        .route("/api/v1/admin/status", get(api::admin::get_admin_status))
+        .route("/api/v1/admin/draining", put(api::admin::set_draining))
+        .route("/api/v1/admin/rescan", post(api::admin::rescan_sessions))
+        .route(
+            "/api/v1/admin/sessions/:id/kill",
+            post(api::admin::kill_session),
+        )
+        // OpenAI-compatible routes
+        .route(
+            "/v1/chat/completions",
+            BodyLimit::message(&config.body_limits).apply(post(chat_completions)),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(
+            session_manager.telemetry().clone(),
+            telemetry::count_requests,
+        ));
+    // Fault injection for tests, never part of a regular build
+    #[cfg(feature = "testing")]
+    let app = app.route(
+        "/api/v1/admin/sessions/:id/faults",
+        get(api::faults::get_faults).post(api::faults::inject_faults),
+    );
+    let app = app.route_layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        api::auth::require_session_access,
+    ));
+    let app = app.route_layer(axum::middleware::from_fn(api::problem::problem_details));
+    // Everything above is API; the frontend below is served without a token
+    let app = if config.api_keys.is_empty() {
+        app
+    } else {
+        info!(
+            per_user = config.api_keys.has_users(),
+            "API token authentication enabled"
+        );
+        app.layer(axum::middleware::from_fn_with_state(
+            config.api_keys.clone(),
+            api::auth::require_token,
+        ))
+    };
+    // Slack signs its callbacks instead of presenting a token
+    let app = if config.slack.is_some() {
+        app.route(
+            api::slack::INTERACTIONS_PATH,
+            BodyLimit::message(&config.body_limits)
+                .apply(post(api::slack::slack_interactions))
+                .route_layer(axum::middleware::from_fn(api::problem::problem_details)),
+        )
+    } else {
+        app
+    };
+    // The specification describes the API to client generators without a token
+    let app = app.route(api::openapi::SPEC_PATH, get(api::openapi::get_openapi_spec));
+    let app = if config.swagger_ui {
+        info!(path = api::openapi::SWAGGER_UI_PATH, "Serving Swagger UI");
+        app.merge(api::openapi::swagger_ui())
+    } else {
+        app
+    };
+    let app = app
         // Static file routes
-        .route("/", get(serve_index))
-        .route("/*path", get(serve_static))
-        .layer(CorsLayer::permissive())
+        .route("/", get(serve_index).layer(api::caching::compression()))
+        .route(
+            "/*path",
+            get(serve_static).layer(api::caching::compression()),
+        )
+        .layer(
+            CorsLayer::permissive().allow_origin(AllowOrigin::predicate({
+                // CORS_ALLOWED_ORIGINS may change with the config file
+                let session_manager = session_manager.clone();
+                move |origin, _| {
+                    origin
+                        .to_str()
+                        .is_ok_and(|origin| session_manager.config().allows_origin(origin))
+                }
+            })),
+        )
+        .layer(axum::middleware::from_fn(api::access_log::log_requests))
         .with_state(state);
 
+    // Only reports anything if TELEMETRY_ENDPOINT is set
+    let _telemetry = session_manager.telemetry().start_reporting();
+
+    // Only answers approvals decided on other instances if APPROVAL_SYNC_DIR is set
+    let _approval_sync = session_manager.start_approval_sync();
+
+    // Applies the settings that may change while running when the config file does
+    let _config_watcher = match &cli.settings.config {
+        Some(path) => match config_watcher::ConfigWatcher::start(
+            path.clone(),
+            settings.clone(),
+            session_manager.clone(),
+        ) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                warn!(error = %e, "Failed to watch config file, changes need a restart");
+                None
+            }
+        },
+        None => None,
+    };
+
+    // Keeps the session index current so listing does not walk the projects directory
+    let _journal_watcher = match journal_watcher::JournalWatcher::start(
+        session_manager.session_index().clone(),
+        config.claude_projects_dir.clone(),
+    ) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            warn!(error = %e, "Failed to watch projects directory, listing will scan it instead");
+            None
+        }
+    };
+
+    // Load the certificate before binding, so a bad one fails startup
+    let rustls_config = match &config.tls {
+        Some(settings) => {
+            let rustls_config = tls::load(settings).await?;
+            tls::reload_on_hangup(settings.clone(), rustls_config.clone())?;
+            Some(rustls_config)
+        }
+        None => None,
+    };
+    let scheme = if rustls_config.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+
     // Start server
     let listener = tokio::net::TcpListener::bind(&config.http_listen_address).await?;
-    info!(address = %config.http_listen_address, "Server listening");
+    info!(address = %config.http_listen_address, scheme, "Server listening");
+    if demo.is_some() {
+        let mut addr = listener.local_addr()?;
+        if addr.ip().is_unspecified() {
+            addr.set_ip(std::net::Ipv4Addr::LOCALHOST.into());
+        }
+        demo::open_browser(&format!("{scheme}://{addr}"));
+    }
+
+    // Advertise on the local network if requested; stays registered until shutdown
+    let _mdns = match &config.mdns_instance_name {
+        Some(instance_name) => {
+            let addr = listener.local_addr()?;
+            if addr.ip().is_loopback() {
+                warn!(address = %addr, "mDNS advertisement enabled but server only listens on loopback");
+            }
+            match mdns::MdnsAdvertisement::start(instance_name, addr) {
+                Ok(advertisement) => Some(advertisement),
+                Err(e) => {
+                    error!(error = %e, "Failed to advertise service via mDNS, continuing without it");
+                    None
+                }
+            }
+        }
+        None => None,
+    };
     info!("To change the listen address, set the HTTP_LISTEN_ADDRESS environment variable (e.g., HTTP_LISTEN_ADDRESS=0.0.0.0:8080)");
 
     // Setup graceful shutdown with double Ctrl+C handling
-    let shutdown_signal = async {
+    let draining_manager = session_manager.clone();
+    let drain_timeout = config.shutdown_timeout;
+    let shutdown_signal = async move {
         // First Ctrl+C - graceful shutdown
         tokio::signal::ctrl_c()
             .await
@@ -84,17 +464,33 @@ async fn main() -> anyhow::Result<()> {
             error!("Received second shutdown signal, forcing immediate exit!");
             std::process::exit(1);
         });
+
+        // Keep serving running conversations and their approvals, but nothing new
+        draining_manager.begin_shutdown();
+        draining_manager.drain(drain_timeout).await;
     };
 
     // Run server with graceful shutdown
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal)
-        .await?;
+    match rustls_config {
+        Some(rustls_config) => tls::serve(listener, app, rustls_config, shutdown_signal).await?,
+        None => {
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown_signal)
+                .await?;
+        }
+    }
 
     // Shutdown session manager
     info!("Shutting down session manager...");
     session_manager.shutdown().await;
+    if let Some(demo) = demo {
+        demo.cleanup();
+    }
     info!("Graceful shutdown completed successfully");
+    if let Some(span_export) = span_export {
+        // Flushing blocks until the collector took the remaining spans
+        tokio::task::spawn_blocking(move || span_export.shutdown()).await?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use std::net::SocketAddr;
+use tracing::{info, warn};
+
+/// DNS-SD service type the frontends browse for.
+pub const SERVICE_TYPE: &str = "_chef-de-vibe._tcp.local.";
+
+/// Keeps the service advertised on the local network until dropped.
+pub struct MdnsAdvertisement {
+    daemon: ServiceDaemon,
+    fullname: String,
+}
+
+impl MdnsAdvertisement {
+    /// Starts advertising `instance_name` on the address the server is bound to.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mDNS daemon cannot be started or the service cannot be registered.
+    pub fn start(instance_name: &str, addr: SocketAddr) -> Result<Self> {
+        let host_name = nix::unistd::gethostname()
+            .ok()
+            .and_then(|name| name.into_string().ok())
+            .unwrap_or_else(|| "chef-de-vibe".to_string());
+
+        let service = service_info(instance_name, &host_name, addr)?;
+        let fullname = service.get_fullname().to_string();
+
+        let daemon = ServiceDaemon::new().context("Failed to start mDNS daemon")?;
+        daemon
+            .register(service)
+            .context("Failed to register mDNS service")?;
+
+        info!(
+            service = %fullname,
+            port = addr.port(),
+            "Advertising service via mDNS"
+        );
+
+        Ok(Self { daemon, fullname })
+    }
+}
+
+impl Drop for MdnsAdvertisement {
+    fn drop(&mut self) {
+        // Say goodbye so browsers drop the entry right away instead of waiting for the TTL
+        if let Err(e) = self.daemon.unregister(&self.fullname) {
+            warn!(service = %self.fullname, error = %e, "Failed to unregister mDNS service");
+        }
+        let _ = self.daemon.shutdown();
+    }
+}
+
+fn service_info(instance_name: &str, host_name: &str, addr: SocketAddr) -> Result<ServiceInfo> {
+    let host_name = format!("{}.local.", host_name.trim_end_matches(".local"));
+    let properties = [("version", env!("CARGO_PKG_VERSION")), ("api", "/api/v1")];
+
+    let service = if addr.ip().is_unspecified() {
+        // Bound to all interfaces: advertise every local address
+        ServiceInfo::new(
+            SERVICE_TYPE,
+            instance_name,
+            &host_name,
+            (),
+            addr.port(),
+            &properties[..],
+        )
+        .map(ServiceInfo::enable_addr_auto)
+    } else {
+        ServiceInfo::new(
+            SERVICE_TYPE,
+            instance_name,
+            &host_name,
+            addr.ip(),
+            addr.port(),
+            &properties[..],
+        )
+    };
+
+    service.context("Invalid mDNS service description")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_info_uses_bound_address() {
+        let addr: SocketAddr = "192.168.1.20:3000".parse().unwrap();
+        let service = service_info("Kitchen", "homelab", addr).unwrap();
+
+        assert_eq!(service.get_type(), SERVICE_TYPE);
+        assert_eq!(service.get_fullname(), "Kitchen._chef-de-vibe._tcp.local.");
+        assert_eq!(service.get_hostname(), "homelab.local.");
+        assert_eq!(service.get_port(), 3000);
+        assert!(service
+            .get_addresses()
+            .contains(&"192.168.1.20".parse().unwrap()));
+        assert_eq!(service.get_property_val_str("api"), Some("/api/v1"));
+    }
+
+    #[test]
+    fn test_service_info_for_unspecified_address() {
+        let addr: SocketAddr = "0.0.0.0:8080".parse().unwrap();
+        let service = service_info("Kitchen", "homelab.local", addr).unwrap();
+
+        assert_eq!(service.get_hostname(), "homelab.local.");
+        assert_eq!(service.get_port(), 8080);
+        assert!(service.get_addresses().is_empty());
+    }
+}
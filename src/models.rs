@@ -1,12 +1,43 @@
+use crate::anchors::Anchor;
+use crate::approval_audit::{ApprovalAuditEntry, ApprovalAuditLog};
+use crate::approval_priority::{self, Priority};
+use crate::approval_risk::{RiskApprovers, RiskLevel};
+use crate::approval_rules::{ApprovalRule, ApprovalRules, ProjectKey};
+use crate::approval_sync::ApprovalSync;
+use crate::config::{ApprovalPriority, DisconnectQueuePolicy, WritePacing};
+use crate::debug_capture::{DebugCapture, Direction};
+use crate::error::{OrchestratorError, OrchestratorResult};
+use crate::input_record::{self, RecordedInput};
+use crate::notifications::WatchEvent;
+use crate::preferences::Preferences;
+use crate::process_stats::ProcessStats;
+use crate::resource_limits::ResourceLimit;
+use crate::scratch::{ScratchFile, ScratchLease};
+use crate::server_events::{ServerEventKind, ServerEvents};
+use crate::session_backend::BackendKind;
+use crate::session_context::{InstructionFile, McpServer, SettingsFile};
+use crate::session_import::ImportFormat;
+use crate::slo::{Operation, SloTracker};
+use crate::telemetry::TelemetryReport;
+use crate::trace_export;
+use crate::transcript_export::ExportFormat;
+use crate::usage::{UsageLedger, UsageTotals};
+use crate::web_push::PushSubscription;
+use crate::wire_log::WireLog;
+use crate::write_pacing::TokenBucket;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::{broadcast, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, watch, Mutex, RwLock};
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct SessionInfo {
     pub session_id: String,
+    #[schema(value_type = String)]
     pub working_directory: PathBuf,
     pub active: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -15,43 +46,419 @@ pub struct SessionInfo {
     pub earliest_message_date: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub latest_message_date: Option<String>,
+    /// Session this one was resumed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resumed_from: Option<String>,
+    /// Whether the requesting user pinned this session, or a session it was resumed from
+    #[serde(default)]
+    pub pinned: bool,
+    /// Identity of the user the session belongs to, if it was started or imported
+    /// through the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Tokens and cost of the turns the session completed through the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<UsageTotals>,
+    /// Tags attached with `PATCH /api/v1/sessions/{session_id}/metadata`, sorted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Key/value metadata attached with `PATCH /api/v1/sessions/{session_id}/metadata`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
 pub enum SessionStatus {
     Pending,
     Ready,
     Failed,
+    /// Claude is gone; the session only lingers until it is reaped
+    Exited,
+    /// The session ran into its duration or turn limit and takes no more input
+    LimitReached,
+    /// Claude was killed for exceeding its memory or CPU limit
+    ResourceLimitExceeded,
+}
+
+/// Whether `session_id` can name the files and directories kept for a session: a single
+/// path component, so that joining it to a directory stays inside that directory.
+#[must_use]
+pub fn is_valid_session_id(session_id: &str) -> bool {
+    let mut components = Path::new(session_id).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    )
+}
+
+/// Where the messages of `session_id` not yet written to Claude are kept.
+fn pending_writes_path(state_dir: &Path, session_id: &str) -> PathBuf {
+    state_dir.join(format!("{session_id}.pending.jsonl"))
 }
 
 #[derive(Debug)]
 pub struct Session {
     pub id: Arc<RwLock<String>>,
     pub working_dir: PathBuf,
+    pub options: SessionOptions,
     pub process_id: Arc<RwLock<Option<u32>>>,
+    // Set while a backend without a per-session process (e.g. an SDK bridge) is connected
+    pub backend_connected: Arc<RwLock<bool>>,
     pub clients: Arc<RwLock<Vec<WebSocketClient>>>,
     pub write_queue: Arc<Mutex<VecDeque<WriteMessage>>>,
+    // Message taken off the write queue whose write to Claude is not confirmed yet
+    in_flight: Mutex<Option<WriteMessage>>,
+    // Messages of disconnected clients waiting to be released or dropped
+    pub held_messages: Arc<Mutex<Vec<WriteMessage>>>,
     pub status: Arc<RwLock<SessionStatus>>,
-    pub broadcast_tx: broadcast::Sender<BroadcastMessage>,
+    pub broadcast_tx: broadcast::Sender<BroadcastEnvelope>,
+    // Sequence numbers of broadcasts, and the latest ones for clients that reconnect
+    broadcast_log: std::sync::Mutex<BroadcastLog>,
     // Approval system fields
     pub approval_clients: Arc<RwLock<Vec<ApprovalWebSocketClient>>>,
     pub pending_approvals: Arc<Mutex<HashMap<String, ApprovalRequest>>>,
     pub approval_broadcast_tx: broadcast::Sender<ApprovalMessage>,
+    // Ephemeral per-client UI state (viewed message, selections), keyed by client ID
+    pub presence: Arc<RwLock<HashMap<String, serde_json::Value>>>,
+    // Non-JSON stdout lines passed through in tolerant mode
+    pub raw_output_lines: AtomicU64,
+    // Directory pending approvals and unsent messages are persisted to, if enabled
+    pub state_dir: Option<PathBuf>,
+    // Raw stdin/stdout capture while debugging is switched on
+    pub debug_capture: Arc<Mutex<Option<DebugCapture>>>,
+    debug_generation: AtomicU64,
+    // Log of every line relayed to and from Claude, if wire logging is enabled
+    wire_log: std::sync::Mutex<Option<WireLog>>,
+    // Flips once Claude has exited, for reaping the session after a grace period
+    exited: watch::Sender<bool>,
+    // Where answered approvals are recorded, if anywhere
+    approval_audit: Option<Arc<ApprovalAuditLog>>,
+    // Allows remembered for identical approval requests, if any
+    approval_rules: Option<Arc<ApprovalRules>>,
+    // Where pending approvals are shared with other instances, if anywhere
+    approval_sync: Option<Arc<ApprovalSync>>,
+    // Who may allow approvals of each risk level
+    risk_approvers: RiskApprovers,
+    // Turns Claude has completed, counted by its `result` messages
+    turns: AtomicU32,
+    // Set when input is written to Claude, cleared when its turn ends
+    turn_in_progress: AtomicBool,
+    // Set once the orchestrator asked Claude to exit, so the exit is not taken for a crash
+    exit_requested: AtomicBool,
+    // Set once the session was handed to whoever started it; a crash before that fails
+    // the start rather than being restarted
+    started: AtomicBool,
+    // Set while a crashed Claude is being respawned
+    restarting: AtomicBool,
+    // Times Claude was respawned after crashing
+    restarts: AtomicU32,
+    // Latest memory and CPU sample of the Claude process
+    process_stats: RwLock<Option<ProcessStats>>,
+    // Limit Claude was killed for exceeding, once it was
+    resource_limit: std::sync::OnceLock<ResourceLimit>,
+    // Bumped whenever approval clients come or all go, so a countdown started when the
+    // last one left can tell whether it still applies
+    approval_attendance: AtomicU64,
+    // Directory client inputs are recorded to before they reach Claude, if enabled
+    input_record_dir: Option<PathBuf>,
+    // Spaces out writes of the queue to Claude, if pacing is configured
+    write_pacing: Option<std::sync::Mutex<TokenBucket>>,
+    // Which approval requests are delivered first, if priority lanes are configured
+    approval_priority: Option<ApprovalPriority>,
+    // Scratch directory of the session, if enabled; the lease is let go when Claude exits
+    scratch_dir: Option<PathBuf>,
+    scratch_lease: std::sync::Mutex<Option<ScratchLease>>,
+    // Where latencies for the SLO report are recorded, if anywhere
+    slo: Option<Arc<SloTracker>>,
+    // Where the tokens and cost of each turn are accounted, if anywhere
+    usage: Option<Arc<UsageLedger>>,
+    // Claude reports the cost of its whole run so far; what it reported last
+    reported_cost_usd: std::sync::Mutex<f64>,
+    // Where lifecycle changes are announced server-wide, if anywhere
+    server_events: Option<Arc<ServerEvents>>,
+    // Whether the session was last announced as active
+    announced_active: AtomicBool,
+    // When the session was set up, for its uptime
+    opened_at: Instant,
+    // Faults injected through the admin API
+    #[cfg(feature = "testing")]
+    pub faults: crate::fault_injection::Faults,
+}
+
+/// One line of Claude's `stream-json` output, by its `type`. Only the fields the server
+/// acts on are typed; clients still receive each line as Claude wrote it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClaudeMessage {
+    System(SystemMessage),
+    Assistant(ConversationMessage),
+    User(ConversationMessage),
+    Result(ResultMessage),
+    ControlRequest(ControlRequest),
+    ControlResponse(ControlResponse),
+    /// Incremental output, only sent when partial messages are enabled
+    StreamEvent {},
+    /// Any other `type`, or a line whose fields have a shape this model does not know
+    #[serde(other)]
+    Unknown,
+}
+
+impl ClaudeMessage {
+    /// Parses a line of Claude output.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the line is not JSON at all.
+    pub fn parse(line: &str) -> Result<Self, serde_json::Error> {
+        match serde_json::from_str(line) {
+            Err(e) if e.is_data() => {
+                serde_json::from_str::<serde::de::IgnoredAny>(line)?;
+                Ok(Self::Unknown)
+            }
+            parsed => parsed,
+        }
+    }
+
+    /// The `type` of the line, `unknown` for lines this model does not know.
+    #[must_use]
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::System(_) => "system",
+            Self::Assistant(_) => "assistant",
+            Self::User(_) => "user",
+            Self::Result(_) => "result",
+            Self::ControlRequest(_) => "control_request",
+            Self::ControlResponse(_) => "control_response",
+            Self::StreamEvent {} => "stream_event",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// A `system` line, such as the `init` line Claude starts with.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SystemMessage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subtype: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+}
+
+/// An `assistant` or `user` line.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConversationMessage {
+    #[serde(default)]
+    pub message: MessageBody,
+}
+
+impl ConversationMessage {
+    /// The text of the message: its plain text content, or its text blocks in order.
+    pub fn text_blocks(&self) -> impl Iterator<Item = &str> {
+        let (text, blocks) = match &self.message.content {
+            MessageContent::Text(text) => (Some(text.as_str()), &[][..]),
+            MessageContent::Blocks(blocks) => (None, blocks.as_slice()),
+        };
+        text.into_iter()
+            .chain(blocks.iter().filter_map(|block| match block {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            }))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MessageBody {
+    #[serde(default)]
+    pub content: MessageContent,
+}
+
+/// `content` of a message: plain text, or a list of blocks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Blocks(Vec<ContentBlock>),
+}
+
+impl Default for MessageContent {
+    fn default() -> Self {
+        Self::Blocks(Vec::new())
+    }
+}
+
+/// A block of a message's content. Missing fields are left empty, so one sparse block
+/// does not make the whole line unknown.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    Text {
+        #[serde(default)]
+        text: String,
+    },
+    Thinking {
+        #[serde(default)]
+        thinking: String,
+    },
+    ToolUse {
+        #[serde(default)]
+        id: String,
+        #[serde(default)]
+        name: String,
+        #[serde(default)]
+        input: serde_json::Value,
+    },
+    ToolResult {
+        #[serde(default)]
+        tool_use_id: String,
+        /// A string, or a list of blocks
+        #[serde(default)]
+        content: serde_json::Value,
+        #[serde(default)]
+        is_error: bool,
+    },
+    /// Images, documents and other blocks the server does not look into
+    #[serde(other)]
+    Other,
+}
+
+/// The `result` line that ends a turn.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResultMessage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subtype: Option<String>,
+    #[serde(default)]
+    pub is_error: bool,
+    /// Final text of the turn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_cost_usd: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_turns: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+}
+
+impl ResultMessage {
+    /// What the turn ended with: its final text, or else its subtype.
+    #[must_use]
+    pub fn detail(&self) -> Option<&str> {
+        self.result.as_deref().or(self.subtype.as_deref())
+    }
+}
+
+/// Tokens a turn used, as Claude reports them
+#[allow(clippy::struct_field_names)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+}
+
+/// A `control_request` Claude waits on an answer to, such as a tool permission check.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ControlRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Passed through to approval clients as it is
+    #[serde(default)]
+    pub request: serde_json::Value,
+}
+
+impl ControlRequest {
+    /// `request.subtype`, e.g. `can_use_tool`; empty if missing.
+    #[must_use]
+    pub fn subtype(&self) -> &str {
+        self.request
+            .get("subtype")
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ControlResponse {
+    #[serde(default)]
+    pub response: serde_json::Value,
 }
 
 #[derive(Debug, Clone)]
 pub enum BroadcastMessage {
     /// Message from Claude to be sent to all clients
-    ClaudeOutput(String),
+    ClaudeOutput {
+        line: Arc<str>,
+        /// When the line was read from Claude
+        received_at: chrono::DateTime<chrono::Utc>,
+    },
+    /// Incremental `stream_event` from Claude, only sent to clients that opted in
+    StreamEvent {
+        line: Arc<str>,
+        /// When the line was read from Claude
+        received_at: chrono::DateTime<chrono::Utc>,
+    },
     /// Message from a client to be sent to all other clients (excludes sender)
     ClientInput {
-        content: String,
+        content: Arc<str>,
         sender_client_id: String,
+        /// When the server got the message from the client
+        received_at: chrono::DateTime<chrono::Utc>,
+    },
+    /// Ephemeral client state relayed to all other clients (excludes sender).
+    /// A `Null` state means the client has left.
+    Presence {
+        client_id: String,
+        state: serde_json::Value,
     },
     /// Disconnect signal when Claude process dies
     Disconnect,
 }
 
+impl BroadcastMessage {
+    /// Whether clients that reconnect get the message again; presence and disconnect
+    /// signals only matter when they happen
+    #[must_use]
+    pub const fn is_replayable(&self) -> bool {
+        matches!(
+            self,
+            Self::ClaudeOutput { .. } | Self::StreamEvent { .. } | Self::ClientInput { .. }
+        )
+    }
+}
+
+/// A broadcast with its place among the broadcasts of its session
+#[derive(Debug, Clone)]
+pub struct BroadcastEnvelope {
+    /// Increases with every broadcast of the session, starting at 1
+    pub seq: u64,
+    pub message: BroadcastMessage,
+}
+
+/// Broadcasts a reconnecting client missed
+#[derive(Debug, Default)]
+pub struct BroadcastReplay {
+    /// Replayable broadcasts after the one the client saw last, oldest first
+    pub missed: Vec<BroadcastEnvelope>,
+    /// False when some of what the client missed is no longer buffered, or the client
+    /// saw a sequence number this session never reached
+    pub complete: bool,
+}
+
+/// Recent replayable broadcasts of a session, for clients that reconnect.
+#[derive(Debug, Default)]
+struct BroadcastLog {
+    last_seq: u64,
+    recent: VecDeque<BroadcastEnvelope>,
+    /// Newest sequence number dropped from `recent` to make room
+    dropped_through: u64,
+}
+
 /// Approval-related data structures
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApprovalRequest {
@@ -60,22 +467,311 @@ pub struct ApprovalRequest {
     pub claude_request_id: String, // Claude's original request_id for internal use
     pub request: serde_json::Value, // Raw Claude request - pass-through
     pub created_at: std::time::SystemTime,
+    /// Questions approvers asked Claude about this request before deciding
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clarifications: Vec<Clarification>,
+    /// How risky allowing the request is, classified when it arrives
+    #[serde(default)]
+    pub risk: RiskLevel,
+    /// What made the request that risky, for approvers to read
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub risk_reasons: Vec<String>,
+    /// Lane the request is delivered in, from its risk
+    #[serde(default)]
+    pub priority: Priority,
+}
+
+/// A question an approver asked Claude about a pending approval, and Claude's answer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Clarification {
+    pub question: String,
+    /// Identity of the approver who asked
+    pub asked_by: String,
+    pub asked_at: chrono::DateTime<chrono::Utc>,
+    /// Text of Claude's next reply after the question; unset while awaited
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer: Option<String>,
+}
+
+impl ApprovalRequest {
+    /// How approval clients see this request
+    #[must_use]
+    pub fn frame(&self) -> ApprovalFrame {
+        ApprovalFrame {
+            id: self.id.clone(),
+            request: self.request.clone(), // Pass through raw Claude request
+            risk: self.risk,
+            risk_reasons: self.risk_reasons.clone(),
+            priority: self.priority,
+            created_at: self
+                .created_at
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            clarifications: self.clarifications.clone(),
+        }
+    }
+
+    /// The frame approval WebSocket clients receive for this request
+    #[must_use]
+    pub fn client_frame(&self) -> String {
+        serde_json::to_string(&self.frame()).unwrap_or_default()
+    }
+
+    /// Whether the latest question about this request still waits for Claude's answer
+    #[must_use]
+    pub fn awaits_clarification(&self) -> bool {
+        self.clarifications
+            .last()
+            .is_some_and(|clarification| clarification.answer.is_none())
+    }
+}
+
+/// Puts approvals in delivery order: high priority first, then oldest first
+pub fn sort_by_priority(approvals: &mut [ApprovalRequest]) {
+    approvals.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then_with(|| a.created_at.cmp(&b.created_at))
+    });
+}
+
+/// A pending approval request as approval WebSocket clients receive it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ApprovalFrame {
+    pub id: String,
+    /// Raw `can_use_tool` control request from Claude
+    pub request: serde_json::Value,
+    pub risk: RiskLevel,
+    /// What made the request that risky, e.g. "deletes files recursively or without
+    /// asking"; empty when nothing stood out
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub risk_reasons: Vec<String>,
+    #[serde(default)]
+    pub priority: Priority,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clarifications: Vec<Clarification>,
+}
+
+/// An approver's answer to a pending approval, as approval clients send it
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ApprovalAnswer {
+    /// `id` of the approval frame answered
+    pub id: String,
+    /// Passed through to Claude, e.g. `{"behavior": "allow", "updatedInput": {...}}`
+    pub response: serde_json::Value,
+}
+
+/// Outcome of one item of a bulk approval response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ApprovalResponseResult {
+    pub id: Option<String>,
+    pub accepted: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BulkApprovalResponse {
+    pub results: Vec<ApprovalResponseResult>,
 }
 
+/// Answer of `GET /api/v1/sessions/:id/approvals`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SessionApprovalsResponse {
+    pub session_id: String,
+    /// Pending requests, high priority first, then oldest first
+    pub approvals: Vec<ApprovalFrame>,
+}
+
+/// Answer of `GET /api/v1/sessions/:id/approval_rules`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SessionApprovalRulesResponse {
+    pub session_id: String,
+    /// Rules of the session, then those of its working directory
+    pub rules: Vec<ApprovalRule>,
+}
+
+/// Query parameters for `POST /api/v1/sessions/import`
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportSessionsQuery {
+    pub format: ImportFormat,
+    /// Directory the imported sessions belong to, and are resumed in
+    #[param(value_type = String)]
+    pub working_dir: PathBuf,
+}
+
+/// A conversation written to the projects directory as a session
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ImportedSession {
+    pub session_id: String,
+    /// Title of the conversation, if it had one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// User and assistant messages imported
+    pub messages: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ImportSessionsResponse {
+    pub sessions: Vec<ImportedSession>,
+}
+
+/// Query parameters for `GET /api/v1/approvals/history`
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ApprovalHistoryQuery {
+    /// Only approvals of this tool (case-insensitive)
+    pub tool: Option<String>,
+    /// Only approvals answered with this `behavior` (case-insensitive)
+    pub decision: Option<String>,
+    /// Only approvals answered by this identity
+    pub user: Option<String>,
+    /// Only approvals of this risk level
+    pub risk: Option<RiskLevel>,
+    /// Only approvals answered at or after this time
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApprovalHistoryResponse {
+    /// Matching approvals, newest first
+    pub entries: Vec<ApprovalAuditEntry>,
+    /// How many approvals match across all pages
+    pub total: usize,
+    /// Offset of the next page; absent on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<usize>,
+}
+
+/// Replayable broadcasts a session keeps for clients that reconnect with `after_seq`.
+pub const BROADCAST_REPLAY_CAPACITY: usize = 1000;
+
+/// Field of a forwarded approval response naming who answered it; set by the server.
+pub const DECIDED_BY_FIELD: &str = "decided_by";
+
+/// Why an answer to an approval that another instance already answered is refused.
+pub const ALREADY_DECIDED: &str = "already decided on another instance";
+
+/// Who approvals denied because no approval client was connected are attributed to.
+pub const DISCONNECT_POLICY_IDENTITY: &str = "approval-disconnect-policy";
+
 #[derive(Debug, Clone)]
 pub enum ApprovalMessage {
-    /// Approval request from Claude (both new and when sending pending on connection)
-    ApprovalRequest(ApprovalRequest),
+    /// Approval request from Claude (both new and when sending pending on connection),
+    /// with its client frame serialized once for every approval client
+    ApprovalRequest {
+        request: Arc<ApprovalRequest>,
+        frame: Arc<str>,
+    },
     /// Approval response from client (raw JSON)
     ApprovalResponse(serde_json::Value),
 }
 
+impl ApprovalMessage {
+    /// A client's `{id, response}`, marked with who sent it for the audit log.
+    #[must_use]
+    pub fn response(mut response: serde_json::Value, decided_by: &str) -> Self {
+        if let Some(object) = response.as_object_mut() {
+            object.insert(DECIDED_BY_FIELD.to_string(), decided_by.into());
+        }
+        Self::ApprovalResponse(response)
+    }
+
+    #[must_use]
+    pub fn request(request: ApprovalRequest) -> Self {
+        let frame = request.client_frame().into();
+        Self::ApprovalRequest {
+            request: Arc::new(request),
+            frame,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApprovalWebSocketClient {
     pub id: String,
     pub ip_address: String,
     pub user_agent: Option<String>,
     pub connected_at: std::time::SystemTime,
+    #[serde(skip)]
+    pub liveness: Arc<Liveness>,
+}
+
+/// When a client was last heard from, and how many pings it has left unanswered since.
+#[derive(Debug)]
+pub struct Liveness {
+    last_seen_ms: AtomicI64,
+    unanswered_pings: AtomicU32,
+}
+
+impl Default for Liveness {
+    fn default() -> Self {
+        Self {
+            last_seen_ms: AtomicI64::new(chrono::Utc::now().timestamp_millis()),
+            unanswered_pings: AtomicU32::new(0),
+        }
+    }
+}
+
+impl Liveness {
+    /// Records that the client sent something; any frame answers the pings before it
+    pub fn seen(&self) {
+        self.last_seen_ms
+            .store(chrono::Utc::now().timestamp_millis(), Ordering::Relaxed);
+        self.unanswered_pings.store(0, Ordering::Relaxed);
+    }
+
+    /// Records a ping sent to the client
+    pub fn pinged(&self) {
+        self.unanswered_pings.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Pings sent since the client was last heard from
+    #[must_use]
+    pub fn unanswered_pings(&self) -> u32 {
+        self.unanswered_pings.load(Ordering::Relaxed)
+    }
+
+    #[must_use]
+    pub fn last_seen(&self) -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::from_timestamp_millis(self.last_seen_ms.load(Ordering::Relaxed))
+            .unwrap_or_default()
+    }
+}
+
+/// Per-client delivery accounting for clients that opted into ack frames.
+#[derive(Debug, Default)]
+pub struct DeliveryTracker {
+    sent: AtomicU64,
+    acked: AtomicU64,
+}
+
+impl DeliveryTracker {
+    /// Allocates the sequence number for the next outbound message
+    pub fn next_seq(&self) -> u64 {
+        self.sent.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Records a client ack; acks are cumulative and never move backwards
+    pub fn ack(&self, seq: u64) {
+        let seq = seq.min(self.sent.load(Ordering::Relaxed));
+        self.acked.fetch_max(seq, Ordering::Relaxed);
+    }
+
+    /// Number of messages sent but not yet acknowledged
+    #[must_use]
+    pub fn lag(&self) -> u64 {
+        self.sent
+            .load(Ordering::Relaxed)
+            .saturating_sub(self.acked.load(Ordering::Relaxed))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -87,125 +783,1543 @@ pub struct WebSocketClient {
     pub user_agent: Option<String>,
     #[allow(dead_code)] // Used in logging and may be needed for client tracking
     pub connected_at: std::time::SystemTime,
+    /// Set when the client opted into ack frames
+    pub delivery: Option<Arc<DeliveryTracker>>,
+    pub liveness: Arc<Liveness>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WriteMessage {
     pub content: String,
-    #[allow(dead_code)] // Used for message attribution and logging
     pub sender_client_id: String,
-    #[allow(dead_code)] // Used for message timing and logging
     pub timestamp: std::time::SystemTime,
 }
 
+/// Where a message waits when write pacing holds it back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacedPosition {
+    /// 1-based position in the session's write queue
+    pub position: usize,
+    /// Roughly how long until it is written
+    pub estimated_wait: Duration,
+}
+
+/// A message waiting to be written to Claude
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QueuedMessage {
+    pub content: String,
+    pub enqueued_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<&WriteMessage> for QueuedMessage {
+    fn from(message: &WriteMessage) -> Self {
+        Self {
+            content: message.content.clone(),
+            enqueued_at: message.timestamp.into(),
+        }
+    }
+}
+
+/// Unwritten messages of one client
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ClientQueue {
+    pub client_id: String,
+    pub connected: bool,
+    pub queued: Vec<QueuedMessage>,
+    /// Set aside when the client disconnected under the `hold` policy
+    pub held: Vec<QueuedMessage>,
+}
+
 // API Request/Response types
-#[derive(Debug, Serialize, Deserialize)]
+/// Query parameters for `GET /api/v1/sessions`
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListSessionsQuery {
+    /// List every segment of a resumed chain instead of only the latest
+    #[serde(default)]
+    pub expand_lineage: bool,
+    /// Order of the returned sessions; unordered when not given
+    #[serde(default)]
+    pub sort: Option<SessionSort>,
+    /// Only sessions with (`true`) or without (`false`) a running Claude process
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active: Option<bool>,
+    /// Only sessions working in this directory or below it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[param(value_type = Option<String>)]
+    pub working_dir: Option<PathBuf>,
+    /// Only sessions with messages at or after this time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only sessions with messages at or before this time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only sessions tagged with this tag
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+impl ListSessionsQuery {
+    /// The filters of the query
+    #[must_use]
+    pub fn filter(&self) -> SessionFilter {
+        SessionFilter {
+            active: self.active,
+            working_dir: self.working_dir.clone(),
+            since: self.since,
+            until: self.until,
+            tag: self.tag.clone(),
+            owner: None,
+        }
+    }
+}
+
+/// Which sessions a listing includes; unset criteria let every session through.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionFilter {
+    pub active: Option<bool>,
+    /// Matched by path components, so `/work/app` does not match `/work/apple`
+    pub working_dir: Option<PathBuf>,
+    /// Sessions whose latest message is not earlier
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Sessions whose earliest message is not later
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+    /// Sessions tagged with this tag
+    pub tag: Option<String>,
+    /// Sessions belonging to this user; never taken from the query
+    pub owner: Option<String>,
+}
+
+impl SessionFilter {
+    /// Whether `session` passes every criterion. Sessions without message dates fail
+    /// either date bound.
+    #[must_use]
+    pub fn matches(&self, session: &SessionInfo) -> bool {
+        let message_date = |date: Option<&str>| {
+            date.and_then(|date| chrono::DateTime::parse_from_rfc3339(date).ok())
+                .map(|date| date.with_timezone(&chrono::Utc))
+        };
+        self.active.is_none_or(|active| active == session.active)
+            && self
+                .working_dir
+                .as_ref()
+                .is_none_or(|dir| session.working_directory.starts_with(dir))
+            && self.since.is_none_or(|since| {
+                message_date(session.latest_message_date.as_deref())
+                    .is_some_and(|latest| latest >= since)
+            })
+            && self.until.is_none_or(|until| {
+                message_date(session.earliest_message_date.as_deref())
+                    .is_some_and(|earliest| earliest <= until)
+            })
+            && self
+                .tag
+                .as_ref()
+                .is_none_or(|tag| session.tags.contains(tag))
+            && self
+                .owner
+                .as_ref()
+                .is_none_or(|owner| session.owner.as_ref() == Some(owner))
+    }
+}
+
+/// Orders for the session list, newest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionSort {
+    /// Pinned sessions in the order they were pinned, then the rest as `Recent`
+    PinnedFirst,
+    /// By latest message
+    Recent,
+    /// By earliest message
+    Created,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ListSessionsResponse {
     pub sessions: Vec<SessionInfo>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Per-session launch options supplied when the session is created.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct SessionOptions {
+    /// Replaces Claude's default system prompt (`--system-prompt`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Appended to Claude's default system prompt (`--append-system-prompt`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub append_system_prompt: Option<String>,
+    /// Wall-clock seconds after which the session stops taking input; the lower of
+    /// this and `SESSION_MAX_DURATION` applies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_duration_secs: Option<u64>,
+    /// Turns after which the session stops taking input; the lower of this and
+    /// `SESSION_MAX_TURNS` applies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_turns: Option<u32>,
+    /// Seconds after the last approval client disconnects before pending approvals are
+    /// denied, unless one reconnects; `0` denies them right away. Without it approvals
+    /// wait for an approver indefinitely.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval_disconnect_deny_after_secs: Option<u64>,
+    /// Labels selecting how the session is treated, e.g. on shutdown (`SHUTDOWN_POLICIES`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Extra Claude CLI flags by name without dashes, e.g. `{"model": "opus"}`; only
+    /// those in `CLAUDE_CLI_FLAG_ALLOWLIST` are accepted.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub cli_flags: BTreeMap<String, String>,
+    /// Runs Claude on the host CLI or in a container instead of the default backend
+    /// (`SESSION_BACKEND`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<BackendKind>,
+    /// Identity of the user creating the session; set by the server, never by the request.
+    #[serde(skip)]
+    pub owner: Option<String>,
+}
+
+/// Generations of the `POST /api/v1/sessions` body, oldest first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CreateSessionRequestVersion {
+    /// The first message as a single `first_message` string
+    FirstMessage,
+    /// `first_message` as a list of messages
+    FirstMessageList,
+    /// `bootstrap_messages`
+    #[default]
+    BootstrapMessages,
+}
+
+/// Body of `POST /api/v1/sessions`, normalized from whichever generation the client
+/// sent; it is always serialized in the current one.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(try_from = "CreateSessionRequestBody")]
 pub struct CreateSessionRequest {
     pub session_id: String,
+    #[schema(value_type = String)]
     pub working_dir: PathBuf,
     pub resume: bool,
     pub bootstrap_messages: Vec<String>,
+    /// Upload whose text is sent as a user message after the bootstrap messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_message_upload: Option<String>,
+    #[serde(flatten)]
+    pub options: SessionOptions,
+    /// Generation of the body the request arrived in
+    #[serde(skip)]
+    pub version: CreateSessionRequestVersion,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// `first_message` of the older request generations
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LegacyFirstMessage {
+    One(String),
+    List(Vec<String>),
+}
+
+/// Every field any generation of the create session body had, as sent.
+#[derive(Deserialize)]
+struct CreateSessionRequestBody {
+    session_id: String,
+    working_dir: PathBuf,
+    resume: bool,
+    #[serde(default)]
+    bootstrap_messages: Option<Vec<String>>,
+    #[serde(default)]
+    first_message: Option<LegacyFirstMessage>,
+    #[serde(default)]
+    first_message_upload: Option<String>,
+    #[serde(flatten)]
+    options: SessionOptions,
+}
+
+impl TryFrom<CreateSessionRequestBody> for CreateSessionRequest {
+    type Error = String;
+
+    fn try_from(body: CreateSessionRequestBody) -> Result<Self, Self::Error> {
+        let (bootstrap_messages, version) = match (body.bootstrap_messages, body.first_message) {
+            (Some(_), Some(_)) => {
+                return Err("send either bootstrap_messages or first_message, not both".to_string())
+            }
+            (Some(messages), None) => (messages, CreateSessionRequestVersion::BootstrapMessages),
+            (None, Some(LegacyFirstMessage::One(message))) => {
+                (vec![message], CreateSessionRequestVersion::FirstMessage)
+            }
+            (None, Some(LegacyFirstMessage::List(messages))) => {
+                (messages, CreateSessionRequestVersion::FirstMessageList)
+            }
+            (None, None) => (Vec::new(), CreateSessionRequestVersion::BootstrapMessages),
+        };
+        Ok(Self {
+            session_id: body.session_id,
+            working_dir: body.working_dir,
+            resume: body.resume,
+            bootstrap_messages,
+            first_message_upload: body.first_message_upload,
+            options: body.options,
+            version,
+        })
+    }
+}
+
+/// Tells a client it sent a request shape that is on its way out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Deprecation {
+    /// The deprecated field the request used
+    pub field: String,
+    /// What to send instead
+    pub replacement: String,
+    pub message: String,
+}
+
+impl CreateSessionRequestVersion {
+    /// The notice for clients still sending this generation, if it is deprecated.
+    #[must_use]
+    pub fn deprecation(self) -> Option<Deprecation> {
+        let shape = match self {
+            Self::FirstMessage => "a string",
+            Self::FirstMessageList => "a list",
+            Self::BootstrapMessages => return None,
+        };
+        Some(Deprecation {
+            field: "first_message".to_string(),
+            replacement: "bootstrap_messages".to_string(),
+            message: format!(
+                "first_message as {shape} is deprecated; send the messages as bootstrap_messages"
+            ),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct CreateSessionResponse {
     pub session_id: String,
     pub websocket_url: String,
     pub approval_websocket_url: String,
+    /// Set when the request used a deprecated shape, which still works for now
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecation: Option<Deprecation>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct GetSessionResponse {
+/// Body of `POST /api/v1/uploads`
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct CreateUploadRequest {
+    /// Expected size in bytes; without it any amount up to the limit is accepted
+    #[serde(default)]
+    pub total_size: Option<u64>,
+}
+
+/// Query parameters for `PUT /api/v1/uploads/:id`
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AppendUploadQuery {
+    /// Where the chunk starts, must be the number of bytes received so far
+    pub offset: u64,
+}
+
+/// Body of `PUT /api/v1/sessions/:id/debug`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SetSessionDebugRequest {
+    pub enabled: bool,
+    /// How long capture stays on, defaults to 10 minutes and is capped at an hour
+    #[serde(default)]
+    pub duration_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SetSessionDebugResponse {
     pub session_id: String,
-    pub working_directory: PathBuf,
-    pub content: Vec<serde_json::Value>,
+    pub enabled: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub websocket_url: Option<String>,
+    #[schema(value_type = Option<String>)]
+    pub debug_file: Option<PathBuf>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub approval_websocket_url: Option<String>,
+    pub expires_in_secs: Option<u64>,
 }
 
-// Session file format types
-#[derive(Debug, Serialize, Deserialize)]
-pub struct SessionFileLine {
-    #[serde(rename = "sessionId")]
-    pub session_id: Option<String>,
-    pub cwd: Option<PathBuf>,
-    #[serde(flatten)]
-    pub other: serde_json::Value,
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SessionQueueResponse {
+    pub session_id: String,
+    pub disconnect_policy: DisconnectQueuePolicy,
+    pub clients: Vec<ClientQueue>,
 }
 
-impl Session {
-    #[must_use]
-    pub fn new(id: String, working_dir: PathBuf) -> Self {
-        let (broadcast_tx, _) = broadcast::channel(1000);
-        let (approval_broadcast_tx, _) = broadcast::channel(1000);
-        Self {
-            id: Arc::new(RwLock::new(id)),
-            working_dir,
-            process_id: Arc::new(RwLock::new(None)),
-            clients: Arc::new(RwLock::new(Vec::new())),
-            write_queue: Arc::new(Mutex::new(VecDeque::new())),
-            status: Arc::new(RwLock::new(SessionStatus::Pending)),
-            broadcast_tx,
-            // Initialize approval system fields
-            approval_clients: Arc::new(RwLock::new(Vec::new())),
-            pending_approvals: Arc::new(Mutex::new(HashMap::new())),
-            approval_broadcast_tx,
-        }
+/// Which connection of a session a client uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientChannel {
+    /// The main WebSocket, or the event stream
+    Main,
+    /// The approval WebSocket
+    Approval,
+}
+
+/// A client connected to a session
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ConnectedClient {
+    pub client_id: String,
+    pub channel: ClientChannel,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    pub connected_at: chrono::DateTime<chrono::Utc>,
+    /// When the client last sent a frame, a pong included
+    pub last_seen_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SessionClientsResponse {
+    pub session_id: String,
+    pub clients: Vec<ConnectedClient>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HeldMessagesAction {
+    /// Write the messages to Claude after all
+    Release,
+    Drop,
+}
+
+/// Body of `POST /api/v1/sessions/:id/queue/:client_id`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ResolveHeldMessagesRequest {
+    pub action: HeldMessagesAction,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ResolveHeldMessagesResponse {
+    pub session_id: String,
+    pub client_id: String,
+    pub action: HeldMessagesAction,
+    pub count: usize,
+}
+
+/// Query parameters for `POST /api/v1/sessions/:id/messages`
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SendMessageQuery {
+    /// Client the message is sent by, as announced on the session's event stream
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SendMessageResponse {
+    pub client_id: String,
+    /// 1-based position in the write queue, when write pacing holds the message back
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_wait_ms: Option<u64>,
+}
+
+/// Body of `PUT /api/v1/sessions/:id/watch`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WatchSessionRequest {
+    /// Name of a configured notification target
+    pub target: String,
+    /// Events to be notified about, all of them when empty
+    #[serde(default)]
+    pub events: Vec<WatchEvent>,
+}
+
+/// Query parameters for `DELETE /api/v1/sessions/:id/watch`
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UnwatchSessionQuery {
+    /// Only remove this target's watch instead of all of them
+    #[serde(default)]
+    pub target: Option<String>,
+}
+
+/// One notification target watching a session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct SessionWatch {
+    pub target: String,
+    pub events: Vec<WatchEvent>,
+}
+
+/// Body of `POST /api/v1/sessions/:id/fork`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ForkSessionRequest {
+    /// `uuid` of the last transcript entry the fork keeps; the whole transcript without it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_uuid: Option<String>,
+    pub bootstrap_messages: Vec<String>,
+    #[serde(flatten)]
+    pub options: SessionOptions,
+}
+
+/// Body of `POST /api/v1/sessions/:id/anchors`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateAnchorRequest {
+    pub name: String,
+    /// `uuid` of the transcript entry to anchor
+    pub message_uuid: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SessionAnchorsResponse {
+    pub session_id: String,
+    pub anchors: Vec<Anchor>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SessionMetadataResponse {
+    pub session_id: String,
+    pub tags: Vec<String>,
+    pub metadata: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SessionWatchesResponse {
+    pub session_id: String,
+    pub watches: Vec<SessionWatch>,
+}
+
+/// Standing configuration of a session's working directory
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SessionContextResponse {
+    pub session_id: String,
+    #[schema(value_type = String)]
+    pub working_directory: PathBuf,
+    pub instructions: Vec<InstructionFile>,
+    pub settings: Vec<SettingsFile>,
+    pub mcp_servers: Vec<McpServer>,
+}
+
+/// The stored UI preferences of the requesting user
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PreferencesResponse {
+    pub identity: String,
+    #[schema(value_type = Object)]
+    pub preferences: Preferences,
+}
+
+/// Sessions the requesting user pinned, in the order they were pinned
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PinnedSessionsResponse {
+    pub identity: String,
+    pub pinned_sessions: Vec<String>,
+}
+
+/// Key browsers pass as `applicationServerKey` when subscribing to push notifications
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WebPushKeyResponse {
+    /// Uncompressed P-256 public key, base64url
+    pub public_key: String,
+}
+
+/// Query parameters for `DELETE /api/v1/push/subscriptions`
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UnsubscribePushQuery {
+    pub endpoint: String,
+}
+
+/// Browsers the requesting user subscribed to push notifications, oldest first
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PushSubscriptionsResponse {
+    pub identity: String,
+    pub subscriptions: Vec<PushSubscription>,
+}
+
+/// What telemetry would send next, and whether it is sent at all
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TelemetryPreviewResponse {
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    pub report: TelemetryReport,
+}
+
+/// Query parameters for `GET /api/v1/sessions/:id/files`
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SessionFilesQuery {
+    /// Show the tree as of this transcript message instead of the latest commit
+    #[serde(default)]
+    pub at_uuid: Option<String>,
+    /// Also return this file's content
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SessionFilesResponse {
+    pub session_id: String,
+    #[schema(value_type = String)]
+    pub working_directory: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub at_uuid: Option<String>,
+    /// Commit the tree was read from; absent if nothing was committed yet at that point
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    pub files: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+/// Outcome of `DELETE /api/v1/sessions/:id`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TerminateSessionResponse {
+    pub session_id: String,
+    /// Whether a Claude process was stopped; bridge sessions have none
+    pub process_terminated: bool,
+    /// Set when Claude ignored SIGTERM and had to be killed
+    pub killed: bool,
+}
+
+/// A session the orchestrator holds, as `GET /api/v1/admin/status` reports it
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AdminSessionState {
+    pub session_id: String,
+    #[schema(value_type = String)]
+    pub working_dir: PathBuf,
+    pub status: SessionStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process_id: Option<u32>,
+    /// Clients of the main socket and event streams
+    pub clients: usize,
+    pub approval_clients: usize,
+    /// Messages waiting to be written to Claude
+    pub queued_messages: usize,
+    /// Messages of disconnected clients set aside under the `hold` policy
+    pub held_messages: usize,
+    pub pending_approvals: usize,
+    /// Whether Claude has no queued input left and is not in the middle of a turn
+    pub drained: bool,
+    pub uptime_secs: u64,
+}
+
+/// Response of `GET /api/v1/admin/status`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AdminStatusResponse {
+    pub uptime_secs: u64,
+    /// Set while new sessions and WebSocket clients are refused
+    pub draining: bool,
+    pub sessions: Vec<AdminSessionState>,
+}
+
+/// Body and response of `PUT /api/v1/admin/draining`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DrainingState {
+    pub draining: bool,
+}
+
+/// Outcome of `POST /api/v1/admin/rescan`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RescanResponse {
+    /// Journals read again because they changed
+    pub rescanned: usize,
+    /// Journals dropped from the index because they are gone
+    pub removed: usize,
+}
+
+/// Files in a session's scratch directory
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SessionScratchResponse {
+    pub session_id: String,
+    #[schema(value_type = String)]
+    pub directory: PathBuf,
+    pub files: Vec<ScratchFile>,
+    /// Set when there were more files than listed
+    pub truncated: bool,
+}
+
+/// Query parameters for `GET /api/v1/sessions/:id/export`
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportSessionQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// Name of a configured transcript hook to run over the content before rendering
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hook: Option<String>,
+}
+
+/// Query parameters for `GET /api/v1/usage`
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UsageQuery {
+    /// First day to include, `YYYY-MM-DD` in UTC
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<chrono::NaiveDate>,
+    /// Last day to include, `YYYY-MM-DD` in UTC
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<chrono::NaiveDate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UsageResponse {
+    pub total: UsageTotals,
+    /// Days with usage, oldest first
+    pub by_day: Vec<DailyUsage>,
+    pub by_working_dir: Vec<WorkingDirUsage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DailyUsage {
+    pub day: chrono::NaiveDate,
+    #[serde(flatten)]
+    pub usage: UsageTotals,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WorkingDirUsage {
+    #[schema(value_type = String)]
+    pub working_dir: PathBuf,
+    #[serde(flatten)]
+    pub usage: UsageTotals,
+}
+
+/// Query parameters for `GET /api/v1/sessions/:id`
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetSessionQuery {
+    /// Name of a configured transcript hook to run over the content
+    #[serde(default)]
+    pub hook: Option<String>,
+    /// Return the whole content even when it is over the response size limit
+    #[serde(default)]
+    pub full: bool,
+    /// First content entry of a page
+    pub offset: Option<usize>,
+    /// Number of content entries in a page
+    pub limit: Option<usize>,
+    /// Keep images inline as base64 instead of pointing them at the media endpoint
+    #[serde(default)]
+    pub inline_media: bool,
+    /// How the content is sent; `json` when not given
+    #[serde(default)]
+    pub format: Option<ContentFormat>,
+}
+
+/// Query parameters for `GET /api/v1/sessions/:id/content`. Without a position the
+/// whole journal is returned.
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SessionContentQuery {
+    /// Journal lines the client already has; only later ones are returned
+    pub after_line: Option<usize>,
+    /// `uuid` of the last entry the client already has, instead of `after_line`
+    pub after_uuid: Option<String>,
+    /// Keep images inline as base64 instead of pointing them at the media endpoint
+    #[serde(default)]
+    pub inline_media: bool,
+}
+
+/// Entries appended to a session's journal since the client's last fetch
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SessionContentResponse {
+    pub session_id: String,
+    /// Journal line of the first entry of `content`
+    pub first_line: usize,
+    pub content: Vec<serde_json::Value>,
+    /// Complete lines in the journal: the `after_line` of the next fetch
+    pub next_line: usize,
+    /// The requested position is past the end of the journal, so the client's copy does
+    /// not match it and should be fetched again from the start
+    pub reset: bool,
+}
+
+/// Representations of `GET /api/v1/sessions/:id`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentFormat {
+    /// A [`GetSessionResponse`], built in memory
+    #[default]
+    Json,
+    /// The content entries alone, one JSON object per line, streamed from the journal
+    /// as they are read
+    Ndjson,
+}
+
+/// Describes how a transcript's content is split into pages, returned in place of (or
+/// alongside) `content` when it is too large to send at once.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ContentPaging {
+    pub total_entries: usize,
+    /// Size of the whole content serialized as JSON
+    pub total_bytes: usize,
+    /// Whether `content` was left out for being over the size limit
+    pub omitted: bool,
+    /// Number of entries per page that keeps a page around the size limit
+    pub page_size: usize,
+    /// Offset of the next page; absent on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GetSessionResponse {
+    pub session_id: String,
+    #[schema(value_type = String)]
+    pub working_directory: PathBuf,
+    pub content: Vec<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub websocket_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approval_websocket_url: Option<String>,
+    /// Status of the session while it is held in memory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<SessionStatus>,
+    /// Latest memory and CPU sample of the session's Claude process
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ProcessStats>,
+    /// Limit Claude was killed for exceeding, when the status is `resource_limit_exceeded`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource_limit: Option<ResourceLimit>,
+    /// Present when the content is paged, see [`ContentPaging`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paging: Option<ContentPaging>,
+    /// Named positions in the conversation, in conversation order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub anchors: Vec<Anchor>,
+    /// Client inputs recorded when they were sent, whether or not Claude got to them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recorded_inputs: Vec<RecordedInput>,
+    /// Tokens and cost of the turns the session completed through the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<UsageTotals>,
+    #[serde(flatten)]
+    pub options: SessionOptions,
+}
+
+// Session file format types
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionFileLine {
+    #[serde(rename = "sessionId")]
+    pub session_id: Option<String>,
+    pub cwd: Option<PathBuf>,
+    #[serde(flatten)]
+    pub other: serde_json::Value,
+}
+
+impl Session {
+    #[must_use]
+    #[allow(dead_code)] // Used by tests and library consumers
+    pub fn new(id: String, working_dir: PathBuf) -> Self {
+        Self::with_options(id, working_dir, SessionOptions::default())
+    }
+
+    #[must_use]
+    pub fn with_options(id: String, working_dir: PathBuf, options: SessionOptions) -> Self {
+        let (broadcast_tx, _) = broadcast::channel(1000);
+        let (approval_broadcast_tx, _) = broadcast::channel(1000);
+        Self {
+            id: Arc::new(RwLock::new(id)),
+            working_dir,
+            options,
+            process_id: Arc::new(RwLock::new(None)),
+            backend_connected: Arc::new(RwLock::new(false)),
+            clients: Arc::new(RwLock::new(Vec::new())),
+            write_queue: Arc::new(Mutex::new(VecDeque::new())),
+            in_flight: Mutex::new(None),
+            held_messages: Arc::new(Mutex::new(Vec::new())),
+            status: Arc::new(RwLock::new(SessionStatus::Pending)),
+            broadcast_tx,
+            broadcast_log: std::sync::Mutex::new(BroadcastLog::default()),
+            // Initialize approval system fields
+            approval_clients: Arc::new(RwLock::new(Vec::new())),
+            pending_approvals: Arc::new(Mutex::new(HashMap::new())),
+            approval_broadcast_tx,
+            presence: Arc::new(RwLock::new(HashMap::new())),
+            raw_output_lines: AtomicU64::new(0),
+            state_dir: None,
+            debug_capture: Arc::new(Mutex::new(None)),
+            debug_generation: AtomicU64::new(0),
+            wire_log: std::sync::Mutex::new(None),
+            exited: watch::channel(false).0,
+            approval_audit: None,
+            approval_rules: None,
+            approval_sync: None,
+            risk_approvers: RiskApprovers::default(),
+            turns: AtomicU32::new(0),
+            turn_in_progress: AtomicBool::new(false),
+            exit_requested: AtomicBool::new(false),
+            started: AtomicBool::new(false),
+            restarting: AtomicBool::new(false),
+            restarts: AtomicU32::new(0),
+            process_stats: RwLock::new(None),
+            resource_limit: std::sync::OnceLock::new(),
+            approval_attendance: AtomicU64::new(0),
+            input_record_dir: None,
+            write_pacing: None,
+            approval_priority: None,
+            scratch_dir: None,
+            scratch_lease: std::sync::Mutex::new(None),
+            slo: None,
+            usage: None,
+            reported_cost_usd: std::sync::Mutex::new(0.0),
+            server_events: None,
+            announced_active: AtomicBool::new(false),
+            opened_at: Instant::now(),
+            #[cfg(feature = "testing")]
+            faults: crate::fault_injection::Faults::default(),
+        }
+    }
+
+    /// Persists pending approvals under `state_dir` so they survive an orchestrator restart
+    #[must_use]
+    pub fn with_state_dir(mut self, state_dir: Option<PathBuf>) -> Self {
+        self.state_dir = state_dir;
+        self
+    }
+
+    /// Records the approvals answered in this session in `audit`
+    #[must_use]
+    pub fn with_approval_audit(mut self, audit: Arc<ApprovalAuditLog>) -> Self {
+        self.approval_audit = Some(audit);
+        self
+    }
+
+    /// Allows approval requests identical to ones allowed before, as remembered in `rules`
+    #[must_use]
+    pub fn with_approval_rules(mut self, rules: Arc<ApprovalRules>) -> Self {
+        self.approval_rules = Some(rules);
+        self
+    }
+
+    /// Where allows of this session are remembered, if anywhere
+    #[must_use]
+    pub fn approval_rules(&self) -> Option<&Arc<ApprovalRules>> {
+        self.approval_rules.as_ref()
+    }
+
+    /// Shares the pending approvals with the other instances using `sync`, which may
+    /// answer them
+    #[must_use]
+    pub fn with_approval_sync(mut self, sync: Option<Arc<ApprovalSync>>) -> Self {
+        self.approval_sync = sync;
+        self
+    }
+
+    #[must_use]
+    pub fn approval_sync(&self) -> Option<&Arc<ApprovalSync>> {
+        self.approval_sync.as_ref()
+    }
+
+    /// The sessions this one shares project-scope approval rules with
+    #[must_use]
+    pub fn rules_project(&self) -> ProjectKey {
+        ProjectKey {
+            owner: self.options.owner.clone(),
+            working_dir: self.working_dir.clone(),
+        }
+    }
+
+    /// Records latencies of the session's operations in `slo`
+    #[must_use]
+    pub fn with_slo(mut self, slo: Arc<SloTracker>) -> Self {
+        self.slo = Some(slo);
+        self
+    }
+
+    /// Accounts the tokens and cost of the session's turns in `usage`
+    #[must_use]
+    pub fn with_usage_ledger(mut self, usage: Arc<UsageLedger>) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// Announces the session's lifecycle changes on `server_events`
+    #[must_use]
+    pub fn with_server_events(mut self, server_events: Arc<ServerEvents>) -> Self {
+        self.server_events = Some(server_events);
+        self
+    }
+
+    /// Publishes a lifecycle change of the session, if anyone is told about them.
+    pub async fn announce(&self, kind: ServerEventKind) {
+        if let Some(server_events) = &self.server_events {
+            server_events.publish(self.get_id().await, kind);
+        }
+    }
+
+    /// Announces the session becoming active or inactive, once per change.
+    async fn announce_activity(&self) {
+        let active = self.is_active().await;
+        if self.announced_active.swap(active, Ordering::SeqCst) != active {
+            self.announce(if active {
+                ServerEventKind::SessionActive
+            } else {
+                ServerEventKind::SessionInactive
+            })
+            .await;
+        }
+    }
+
+    #[must_use]
+    pub const fn slo(&self) -> Option<&Arc<SloTracker>> {
+        self.slo.as_ref()
+    }
+
+    /// Records client inputs in a sidecar transcript under `dir` before they are queued
+    #[must_use]
+    pub fn with_input_record(mut self, dir: Option<PathBuf>) -> Self {
+        self.input_record_dir = dir;
+        self
+    }
+
+    /// Logs the traffic relayed to and from Claude to `wire_log`
+    #[must_use]
+    pub fn with_wire_log(mut self, wire_log: Option<WireLog>) -> Self {
+        self.wire_log = std::sync::Mutex::new(wire_log);
+        self
+    }
+
+    /// Paces writes of queued messages to Claude
+    #[must_use]
+    pub fn with_write_pacing(mut self, pacing: Option<WritePacing>) -> Self {
+        self.write_pacing =
+            pacing.map(|pacing| std::sync::Mutex::new(TokenBucket::new(pacing, Instant::now())));
+        self
+    }
+
+    /// Delivers risky approval requests ahead of the others, which are batched
+    #[must_use]
+    pub const fn with_approval_priority(mut self, priority: Option<ApprovalPriority>) -> Self {
+        self.approval_priority = priority;
+        self
+    }
+
+    /// Gives the session a scratch directory, kept until Claude exits
+    #[must_use]
+    pub fn with_scratch(mut self, lease: Option<ScratchLease>) -> Self {
+        self.scratch_dir = lease.as_ref().map(|lease| lease.path().to_path_buf());
+        self.scratch_lease = std::sync::Mutex::new(lease);
+        self
+    }
+
+    #[must_use]
+    pub fn scratch_dir(&self) -> Option<&Path> {
+        self.scratch_dir.as_deref()
+    }
+
+    #[must_use]
+    pub const fn approval_priority(&self) -> Option<ApprovalPriority> {
+        self.approval_priority
+    }
+
+    /// Lane approval requests of `risk` are delivered in
+    #[must_use]
+    pub fn approval_lane(&self, risk: RiskLevel) -> Priority {
+        approval_priority::classify(self.approval_priority, risk)
+    }
+
+    /// Restricts who may allow approvals of each risk level
+    #[must_use]
+    pub fn with_risk_approvers(mut self, approvers: RiskApprovers) -> Self {
+        self.risk_approvers = approvers;
+        self
+    }
+
+    /// Checks that `decided_by` may send `response` to the pending approval `id`.
+    /// Responses to unknown IDs pass, as they are dropped when forwarded anyway.
+    ///
+    /// # Errors
+    ///
+    /// Returns why the response is refused.
+    pub async fn check_approval_response(
+        &self,
+        id: &str,
+        response: Option<&serde_json::Value>,
+        decided_by: &str,
+    ) -> Result<(), String> {
+        match self.pending_approvals.lock().await.get(id) {
+            Some(_) if self.is_decided_elsewhere(id) => Err(ALREADY_DECIDED.to_string()),
+            Some(request) => self
+                .risk_approvers
+                .check(request.risk, response, decided_by),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether a pending approval was answered on another instance, with the answer
+    /// yet to reach Claude
+    fn is_decided_elsewhere(&self, id: &str) -> bool {
+        self.approval_sync
+            .as_ref()
+            .is_some_and(|sync| sync.is_decided(id))
+    }
+
+    /// Records that `request` was answered with `response` by `decided_by`
+    pub fn record_approval_decision(
+        &self,
+        request: &ApprovalRequest,
+        response: &serde_json::Value,
+        decided_by: &str,
+    ) {
+        if let Some(audit) = &self.approval_audit {
+            audit.record(ApprovalAuditEntry::new(request, response, decided_by));
+        }
+        if let (Some(slo), Ok(waited)) = (&self.slo, request.created_at.elapsed()) {
+            slo.record(Operation::ApprovalRoundTrip, waited);
+        }
+        trace_export::record_approval_round_trip(request, decided_by);
+    }
+
+    pub async fn add_client(&self, client: WebSocketClient) {
+        let mut clients = self.clients.write().await;
+        clients.push(client);
+    }
+
+    pub async fn remove_client(&self, client_id: &str) {
+        let mut clients = self.clients.write().await;
+        clients.retain(|c| c.id != client_id);
+    }
+
+    #[must_use]
+    #[allow(dead_code)] // Used by tests and library consumers
+    pub async fn get_clients(&self) -> Vec<WebSocketClient> {
+        let clients = self.clients.read().await;
+        clients.clone()
+    }
+
+    /// Unacknowledged message counts for clients that use ack frames
+    #[must_use]
+    #[allow(dead_code)] // Used by tests and library consumers
+    pub async fn get_delivery_lag(&self) -> Vec<(String, u64)> {
+        let clients = self.clients.read().await;
+        clients
+            .iter()
+            .filter_map(|c| c.delivery.as_ref().map(|d| (c.id.clone(), d.lag())))
+            .collect()
+    }
+
+    /// Queues a message for Claude. Returns where it waits when pacing holds it back.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the write pacing lock is poisoned.
+    pub async fn enqueue_message(&self, message: WriteMessage) -> Option<PacedPosition> {
+        if let Some(dir) = &self.input_record_dir {
+            let input = RecordedInput::new(
+                self.get_id().await,
+                message.sender_client_id.clone(),
+                message.timestamp.into(),
+                &message.content,
+            );
+            input_record::append(dir, &input).await;
+        }
+        let mut queue = self.write_queue.lock().await;
+        queue.push_back(message);
+        self.persist_write_queue(&queue).await;
+        let position = queue.len();
+        let estimated_wait = self
+            .write_pacing
+            .as_ref()?
+            .lock()
+            .expect("write pacing lock poisoned")
+            .wait_for(position, Instant::now())?;
+        Some(PacedPosition {
+            position,
+            estimated_wait,
+        })
+    }
+
+    #[allow(dead_code)] // Used by tests and library consumers
+    pub async fn dequeue_message(&self) -> Option<WriteMessage> {
+        let mut queue = self.write_queue.lock().await;
+        queue.pop_front()
+    }
+
+    /// Takes the next message to write to Claude, if pacing allows a write now.
+    ///
+    /// # Errors
+    ///
+    /// Returns how long until pacing allows the next write; the message stays queued.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the write pacing lock is poisoned.
+    pub async fn dequeue_paced_message(&self) -> Result<Option<WriteMessage>, Duration> {
+        let mut queue = self.write_queue.lock().await;
+        if queue.is_empty() {
+            return Ok(None);
+        }
+        if let Some(pacing) = &self.write_pacing {
+            pacing
+                .lock()
+                .expect("write pacing lock poisoned")
+                .try_take(Instant::now())?;
+        }
+        let message = queue.pop_front();
+        // Stays on disk until `confirm_written`
+        if self.state_dir.is_some() {
+            self.in_flight.lock().await.clone_from(&message);
+        }
+        Ok(message)
+    }
+
+    /// Notes that the message last taken off the write queue reached Claude's stdin, so
+    /// it is not replayed anymore.
+    pub async fn confirm_written(&self) {
+        let queue = self.write_queue.lock().await;
+        self.in_flight.lock().await.take();
+        self.persist_write_queue(&queue).await;
+    }
+
+    /// Takes every message not confirmed written to Claude, the one on its way first,
+    /// leaving them on disk until `confirm_written`.
+    pub async fn take_unwritten(&self) -> Vec<WriteMessage> {
+        let mut queue = self.write_queue.lock().await;
+        let in_flight = self.in_flight.lock().await.take();
+        in_flight.into_iter().chain(queue.drain(..)).collect()
+    }
+
+    /// Puts messages taken with `take_unwritten` back at the front of the write queue.
+    pub async fn requeue(&self, messages: Vec<WriteMessage>) {
+        let mut queue = self.write_queue.lock().await;
+        for message in messages.into_iter().rev() {
+            queue.push_front(message);
+        }
+    }
+
+    /// Applies the disconnect `policy` to the messages `client_id` enqueued that have not
+    /// been written to Claude yet. Returns how many were dropped or held.
+    pub async fn handle_client_disconnect(
+        &self,
+        client_id: &str,
+        policy: DisconnectQueuePolicy,
+    ) -> usize {
+        if policy == DisconnectQueuePolicy::Keep {
+            return 0;
+        }
+
+        let removed: Vec<WriteMessage> = {
+            let mut queue = self.write_queue.lock().await;
+            let (removed, kept): (VecDeque<_>, _) = queue
+                .drain(..)
+                .partition(|message| message.sender_client_id == client_id);
+            *queue = kept;
+            self.persist_write_queue(&queue).await;
+            removed.into()
+        };
+        let count = removed.len();
+        if policy == DisconnectQueuePolicy::Hold {
+            self.held_messages.lock().await.extend(removed);
+        }
+        count
+    }
+
+    /// Puts `client_id`'s held messages back at the end of the write queue, or discards
+    /// them when `release` is false. Returns how many there were.
+    pub async fn resolve_held_messages(&self, client_id: &str, release: bool) -> usize {
+        let resolved: Vec<WriteMessage> = {
+            let mut held = self.held_messages.lock().await;
+            let (resolved, kept) = held
+                .drain(..)
+                .partition(|message| message.sender_client_id == client_id);
+            *held = kept;
+            resolved
+        };
+        let count = resolved.len();
+        if release {
+            let mut queue = self.write_queue.lock().await;
+            queue.extend(resolved);
+            self.persist_write_queue(&queue).await;
+        }
+        count
+    }
+
+    /// Clients connected to the main and approval channels, with when they were last seen
+    #[must_use]
+    pub async fn connected_clients(&self) -> Vec<ConnectedClient> {
+        let main = self.clients.read().await;
+        let approval = self.approval_clients.read().await;
+        main.iter()
+            .map(|client| ConnectedClient {
+                client_id: client.id.clone(),
+                channel: ClientChannel::Main,
+                user_agent: client.user_agent.clone(),
+                connected_at: client.connected_at.into(),
+                last_seen_at: client.liveness.last_seen(),
+            })
+            .chain(approval.iter().map(|client| ConnectedClient {
+                client_id: client.id.clone(),
+                channel: ClientChannel::Approval,
+                user_agent: client.user_agent.clone(),
+                connected_at: client.connected_at.into(),
+                last_seen_at: client.liveness.last_seen(),
+            }))
+            .collect()
+    }
+
+    /// Unwritten messages grouped by the client that sent them
+    #[must_use]
+    pub async fn get_client_queues(&self) -> Vec<ClientQueue> {
+        let queued: Vec<WriteMessage> = self.write_queue.lock().await.iter().cloned().collect();
+        let held = self.held_messages.lock().await.clone();
+        let clients = self.clients.read().await;
+
+        let mut by_client: Vec<ClientQueue> = Vec::new();
+        for (message, is_held) in queued
+            .iter()
+            .map(|m| (m, false))
+            .chain(held.iter().map(|m| (m, true)))
+        {
+            let index = if let Some(index) = by_client
+                .iter()
+                .position(|q| q.client_id == message.sender_client_id)
+            {
+                index
+            } else {
+                by_client.push(ClientQueue {
+                    client_id: message.sender_client_id.clone(),
+                    connected: clients.iter().any(|c| c.id == message.sender_client_id),
+                    queued: Vec::new(),
+                    held: Vec::new(),
+                });
+                by_client.len() - 1
+            };
+            let queue = &mut by_client[index];
+            if is_held {
+                queue.held.push(message.into());
+            } else {
+                queue.queued.push(message.into());
+            }
+        }
+        by_client
+    }
+
+    pub async fn set_status(&self, status: SessionStatus) {
+        let mut current_status = self.status.write().await;
+        *current_status = status;
+    }
+
+    #[must_use]
+    pub async fn get_status(&self) -> SessionStatus {
+        let status = self.status.read().await;
+        *status
+    }
+
+    #[must_use]
+    pub async fn is_active(&self) -> bool {
+        let process_id = self.process_id.read().await;
+        process_id.is_some() || *self.backend_connected.read().await
+    }
+
+    pub async fn set_process_stats(&self, stats: ProcessStats) {
+        *self.process_stats.write().await = Some(stats);
+    }
+
+    /// The latest sample of the Claude process, if it is being sampled
+    #[must_use]
+    pub async fn get_process_stats(&self) -> Option<ProcessStats> {
+        *self.process_stats.read().await
+    }
+
+    /// Accounts the tokens and cost of the turn `result` ended.
+    pub async fn record_usage(&self, result: &ResultMessage) {
+        let Some(usage) = &self.usage else {
+            return;
+        };
+        let cost_usd = result.total_cost_usd.map_or(0.0, |total| {
+            let mut reported = self
+                .reported_cost_usd
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            // A lower total comes from a new run, e.g. after a restart
+            let cost = if total >= *reported {
+                total - *reported
+            } else {
+                total
+            };
+            *reported = total;
+            cost
+        });
+        usage.record(
+            &self.get_id().await,
+            &self.working_dir,
+            self.owner(),
+            result.usage.as_ref(),
+            cost_usd,
+        );
+    }
+
+    /// Counts a turn Claude has completed and returns the total so far.
+    pub fn record_turn(&self) -> u32 {
+        self.turn_in_progress.store(false, Ordering::Relaxed);
+        self.turns.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Notes that input was written to Claude, which starts a turn.
+    pub fn start_turn(&self) {
+        self.turn_in_progress.store(true, Ordering::Relaxed);
+    }
+
+    /// Notes that the orchestrator is ending Claude, so its exit is not a crash.
+    pub fn expect_exit(&self) {
+        self.exit_requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the orchestrator asked Claude to exit.
+    #[must_use]
+    pub fn exit_expected(&self) -> bool {
+        self.exit_requested.load(Ordering::Relaxed)
+    }
+
+    pub fn mark_started(&self) {
+        self.started.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the session was handed to whoever started it.
+    #[must_use]
+    pub fn has_started(&self) -> bool {
+        self.started.load(Ordering::Relaxed)
+    }
+
+    /// Counts a restart after a crash and returns the total so far.
+    pub fn record_restart(&self) -> u32 {
+        self.restarts.fetch_add(1, Ordering::Relaxed) + 1
     }
 
-    pub async fn add_client(&self, client: WebSocketClient) {
-        let mut clients = self.clients.write().await;
-        clients.push(client);
+    pub fn set_restarting(&self, restarting: bool) {
+        self.restarting.store(restarting, Ordering::Relaxed);
     }
 
-    pub async fn remove_client(&self, client_id: &str) {
-        let mut clients = self.clients.write().await;
-        clients.retain(|c| c.id != client_id);
+    /// Whether a crashed Claude is being respawned; input is queued for it meanwhile.
+    #[must_use]
+    pub fn is_restarting(&self) -> bool {
+        self.restarting.load(Ordering::Relaxed)
     }
 
+    /// Whether Claude has no queued input left and is not in the middle of a turn.
     #[must_use]
-    pub async fn get_clients(&self) -> Vec<WebSocketClient> {
-        let clients = self.clients.read().await;
-        clients.clone()
+    pub async fn is_drained(&self) -> bool {
+        !self.turn_in_progress.load(Ordering::Relaxed) && self.write_queue.lock().await.is_empty()
     }
 
-    pub async fn enqueue_message(&self, message: WriteMessage) {
-        let mut queue = self.write_queue.lock().await;
-        queue.push_back(message);
+    /// Processes, clients and backlogs of the session, as the admin API reports them
+    #[must_use]
+    pub async fn admin_state(&self) -> AdminSessionState {
+        // Each lock is let go before the next is taken
+        let clients = self.clients.read().await.len();
+        let approval_clients = self.approval_clients.read().await.len();
+        let queued_messages = self.write_queue.lock().await.len();
+        let held_messages = self.held_messages.lock().await.len();
+        let pending_approvals = self.pending_approvals.lock().await.len();
+        AdminSessionState {
+            session_id: self.get_id().await,
+            working_dir: self.working_dir.clone(),
+            status: self.get_status().await,
+            process_id: self.get_process_id().await,
+            clients,
+            approval_clients,
+            queued_messages,
+            held_messages,
+            pending_approvals,
+            drained: self.is_drained().await,
+            uptime_secs: self.opened_at.elapsed().as_secs(),
+        }
     }
 
-    pub async fn dequeue_message(&self) -> Option<WriteMessage> {
-        let mut queue = self.write_queue.lock().await;
-        queue.pop_front()
+    /// Moves the session to [`SessionStatus::LimitReached`] unless it already ended,
+    /// dropping unwritten messages and telling its clients which limit was hit.
+    /// Returns whether the status changed.
+    pub async fn reach_limit(&self, limit: &str) -> bool {
+        {
+            let mut status = self.status.write().await;
+            if matches!(
+                *status,
+                SessionStatus::LimitReached
+                    | SessionStatus::ResourceLimitExceeded
+                    | SessionStatus::Exited
+                    | SessionStatus::Failed
+            ) {
+                return false;
+            }
+            *status = SessionStatus::LimitReached;
+        }
+        // Nothing accepted before the limit is written to Claude either
+        {
+            let mut queue = self.write_queue.lock().await;
+            queue.clear();
+            self.persist_write_queue(&queue).await;
+        }
+
+        let notice = serde_json::json!({
+            "type": "system",
+            "subtype": "limit_reached",
+            "limit": limit,
+            "turns": self.turns.load(Ordering::Relaxed),
+        });
+        let _ = self.broadcast_message(BroadcastMessage::ClaudeOutput {
+            line: notice.to_string().into(),
+            received_at: chrono::Utc::now(),
+        });
+        true
     }
 
-    pub async fn set_status(&self, status: SessionStatus) {
-        let mut current_status = self.status.write().await;
-        *current_status = status;
+    /// Records that Claude was killed for exceeding `limit`, moving the session to
+    /// [`SessionStatus::ResourceLimitExceeded`] and telling its clients which limit it was.
+    pub async fn exceed_resource_limit(&self, limit: ResourceLimit) {
+        if self.resource_limit.set(limit).is_err() {
+            return;
+        }
+        *self.status.write().await = SessionStatus::ResourceLimitExceeded;
+
+        let notice = serde_json::json!({
+            "type": "system",
+            "subtype": "resource_limit_exceeded",
+            "resource": limit,
+        });
+        let _ = self.broadcast_message(BroadcastMessage::ClaudeOutput {
+            line: notice.to_string().into(),
+            received_at: chrono::Utc::now(),
+        });
     }
 
+    /// Limit Claude was killed for exceeding, if it was.
     #[must_use]
-    pub async fn get_status(&self) -> SessionStatus {
-        let status = self.status.read().await;
-        *status
+    pub fn resource_limit(&self) -> Option<ResourceLimit> {
+        self.resource_limit.get().copied()
     }
 
-    #[must_use]
-    pub async fn is_active(&self) -> bool {
-        let process_id = self.process_id.read().await;
-        process_id.is_some()
+    /// Moves the session to its terminal state once Claude is gone and drops what only
+    /// a running Claude could have used: unwritten messages and client presence. A
+    /// session that reached a limit keeps that status.
+    pub async fn mark_exited(&self) {
+        {
+            let mut status = self.status.write().await;
+            if !matches!(
+                *status,
+                SessionStatus::LimitReached | SessionStatus::ResourceLimitExceeded
+            ) {
+                *status = SessionStatus::Exited;
+            }
+        }
+        self.write_queue.lock().await.clear();
+        self.held_messages.lock().await.clear();
+        self.presence.write().await.clear();
+        // Starts the retention period of the scratch directory
+        self.scratch_lease
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .take();
+        self.exited.send_replace(true);
+    }
+
+    /// Waits until [`Session::mark_exited`] has been called
+    pub async fn wait_exited(&self) {
+        let mut exited = self.exited.subscribe();
+        let _ = exited.wait_for(|exited| *exited).await;
+    }
+
+    pub async fn set_backend_connected(&self, connected: bool) {
+        *self.backend_connected.write().await = connected;
+        self.announce_activity().await;
     }
 
     pub async fn set_process_id(&self, pid: Option<u32>) {
-        let mut process_id = self.process_id.write().await;
-        *process_id = pid;
+        *self.process_id.write().await = pid;
+        self.announce_activity().await;
     }
 
     #[must_use]
@@ -221,8 +2335,36 @@ impl Session {
     }
 
     pub async fn set_id(&self, new_id: String) {
-        let mut id = self.id.write().await;
-        *id = new_id;
+        // Held so unsent messages are not persisted under the old ID meanwhile
+        let _queue = self.write_queue.lock().await;
+        let old_id = std::mem::replace(&mut *self.id.write().await, new_id.clone());
+        if let Some(wire_log) = self
+            .wire_log
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .as_mut()
+        {
+            wire_log.rename(&new_id);
+        }
+
+        // Unsent messages follow the session to its new ID
+        if let Some(state_dir) = &self.state_dir {
+            let from = pending_writes_path(state_dir, &old_id);
+            match tokio::fs::rename(&from, pending_writes_path(state_dir, &new_id)).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => tracing::warn!(
+                    path = %from.display(),
+                    error = %e,
+                    "Failed to move unsent messages to the new session ID"
+                ),
+                _ => {}
+            }
+        }
+    }
+
+    /// Identity of the user who created the session, if known
+    #[must_use]
+    pub fn owner(&self) -> Option<&str> {
+        self.options.owner.as_deref()
     }
 
     /// Broadcasts a message to all or filtered clients
@@ -233,20 +2375,144 @@ impl Session {
     pub fn broadcast_message(
         &self,
         message: BroadcastMessage,
-    ) -> Result<usize, broadcast::error::SendError<BroadcastMessage>> {
-        self.broadcast_tx.send(message)
+    ) -> Result<usize, broadcast::error::SendError<BroadcastEnvelope>> {
+        #[cfg(feature = "testing")]
+        if self.faults.drop_broadcast() {
+            tracing::warn!(message = ?message, "Injected fault: dropped broadcast message");
+            return Ok(0);
+        }
+        // Sent under the lock, so receivers get broadcasts in sequence order
+        let mut log = self.broadcast_log();
+        log.last_seq += 1;
+        let envelope = BroadcastEnvelope {
+            seq: log.last_seq,
+            message,
+        };
+        if envelope.message.is_replayable() {
+            if log.recent.len() == BROADCAST_REPLAY_CAPACITY {
+                if let Some(dropped) = log.recent.pop_front() {
+                    log.dropped_through = dropped.seq;
+                }
+            }
+            log.recent.push_back(envelope.clone());
+        }
+        self.broadcast_tx.send(envelope)
+    }
+
+    fn broadcast_log(&self) -> std::sync::MutexGuard<'_, BroadcastLog> {
+        self.broadcast_log
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
     }
 
     /// Get a receiver for broadcast messages
     #[must_use]
-    pub fn subscribe_to_broadcasts(&self) -> broadcast::Receiver<BroadcastMessage> {
+    pub fn subscribe_to_broadcasts(&self) -> broadcast::Receiver<BroadcastEnvelope> {
         self.broadcast_tx.subscribe()
     }
 
+    /// Gets a receiver for broadcast messages, together with the replayable ones sent
+    /// after `after_seq` that are still buffered. Nothing is lost or received twice
+    /// between the two.
+    #[must_use]
+    pub fn resume_broadcasts(
+        &self,
+        after_seq: u64,
+    ) -> (broadcast::Receiver<BroadcastEnvelope>, BroadcastReplay) {
+        let log = self.broadcast_log();
+        let replay = BroadcastReplay {
+            missed: log
+                .recent
+                .iter()
+                .filter(|envelope| envelope.seq > after_seq)
+                .cloned()
+                .collect(),
+            complete: log.dropped_through <= after_seq && after_seq <= log.last_seq,
+        };
+        (self.broadcast_tx.subscribe(), replay)
+    }
+
+    /// Records a non-JSON stdout line and returns the new total
+    pub fn record_raw_output_line(&self) -> u64 {
+        self.raw_output_lines.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    #[must_use]
+    pub fn get_raw_output_lines(&self) -> u64 {
+        self.raw_output_lines.load(Ordering::Relaxed)
+    }
+
+    // Debug capture methods
+    /// Starts (or restarts) debug capture into a new file under `dir` and turns on
+    /// trace logging for this session. Returns the capture file and a generation
+    /// number to pass to [`Session::stop_debug_capture`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the capture file cannot be created.
+    pub async fn start_debug_capture(
+        &self,
+        dir: &std::path::Path,
+    ) -> std::io::Result<(PathBuf, u64)> {
+        let session_id = self.get_id().await;
+        let capture = DebugCapture::create(dir, &session_id)?;
+        let path = capture.path.clone();
+        *self.debug_capture.lock().await = Some(capture);
+        crate::debug_capture::enable_session_tracing(&session_id);
+        let generation = self.debug_generation.fetch_add(1, Ordering::Relaxed) + 1;
+        Ok((path, generation))
+    }
+
+    /// Stops debug capture. With a generation, only stops if no newer capture has
+    /// been started since. Returns whether a capture was stopped.
+    pub async fn stop_debug_capture(&self, generation: Option<u64>) -> bool {
+        let mut capture = self.debug_capture.lock().await;
+        if generation.is_some_and(|g| g != self.debug_generation.load(Ordering::Relaxed)) {
+            return false;
+        }
+        crate::debug_capture::disable_session_tracing(&self.get_id().await);
+        capture.take().is_some()
+    }
+
+    /// Appends a line of Claude traffic to the wire log and the debug capture, where
+    /// they are on. `origin` is the client that sent it, or the identity that answered
+    /// an approval.
+    pub async fn record_traffic(&self, direction: Direction, origin: Option<&str>, line: &str) {
+        if let Some(wire_log) = self
+            .wire_log
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .as_mut()
+        {
+            wire_log.record(direction, origin, line);
+        }
+        if let Some(capture) = self.debug_capture.lock().await.as_mut() {
+            capture.record(direction, line);
+        }
+    }
+
+    // Presence methods
+    pub async fn set_presence(&self, client_id: &str, state: serde_json::Value) {
+        let mut presence = self.presence.write().await;
+        presence.insert(client_id.to_string(), state);
+    }
+
+    pub async fn remove_presence(&self, client_id: &str) -> Option<serde_json::Value> {
+        let mut presence = self.presence.write().await;
+        presence.remove(client_id)
+    }
+
+    #[must_use]
+    pub async fn get_presence(&self) -> HashMap<String, serde_json::Value> {
+        let presence = self.presence.read().await;
+        presence.clone()
+    }
+
     // Approval system methods
     pub async fn add_approval_client(&self, client: ApprovalWebSocketClient) {
         let mut clients = self.approval_clients.write().await;
         clients.push(client);
+        self.approval_attendance.fetch_add(1, Ordering::SeqCst);
     }
 
     pub async fn remove_approval_client(&self, client_id: &str) {
@@ -254,20 +2520,298 @@ impl Session {
         clients.retain(|c| c.id != client_id);
     }
 
-    #[must_use]
-    pub async fn get_approval_clients(&self) -> Vec<ApprovalWebSocketClient> {
+    /// Marks the approvals as unattended if no approval client is left while some are
+    /// pending. Returns the mark to pass to [`Self::deny_unattended_approvals`] and how
+    /// many approvals are pending.
+    pub async fn approvals_unattended(&self) -> Option<(u64, usize)> {
         let clients = self.approval_clients.read().await;
-        clients.clone()
+        let pending = self.pending_approvals.lock().await.len();
+        if !clients.is_empty() || pending == 0 {
+            return None;
+        }
+        Some((
+            self.approval_attendance.fetch_add(1, Ordering::SeqCst) + 1,
+            pending,
+        ))
+    }
+
+    /// Denies every pending approval, unless an approval client connected since `mark`
+    /// was taken. Returns how many approvals were denied.
+    pub async fn deny_unattended_approvals(&self, mark: u64) -> usize {
+        let _clients = self.approval_clients.read().await;
+        if self.approval_attendance.load(Ordering::SeqCst) != mark {
+            return 0;
+        }
+        let ids: Vec<String> = self
+            .pending_approvals
+            .lock()
+            .await
+            .keys()
+            .cloned()
+            .collect();
+        ids.iter()
+            .filter(|id| {
+                let response = serde_json::json!({
+                    "id": id,
+                    "response": {
+                        "behavior": "deny",
+                        "message": "No approver was connected to answer this request",
+                    },
+                });
+                self.broadcast_approval_message(ApprovalMessage::response(
+                    response,
+                    DISCONNECT_POLICY_IDENTITY,
+                ))
+                .is_ok()
+            })
+            .count()
     }
 
     pub async fn add_pending_approval(&self, request: ApprovalRequest) {
         let mut pending = self.pending_approvals.lock().await;
         pending.insert(request.id.clone(), request); // Updated to use id instead of request_id
+        self.persist_pending_approvals(&pending).await;
+        self.announce(ServerEventKind::PendingApprovals {
+            count: pending.len(),
+        })
+        .await;
+    }
+
+    #[must_use]
+    pub async fn is_pending_approval(&self, id: &str) -> bool {
+        self.pending_approvals.lock().await.contains_key(id)
     }
 
     pub async fn remove_pending_approval(&self, request_id: &str) -> Option<ApprovalRequest> {
         let mut pending = self.pending_approvals.lock().await;
-        pending.remove(request_id)
+        let removed = pending.remove(request_id);
+        if removed.is_some() {
+            self.persist_pending_approvals(&pending).await;
+            self.announce(ServerEventKind::PendingApprovals {
+                count: pending.len(),
+            })
+            .await;
+        }
+        removed
+    }
+
+    /// Where pending approvals are persisted; nowhere for IDs that are not file names.
+    async fn approvals_file(&self) -> Option<PathBuf> {
+        let state_dir = self.state_dir.as_ref()?;
+        let session_id = self.get_id().await;
+        is_valid_session_id(&session_id)
+            .then(|| state_dir.join(format!("{session_id}.approvals.json")))
+    }
+
+    /// Publishes the pending approvals to the other instances, if they are shared
+    async fn publish_pending_approvals(&self, pending: &HashMap<String, ApprovalRequest>) {
+        if let Some(sync) = &self.approval_sync {
+            sync.publish(
+                &self.get_id().await,
+                self.owner(),
+                pending.values().cloned().collect(),
+            )
+            .await;
+        }
+    }
+
+    /// Writes the pending approvals to disk, removing the file once none are left.
+    /// Called with the `pending_approvals` lock held so writes stay ordered.
+    async fn persist_pending_approvals(&self, pending: &HashMap<String, ApprovalRequest>) {
+        self.publish_pending_approvals(pending).await;
+        let Some(path) = self.approvals_file().await else {
+            return;
+        };
+
+        let result = if pending.is_empty() {
+            match tokio::fs::remove_file(&path).await {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                other => other,
+            }
+        } else {
+            let requests: Vec<&ApprovalRequest> = pending.values().collect();
+            let tmp_path = path.with_extension("json.tmp");
+            match serde_json::to_vec(&requests) {
+                Ok(bytes) => match tokio::fs::write(&tmp_path, bytes).await {
+                    Ok(()) => tokio::fs::rename(&tmp_path, &path).await,
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e.into()),
+            }
+        };
+
+        if let Err(e) = result {
+            tracing::warn!(
+                path = %path.display(),
+                error = %e,
+                "Failed to persist pending approvals"
+            );
+        }
+    }
+
+    /// Writes the messages not yet written to Claude to disk, one JSON object per line,
+    /// removing the file once none are left. Called with the `write_queue` lock held so
+    /// writes stay ordered.
+    async fn persist_write_queue(&self, queue: &VecDeque<WriteMessage>) {
+        let Some(state_dir) = &self.state_dir else {
+            return;
+        };
+        let path = pending_writes_path(state_dir, &self.get_id().await);
+        let in_flight = self.in_flight.lock().await.clone();
+
+        let result = if in_flight.is_none() && queue.is_empty() {
+            match tokio::fs::remove_file(&path).await {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                other => other,
+            }
+        } else {
+            let tmp_path = path.with_extension("jsonl.tmp");
+            match in_flight
+                .iter()
+                .chain(queue)
+                .map(|message| serde_json::to_string(message).map(|line| line + "\n"))
+                .collect::<Result<String, _>>()
+            {
+                Ok(lines) => match tokio::fs::write(&tmp_path, lines).await {
+                    Ok(()) => tokio::fs::rename(&tmp_path, &path).await,
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e.into()),
+            }
+        };
+
+        if let Err(e) = result {
+            tracing::warn!(
+                path = %path.display(),
+                error = %e,
+                "Failed to persist unsent messages"
+            );
+        }
+    }
+
+    /// Messages a previous run of this session never wrote to Claude, oldest first.
+    pub async fn unsent_writes(&self) -> Vec<WriteMessage> {
+        let Some(state_dir) = &self.state_dir else {
+            return Vec::new();
+        };
+        let path = pending_writes_path(state_dir, &self.get_id().await);
+        let Ok(contents) = tokio::fs::read_to_string(&path).await else {
+            return Vec::new();
+        };
+        contents
+            .lines()
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(message) => Some(message),
+                Err(e) => {
+                    tracing::warn!(
+                        path = %path.display(),
+                        error = %e,
+                        "Skipping unreadable unsent message"
+                    );
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Loads approvals persisted by a previous orchestrator run into the pending set.
+    /// Returns the restored requests so they can be re-broadcast.
+    pub async fn restore_pending_approvals(&self) -> Vec<ApprovalRequest> {
+        let Some(path) = self.approvals_file().await else {
+            return Vec::new();
+        };
+
+        let restored: Vec<ApprovalRequest> = match tokio::fs::read(&path).await {
+            Ok(bytes) => match serde_json::from_slice(&bytes) {
+                Ok(requests) => requests,
+                Err(e) => {
+                    tracing::warn!(
+                        path = %path.display(),
+                        error = %e,
+                        "Ignoring unreadable pending approvals file"
+                    );
+                    return Vec::new();
+                }
+            },
+            Err(_) => return Vec::new(),
+        };
+
+        let mut pending = self.pending_approvals.lock().await;
+        for request in &restored {
+            pending.insert(request.id.clone(), request.clone());
+        }
+        if !restored.is_empty() {
+            self.publish_pending_approvals(&pending).await;
+            self.announce(ServerEventKind::PendingApprovals {
+                count: pending.len(),
+            })
+            .await;
+        }
+        restored
+    }
+
+    /// Records an approver's question about a pending approval, which stays pending
+    /// until answered. Returns the updated request.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no approval with this ID is pending or an earlier question
+    /// about it is still unanswered.
+    pub async fn ask_clarification(
+        &self,
+        id: &str,
+        question: &str,
+        asked_by: &str,
+    ) -> Result<ApprovalRequest, String> {
+        let mut pending = self.pending_approvals.lock().await;
+        let request = pending
+            .get_mut(id)
+            .ok_or_else(|| "no pending approval with this id".to_string())?;
+        if request.awaits_clarification() {
+            return Err("an earlier question is still awaiting an answer".to_string());
+        }
+        request.clarifications.push(Clarification {
+            question: question.to_string(),
+            asked_by: asked_by.to_string(),
+            asked_at: chrono::Utc::now(),
+            answer: None,
+        });
+        let request = request.clone();
+        self.persist_pending_approvals(&pending).await;
+        Ok(request)
+    }
+
+    /// Attaches Claude's reply to every pending approval awaiting an answer to a
+    /// question. Returns the requests that were updated.
+    pub async fn answer_clarifications(&self, answer: &str) -> Vec<ApprovalRequest> {
+        let mut pending = self.pending_approvals.lock().await;
+        let mut answered = Vec::new();
+        for request in pending.values_mut() {
+            if let Some(clarification) = request
+                .clarifications
+                .last_mut()
+                .filter(|clarification| clarification.answer.is_none())
+            {
+                clarification.answer = Some(answer.to_string());
+                answered.push(request.clone());
+            }
+        }
+        if !answered.is_empty() {
+            self.persist_pending_approvals(&pending).await;
+        }
+        answered
+    }
+
+    /// Drops the persisted approvals, e.g. once the Claude process has exited
+    pub async fn clear_pending_approvals(&self) {
+        let mut pending = self.pending_approvals.lock().await;
+        let had_pending = !pending.is_empty();
+        pending.clear();
+        self.persist_pending_approvals(&pending).await;
+        if had_pending {
+            self.announce(ServerEventKind::PendingApprovals { count: 0 })
+                .await;
+        }
     }
 
     #[must_use]
@@ -276,6 +2820,14 @@ impl Session {
         pending.values().cloned().collect()
     }
 
+    /// Pending approvals in delivery order: high priority first, then oldest first
+    #[must_use]
+    pub async fn get_pending_approvals_by_priority(&self) -> Vec<ApprovalRequest> {
+        let mut pending = self.get_pending_approvals().await;
+        sort_by_priority(&mut pending);
+        pending
+    }
+
     /// Broadcasts an approval message to all approval clients
     ///
     /// # Errors
@@ -288,6 +2840,98 @@ impl Session {
         self.approval_broadcast_tx.send(message)
     }
 
+    /// Forwards several approval responses (`{id, response}` each) at once.
+    ///
+    /// Each item is checked and forwarded on its own, so one bad item does not hold up the
+    /// rest. An item is accepted when its ID is pending, was not already answered
+    /// earlier in the same batch, and `decided_by` may answer a request of its risk level.
+    pub async fn submit_approval_responses(
+        &self,
+        responses: Vec<serde_json::Value>,
+        decided_by: &str,
+    ) -> Vec<ApprovalResponseResult> {
+        let pending = self.pending_approvals.lock().await;
+        let mut seen = std::collections::HashSet::new();
+
+        responses
+            .into_iter()
+            .map(|item| {
+                let id = item
+                    .get("id")
+                    .and_then(|id| id.as_str())
+                    .map(ToString::to_string);
+                let error = match &id {
+                    None => Some("expected {id, response}".to_string()),
+                    Some(_) if item.get("response").is_none() => {
+                        Some("expected {id, response}".to_string())
+                    }
+                    Some(id) if !seen.insert(id.clone()) => {
+                        Some("duplicate id in batch".to_string())
+                    }
+                    Some(id) => match pending.get(id) {
+                        None => Some("no pending approval with this id".to_string()),
+                        Some(_) if self.is_decided_elsewhere(id) => {
+                            Some(ALREADY_DECIDED.to_string())
+                        }
+                        Some(request) => {
+                            match self.risk_approvers.check(
+                                request.risk,
+                                item.get("response"),
+                                decided_by,
+                            ) {
+                                Err(e) => Some(e),
+                                Ok(()) => self
+                                    .broadcast_approval_message(ApprovalMessage::response(
+                                        item, decided_by,
+                                    ))
+                                    .err()
+                                    .map(|e| format!("failed to forward response: {e}")),
+                            }
+                        }
+                    },
+                };
+                ApprovalResponseResult {
+                    id,
+                    accepted: error.is_none(),
+                    error,
+                }
+            })
+            .collect()
+    }
+
+    /// Forwards the response to one pending approval, checked like an item of
+    /// [`Self::submit_approval_responses`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no approval with this ID is pending, `decided_by` may not allow
+    /// it, or the response cannot be forwarded.
+    pub async fn submit_approval_response(
+        &self,
+        id: &str,
+        response: serde_json::Value,
+        decided_by: &str,
+    ) -> OrchestratorResult<()> {
+        let pending = self.pending_approvals.lock().await;
+        let request = pending
+            .get(id)
+            .ok_or_else(|| OrchestratorError::ApprovalNotFound(id.to_string()))?;
+        if self.is_decided_elsewhere(id) {
+            return Err(OrchestratorError::ApprovalAlreadyDecided(id.to_string()));
+        }
+        self.risk_approvers
+            .check(request.risk, Some(&response), decided_by)
+            .map_err(OrchestratorError::Forbidden)?;
+        self.broadcast_approval_message(ApprovalMessage::response(
+            serde_json::json!({"id": id, "response": response}),
+            decided_by,
+        ))
+        .map_err(|e| {
+            OrchestratorError::InternalError(format!("Failed to forward approval response: {e}"))
+        })?;
+        Ok(())
+    }
+
     /// Get a receiver for approval broadcast messages
     #[must_use]
     pub fn subscribe_to_approval_broadcasts(&self) -> broadcast::Receiver<ApprovalMessage> {
@@ -303,8 +2947,17 @@ impl WebSocketClient {
             ip_address,
             user_agent,
             connected_at: std::time::SystemTime::now(),
+            delivery: None,
+            liveness: Arc::default(),
         }
     }
+
+    /// Enables seq tagging and ack tracking for this client
+    #[must_use]
+    pub fn with_delivery_tracking(mut self) -> Self {
+        self.delivery = Some(Arc::new(DeliveryTracker::default()));
+        self
+    }
 }
 
 impl ApprovalWebSocketClient {
@@ -315,6 +2968,7 @@ impl ApprovalWebSocketClient {
             ip_address,
             user_agent,
             connected_at: std::time::SystemTime::now(),
+            liveness: Arc::default(),
         }
     }
 }
@@ -347,6 +3001,237 @@ mod tests {
         assert_eq!(remaining_clients[0].id, "client2");
     }
 
+    #[tokio::test]
+    async fn test_session_presence() {
+        let session = Session::new("test-session".to_string(), PathBuf::from("/tmp"));
+
+        session
+            .set_presence("client1", serde_json::json!({"viewing": "uuid-1"}))
+            .await;
+        session
+            .set_presence("client1", serde_json::json!({"viewing": "uuid-2"}))
+            .await;
+        session
+            .set_presence("client2", serde_json::json!({"viewing": "uuid-3"}))
+            .await;
+
+        let presence = session.get_presence().await;
+        assert_eq!(presence.len(), 2);
+        assert_eq!(presence["client1"]["viewing"], "uuid-2");
+
+        assert!(session.remove_presence("client1").await.is_some());
+        assert!(session.remove_presence("client1").await.is_none());
+        assert_eq!(session.get_presence().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_submit_approval_responses() {
+        let session = Session::new("bulk".to_string(), PathBuf::from("/tmp"))
+            .with_risk_approvers("destructive=admin".parse().unwrap());
+        let mut approval_rx = session.subscribe_to_approval_broadcasts();
+        for (id, risk) in [
+            ("pending", RiskLevel::ReadOnly),
+            ("risky", RiskLevel::Destructive),
+        ] {
+            session
+                .add_pending_approval(ApprovalRequest {
+                    id: id.to_string(),
+                    session_id: "bulk".to_string(),
+                    claude_request_id: format!("claude-{id}"),
+                    request: serde_json::json!({"tool_name": "Read"}),
+                    created_at: std::time::SystemTime::now(),
+                    clarifications: Vec::new(),
+                    risk,
+                    risk_reasons: Vec::new(),
+                    priority: Priority::default(),
+                })
+                .await;
+        }
+
+        let allow = serde_json::json!({"behavior": "allow"});
+        let results = session
+            .submit_approval_responses(
+                vec![
+                    serde_json::json!({"id": "pending", "response": allow}),
+                    serde_json::json!({"id": "pending", "response": allow}),
+                    serde_json::json!({"id": "unknown", "response": allow}),
+                    serde_json::json!({"id": "pending"}),
+                    serde_json::json!({"id": "risky", "response": allow}),
+                ],
+                "alice",
+            )
+            .await;
+
+        let accepted: Vec<bool> = results.iter().map(|r| r.accepted).collect();
+        assert_eq!(accepted, vec![true, false, false, false, false]);
+        assert_eq!(
+            results[2].error.as_deref(),
+            Some("no pending approval with this id")
+        );
+        assert_eq!(
+            results[4].error.as_deref(),
+            Some("only admin may allow destructive requests")
+        );
+
+        // Only the accepted item is forwarded
+        assert!(matches!(
+            approval_rx.try_recv(),
+            Ok(ApprovalMessage::ApprovalResponse(response))
+                if response["id"] == "pending" && response[DECIDED_BY_FIELD] == "alice"
+        ));
+        assert!(approval_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_approval_request_frame_shared() {
+        let message = ApprovalMessage::request(ApprovalRequest {
+            id: "approval-1".to_string(),
+            session_id: "shared".to_string(),
+            claude_request_id: "claude-1".to_string(),
+            request: serde_json::json!({"tool_name": "Bash"}),
+            created_at: std::time::UNIX_EPOCH + std::time::Duration::from_secs(42),
+            clarifications: Vec::new(),
+            risk: RiskLevel::WorkspaceWrite,
+            risk_reasons: Vec::new(),
+            priority: Priority::default(),
+        });
+
+        // Every receiver gets the same frame, not its own serialization
+        let copy = message.clone();
+        let (
+            ApprovalMessage::ApprovalRequest { frame, .. },
+            ApprovalMessage::ApprovalRequest { frame: copy, .. },
+        ) = (&message, &copy)
+        else {
+            panic!("expected an approval request");
+        };
+        assert!(Arc::ptr_eq(frame, copy));
+
+        let frame: serde_json::Value = serde_json::from_str(frame).unwrap();
+        assert_eq!(
+            frame,
+            serde_json::json!({
+                "id": "approval-1",
+                "request": {"tool_name": "Bash"},
+                "created_at": 42,
+                "risk": "workspace_write",
+                "priority": "normal",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pending_approvals_persistence() {
+        let state_dir = tempfile::TempDir::new().unwrap();
+        let session = Session::new("persisted".to_string(), PathBuf::from("/tmp"))
+            .with_state_dir(Some(state_dir.path().to_path_buf()));
+
+        for id in ["approval-1", "approval-2"] {
+            session
+                .add_pending_approval(ApprovalRequest {
+                    id: id.to_string(),
+                    session_id: "persisted".to_string(),
+                    claude_request_id: format!("claude-{id}"),
+                    request: serde_json::json!({"tool_name": "Bash"}),
+                    created_at: std::time::SystemTime::now(),
+                    clarifications: Vec::new(),
+                    risk: RiskLevel::WorkspaceWrite,
+                    risk_reasons: Vec::new(),
+                    priority: Priority::default(),
+                })
+                .await;
+        }
+        session.remove_pending_approval("approval-1").await;
+
+        // A fresh session with the same ID picks up what is still outstanding
+        let restarted = Session::new("persisted".to_string(), PathBuf::from("/tmp"))
+            .with_state_dir(Some(state_dir.path().to_path_buf()));
+        let restored = restarted.restore_pending_approvals().await;
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].claude_request_id, "claude-approval-2");
+        assert_eq!(restarted.get_pending_approvals().await.len(), 1);
+
+        restarted.clear_pending_approvals().await;
+        assert!(!state_dir.path().join("persisted.approvals.json").exists());
+
+        // IDs that are not file names would write outside the state directory
+        let nested = state_dir.path().join("nested");
+        std::fs::create_dir(&nested).unwrap();
+        let escaping = Session::new("../escaped".to_string(), PathBuf::from("/tmp"))
+            .with_state_dir(Some(nested));
+        escaping
+            .add_pending_approval(ApprovalRequest {
+                id: "approval-3".to_string(),
+                session_id: "../escaped".to_string(),
+                claude_request_id: "claude-approval-3".to_string(),
+                request: serde_json::json!({"tool_name": "Bash"}),
+                created_at: std::time::SystemTime::now(),
+                clarifications: Vec::new(),
+                risk: RiskLevel::WorkspaceWrite,
+                risk_reasons: Vec::new(),
+                priority: Priority::default(),
+            })
+            .await;
+        assert_eq!(escaping.get_pending_approvals().await.len(), 1);
+        assert!(!state_dir.path().join("escaped.approvals.json").exists());
+    }
+
+    #[tokio::test]
+    async fn test_unsent_messages_persistence() {
+        let state_dir = tempfile::TempDir::new().unwrap();
+        let session = Session::new("queued".to_string(), PathBuf::from("/tmp"))
+            .with_state_dir(Some(state_dir.path().to_path_buf()));
+        let message = |content: &str| WriteMessage {
+            content: content.to_string(),
+            sender_client_id: "client1".to_string(),
+            timestamp: std::time::SystemTime::now(),
+        };
+        session.enqueue_message(message("first")).await;
+        session.enqueue_message(message("second")).await;
+
+        // Taken off the queue is not written yet
+        let taken = session.dequeue_paced_message().await.unwrap().unwrap();
+        assert_eq!(taken.content, "first");
+        let unsent = |session_id: &str| {
+            std::fs::read_to_string(state_dir.path().join(format!("{session_id}.pending.jsonl")))
+                .map_or(0, |contents| contents.lines().count())
+        };
+        assert_eq!(unsent("queued"), 2);
+        session.confirm_written().await;
+        assert_eq!(unsent("queued"), 1);
+
+        // A resumed session under a new ID still has them
+        session.set_id("resumed".to_string()).await;
+        let restarted = Session::new("resumed".to_string(), PathBuf::from("/tmp"))
+            .with_state_dir(Some(state_dir.path().to_path_buf()));
+        let replayed = restarted.unsent_writes().await;
+        assert_eq!(replayed.len(), 1);
+        assert_eq!(replayed[0].content, "second");
+
+        session.dequeue_paced_message().await.unwrap();
+        session.confirm_written().await;
+        assert!(!state_dir.path().join("queued.pending.jsonl").exists());
+        assert!(!state_dir.path().join("resumed.pending.jsonl").exists());
+    }
+
+    #[test]
+    fn test_delivery_tracker() {
+        let tracker = DeliveryTracker::default();
+        assert_eq!(tracker.next_seq(), 1);
+        assert_eq!(tracker.next_seq(), 2);
+        assert_eq!(tracker.next_seq(), 3);
+        assert_eq!(tracker.lag(), 3);
+
+        tracker.ack(2);
+        assert_eq!(tracker.lag(), 1);
+
+        // Stale and future acks don't corrupt the count
+        tracker.ack(1);
+        assert_eq!(tracker.lag(), 1);
+        tracker.ack(99);
+        assert_eq!(tracker.lag(), 0);
+    }
+
     #[tokio::test]
     async fn test_write_queue() {
         let session = Session::new("test-session".to_string(), PathBuf::from("/tmp"));
@@ -375,6 +3260,67 @@ mod tests {
         assert!(session.dequeue_message().await.is_none());
     }
 
+    #[tokio::test]
+    async fn test_client_disconnect_queue_policies() {
+        let session = Session::new("test-session".to_string(), PathBuf::from("/tmp"));
+        let message = |content: &str, client: &str| WriteMessage {
+            content: content.to_string(),
+            sender_client_id: client.to_string(),
+            timestamp: std::time::SystemTime::now(),
+        };
+        let contents =
+            |queue: &[QueuedMessage]| queue.iter().map(|m| m.content.clone()).collect::<Vec<_>>();
+
+        session.enqueue_message(message("a1", "a")).await;
+        session.enqueue_message(message("b1", "b")).await;
+        session.enqueue_message(message("a2", "a")).await;
+
+        assert_eq!(
+            session
+                .handle_client_disconnect("a", DisconnectQueuePolicy::Keep)
+                .await,
+            0
+        );
+        assert_eq!(session.write_queue.lock().await.len(), 3);
+
+        assert_eq!(
+            session
+                .handle_client_disconnect("a", DisconnectQueuePolicy::Hold)
+                .await,
+            2
+        );
+        let queues = session.get_client_queues().await;
+        assert_eq!(queues.len(), 2);
+        assert_eq!(queues[0].client_id, "b");
+        assert_eq!(contents(&queues[0].queued), vec!["b1"]);
+        assert_eq!(queues[1].client_id, "a");
+        assert!(!queues[1].connected);
+        assert!(queues[1].queued.is_empty());
+        assert_eq!(contents(&queues[1].held), vec!["a1", "a2"]);
+
+        // Released messages go behind whatever is queued already
+        assert_eq!(session.resolve_held_messages("a", true).await, 2);
+        assert!(session.held_messages.lock().await.is_empty());
+        let order: Vec<String> = session
+            .write_queue
+            .lock()
+            .await
+            .iter()
+            .map(|m| m.content.clone())
+            .collect();
+        assert_eq!(order, vec!["b1", "a1", "a2"]);
+
+        assert_eq!(
+            session
+                .handle_client_disconnect("b", DisconnectQueuePolicy::Drop)
+                .await,
+            1
+        );
+        assert!(session.held_messages.lock().await.is_empty());
+        assert_eq!(session.write_queue.lock().await.len(), 2);
+        assert_eq!(session.resolve_held_messages("b", false).await, 0);
+    }
+
     #[tokio::test]
     async fn test_session_status() {
         let session = Session::new("test-session".to_string(), PathBuf::from("/tmp"));
@@ -386,5 +3332,194 @@ mod tests {
 
         session.set_status(SessionStatus::Failed).await;
         assert_eq!(session.get_status().await, SessionStatus::Failed);
+
+        session
+            .enqueue_message(WriteMessage {
+                content: "{}".to_string(),
+                sender_client_id: "client".to_string(),
+                timestamp: std::time::SystemTime::now(),
+            })
+            .await;
+        session.mark_exited().await;
+        session.wait_exited().await;
+        assert_eq!(session.get_status().await, SessionStatus::Exited);
+        assert!(session.dequeue_message().await.is_none());
+    }
+
+    #[test]
+    fn test_resume_broadcasts() {
+        let session = Session::new("resumable".to_string(), PathBuf::from("/tmp"));
+        let output = |n: usize| BroadcastMessage::ClaudeOutput {
+            line: format!("{{\"n\":{n}}}").into(),
+            received_at: chrono::Utc::now(),
+        };
+        let mut rx = session.subscribe_to_broadcasts();
+        let _ = session.broadcast_message(output(1));
+        let _ = session.broadcast_message(BroadcastMessage::Presence {
+            client_id: "c1".to_string(),
+            state: serde_json::Value::Null,
+        });
+        let _ = session.broadcast_message(output(3));
+        assert_eq!(rx.try_recv().unwrap().seq, 1);
+        assert_eq!(rx.try_recv().unwrap().seq, 2);
+
+        // Presence is not replayed
+        let (mut resumed, replay) = session.resume_broadcasts(1);
+        assert!(replay.complete);
+        let missed: Vec<u64> = replay.missed.iter().map(|envelope| envelope.seq).collect();
+        assert_eq!(missed, vec![3]);
+        let _ = session.broadcast_message(output(4));
+        assert_eq!(resumed.try_recv().unwrap().seq, 4);
+
+        // A sequence number the session never reached cannot be resumed after
+        assert!(!session.resume_broadcasts(9).1.complete);
+
+        for n in 0..BROADCAST_REPLAY_CAPACITY {
+            let _ = session.broadcast_message(output(n));
+        }
+        let (_, replay) = session.resume_broadcasts(1);
+        assert!(!replay.complete);
+        assert_eq!(replay.missed.len(), BROADCAST_REPLAY_CAPACITY);
+        assert!(session.resume_broadcasts(5).1.complete);
+    }
+
+    #[tokio::test]
+    async fn test_session_reach_limit() {
+        let session = Session::new("limited".to_string(), PathBuf::from("/tmp"));
+        session.set_status(SessionStatus::Ready).await;
+        let mut rx = session.subscribe_to_broadcasts();
+        assert_eq!(session.record_turn(), 1);
+
+        assert!(session.reach_limit("turns").await);
+        assert!(!session.reach_limit("duration").await);
+        assert_eq!(session.get_status().await, SessionStatus::LimitReached);
+        let BroadcastMessage::ClaudeOutput { line, .. } = rx.recv().await.unwrap().message else {
+            panic!("Expected the limit notice");
+        };
+        let notice: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(notice["subtype"], "limit_reached");
+        assert_eq!(notice["limit"], "turns");
+        assert_eq!(notice["turns"], 1);
+
+        // Claude exiting afterwards does not hide why the session stopped
+        session.mark_exited().await;
+        assert_eq!(session.get_status().await, SessionStatus::LimitReached);
+    }
+
+    #[test]
+    fn test_claude_message_parse() {
+        let assistant = ClaudeMessage::parse(
+            r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"thinking","thinking":"hm"},{"type":"text","text":"Hi"},{"type":"tool_use","id":"t1","name":"Bash","input":{}},{"type":"text","text":"there"}]},"session_id":"s1"}"#,
+        )
+        .unwrap();
+        let ClaudeMessage::Assistant(assistant) = assistant else {
+            panic!("not an assistant message: {assistant:?}");
+        };
+        assert_eq!(assistant.text_blocks().collect::<Vec<_>>(), ["Hi", "there"]);
+
+        let result = ClaudeMessage::parse(
+            r#"{"type":"result","subtype":"error_max_turns","is_error":true,"usage":{"input_tokens":3,"output_tokens":2}}"#,
+        )
+        .unwrap();
+        let ClaudeMessage::Result(result) = result else {
+            panic!("not a result: {result:?}");
+        };
+        assert!(result.is_error);
+        assert_eq!(result.detail(), Some("error_max_turns"));
+        assert_eq!(result.usage.unwrap().output_tokens, 2);
+
+        let request = ClaudeMessage::parse(
+            r#"{"type":"control_request","request_id":"r1","request":{"subtype":"can_use_tool","tool_name":"Bash"}}"#,
+        )
+        .unwrap();
+        let ClaudeMessage::ControlRequest(request) = request else {
+            panic!("not a control request: {request:?}");
+        };
+        assert_eq!(request.subtype(), "can_use_tool");
+        assert_eq!(request.request_id.as_deref(), Some("r1"));
+
+        assert_eq!(
+            ClaudeMessage::parse(r#"{"type":"stream_event","event":{}}"#).unwrap(),
+            ClaudeMessage::StreamEvent {}
+        );
+        // Valid JSON this model does not know is passed along as unknown
+        for line in [
+            r#"{"type":"start"}"#,
+            r#"{"type":"assistant","message":{"content":42}}"#,
+            "[1, 2]",
+            "7",
+        ] {
+            assert_eq!(ClaudeMessage::parse(line).unwrap(), ClaudeMessage::Unknown);
+        }
+        assert!(ClaudeMessage::parse("not json").is_err());
+        assert!(ClaudeMessage::parse("7 trailing").is_err());
+    }
+
+    #[test]
+    fn test_create_session_request_options() {
+        let request: CreateSessionRequest = serde_json::from_str(
+            r#"{
+                "session_id": "s1",
+                "working_dir": "/tmp",
+                "resume": false,
+                "bootstrap_messages": [],
+                "append_system_prompt": "Never push to main"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(request.options.system_prompt, None);
+        assert_eq!(
+            request.options.append_system_prompt.as_deref(),
+            Some("Never push to main")
+        );
+
+        let request: CreateSessionRequest = serde_json::from_str(
+            r#"{"session_id": "s1", "working_dir": "/tmp", "resume": false, "bootstrap_messages": []}"#,
+        )
+        .unwrap();
+        assert!(request.options.system_prompt.is_none());
+        assert!(request.options.append_system_prompt.is_none());
+    }
+
+    #[test]
+    fn test_create_session_request_legacy_shapes() {
+        let parse = |fields: &str| {
+            serde_json::from_str::<CreateSessionRequest>(&format!(
+                r#"{{"session_id": "s1", "working_dir": "/tmp", "resume": false{fields}}}"#
+            ))
+        };
+
+        let request = parse(r#", "first_message": "hello""#).unwrap();
+        assert_eq!(request.bootstrap_messages, vec!["hello"]);
+        assert_eq!(request.version, CreateSessionRequestVersion::FirstMessage);
+        assert_eq!(
+            request.version.deprecation().unwrap().field,
+            "first_message"
+        );
+
+        let request = parse(r#", "first_message": ["a", "b"], "max_turns": 3"#).unwrap();
+        assert_eq!(request.bootstrap_messages, vec!["a", "b"]);
+        assert_eq!(
+            request.version,
+            CreateSessionRequestVersion::FirstMessageList
+        );
+        assert_eq!(request.options.max_turns, Some(3));
+
+        let request = parse(r#", "bootstrap_messages": ["a"]"#).unwrap();
+        assert_eq!(
+            request.version,
+            CreateSessionRequestVersion::BootstrapMessages
+        );
+        assert!(request.version.deprecation().is_none());
+
+        // Left to the handler, which also accepts an upload instead
+        assert!(parse("").unwrap().bootstrap_messages.is_empty());
+        assert!(parse(r#", "bootstrap_messages": ["a"], "first_message": "b""#).is_err());
+
+        // Always sent in the current shape
+        let sent = serde_json::to_value(parse(r#", "first_message": "hello""#).unwrap()).unwrap();
+        assert_eq!(sent["bootstrap_messages"], serde_json::json!(["hello"]));
+        assert!(sent.get("first_message").is_none());
+        assert!(sent.get("version").is_none());
     }
 }
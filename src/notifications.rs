@@ -0,0 +1,556 @@
+use crate::approval_priority::{Priority, PriorityLanes};
+use crate::config::NotificationTarget;
+use crate::error::{OrchestratorError, OrchestratorResult};
+use crate::models::{
+    ApprovalMessage, ApprovalRequest, BroadcastEnvelope, BroadcastMessage, ClaudeMessage,
+    SessionWatch,
+};
+use crate::push_notify::PushNotifier;
+use crate::web_push::WebPush;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::{broadcast, RwLock};
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+/// How long a single delivery may take before it is given up.
+pub(crate) const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Session events a watcher can subscribe to.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchEvent {
+    /// Claude finished a turn without error
+    Completed,
+    /// Claude finished a turn with an error, or exited in the middle of one
+    Failed,
+    /// Claude is waiting for a tool approval
+    ApprovalNeeded,
+    /// Claude exited with an error status or was killed by a signal, in a turn or not
+    Crashed,
+}
+
+impl WatchEvent {
+    pub const ALL: [Self; 4] = [
+        Self::Completed,
+        Self::Failed,
+        Self::ApprovalNeeded,
+        Self::Crashed,
+    ];
+}
+
+/// What watchers of a session are sent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub event: WatchEvent,
+    pub session_id: String,
+    pub working_directory: PathBuf,
+    pub detail: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Owner of the session, whom browser pushes are limited to besides admins
+    #[serde(skip)]
+    pub owner: Option<String>,
+}
+
+impl Notification {
+    pub(crate) fn summary(&self) -> String {
+        let what = match self.event {
+            WatchEvent::Completed => "completed",
+            WatchEvent::Failed => "failed",
+            WatchEvent::ApprovalNeeded => "needs approval",
+            WatchEvent::Crashed => "crashed",
+        };
+        format!(
+            "Session {} ({}) {what}",
+            self.session_id,
+            self.working_directory.display()
+        )
+    }
+}
+
+/// Keeps track of who watches which session and delivers their notifications.
+pub struct Notifier {
+    targets: HashMap<String, NotificationTarget>,
+    // Session ID -> target name -> subscribed events
+    watches: DashMap<String, BTreeMap<String, BTreeSet<WatchEvent>>>,
+    client: reqwest::Client,
+    /// Browsers subscribed to every session they are interested in, when Web Push is set up
+    web_push: Option<Arc<WebPush>>,
+    /// ntfy and Gotify, told about every session, when set up
+    push: Option<Arc<PushNotifier>>,
+}
+
+impl Notifier {
+    #[must_use]
+    pub fn new(targets: HashMap<String, NotificationTarget>) -> Self {
+        Self {
+            targets,
+            watches: DashMap::new(),
+            client: reqwest::Client::new(),
+            web_push: None,
+            push: None,
+        }
+    }
+
+    /// Also pushes every notification to the browsers subscribed through `web_push`.
+    #[must_use]
+    pub fn with_web_push(mut self, web_push: WebPush) -> Self {
+        self.web_push = Some(Arc::new(web_push));
+        self
+    }
+
+    /// Also pushes the notifications of every session through ntfy or Gotify.
+    #[must_use]
+    pub fn with_push(mut self, push: PushNotifier) -> Self {
+        self.push = Some(Arc::new(push));
+        self
+    }
+
+    /// Whether notifications can reach anyone, i.e. whether sessions need following at all
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        !self.targets.is_empty() || self.web_push.is_some() || self.push.is_some()
+    }
+
+    #[must_use]
+    pub fn web_push(&self) -> Option<&Arc<WebPush>> {
+        self.web_push.as_ref()
+    }
+
+    /// Subscribes `target` to `events` of a session, replacing its earlier subscription.
+    /// An empty event list subscribes to every event.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no target with that name is configured.
+    pub fn watch(
+        &self,
+        session_id: &str,
+        target: &str,
+        events: &[WatchEvent],
+    ) -> OrchestratorResult<()> {
+        if !self.targets.contains_key(target) {
+            return Err(OrchestratorError::InvalidRequest(format!(
+                "Unknown notification target: {target}"
+            )));
+        }
+        let events = if events.is_empty() {
+            WatchEvent::ALL.into_iter().collect()
+        } else {
+            events.iter().copied().collect()
+        };
+        self.watches
+            .entry(session_id.to_string())
+            .or_default()
+            .insert(target.to_string(), events);
+        Ok(())
+    }
+
+    /// Removes the watch of `target` on a session, or every watch on it when `target` is `None`.
+    pub fn unwatch(&self, session_id: &str, target: Option<&str>) {
+        match target {
+            Some(target) => {
+                self.watches.remove_if_mut(session_id, |_, watches| {
+                    watches.remove(target);
+                    watches.is_empty()
+                });
+            }
+            None => {
+                self.watches.remove(session_id);
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn watches(&self, session_id: &str) -> Vec<SessionWatch> {
+        self.watches
+            .get(session_id)
+            .map(|watches| {
+                watches
+                    .iter()
+                    .map(|(target, events)| SessionWatch {
+                        target: target.clone(),
+                        events: events.iter().copied().collect(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Carries the watches of a session over to the ID it was resumed under.
+    pub fn rename_session(&self, old_session_id: &str, new_session_id: &str) {
+        if let Some(web_push) = &self.web_push {
+            web_push.rename_session(old_session_id, new_session_id);
+        }
+        if let Some((_, watches)) = self.watches.remove(old_session_id) {
+            self.watches
+                .entry(new_session_id.to_string())
+                .or_default()
+                .extend(watches);
+        }
+    }
+
+    /// Sends a notification to every target watching its session for its event, and
+    /// pushes it to subscribed browsers. Deliveries run in the background and failures
+    /// are only logged.
+    pub fn notify(self: &Arc<Self>, notification: &Notification) {
+        if let Some(web_push) = &self.web_push {
+            web_push.dispatch(notification);
+        }
+        if let Some(push) = &self.push {
+            push.dispatch(notification);
+        }
+        let Some(watches) = self.watches.get(&notification.session_id) else {
+            return;
+        };
+        let subscribed: Vec<String> = watches
+            .iter()
+            .filter(|(_, events)| events.contains(&notification.event))
+            .map(|(target, _)| target.clone())
+            .collect();
+        drop(watches);
+
+        for target_name in subscribed {
+            let notifier = self.clone();
+            let notification = notification.clone();
+            tokio::spawn(async move {
+                let Some(target) = notifier.targets.get(&target_name) else {
+                    return;
+                };
+                let delivery =
+                    tokio::time::timeout(DELIVERY_TIMEOUT, notifier.deliver(target, &notification))
+                        .await
+                        .unwrap_or_else(|_| {
+                            Err(format!("timed out after {}s", DELIVERY_TIMEOUT.as_secs()))
+                        });
+                match delivery {
+                    Ok(()) => info!(
+                        session_id = %notification.session_id,
+                        target = %target_name,
+                        event = ?notification.event,
+                        "Delivered session notification"
+                    ),
+                    Err(e) => warn!(
+                        session_id = %notification.session_id,
+                        target = %target_name,
+                        event = ?notification.event,
+                        error = %e,
+                        "Failed to deliver session notification"
+                    ),
+                }
+            });
+        }
+    }
+
+    async fn deliver(
+        &self,
+        target: &NotificationTarget,
+        notification: &Notification,
+    ) -> Result<(), String> {
+        match target {
+            NotificationTarget::Webhook(url) => self.post(url, notification).await,
+            NotificationTarget::Slack(url) => {
+                let text = format!("{}: {}", notification.summary(), notification.detail);
+                self.post(url, &serde_json::json!({ "text": text })).await
+            }
+            NotificationTarget::Email(address) => send_mail(address, notification).await,
+        }
+    }
+
+    async fn post(&self, url: &str, body: &impl Serialize) -> Result<(), String> {
+        self.client
+            .post(url)
+            .json(body)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Mails a notification by piping it to `sendmail -t`.
+async fn send_mail(address: &str, notification: &Notification) -> Result<(), String> {
+    let message = format!(
+        "To: {address}\nSubject: [chef-de-vibe] {}\n\n{}\n",
+        notification.summary(),
+        notification.detail
+    );
+    let mut child = Command::new("sendmail")
+        .arg("-t")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("failed to start sendmail: {e}"))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin
+        .write_all(message.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+    drop(stdin);
+
+    let output = child.wait_with_output().await.map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "sendmail exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Tracks whether Claude is in the middle of a turn, so an exit can be told apart from
+/// an idle session shutting down.
+#[derive(Debug, Default)]
+pub(crate) struct TurnTracker {
+    pub(crate) in_turn: bool,
+}
+
+impl TurnTracker {
+    /// Looks at one line of Claude output and returns the event it completes, if any.
+    pub(crate) fn observe(&mut self, line: &str) -> Option<(WatchEvent, String)> {
+        match ClaudeMessage::parse(line).ok()? {
+            ClaudeMessage::Result(result) => {
+                self.in_turn = false;
+                let detail = result.detail().unwrap_or_default().to_string();
+                Some(if result.is_error {
+                    (WatchEvent::Failed, detail)
+                } else {
+                    (WatchEvent::Completed, detail)
+                })
+            }
+            ClaudeMessage::Assistant(_) | ClaudeMessage::User(_) => {
+                self.in_turn = true;
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What an approval notification says about `requests`, e.g. `Claude wants to use Bash`
+/// or `Claude wants to use Grep, Read (3 requests)`.
+fn approval_detail(requests: &[Arc<ApprovalRequest>]) -> String {
+    let tools: BTreeSet<&str> = requests
+        .iter()
+        .map(|request| {
+            request
+                .request
+                .get("tool_name")
+                .and_then(|t| t.as_str())
+                .unwrap_or("a tool")
+        })
+        .collect();
+    let tools = tools.into_iter().collect::<Vec<_>>().join(", ");
+    if requests.len() == 1 {
+        format!("Claude wants to use {tools}")
+    } else {
+        format!("Claude wants to use {tools} ({} requests)", requests.len())
+    }
+}
+
+/// Turns a session's output and approval requests into notifications for its watchers.
+///
+/// Runs until Claude exits or the session is dropped. The session ID is read on every
+/// event since it changes when a resumed session settles on its new ID. Normal priority
+/// approval requests are held for `batch_window` and announced in one notification.
+#[allow(clippy::too_many_lines)]
+pub async fn follow_session(
+    notifier: Arc<Notifier>,
+    session_id: Arc<RwLock<String>>,
+    working_directory: PathBuf,
+    owner: Option<String>,
+    mut output: broadcast::Receiver<BroadcastEnvelope>,
+    mut approvals: broadcast::Receiver<ApprovalMessage>,
+    batch_window: Duration,
+) {
+    let mut turn = TurnTracker::default();
+    // Restored approvals are broadcast again, they only need announcing once
+    let mut announced_approvals = HashSet::new();
+    let mut held_approvals = PriorityLanes::new(batch_window);
+
+    loop {
+        let batch_due = held_approvals.due();
+        let (event, detail) = tokio::select! {
+            message = output.recv() => match message.map(|envelope| envelope.message) {
+                Ok(BroadcastMessage::ClaudeOutput { line, .. }) => match turn.observe(&line) {
+                    Some(event) => event,
+                    None => continue,
+                },
+                Ok(BroadcastMessage::Disconnect) | Err(broadcast::error::RecvError::Closed) => {
+                    if turn.in_turn {
+                        let notification = Notification {
+                            event: WatchEvent::Failed,
+                            session_id: session_id.read().await.clone(),
+                            working_directory: working_directory.clone(),
+                            detail: "Claude exited before finishing its turn".to_string(),
+                            timestamp: chrono::Utc::now(),
+                            owner: owner.clone(),
+                        };
+                        notifier.notify(&notification);
+                    }
+                    break;
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    debug!(skipped, "Session notifications fell behind Claude output");
+                    continue;
+                }
+            },
+            message = approvals.recv() => match message {
+                Ok(ApprovalMessage::ApprovalRequest { request, .. }) => {
+                    if !announced_approvals.insert(request.id.clone()) {
+                        continue;
+                    }
+                    if request.priority == Priority::High {
+                        (WatchEvent::ApprovalNeeded, approval_detail(&[request]))
+                    } else {
+                        held_approvals.push(request.priority, request, Instant::now());
+                        continue;
+                    }
+                }
+                Ok(ApprovalMessage::ApprovalResponse(_))
+                | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            () = tokio::time::sleep_until(batch_due.unwrap_or_else(Instant::now).into()),
+                if batch_due.is_some() =>
+            {
+                let batch: Vec<_> =
+                    std::iter::from_fn(|| held_approvals.pop_ready(Instant::now())).collect();
+                if batch.is_empty() {
+                    continue;
+                }
+                (WatchEvent::ApprovalNeeded, approval_detail(&batch))
+            }
+        };
+
+        notifier.notify(&Notification {
+            event,
+            session_id: session_id.read().await.clone(),
+            working_directory: working_directory.clone(),
+            detail,
+            timestamp: chrono::Utc::now(),
+            owner: owner.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notifier() -> Notifier {
+        Notifier::new(HashMap::from([
+            (
+                "me".to_string(),
+                NotificationTarget::Webhook("http://127.0.0.1:9/hook".to_string()),
+            ),
+            (
+                "team".to_string(),
+                NotificationTarget::Slack("http://127.0.0.1:9/slack".to_string()),
+            ),
+        ]))
+    }
+
+    #[test]
+    fn test_approval_detail() {
+        let request = |tool: &str| {
+            Arc::new(ApprovalRequest {
+                id: tool.to_string(),
+                session_id: "s1".to_string(),
+                claude_request_id: tool.to_string(),
+                request: serde_json::json!({"tool_name": tool}),
+                created_at: std::time::SystemTime::now(),
+                clarifications: Vec::new(),
+                risk: crate::approval_risk::RiskLevel::ReadOnly,
+                risk_reasons: Vec::new(),
+                priority: Priority::Normal,
+            })
+        };
+        assert_eq!(
+            approval_detail(&[request("Bash")]),
+            "Claude wants to use Bash"
+        );
+        assert_eq!(
+            approval_detail(&[request("Read"), request("Grep"), request("Read")]),
+            "Claude wants to use Grep, Read (3 requests)"
+        );
+    }
+
+    #[test]
+    fn test_watch_and_unwatch() {
+        let notifier = notifier();
+        assert!(notifier.watch("s1", "nobody", &[]).is_err());
+
+        notifier.watch("s1", "me", &[]).unwrap();
+        notifier
+            .watch("s1", "team", &[WatchEvent::ApprovalNeeded])
+            .unwrap();
+        assert_eq!(
+            notifier.watches("s1"),
+            vec![
+                SessionWatch {
+                    target: "me".to_string(),
+                    events: WatchEvent::ALL.to_vec(),
+                },
+                SessionWatch {
+                    target: "team".to_string(),
+                    events: vec![WatchEvent::ApprovalNeeded],
+                },
+            ]
+        );
+
+        notifier.rename_session("s1", "s2");
+        assert!(notifier.watches("s1").is_empty());
+        assert_eq!(notifier.watches("s2").len(), 2);
+
+        notifier.unwatch("s2", Some("me"));
+        assert_eq!(notifier.watches("s2").len(), 1);
+        notifier.unwatch("s2", Some("team"));
+        assert!(notifier.watches.is_empty());
+
+        notifier.watch("s3", "me", &[]).unwrap();
+        notifier.unwatch("s3", None);
+        assert!(notifier.watches("s3").is_empty());
+    }
+
+    #[test]
+    fn test_turn_tracker() {
+        let mut turn = TurnTracker::default();
+        assert_eq!(turn.observe(r#"{"type":"system","subtype":"init"}"#), None);
+        assert!(!turn.in_turn);
+
+        assert_eq!(
+            turn.observe(r#"{"type":"assistant","message":{"content":[]}}"#),
+            None
+        );
+        assert!(turn.in_turn);
+        assert_eq!(
+            turn.observe(
+                r#"{"type":"result","subtype":"success","is_error":false,"result":"Done"}"#
+            ),
+            Some((WatchEvent::Completed, "Done".to_string()))
+        );
+        assert!(!turn.in_turn);
+
+        assert_eq!(
+            turn.observe(r#"{"type":"result","subtype":"error_max_turns","is_error":true}"#),
+            Some((WatchEvent::Failed, "error_max_turns".to_string()))
+        );
+        assert_eq!(turn.observe("not json"), None);
+    }
+}
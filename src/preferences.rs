@@ -0,0 +1,220 @@
+use crate::error::{OrchestratorError, OrchestratorResult};
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+const PREFERENCES_FILE: &str = "preferences.json";
+
+/// Largest preferences object accepted per identity, serialized.
+pub const MAX_PREFERENCES_SIZE: usize = 64 * 1024;
+
+/// Preference listing the sessions a user pinned, in the order they were pinned.
+pub const PINNED_SESSIONS_KEY: &str = "pinned_sessions";
+
+/// Identity used when no identity header is configured.
+pub const DEFAULT_IDENTITY: &str = "default";
+
+pub type Preferences = serde_json::Map<String, serde_json::Value>;
+
+/// UI preferences of each user, opaque to the server, so they follow the user across
+/// devices instead of living in one browser's local storage.
+#[derive(Debug, Default)]
+pub struct PreferenceStore {
+    /// Identity -> the preferences object last stored for it
+    preferences: DashMap<String, Preferences>,
+    /// Where preferences are persisted, if a session state directory is configured
+    path: Option<PathBuf>,
+}
+
+impl PreferenceStore {
+    /// Loads the preferences persisted in `state_dir`, or starts empty.
+    #[must_use]
+    pub fn load(state_dir: Option<&Path>) -> Self {
+        let path = state_dir.map(|dir| dir.join(PREFERENCES_FILE));
+        let mut preferences = DashMap::new();
+
+        if let Some(path) = &path {
+            match std::fs::read(path) {
+                Ok(bytes) => match serde_json::from_slice::<HashMap<String, Preferences>>(&bytes) {
+                    Ok(map) => preferences.extend(map),
+                    Err(e) => warn!(
+                        path = %path.display(),
+                        error = %e,
+                        "Ignoring unreadable preferences file"
+                    ),
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!(
+                    path = %path.display(),
+                    error = %e,
+                    "Failed to read preferences file"
+                ),
+            }
+        }
+
+        Self { preferences, path }
+    }
+
+    /// The preferences of `identity`; empty if it never stored any.
+    #[must_use]
+    pub fn get(&self, identity: &str) -> Preferences {
+        self.preferences
+            .get(identity)
+            .map(|preferences| preferences.clone())
+            .unwrap_or_default()
+    }
+
+    /// Replaces the preferences of `identity`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the preferences exceed [`MAX_PREFERENCES_SIZE`].
+    pub fn set(&self, identity: &str, preferences: Preferences) -> OrchestratorResult<()> {
+        let size = checked_size(&preferences)?;
+        self.preferences.insert(identity.to_string(), preferences);
+        debug!(identity = %identity, size, "Stored preferences");
+        self.persist();
+        Ok(())
+    }
+
+    /// The sessions `identity` pinned, in the order they were pinned.
+    #[must_use]
+    pub fn pinned_sessions(&self, identity: &str) -> Vec<String> {
+        self.preferences
+            .get(identity)
+            .map(|preferences| pinned_in(&preferences))
+            .unwrap_or_default()
+    }
+
+    /// Pins or unpins a session for `identity` without touching its other preferences;
+    /// returns the pinned sessions afterwards. Newly pinned sessions go last.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the preferences would exceed [`MAX_PREFERENCES_SIZE`].
+    pub fn set_pinned(
+        &self,
+        identity: &str,
+        session_id: &str,
+        pinned: bool,
+    ) -> OrchestratorResult<Vec<String>> {
+        let pinned_sessions = {
+            let mut preferences = self.preferences.entry(identity.to_string()).or_default();
+            let mut pinned_sessions = pinned_in(&preferences);
+            let was_pinned = pinned_sessions.iter().any(|id| id == session_id);
+            if pinned == was_pinned {
+                return Ok(pinned_sessions);
+            }
+            if pinned {
+                pinned_sessions.push(session_id.to_string());
+            } else {
+                pinned_sessions.retain(|id| id != session_id);
+            }
+            let mut updated = preferences.clone();
+            updated.insert(
+                PINNED_SESSIONS_KEY.to_string(),
+                serde_json::json!(pinned_sessions),
+            );
+            checked_size(&updated)?;
+            *preferences = updated;
+            pinned_sessions
+        };
+        debug!(identity = %identity, session_id = %session_id, pinned, "Updated pinned sessions");
+        self.persist();
+        Ok(pinned_sessions)
+    }
+
+    fn persist(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let snapshot: HashMap<String, Preferences> = self
+            .preferences
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        let tmp_path = path.with_extension("json.tmp");
+        let result = serde_json::to_vec(&snapshot)
+            .map_err(std::io::Error::from)
+            .and_then(|bytes| std::fs::write(&tmp_path, bytes))
+            .and_then(|()| std::fs::rename(&tmp_path, path));
+        if let Err(e) = result {
+            warn!(path = %path.display(), error = %e, "Failed to persist preferences");
+        }
+    }
+}
+
+/// Serialized size of `preferences`, if within [`MAX_PREFERENCES_SIZE`].
+fn checked_size(preferences: &Preferences) -> OrchestratorResult<usize> {
+    let size = serde_json::to_vec(preferences)?.len();
+    if size > MAX_PREFERENCES_SIZE {
+        return Err(OrchestratorError::InvalidRequest(format!(
+            "Preferences are limited to {MAX_PREFERENCES_SIZE} bytes, got {size}"
+        )));
+    }
+    Ok(size)
+}
+
+/// Session IDs under [`PINNED_SESSIONS_KEY`]; anything else stored there is ignored.
+fn pinned_in(preferences: &Preferences) -> Vec<String> {
+    preferences
+        .get(PINNED_SESSIONS_KEY)
+        .and_then(serde_json::Value::as_array)
+        .map(|ids| {
+            ids.iter()
+                .filter_map(|id| id.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_preferences_round_trip() {
+        let state_dir = TempDir::new().unwrap();
+        let store = PreferenceStore::load(Some(state_dir.path()));
+        assert!(store.get("alice").is_empty());
+
+        let serde_json::Value::Object(preferences) =
+            serde_json::json!({"theme": "dark", "pinned_sessions": ["a", "b"]})
+        else {
+            unreachable!()
+        };
+        store.set("alice", preferences.clone()).unwrap();
+        assert!(store.get("bob").is_empty());
+
+        let reloaded = PreferenceStore::load(Some(state_dir.path()));
+        assert_eq!(reloaded.get("alice"), preferences);
+    }
+
+    #[test]
+    fn test_pinned_sessions_keep_other_preferences() {
+        let store = PreferenceStore::load(None);
+        let serde_json::Value::Object(preferences) = serde_json::json!({"theme": "dark"}) else {
+            unreachable!()
+        };
+        store.set("alice", preferences).unwrap();
+
+        store.set_pinned("alice", "a", true).unwrap();
+        store.set_pinned("alice", "b", true).unwrap();
+        assert_eq!(store.set_pinned("alice", "a", true).unwrap(), ["a", "b"]);
+        assert_eq!(store.set_pinned("alice", "a", false).unwrap(), ["b"]);
+        assert_eq!(store.pinned_sessions("alice"), ["b"]);
+        assert!(store.pinned_sessions("bob").is_empty());
+        assert_eq!(store.get("alice")["theme"], "dark");
+    }
+
+    #[test]
+    fn test_preferences_size_limit() {
+        let store = PreferenceStore::load(None);
+        let mut preferences = Preferences::new();
+        preferences.insert("blob".to_string(), "x".repeat(MAX_PREFERENCES_SIZE).into());
+        assert!(store.set("alice", preferences).is_err());
+        assert!(store.get("alice").is_empty());
+    }
+}
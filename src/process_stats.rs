@@ -0,0 +1,210 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use utoipa::ToSchema;
+
+/// Resource usage of a session's Claude process at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ProcessStats {
+    /// Resident memory
+    pub rss_bytes: u64,
+    /// CPU time used since the previous sample, as a percentage of one core
+    pub cpu_percent: f64,
+    pub sampled_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// CPU time counters, in clock ticks, of a process and of the whole machine.
+#[derive(Debug, Clone, Copy)]
+struct CpuTicks {
+    process: u64,
+    total: u64,
+    cpus: u64,
+}
+
+/// Samples one process from `/proc`, remembering the previous sample so CPU usage can
+/// be reported over the interval between samples.
+#[derive(Debug)]
+pub struct ProcessSampler {
+    pid: u32,
+    previous: Option<CpuTicks>,
+}
+
+impl ProcessSampler {
+    #[must_use]
+    pub fn new(pid: u32) -> Self {
+        Self {
+            pid,
+            previous: None,
+        }
+    }
+
+    /// Takes a sample; `None` once the process is gone or on systems without `/proc`.
+    /// The first sample reports no CPU usage, since there is no interval yet.
+    pub fn sample(&mut self) -> Option<ProcessStats> {
+        let status = std::fs::read_to_string(format!("/proc/{}/status", self.pid)).ok()?;
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", self.pid)).ok()?;
+        let machine = std::fs::read_to_string("/proc/stat").ok()?;
+
+        let ticks = CpuTicks {
+            process: process_ticks(&stat)?,
+            total: machine_ticks(&machine)?,
+            cpus: machine.lines().filter(|l| is_cpu_line(l)).count().max(1) as u64,
+        };
+        let cpu_percent = self
+            .previous
+            .map_or(0.0, |previous| cpu_percent(previous, ticks));
+        self.previous = Some(ticks);
+
+        Some(ProcessStats {
+            rss_bytes: rss_bytes(&status)?,
+            cpu_percent,
+            sampled_at: chrono::Utc::now(),
+        })
+    }
+}
+
+/// `VmRSS` of `/proc/<pid>/status`, which is given in kB.
+fn rss_bytes(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// User plus system time of `/proc/<pid>/stat`. Fields are counted after the command
+/// name, which is parenthesized and may itself contain spaces.
+fn process_ticks(stat: &str) -> Option<u64> {
+    let fields: Vec<&str> = stat
+        .get(stat.rfind(')')? + 1..)?
+        .split_whitespace()
+        .collect();
+    // utime and stime are fields 14 and 15 of the full line
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(utime + stime)
+}
+
+/// Sum of the aggregate `cpu` line of `/proc/stat`.
+fn machine_ticks(stat: &str) -> Option<u64> {
+    let line = stat.lines().find(|line| line.starts_with("cpu "))?;
+    Some(
+        line.split_whitespace()
+            .skip(1)
+            .filter_map(|field| field.parse::<u64>().ok())
+            .sum(),
+    )
+}
+
+fn is_cpu_line(line: &str) -> bool {
+    line.strip_prefix("cpu")
+        .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn cpu_percent(previous: CpuTicks, current: CpuTicks) -> f64 {
+    let total = current.total.saturating_sub(previous.total);
+    if total == 0 {
+        return 0.0;
+    }
+    // Machine ticks cover every core, so scale to a share of one core
+    let process = current.process.saturating_sub(previous.process);
+    process as f64 * current.cpus as f64 * 100.0 / total as f64
+}
+
+/// Prometheus text exposition of the latest sample of each session's Claude process.
+#[must_use]
+pub fn prometheus_metrics(samples: &[(String, ProcessStats)]) -> String {
+    let mut out = String::new();
+    #[allow(clippy::cast_precision_loss)]
+    write_gauge(
+        &mut out,
+        "chef_de_vibe_session_rss_bytes",
+        "Resident memory of the session's Claude process",
+        samples,
+        |stats| stats.rss_bytes as f64,
+    );
+    write_gauge(
+        &mut out,
+        "chef_de_vibe_session_cpu_percent",
+        "CPU use of the session's Claude process, as a percentage of one core",
+        samples,
+        |stats| stats.cpu_percent,
+    );
+    out
+}
+
+fn write_gauge(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    samples: &[(String, ProcessStats)],
+    value: impl Fn(&ProcessStats) -> f64,
+) {
+    let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} gauge");
+    for (session_id, stats) in samples {
+        let label = session_id
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n");
+        let _ = writeln!(out, "{name}{{session_id=\"{label}\"}} {}", value(stats));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_files() {
+        let status = "Name:\tclaude\nVmPeak:\t  900 kB\nVmRSS:\t  2048 kB\n";
+        assert_eq!(rss_bytes(status), Some(2048 * 1024));
+
+        let stat = "4242 (node (claude)) S 1 4242 4242 0 -1 4194560 100 0 0 0 250 50 0 0 20 0";
+        assert_eq!(process_ticks(stat), Some(300));
+
+        let machine =
+            "cpu  100 0 100 800 0 0 0 0 0 0\ncpu0 50 0 50 400\ncpu1 50 0 50 400\nintr 1\n";
+        assert_eq!(machine_ticks(machine), Some(1000));
+        assert_eq!(machine.lines().filter(|l| is_cpu_line(l)).count(), 2);
+    }
+
+    #[test]
+    fn test_prometheus_metrics() {
+        let stats = ProcessStats {
+            rss_bytes: 4096,
+            cpu_percent: 12.5,
+            sampled_at: chrono::Utc::now(),
+        };
+        let metrics = prometheus_metrics(&[("a\"b".to_string(), stats)]);
+        assert!(metrics.contains("# TYPE chef_de_vibe_session_rss_bytes gauge\n"));
+        assert!(metrics.contains("chef_de_vibe_session_rss_bytes{session_id=\"a\\\"b\"} 4096\n"));
+        assert!(metrics.contains("chef_de_vibe_session_cpu_percent{session_id=\"a\\\"b\"} 12.5\n"));
+    }
+
+    #[test]
+    fn test_cpu_percent_of_one_core() {
+        let previous = CpuTicks {
+            process: 100,
+            total: 1000,
+            cpus: 4,
+        };
+        // A process busy on one of four cores gets a quarter of the machine's ticks
+        let current = CpuTicks {
+            process: 200,
+            total: 1400,
+            cpus: 4,
+        };
+        assert!((cpu_percent(previous, current) - 100.0).abs() < f64::EPSILON);
+        assert!(cpu_percent(current, current).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_sample_own_process() {
+        if !std::path::Path::new("/proc/self/stat").exists() {
+            return;
+        }
+        let mut sampler = ProcessSampler::new(std::process::id());
+        let stats = sampler.sample().unwrap();
+        assert!(stats.rss_bytes > 0);
+        assert!(sampler.sample().is_some());
+        assert!(ProcessSampler::new(u32::MAX).sample().is_none());
+    }
+}
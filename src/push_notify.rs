@@ -0,0 +1,203 @@
+use crate::config::{PushBackend, PushSettings};
+use crate::notifications::{Notification, WatchEvent, DELIVERY_TIMEOUT};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Pushes notifications of every session to phones and desktops through ntfy or Gotify,
+/// for the events that are turned on.
+pub struct PushNotifier {
+    settings: PushSettings,
+    client: reqwest::Client,
+}
+
+impl PushNotifier {
+    #[must_use]
+    pub fn new(settings: PushSettings) -> Self {
+        Self {
+            settings,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Whether `event` is pushed at all.
+    #[must_use]
+    pub fn wants(&self, event: WatchEvent) -> bool {
+        self.settings.events.contains(&event)
+    }
+
+    /// Sends `notification` to every backend, if its event is turned on. Deliveries run
+    /// in the background and failures are only logged.
+    pub fn dispatch(self: &Arc<Self>, notification: &Notification) {
+        if !self.wants(notification.event) {
+            return;
+        }
+        for index in 0..self.settings.backends.len() {
+            let notifier = self.clone();
+            let notification = notification.clone();
+            tokio::spawn(async move {
+                let backend = &notifier.settings.backends[index];
+                let delivery = tokio::time::timeout(
+                    DELIVERY_TIMEOUT,
+                    notifier.deliver(backend, &notification),
+                )
+                .await
+                .unwrap_or_else(|_| {
+                    Err(format!("timed out after {}s", DELIVERY_TIMEOUT.as_secs()))
+                });
+                match delivery {
+                    Ok(()) => info!(
+                        session_id = %notification.session_id,
+                        backend = backend.kind(),
+                        event = ?notification.event,
+                        "Pushed session notification"
+                    ),
+                    Err(e) => warn!(
+                        session_id = %notification.session_id,
+                        backend = backend.kind(),
+                        event = ?notification.event,
+                        error = %e,
+                        "Failed to push session notification"
+                    ),
+                }
+            });
+        }
+    }
+
+    async fn deliver(
+        &self,
+        backend: &PushBackend,
+        notification: &Notification,
+    ) -> Result<(), String> {
+        let title = notification.summary();
+        let message = if notification.detail.is_empty() {
+            title.clone()
+        } else {
+            notification.detail.clone()
+        };
+        let urgent = matches!(
+            notification.event,
+            WatchEvent::ApprovalNeeded | WatchEvent::Crashed
+        );
+        let request = match backend {
+            PushBackend::Ntfy { topic_url, token } => {
+                // JSON publishing goes to the server root and names the topic in the body
+                let (server, topic) = topic_url
+                    .trim_end_matches('/')
+                    .rsplit_once('/')
+                    .ok_or_else(|| format!("not a topic URL: {topic_url}"))?;
+                let request = self.client.post(server).json(&json!({
+                    "topic": topic,
+                    "title": title,
+                    "message": message,
+                    "priority": if urgent { 4 } else { 3 },
+                    "tags": [ntfy_tag(notification.event)],
+                }));
+                match token {
+                    Some(token) => request.bearer_auth(token),
+                    None => request,
+                }
+            }
+            PushBackend::Gotify {
+                server_url,
+                app_token,
+            } => self
+                .client
+                .post(format!("{}/message", server_url.trim_end_matches('/')))
+                .header("X-Gotify-Key", app_token)
+                .json(&json!({
+                    "title": title,
+                    "message": message,
+                    "priority": if urgent { 8 } else { 5 },
+                })),
+        };
+        request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Emoji shortcode ntfy shows in front of the title.
+fn ntfy_tag(event: WatchEvent) -> &'static str {
+    match event {
+        WatchEvent::Completed => "white_check_mark",
+        WatchEvent::Failed => "x",
+        WatchEvent::ApprovalNeeded => "raising_hand",
+        WatchEvent::Crashed => "boom",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderMap;
+    use std::collections::BTreeSet;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_deliver_to_ntfy_and_gotify() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let record = |received: Arc<Mutex<Vec<(String, HeaderMap, serde_json::Value)>>>,
+                      path: &'static str| {
+            move |headers: HeaderMap, axum::Json(body): axum::Json<serde_json::Value>| async move {
+                received
+                    .lock()
+                    .unwrap()
+                    .push((path.to_string(), headers, body));
+            }
+        };
+        let app = axum::Router::new()
+            .route("/", axum::routing::post(record(received.clone(), "/")))
+            .route(
+                "/gotify/message",
+                axum::routing::post(record(received.clone(), "/gotify/message")),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let ntfy = PushBackend::Ntfy {
+            topic_url: format!("{server}/approvals"),
+            token: Some("tk_secret".to_string()),
+        };
+        let gotify = PushBackend::Gotify {
+            server_url: format!("{server}/gotify/"),
+            app_token: "app-token".to_string(),
+        };
+        let notifier = PushNotifier::new(PushSettings {
+            backends: vec![ntfy.clone(), gotify.clone()],
+            events: BTreeSet::from([WatchEvent::ApprovalNeeded]),
+        });
+        assert!(notifier.wants(WatchEvent::ApprovalNeeded));
+        assert!(!notifier.wants(WatchEvent::Completed));
+
+        let notification = Notification {
+            event: WatchEvent::ApprovalNeeded,
+            session_id: "s1".to_string(),
+            working_directory: PathBuf::from("/work"),
+            detail: "Claude wants to use Bash".to_string(),
+            timestamp: chrono::Utc::now(),
+            owner: None,
+        };
+        notifier.deliver(&ntfy, &notification).await.unwrap();
+        notifier.deliver(&gotify, &notification).await.unwrap();
+
+        let received = received.lock().unwrap();
+        let (path, headers, body) = &received[0];
+        assert_eq!(path, "/");
+        assert_eq!(headers["authorization"], "Bearer tk_secret");
+        assert_eq!(body["topic"], "approvals");
+        assert_eq!(body["title"], "Session s1 (/work) needs approval");
+        assert_eq!(body["message"], "Claude wants to use Bash");
+        assert_eq!(body["priority"], 4);
+
+        let (path, headers, body) = &received[1];
+        assert_eq!(path, "/gotify/message");
+        assert_eq!(headers["x-gotify-key"], "app-token");
+        assert_eq!(body["priority"], 8);
+    }
+}
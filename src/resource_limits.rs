@@ -0,0 +1,280 @@
+use crate::config::ResourceLimits;
+use crate::error::{OrchestratorError, OrchestratorResult};
+use crate::models::is_valid_session_id;
+use nix::sys::resource::{setrlimit, Resource};
+use nix::sys::signal::Signal;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+
+/// Name of a session's cgroup, followed by the session ID.
+const CGROUP_PREFIX: &str = "chef-de-vibe-";
+
+/// Period the `cpu.max` quota is given over, in microseconds.
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// CPU seconds past `CLAUDE_CPU_LIMIT_SECS` a process gets to exit on `SIGXCPU` before
+/// it is sent `SIGKILL`.
+const CPU_GRACE_SECS: u64 = 5;
+
+/// How long a cgroup's leftover processes may take to die before it is left behind.
+const CGROUP_REMOVE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Limit a Claude process was killed for exceeding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceLimit {
+    /// `CLAUDE_MEMORY_LIMIT_MB`, recognised when sessions run in a cgroup
+    Memory,
+    /// `CLAUDE_CPU_LIMIT_SECS`
+    Cpu,
+}
+
+impl ResourceLimit {
+    #[must_use]
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::Memory => "memory",
+            Self::Cpu => "CPU time",
+        }
+    }
+}
+
+/// Cgroup v2 group a session's Claude, and every tool it starts, runs in.
+#[derive(Debug)]
+pub struct SessionCgroup {
+    path: PathBuf,
+}
+
+impl SessionCgroup {
+    /// Creates the group of `session_id` under `root`, or takes over the one a previous
+    /// run of the session left, and sets its limits. IDs that are not file names are
+    /// refused, as they could name a group outside the prefix.
+    fn create(root: &Path, session_id: &str, limits: &ResourceLimits) -> std::io::Result<Self> {
+        if !is_valid_session_id(session_id) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Session ID {session_id} cannot name a cgroup"),
+            ));
+        }
+        let path = root.join(format!("{CGROUP_PREFIX}{session_id}"));
+        match std::fs::create_dir(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => return Err(e),
+            _ => {}
+        }
+        let cgroup = Self { path };
+        if let Some(bytes) = limits.memory_bytes {
+            cgroup.write("memory.max", &bytes.to_string())?;
+            // Swapping would only slow a runaway down, not stop it; missing without swap
+            if let Err(e) = cgroup.write("memory.swap.max", "0") {
+                debug!(error = %e, "Cannot turn off swap for session cgroup");
+            }
+        }
+        if let Some(cores) = limits.cpu_cores {
+            #[allow(
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss,
+                clippy::cast_precision_loss
+            )]
+            let quota = (cores * CPU_PERIOD_US as f64).round().max(1.0) as u64;
+            cgroup.write("cpu.max", &format!("{quota} {CPU_PERIOD_US}"))?;
+        }
+        if let Some(processes) = limits.max_processes {
+            cgroup.write("pids.max", &processes.to_string())?;
+        }
+        Ok(cgroup)
+    }
+
+    fn write(&self, file: &str, value: &str) -> std::io::Result<()> {
+        let path = self.path.join(file);
+        std::fs::write(&path, value)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {e}", path.display())))
+    }
+
+    /// Whether the kernel killed anything in the group for going over `memory.max`.
+    fn oom_killed(&self) -> bool {
+        std::fs::read_to_string(self.path.join("memory.events"))
+            .ok()
+            .and_then(|events| {
+                events.lines().find_map(|line| {
+                    line.strip_prefix("oom_kill ")
+                        .and_then(|count| count.trim().parse::<u64>().ok())
+                })
+            })
+            .is_some_and(|count| count > 0)
+    }
+
+    /// Kills whatever Claude left running in the group and removes it.
+    pub async fn remove(self) {
+        if let Err(e) = self.write("cgroup.kill", "1") {
+            debug!(error = %e, "Cannot kill leftover processes of session cgroup");
+        }
+        // The group can only be removed once the kernel has reaped its processes
+        let deadline = tokio::time::Instant::now() + CGROUP_REMOVE_TIMEOUT;
+        loop {
+            match std::fs::remove_dir(&self.path) {
+                Ok(()) => return,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+                Err(e) if tokio::time::Instant::now() >= deadline => {
+                    warn!(path = %self.path.display(), error = %e, "Failed to remove session cgroup");
+                    return;
+                }
+                Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+            }
+        }
+    }
+}
+
+/// Constrains the Claude `cmd` starts for `session_id`, and every process it starts in
+/// turn. With a cgroup root configured, Claude is moved into a cgroup of its own before
+/// it runs, which is returned; otherwise limits are applied as rlimits, which each
+/// process gets separately.
+///
+/// # Errors
+///
+/// Returns an error if the session's cgroup cannot be created or configured.
+pub fn apply(
+    limits: &ResourceLimits,
+    cmd: &mut Command,
+    session_id: &str,
+) -> OrchestratorResult<Option<SessionCgroup>> {
+    if limits.is_unlimited() {
+        return Ok(None);
+    }
+    let cgroup = limits
+        .cgroup_root
+        .as_deref()
+        .map(|root| {
+            let cgroup = SessionCgroup::create(root, session_id, limits)?;
+            let procs = std::fs::OpenOptions::new()
+                .write(true)
+                .open(cgroup.path.join("cgroup.procs"))?;
+            Ok::<_, std::io::Error>((cgroup, procs))
+        })
+        .transpose()
+        .map_err(|e| {
+            OrchestratorError::ClaudeSpawnFailed(format!("Failed to set up session cgroup: {e}"))
+        })?;
+
+    let (cgroup, mut procs) = cgroup.unzip();
+    let cpu_secs = limits.cpu_secs;
+    // The cgroup limits the whole tree; rlimits are the fallback without one
+    let (memory_bytes, max_processes) = if cgroup.is_some() {
+        (None, None)
+    } else {
+        (limits.memory_bytes, limits.max_processes)
+    };
+    debug!(
+        session_id = %session_id,
+        cgroup = ?cgroup.as_ref().map(|cgroup| &cgroup.path),
+        ?cpu_secs,
+        ?memory_bytes,
+        ?max_processes,
+        "Applying resource limits to Claude"
+    );
+    // SAFETY: only async-signal-safe system calls run between fork and exec
+    unsafe {
+        cmd.pre_exec(move || {
+            if let Some(procs) = procs.as_mut() {
+                // "0" moves the writing process, i.e. the child before it runs Claude
+                procs.write_all(b"0")?;
+            }
+            if let Some(secs) = cpu_secs {
+                setrlimit(Resource::RLIMIT_CPU, secs, secs + CPU_GRACE_SECS)?;
+            }
+            if let Some(bytes) = memory_bytes {
+                setrlimit(Resource::RLIMIT_DATA, bytes, bytes)?;
+            }
+            if let Some(processes) = max_processes {
+                setrlimit(Resource::RLIMIT_NPROC, processes, processes)?;
+            }
+            Ok(())
+        });
+    }
+    Ok(cgroup)
+}
+
+/// The limit a Claude process that exited with `status` was killed for exceeding, if
+/// it was killed for that.
+#[must_use]
+pub fn exceeded(status: ExitStatus, cgroup: Option<&SessionCgroup>) -> Option<ResourceLimit> {
+    let signal = Signal::try_from(status.signal()?).ok()?;
+    match signal {
+        Signal::SIGXCPU => Some(ResourceLimit::Cpu),
+        Signal::SIGKILL if cgroup.is_some_and(SessionCgroup::oom_killed) => {
+            Some(ResourceLimit::Memory)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn limits(cgroup_root: Option<&Path>) -> ResourceLimits {
+        ResourceLimits {
+            memory_bytes: Some(512 * 1024 * 1024),
+            cpu_secs: None,
+            cpu_cores: Some(1.5),
+            max_processes: Some(64),
+            cgroup_root: cgroup_root.map(Path::to_path_buf),
+        }
+    }
+
+    #[test]
+    fn test_cgroup_limits_and_oom_detection() {
+        // A plain directory stands in for the cgroup filesystem
+        let root = TempDir::new().unwrap();
+        let cgroup = SessionCgroup::create(root.path(), "s1", &limits(Some(root.path()))).unwrap();
+        let read = |file: &str| std::fs::read_to_string(cgroup.path.join(file)).unwrap();
+        assert_eq!(cgroup.path, root.path().join("chef-de-vibe-s1"));
+        assert_eq!(read("memory.max"), "536870912");
+        assert_eq!(read("cpu.max"), "150000 100000");
+        assert_eq!(read("pids.max"), "64");
+
+        let killed = ExitStatus::from_raw(Signal::SIGKILL as i32);
+        assert_eq!(exceeded(killed, Some(&cgroup)), None);
+        std::fs::write(
+            cgroup.path.join("memory.events"),
+            "low 0\nhigh 0\nmax 12\noom 1\noom_kill 1\n",
+        )
+        .unwrap();
+        assert_eq!(exceeded(killed, Some(&cgroup)), Some(ResourceLimit::Memory));
+        assert_eq!(exceeded(killed, None), None);
+        let xcpu = ExitStatus::from_raw(Signal::SIGXCPU as i32);
+        assert_eq!(exceeded(xcpu, None), Some(ResourceLimit::Cpu));
+        assert_eq!(exceeded(ExitStatus::from_raw(1 << 8), Some(&cgroup)), None);
+
+        let nested = root.path().join("nested");
+        std::fs::create_dir(&nested).unwrap();
+        for session_id in ["x/../../escaped", "..", "a/b"] {
+            let refused = SessionCgroup::create(&nested, session_id, &limits(Some(&nested)));
+            assert_eq!(
+                refused.unwrap_err().kind(),
+                std::io::ErrorKind::InvalidInput
+            );
+        }
+        assert!(!root.path().join("escaped").exists());
+    }
+
+    #[tokio::test]
+    async fn test_cpu_limit_kills_runaway() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "while :; do :; done"]);
+        let limits = ResourceLimits {
+            cpu_secs: Some(1),
+            ..ResourceLimits::default()
+        };
+        assert!(apply(&limits, &mut cmd, "s1").unwrap().is_none());
+        let status = cmd.status().await.unwrap();
+        assert_eq!(exceeded(status, None), Some(ResourceLimit::Cpu));
+    }
+}
@@ -0,0 +1,328 @@
+use crate::config::ScratchSettings;
+use crate::error::{OrchestratorError, OrchestratorResult};
+use crate::models::is_valid_session_id;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+use walkdir::WalkDir;
+
+/// Environment variable Claude finds its session's scratch directory in.
+pub const SCRATCH_DIR_ENV: &str = "CHEF_DE_VIBE_SCRATCH_DIR";
+
+/// Most files listed for one directory.
+const MAX_LISTED_FILES: usize = 1000;
+
+/// Largest scratch file served.
+const MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// A file in a session's scratch directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ScratchFile {
+    /// Path relative to the scratch directory
+    pub path: String,
+    pub size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+    /// Running sessions using the directory
+    leases: usize,
+    /// Bumped on every release, so only the removal scheduled last goes ahead
+    generation: u64,
+}
+
+/// Per-session scratch directories under the configured root, removed once their
+/// session ended and the retention period passed. A resumed session gets its
+/// directory back if it is still there.
+#[derive(Debug)]
+pub struct ScratchDirs {
+    settings: ScratchSettings,
+    usage: Mutex<HashMap<PathBuf, Usage>>,
+}
+
+impl ScratchDirs {
+    #[must_use]
+    pub fn new(settings: ScratchSettings) -> Arc<Self> {
+        Arc::new(Self {
+            settings,
+            usage: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Directory of `session_id`, whether it exists or not.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ID cannot name a directory.
+    pub fn path(&self, session_id: &str) -> OrchestratorResult<PathBuf> {
+        if !is_valid_session_id(session_id) {
+            return Err(OrchestratorError::InvalidRequest(format!(
+                "Session ID {session_id} cannot name a scratch directory"
+            )));
+        }
+        Ok(self.settings.root.join(session_id))
+    }
+
+    /// Creates the directory of `session_id` if needed and keeps it while the lease lives.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ID cannot name a directory or it cannot be created.
+    pub fn lease(self: &Arc<Self>, session_id: &str) -> OrchestratorResult<ScratchLease> {
+        let path = self.path(session_id)?;
+        std::fs::create_dir_all(&path).map_err(|e| {
+            OrchestratorError::InternalError(format!(
+                "Failed to create scratch directory {}: {e}",
+                path.display()
+            ))
+        })?;
+        self.usage
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .entry(path.clone())
+            .or_default()
+            .leases += 1;
+        debug!(path = %path.display(), "Leased scratch directory");
+        Ok(ScratchLease {
+            dirs: self.clone(),
+            path,
+        })
+    }
+
+    fn release(self: &Arc<Self>, path: &Path) {
+        let generation = {
+            let mut usage = self
+                .usage
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            let entry = usage.entry(path.to_path_buf()).or_default();
+            entry.leases = entry.leases.saturating_sub(1);
+            entry.generation += 1;
+            if entry.leases > 0 {
+                return;
+            }
+            entry.generation
+        };
+        self.schedule_removal(path.to_path_buf(), generation, self.settings.retention);
+    }
+
+    /// Removes `path` after `delay`, unless it was leased or released again meanwhile.
+    fn schedule_removal(self: &Arc<Self>, path: PathBuf, generation: u64, delay: Duration) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let dirs = self.clone();
+        runtime.spawn(async move {
+            tokio::time::sleep(delay).await;
+            {
+                let mut usage = dirs
+                    .usage
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner);
+                match usage.get(&path) {
+                    Some(entry) if entry.leases > 0 || entry.generation != generation => return,
+                    _ => usage.remove(&path),
+                };
+            }
+            match tokio::fs::remove_dir_all(&path).await {
+                Ok(()) => info!(path = %path.display(), "Removed scratch directory"),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    warn!(path = %path.display(), error = %e, "Failed to remove scratch directory");
+                }
+            }
+        });
+    }
+
+    /// Schedules removal of directories left behind by an earlier run, counting their
+    /// retention from when they were last modified.
+    pub fn sweep(self: &Arc<Self>) {
+        let Ok(entries) = std::fs::read_dir(&self.settings.root) else {
+            return;
+        };
+        let now = SystemTime::now();
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if !metadata.is_dir() {
+                continue;
+            }
+            let idle = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .unwrap_or_default();
+            let delay = self.settings.retention.saturating_sub(idle);
+            self.schedule_removal(entry.path(), 0, delay);
+        }
+    }
+}
+
+/// Files under `dir`, by path, at most [`MAX_LISTED_FILES`] of them. The flag tells
+/// whether some were left out.
+#[must_use]
+pub fn list(dir: &Path) -> (Vec<ScratchFile>, bool) {
+    let mut files = Vec::new();
+    let mut truncated = false;
+    for entry in WalkDir::new(dir).sort_by_file_name().into_iter().flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        if files.len() == MAX_LISTED_FILES {
+            truncated = true;
+            break;
+        }
+        let Ok(relative) = entry.path().strip_prefix(dir) else {
+            continue;
+        };
+        let metadata = entry.metadata().ok();
+        files.push(ScratchFile {
+            path: relative.to_string_lossy().into_owned(),
+            size: metadata.as_ref().map_or(0, std::fs::Metadata::len),
+            modified: metadata
+                .and_then(|metadata| metadata.modified().ok())
+                .map(chrono::DateTime::from),
+        });
+    }
+    (files, truncated)
+}
+
+/// Content of the file at `relative` under `dir`.
+///
+/// # Errors
+///
+/// Returns an error if the path leaves `dir`, there is no such file, or it is over the
+/// size limit.
+pub async fn read(dir: &Path, relative: &str) -> OrchestratorResult<Vec<u8>> {
+    let not_found = || OrchestratorError::ScratchFileNotFound(relative.to_string());
+    if !Path::new(relative)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(OrchestratorError::InvalidRequest(format!(
+            "{relative} is not a path inside the scratch directory"
+        )));
+    }
+    let dir = tokio::fs::canonicalize(dir)
+        .await
+        .map_err(|_| not_found())?;
+    let path = tokio::fs::canonicalize(dir.join(relative))
+        .await
+        .map_err(|_| not_found())?;
+    // Symlinks may point anywhere
+    if !path.starts_with(&dir) {
+        return Err(OrchestratorError::InvalidRequest(format!(
+            "{relative} is not a path inside the scratch directory"
+        )));
+    }
+    let metadata = tokio::fs::metadata(&path).await.map_err(|_| not_found())?;
+    if !metadata.is_file() {
+        return Err(not_found());
+    }
+    if metadata.len() > MAX_FILE_SIZE {
+        return Err(OrchestratorError::PayloadTooLarge(format!(
+            "{relative} is larger than {MAX_FILE_SIZE} bytes"
+        )));
+    }
+    tokio::fs::read(&path).await.map_err(|e| {
+        OrchestratorError::InternalError(format!("Failed to read scratch file {relative}: {e}"))
+    })
+}
+
+/// Keeps a session's scratch directory from being removed; dropping it starts the
+/// retention period.
+#[derive(Debug)]
+pub struct ScratchLease {
+    dirs: Arc<ScratchDirs>,
+    path: PathBuf,
+}
+
+impl ScratchLease {
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ScratchLease {
+    fn drop(&mut self) {
+        self.dirs.release(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn dirs(root: &Path, retention: Duration) -> Arc<ScratchDirs> {
+        ScratchDirs::new(ScratchSettings {
+            root: root.to_path_buf(),
+            retention,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_lease_and_retention() {
+        let root = TempDir::new().unwrap();
+        let dirs = dirs(root.path(), Duration::from_millis(100));
+        assert!(dirs.path("../escape").is_err());
+        assert!(dirs.path("").is_err());
+
+        let lease = dirs.lease("session-a").unwrap();
+        let path = lease.path().to_path_buf();
+        assert!(path.is_dir());
+
+        // A resumed session takes the directory over before it is removed
+        drop(lease);
+        let resumed = dirs.lease("session-a").unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(path.is_dir());
+
+        drop(resumed);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(path.is_dir(), "Kept during the retention period");
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_list_and_read() {
+        let root = TempDir::new().unwrap();
+        let dir = root.path().join("session");
+        std::fs::create_dir_all(dir.join("reports")).unwrap();
+        std::fs::write(dir.join("reports/summary.md"), "# Summary").unwrap();
+        std::fs::write(dir.join("notes.txt"), "todo").unwrap();
+        std::fs::write(root.path().join("secret"), "no").unwrap();
+
+        let (files, truncated) = list(&dir);
+        assert!(!truncated);
+        let paths: Vec<_> = files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(paths, ["notes.txt", "reports/summary.md"]);
+        assert_eq!(files[1].size, 9);
+
+        assert_eq!(
+            read(&dir, "reports/summary.md").await.unwrap(),
+            b"# Summary"
+        );
+        assert!(matches!(
+            read(&dir, "../secret").await,
+            Err(OrchestratorError::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            read(&dir, "missing.txt").await,
+            Err(OrchestratorError::ScratchFileNotFound(_))
+        ));
+        assert!(matches!(
+            read(&dir, "reports").await,
+            Err(OrchestratorError::ScratchFileNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,105 @@
+use crate::resource_limits::ResourceLimit;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::sync::broadcast;
+use tracing::debug;
+
+/// Events a subscriber may fall behind by before it misses some.
+const CHANNEL_CAPACITY: usize = 256;
+
+/// What happened to a session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ServerEventKind {
+    SessionCreated {
+        working_directory: PathBuf,
+    },
+    /// Claude reported a new ID on resuming; later events use it
+    SessionRenamed {
+        previous_session_id: String,
+    },
+    /// A Claude process (or SDK bridge) serves the session
+    SessionActive,
+    /// Nothing serves the session anymore, for now or for good
+    SessionInactive,
+    ClaudeExited {
+        crashed: bool,
+        /// Whether Claude is respawned (see `CLAUDE_RESTART_MAX`)
+        restarting: bool,
+        /// Limit Claude was killed for exceeding
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        resource_limit: Option<ResourceLimit>,
+    },
+    PendingApprovals {
+        count: usize,
+    },
+}
+
+/// A session lifecycle event as sent on `/api/v1/events_ws`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerEvent {
+    pub session_id: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    #[serde(flatten)]
+    pub kind: ServerEventKind,
+}
+
+/// Fans session lifecycle events out to everyone following the whole server, so
+/// frontends learn about sessions coming and going without polling.
+#[derive(Debug)]
+pub struct ServerEvents {
+    tx: broadcast::Sender<ServerEvent>,
+}
+
+impl Default for ServerEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServerEvents {
+    #[must_use]
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { tx }
+    }
+
+    /// Sends an event to the current subscribers; without any it is dropped.
+    pub fn publish(&self, session_id: String, kind: ServerEventKind) {
+        debug!(session_id = %session_id, event = ?kind, "Publishing server event");
+        let _ = self.tx.send(ServerEvent {
+            session_id,
+            timestamp: chrono::Utc::now(),
+            kind,
+        });
+    }
+
+    #[must_use]
+    pub fn subscribe(&self) -> broadcast::Receiver<ServerEvent> {
+        self.tx.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_events_reach_subscribers() {
+        let events = ServerEvents::new();
+        events.publish("before".to_string(), ServerEventKind::SessionActive);
+        let mut rx = events.subscribe();
+        events.publish(
+            "s1".to_string(),
+            ServerEventKind::PendingApprovals { count: 2 },
+        );
+
+        let event = rx.recv().await.unwrap();
+        assert_eq!(event.session_id, "s1");
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "pending_approvals");
+        assert_eq!(json["count"], 2);
+        assert_eq!(serde_json::from_value::<ServerEvent>(json).unwrap(), event);
+        assert!(rx.try_recv().is_err());
+    }
+}
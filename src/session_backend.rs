@@ -0,0 +1,718 @@
+use crate::claude_cli::{Capabilities, SpawnArgs};
+use crate::claude_process::{compact_json_message, ClaudeProcess};
+use crate::config::{Config, ContainerSettings};
+use crate::error::{OrchestratorError, OrchestratorResult};
+use crate::models::SessionOptions;
+use crate::resource_limits::{self, ResourceLimit};
+use crate::scratch::SCRATCH_DIR_ENV;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+#[cfg(unix)]
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
+use utoipa::ToSchema;
+
+/// How long a bridge may take to acknowledge a `start` request.
+const BRIDGE_START_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What a backend needs to know to start (or resume) a session.
+#[derive(Debug, Clone, Copy)]
+pub struct StartRequest<'a> {
+    pub session_id: &'a str,
+    pub working_dir: &'a Path,
+    pub resume: bool,
+    pub bootstrap_messages: &'a [String],
+    pub options: &'a SessionOptions,
+    /// Directory Claude may keep generated files in, outside the working tree
+    pub scratch_dir: Option<&'a Path>,
+}
+
+/// How a session ended on the backend side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEnd {
+    /// Claude exited successfully, or the bridge closed the session
+    Clean,
+    /// Claude exited with an error status or was killed by a signal
+    Crashed,
+    /// Claude was killed for going over one of its resource limits
+    ResourceLimitExceeded(ResourceLimit),
+}
+
+/// A running session: stream-json lines in both directions plus a way to learn
+/// when the other side is gone.
+pub struct BackendConnection {
+    /// Session ID Claude is actually using (differs from the requested one on resume)
+    pub session_id: String,
+    pub stdin_tx: mpsc::Sender<String>,
+    pub stdout_rx: mpsc::Receiver<String>,
+    /// OS process serving only this session, if the backend runs one
+    pub process_id: Option<u32>,
+    /// Resolves once the session has ended on the backend side
+    pub closed: Pin<Box<dyn Future<Output = SessionEnd> + Send>>,
+}
+
+/// Where session traffic goes. `SessionManager` only ever talks to this trait.
+#[async_trait]
+pub trait SessionBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Starts a session and sends its bootstrap messages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session could not be started.
+    async fn start(&self, request: StartRequest<'_>) -> OrchestratorResult<BackendConnection>;
+}
+
+/// Backend a session can ask to run on instead of the default one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendKind {
+    /// The Claude CLI on the host
+    Cli,
+    /// The Claude CLI in a container of `CONTAINER_IMAGE`
+    Container,
+}
+
+impl FromStr for BackendKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "cli" => Ok(Self::Cli),
+            "container" => Ok(Self::Container),
+            other => anyhow::bail!("expected 'cli' or 'container', got '{other}'"),
+        }
+    }
+}
+
+/// The backends sessions run on: the one selected by the configuration, and those a
+/// session can ask for by kind.
+#[derive(Clone)]
+pub struct Backends {
+    default: Arc<dyn SessionBackend>,
+    cli: Arc<dyn SessionBackend>,
+    /// Set when `CONTAINER_IMAGE` is
+    container: Option<Arc<dyn SessionBackend>>,
+}
+
+impl Backends {
+    /// The backends the configuration provides. An SDK bridge serves every session that
+    /// does not ask for a backend, then `SESSION_BACKEND`, then containers when they are
+    /// configured.
+    #[must_use]
+    pub fn from_config(config: &Arc<Config>) -> Self {
+        let cli: Arc<dyn SessionBackend> = Arc::new(CliBackend::new(config.clone()));
+        let container = config.container.as_ref().map(|container| {
+            Arc::new(ContainerBackend::new(
+                container.clone(),
+                config.claude_projects_dir.parent().map(Path::to_path_buf),
+                config.partial_messages,
+            )) as Arc<dyn SessionBackend>
+        });
+        let default = match config.session_backend {
+            Some(BackendKind::Cli) => cli.clone(),
+            // `SESSION_BACKEND=container` without `CONTAINER_IMAGE` fails config validation
+            _ => container.clone().unwrap_or_else(|| cli.clone()),
+        };
+        #[cfg(unix)]
+        let default = match &config.claude_sdk_socket {
+            Some(socket_path) => Arc::new(SdkSocketBackend::new(socket_path.clone())),
+            None => default,
+        };
+        Self {
+            default,
+            cli,
+            container,
+        }
+    }
+
+    /// The backend a session asking for `kind` runs on; the default one when it asks for
+    /// none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session asks for containers and none are configured.
+    pub fn select(&self, kind: Option<BackendKind>) -> OrchestratorResult<Arc<dyn SessionBackend>> {
+        match kind {
+            None => Ok(self.default.clone()),
+            Some(BackendKind::Cli) => Ok(self.cli.clone()),
+            Some(BackendKind::Container) => self.container.clone().ok_or_else(|| {
+                OrchestratorError::InvalidRequest(
+                    "The container backend is not configured (CONTAINER_IMAGE)".to_string(),
+                )
+            }),
+        }
+    }
+}
+
+/// Spawns one interactive Claude CLI process per session.
+pub struct CliBackend {
+    config: Arc<Config>,
+}
+
+impl CliBackend {
+    #[must_use]
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait]
+impl SessionBackend for CliBackend {
+    fn name(&self) -> &'static str {
+        "cli"
+    }
+
+    async fn start(&self, request: StartRequest<'_>) -> OrchestratorResult<BackendConnection> {
+        let (process, session_id) = ClaudeProcess::spawn(
+            &self.config,
+            request.session_id,
+            request.working_dir,
+            request.resume,
+            request.bootstrap_messages,
+            request.options,
+            request.scratch_dir,
+        )
+        .await?;
+
+        Ok(process_connection(process, session_id))
+    }
+}
+
+/// Connection to a session served by a child process speaking stream-json on stdio.
+fn process_connection(process: ClaudeProcess, session_id: String) -> BackendConnection {
+    let mut child = process.child;
+    let cgroup = process.cgroup;
+    let process_id = child.id();
+    let waiter_session_id = session_id.clone();
+    let closed = Box::pin(async move {
+        // Waiting on the child is what reaps it, so this must always be driven
+        let end = match child.wait().await {
+            Ok(status) => {
+                warn!(
+                    session_id = %waiter_session_id,
+                    process_id = ?process_id,
+                    exit_code = status.code(),
+                    exit_success = status.success(),
+                    "Claude process has exited"
+                );
+                if status.success() {
+                    SessionEnd::Clean
+                } else if let Some(limit) = resource_limits::exceeded(status, cgroup.as_ref()) {
+                    warn!(
+                        session_id = %waiter_session_id,
+                        process_id = ?process_id,
+                        limit = ?limit,
+                        "Claude process was killed for exceeding its resource limit"
+                    );
+                    SessionEnd::ResourceLimitExceeded(limit)
+                } else {
+                    SessionEnd::Crashed
+                }
+            }
+            Err(e) => {
+                error!(
+                    session_id = %waiter_session_id,
+                    process_id = ?process_id,
+                    error = %e,
+                    "Error waiting for Claude process to exit"
+                );
+                SessionEnd::Crashed
+            }
+        };
+        if let Some(cgroup) = cgroup {
+            cgroup.remove().await;
+        }
+        end
+    });
+
+    BackendConnection {
+        session_id,
+        stdin_tx: process.stdin_tx,
+        stdout_rx: process.stdout_rx,
+        process_id,
+        closed,
+    }
+}
+
+/// Runs each session's Claude in a throwaway container, with stdio attached through
+/// the container runtime's `run -i`.
+///
+/// The working directory is mounted at the same path inside the container, and the
+/// host's Claude config directory at `config_mount`, so transcripts are written where
+/// discovery finds them. The reported process is the runtime client, which forwards
+/// signals to the container.
+pub struct ContainerBackend {
+    container: ContainerSettings,
+    /// Host directory holding Claude's config and projects
+    claude_config_dir: Option<PathBuf>,
+    partial_messages: bool,
+}
+
+impl ContainerBackend {
+    #[must_use]
+    pub fn new(
+        container: ContainerSettings,
+        claude_config_dir: Option<PathBuf>,
+        partial_messages: bool,
+    ) -> Self {
+        Self {
+            container,
+            claude_config_dir,
+            partial_messages,
+        }
+    }
+
+    fn command(&self, request: &StartRequest<'_>) -> tokio::process::Command {
+        let container = &self.container;
+        let mut cmd = tokio::process::Command::new(&container.runtime);
+        cmd.current_dir(request.working_dir)
+            .args(["run", "--rm", "--interactive"])
+            .arg(format!(
+                "--label=chef-de-vibe.session={}",
+                request.session_id
+            ))
+            .arg(format!("--volume={0}:{0}", request.working_dir.display()))
+            .arg(format!("--workdir={}", request.working_dir.display()));
+        if let Some(scratch_dir) = request.scratch_dir {
+            cmd.arg(format!("--volume={0}:{0}", scratch_dir.display()))
+                .arg(format!("--env={SCRATCH_DIR_ENV}={}", scratch_dir.display()));
+        }
+        if let Some(config_dir) = &self.claude_config_dir {
+            cmd.arg(format!(
+                "--volume={}:{}",
+                config_dir.display(),
+                container.config_mount.display()
+            ));
+        }
+        if std::env::var_os("ANTHROPIC_API_KEY").is_some() {
+            // Passed by name so the key stays out of the command line
+            cmd.arg("--env=ANTHROPIC_API_KEY");
+        }
+        if let Some(memory) = &container.memory {
+            cmd.arg(format!("--memory={memory}"));
+        }
+        if let Some(cpus) = &container.cpus {
+            cmd.arg(format!("--cpus={cpus}"));
+        }
+        cmd.args(&container.extra_args)
+            .arg(&container.image)
+            .arg(&container.claude_binary);
+
+        // The CLI in the image cannot be probed from the host, so assume a current one
+        cmd.args(
+            SpawnArgs::new(Capabilities::for_version(None), request.session_id)
+                .resume(request.resume)
+                .options(request.options)
+                .partial_messages(self.partial_messages)
+                .build(),
+        );
+        cmd
+    }
+}
+
+#[async_trait]
+impl SessionBackend for ContainerBackend {
+    fn name(&self) -> &'static str {
+        "container"
+    }
+
+    async fn start(&self, request: StartRequest<'_>) -> OrchestratorResult<BackendConnection> {
+        info!(
+            session_id = %request.session_id,
+            runtime = %self.container.runtime.display(),
+            image = %self.container.image,
+            "Starting Claude in a container"
+        );
+        let (process, session_id) = ClaudeProcess::spawn_command(
+            self.command(&request),
+            request.session_id,
+            request.resume,
+            request.bootstrap_messages,
+        )
+        .await?;
+        Ok(process_connection(process, session_id))
+    }
+}
+
+/// Talks stream-json to an Agent SDK bridge over a Unix socket, one connection per
+/// session, instead of spawning the CLI.
+///
+/// The first line on a connection is a `start` request:
+/// `{"type":"start","session_id":..,"cwd":..,"resume":..,"system_prompt":..,"append_system_prompt":..,"cli_flags":..,"scratch_dir":..}`.
+/// The bridge answers `{"type":"started","session_id":..}` (or `{"type":"error","message":..}`),
+/// after which lines are exchanged exactly as on the CLI's stdin/stdout. Closing the
+/// connection ends the session.
+#[cfg(unix)]
+pub struct SdkSocketBackend {
+    socket_path: PathBuf,
+}
+
+#[cfg(unix)]
+impl SdkSocketBackend {
+    #[must_use]
+    pub fn new(socket_path: PathBuf) -> Self {
+        Self { socket_path }
+    }
+
+    fn start_line(request: &StartRequest<'_>) -> String {
+        serde_json::json!({
+            "type": "start",
+            "session_id": request.session_id,
+            "cwd": request.working_dir,
+            "resume": request.resume,
+            "system_prompt": request.options.system_prompt,
+            "append_system_prompt": request.options.append_system_prompt,
+            "cli_flags": request.options.cli_flags,
+            "scratch_dir": request.scratch_dir,
+        })
+        .to_string()
+    }
+
+    /// Pumps lines between the socket and the session channels until either side closes.
+    fn connection(
+        session_id: String,
+        reader: BufReader<OwnedReadHalf>,
+        mut writer: OwnedWriteHalf,
+    ) -> BackendConnection {
+        let (stdin_tx, mut stdin_rx) = mpsc::channel::<String>(100);
+        let writer_session_id = session_id.clone();
+        tokio::spawn(async move {
+            while let Some(msg) = stdin_rx.recv().await {
+                let mut line = msg;
+                line.push('\n');
+                if let Err(e) = writer.write_all(line.as_bytes()).await {
+                    error!(session_id = %writer_session_id, error = %e, "Failed to write to Agent SDK bridge");
+                    break;
+                }
+            }
+            debug!(session_id = %writer_session_id, "Agent SDK bridge writer stopped");
+        });
+
+        let (stdout_tx, stdout_rx) = mpsc::channel::<String>(100);
+        let (closed_tx, closed_rx) = tokio::sync::oneshot::channel::<()>();
+        let reader_session_id = session_id.clone();
+        tokio::spawn(async move {
+            let mut lines = reader.lines();
+            loop {
+                match lines.next_line().await {
+                    Ok(Some(line)) => {
+                        if stdout_tx.send(line).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        error!(session_id = %reader_session_id, error = %e, "Failed to read from Agent SDK bridge");
+                        break;
+                    }
+                }
+            }
+            warn!(session_id = %reader_session_id, "Agent SDK bridge closed the session");
+            let _ = closed_tx.send(());
+        });
+
+        BackendConnection {
+            session_id,
+            stdin_tx,
+            stdout_rx,
+            process_id: None,
+            closed: Box::pin(async move {
+                let _ = closed_rx.await;
+                SessionEnd::Clean
+            }),
+        }
+    }
+}
+
+#[cfg(unix)]
+#[async_trait]
+impl SessionBackend for SdkSocketBackend {
+    fn name(&self) -> &'static str {
+        "sdk-socket"
+    }
+
+    async fn start(&self, request: StartRequest<'_>) -> OrchestratorResult<BackendConnection> {
+        let spawn_failed = |message: String| {
+            error!(
+                session_id = %request.session_id,
+                socket = %self.socket_path.display(),
+                error = %message,
+                "Failed to start session through Agent SDK bridge"
+            );
+            OrchestratorError::ClaudeSpawnFailed(message)
+        };
+
+        let stream = tokio::net::UnixStream::connect(&self.socket_path)
+            .await
+            .map_err(|e| {
+                spawn_failed(format!(
+                    "Failed to connect to Agent SDK bridge at {}: {e}",
+                    self.socket_path.display()
+                ))
+            })?;
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        let mut handshake = Self::start_line(&request);
+        handshake.push('\n');
+        for (line_idx, line) in request.bootstrap_messages.iter().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            handshake.push_str(&compact_json_message(
+                line,
+                &format!("bootstrap_messages_line_{line_idx}"),
+            )?);
+            handshake.push('\n');
+        }
+        writer
+            .write_all(handshake.as_bytes())
+            .await
+            .map_err(|e| spawn_failed(format!("Failed to send start request: {e}")))?;
+
+        let mut reply = String::new();
+        match tokio::time::timeout(BRIDGE_START_TIMEOUT, reader.read_line(&mut reply)).await {
+            Ok(Ok(0)) => return Err(spawn_failed("Bridge closed the connection".into())),
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => return Err(spawn_failed(format!("Failed to read start reply: {e}"))),
+            Err(_) => return Err(spawn_failed("Timed out waiting for start reply".into())),
+        }
+        let reply: serde_json::Value = serde_json::from_str(&reply)
+            .map_err(|e| spawn_failed(format!("Invalid start reply: {e}")))?;
+        let session_id = match reply.get("type").and_then(|t| t.as_str()) {
+            Some("started") => reply
+                .get("session_id")
+                .and_then(|id| id.as_str())
+                .unwrap_or(request.session_id)
+                .to_string(),
+            Some("error") => {
+                let message = reply
+                    .get("message")
+                    .and_then(|m| m.as_str())
+                    .unwrap_or("unknown error");
+                return Err(spawn_failed(format!("Bridge refused session: {message}")));
+            }
+            _ => return Err(spawn_failed(format!("Unexpected start reply: {reply}"))),
+        };
+        info!(
+            requested_session_id = %request.session_id,
+            session_id = %session_id,
+            socket = %self.socket_path.display(),
+            "Session started through Agent SDK bridge"
+        );
+
+        Ok(Self::connection(session_id, reader, writer))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use tokio::net::UnixListener;
+
+    #[tokio::test]
+    async fn test_sdk_socket_backend_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let socket_path = dir.path().join("bridge.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+
+        // Minimal bridge: acknowledge under a new ID, echo one message, then hang up
+        let bridge = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+
+            let start: serde_json::Value =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            let bootstrap = lines.next_line().await.unwrap().unwrap();
+            writer
+                .write_all(b"{\"type\":\"started\",\"session_id\":\"resumed-id\"}\n")
+                .await
+                .unwrap();
+            let message = lines.next_line().await.unwrap().unwrap();
+            writer
+                .write_all(format!("{message}\n").as_bytes())
+                .await
+                .unwrap();
+            (start, bootstrap)
+        });
+
+        let backend = SdkSocketBackend::new(socket_path);
+        let options = SessionOptions {
+            system_prompt: Some("terse".to_string()),
+            append_system_prompt: None,
+            ..Default::default()
+        };
+        let mut connection = backend
+            .start(StartRequest {
+                session_id: "original-id",
+                working_dir: Path::new("/tmp"),
+                resume: true,
+                bootstrap_messages: &["{\n\"role\": \"user\"}".to_string()],
+                options: &options,
+                scratch_dir: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(connection.session_id, "resumed-id");
+        assert_eq!(connection.process_id, None);
+
+        connection
+            .stdin_tx
+            .send(r#"{"role":"user","content":"hi"}"#.to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            connection.stdout_rx.recv().await.unwrap(),
+            r#"{"role":"user","content":"hi"}"#
+        );
+
+        let (start, bootstrap) = bridge.await.unwrap();
+        assert_eq!(start["type"], "start");
+        assert_eq!(start["session_id"], "original-id");
+        assert_eq!(start["resume"], true);
+        assert_eq!(start["system_prompt"], "terse");
+        assert_eq!(bootstrap, r#"{"role":"user"}"#);
+
+        let end = tokio::time::timeout(Duration::from_secs(5), connection.closed)
+            .await
+            .expect("Closing the bridge connection should end the session");
+        assert_eq!(end, SessionEnd::Clean);
+    }
+
+    #[tokio::test]
+    async fn test_sdk_socket_backend_refused() {
+        let dir = TempDir::new().unwrap();
+        let socket_path = dir.path().join("bridge.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream
+                .write_all(b"{\"type\":\"error\",\"message\":\"no capacity\"}\n")
+                .await
+                .unwrap();
+        });
+
+        let result = SdkSocketBackend::new(socket_path)
+            .start(StartRequest {
+                session_id: "refused",
+                working_dir: Path::new("/tmp"),
+                resume: false,
+                bootstrap_messages: &[],
+                options: &SessionOptions::default(),
+                scratch_dir: None,
+            })
+            .await;
+        match result {
+            Err(OrchestratorError::ClaudeSpawnFailed(message)) => {
+                assert!(message.contains("no capacity"));
+            }
+            other => panic!("Expected ClaudeSpawnFailed, got {:?}", other.err()),
+        }
+    }
+
+    fn container_settings(runtime: PathBuf) -> ContainerSettings {
+        ContainerSettings {
+            runtime,
+            image: "claude-sandbox:latest".to_string(),
+            claude_binary: "claude".to_string(),
+            config_mount: PathBuf::from("/root/.claude"),
+            memory: Some("2g".to_string()),
+            cpus: None,
+            extra_args: vec!["--network=none".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_container_backend_command() {
+        let backend = ContainerBackend::new(
+            container_settings(PathBuf::from("podman")),
+            Some(PathBuf::from("/home/user/.claude")),
+            false,
+        );
+        let options = SessionOptions::default();
+        let cmd = backend.command(&StartRequest {
+            session_id: "boxed",
+            working_dir: Path::new("/home/user/project"),
+            resume: false,
+            bootstrap_messages: &[],
+            options: &options,
+            scratch_dir: Some(Path::new("/var/scratch/boxed")),
+        });
+        let cmd = cmd.as_std();
+        assert_eq!(cmd.get_program(), "podman");
+        let args: Vec<_> = cmd.get_args().map(|a| a.to_str().unwrap()).collect();
+
+        let image = args
+            .iter()
+            .position(|a| *a == "claude-sandbox:latest")
+            .unwrap();
+        let run_args = &args[..image];
+        assert_eq!(&run_args[..3], ["run", "--rm", "--interactive"]);
+        for expected in [
+            "--label=chef-de-vibe.session=boxed",
+            "--volume=/home/user/project:/home/user/project",
+            "--workdir=/home/user/project",
+            "--volume=/home/user/.claude:/root/.claude",
+            "--memory=2g",
+            "--network=none",
+            "--volume=/var/scratch/boxed:/var/scratch/boxed",
+            "--env=CHEF_DE_VIBE_SCRATCH_DIR=/var/scratch/boxed",
+        ] {
+            assert!(run_args.contains(&expected), "missing {expected}");
+        }
+        assert!(!run_args.iter().any(|a| a.starts_with("--cpus")));
+
+        // Claude's own arguments follow the image
+        assert_eq!(args[image + 1], "claude");
+        assert!(args[image + 2..].contains(&"--session-id"));
+        assert!(args[image + 2..].contains(&"boxed"));
+    }
+
+    #[tokio::test]
+    async fn test_container_backend_round_trip() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Fake runtime that ignores its arguments and echoes stdin, like the CLI mock
+        let dir = TempDir::new().unwrap();
+        let runtime = dir.path().join("runtime");
+        std::fs::write(&runtime, "#!/bin/sh\nexec cat\n").unwrap();
+        std::fs::set_permissions(&runtime, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let backend = ContainerBackend::new(container_settings(runtime), None, false);
+        let options = SessionOptions::default();
+        let mut connection = backend
+            .start(StartRequest {
+                session_id: "boxed",
+                working_dir: dir.path(),
+                resume: false,
+                bootstrap_messages: &[r#"{"role": "user"}"#.to_string()],
+                options: &options,
+                scratch_dir: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(connection.session_id, "boxed");
+        assert!(connection.process_id.is_some());
+        assert_eq!(
+            connection.stdout_rx.recv().await.unwrap(),
+            r#"{"role":"user"}"#
+        );
+
+        drop(connection.stdin_tx);
+        tokio::time::timeout(Duration::from_secs(5), connection.closed)
+            .await
+            .expect("The runtime exiting should end the session");
+    }
+}
@@ -0,0 +1,307 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{debug, instrument, warn};
+use utoipa::ToSchema;
+
+/// Placeholder for secret-looking values of MCP server configs.
+const REDACTED: &str = "[redacted]";
+
+/// Where a piece of standing configuration comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextScope {
+    /// The user's own configuration, applies to every project
+    User,
+    /// Checked into the project (or one of its parent directories)
+    Project,
+    /// Personal overrides inside the project, usually not checked in
+    Local,
+}
+
+/// A memory file (`CLAUDE.md` and friends) Claude loads as instructions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct InstructionFile {
+    pub scope: ContextScope,
+    #[schema(value_type = String)]
+    pub path: PathBuf,
+    pub content: String,
+}
+
+/// The permission rules of one settings file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SettingsFile {
+    pub scope: ContextScope,
+    #[schema(value_type = String)]
+    pub path: PathBuf,
+    /// The file's `permissions` object, `null` if it has none
+    #[schema(value_type = Object)]
+    pub permissions: serde_json::Value,
+}
+
+/// One configured MCP server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct McpServer {
+    pub name: String,
+    pub scope: ContextScope,
+    #[schema(value_type = String)]
+    pub path: PathBuf,
+    /// The server's config with `env` and `headers` values redacted
+    #[schema(value_type = Object)]
+    pub config: serde_json::Value,
+}
+
+/// What Claude is told before it is asked anything in a working directory.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionContext {
+    pub instructions: Vec<InstructionFile>,
+    pub settings: Vec<SettingsFile>,
+    pub mcp_servers: Vec<McpServer>,
+}
+
+async fn read_optional(path: &Path) -> Option<String> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "Failed to read context file");
+            None
+        }
+    }
+}
+
+async fn read_json(path: &Path) -> Option<serde_json::Value> {
+    let content = read_optional(path).await?;
+    match serde_json::from_str(&content) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "Ignoring unparsable context file");
+            None
+        }
+    }
+}
+
+/// Keeps an MCP server config readable without exposing the secrets usually kept in
+/// its environment and headers.
+fn redact_mcp_config(mut config: serde_json::Value) -> serde_json::Value {
+    for field in ["env", "headers"] {
+        if let Some(values) = config.get_mut(field).and_then(|v| v.as_object_mut()) {
+            for value in values.values_mut() {
+                *value = serde_json::Value::String(REDACTED.to_string());
+            }
+        }
+    }
+    config
+}
+
+fn mcp_servers(
+    servers: Option<&serde_json::Value>,
+    scope: ContextScope,
+    path: &Path,
+) -> Vec<McpServer> {
+    servers
+        .and_then(|s| s.as_object())
+        .map(|servers| {
+            servers
+                .iter()
+                .map(|(name, config)| McpServer {
+                    name: name.clone(),
+                    scope,
+                    path: path.to_path_buf(),
+                    config: redact_mcp_config(config.clone()),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Collects the instructions, permission settings and MCP servers that apply to Claude
+/// in `working_dir`.
+///
+/// `user_dir` is the user's Claude directory (`~/.claude`); the user-wide MCP config is
+/// read from `.claude.json` next to it. Project memory files are picked up from
+/// `working_dir` and every parent directory, outermost first, the way Claude loads them.
+/// Missing files are skipped and unreadable ones logged and skipped.
+#[instrument(fields(user_dir = %user_dir.display(), working_dir = %working_dir.display()))]
+pub async fn collect(user_dir: &Path, working_dir: &Path) -> SessionContext {
+    let mut context = SessionContext::default();
+
+    let mut instruction_paths = vec![(ContextScope::User, user_dir.join("CLAUDE.md"))];
+    let mut ancestors: Vec<&Path> = working_dir.ancestors().collect();
+    ancestors.reverse();
+    for dir in ancestors {
+        instruction_paths.push((ContextScope::Project, dir.join("CLAUDE.md")));
+        instruction_paths.push((ContextScope::Project, dir.join(".claude").join("CLAUDE.md")));
+        instruction_paths.push((ContextScope::Local, dir.join("CLAUDE.local.md")));
+    }
+    // In the home directory `.claude/CLAUDE.md` is the user's own file, listed once
+    let mut seen = std::collections::HashSet::new();
+    instruction_paths.retain(|(_, path)| seen.insert(path.clone()));
+    for (scope, path) in instruction_paths {
+        if let Some(content) = read_optional(&path).await {
+            context.instructions.push(InstructionFile {
+                scope,
+                path,
+                content,
+            });
+        }
+    }
+
+    for (scope, path) in [
+        (ContextScope::User, user_dir.join("settings.json")),
+        (
+            ContextScope::Project,
+            working_dir.join(".claude").join("settings.json"),
+        ),
+        (
+            ContextScope::Local,
+            working_dir.join(".claude").join("settings.local.json"),
+        ),
+    ] {
+        if let Some(settings) = read_json(&path).await {
+            context.settings.push(SettingsFile {
+                scope,
+                permissions: settings
+                    .get("permissions")
+                    .cloned()
+                    .unwrap_or(serde_json::Value::Null),
+                path,
+            });
+        }
+    }
+
+    if let Some(user_config_path) = user_dir.parent().map(|home| home.join(".claude.json")) {
+        if let Some(user_config) = read_json(&user_config_path).await {
+            context.mcp_servers.extend(mcp_servers(
+                user_config.get("mcpServers"),
+                ContextScope::User,
+                &user_config_path,
+            ));
+            // Servers added with `claude mcp add` without a scope live under the project's path
+            let project = user_config
+                .get("projects")
+                .and_then(|projects| projects.get(working_dir.to_string_lossy().as_ref()));
+            context.mcp_servers.extend(mcp_servers(
+                project.and_then(|p| p.get("mcpServers")),
+                ContextScope::Local,
+                &user_config_path,
+            ));
+        }
+    }
+    let project_mcp_path = working_dir.join(".mcp.json");
+    if let Some(project_mcp) = read_json(&project_mcp_path).await {
+        context.mcp_servers.extend(mcp_servers(
+            project_mcp.get("mcpServers"),
+            ContextScope::Project,
+            &project_mcp_path,
+        ));
+    }
+
+    debug!(
+        instructions = context.instructions.len(),
+        settings = context.settings.len(),
+        mcp_servers = context.mcp_servers.len(),
+        "Collected session context"
+    );
+    context
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_collect() {
+        let temp = TempDir::new().unwrap();
+        let user_dir = temp.path().join(".claude");
+        let parent = temp.path().join("work");
+        let project = parent.join("project");
+        fs::create_dir_all(&user_dir).unwrap();
+        fs::create_dir_all(project.join(".claude")).unwrap();
+
+        fs::write(user_dir.join("CLAUDE.md"), "Always be polite.").unwrap();
+        fs::write(parent.join("CLAUDE.md"), "Monorepo rules.").unwrap();
+        fs::write(project.join("CLAUDE.md"), "Run cargo test.").unwrap();
+        fs::write(project.join("CLAUDE.local.md"), "My sandbox URL.").unwrap();
+        fs::write(
+            user_dir.join("settings.json"),
+            r#"{"permissions": {"allow": ["Bash(git status)"]}, "model": "opus"}"#,
+        )
+        .unwrap();
+        fs::write(
+            project.join(".claude").join("settings.json"),
+            r#"{"env": {}}"#,
+        )
+        .unwrap();
+        fs::write(
+            project.join(".claude").join("settings.local.json"),
+            "{broken",
+        )
+        .unwrap();
+        fs::write(
+            project.join(".mcp.json"),
+            r#"{"mcpServers": {"db": {"command": "db-mcp", "env": {"DB_PASSWORD": "hunter2"}}}}"#,
+        )
+        .unwrap();
+        fs::write(
+            temp.path().join(".claude.json"),
+            serde_json::json!({
+                "mcpServers": {"search": {"type": "http", "url": "https://mcp.example.com", "headers": {"Authorization": "Bearer x"}}},
+                "projects": {project.to_string_lossy(): {"mcpServers": {"notes": {"command": "notes-mcp"}}}},
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let context = collect(&user_dir, &project).await;
+
+        let instructions: Vec<(ContextScope, &str)> = context
+            .instructions
+            .iter()
+            .map(|file| (file.scope, file.content.as_str()))
+            .collect();
+        assert_eq!(
+            instructions,
+            vec![
+                (ContextScope::User, "Always be polite."),
+                (ContextScope::Project, "Monorepo rules."),
+                (ContextScope::Project, "Run cargo test."),
+                (ContextScope::Local, "My sandbox URL."),
+            ]
+        );
+
+        // The unparsable local settings file is skipped
+        assert_eq!(context.settings.len(), 2);
+        assert_eq!(context.settings[0].scope, ContextScope::User);
+        assert_eq!(
+            context.settings[0].permissions,
+            serde_json::json!({"allow": ["Bash(git status)"]})
+        );
+        assert_eq!(context.settings[1].scope, ContextScope::Project);
+        assert!(context.settings[1].permissions.is_null());
+
+        let servers: Vec<(&str, ContextScope)> = context
+            .mcp_servers
+            .iter()
+            .map(|server| (server.name.as_str(), server.scope))
+            .collect();
+        assert_eq!(
+            servers,
+            vec![
+                ("search", ContextScope::User),
+                ("notes", ContextScope::Local),
+                ("db", ContextScope::Project),
+            ]
+        );
+        assert_eq!(
+            context.mcp_servers[0].config["headers"]["Authorization"],
+            REDACTED
+        );
+        assert_eq!(
+            context.mcp_servers[2].config["env"]["DB_PASSWORD"],
+            REDACTED
+        );
+        assert_eq!(context.mcp_servers[2].config["command"], "db-mcp");
+    }
+}
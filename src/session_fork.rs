@@ -0,0 +1,155 @@
+use crate::anchors;
+use crate::error::{OrchestratorError, OrchestratorResult};
+use crate::session_import::project_dir_name;
+use std::collections::HashSet;
+use std::path::Path;
+use tracing::info;
+use uuid::Uuid;
+
+/// Journal lines of `content` as session `session_id`, up to and including the entry
+/// with `message_uuid`, or all of them without one.
+///
+/// # Errors
+///
+/// Returns an error if no entry of `content` has `message_uuid`.
+pub fn forked_lines(
+    content: &[serde_json::Value],
+    message_uuid: Option<&str>,
+    session_id: &str,
+) -> OrchestratorResult<Vec<serde_json::Value>> {
+    let end = match message_uuid {
+        Some(uuid) => {
+            anchors::entry_index(content, uuid).ok_or_else(|| {
+                OrchestratorError::InvalidRequest(format!("No message with uuid {uuid}"))
+            })? + 1
+        }
+        None => content.len(),
+    };
+    let kept = &content[..end];
+    let uuids: HashSet<&str> = kept
+        .iter()
+        .filter_map(|line| line.get("uuid").and_then(|u| u.as_str()))
+        .collect();
+
+    Ok(kept
+        .iter()
+        // Summaries of the part left behind would title the fork after it
+        .filter(|line| {
+            line["type"] != "summary"
+                || line["leafUuid"]
+                    .as_str()
+                    .is_some_and(|leaf| uuids.contains(leaf))
+        })
+        .map(|line| {
+            let mut line = line.clone();
+            if let Some(id) = line.get_mut("sessionId") {
+                *id = serde_json::Value::String(session_id.to_string());
+            }
+            line
+        })
+        .collect())
+}
+
+/// Writes the transcript `content` of a session of `working_dir`, cut after the entry
+/// with `message_uuid`, as a new session in the projects directory, where it can be
+/// resumed. Returns the ID of the new session.
+///
+/// # Errors
+///
+/// Returns an error if no entry of `content` has `message_uuid`, the cut leaves no
+/// messages, or the session file cannot be written.
+pub fn fork_session(
+    projects_dir: &Path,
+    working_dir: &Path,
+    content: &[serde_json::Value],
+    message_uuid: Option<&str>,
+) -> OrchestratorResult<String> {
+    let session_id = Uuid::new_v4().to_string();
+    let lines = forked_lines(content, message_uuid, &session_id)?;
+    let messages = lines
+        .iter()
+        .filter(|line| line["type"] != "summary")
+        .count();
+    if messages == 0 {
+        return Err(OrchestratorError::InvalidRequest(
+            "The session has no messages to fork".to_string(),
+        ));
+    }
+
+    let session_dir = projects_dir.join(project_dir_name(working_dir));
+    std::fs::create_dir_all(&session_dir)?;
+    let mut transcript = String::new();
+    for line in &lines {
+        transcript.push_str(&line.to_string());
+        transcript.push('\n');
+    }
+    std::fs::write(session_dir.join(format!("{session_id}.jsonl")), transcript)?;
+
+    info!(
+        session_id = %session_id,
+        working_dir = %working_dir.display(),
+        messages,
+        "Forked session transcript"
+    );
+    Ok(session_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn transcript() -> Vec<serde_json::Value> {
+        vec![
+            serde_json::json!({"type": "summary", "summary": "Whole thing", "leafUuid": "u3"}),
+            serde_json::json!({"type": "summary", "summary": "Start", "leafUuid": "u1"}),
+            serde_json::json!({"type": "user", "uuid": "u1", "sessionId": "source"}),
+            serde_json::json!({"type": "assistant", "uuid": "u2", "parentUuid": "u1", "sessionId": "source"}),
+            serde_json::json!({"type": "user", "uuid": "u3", "parentUuid": "u2", "sessionId": "source"}),
+        ]
+    }
+
+    #[test]
+    fn test_forked_lines_cut_at_message() {
+        let lines = forked_lines(&transcript(), Some("u2"), "fork").unwrap();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["summary"], "Start");
+        assert_eq!(lines[1]["uuid"], "u1");
+        assert_eq!(lines[1]["sessionId"], "fork");
+        assert_eq!(lines[2]["uuid"], "u2");
+
+        let lines = forked_lines(&transcript(), None, "fork").unwrap();
+        assert_eq!(lines.len(), 5);
+
+        assert!(matches!(
+            forked_lines(&transcript(), Some("missing"), "fork"),
+            Err(OrchestratorError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_fork_session_writes_transcript() {
+        let projects_dir = TempDir::new().unwrap();
+        let working_dir = Path::new("/work/app");
+        let session_id =
+            fork_session(projects_dir.path(), working_dir, &transcript(), Some("u1")).unwrap();
+
+        let path = projects_dir
+            .path()
+            .join("-work-app")
+            .join(format!("{session_id}.jsonl"));
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["sessionId"], session_id);
+
+        let only_summaries = &transcript()[..2];
+        assert!(matches!(
+            fork_session(projects_dir.path(), working_dir, only_summaries, None),
+            Err(OrchestratorError::InvalidRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,309 @@
+use crate::error::{OrchestratorError, OrchestratorResult};
+use crate::models::ImportedSession;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::Path;
+use tracing::info;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Conversation export formats sessions can be imported from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+    /// `conversations.json` of a Claude Desktop / claude.ai data export, or one
+    /// conversation of it.
+    ClaudeDesktop,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DesktopExport {
+    Many(Vec<DesktopConversation>),
+    One(DesktopConversation),
+}
+
+#[derive(Deserialize)]
+struct DesktopConversation {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    chat_messages: Vec<DesktopMessage>,
+}
+
+#[derive(Deserialize)]
+struct DesktopMessage {
+    sender: String,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    content: Vec<DesktopContent>,
+    created_at: Option<String>,
+    #[serde(default)]
+    attachments: Vec<DesktopAttachment>,
+}
+
+#[derive(Deserialize)]
+struct DesktopContent {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct DesktopAttachment {
+    #[serde(default)]
+    file_name: String,
+    #[serde(default)]
+    extracted_content: String,
+}
+
+impl DesktopMessage {
+    /// The message as plain text: its text blocks, then the text of its attachments.
+    fn plain_text(&self) -> String {
+        let blocks: Vec<&str> = self
+            .content
+            .iter()
+            .filter(|block| block.kind == "text" && !block.text.is_empty())
+            .map(|block| block.text.as_str())
+            .collect();
+        let mut text = if blocks.is_empty() {
+            self.text.clone()
+        } else {
+            blocks.join("\n\n")
+        };
+        for attachment in self
+            .attachments
+            .iter()
+            .filter(|a| !a.extracted_content.is_empty())
+        {
+            let _ = write!(
+                text,
+                "\n\n<attachment name=\"{}\">\n{}\n</attachment>",
+                attachment.file_name, attachment.extracted_content
+            );
+        }
+        text.trim().to_string()
+    }
+}
+
+/// Name of the projects subdirectory the Claude CLI keeps a directory's sessions in.
+pub(crate) fn project_dir_name(working_dir: &Path) -> String {
+    working_dir
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+/// Journal lines of a conversation imported as session `session_id`.
+fn conversation_lines(
+    conversation: &DesktopConversation,
+    session_id: &str,
+    working_dir: &Path,
+) -> Vec<serde_json::Value> {
+    let mut lines = Vec::new();
+    let mut parent_uuid: Option<String> = None;
+
+    for message in &conversation.chat_messages {
+        let text = message.plain_text();
+        let message_body = match message.sender.as_str() {
+            _ if text.is_empty() => continue,
+            "human" => serde_json::json!({"role": "user", "content": text}),
+            "assistant" => serde_json::json!({
+                "type": "message",
+                "role": "assistant",
+                "model": "<synthetic>",
+                "content": [{"type": "text", "text": text}],
+            }),
+            _ => continue,
+        };
+        let uuid = Uuid::new_v4().to_string();
+        lines.push(serde_json::json!({
+            "parentUuid": parent_uuid,
+            "isSidechain": false,
+            "userType": "external",
+            "cwd": working_dir,
+            "sessionId": session_id,
+            "type": message_body["role"],
+            "message": message_body,
+            "uuid": uuid,
+            "timestamp": message
+                .created_at
+                .clone()
+                .unwrap_or_else(|| chrono::Utc::now().to_rfc3339()),
+        }));
+        parent_uuid = Some(uuid);
+    }
+
+    if let Some(leaf_uuid) = parent_uuid.filter(|_| !conversation.name.is_empty()) {
+        lines.push(serde_json::json!({
+            "type": "summary",
+            "summary": conversation.name,
+            "leafUuid": leaf_uuid,
+        }));
+    }
+    lines
+}
+
+/// Writes every conversation of `export` with at least one message as a new session of
+/// `working_dir` in the projects directory, where it can be listed and resumed.
+///
+/// # Errors
+///
+/// Returns an error if the export is not in `format` or has no messages, or a session
+/// file cannot be written.
+pub fn import_sessions(
+    projects_dir: &Path,
+    working_dir: &Path,
+    format: ImportFormat,
+    export: serde_json::Value,
+) -> OrchestratorResult<Vec<ImportedSession>> {
+    let conversations = match format {
+        ImportFormat::ClaudeDesktop => match serde_json::from_value(export) {
+            Ok(DesktopExport::Many(conversations)) => conversations,
+            Ok(DesktopExport::One(conversation)) => vec![conversation],
+            Err(e) => {
+                return Err(OrchestratorError::InvalidRequest(format!(
+                    "Not a Claude Desktop export: {e}"
+                )))
+            }
+        },
+    };
+
+    let session_dir = projects_dir.join(project_dir_name(working_dir));
+    let mut imported = Vec::new();
+    for conversation in &conversations {
+        let session_id = Uuid::new_v4().to_string();
+        let lines = conversation_lines(conversation, &session_id, working_dir);
+        let messages = lines
+            .iter()
+            .filter(|line| line["type"] != "summary")
+            .count();
+        if messages == 0 {
+            continue;
+        }
+
+        std::fs::create_dir_all(&session_dir)?;
+        let mut content = String::new();
+        for line in &lines {
+            content.push_str(&line.to_string());
+            content.push('\n');
+        }
+        std::fs::write(session_dir.join(format!("{session_id}.jsonl")), content)?;
+
+        info!(
+            session_id = %session_id,
+            working_dir = %working_dir.display(),
+            messages,
+            "Imported conversation as session"
+        );
+        imported.push(ImportedSession {
+            session_id,
+            summary: Some(conversation.name.clone()).filter(|name| !name.is_empty()),
+            messages,
+        });
+    }
+
+    if imported.is_empty() {
+        return Err(OrchestratorError::InvalidRequest(
+            "The export has no conversations with messages".to_string(),
+        ));
+    }
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_project_dir_name() {
+        assert_eq!(
+            project_dir_name(Path::new("/home/user/my.project")),
+            "-home-user-my-project"
+        );
+    }
+
+    #[test]
+    fn test_import_claude_desktop_export() {
+        let projects_dir = TempDir::new().unwrap();
+        let working_dir = Path::new("/work/app");
+        let export = serde_json::json!([
+            {
+                "uuid": "conversation-1",
+                "name": "Refactor the parser",
+                "chat_messages": [
+                    {
+                        "sender": "human",
+                        "text": "Can you help?",
+                        "content": [{"type": "text", "text": "Can you help?"}],
+                        "created_at": "2024-05-01T12:00:00Z",
+                        "attachments": [{"file_name": "parser.rs", "extracted_content": "fn parse() {}"}]
+                    },
+                    {
+                        "sender": "assistant",
+                        "text": "",
+                        "content": [{"type": "text", "text": "Sure."}, {"type": "tool_use"}],
+                        "created_at": "2024-05-01T12:00:05Z"
+                    }
+                ]
+            },
+            {"uuid": "conversation-2", "name": "Empty", "chat_messages": []}
+        ]);
+
+        let imported = import_sessions(
+            projects_dir.path(),
+            working_dir,
+            ImportFormat::ClaudeDesktop,
+            export,
+        )
+        .unwrap();
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].messages, 2);
+        assert_eq!(imported[0].summary.as_deref(), Some("Refactor the parser"));
+
+        let path = projects_dir
+            .path()
+            .join("-work-app")
+            .join(format!("{}.jsonl", imported[0].session_id));
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["type"], "user");
+        assert_eq!(lines[0]["cwd"], "/work/app");
+        assert_eq!(lines[0]["parentUuid"], serde_json::Value::Null);
+        assert!(lines[0]["message"]["content"]
+            .as_str()
+            .unwrap()
+            .contains("<attachment name=\"parser.rs\">"));
+        assert_eq!(lines[1]["type"], "assistant");
+        assert_eq!(lines[1]["parentUuid"], lines[0]["uuid"]);
+        assert_eq!(lines[1]["message"]["content"][0]["text"], "Sure.");
+        assert_eq!(lines[2]["type"], "summary");
+        assert_eq!(lines[2]["leafUuid"], lines[1]["uuid"]);
+    }
+
+    #[test]
+    fn test_import_rejects_unusable_exports() {
+        let projects_dir = TempDir::new().unwrap();
+        for export in [
+            serde_json::json!("not a conversation"),
+            serde_json::json!([{"chat_messages": [{"text": "no sender"}]}]),
+            serde_json::json!([{"name": "Empty", "chat_messages": []}]),
+        ] {
+            let result = import_sessions(
+                projects_dir.path(),
+                Path::new("/work"),
+                ImportFormat::ClaudeDesktop,
+                export,
+            );
+            assert!(matches!(result, Err(OrchestratorError::InvalidRequest(_))));
+        }
+    }
+}
@@ -1,16 +1,50 @@
-use crate::claude_process::ClaudeProcess;
-use crate::config::Config;
+use crate::anchors::AnchorStore;
+use crate::approval_audit::ApprovalAuditLog;
+use crate::approval_risk;
+use crate::approval_rules::{self, ApprovalRules, RULE_IDENTITY_PREFIX};
+use crate::approval_sync::{self, ApprovalSync, Decision, ReplicatedApprovals};
+use crate::approval_webhook::{self, ApprovalWebhook};
+use crate::config::{
+    Config, ControlRoute, LimitAction, ProcessMonitoring, ShutdownBehavior, StdoutMode,
+    WireLogSettings,
+};
+use crate::debug_capture::Direction;
 use crate::error::{OrchestratorError, OrchestratorResult};
+use crate::event_export::{self, EventExporter};
+use crate::index::SessionIndex;
+use crate::lineage::ResumeLineage;
 use crate::models::{
-    ApprovalMessage, ApprovalRequest, BroadcastMessage, Session, SessionStatus, WriteMessage,
+    self, AdminStatusResponse, ApprovalMessage, ApprovalRequest, ApprovalResponseResult,
+    BroadcastEnvelope, BroadcastMessage, ClaudeMessage, ConversationMessage, PacedPosition,
+    Session, SessionOptions, SessionStatus, TerminateSessionResponse, WriteMessage,
+    DECIDED_BY_FIELD,
 };
+use crate::notifications::{self, Notification, Notifier, WatchEvent};
+use crate::preferences::{PreferenceStore, DEFAULT_IDENTITY};
+use crate::process_stats::ProcessSampler;
+use crate::push_notify::PushNotifier;
+use crate::scratch::ScratchDirs;
+use crate::server_events::{ServerEventKind, ServerEvents};
+use crate::session_backend::{Backends, SessionBackend, SessionEnd, StartRequest};
+use crate::session_metadata::SessionMetadataStore;
+use crate::session_owners::SessionOwners;
+use crate::slack::{self, SlackApprovals};
+use crate::slo::{Operation, SloTracker};
+use crate::telemetry::{SessionEvent, Telemetry};
+use crate::uploads::UploadStore;
+use crate::usage::UsageLedger;
+use crate::web_push::{VapidKey, WebPush};
+use crate::wire_log::WireLog;
+use crate::working_dir_sandbox::{self, SandboxMode, SANDBOX_IDENTITY};
 use dashmap::DashMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::broadcast;
+use std::time::Instant;
+use tokio::sync::{broadcast, mpsc, oneshot, watch, OwnedSemaphorePermit, Semaphore};
 use tokio::task::JoinHandle;
 use tokio::time::{timeout, Duration};
-use tracing::{debug, error, info, instrument, warn};
+use tracing::{debug, error, info, info_span, instrument, warn, Instrument};
 use uuid::Uuid;
 
 /// Compacts JSON message to a single line for Claude stdin.
@@ -52,19 +86,1083 @@ fn compact_json_message(message: &str, context: &str) -> OrchestratorResult<Stri
     }
 }
 
+/// Sends SIGTERM to a session's Claude process.
+fn terminate_process(session_id: &str, pid: u32) {
+    signal_process(session_id, pid, false);
+}
+
+/// Sends SIGKILL to a Claude process that did not exit when asked to.
+fn kill_process(session_id: &str, pid: u32) {
+    signal_process(session_id, pid, true);
+}
+
+fn signal_process(session_id: &str, pid: u32, force: bool) {
+    #[cfg(unix)]
+    {
+        use nix::sys::signal::{kill, Signal};
+        use nix::unistd::Pid;
+        let signal = if force {
+            Signal::SIGKILL
+        } else {
+            Signal::SIGTERM
+        };
+        if let Ok(pid_i32) = i32::try_from(pid) {
+            if let Err(e) = kill(Pid::from_raw(pid_i32), signal) {
+                warn!(
+                    session_id = %session_id,
+                    process_id = pid,
+                    signal = %signal,
+                    error = %e,
+                    "Failed to signal Claude process"
+                );
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    warn!(
+        session_id = %session_id,
+        process_id = pid,
+        force,
+        "Process killing not implemented for non-Unix systems"
+    );
+}
+
+/// Polls `done` until it holds or `limit` passes; returns whether it held.
+async fn wait_until<F, Fut>(limit: Duration, mut done: F) -> bool
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    timeout(limit, async {
+        while !done().await {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .is_ok()
+}
+
+/// Ends a session's Claude process on orchestrator shutdown as `behavior` says.
+async fn shutdown_session(session: Arc<Session>, behavior: ShutdownBehavior, grace: Duration) {
+    let Some(pid) = session.get_process_id().await else {
+        return;
+    };
+    session.expect_exit();
+    let session_id = session.get_id().await;
+    let session = &session;
+
+    match behavior {
+        ShutdownBehavior::Kill => {
+            info!(session_id = %session_id, process_id = pid, "Killing Claude process on shutdown");
+            kill_process(&session_id, pid);
+            session.set_process_id(None).await;
+            return;
+        }
+        ShutdownBehavior::Terminate => {}
+        ShutdownBehavior::Protected { deadline } => {
+            info!(
+                session_id = %session_id,
+                deadline_secs = deadline.as_secs(),
+                "Waiting for protected session to drain before shutdown"
+            );
+            let drained = wait_until(deadline, move || async move {
+                !session.is_active().await || session.is_drained().await
+            })
+            .await;
+            if drained {
+                info!(session_id = %session_id, "Protected session drained");
+            } else {
+                warn!(
+                    session_id = %session_id,
+                    deadline_secs = deadline.as_secs(),
+                    "Protected session did not drain before its deadline, terminating it anyway"
+                );
+            }
+        }
+    }
+
+    terminate_gracefully(session, &session_id, pid, grace).await;
+}
+
+/// Sends SIGTERM to a session's Claude process and SIGKILL if it is still there after
+/// `grace`. Returns whether it exited without being killed.
+async fn terminate_gracefully(
+    session: &Session,
+    session_id: &str,
+    pid: u32,
+    grace: Duration,
+) -> bool {
+    session.expect_exit();
+    terminate_process(session_id, pid);
+    let exited = wait_until(grace, move || async move { !session.is_active().await }).await;
+    if !exited {
+        warn!(session_id = %session_id, process_id = pid, "Claude process ignored SIGTERM, killing it");
+        kill_process(session_id, pid);
+    }
+    session.set_process_id(None).await;
+    exited
+}
+
+/// The error `control_response` answering a `control_request` no client handles, so
+/// Claude carries on instead of waiting for an answer that never comes.
+fn unsupported_control_response(request_id: Option<&str>, subtype: &str) -> String {
+    serde_json::json!({
+        "type": "control_response",
+        "response": {
+            "subtype": "error",
+            "request_id": request_id,
+            "error": format!("Unsupported control request subtype: '{subtype}'")
+        }
+    })
+    .to_string()
+}
+
+/// Opens the wire log of `session_id` if wire logging is on. A session whose log cannot
+/// be opened runs without one.
+fn open_wire_log(settings: Option<&WireLogSettings>, session_id: &str) -> Option<WireLog> {
+    WireLog::open(settings?, session_id)
+        .inspect_err(|e| {
+            warn!(session_id = %session_id, error = %e, "Failed to open wire log");
+        })
+        .ok()
+}
+
+/// Answers `request` with `response` without asking approvers, and records the decision
+/// as made by `decided_by`.
+async fn answer_approval_directly(
+    session: &Session,
+    stdin_tx: &mpsc::Sender<String>,
+    request: &ApprovalRequest,
+    response: &serde_json::Value,
+    decided_by: &str,
+) {
+    let response_json = serde_json::json!({
+        "type": "control_response",
+        "response": {
+            "subtype": "success",
+            "request_id": request.claude_request_id,
+            "response": response,
+        }
+    })
+    .to_string();
+    session
+        .record_traffic(Direction::Stdin, Some(decided_by), &response_json)
+        .await;
+    if stdin_tx.send(response_json).await.is_err() {
+        error!(
+            session_id = %request.session_id,
+            "Failed to send control_response to Claude stdin"
+        );
+    }
+    session.record_approval_decision(request, response, decided_by);
+}
+
+/// Samples a session's Claude process until it exits, telling the session's clients
+/// whenever memory or CPU use rises above its warning threshold.
+async fn monitor_process(session: Arc<Session>, pid: u32, monitoring: ProcessMonitoring) {
+    let Some(interval) = monitoring.sample_interval else {
+        return;
+    };
+    let mut sampler = ProcessSampler::new(pid);
+    let mut memory_warned = false;
+    let mut cpu_warned = false;
+
+    loop {
+        let Some(stats) = sampler.sample() else {
+            return;
+        };
+        session.set_process_stats(stats).await;
+
+        #[allow(clippy::cast_precision_loss)]
+        let checks = [
+            (
+                "memory",
+                stats.rss_bytes as f64,
+                monitoring.rss_warning_bytes.map(|bytes| bytes as f64),
+                &mut memory_warned,
+            ),
+            (
+                "cpu",
+                stats.cpu_percent,
+                monitoring.cpu_warning_percent,
+                &mut cpu_warned,
+            ),
+        ];
+        for (resource, value, threshold, warned) in checks {
+            let Some(threshold) = threshold else {
+                continue;
+            };
+            let over = value > threshold;
+            if over && !*warned {
+                let session_id = session.get_id().await;
+                warn!(
+                    session_id = %session_id,
+                    process_id = pid,
+                    resource,
+                    value,
+                    threshold,
+                    "Claude process is over its resource warning threshold"
+                );
+                let event = serde_json::json!({
+                    "type": "system",
+                    "subtype": "resource_warning",
+                    "resource": resource,
+                    "value": value,
+                    "threshold": threshold,
+                });
+                let _ = session.broadcast_message(BroadcastMessage::ClaudeOutput {
+                    line: event.to_string().into(),
+                    received_at: chrono::Utc::now(),
+                });
+            }
+            // Warn again only after usage has dropped back below the threshold
+            *warned = over;
+        }
+
+        tokio::select! {
+            () = tokio::time::sleep(interval) => {}
+            () = session.wait_exited() => return,
+        }
+    }
+}
+
+/// The text blocks of an `assistant` message, joined by blank lines.
+fn assistant_text(message: &ConversationMessage) -> String {
+    message.text_blocks().collect::<Vec<_>>().join("\n\n")
+}
+
+/// Stops a session that ran into `limit`: it takes no more input, and with
+/// [`LimitAction::Terminate`] its Claude process is also asked to exit.
+async fn reach_limit(session: &Session, limit: &str, action: LimitAction) {
+    if !session.reach_limit(limit).await {
+        return;
+    }
+    let session_id = session.get_id().await;
+    info!(session_id = %session_id, limit = %limit, action = ?action, "Session reached its limit");
+
+    if action == LimitAction::Terminate {
+        if let Some(pid) = session.get_process_id().await {
+            terminate_process(&session_id, pid);
+        } else {
+            debug!(session_id = %session_id, "Limited session has no process to signal");
+        }
+    }
+}
+
+/// Enforces a session's wall-clock limit, unless Claude exits first.
+async fn enforce_duration_limit(
+    session: Arc<Session>,
+    max_duration: Duration,
+    action: LimitAction,
+) {
+    tokio::select! {
+        () = tokio::time::sleep(max_duration) => reach_limit(&session, "duration", action).await,
+        () = session.wait_exited() => {}
+    }
+}
+
+/// Lets a session notice a restart happening in the background as a system line.
+fn announce(session: &Session, notice: &serde_json::Value) {
+    let _ = session.broadcast_message(BroadcastMessage::ClaudeOutput {
+        line: notice.to_string().into(),
+        received_at: chrono::Utc::now(),
+    });
+}
+
+/// What (re)starting a session's Claude takes, so it can outlive the request that
+/// started the session.
+#[derive(Clone)]
+struct Launcher {
+    config: Arc<Config>,
+    backend: Arc<dyn SessionBackend>,
+    sessions: Arc<DashMap<String, Arc<Session>>>,
+    lineage: Arc<ResumeLineage>,
+    owners: Arc<SessionOwners>,
+    notifier: Arc<Notifier>,
+    anchors: Arc<AnchorStore>,
+    approval_rules: Arc<ApprovalRules>,
+    metadata: Arc<SessionMetadataStore>,
+}
+
+impl Launcher {
+    /// Moves `session` from `old_id` to the ID Claude gave it on resuming.
+    async fn adopt_id(&self, session: &Arc<Session>, old_id: &str, new_id: &str) {
+        info!(
+            old_session_id = %old_id,
+            new_session_id = %new_id,
+            "Session ID changed during resume, updating session mapping"
+        );
+        self.sessions.remove(old_id);
+        session.set_id(new_id.to_string()).await;
+        self.sessions.insert(new_id.to_string(), session.clone());
+        session
+            .announce(ServerEventKind::SessionRenamed {
+                previous_session_id: old_id.to_string(),
+            })
+            .await;
+        self.lineage.record(old_id, new_id);
+        if let Some(owner) = session.owner() {
+            self.owners.record(new_id, owner);
+        }
+        self.notifier.rename_session(old_id, new_id);
+        self.anchors.copy_session(old_id, new_id);
+        self.approval_rules.copy_session(old_id, new_id);
+        self.metadata.copy_session(old_id, new_id);
+    }
+
+    /// Whether `session` is still the one registered under its ID, i.e. was not stopped.
+    async fn is_registered(&self, session: &Arc<Session>) -> bool {
+        let session_id = session.get_id().await;
+        self.sessions
+            .get(&session_id)
+            .is_some_and(|current| Arc::ptr_eq(&current, session))
+    }
+
+    /// Whether Claude ending as `end` was a crash of a running session worth restarting.
+    async fn should_restart(&self, session: &Arc<Session>, end: SessionEnd) -> bool {
+        self.config.restart_policy.is_some()
+            && end == SessionEnd::Crashed
+            && session.has_started()
+            && !session.exit_expected()
+            && session.get_status().await == SessionStatus::Ready
+            && self.is_registered(session).await
+    }
+
+    /// Waits for the Claude of `session` to end, respawning it after crashes as
+    /// `CLAUDE_RESTART_MAX` allows, and marks the session exited once it is gone for good.
+    /// Claude killed for exceeding a resource limit is not respawned.
+    async fn supervise(self, session: Arc<Session>, mut ended: oneshot::Receiver<SessionEnd>) {
+        let mut exceeded = None;
+        loop {
+            let end = ended.await.unwrap_or(SessionEnd::Crashed);
+            let restarting = self.should_restart(&session, end).await;
+            if let SessionEnd::ResourceLimitExceeded(limit) = end {
+                exceeded = Some(limit);
+            }
+            session
+                .announce(ServerEventKind::ClaudeExited {
+                    crashed: end != SessionEnd::Clean,
+                    restarting,
+                    resource_limit: exceeded,
+                })
+                .await;
+            if end != SessionEnd::Clean && !session.exit_expected() {
+                self.notifier.notify(&Notification {
+                    event: WatchEvent::Crashed,
+                    session_id: session.get_id().await,
+                    working_directory: session.working_dir.clone(),
+                    detail: match exceeded {
+                        Some(limit) => format!(
+                            "Claude was killed for exceeding its {} limit",
+                            limit.describe()
+                        ),
+                        None if restarting => "Claude crashed and is being restarted".to_string(),
+                        None => "Claude crashed".to_string(),
+                    },
+                    timestamp: chrono::Utc::now(),
+                    owner: session.owner().map(str::to_string),
+                });
+            }
+            if !restarting {
+                break;
+            }
+            match self.restart(&session).await {
+                Some(next) => ended = next,
+                None => break,
+            }
+        }
+
+        let session_id = session.get_id().await;
+        if let Some(limit) = exceeded {
+            session.exceed_resource_limit(limit).await;
+        }
+        session.mark_exited().await;
+
+        // Immediately broadcast disconnect to all WebSocket clients
+        if let Err(e) = session.broadcast_message(BroadcastMessage::Disconnect) {
+            error!(
+                session_id = %session_id,
+                error = %e,
+                "Failed to broadcast disconnect after process exit"
+            );
+        } else {
+            info!(
+                session_id = %session_id,
+                "Successfully broadcast disconnect after process exit"
+            );
+        }
+    }
+
+    /// Respawns the crashed Claude of `session` with `--resume`, backing off between
+    /// attempts until one comes up or the restart budget is spent. Returns how to learn
+    /// when the new process ends.
+    async fn restart(&self, session: &Arc<Session>) -> Option<oneshot::Receiver<SessionEnd>> {
+        let policy = self.config.restart_policy?;
+        session.set_restarting(true);
+        let restarted = loop {
+            let session_id = session.get_id().await;
+            let attempt = session.record_restart();
+            if attempt > policy.max_restarts {
+                error!(
+                    session_id = %session_id,
+                    max_restarts = policy.max_restarts,
+                    "Claude process keeps crashing, giving up on restarting it"
+                );
+                break None;
+            }
+            let delay = policy.backoff(attempt);
+            warn!(
+                session_id = %session_id,
+                attempt = attempt,
+                retry_in_ms = delay.as_millis(),
+                "Claude process crashed, restarting it"
+            );
+            announce(
+                session,
+                &serde_json::json!({
+                    "type": "system",
+                    "subtype": "claude_restarting",
+                    "attempt": attempt,
+                    "max_restarts": policy.max_restarts,
+                    "retry_in_ms": delay.as_millis(),
+                }),
+            );
+            tokio::time::sleep(delay).await;
+
+            // A resumed Claude only reports its session ID once it gets input
+            let Some(unwritten) = self.next_input(session).await else {
+                break None;
+            };
+            let bootstrap = unwritten.iter().map(|msg| msg.content.clone()).collect();
+            match SessionManager::spawn_claude_process(
+                &self.config,
+                self.backend.clone(),
+                &session_id,
+                &session.working_dir,
+                true,
+                bootstrap,
+                session.clone(),
+            )
+            .await
+            {
+                Ok((new_id, ended)) => {
+                    session.confirm_written().await;
+                    if new_id != session_id {
+                        self.adopt_id(session, &session_id, &new_id).await;
+                    }
+                    session.start_turn();
+                    info!(session_id = %new_id, attempt = attempt, "Restarted crashed Claude process");
+                    announce(
+                        session,
+                        &serde_json::json!({
+                            "type": "system",
+                            "subtype": "claude_reconnected",
+                            "attempt": attempt,
+                            "session_id": new_id,
+                        }),
+                    );
+                    break Some(ended);
+                }
+                Err(e) => {
+                    warn!(session_id = %session_id, error = %e, "Failed to restart Claude process");
+                    session.requeue(unwritten).await;
+                }
+            }
+        };
+        session.set_restarting(false);
+        restarted
+    }
+
+    /// Takes everything not yet written for `session` once there is something, or `None`
+    /// if the session was stopped while waiting.
+    async fn next_input(&self, session: &Arc<Session>) -> Option<Vec<WriteMessage>> {
+        loop {
+            if session.exit_expected() || !self.is_registered(session).await {
+                return None;
+            }
+            let unwritten = session.take_unwritten().await;
+            if !unwritten.is_empty() {
+                return Some(unwritten);
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+}
+
+/// Undoes a session start whose caller stopped waiting for it: stops the worker, kills
+/// whatever Claude was already spawned and forgets the session. A Claude still being
+/// spawned when the worker is aborted is killed on drop by the CLI backend.
+struct AbandonedStart {
+    sessions: Arc<DashMap<String, Arc<Session>>>,
+    session: Option<Arc<Session>>,
+    worker: Option<tokio::task::AbortHandle>,
+    armed: bool,
+}
+
+impl AbandonedStart {
+    fn new(sessions: Arc<DashMap<String, Arc<Session>>>) -> Self {
+        Self {
+            sessions,
+            session: None,
+            worker: None,
+            armed: true,
+        }
+    }
+
+    /// The start ran to completion, successful or not; nothing to undo.
+    fn disarm(&mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for AbandonedStart {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        // Nothing was started yet, or an already running session was handed out
+        let Some(session) = self.session.take() else {
+            return;
+        };
+        if let Some(worker) = self.worker.take() {
+            worker.abort();
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let sessions = self.sessions.clone();
+        runtime.spawn(async move {
+            // The ID changes once a resumed session is up
+            let session_id = session.get_id().await;
+            warn!(session_id = %session_id, "Session start abandoned by the caller, cleaning up");
+            sessions.remove_if(&session_id, |_, current| Arc::ptr_eq(current, &session));
+            session.expect_exit();
+            if let Some(pid) = session.get_process_id().await {
+                terminate_process(&session_id, pid);
+            }
+            session.mark_exited().await;
+        });
+    }
+}
+
 pub struct SessionManager {
     sessions: Arc<DashMap<String, Arc<Session>>>,
-    config: Arc<Config>,
+    /// Swapped when the config file changes; see [`SessionManager::reload_config`]
+    config: std::sync::RwLock<Arc<Config>>,
     worker_handles: Arc<DashMap<String, JoinHandle<()>>>,
+    lineage: Arc<ResumeLineage>,
+    owners: Arc<SessionOwners>,
+    backends: Backends,
+    notifier: Arc<Notifier>,
+    exporter: Arc<EventExporter>,
+    /// Set when `APPROVAL_WEBHOOK_URL` is
+    approval_webhook: Option<Arc<ApprovalWebhook>>,
+    /// Set when `SLACK_BOT_TOKEN` is
+    slack: Option<Arc<SlackApprovals>>,
+    /// Set when `APPROVAL_SYNC_DIR` is
+    approval_sync: Option<Arc<ApprovalSync>>,
+    uploads: Arc<UploadStore>,
+    scratch: Option<Arc<ScratchDirs>>,
+    slo: Arc<SloTracker>,
+    session_index: Arc<SessionIndex>,
+    telemetry: Arc<Telemetry>,
+    preferences: Arc<PreferenceStore>,
+    anchors: Arc<AnchorStore>,
+    approval_rules: Arc<ApprovalRules>,
+    approval_audit: Arc<ApprovalAuditLog>,
+    usage: Arc<UsageLedger>,
+    metadata: Arc<SessionMetadataStore>,
+    server_events: Arc<ServerEvents>,
+    /// One permit per session allowed to run at once, if `MAX_RUNNING_SESSIONS` caps them
+    slots: Option<Arc<Semaphore>>,
+    /// Set on shutdown: running sessions may finish, but no new ones or clients are let in
+    draining: AtomicBool,
+    /// Set once a shutdown began, after which draining cannot be stopped
+    shutting_down: AtomicBool,
+    started_at: Instant,
 }
 
 impl SessionManager {
+    /// # Panics
+    ///
+    /// Panics if `config.web_push` holds keys that did not pass config validation.
     #[must_use]
     pub fn new(config: Config) -> Self {
+        let lineage = ResumeLineage::load(config.session_state_dir.as_deref());
+        let owners = SessionOwners::load(config.session_state_dir.as_deref());
+        let preferences = PreferenceStore::load(config.session_state_dir.as_deref());
+        let anchors = AnchorStore::load(config.session_state_dir.as_deref());
+        let approval_rules = ApprovalRules::load(config.session_state_dir.as_deref());
+        let approval_audit = ApprovalAuditLog::load(config.session_state_dir.as_deref());
+        let usage = UsageLedger::load(config.session_state_dir.as_deref());
+        let metadata = SessionMetadataStore::load(config.session_state_dir.as_deref());
+        let mut notifier = Notifier::new(config.notification_targets.clone());
+        if let Some(settings) = &config.web_push {
+            let vapid = VapidKey::new(settings).expect("VAPID keys are validated with the config");
+            notifier = notifier.with_web_push(WebPush::load(
+                vapid,
+                config.session_state_dir.as_deref(),
+                config.api_keys.clone(),
+            ));
+        }
+        if let Some(settings) = &config.push {
+            notifier = notifier.with_push(PushNotifier::new(settings.clone()));
+        }
+        let scratch = config.scratch.clone().map(ScratchDirs::new);
+        if let Some(scratch) = &scratch {
+            scratch.sweep();
+        }
+        let slots = config
+            .session_capacity
+            .map(|capacity| Arc::new(Semaphore::new(capacity.max_running)));
+        let config = Arc::new(config);
         Self {
             sessions: Arc::new(DashMap::new()),
-            config: Arc::new(config),
+            backends: Backends::from_config(&config),
+            notifier: Arc::new(notifier),
+            exporter: Arc::new(EventExporter::new(&config.event_sinks)),
+            approval_webhook: config
+                .approval_webhook
+                .as_ref()
+                .map(|settings| Arc::new(ApprovalWebhook::new(settings))),
+            slack: config
+                .slack
+                .clone()
+                .map(|settings| Arc::new(SlackApprovals::new(settings))),
+            approval_sync: config
+                .approval_sync_dir
+                .clone()
+                .map(|dir| Arc::new(ApprovalSync::new(dir))),
+            uploads: Arc::new(UploadStore::new(
+                config.session_state_dir.as_ref().map_or_else(
+                    || std::env::temp_dir().join("chef-de-vibe-uploads"),
+                    |dir| dir.join("uploads"),
+                ),
+            )),
+            scratch,
+            slo: Arc::new(SloTracker::new(config.slo.clone())),
+            session_index: Arc::new(SessionIndex::open(config.session_state_dir.as_deref())),
+            telemetry: Arc::new(Telemetry::new(config.telemetry_endpoint.clone())),
+            config: std::sync::RwLock::new(config),
             worker_handles: Arc::new(DashMap::new()),
+            lineage: Arc::new(lineage),
+            owners: Arc::new(owners),
+            preferences: Arc::new(preferences),
+            anchors: Arc::new(anchors),
+            approval_rules: Arc::new(approval_rules),
+            approval_audit: Arc::new(approval_audit),
+            usage: Arc::new(usage),
+            metadata: Arc::new(metadata),
+            server_events: Arc::new(ServerEvents::new()),
+            slots,
+            draining: AtomicBool::new(false),
+            shutting_down: AtomicBool::new(false),
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Checks that `working_dir` is a directory sessions may run in.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory does not exist or is outside the configured
+    /// working directory roots.
+    pub fn validate_working_dir(&self, working_dir: &Path) -> OrchestratorResult<()> {
+        if !working_dir.exists() {
+            error!(
+                working_dir = %working_dir.display(),
+                "Working directory does not exist"
+            );
+            return Err(OrchestratorError::WorkingDirInvalid(format!(
+                "Working directory does not exist: {}",
+                working_dir.display()
+            )));
+        }
+
+        if !working_dir.is_dir() {
+            error!(
+                working_dir = %working_dir.display(),
+                "Path is not a directory"
+            );
+            return Err(OrchestratorError::WorkingDirInvalid(format!(
+                "Path is not a directory: {}",
+                working_dir.display()
+            )));
+        }
+
+        let config = self.config();
+        let roots = &config.working_dir_roots;
+        if !roots.is_empty() {
+            let canonical = std::fs::canonicalize(working_dir).map_err(|e| {
+                OrchestratorError::WorkingDirInvalid(format!(
+                    "Cannot resolve working directory {}: {e}",
+                    working_dir.display()
+                ))
+            })?;
+            if !roots.iter().any(|root| canonical.starts_with(root)) {
+                warn!(
+                    working_dir = %canonical.display(),
+                    "Working directory is outside the allowed roots"
+                );
+                return Err(OrchestratorError::WorkingDirInvalid(format!(
+                    "Working directory is outside the allowed roots: {}",
+                    canonical.display()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Content uploaded ahead of session creation
+    #[must_use]
+    pub fn uploads(&self) -> &Arc<UploadStore> {
+        &self.uploads
+    }
+
+    /// Scratch directories of sessions, if enabled
+    #[must_use]
+    pub const fn scratch(&self) -> Option<&Arc<ScratchDirs>> {
+        self.scratch.as_ref()
+    }
+
+    /// Latency samples behind the SLO report
+    #[must_use]
+    pub fn slo(&self) -> &Arc<SloTracker> {
+        &self.slo
+    }
+
+    /// Metadata of the journals on disk, for listing sessions
+    #[must_use]
+    pub fn session_index(&self) -> &Arc<SessionIndex> {
+        &self.session_index
+    }
+
+    /// UI preferences stored per user
+    #[must_use]
+    pub fn preferences(&self) -> &Arc<PreferenceStore> {
+        &self.preferences
+    }
+
+    /// Named positions in the conversations of sessions
+    #[must_use]
+    pub fn anchors(&self) -> &Arc<AnchorStore> {
+        &self.anchors
+    }
+
+    /// Allows remembered for identical approval requests
+    #[must_use]
+    pub fn approval_rules(&self) -> &Arc<ApprovalRules> {
+        &self.approval_rules
+    }
+
+    /// Tags and metadata users attached to sessions
+    #[must_use]
+    pub fn session_metadata(&self) -> &Arc<SessionMetadataStore> {
+        &self.metadata
+    }
+
+    /// Tokens and cost of every session's turns
+    #[must_use]
+    pub const fn usage(&self) -> &Arc<UsageLedger> {
+        &self.usage
+    }
+
+    /// Lifecycle events of all sessions, for `/api/v1/events_ws`
+    #[must_use]
+    pub const fn server_events(&self) -> &Arc<ServerEvents> {
+        &self.server_events
+    }
+
+    /// The configuration, with the settings of the last config file reload
+    ///
+    /// # Panics
+    ///
+    /// Panics if a reload panicked while swapping the configuration.
+    #[must_use]
+    pub fn config(&self) -> Arc<Config> {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Takes the settings of [`crate::config::RELOADABLE_SETTINGS`] from `reloaded`.
+    /// Sessions already running keep the settings they were started with.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an earlier reload panicked while swapping the configuration.
+    pub fn reload_config(&self, reloaded: &Config) {
+        let mut config = self.config.write().unwrap();
+        let mut updated = Config::clone(&config);
+        updated.take_reloadable(reloaded);
+        *config = Arc::new(updated);
+    }
+
+    /// Approvals answered across all sessions
+    #[must_use]
+    pub fn approval_audit(&self) -> &Arc<ApprovalAuditLog> {
+        &self.approval_audit
+    }
+
+    /// Anonymous usage counters
+    #[must_use]
+    pub fn telemetry(&self) -> &Arc<Telemetry> {
+        &self.telemetry
+    }
+
+    /// Starts passing approvals decided on other instances to the sessions running here,
+    /// if `APPROVAL_SYNC_DIR` is set.
+    pub fn start_approval_sync(&self) -> Option<JoinHandle<()>> {
+        self.approval_sync
+            .as_ref()
+            .map(|sync| sync.start(self.sessions.clone()))
+    }
+
+    /// Pending approvals of a session running on another instance sharing
+    /// `APPROVAL_SYNC_DIR` that were not answered yet, in delivery order.
+    pub async fn replicated_approvals(&self, session_id: &str) -> Option<ReplicatedApprovals> {
+        let sync = self.approval_sync.as_ref()?;
+        let mut replicated = sync.pending(session_id).await?;
+        replicated
+            .approvals
+            .retain(|request| !sync.is_decided(&request.id));
+        models::sort_by_priority(&mut replicated.approvals);
+        Some(replicated)
+    }
+
+    /// Answers an approval of a session running on another instance sharing
+    /// `APPROVAL_SYNC_DIR`, which passes the answer on to Claude. Checked like an answer
+    /// given on that instance.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session is not running on any instance, no approval with
+    /// this ID is pending, `decided_by` may not allow it, or another answer to it was
+    /// recorded first.
+    pub async fn submit_replicated_approval_response(
+        &self,
+        session_id: &str,
+        approval_id: &str,
+        response: serde_json::Value,
+        decided_by: &str,
+    ) -> OrchestratorResult<()> {
+        let sync = self
+            .approval_sync
+            .as_ref()
+            .ok_or_else(|| OrchestratorError::SessionNotFound(session_id.to_string()))?;
+        let replicated = sync
+            .pending(session_id)
+            .await
+            .ok_or_else(|| OrchestratorError::SessionNotFound(session_id.to_string()))?;
+        let request = replicated
+            .approvals
+            .iter()
+            .find(|request| request.id == approval_id)
+            .ok_or_else(|| OrchestratorError::ApprovalNotFound(approval_id.to_string()))?;
+        self.config()
+            .risk_approvers
+            .check(request.risk, Some(&response), decided_by)
+            .map_err(OrchestratorError::Forbidden)?;
+
+        let decision = Decision::new(session_id, approval_id, response, decided_by);
+        if let Some(earlier) = sync.claim(&decision).await {
+            return Err(OrchestratorError::ApprovalAlreadyDecided(format!(
+                "{approval_id} was answered by {} first",
+                earlier.decided_by
+            )));
+        }
+        info!(
+            session_id = %session_id,
+            approval_id = %approval_id,
+            decided_by = %decided_by,
+            "Recorded approval decision for another instance"
+        );
+        Ok(())
+    }
+
+    /// Answers several approvals of a session running on another instance, each checked
+    /// like [`Self::submit_replicated_approval_response`]. `None` if the session is not
+    /// running on any instance.
+    pub async fn submit_replicated_approval_responses(
+        &self,
+        session_id: &str,
+        responses: Vec<serde_json::Value>,
+        decided_by: &str,
+    ) -> Option<Vec<ApprovalResponseResult>> {
+        self.replicated_approvals(session_id).await?;
+        let mut seen = std::collections::HashSet::new();
+        let mut results = Vec::with_capacity(responses.len());
+        for mut item in responses {
+            let id = item
+                .get("id")
+                .and_then(|id| id.as_str())
+                .map(ToString::to_string);
+            let error = match (&id, item.get_mut("response").map(serde_json::Value::take)) {
+                (Some(id), Some(response)) if seen.insert(id.clone()) => {
+                    match self
+                        .submit_replicated_approval_response(session_id, id, response, decided_by)
+                        .await
+                    {
+                        Ok(()) => None,
+                        Err(OrchestratorError::ApprovalNotFound(_)) => {
+                            Some("no pending approval with this id".to_string())
+                        }
+                        Err(OrchestratorError::ApprovalAlreadyDecided(_)) => {
+                            Some(models::ALREADY_DECIDED.to_string())
+                        }
+                        Err(OrchestratorError::Forbidden(e)) => Some(e),
+                        Err(e) => Some(e.to_string()),
+                    }
+                }
+                (Some(_), Some(_)) => Some("duplicate id in batch".to_string()),
+                _ => Some("expected {id, response}".to_string()),
+            };
+            results.push(ApprovalResponseResult {
+                id,
+                accepted: error.is_none(),
+                error,
+            });
+        }
+        Some(results)
+    }
+
+    /// Posts approval requests to Slack, if `SLACK_BOT_TOKEN` is set
+    #[must_use]
+    pub const fn slack(&self) -> Option<&Arc<SlackApprovals>> {
+        self.slack.as_ref()
+    }
+
+    /// Watch subscriptions and notification delivery for the sessions of this manager
+    #[must_use]
+    pub fn notifier(&self) -> &Arc<Notifier> {
+        &self.notifier
+    }
+
+    /// Applies the session's `approval_disconnect_deny_after_secs` once an approval client
+    /// left: when none is left while approvals are pending, they are denied right away or
+    /// after a countdown announced to the session's watchers. A client connecting in the
+    /// meantime cancels the countdown.
+    pub async fn approval_client_left(&self, session: &Arc<Session>) {
+        let Some(deny_after) = session.options.approval_disconnect_deny_after_secs else {
+            return;
+        };
+        let Some((mark, pending)) = session.approvals_unattended().await else {
+            return;
+        };
+        let session_id = session.get_id().await;
+
+        if deny_after > 0 {
+            info!(
+                session_id = %session_id,
+                pending,
+                deny_after,
+                "Last approval client left, denying pending approvals unless one reconnects"
+            );
+            self.notifier.notify(&Notification {
+                event: WatchEvent::ApprovalNeeded,
+                session_id: session_id.clone(),
+                working_directory: session.working_dir.clone(),
+                detail: format!(
+                    "No approver is connected; {pending} pending approval(s) will be denied in {deny_after}s unless one reconnects"
+                ),
+                timestamp: chrono::Utc::now(),
+                owner: session.owner().map(str::to_string),
+            });
+        }
+
+        let session = session.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(deny_after)).await;
+            let denied = session.deny_unattended_approvals(mark).await;
+            if denied > 0 {
+                warn!(
+                    session_id = %session_id,
+                    denied,
+                    "Denied pending approvals, no approver was connected"
+                );
+            }
+        });
+    }
+
+    /// Which session `session_id` was resumed from, if it was started with `--resume`
+    #[must_use]
+    pub fn resumed_from(&self, session_id: &str) -> Option<String> {
+        self.lineage.parent_of(session_id)
+    }
+
+    /// The user `session_id` belongs to, if it was started or imported through the server
+    /// by someone known.
+    #[must_use]
+    pub fn session_owner(&self, session_id: &str) -> Option<String> {
+        match self.sessions.get(session_id) {
+            Some(session) => session.owner().map(str::to_string),
+            None => self.owners.owner_of(session_id).or_else(|| {
+                // Sessions running on another instance, whose owner is only known there
+                self.approval_sync
+                    .as_ref()
+                    .and_then(|sync| sync.owner_of(session_id))
+            }),
+        }
+    }
+
+    /// Records `owner` as the owner of `session_id`, e.g. of an imported session.
+    pub fn record_session_owner(&self, session_id: &str, owner: &str) {
+        self.owners.record(session_id, owner);
+    }
+
+    /// Takes a slot for a new session when `MAX_RUNNING_SESSIONS` caps them. When all are
+    /// taken, waits up to `SESSION_QUEUE_TIMEOUT` for one to free up, if set.
+    async fn acquire_slot(
+        &self,
+        session_id: &str,
+    ) -> OrchestratorResult<Option<OwnedSemaphorePermit>> {
+        let (Some(slots), Some(capacity)) = (&self.slots, self.config().session_capacity) else {
+            return Ok(None);
+        };
+        let full = || {
+            warn!(session_id = %session_id, max_running = capacity.max_running, "Refusing session start, all slots are taken");
+            OrchestratorError::TooManySessions(format!(
+                "{} sessions are already running",
+                capacity.max_running
+            ))
+        };
+        if let Ok(slot) = slots.clone().try_acquire_owned() {
+            return Ok(Some(slot));
+        }
+        let Some(queue_timeout) = capacity.queue_timeout else {
+            return Err(full());
+        };
+
+        info!(session_id = %session_id, max_running = capacity.max_running, "All session slots are taken, queueing start");
+        match timeout(queue_timeout, slots.clone().acquire_owned()).await {
+            Ok(slot) => Ok(Some(slot.expect("session slots are never closed"))),
+            Err(_) => Err(full()),
+        }
+    }
+
+    /// Drops a session from memory a grace period after its Claude exited, so clients
+    /// can still see what happened but dead sessions don't pile up. The transcript stays
+    /// on disk and the session keeps resolving from there.
+    async fn reap_after_exit(
+        sessions: Arc<DashMap<String, Arc<Session>>>,
+        telemetry: Arc<Telemetry>,
+        session: Arc<Session>,
+        grace_period: Duration,
+    ) {
+        session.wait_exited().await;
+        telemetry.record_session(SessionEvent::Exited);
+        tokio::time::sleep(grace_period).await;
+
+        // The ID may have been taken over by a newer session in the meantime
+        let session_id = session.get_id().await;
+        if sessions
+            .remove_if(&session_id, |_, current| Arc::ptr_eq(current, &session))
+            .is_some()
+        {
+            info!(
+                session_id = %session_id,
+                grace_period_secs = grace_period.as_secs(),
+                "Reaped exited session"
+            );
         }
     }
 
@@ -87,7 +1185,7 @@ impl SessionManager {
         debug!(
             session_id = %session_id,
             filename = %filename,
-            projects_dir = %self.config.claude_projects_dir.display(),
+            projects_dir = %self.config().claude_projects_dir.display(),
             timeout_seconds = timeout_duration.as_secs(),
             "Starting to wait for session file creation"
         );
@@ -137,7 +1235,7 @@ impl SessionManager {
 
         // Use timeout to wait for file to be ready
         if let Ok(result) = timeout(timeout_duration, async {
-            let mut interval = tokio::time::interval(Duration::from_millis(2000));
+            let mut interval = tokio::time::interval(Duration::from_secs(2));
             loop {
                 if check_file_ready().await {
                     return Ok(());
@@ -170,7 +1268,7 @@ impl SessionManager {
     fn find_session_file(&self, filename: &str) -> Option<PathBuf> {
         use walkdir::WalkDir;
 
-        for entry in WalkDir::new(&self.config.claude_projects_dir)
+        for entry in WalkDir::new(&self.config().claude_projects_dir)
             .into_iter()
             .filter_map(std::result::Result::ok)
         {
@@ -188,15 +1286,73 @@ impl SessionManager {
     ///
     /// Returns an error if the working directory is invalid, if the Claude process
     /// fails to spawn, or if the session creation fails.
-    #[instrument(skip(self), fields(session_id = %session_id, working_dir = %working_dir.display(), resume = resume, bootstrap_messages_len = bootstrap_messages.len()))]
-    #[allow(clippy::too_many_lines)]
     pub async fn create_session(
         &self,
         session_id: String,
         working_dir: &Path,
         resume: bool,
         bootstrap_messages: Vec<String>,
+        options: SessionOptions,
     ) -> OrchestratorResult<String> {
+        let (session_id, _) = self
+            .create_session_with_output(
+                session_id,
+                working_dir,
+                resume,
+                bootstrap_messages,
+                options,
+            )
+            .await?;
+        Ok(session_id)
+    }
+
+    /// Like [`SessionManager::create_session`], but also returns a receiver subscribed
+    /// before the bootstrap messages were sent, so none of Claude's replies to them are missed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the working directory is invalid, if the Claude process
+    /// fails to spawn, or if the session creation fails.
+    pub async fn create_session_with_output(
+        &self,
+        session_id: String,
+        working_dir: &Path,
+        resume: bool,
+        bootstrap_messages: Vec<String>,
+        options: SessionOptions,
+    ) -> OrchestratorResult<(String, broadcast::Receiver<BroadcastEnvelope>)> {
+        // Dropped mid-way if the caller goes away, e.g. the HTTP client disconnects
+        let mut abandoned = AbandonedStart::new(self.sessions.clone());
+        let result = self
+            .start_session(
+                session_id,
+                working_dir,
+                resume,
+                bootstrap_messages,
+                options,
+                &mut abandoned,
+            )
+            .await;
+        abandoned.disarm();
+        self.telemetry.record_session(match &result {
+            Ok(_) if resume => SessionEvent::Resumed,
+            Ok(_) => SessionEvent::Created,
+            Err(_) => SessionEvent::FailedToStart,
+        });
+        result
+    }
+
+    #[instrument(skip(self, options, abandoned), fields(session_id = %session_id, working_dir = %working_dir.display(), resume = resume, bootstrap_messages_len = bootstrap_messages.len()))]
+    #[allow(clippy::too_many_lines)]
+    async fn start_session(
+        &self,
+        session_id: String,
+        working_dir: &Path,
+        resume: bool,
+        mut bootstrap_messages: Vec<String>,
+        options: SessionOptions,
+        abandoned: &mut AbandonedStart,
+    ) -> OrchestratorResult<(String, broadcast::Receiver<BroadcastEnvelope>)> {
         info!(
             session_id = %session_id,
             working_dir = %working_dir.display(),
@@ -204,53 +1360,38 @@ impl SessionManager {
             "Creating session"
         );
 
-        // Check if session already exists and is running
-        if let Some(session) = self.sessions.get(&session_id) {
+        self.check_accepting()?;
+
+        // Check if session already exists and is running. The map guard is dropped
+        // before awaiting, as removing the entry below needs the same shard.
+        let existing = self
+            .sessions
+            .get(&session_id)
+            .map(|session| session.clone());
+        if let Some(session) = existing {
             if session.is_active().await {
                 info!(
                     session_id = %session_id,
                     "Session already exists and is active, returning existing session"
                 );
-                return Ok(session_id);
+                return Ok((session_id, session.subscribe_to_broadcasts()));
             }
             // Session exists but not running, remove it
             warn!(
                 session_id = %session_id,
                 "Session exists but is not active, removing it before creating new one"
             );
-            self.sessions.remove(&session_id);
+            self.sessions
+                .remove_if(&session_id, |_, current| Arc::ptr_eq(current, &session));
         }
 
         debug!(
             session_id = %session_id,
-            working_dir = %working_dir.display(),
-            "Validating working directory"
-        );
-
-        // Validate working directory
-        if !working_dir.exists() {
-            error!(
-                session_id = %session_id,
-                working_dir = %working_dir.display(),
-                "Working directory does not exist"
-            );
-            return Err(OrchestratorError::WorkingDirInvalid(format!(
-                "Working directory does not exist: {}",
-                working_dir.display()
-            )));
-        }
-
-        if !working_dir.is_dir() {
-            error!(
-                session_id = %session_id,
-                working_dir = %working_dir.display(),
-                "Path is not a directory"
-            );
-            return Err(OrchestratorError::WorkingDirInvalid(format!(
-                "Path is not a directory: {}",
-                working_dir.display()
-            )));
-        }
+            working_dir = %working_dir.display(),
+            "Validating working directory"
+        );
+
+        self.validate_working_dir(working_dir)?;
 
         debug!(
             session_id = %session_id,
@@ -258,24 +1399,140 @@ impl SessionManager {
             "Working directory validation passed"
         );
 
+        let config = self.config();
+        config
+            .cli_flag_allowlist
+            .check(&options.cli_flags)
+            .map_err(OrchestratorError::InvalidRequest)?;
+        let backend = self.backends.select(options.backend)?;
+
+        let slot = self.acquire_slot(&session_id).await?;
+
+        let scratch = self
+            .scratch
+            .as_ref()
+            .map(|scratch| scratch.lease(&session_id))
+            .transpose()?;
+
+        // A resumed session keeps its owner; its new ID is recorded once Claude reports it
+        if let (false, Some(owner)) = (resume, &options.owner) {
+            self.owners.record(&session_id, owner);
+        }
+
         // Create new session
-        let session = Arc::new(Session::new(session_id.clone(), working_dir.to_path_buf()));
+        let session = Arc::new(
+            Session::with_options(session_id.clone(), working_dir.to_path_buf(), options)
+                .with_state_dir(config.session_state_dir.clone())
+                .with_approval_audit(self.approval_audit.clone())
+                .with_approval_rules(self.approval_rules.clone())
+                .with_approval_sync(self.approval_sync.clone())
+                .with_risk_approvers(config.risk_approvers.clone())
+                .with_input_record(
+                    config
+                        .session_state_dir
+                        .clone()
+                        .filter(|_| config.record_client_inputs),
+                )
+                .with_wire_log(open_wire_log(config.wire_log.as_ref(), &session_id))
+                .with_write_pacing(config.write_pacing)
+                .with_approval_priority(config.approval_priority)
+                .with_scratch(scratch)
+                .with_slo(self.slo.clone())
+                .with_usage_ledger(self.usage.clone())
+                .with_server_events(self.server_events.clone()),
+        );
+        let output = session.subscribe_to_broadcasts();
         debug!(
             session_id = %session_id,
             "Created new session instance"
         );
 
+        // The slot frees up once Claude is gone, however the session ends
+        if let Some(slot) = slot {
+            let session = session.clone();
+            tokio::spawn(async move {
+                session.wait_exited().await;
+                drop(slot);
+            });
+        }
+
+        // Subscribe before anything is sent so watchers see every event of the run
+        if self.notifier.is_enabled() {
+            tokio::spawn(notifications::follow_session(
+                self.notifier.clone(),
+                session.id.clone(),
+                working_dir.to_path_buf(),
+                session.owner().map(str::to_string),
+                session.subscribe_to_broadcasts(),
+                session.subscribe_to_approval_broadcasts(),
+                config
+                    .approval_priority
+                    .map(|priority| priority.batch_window)
+                    .unwrap_or_default(),
+            ));
+        }
+        if self.exporter.is_enabled() {
+            tokio::spawn(event_export::follow_session(
+                self.exporter.clone(),
+                session.id.clone(),
+                working_dir.to_path_buf(),
+                session.subscribe_to_broadcasts(),
+                session.subscribe_to_approval_broadcasts(),
+            ));
+        }
+        if let Some(webhook) = &self.approval_webhook {
+            tokio::spawn(approval_webhook::follow_session(
+                webhook.clone(),
+                session.id.clone(),
+                working_dir.to_path_buf(),
+                session.subscribe_to_approval_broadcasts(),
+            ));
+        }
+        if let Some(slack) = &self.slack {
+            tokio::spawn(slack::follow_session(
+                slack.clone(),
+                session.id.clone(),
+                working_dir.to_path_buf(),
+                session.subscribe_to_approval_broadcasts(),
+            ));
+        }
+
         // Store session immediately with pending status
         self.sessions.insert(session_id.clone(), session.clone());
+        abandoned.session = Some(session.clone());
+        session
+            .announce(ServerEventKind::SessionCreated {
+                working_directory: working_dir.to_path_buf(),
+            })
+            .await;
         info!(
             session_id = %session_id,
             "Session stored in session manager with pending status"
         );
 
+        tokio::spawn(Self::reap_after_exit(
+            self.sessions.clone(),
+            self.telemetry.clone(),
+            session.clone(),
+            config.dead_session_grace_period,
+        ));
+
+        // What a previous run never got to write to Claude comes before anything new
+        if resume {
+            let unsent = session.unsent_writes().await;
+            if !unsent.is_empty() {
+                info!(
+                    session_id = %session_id,
+                    unsent_count = unsent.len(),
+                    "Replaying messages the previous run did not write to Claude"
+                );
+                bootstrap_messages.splice(0..0, unsent.into_iter().map(|msg| msg.content));
+            }
+        }
+
         // Spawn background worker
-        let config = self.config.clone();
+        let launcher = self.launcher(backend);
         let session_clone = session.clone();
-        let sessions = self.sessions.clone();
         let worker_session_id = session_id.clone();
         let working_dir = working_dir.to_path_buf();
 
@@ -284,6 +1541,7 @@ impl SessionManager {
             "Spawning background worker for Claude process"
         );
 
+        let slo = self.slo.clone();
         let handle = tokio::spawn(async move {
             debug!(
                 session_id = %worker_session_id,
@@ -292,39 +1550,77 @@ impl SessionManager {
                 "Starting Claude process spawn in background worker"
             );
 
-            match Self::spawn_claude_process(
-                &config,
+            let started = Instant::now();
+            let spawned = Self::spawn_claude_process(
+                &launcher.config,
+                launcher.backend.clone(),
                 &worker_session_id,
                 &working_dir,
                 resume,
                 bootstrap_messages.clone(),
                 session_clone.clone(),
             )
-            .await
-            {
-                Ok(actual_session_id) => {
+            .await;
+            match &spawned {
+                Ok(_) => slo.record(Operation::SessionSpawn, started.elapsed()),
+                Err(_) => slo.record_error(Operation::SessionSpawn, started.elapsed()),
+            }
+            match spawned {
+                Ok((actual_session_id, ended)) => {
                     info!(
                         requested_session_id = %worker_session_id,
                         actual_session_id = %actual_session_id,
                         "Claude process spawned successfully"
                     );
 
+                    // Replayed messages went out with the bootstrap ones
+                    session_clone.confirm_written().await;
+
                     // If session ID changed (resume case), update the mapping
                     if actual_session_id != worker_session_id {
+                        launcher
+                            .adopt_id(&session_clone, &worker_session_id, &actual_session_id)
+                            .await;
+                    }
+
+                    let limits = launcher.config.session_limits.narrowed(
+                        session_clone
+                            .options
+                            .max_duration_secs
+                            .map(Duration::from_secs),
+                        session_clone.options.max_turns,
+                    );
+                    if let Some(max_duration) = limits.max_duration {
+                        tokio::spawn(enforce_duration_limit(
+                            session_clone.clone(),
+                            max_duration,
+                            limits.action,
+                        ));
+                    }
+                    tokio::spawn(launcher.clone().supervise(session_clone.clone(), ended));
+
+                    // Bring back approvals Claude may still be blocked on from a previous run
+                    let restored = session_clone.restore_pending_approvals().await;
+                    if !restored.is_empty() {
                         info!(
-                            old_session_id = %worker_session_id,
-                            new_session_id = %actual_session_id,
-                            "Session ID changed during resume, updating session mapping"
+                            session_id = %actual_session_id,
+                            restored_count = restored.len(),
+                            "Restored persisted pending approvals"
+                        );
+                    }
+                    for approval_request in restored {
+                        let _ = session_clone
+                            .broadcast_approval_message(ApprovalMessage::request(approval_request));
+                    }
+
+                    // Bootstrap turns may already have used up the turn limit
+                    if session_clone.get_status().await == SessionStatus::Pending {
+                        session_clone.set_status(SessionStatus::Ready).await;
+                        info!(
+                            session_id = %actual_session_id,
+                            "Session status set to Ready"
                         );
-                        sessions.remove(&worker_session_id);
-                        session_clone.set_id(actual_session_id.clone()).await;
-                        sessions.insert(actual_session_id.clone(), session_clone.clone());
                     }
-                    session_clone.set_status(SessionStatus::Ready).await;
-                    info!(
-                        session_id = %actual_session_id,
-                        "Session status set to Ready"
-                    );
                 }
                 Err(e) => {
                     error!(
@@ -338,11 +1634,14 @@ impl SessionManager {
                         session_id = %worker_session_id,
                         "Session status set to Failed, removing from sessions"
                     );
-                    sessions.remove(&worker_session_id);
+                    launcher.sessions.remove(&worker_session_id);
+                    // Nothing left to reap
+                    session_clone.mark_exited().await;
                 }
             }
         });
 
+        abandoned.worker = Some(handle.abort_handle());
         self.worker_handles.insert(session_id.clone(), handle);
         debug!(
             session_id = %session_id,
@@ -374,7 +1673,7 @@ impl SessionManager {
         );
 
         match final_status {
-            SessionStatus::Ready => {
+            SessionStatus::Ready | SessionStatus::LimitReached => {
                 // Get the actual session ID (may be different for resume case)
                 let actual_session_id = if resume {
                     session.get_id().await
@@ -383,18 +1682,19 @@ impl SessionManager {
                 };
 
                 // Check if the process has already exited (immediate failure)
-                if let Some(pid) = session.get_process_id().await {
+                if session.is_active().await {
+                    let process_id = session.get_process_id().await;
                     debug!(
                         session_id = %actual_session_id,
-                        process_id = pid,
+                        process_id = ?process_id,
                         "Checking if Claude process is still running before waiting for session file"
                     );
 
                     // Give the process a moment to stabilize (1s)
-                    tokio::time::sleep(Duration::from_millis(1000)).await;
+                    tokio::time::sleep(Duration::from_secs(1)).await;
 
                     // Check if process is still alive
-                    if session.get_process_id().await.is_none() {
+                    if !session.is_active().await {
                         error!(
                             session_id = %actual_session_id,
                             "Claude process exited immediately after spawn"
@@ -421,10 +1721,17 @@ impl SessionManager {
 
                 // Use a 20 second timeout for file creation
                 let file_timeout = Duration::from_secs(20);
-                if let Err(e) = self
+                let waiting = Instant::now();
+                let journal = self
                     .wait_for_session_file(&actual_session_id, file_timeout)
-                    .await
-                {
+                    .await;
+                match &journal {
+                    Ok(()) => self.slo.record(Operation::JournalReady, waiting.elapsed()),
+                    Err(_) => self
+                        .slo
+                        .record_error(Operation::JournalReady, waiting.elapsed()),
+                }
+                if let Err(e) = journal {
                     error!(
                         session_id = %actual_session_id,
                         error = %e,
@@ -439,15 +1746,26 @@ impl SessionManager {
                         actual_session_id = %actual_session_id,
                         "Session created successfully (resumed with different ID) and file is ready"
                     );
-                    Ok(actual_session_id)
+                    session.mark_started();
+                    Ok((actual_session_id, output))
                 } else {
                     info!(
                         session_id = %session_id,
                         "Session created successfully and file is ready"
                     );
-                    Ok(session_id)
+                    session.mark_started();
+                    Ok((session_id, output))
                 }
             }
+            SessionStatus::Exited | SessionStatus::ResourceLimitExceeded => {
+                error!(
+                    session_id = %session_id,
+                    "Session creation failed - Claude process exited during startup"
+                );
+                Err(OrchestratorError::ClaudeSpawnFailed(
+                    "Claude binary failed to start - process exited immediately".into(),
+                ))
+            }
             SessionStatus::Failed => {
                 error!(
                     session_id = %session_id,
@@ -469,47 +1787,58 @@ impl SessionManager {
         }
     }
 
-    #[instrument(skip(config, session), fields(session_id = %session_id, working_dir = %working_dir.display(), resume = resume, bootstrap_messages_len = bootstrap_messages.len()))]
+    #[instrument(skip(config, backend, session), fields(session_id = %session_id, backend = backend.name(), working_dir = %working_dir.display(), resume = resume, bootstrap_messages_len = bootstrap_messages.len()))]
     #[allow(clippy::too_many_lines)]
     async fn spawn_claude_process(
         config: &Config,
+        backend: Arc<dyn SessionBackend>,
         session_id: &str,
         working_dir: &Path,
         resume: bool,
         bootstrap_messages: Vec<String>,
         session: Arc<Session>,
-    ) -> OrchestratorResult<String> {
+    ) -> OrchestratorResult<(String, oneshot::Receiver<SessionEnd>)> {
         info!(
             session_id = %session_id,
             working_dir = %working_dir.display(),
             resume = resume,
-            claude_binary = %config.claude_binary_path.display(),
+            backend = backend.name(),
             "Spawning Claude process"
         );
 
-        // Spawn Claude process
-        let (process, actual_session_id) = match ClaudeProcess::spawn(
-            config,
-            session_id,
-            working_dir,
-            resume,
-            &bootstrap_messages,
-        )
-        .await
+        // Written by the backend as Claude starts
+        for message in &bootstrap_messages {
+            session
+                .record_traffic(Direction::Stdin, None, message)
+                .await;
+        }
+
+        // Start the session on the configured backend
+        let connection = match backend
+            .start(StartRequest {
+                session_id,
+                working_dir,
+                resume,
+                bootstrap_messages: &bootstrap_messages,
+                options: &session.options,
+                scratch_dir: session.scratch_dir(),
+            })
+            .await
         {
-            Ok((proc, id)) => {
+            Ok(connection) => {
                 info!(
                     requested_session_id = %session_id,
-                    actual_session_id = %id,
+                    actual_session_id = %connection.session_id,
+                    backend = backend.name(),
                     "Claude process spawned successfully"
                 );
-                (proc, id)
+                connection
             }
             Err(e) => {
                 error!(
                     session_id = %session_id,
                     working_dir = %working_dir.display(),
-                    claude_binary = %config.claude_binary_path.display(),
+                    backend = backend.name(),
                     error = %e,
                     "Failed to spawn Claude process"
                 );
@@ -517,77 +1846,64 @@ impl SessionManager {
             }
         };
 
-        // Extract components from process before moving
-        let mut child = process.child;
-        let stdin_tx = process.stdin_tx;
-        let mut stdout_rx = process.stdout_rx;
+        // Extract components from the connection before moving
+        let actual_session_id = connection.session_id;
+        let stdin_tx = connection.stdin_tx;
+        let mut stdout_rx = connection.stdout_rx;
+        let process_id = connection.process_id;
+        let closed = connection.closed;
 
         debug!(
             session_id = %actual_session_id,
             "Extracted Claude process components"
         );
 
-        // Store process ID in session
-        session.set_process_id(child.id()).await;
+        // Store process ID in session; backends without one are tracked as connected
+        session.set_process_id(process_id).await;
+        session.set_backend_connected(process_id.is_none()).await;
         debug!(
             session_id = %actual_session_id,
-            process_id = ?child.id(),
+            process_id = ?process_id,
             "Stored Claude process ID in session"
         );
 
-        // Spawn dedicated task to wait for process exit and trigger immediate cleanup
+        if let Some(pid) = process_id {
+            tokio::spawn(monitor_process(
+                session.clone(),
+                pid,
+                config.process_monitoring,
+            ));
+        }
+
+        // Spawn dedicated task to wait for process exit and trigger immediate cleanup;
+        // whether the session is over is up to its supervisor
         let process_waiter_session = session.clone();
         let process_waiter_session_id = actual_session_id.clone();
+        let (ended_tx, ended_rx) = oneshot::channel();
+        let (run_over_tx, run_over) = watch::channel(false);
         tokio::spawn(async move {
-            let process_id = child.id();
             debug!(
                 session_id = %process_waiter_session_id,
-                process_id = ?process_id,
                 "Starting dedicated process waiter task"
             );
 
-            // Wait for the process to exit - this is the proper way to wait and avoid zombies
-            let exit_status = child.wait().await;
-
-            match exit_status {
-                Ok(status) => {
-                    warn!(
-                        session_id = %process_waiter_session_id,
-                        process_id = ?process_id,
-                        exit_code = status.code(),
-                        exit_success = status.success(),
-                        "Claude process has exited"
-                    );
-                }
-                Err(e) => {
-                    error!(
-                        session_id = %process_waiter_session_id,
-                        process_id = ?process_id,
-                        error = %e,
-                        "Error waiting for Claude process to exit"
-                    );
-                }
-            }
+            // Wait for the session to end - for the CLI this also reaps the process
+            let end = closed.await;
 
             // Clear the process ID from the session
             process_waiter_session.set_process_id(None).await;
+            process_waiter_session.set_backend_connected(false).await;
 
-            // Immediately broadcast disconnect to all WebSocket clients
-            if let Err(e) = process_waiter_session.broadcast_message(BroadcastMessage::Disconnect) {
-                error!(
-                    session_id = %process_waiter_session_id,
-                    error = %e,
-                    "Failed to broadcast disconnect after process exit"
-                );
-            } else {
-                info!(
-                    session_id = %process_waiter_session_id,
-                    "Successfully broadcast disconnect after process exit"
-                );
-            }
+            // Nobody is waiting on these approvals anymore
+            process_waiter_session.clear_pending_approvals().await;
+
+            // Stop the tasks serving this process before a restarted one gets its own
+            run_over_tx.send_replace(true);
+            let _ = ended_tx.send(end);
 
             debug!(
                 session_id = %process_waiter_session_id,
+                end = ?end,
                 "Process waiter task finished"
             );
         });
@@ -595,6 +1911,15 @@ impl SessionManager {
         // Spawn task to handle Claude output and broadcast to WebSocket clients
         let output_session = session.clone();
         let output_session_id = actual_session_id.clone();
+        let stdout_mode = config.stdout_mode;
+        let control_routes = config.control_routes.clone();
+        let risk_patterns = config.risk_patterns.clone();
+        let sandbox = config.working_dir_sandbox;
+        let control_stdin_tx = stdin_tx.clone();
+        let limits = config.session_limits.narrowed(
+            session.options.max_duration_secs.map(Duration::from_secs),
+            session.options.max_turns,
+        );
         tokio::spawn(async move {
             info!(
                 session_id = %output_session_id,
@@ -603,7 +1928,13 @@ impl SessionManager {
 
             let mut lines_processed = 0;
             while let Some(line) = stdout_rx.recv().await {
+                let received_at = chrono::Utc::now();
                 lines_processed += 1;
+                #[cfg(feature = "testing")]
+                let line = output_session.faults.corrupt_stdout_line(line);
+                output_session
+                    .record_traffic(Direction::Stdout, None, &line)
+                    .await;
                 debug!(
                     session_id = %output_session_id,
                     line_number = lines_processed,
@@ -612,8 +1943,27 @@ impl SessionManager {
                 );
 
                 // Parse and validate JSON
-                let parsed_line: serde_json::Value = match serde_json::from_str(&line) {
-                    Ok(value) => value,
+                let message = match ClaudeMessage::parse(&line) {
+                    Ok(message) => message,
+                    Err(e) if stdout_mode == StdoutMode::Tolerant => {
+                        let raw_lines = output_session.record_raw_output_line();
+                        warn!(
+                            session_id = %output_session_id,
+                            line_number = lines_processed,
+                            line_content = %line,
+                            error = %e,
+                            raw_lines = raw_lines,
+                            "Passing through non-JSON line from Claude process"
+                        );
+
+                        let raw_event =
+                            serde_json::json!({ "type": "raw", "text": line }).to_string();
+                        let _ = output_session.broadcast_message(BroadcastMessage::ClaudeOutput {
+                            line: raw_event.into(),
+                            received_at,
+                        });
+                        continue;
+                    }
                     Err(e) => {
                         error!(
                             session_id = %output_session_id,
@@ -644,46 +1994,45 @@ impl SessionManager {
                     "Valid JSON received from Claude, checking message type"
                 );
 
-                // Check if this is a control_request for tool approval
-                let message_type = parsed_line.get("type");
-                let message_subtype = parsed_line.get("subtype");
+                // Check if this is a control_request and where its subtype is routed
                 debug!(
                     session_id = %output_session_id,
-                    message_type = ?message_type,
-                    message_subtype = ?message_subtype,
+                    message_type = message.kind(),
                     "Checking if this is a control_request"
                 );
 
-                if parsed_line.get("type")
-                    == Some(&serde_json::Value::String("control_request".to_string()))
-                    && parsed_line
-                        .get("request")
-                        .and_then(|r| r.get("subtype"))
-                        .is_some_and(|st| {
-                            st == &serde_json::Value::String("can_use_tool".to_string())
-                        })
+                let control_route = match &message {
+                    ClaudeMessage::ControlRequest(request) => {
+                        Some(control_routes.route(request.subtype()))
+                    }
+                    _ => None,
+                };
+
+                if let (Some(ControlRoute::Approvals), ClaudeMessage::ControlRequest(control)) =
+                    (control_route, &message)
                 {
                     debug!(
                         session_id = %output_session_id,
                         line_number = lines_processed,
-                        "Detected control_request for tool approval"
+                        subtype = control.subtype(),
+                        "Detected control_request routed to approvals"
                     );
 
                     // Generate unique ID for our wrapper (for frontend/backend matching)
                     let approval_id = Uuid::new_v4().to_string();
 
                     // Extract Claude's original request_id for internal error handling
-                    let claude_request_id = parsed_line
-                        .get("request_id")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or(&Uuid::new_v4().to_string())
-                        .to_string();
+                    let claude_request_id = control
+                        .request_id
+                        .clone()
+                        .unwrap_or_else(|| Uuid::new_v4().to_string());
 
                     // Pass through the entire nested 'request' object from Claude as-is
-                    let claude_request = parsed_line
-                        .get("request")
-                        .cloned()
-                        .unwrap_or_else(|| serde_json::json!({}));
+                    let claude_request = if control.request.is_null() {
+                        serde_json::json!({})
+                    } else {
+                        control.request.clone()
+                    };
 
                     debug!(
                         session_id = %output_session_id,
@@ -692,6 +2041,24 @@ impl SessionManager {
                         "Creating wrapped approval request (pass-through approach)"
                     );
 
+                    let mut assessment = approval_risk::classify(
+                        &claude_request,
+                        &output_session.working_dir,
+                        &risk_patterns,
+                    );
+                    let escaping_path = (sandbox != SandboxMode::Off)
+                        .then(|| {
+                            working_dir_sandbox::escaping_path(
+                                &claude_request,
+                                &output_session.working_dir,
+                            )
+                        })
+                        .flatten();
+                    if let Some(path) = &escaping_path {
+                        assessment.flag_outside_working_dir(path);
+                    }
+                    let risk = assessment.level;
+
                     // Create approval request with raw Claude data - no parsing
                     let approval_request = ApprovalRequest {
                         id: approval_id.clone(),
@@ -699,8 +2066,61 @@ impl SessionManager {
                         claude_request_id: claude_request_id.clone(),
                         request: claude_request, // Raw Claude request - pass through
                         created_at: std::time::SystemTime::now(),
+                        clarifications: Vec::new(),
+                        risk,
+                        risk_reasons: assessment.reasons,
+                        priority: output_session.approval_lane(risk),
                     };
 
+                    if let (SandboxMode::Deny, Some(path)) = (sandbox, &escaping_path) {
+                        warn!(
+                            session_id = %output_session_id,
+                            approval_id = %approval_id,
+                            path = %path.display(),
+                            "Denying tool request outside the working directory"
+                        );
+                        answer_approval_directly(
+                            &output_session,
+                            &control_stdin_tx,
+                            &approval_request,
+                            &serde_json::json!({
+                                "behavior": "deny",
+                                "message": format!(
+                                    "{} is outside the session's working directory",
+                                    path.display()
+                                ),
+                            }),
+                            SANDBOX_IDENTITY,
+                        )
+                        .await;
+                        continue;
+                    }
+
+                    // Requests allowed before with a remembered scope are answered at once
+                    if let Some(rule) = output_session.approval_rules().and_then(|rules| {
+                        rules.matching(
+                            &output_session_id,
+                            &output_session.rules_project(),
+                            &approval_request.request,
+                        )
+                    }) {
+                        info!(
+                            session_id = %output_session_id,
+                            approval_id = %approval_id,
+                            rule_id = %rule.id,
+                            "Allowing approval request by a remembered rule"
+                        );
+                        answer_approval_directly(
+                            &output_session,
+                            &control_stdin_tx,
+                            &approval_request,
+                            &serde_json::json!({"behavior": "allow", "updatedInput": rule.input}),
+                            &format!("{RULE_IDENTITY_PREFIX}{}", rule.id),
+                        )
+                        .await;
+                        continue;
+                    }
+
                     // Store the approval request in the session
                     output_session
                         .add_pending_approval(approval_request.clone())
@@ -710,12 +2130,13 @@ impl SessionManager {
                         session_id = %output_session_id,
                         approval_id = %approval_id,
                         claude_request_id = %claude_request_id,
+                        risk = %risk,
                         "Stored approval request and broadcasting to approval clients"
                     );
 
                     // Broadcast to approval WebSocket clients
                     if let Err(e) = output_session.broadcast_approval_message(
-                        ApprovalMessage::ApprovalRequest(approval_request),
+                        ApprovalMessage::request(approval_request),
                     ) {
                         error!(
                             session_id = %output_session_id,
@@ -727,17 +2148,49 @@ impl SessionManager {
 
                     // Do NOT broadcast control_requests to regular Claude WebSocket clients
                     // Claude will wait for our response via stdin
+                } else if let (Some(ControlRoute::Auto), ClaudeMessage::ControlRequest(control)) =
+                    (control_route, &message)
+                {
+                    let subtype = control.subtype();
+                    let response_json =
+                        unsupported_control_response(control.request_id.as_deref(), subtype);
+                    info!(
+                        session_id = %output_session_id,
+                        subtype = %subtype,
+                        "Answering unhandled control_request with an error"
+                    );
+                    output_session
+                        .record_traffic(Direction::Stdin, None, &response_json)
+                        .await;
+                    if control_stdin_tx.send(response_json).await.is_err() {
+                        error!(
+                            session_id = %output_session_id,
+                            "Failed to send control_response to Claude stdin"
+                        );
+                    }
                 } else {
                     // This is a regular Claude message, broadcast to regular clients
                     debug!(
                         session_id = %output_session_id,
                         line_number = lines_processed,
-                        message_type = ?message_type,
+                        message_type = message.kind(),
                         "Regular Claude message (not control_request), broadcasting to clients"
                     );
 
-                    // Broadcast Claude output to all clients
-                    match output_session.broadcast_message(BroadcastMessage::ClaudeOutput(line)) {
+                    // Broadcast Claude output to all clients; partial output only reaches
+                    // the clients that opted into streaming
+                    let output = if matches!(message, ClaudeMessage::StreamEvent {}) {
+                        BroadcastMessage::StreamEvent {
+                            line: line.into(),
+                            received_at,
+                        }
+                    } else {
+                        BroadcastMessage::ClaudeOutput {
+                            line: line.into(),
+                            received_at,
+                        }
+                    };
+                    match output_session.broadcast_message(output) {
                         Ok(receiver_count) => {
                             debug!(
                                 session_id = %output_session_id,
@@ -757,6 +2210,30 @@ impl SessionManager {
                             // Continue processing - approval system may still need us
                         }
                     }
+
+                    // Claude's reply to an approver's question goes with the approval
+                    if let ClaudeMessage::Assistant(assistant) = &message {
+                        let text = assistant_text(assistant);
+                        if !text.is_empty() {
+                            for answered in output_session.answer_clarifications(&text).await {
+                                info!(
+                                    session_id = %output_session_id,
+                                    approval_id = %answered.id,
+                                    "Attached Claude's answer to approval clarification"
+                                );
+                                let _ = output_session
+                                    .broadcast_approval_message(ApprovalMessage::request(answered));
+                            }
+                        }
+                    }
+
+                    if let ClaudeMessage::Result(result) = &message {
+                        output_session.record_usage(result).await;
+                        let turns = output_session.record_turn();
+                        if limits.max_turns.is_some_and(|max_turns| turns >= max_turns) {
+                            reach_limit(&output_session, "turns", limits.action).await;
+                        }
+                    }
                 }
             }
 
@@ -764,53 +2241,90 @@ impl SessionManager {
             info!(
                 session_id = %output_session_id,
                 total_lines_processed = lines_processed,
+                raw_lines = output_session.get_raw_output_lines(),
                 "Claude output handler finished - process waiter will handle disconnect"
             );
             // Note: Don't broadcast disconnect here since the dedicated process waiter will handle it
-        });
+        }
+        .instrument(info_span!("claude_output", session_id = %actual_session_id)));
 
         // Spawn task to process write queue
         let write_session = session.clone();
         let write_stdin_tx = stdin_tx.clone();
-        tokio::spawn(async move {
-            loop {
-                // Check if process is still alive
-                if write_session.get_process_id().await.is_none() {
-                    break;
-                }
+        let write_run_over = run_over.clone();
+        tokio::spawn(
+            async move {
+                loop {
+                    // Check if process is still alive
+                    if *write_run_over.borrow() || !write_session.is_active().await {
+                        break;
+                    }
 
-                // Process write queue
-                if let Some(msg) = write_session.dequeue_message().await {
-                    // Compact JSON to ensure single-line format
-                    let compacted_message = match compact_json_message(&msg.content, "write_queue")
-                    {
-                        Ok(compacted) => compacted,
-                        Err(e) => {
-                            error!("Failed to compact message from write queue: {}", e);
-                            break;
+                    // Process write queue; under pacing a message stays queued, where
+                    // its sender can see its position, until the bucket lets it through
+                    let next = match write_session.dequeue_paced_message().await {
+                        Ok(next) => next,
+                        Err(wait) => {
+                            tokio::time::sleep(wait).await;
+                            continue;
                         }
                     };
-
-                    if write_stdin_tx.send(compacted_message).await.is_err() {
-                        eprintln!("Failed to send message to Claude stdin");
-                        break;
+                    if let Some(msg) = next {
+                        // Before the queue looks empty, so shutdown never sees it drained
+                        // while the message is on its way
+                        write_session.start_turn();
+                        // Compact JSON to ensure single-line format
+                        let compacted_message =
+                            match compact_json_message(&msg.content, "write_queue") {
+                                Ok(compacted) => compacted,
+                                Err(e) => {
+                                    error!("Failed to compact message from write queue: {}", e);
+                                    break;
+                                }
+                            };
+
+                        #[cfg(feature = "testing")]
+                        if let Some(delay) = write_session.faults.stdin_delay() {
+                            tokio::time::sleep(delay).await;
+                        }
+                        write_session
+                            .record_traffic(
+                                Direction::Stdin,
+                                Some(&msg.sender_client_id),
+                                &compacted_message,
+                            )
+                            .await;
+                        if write_stdin_tx.send(compacted_message).await.is_err() {
+                            eprintln!("Failed to send message to Claude stdin");
+                            break;
+                        }
+                        write_session.confirm_written().await;
                     }
-                }
 
-                // Small delay to prevent busy loop
-                tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+                    // Small delay to prevent busy loop
+                    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+                }
             }
-        });
+            .instrument(info_span!("claude_write_queue", session_id = %actual_session_id)),
+        );
 
         // Spawn task to handle approval responses
         let approval_session = session.clone();
         let approval_stdin_tx = stdin_tx.clone();
         let approval_session_id = actual_session_id.clone();
+        let mut approval_run_over = run_over;
         tokio::spawn(async move {
             let mut approval_rx = approval_session.subscribe_to_approval_broadcasts();
             info!(session_id = %approval_session_id, "Starting approval response handler");
 
-            while let Ok(approval_message) = approval_rx.recv().await {
+            loop {
+                let approval_message = tokio::select! {
+                    message = approval_rx.recv() => match message {
+                        Ok(message) => message,
+                        Err(_) => break,
+                    },
+                    _ = approval_run_over.wait_for(|over| *over) => break,
+                };
                 if let ApprovalMessage::ApprovalResponse(response_data) = approval_message {
                     // Extract our wrapper id from the client response
                     let wrapper_id = response_data
@@ -826,11 +2340,37 @@ impl SessionManager {
                     );
 
                     // Check if process is still alive
-                    if approval_session.get_process_id().await.is_none() {
+                    if !approval_session.is_active().await {
                         debug!(session_id = %approval_session_id, "Claude process not active, stopping approval response handler");
                         break;
                     }
 
+                    // Other instances sharing the approvals may have answered first
+                    if let Some(sync) = approval_session.approval_sync() {
+                        if !approval_sync::is_synced(&response_data)
+                            && approval_session.is_pending_approval(&wrapper_id).await
+                        {
+                            let decision = Decision::new(
+                                &approval_session_id,
+                                &wrapper_id,
+                                response_data.get("response").cloned().unwrap_or_default(),
+                                response_data
+                                    .get(DECIDED_BY_FIELD)
+                                    .and_then(|v| v.as_str())
+                                    .unwrap_or(DEFAULT_IDENTITY),
+                            );
+                            if let Some(earlier) = sync.claim(&decision).await {
+                                warn!(
+                                    session_id = %approval_session_id,
+                                    wrapper_id = %wrapper_id,
+                                    decided_by = %earlier.decided_by,
+                                    "Approval was answered on another instance first, dropping this response"
+                                );
+                                continue;
+                            }
+                        }
+                    }
+
                     // Remove the approval request from pending using our wrapper id
                     if let Some(removed_request) =
                         approval_session.remove_pending_approval(&wrapper_id).await
@@ -842,15 +2382,19 @@ impl SessionManager {
                         );
 
                         // Use the stored Claude request_id from the approval request
-                        let claude_request_id = removed_request.claude_request_id;
+                        let claude_request_id = removed_request.claude_request_id.clone();
 
                         // Pass through client's raw response to Claude without parsing
                         let default_response = serde_json::json!({
                             "behavior": "deny",
                             "message": "Invalid response format"
                         });
-                        let client_response =
-                            response_data.get("response").unwrap_or(&default_response);
+                        let mut client_response = response_data
+                            .get("response")
+                            .unwrap_or(&default_response)
+                            .clone();
+                        // Claude is not told how long the answer is remembered
+                        let remember = approval_rules::take_remember(&mut client_response);
 
                         let control_response = serde_json::json!({
                             "type": "control_response",
@@ -889,6 +2433,13 @@ impl SessionManager {
                             "Sending control_response to Claude stdin"
                         );
 
+                        let decided_by = response_data
+                            .get(DECIDED_BY_FIELD)
+                            .and_then(|v| v.as_str())
+                            .unwrap_or(DEFAULT_IDENTITY);
+                        approval_session
+                            .record_traffic(Direction::Stdin, Some(decided_by), &response_json)
+                            .await;
                         if let Err(e) = approval_stdin_tx.send(response_json).await {
                             error!(
                                 session_id = %approval_session_id,
@@ -905,6 +2456,32 @@ impl SessionManager {
                             claude_request_id = %claude_request_id,
                             "Successfully sent approval response to Claude"
                         );
+
+                        approval_session.record_approval_decision(
+                            &removed_request,
+                            &client_response,
+                            decided_by,
+                        );
+                        let remembered = approval_session
+                            .approval_rules()
+                            .filter(|_| client_response["behavior"] == "allow")
+                            .and_then(|rules| {
+                                rules.remember(
+                                    &approval_session_id,
+                                    &approval_session.rules_project(),
+                                    remember,
+                                    &removed_request,
+                                    decided_by,
+                                )
+                            });
+                        if let Some(rule) = remembered {
+                                info!(
+                                    session_id = %approval_session_id,
+                                    rule_id = %rule.id,
+                                    scope = ?rule.scope,
+                                    "Remembered approval rule"
+                                );
+                            }
                     } else {
                         warn!(
                             session_id = %approval_session_id,
@@ -917,9 +2494,24 @@ impl SessionManager {
             }
 
             info!(session_id = %approval_session_id, "Approval response handler stopped");
-        });
+        }
+        .instrument(info_span!("claude_approvals", session_id = %actual_session_id)));
+
+        Ok((actual_session_id, ended_rx))
+    }
 
-        Ok(actual_session_id)
+    fn launcher(&self, backend: Arc<dyn SessionBackend>) -> Launcher {
+        Launcher {
+            config: self.config(),
+            backend,
+            sessions: self.sessions.clone(),
+            lineage: self.lineage.clone(),
+            owners: self.owners.clone(),
+            notifier: self.notifier.clone(),
+            anchors: self.anchors.clone(),
+            approval_rules: self.approval_rules.clone(),
+            metadata: self.metadata.clone(),
+        }
     }
 
     #[must_use]
@@ -928,97 +2520,253 @@ impl SessionManager {
     }
 
     pub async fn get_active_sessions(&self) -> Vec<Arc<Session>> {
+        // Map guards are not held across the awaits below, as the reaper removes
+        // exited sessions from the same shards
+        let sessions: Vec<Arc<Session>> = self
+            .sessions
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect();
         let mut active_sessions = Vec::new();
-        for entry in self.sessions.iter() {
-            let session = entry.value();
+        for session in sessions {
             if session.is_active().await {
-                active_sessions.push(session.clone());
+                active_sessions.push(session);
             }
         }
         active_sessions
     }
 
-    pub async fn shutdown(&self) {
-        // Send SIGTERM to all Claude processes using process IDs
-        for entry in self.sessions.iter() {
-            let session = entry.value();
-            if let Some(pid) = session.get_process_id().await {
-                // Try to kill the process using system kill
-                #[cfg(unix)]
-                {
-                    use nix::sys::signal::{kill, Signal};
-                    use nix::unistd::Pid;
-                    if let Ok(pid_i32) = i32::try_from(pid) {
-                        if let Err(e) = kill(Pid::from_raw(pid_i32), Signal::SIGTERM) {
-                            warn!(
-                                session_id = %session.get_id().await,
-                                process_id = pid,
-                                error = %e,
-                                "Failed to send SIGTERM to Claude process"
-                            );
-                        }
-                    }
-                }
-                #[cfg(not(unix))]
-                {
-                    warn!(
-                        session_id = %session.get_id().await,
-                        process_id = pid,
-                        "Process killing not implemented for non-Unix systems"
-                    );
-                }
-            }
+    /// Stops letting in new sessions and WebSocket clients until [`Self::stop_draining`].
+    pub fn start_draining(&self) {
+        if !self.draining.swap(true, Ordering::SeqCst) {
+            info!("Draining: refusing new sessions and WebSocket clients");
         }
+    }
 
-        // Wait for graceful shutdown
-        tokio::time::sleep(self.config.shutdown_timeout).await;
+    /// Starts draining for good, ahead of [`Self::drain`] and [`Self::shutdown`].
+    pub fn begin_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        self.start_draining();
+    }
 
-        // Force kill any remaining processes
-        for entry in self.sessions.iter() {
-            let session = entry.value();
-            if let Some(pid) = session.get_process_id().await {
-                #[cfg(unix)]
+    /// Lets new sessions and WebSocket clients in again, e.g. when draining was started
+    /// through the admin API to take the server out of rotation for a while. Does nothing
+    /// once a shutdown began.
+    pub fn stop_draining(&self) {
+        if self.shutting_down.load(Ordering::SeqCst) {
+            warn!("Not stopping draining: the server is shutting down");
+            return;
+        }
+        if self.draining.swap(false, Ordering::SeqCst) {
+            info!("Stopped draining: accepting new sessions and WebSocket clients");
+        }
+    }
+
+    #[must_use]
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Uptime, draining mode and the internal state of every session held.
+    pub async fn admin_status(&self) -> AdminStatusResponse {
+        let sessions: Vec<Arc<Session>> = self
+            .sessions
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect();
+        let mut states = Vec::with_capacity(sessions.len());
+        for session in sessions {
+            states.push(session.admin_state().await);
+        }
+        states.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+        AdminStatusResponse {
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            draining: self.is_draining(),
+            sessions: states,
+        }
+    }
+
+    /// Refuses new sessions and clients once draining has started.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ShuttingDown` while the orchestrator is draining.
+    pub fn check_accepting(&self) -> OrchestratorResult<()> {
+        if self.is_draining() {
+            return Err(OrchestratorError::ShuttingDown(
+                "The server is shutting down and accepts no new sessions or connections".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Waits up to `limit` for every session to go idle: its queued input written, its
+    /// turn finished and no approval left pending. Returns whether they all did.
+    pub async fn drain(&self, limit: Duration) -> bool {
+        let sessions = &self.sessions;
+        let drained = wait_until(limit, move || async move {
+            let sessions: Vec<Arc<Session>> =
+                sessions.iter().map(|entry| entry.value().clone()).collect();
+            for session in sessions {
+                if session.is_active().await
+                    && (!session.is_drained().await
+                        || !session.get_pending_approvals().await.is_empty())
                 {
-                    use nix::sys::signal::{kill, Signal};
-                    use nix::unistd::Pid;
-                    if let Ok(pid_i32) = i32::try_from(pid) {
-                        if let Err(e) = kill(Pid::from_raw(pid_i32), Signal::SIGKILL) {
-                            warn!(
-                                session_id = %session.get_id().await,
-                                process_id = pid,
-                                error = %e,
-                                "Failed to send SIGKILL to Claude process"
-                            );
-                        }
-                    }
+                    return false;
                 }
-                session.set_process_id(None).await;
             }
+            true
+        })
+        .await;
+        if drained {
+            info!("All sessions drained");
+        } else {
+            warn!(
+                limit_secs = limit.as_secs(),
+                "Sessions still busy when the drain period ran out"
+            );
+        }
+        drained
+    }
+
+    /// Ends every session's Claude process as `SHUTDOWN_POLICIES` says for its tags,
+    /// all at once, and returns when they are gone.
+    pub async fn shutdown(&self) {
+        let sessions: Vec<Arc<Session>> = self
+            .sessions
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect();
+        let config = self.config();
+        futures::future::join_all(sessions.into_iter().map(|session| {
+            let behavior = config.shutdown_policies.behavior(&session.options.tags);
+            shutdown_session(session, behavior, config.shutdown_timeout)
+        }))
+        .await;
+    }
+
+    /// Enqueues a message for a specific session. Returns where it waits when write
+    /// pacing holds it back.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session is not found or not active.
+    #[allow(dead_code)] // Public API for enqueueing by session ID
+    pub async fn enqueue_message(
+        &self,
+        session_id: &str,
+        message: WriteMessage,
+    ) -> OrchestratorResult<Option<PacedPosition>> {
+        let session = self
+            .get_session(session_id)
+            .ok_or_else(|| OrchestratorError::SessionNotFound(session_id.to_string()))?;
+        Self::enqueue_to(&session, message).await
+    }
+
+    /// Enqueues a message for `session`, which clients connected before a restart
+    /// changed its ID still hold. Input sent while a crashed Claude is restarting waits
+    /// for the new process.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session is not active.
+    pub async fn enqueue_to(
+        session: &Session,
+        message: WriteMessage,
+    ) -> OrchestratorResult<Option<PacedPosition>> {
+        if !session.is_active().await && !session.is_restarting() {
+            return Err(OrchestratorError::ProcessCommunicationError(
+                "Session is not active".into(),
+            ));
+        }
+        if session.get_status().await == SessionStatus::LimitReached {
+            return Err(OrchestratorError::InvalidRequest(
+                "Session has reached its limit and takes no more input".into(),
+            ));
         }
+
+        Ok(session.enqueue_message(message).await)
     }
 
-    /// Enqueues a message for a specific session.
+    /// Ends a session on request: its Claude process gets SIGTERM, and SIGKILL if it has
+    /// not exited after `SESSION_TERMINATE_TIMEOUT`. The session is forgotten and its
+    /// clients are disconnected. Sessions without a process of their own (SDK bridge)
+    /// are only forgotten.
     ///
     /// # Errors
     ///
-    /// Returns an error if the session is not found or not active.
-    pub async fn enqueue_message(
+    /// Returns an error if there is no such session.
+    pub async fn terminate_session(
         &self,
         session_id: &str,
-        message: WriteMessage,
-    ) -> OrchestratorResult<()> {
+    ) -> OrchestratorResult<TerminateSessionResponse> {
+        self.end_session(session_id, false).await
+    }
+
+    /// Ends a session at once: its Claude process gets SIGKILL without a chance to exit
+    /// on its own. Otherwise like [`Self::terminate_session`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such session.
+    pub async fn kill_session(
+        &self,
+        session_id: &str,
+    ) -> OrchestratorResult<TerminateSessionResponse> {
+        self.end_session(session_id, true).await
+    }
+
+    async fn end_session(
+        &self,
+        session_id: &str,
+        force: bool,
+    ) -> OrchestratorResult<TerminateSessionResponse> {
         let session = self
             .get_session(session_id)
             .ok_or_else(|| OrchestratorError::SessionNotFound(session_id.to_string()))?;
+        let pid = session.get_process_id().await;
+        info!(session_id = %session_id, process_id = ?pid, force, "Terminating session on request");
 
-        if !session.is_active().await {
-            return Err(OrchestratorError::ProcessCommunicationError(
-                "Session is not active".into(),
-            ));
-        }
+        let graceful = match pid {
+            Some(pid) if force => {
+                session.expect_exit();
+                kill_process(session_id, pid);
+                session.set_process_id(None).await;
+                false
+            }
+            Some(pid) => {
+                terminate_gracefully(&session, session_id, pid, self.config().terminate_timeout)
+                    .await
+            }
+            None => true,
+        };
+        self.sessions
+            .remove_if(session_id, |_, current| Arc::ptr_eq(current, &session));
+        // The process waiter does the same once it sees the exit; bridge sessions have none
+        let _ = session.broadcast_message(BroadcastMessage::Disconnect);
+        info!(session_id = %session_id, graceful, "Session terminated");
+
+        Ok(TerminateSessionResponse {
+            session_id: session_id.to_string(),
+            process_terminated: pid.is_some(),
+            killed: !graceful,
+        })
+    }
 
-        session.enqueue_message(message).await;
-        Ok(())
+    /// Ends a session that is no longer wanted: its Claude process is asked to exit and
+    /// the session is forgotten. Sessions without a process of their own (SDK bridge)
+    /// are only forgotten.
+    pub async fn stop_session(&self, session_id: &str) {
+        let Some((_, session)) = self.sessions.remove(session_id) else {
+            return;
+        };
+        session.expect_exit();
+        let Some(pid) = session.get_process_id().await else {
+            debug!(session_id = %session_id, "Stopped session has no process to signal");
+            return;
+        };
+        terminate_process(session_id, pid);
+        info!(session_id = %session_id, process_id = pid, "Session stopped");
     }
 }
 
@@ -1029,6 +2777,7 @@ mod tests {
     use std::fs;
     use tempfile::TempDir;
 
+    #[allow(clippy::too_many_lines)]
     fn create_test_config(temp_dir: &TempDir) -> Config {
         // Create a mock Claude binary
         let claude_path = temp_dir.path().join("mock_claude");
@@ -1109,6 +2858,53 @@ done
             http_listen_address: "127.0.0.1:8080".to_string(),
             claude_projects_dir: projects_dir,
             shutdown_timeout: std::time::Duration::from_secs(1),
+            terminate_timeout: std::time::Duration::from_secs(1),
+            shutdown_policies: crate::config::ShutdownPolicies::default(),
+            stdout_mode: crate::config::StdoutMode::Strict,
+            session_state_dir: None,
+            approval_sync_dir: None,
+            mdns_instance_name: None,
+            transcript_hooks: std::collections::HashMap::new(),
+            security_headers: crate::config::SecurityHeaders::default(),
+            claude_sdk_socket: None,
+            disconnect_queue_policy: crate::config::DisconnectQueuePolicy::default(),
+            notification_targets: std::collections::HashMap::new(),
+            dead_session_grace_period: std::time::Duration::from_secs(60),
+            telemetry_endpoint: None,
+            working_dir_roots: Vec::new(),
+            identity_header: None,
+            api_keys: crate::config::ApiKeys::default(),
+            control_routes: crate::config::ControlRoutes::default(),
+            session_limits: crate::config::SessionLimits::default(),
+            session_capacity: None,
+            partial_messages: false,
+            process_monitoring: crate::config::ProcessMonitoring::default(),
+            resource_limits: crate::config::ResourceLimits::default(),
+            restart_policy: None,
+            cli_flag_allowlist: crate::config::CliFlagAllowlist::default(),
+            session_response_max_bytes: None,
+            container: None,
+            session_backend: None,
+            risk_approvers: crate::approval_risk::RiskApprovers::default(),
+            risk_patterns: crate::approval_risk::RiskPatterns::default(),
+            working_dir_sandbox: crate::working_dir_sandbox::SandboxMode::default(),
+            frontend_dir: None,
+            event_sinks: Vec::new(),
+            body_limits: crate::config::BodyLimits::default(),
+            record_client_inputs: false,
+            web_push: None,
+            tls: None,
+            write_pacing: None,
+            approval_priority: None,
+            approval_webhook: None,
+            slack: None,
+            push: None,
+            scratch: None,
+            wire_log: None,
+            slo: crate::config::SloSettings::default(),
+            heartbeat: crate::config::Heartbeat::default(),
+            swagger_ui: false,
+            cors_allowed_origins: Vec::new(),
         }
     }
 
@@ -1134,6 +2930,7 @@ done
                 &working_dir,
                 false,
                 vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+                SessionOptions::default(),
             )
             .await
             .unwrap();
@@ -1148,6 +2945,58 @@ done
         std::env::remove_var("CLAUDE_PROJECTS_DIR");
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn test_draining_refuses_new_sessions() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = create_test_config(&temp_dir);
+        let working_dir = temp_dir.path().join("work");
+        fs::create_dir_all(&working_dir).unwrap();
+        let manager = SessionManager::new(config);
+        assert!(manager.check_accepting().is_ok());
+
+        manager.start_draining();
+        assert!(manager.is_draining());
+        let result = manager
+            .create_session(
+                "draining-session".to_string(),
+                &working_dir,
+                false,
+                vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+                SessionOptions::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(OrchestratorError::ShuttingDown(_))));
+        assert!(manager.get_session("draining-session").is_none());
+
+        // Nothing is running, so there is nothing to wait for
+        assert!(manager.drain(Duration::from_secs(5)).await);
+
+        manager.stop_draining();
+        assert!(manager.check_accepting().is_ok());
+        manager.begin_shutdown();
+        manager.stop_draining();
+        assert!(manager.is_draining());
+    }
+
+    #[test]
+    fn test_reload_takes_only_reloadable_settings() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = create_test_config(&temp_dir);
+        let manager = SessionManager::new(config.clone());
+
+        let allowed = temp_dir.path().join("allowed");
+        fs::create_dir_all(&allowed).unwrap();
+        let mut reloaded = config;
+        reloaded.working_dir_roots = vec![fs::canonicalize(&allowed).unwrap()];
+        reloaded.shutdown_timeout = Duration::from_secs(42);
+        manager.reload_config(&reloaded);
+
+        assert!(manager.validate_working_dir(temp_dir.path()).is_err());
+        assert!(manager.validate_working_dir(&allowed).is_ok());
+        assert_ne!(manager.config().shutdown_timeout, Duration::from_secs(42));
+    }
+
     #[tokio::test]
     async fn test_invalid_working_directory() {
         let temp_dir = TempDir::new().unwrap();
@@ -1161,6 +3010,7 @@ done
                 &non_existent,
                 false,
                 vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+                SessionOptions::default(),
             )
             .await;
 
@@ -1171,6 +3021,44 @@ done
         ));
     }
 
+    #[tokio::test]
+    async fn test_working_dir_outside_roots() {
+        let temp_dir = TempDir::new().unwrap();
+        let allowed = temp_dir.path().join("allowed");
+        let outside = temp_dir.path().join("outside");
+        fs::create_dir_all(&allowed).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        let mut config = create_test_config(&temp_dir);
+        config.working_dir_roots = vec![fs::canonicalize(&allowed).unwrap()];
+        let manager = SessionManager::new(config);
+
+        // Neither directly nor through a symlink or `..` inside an allowed root
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&outside, allowed.join("link")).unwrap();
+        for working_dir in [
+            outside.clone(),
+            allowed.join("..").join("outside"),
+            #[cfg(unix)]
+            allowed.join("link"),
+        ] {
+            let result = manager
+                .create_session(
+                    "test-session".to_string(),
+                    &working_dir,
+                    false,
+                    vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+                    SessionOptions::default(),
+                )
+                .await;
+            match result {
+                Err(OrchestratorError::WorkingDirInvalid(msg)) => {
+                    assert!(msg.contains("outside the allowed roots"), "{msg}");
+                }
+                other => panic!("Expected WorkingDirInvalid for {working_dir:?}, got {other:?}"),
+            }
+        }
+    }
+
     #[tokio::test]
     #[serial]
     async fn test_session_already_exists() {
@@ -1194,6 +3082,7 @@ done
                 &working_dir,
                 false,
                 vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+                SessionOptions::default(),
             )
             .await
             .unwrap();
@@ -1205,6 +3094,7 @@ done
                 &working_dir,
                 false,
                 vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+                SessionOptions::default(),
             )
             .await
             .unwrap();
@@ -1237,6 +3127,7 @@ done
                 &working_dir,
                 false,
                 vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+                SessionOptions::default(),
             )
             .await
             .unwrap();
@@ -1278,6 +3169,7 @@ done
                 &working_dir,
                 false,
                 vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+                SessionOptions::default(),
             )
             .await
             .unwrap();
@@ -1291,6 +3183,387 @@ done
         std::env::remove_var("CLAUDE_PROJECTS_DIR");
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn test_shutdown_policies_by_tag() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = create_test_config(&temp_dir);
+        config.shutdown_policies = "ci=kill;migration=protected:2".parse().unwrap();
+        let working_dir = temp_dir.path().join("work");
+        fs::create_dir_all(&working_dir).unwrap();
+        std::env::set_var(
+            "CLAUDE_PROJECTS_DIR",
+            config.claude_projects_dir.to_str().unwrap(),
+        );
+
+        let manager = SessionManager::new(config);
+        for (session_id, tag) in [("ci-session", "ci"), ("migration-session", "migration")] {
+            manager
+                .create_session(
+                    session_id.to_string(),
+                    &working_dir,
+                    false,
+                    vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+                    SessionOptions {
+                        tags: vec![tag.to_string()],
+                        ..Default::default()
+                    },
+                )
+                .await
+                .unwrap();
+        }
+        // The mock never ends a turn, so the migration session never drains
+        manager
+            .enqueue_message(
+                "migration-session",
+                WriteMessage {
+                    content: r#"{"role": "user", "content": "migrate"}"#.to_string(),
+                    sender_client_id: "client1".to_string(),
+                    timestamp: std::time::SystemTime::now(),
+                },
+            )
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let started = std::time::Instant::now();
+        let shutdown = manager.shutdown();
+        tokio::pin!(shutdown);
+        // The CI session is killed straight away while the migration keeps running
+        assert!(timeout(Duration::from_millis(500), &mut shutdown)
+            .await
+            .is_err());
+        let ci_session = manager.get_session("ci-session").unwrap();
+        let migration_session = manager.get_session("migration-session").unwrap();
+        assert!(!ci_session.is_active().await);
+        assert!(migration_session.is_active().await);
+
+        shutdown.await;
+        assert!(started.elapsed() >= Duration::from_secs(2));
+        assert!(!migration_session.is_active().await);
+
+        std::env::remove_var("CLAUDE_PROJECTS_DIR");
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_exited_session_is_reaped() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = create_test_config(&temp_dir);
+        config.dead_session_grace_period = std::time::Duration::from_millis(200);
+        let working_dir = temp_dir.path().join("work");
+        fs::create_dir_all(&working_dir).unwrap();
+
+        std::env::set_var(
+            "CLAUDE_PROJECTS_DIR",
+            config.claude_projects_dir.to_str().unwrap(),
+        );
+
+        let manager = SessionManager::new(config);
+        manager
+            .create_session(
+                "dying-session".to_string(),
+                &working_dir,
+                false,
+                vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+                SessionOptions::default(),
+            )
+            .await
+            .unwrap();
+
+        let session = manager.get_session("dying-session").unwrap();
+        let pid = session.get_process_id().await.unwrap();
+        nix::sys::signal::kill(
+            nix::unistd::Pid::from_raw(i32::try_from(pid).unwrap()),
+            nix::sys::signal::Signal::SIGKILL,
+        )
+        .unwrap();
+
+        tokio::time::timeout(std::time::Duration::from_secs(5), session.wait_exited())
+            .await
+            .expect("Session should be marked exited");
+        assert_eq!(session.get_status().await, SessionStatus::Exited);
+        // Still visible during the grace period
+        assert!(manager.get_session("dying-session").is_some());
+
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        assert!(manager.get_session("dying-session").is_none());
+
+        std::env::remove_var("CLAUDE_PROJECTS_DIR");
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_running_sessions_are_capped() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = create_test_config(&temp_dir);
+        config.session_capacity = Some(crate::config::SessionCapacity {
+            max_running: 1,
+            queue_timeout: None,
+        });
+        let working_dir = temp_dir.path().join("work");
+        fs::create_dir_all(&working_dir).unwrap();
+
+        std::env::set_var(
+            "CLAUDE_PROJECTS_DIR",
+            config.claude_projects_dir.to_str().unwrap(),
+        );
+
+        let manager = Arc::new(SessionManager::new(config));
+        let start = |session_id: &'static str| {
+            let manager = manager.clone();
+            let working_dir = working_dir.clone();
+            async move {
+                manager
+                    .create_session(
+                        session_id.to_string(),
+                        &working_dir,
+                        false,
+                        vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+                        SessionOptions::default(),
+                    )
+                    .await
+            }
+        };
+
+        start("first").await.unwrap();
+        let refused = start("second").await.unwrap_err();
+        assert!(matches!(refused, OrchestratorError::TooManySessions(_)));
+        // Joining a running session takes no slot
+        start("first").await.unwrap();
+
+        manager.stop_session("first").await;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while start("second").await.is_err() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .expect("Slot should free up once the first session exited");
+
+        std::env::remove_var("CLAUDE_PROJECTS_DIR");
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_queued_start_waits_for_a_slot() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = create_test_config(&temp_dir);
+        config.session_capacity = Some(crate::config::SessionCapacity {
+            max_running: 1,
+            queue_timeout: Some(Duration::from_secs(10)),
+        });
+        let working_dir = temp_dir.path().join("work");
+        fs::create_dir_all(&working_dir).unwrap();
+
+        std::env::set_var(
+            "CLAUDE_PROJECTS_DIR",
+            config.claude_projects_dir.to_str().unwrap(),
+        );
+
+        let manager = Arc::new(SessionManager::new(config));
+        let start = |session_id: &'static str| {
+            let manager = manager.clone();
+            let working_dir = working_dir.clone();
+            async move {
+                manager
+                    .create_session(
+                        session_id.to_string(),
+                        &working_dir,
+                        false,
+                        vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+                        SessionOptions::default(),
+                    )
+                    .await
+            }
+        };
+
+        start("first").await.unwrap();
+        let queued = tokio::spawn(start("second"));
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(!queued.is_finished());
+        assert!(manager.get_session("second").is_none());
+
+        manager.stop_session("first").await;
+        assert_eq!(queued.await.unwrap().unwrap(), "second");
+
+        std::env::remove_var("CLAUDE_PROJECTS_DIR");
+    }
+
+    #[tokio::test]
+    async fn test_cli_flags_must_be_allowed() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = create_test_config(&temp_dir);
+        let working_dir = temp_dir.path().join("work");
+        fs::create_dir_all(&working_dir).unwrap();
+
+        let manager = SessionManager::new(config);
+        let result = manager
+            .create_session(
+                "flagged".to_string(),
+                &working_dir,
+                false,
+                vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+                SessionOptions {
+                    cli_flags: [("model".to_string(), "opus".to_string())].into(),
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(matches!(result, Err(OrchestratorError::InvalidRequest(_))));
+        assert!(manager.get_session("flagged").is_none());
+    }
+
+    /// The next system line Claude's output carries, such as a restart notice.
+    async fn next_system_event(
+        events: &mut broadcast::Receiver<BroadcastEnvelope>,
+    ) -> serde_json::Value {
+        loop {
+            if let BroadcastMessage::ClaudeOutput { line, .. } =
+                events.recv().await.unwrap().message
+            {
+                let event: serde_json::Value = serde_json::from_str(&line).unwrap();
+                if event["type"] == "system" {
+                    return event;
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_crashed_claude_is_restarted() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = create_test_config(&temp_dir);
+        config.restart_policy = Some(crate::config::RestartPolicy {
+            max_restarts: 1,
+            backoff: Duration::from_millis(50),
+        });
+        // Resuming waits for Claude to report the session ID
+        let script = fs::read_to_string(&config.claude_binary_path)
+            .unwrap()
+            .replace(
+                r#"{"sessionId": "$SESSION_ID", "type": "start"}"#,
+                r#"{"session_id": "$SESSION_ID", "type": "system"}"#,
+            );
+        fs::write(&config.claude_binary_path, script).unwrap();
+        let working_dir = temp_dir.path().join("work");
+        fs::create_dir_all(&working_dir).unwrap();
+
+        std::env::set_var(
+            "CLAUDE_PROJECTS_DIR",
+            config.claude_projects_dir.to_str().unwrap(),
+        );
+
+        let manager = SessionManager::new(config);
+        manager
+            .create_session(
+                "crashy".to_string(),
+                &working_dir,
+                false,
+                vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+                SessionOptions::default(),
+            )
+            .await
+            .unwrap();
+        let session = manager.get_session("crashy").unwrap();
+        let mut events = session.subscribe_to_broadcasts();
+
+        let first_pid = session.get_process_id().await.unwrap();
+        kill_process("crashy", first_pid);
+        let restarting = timeout(Duration::from_secs(5), next_system_event(&mut events))
+            .await
+            .unwrap();
+        assert_eq!(restarting["subtype"], "claude_restarting");
+        assert_eq!(restarting["attempt"], 1);
+
+        // Input sent meanwhile is what the resumed Claude starts with
+        manager
+            .enqueue_message(
+                "crashy",
+                WriteMessage {
+                    content: r#"{"role": "user", "content": "still there?"}"#.to_string(),
+                    sender_client_id: "client1".to_string(),
+                    timestamp: std::time::SystemTime::now(),
+                },
+            )
+            .await
+            .unwrap();
+        let reconnected = timeout(Duration::from_secs(5), next_system_event(&mut events))
+            .await
+            .unwrap();
+        assert_eq!(reconnected["subtype"], "claude_reconnected");
+        assert_eq!(reconnected["session_id"], "crashy");
+        assert!(session.is_active().await);
+        assert_ne!(session.get_process_id().await, Some(first_pid));
+
+        // Past the restart budget the session ends
+        kill_process("crashy", session.get_process_id().await.unwrap());
+        timeout(Duration::from_secs(5), session.wait_exited())
+            .await
+            .unwrap();
+        assert_eq!(session.get_status().await, SessionStatus::Exited);
+
+        std::env::remove_var("CLAUDE_PROJECTS_DIR");
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_unsent_messages_replayed_on_resume() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = create_test_config(&temp_dir);
+        // Resuming waits for Claude to report the session ID
+        let script = fs::read_to_string(&config.claude_binary_path)
+            .unwrap()
+            .replace(
+                r#"{"sessionId": "$SESSION_ID", "type": "start"}"#,
+                r#"{"session_id": "$SESSION_ID", "type": "system"}"#,
+            );
+        fs::write(&config.claude_binary_path, script).unwrap();
+        let state_dir = temp_dir.path().join("state");
+        fs::create_dir_all(&state_dir).unwrap();
+        config.session_state_dir = Some(state_dir.clone());
+        let working_dir = temp_dir.path().join("work");
+        fs::create_dir_all(&working_dir).unwrap();
+
+        std::env::set_var(
+            "CLAUDE_PROJECTS_DIR",
+            config.claude_projects_dir.to_str().unwrap(),
+        );
+
+        // Left behind by a run that exited before writing it
+        let unsent = serde_json::to_string(&WriteMessage {
+            content: r#"{"role": "user", "content": "left-behind"}"#.to_string(),
+            sender_client_id: "client1".to_string(),
+            timestamp: std::time::SystemTime::now(),
+        })
+        .unwrap();
+        fs::write(state_dir.join("interrupted.pending.jsonl"), unsent + "\n").unwrap();
+
+        let manager = SessionManager::new(config.clone());
+        manager
+            .create_session(
+                "interrupted".to_string(),
+                &working_dir,
+                true,
+                vec![r#"{"role": "user", "content": "resumed"}"#.to_string()],
+                SessionOptions::default(),
+            )
+            .await
+            .unwrap();
+
+        // Other runs of the mock binary leave project directories of their own
+        let transcript = fs::read_dir(&config.claude_projects_dir)
+            .unwrap()
+            .find_map(|dir| fs::read_to_string(dir.unwrap().path().join("interrupted.jsonl")).ok())
+            .unwrap();
+        let left_behind = transcript.find("left-behind").unwrap();
+        assert!(left_behind < transcript.find("resumed").unwrap());
+        assert!(!state_dir.join("interrupted.pending.jsonl").exists());
+
+        std::env::remove_var("CLAUDE_PROJECTS_DIR");
+    }
+
     #[tokio::test]
     #[serial]
     async fn test_claude_binary_immediate_failure() {
@@ -1321,6 +3594,53 @@ exit 1
             http_listen_address: "127.0.0.1:8080".to_string(),
             claude_projects_dir: projects_dir.clone(),
             shutdown_timeout: std::time::Duration::from_secs(1),
+            terminate_timeout: std::time::Duration::from_secs(1),
+            shutdown_policies: crate::config::ShutdownPolicies::default(),
+            stdout_mode: crate::config::StdoutMode::Strict,
+            session_state_dir: None,
+            approval_sync_dir: None,
+            mdns_instance_name: None,
+            transcript_hooks: std::collections::HashMap::new(),
+            security_headers: crate::config::SecurityHeaders::default(),
+            claude_sdk_socket: None,
+            disconnect_queue_policy: crate::config::DisconnectQueuePolicy::default(),
+            notification_targets: std::collections::HashMap::new(),
+            dead_session_grace_period: std::time::Duration::from_secs(60),
+            telemetry_endpoint: None,
+            working_dir_roots: Vec::new(),
+            identity_header: None,
+            api_keys: crate::config::ApiKeys::default(),
+            control_routes: crate::config::ControlRoutes::default(),
+            session_limits: crate::config::SessionLimits::default(),
+            session_capacity: None,
+            partial_messages: false,
+            process_monitoring: crate::config::ProcessMonitoring::default(),
+            resource_limits: crate::config::ResourceLimits::default(),
+            restart_policy: None,
+            cli_flag_allowlist: crate::config::CliFlagAllowlist::default(),
+            session_response_max_bytes: None,
+            container: None,
+            session_backend: None,
+            risk_approvers: crate::approval_risk::RiskApprovers::default(),
+            risk_patterns: crate::approval_risk::RiskPatterns::default(),
+            working_dir_sandbox: crate::working_dir_sandbox::SandboxMode::default(),
+            frontend_dir: None,
+            event_sinks: Vec::new(),
+            body_limits: crate::config::BodyLimits::default(),
+            record_client_inputs: false,
+            web_push: None,
+            tls: None,
+            write_pacing: None,
+            approval_priority: None,
+            approval_webhook: None,
+            slack: None,
+            push: None,
+            scratch: None,
+            wire_log: None,
+            slo: crate::config::SloSettings::default(),
+            heartbeat: crate::config::Heartbeat::default(),
+            swagger_ui: false,
+            cors_allowed_origins: Vec::new(),
         };
 
         // Set environment variable for the mock Claude binary
@@ -1338,6 +3658,7 @@ exit 1
                 &working_dir,
                 false,
                 vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+                SessionOptions::default(),
             )
             .await;
 
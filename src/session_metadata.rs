@@ -0,0 +1,356 @@
+use crate::error::{OrchestratorError, OrchestratorResult};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+
+const METADATA_FILE: &str = "session_metadata.json";
+
+/// Most tags a single session may have.
+pub const MAX_TAGS_PER_SESSION: usize = 32;
+
+/// Most metadata entries a single session may have.
+pub const MAX_METADATA_ENTRIES: usize = 64;
+
+/// Longest tag or metadata key accepted, in characters.
+pub const MAX_KEY_LEN: usize = 64;
+
+/// Longest metadata value accepted, in characters.
+pub const MAX_VALUE_LEN: usize = 1024;
+
+/// Tags and key/value metadata users attached to a session to organize it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct SessionMetadata {
+    /// Sorted, without duplicates
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl SessionMetadata {
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.metadata.is_empty()
+    }
+}
+
+/// Changes to a session's tags and metadata. Tags are replaced by `tags` if given, then
+/// `remove_tags` and `add_tags` apply; `metadata` is merged in, with `null` removing a key.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct SessionMetadataPatch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub add_tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remove_tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, Option<String>>,
+}
+
+/// A tag, or metadata key, that is short and free of whitespace and commas, so it can be
+/// passed in a query string and listed as is.
+fn check_name(kind: &str, name: &str) -> OrchestratorResult<()> {
+    if name.is_empty() {
+        return Err(OrchestratorError::InvalidRequest(format!(
+            "{kind} must not be empty"
+        )));
+    }
+    if name.chars().count() > MAX_KEY_LEN {
+        return Err(OrchestratorError::InvalidRequest(format!(
+            "{kind} {name} is longer than {MAX_KEY_LEN} characters"
+        )));
+    }
+    if name
+        .chars()
+        .any(|c| c.is_whitespace() || c.is_control() || c == ',')
+    {
+        return Err(OrchestratorError::InvalidRequest(format!(
+            "{kind} {name} must not contain whitespace or commas"
+        )));
+    }
+    Ok(())
+}
+
+impl SessionMetadataPatch {
+    /// `current` with the patch applied.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a tag, key or value is invalid, or the session would end up
+    /// with too many tags or entries.
+    pub fn apply(&self, current: &SessionMetadata) -> OrchestratorResult<SessionMetadata> {
+        let mut updated = current.clone();
+        if let Some(tags) = &self.tags {
+            updated.tags.clone_from(tags);
+        }
+        updated.tags.retain(|tag| !self.remove_tags.contains(tag));
+        updated.tags.extend(self.add_tags.iter().cloned());
+        updated.tags.sort();
+        updated.tags.dedup();
+        for tag in &updated.tags {
+            check_name("Tag", tag)?;
+        }
+        if updated.tags.len() > MAX_TAGS_PER_SESSION {
+            return Err(OrchestratorError::InvalidRequest(format!(
+                "Sessions are limited to {MAX_TAGS_PER_SESSION} tags"
+            )));
+        }
+
+        for (key, value) in &self.metadata {
+            match value {
+                Some(value) => {
+                    check_name("Metadata key", key)?;
+                    if value.chars().count() > MAX_VALUE_LEN {
+                        return Err(OrchestratorError::InvalidRequest(format!(
+                            "Metadata value of {key} is longer than {MAX_VALUE_LEN} characters"
+                        )));
+                    }
+                    updated.metadata.insert(key.clone(), value.clone());
+                }
+                None => {
+                    updated.metadata.remove(key);
+                }
+            }
+        }
+        if updated.metadata.len() > MAX_METADATA_ENTRIES {
+            return Err(OrchestratorError::InvalidRequest(format!(
+                "Sessions are limited to {MAX_METADATA_ENTRIES} metadata entries"
+            )));
+        }
+        Ok(updated)
+    }
+}
+
+/// Tags and metadata of every session, kept apart from Claude's transcripts.
+#[derive(Debug, Default)]
+pub struct SessionMetadataStore {
+    /// Session ID -> its tags and metadata; sessions without any are left out
+    sessions: DashMap<String, SessionMetadata>,
+    /// Where metadata is persisted, if a session state directory is configured
+    path: Option<PathBuf>,
+}
+
+impl SessionMetadataStore {
+    /// Loads the metadata persisted in `state_dir`, or starts empty.
+    #[must_use]
+    pub fn load(state_dir: Option<&Path>) -> Self {
+        let path = state_dir.map(|dir| dir.join(METADATA_FILE));
+        let mut sessions = DashMap::new();
+
+        if let Some(path) = &path {
+            match std::fs::read(path) {
+                Ok(bytes) => {
+                    match serde_json::from_slice::<HashMap<String, SessionMetadata>>(&bytes) {
+                        Ok(map) => sessions.extend(map),
+                        Err(e) => warn!(
+                            path = %path.display(),
+                            error = %e,
+                            "Ignoring unreadable session metadata file"
+                        ),
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!(
+                    path = %path.display(),
+                    error = %e,
+                    "Failed to read session metadata file"
+                ),
+            }
+        }
+
+        Self { sessions, path }
+    }
+
+    /// The tags and metadata of a session; empty if it has none.
+    #[must_use]
+    pub fn get(&self, session_id: &str) -> SessionMetadata {
+        self.sessions
+            .get(session_id)
+            .map(|metadata| metadata.clone())
+            .unwrap_or_default()
+    }
+
+    /// Applies `patch` to a session's tags and metadata and returns the result.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the patch is invalid; nothing changes then.
+    pub fn update(
+        &self,
+        session_id: &str,
+        patch: &SessionMetadataPatch,
+    ) -> OrchestratorResult<SessionMetadata> {
+        let updated = {
+            let mut entry = self.sessions.entry(session_id.to_string()).or_default();
+            patch
+                .apply(&entry)
+                .inspect(|updated| entry.clone_from(updated))
+        };
+        self.sessions
+            .remove_if(session_id, |_, metadata| metadata.is_empty());
+        let updated = updated?;
+        debug!(
+            session_id = %session_id,
+            tags = updated.tags.len(),
+            entries = updated.metadata.len(),
+            "Updated session metadata"
+        );
+        self.persist();
+        Ok(updated)
+    }
+
+    /// Gives a session resumed under a new ID the tags and metadata of the one it
+    /// continues. The original keeps its own.
+    pub fn copy_session(&self, old_session_id: &str, new_session_id: &str) {
+        let metadata = self.get(old_session_id);
+        if metadata.is_empty() {
+            return;
+        }
+        self.sessions.insert(new_session_id.to_string(), metadata);
+        self.persist();
+    }
+
+    fn persist(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let snapshot: HashMap<String, SessionMetadata> = self
+            .sessions
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        let tmp_path = path.with_extension("json.tmp");
+        let result = serde_json::to_vec(&snapshot)
+            .map_err(std::io::Error::from)
+            .and_then(|bytes| std::fs::write(&tmp_path, bytes))
+            .and_then(|()| std::fs::rename(&tmp_path, path));
+        if let Err(e) = result {
+            warn!(path = %path.display(), error = %e, "Failed to persist session metadata");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_metadata_patches_round_trip() {
+        let state_dir = TempDir::new().unwrap();
+        let store = SessionMetadataStore::load(Some(state_dir.path()));
+
+        let updated = store
+            .update(
+                "s1",
+                &SessionMetadataPatch {
+                    add_tags: tags(&["bugfix", "backend"]),
+                    metadata: BTreeMap::from([
+                        ("ticket".to_string(), Some("PROJ-42".to_string())),
+                        ("project".to_string(), Some("api".to_string())),
+                    ]),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(updated.tags, tags(&["backend", "bugfix"]));
+
+        store
+            .update(
+                "s1",
+                &SessionMetadataPatch {
+                    remove_tags: tags(&["backend"]),
+                    metadata: BTreeMap::from([("project".to_string(), None)]),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        store.copy_session("s1", "s2");
+
+        let reloaded = SessionMetadataStore::load(Some(state_dir.path()));
+        for session_id in ["s1", "s2"] {
+            let metadata = reloaded.get(session_id);
+            assert_eq!(metadata.tags, tags(&["bugfix"]));
+            assert_eq!(
+                metadata.metadata,
+                BTreeMap::from([("ticket".to_string(), "PROJ-42".to_string())])
+            );
+        }
+
+        // Replacing every tag and removing every entry forgets the session
+        reloaded
+            .update(
+                "s2",
+                &SessionMetadataPatch {
+                    tags: Some(Vec::new()),
+                    metadata: BTreeMap::from([("ticket".to_string(), None)]),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert!(!reloaded.sessions.contains_key("s2"));
+    }
+
+    #[test]
+    fn test_invalid_patches_change_nothing() {
+        let store = SessionMetadataStore::load(None);
+        store
+            .update(
+                "s1",
+                &SessionMetadataPatch {
+                    add_tags: tags(&["keep"]),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        for patch in [
+            SessionMetadataPatch {
+                add_tags: tags(&["two words"]),
+                ..Default::default()
+            },
+            SessionMetadataPatch {
+                add_tags: tags(&["a,b"]),
+                ..Default::default()
+            },
+            SessionMetadataPatch {
+                tags: Some(tags(&[""])),
+                ..Default::default()
+            },
+            SessionMetadataPatch {
+                add_tags: (0..=MAX_TAGS_PER_SESSION)
+                    .map(|i| format!("t{i}"))
+                    .collect(),
+                ..Default::default()
+            },
+            SessionMetadataPatch {
+                metadata: BTreeMap::from([(
+                    "notes".to_string(),
+                    Some("x".repeat(MAX_VALUE_LEN + 1)),
+                )]),
+                ..Default::default()
+            },
+        ] {
+            assert!(matches!(
+                store.update("s1", &patch),
+                Err(OrchestratorError::InvalidRequest(_))
+            ));
+        }
+        assert_eq!(store.get("s1").tags, tags(&["keep"]));
+
+        // A session whose first patch fails is not remembered
+        let patch = SessionMetadataPatch {
+            add_tags: tags(&["two words"]),
+            ..Default::default()
+        };
+        assert!(store.update("s2", &patch).is_err());
+        assert!(!store.sessions.contains_key("s2"));
+    }
+}
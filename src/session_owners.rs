@@ -0,0 +1,102 @@
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+const OWNERS_FILE: &str = "session_owners.json";
+
+/// Records which user each session started through the server belongs to, so its
+/// transcript stays theirs after the session ended.
+#[derive(Debug, Default)]
+pub struct SessionOwners {
+    /// Session ID -> identity of its owner
+    owners: DashMap<String, String>,
+    /// Where the mapping is persisted, if a session state directory is configured
+    path: Option<PathBuf>,
+}
+
+impl SessionOwners {
+    /// Loads the owners persisted in `state_dir`, or starts empty.
+    #[must_use]
+    pub fn load(state_dir: Option<&Path>) -> Self {
+        let path = state_dir.map(|dir| dir.join(OWNERS_FILE));
+        let mut owners = DashMap::new();
+
+        if let Some(path) = &path {
+            match std::fs::read(path) {
+                Ok(bytes) => match serde_json::from_slice::<HashMap<String, String>>(&bytes) {
+                    Ok(map) => owners.extend(map),
+                    Err(e) => warn!(
+                        path = %path.display(),
+                        error = %e,
+                        "Ignoring unreadable session owners file"
+                    ),
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!(
+                    path = %path.display(),
+                    error = %e,
+                    "Failed to read session owners file"
+                ),
+            }
+        }
+
+        Self { owners, path }
+    }
+
+    /// Remembers that `session_id` belongs to `owner`.
+    pub fn record(&self, session_id: &str, owner: &str) {
+        if self.owner_of(session_id).as_deref() == Some(owner) {
+            return;
+        }
+        self.owners
+            .insert(session_id.to_string(), owner.to_string());
+        debug!(session_id = %session_id, owner = %owner, "Recorded session owner");
+        self.persist();
+    }
+
+    /// The user `session_id` belongs to, if it was started through the server.
+    #[must_use]
+    pub fn owner_of(&self, session_id: &str) -> Option<String> {
+        self.owners.get(session_id).map(|owner| owner.clone())
+    }
+
+    fn persist(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let snapshot: HashMap<String, String> = self
+            .owners
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        let tmp_path = path.with_extension("json.tmp");
+        let result = serde_json::to_vec(&snapshot)
+            .map_err(std::io::Error::from)
+            .and_then(|bytes| std::fs::write(&tmp_path, bytes))
+            .and_then(|()| std::fs::rename(&tmp_path, path));
+        if let Err(e) = result {
+            warn!(path = %path.display(), error = %e, "Failed to persist session owners");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_owners_round_trip() {
+        let state_dir = TempDir::new().unwrap();
+
+        let owners = SessionOwners::load(Some(state_dir.path()));
+        owners.record("a", "alice");
+        owners.record("b", "bob");
+
+        let reloaded = SessionOwners::load(Some(state_dir.path()));
+        assert_eq!(reloaded.owner_of("a").as_deref(), Some("alice"));
+        assert_eq!(reloaded.owner_of("b").as_deref(), Some("bob"));
+        assert_eq!(reloaded.owner_of("c"), None);
+    }
+}
@@ -0,0 +1,581 @@
+use crate::config::SlackSettings;
+use crate::models::{ApprovalMessage, ApprovalRequest, DECIDED_BY_FIELD};
+use axum::http::HeaderMap;
+use dashmap::DashMap;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, RwLock};
+use tracing::{debug, warn};
+
+const API_BASE: &str = "https://slack.com/api";
+
+/// `action_id` of the Allow button.
+pub const ALLOW_ACTION: &str = "approval_allow";
+
+/// `action_id` of the Deny button.
+pub const DENY_ACTION: &str = "approval_deny";
+
+/// Callbacks signed longer ago than this are refused, so captured ones cannot be replayed.
+const MAX_CALLBACK_AGE: Duration = Duration::from_secs(5 * 60);
+
+/// Tool input shown in a message is cut to this many characters; Slack refuses
+/// sections over 3000.
+const MAX_INPUT_CHARS: usize = 2500;
+
+/// Identities of Slack users are their user name with this prefix, e.g. `slack:alice`.
+pub const IDENTITY_PREFIX: &str = "slack:";
+
+/// A message posted for an approval request, kept to update it once it is decided.
+#[derive(Debug, Clone)]
+struct PostedMessage {
+    channel: String,
+    ts: String,
+    /// The blocks of the message without its buttons
+    blocks: Vec<Value>,
+}
+
+/// Posts approval requests to a Slack channel with Allow and Deny buttons, and marks
+/// them with the decision once one is made.
+pub struct SlackApprovals {
+    settings: SlackSettings,
+    api_base: String,
+    client: reqwest::Client,
+    /// Approval ID -> the message the request was posted as
+    messages: DashMap<String, PostedMessage>,
+}
+
+/// What a button press asks for, from the interaction payload Slack sends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ButtonPress {
+    pub session_id: String,
+    pub approval_id: String,
+    pub allow: bool,
+    /// Identity the decision is made as, see [`IDENTITY_PREFIX`]
+    pub decided_by: String,
+    /// Where messages only the pressing user sees can be sent
+    pub response_url: Option<String>,
+}
+
+/// Value of both buttons, naming the request they answer.
+#[derive(Debug, serde::Serialize, Deserialize)]
+struct ButtonValue {
+    session_id: String,
+    approval_id: String,
+}
+
+#[derive(Deserialize)]
+struct InteractionPayload {
+    user: InteractionUser,
+    #[serde(default)]
+    actions: Vec<InteractionAction>,
+    response_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct InteractionUser {
+    id: String,
+    username: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct InteractionAction {
+    action_id: String,
+    value: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct InteractionForm {
+    payload: String,
+}
+
+impl SlackApprovals {
+    #[must_use]
+    pub fn new(settings: SlackSettings) -> Self {
+        Self {
+            settings,
+            api_base: API_BASE.to_string(),
+            client: reqwest::Client::new(),
+            messages: DashMap::new(),
+        }
+    }
+
+    /// Calls the Web API at `api_base` instead of Slack's, for tests.
+    #[allow(dead_code)] // Used by tests
+    #[must_use]
+    pub fn with_api_base(mut self, api_base: impl Into<String>) -> Self {
+        self.api_base = api_base.into();
+        self
+    }
+
+    /// Whether `body` was sent by Slack, going by the signature headers of the request,
+    /// and recently enough as of `now`.
+    #[must_use]
+    pub fn verify(&self, headers: &HeaderMap, body: &[u8], now: SystemTime) -> bool {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        let (Some(timestamp), Some(signature)) = (
+            header("x-slack-request-timestamp"),
+            header("x-slack-signature"),
+        ) else {
+            return false;
+        };
+        let Ok(sent) = timestamp.parse::<u64>() else {
+            return false;
+        };
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        if now.abs_diff(sent) > MAX_CALLBACK_AGE.as_secs() {
+            return false;
+        }
+        let Some(tag) = signature.strip_prefix("v0=").and_then(decode_hex) else {
+            return false;
+        };
+        let key = ring::hmac::Key::new(
+            ring::hmac::HMAC_SHA256,
+            self.settings.signing_secret.as_bytes(),
+        );
+        let signed = [format!("v0:{timestamp}:").as_bytes(), body].concat();
+        ring::hmac::verify(&key, &signed, &tag).is_ok()
+    }
+
+    /// Posts `request` with its buttons.
+    async fn post_request(
+        &self,
+        session_id: &str,
+        working_directory: &std::path::Path,
+        request: &ApprovalRequest,
+    ) {
+        let blocks = request_blocks(session_id, working_directory, request);
+        let value = serde_json::to_string(&ButtonValue {
+            session_id: session_id.to_string(),
+            approval_id: request.id.clone(),
+        })
+        .unwrap_or_default();
+        let buttons = json!({
+            "type": "actions",
+            "elements": [
+                {
+                    "type": "button",
+                    "text": {"type": "plain_text", "text": "Allow"},
+                    "style": "primary",
+                    "action_id": ALLOW_ACTION,
+                    "value": value,
+                },
+                {
+                    "type": "button",
+                    "text": {"type": "plain_text", "text": "Deny"},
+                    "style": "danger",
+                    "action_id": DENY_ACTION,
+                    "value": value,
+                },
+            ],
+        });
+        let message = json!({
+            "channel": self.settings.channel,
+            "text": format!("Claude wants to use {}", tool_name(request)),
+            "blocks": blocks.iter().chain([&buttons]).collect::<Vec<_>>(),
+        });
+        match self.call("chat.postMessage", &message).await {
+            Ok(posted) => {
+                let (Some(channel), Some(ts)) = (
+                    posted.get("channel").and_then(Value::as_str),
+                    posted.get("ts").and_then(Value::as_str),
+                ) else {
+                    return;
+                };
+                self.messages.insert(
+                    request.id.clone(),
+                    PostedMessage {
+                        channel: channel.to_string(),
+                        ts: ts.to_string(),
+                        blocks,
+                    },
+                );
+            }
+            Err(e) => warn!(
+                approval_id = %request.id,
+                error = %e,
+                "Failed to post approval request to Slack"
+            ),
+        }
+    }
+
+    /// Replaces the buttons of the message posted for `approval_id` with the decision.
+    async fn mark_decided(&self, approval_id: &str, decision: &str) {
+        let Some((_, posted)) = self.messages.remove(approval_id) else {
+            return;
+        };
+        let context = json!({
+            "type": "context",
+            "elements": [{"type": "mrkdwn", "text": escape(decision)}],
+        });
+        let message = json!({
+            "channel": posted.channel,
+            "ts": posted.ts,
+            "text": decision,
+            "blocks": posted.blocks.iter().chain([&context]).collect::<Vec<_>>(),
+        });
+        if let Err(e) = self.call("chat.update", &message).await {
+            warn!(approval_id = %approval_id, error = %e, "Failed to update Slack approval message");
+        }
+    }
+
+    /// Tells the user who pressed a button why nothing happened.
+    pub async fn reply_privately(&self, response_url: &str, text: &str) {
+        let reply = json!({
+            "response_type": "ephemeral",
+            "replace_original": false,
+            "text": text,
+        });
+        if let Err(e) = self
+            .client
+            .post(response_url)
+            .timeout(crate::notifications::DELIVERY_TIMEOUT)
+            .json(&reply)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+        {
+            debug!(error = %e, "Failed to reply to Slack user");
+        }
+    }
+
+    /// Calls the Web API method `method`, returning its response when Slack reports `ok`.
+    async fn call(&self, method: &str, body: &Value) -> Result<Value, String> {
+        let response: Value = self
+            .client
+            .post(format!("{}/{method}", self.api_base))
+            .bearer_auth(&self.settings.bot_token)
+            .timeout(crate::notifications::DELIVERY_TIMEOUT)
+            .json(body)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| e.to_string())?
+            .json()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.get("ok").and_then(Value::as_bool) == Some(true) {
+            Ok(response)
+        } else {
+            Err(response
+                .get("error")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error")
+                .to_string())
+        }
+    }
+}
+
+/// The button press in an interaction callback body (`payload=<json>`, form encoded).
+/// Interactions that are not a press of one of the approval buttons give `None`.
+#[must_use]
+pub fn parse_button_press(body: &[u8]) -> Option<ButtonPress> {
+    let form: InteractionForm = serde_urlencoded::from_bytes(body).ok()?;
+    let payload: InteractionPayload = serde_json::from_str(&form.payload).ok()?;
+    let action = payload
+        .actions
+        .iter()
+        .find(|action| action.action_id == ALLOW_ACTION || action.action_id == DENY_ACTION)?;
+    let value: ButtonValue = serde_json::from_str(action.value.as_deref()?).ok()?;
+    let user = payload.user.username.unwrap_or(payload.user.id);
+    Some(ButtonPress {
+        session_id: value.session_id,
+        approval_id: value.approval_id,
+        allow: action.action_id == ALLOW_ACTION,
+        decided_by: format!("{IDENTITY_PREFIX}{user}"),
+        response_url: payload.response_url,
+    })
+}
+
+/// The approval response a button press stands for. Allowing passes the tool input on
+/// unchanged, as Claude expects `updatedInput` with every allow.
+#[must_use]
+pub fn approval_response(press: &ButtonPress, request: &ApprovalRequest) -> Value {
+    if press.allow {
+        json!({
+            "behavior": "allow",
+            "updatedInput": request.request.get("input").cloned().unwrap_or_else(|| json!({})),
+        })
+    } else {
+        json!({
+            "behavior": "deny",
+            "message": format!("Denied from Slack by {}", press.decided_by.trim_start_matches(IDENTITY_PREFIX)),
+        })
+    }
+}
+
+/// Posts a session's approval requests to Slack and marks them once decided, however
+/// that happens.
+///
+/// Runs until the session's approval channel closes. The session ID is read on every
+/// request since it changes when a resumed session settles on its new ID.
+pub async fn follow_session(
+    slack: Arc<SlackApprovals>,
+    session_id: Arc<RwLock<String>>,
+    working_directory: PathBuf,
+    mut approvals: broadcast::Receiver<ApprovalMessage>,
+) {
+    loop {
+        match approvals.recv().await {
+            Ok(ApprovalMessage::ApprovalRequest { request, .. }) => {
+                // Restored approvals are broadcast again, they only need posting once
+                if slack.messages.contains_key(&request.id) {
+                    continue;
+                }
+                let session_id = session_id.read().await.clone();
+                slack
+                    .post_request(&session_id, &working_directory, &request)
+                    .await;
+            }
+            Ok(ApprovalMessage::ApprovalResponse(response)) => {
+                let Some(approval_id) = response.get("id").and_then(Value::as_str) else {
+                    continue;
+                };
+                let behavior = response
+                    .get("response")
+                    .and_then(|r| r.get("behavior"))
+                    .and_then(Value::as_str);
+                let verb = if behavior == Some("allow") {
+                    "Allowed"
+                } else {
+                    "Denied"
+                };
+                let decision = match response.get(DECIDED_BY_FIELD).and_then(Value::as_str) {
+                    Some(decided_by) => format!("{verb} by {decided_by}"),
+                    None => verb.to_string(),
+                };
+                slack.mark_decided(approval_id, &decision).await;
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => {}
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+fn tool_name(request: &ApprovalRequest) -> &str {
+    request
+        .request
+        .get("tool_name")
+        .and_then(Value::as_str)
+        .unwrap_or("a tool")
+}
+
+/// Blocks describing `request`: tool, risk and its reasons, session and input.
+fn request_blocks(
+    session_id: &str,
+    working_directory: &std::path::Path,
+    request: &ApprovalRequest,
+) -> Vec<Value> {
+    let mut input = request
+        .request
+        .get("input")
+        .map(|input| serde_json::to_string_pretty(input).unwrap_or_default())
+        .unwrap_or_default();
+    if let Some((cut, _)) = input.char_indices().nth(MAX_INPUT_CHARS) {
+        input.truncate(cut);
+        input.push('…');
+    }
+    let reasons: String = request
+        .risk_reasons
+        .iter()
+        .flat_map(|reason| ["\n• ".to_string(), escape(reason)])
+        .collect();
+    vec![
+        json!({
+            "type": "section",
+            "text": {
+                "type": "mrkdwn",
+                "text": format!(
+                    "*Claude wants to use {}* (risk: {}){reasons}\nSession `{}` in `{}`",
+                    escape(tool_name(request)),
+                    request.risk,
+                    escape(session_id),
+                    escape(&working_directory.display().to_string()),
+                ),
+            },
+        }),
+        json!({
+            "type": "section",
+            "text": {"type": "mrkdwn", "text": format!("```{}```", escape(&input))},
+        }),
+    ]
+}
+
+/// Escapes the characters Slack treats as markup in message text.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use std::fmt::Write;
+
+    fn slack() -> SlackApprovals {
+        SlackApprovals::new(SlackSettings {
+            bot_token: "xoxb-test".to_string(),
+            channel: "C123".to_string(),
+            signing_secret: "8f742231b10e8888abcd99yyyzzz85a5".to_string(),
+        })
+    }
+
+    fn signed_headers(body: &[u8], timestamp: u64, secret: &str) -> HeaderMap {
+        let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes());
+        let tag = ring::hmac::sign(
+            &key,
+            &[format!("v0:{timestamp}:").as_bytes(), body].concat(),
+        );
+        let signature = tag
+            .as_ref()
+            .iter()
+            .fold(String::from("v0="), |mut hex, byte| {
+                let _ = write!(hex, "{byte:02x}");
+                hex
+            });
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-slack-request-timestamp",
+            HeaderValue::from_str(&timestamp.to_string()).unwrap(),
+        );
+        headers.insert(
+            "x-slack-signature",
+            HeaderValue::from_str(&signature).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_verify() {
+        let slack = slack();
+        let body = b"payload=%7B%7D";
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let sent = 1_700_000_000 - 10;
+
+        let headers = signed_headers(body, sent, &slack.settings.signing_secret);
+        assert!(slack.verify(&headers, body, now));
+        // Tampered body, wrong secret, stale timestamp, no headers
+        assert!(!slack.verify(&headers, b"payload=%7B%7D%20", now));
+        assert!(!slack.verify(&signed_headers(body, sent, "other"), body, now));
+        assert!(!slack.verify(&headers, body, now + Duration::from_secs(60 * 60)));
+        assert!(!slack.verify(&HeaderMap::new(), body, now));
+    }
+
+    #[test]
+    fn test_parse_button_press() {
+        let payload = json!({
+            "type": "block_actions",
+            "user": {"id": "U1", "username": "alice"},
+            "response_url": "https://hooks.slack.com/actions/1",
+            "actions": [{
+                "action_id": DENY_ACTION,
+                "value": "{\"session_id\":\"s1\",\"approval_id\":\"a1\"}",
+            }],
+        });
+        let body = serde_urlencoded::to_string([("payload", payload.to_string())]).unwrap();
+        assert_eq!(
+            parse_button_press(body.as_bytes()),
+            Some(ButtonPress {
+                session_id: "s1".to_string(),
+                approval_id: "a1".to_string(),
+                allow: false,
+                decided_by: "slack:alice".to_string(),
+                response_url: Some("https://hooks.slack.com/actions/1".to_string()),
+            })
+        );
+
+        let other = json!({"user": {"id": "U1"}, "actions": [{"action_id": "other"}]});
+        let body = serde_urlencoded::to_string([("payload", other.to_string())]).unwrap();
+        assert_eq!(parse_button_press(body.as_bytes()), None);
+        assert_eq!(parse_button_press(b"not a form"), None);
+    }
+
+    #[tokio::test]
+    async fn test_follow_session_posts_and_updates_requests() {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let record = |calls: Arc<std::sync::Mutex<Vec<(String, Value)>>>, method: &'static str| {
+            move |axum::Json(body): axum::Json<Value>| async move {
+                calls.lock().unwrap().push((method.to_string(), body));
+                axum::Json(json!({"ok": true, "channel": "C123", "ts": "1700000000.000100"}))
+            }
+        };
+        let app = axum::Router::new()
+            .route(
+                "/chat.postMessage",
+                axum::routing::post(record(calls.clone(), "chat.postMessage")),
+            )
+            .route(
+                "/chat.update",
+                axum::routing::post(record(calls.clone(), "chat.update")),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let api_base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let slack = Arc::new(slack().with_api_base(api_base));
+        let (approvals, rx) = broadcast::channel(16);
+        let follow = tokio::spawn(follow_session(
+            slack,
+            Arc::new(RwLock::new("s1".to_string())),
+            PathBuf::from("/work"),
+            rx,
+        ));
+        let request = ApprovalRequest {
+            id: "a1".to_string(),
+            session_id: "s1".to_string(),
+            claude_request_id: "c1".to_string(),
+            request: json!({"tool_name": "Bash", "input": {"command": "make <all>"}}),
+            created_at: SystemTime::now(),
+            clarifications: Vec::new(),
+            risk: crate::approval_risk::RiskLevel::SystemWrite,
+            risk_reasons: Vec::new(),
+            priority: crate::approval_priority::Priority::default(),
+        };
+        approvals
+            .send(ApprovalMessage::request(request.clone()))
+            .unwrap();
+        // Restored requests are broadcast again
+        approvals.send(ApprovalMessage::request(request)).unwrap();
+        approvals
+            .send(ApprovalMessage::response(
+                json!({"id": "a1", "response": {"behavior": "allow", "updatedInput": {}}}),
+                "slack:alice",
+            ))
+            .unwrap();
+        drop(approvals);
+        follow.await.unwrap();
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        let (method, posted) = &calls[0];
+        assert_eq!(method, "chat.postMessage");
+        assert_eq!(posted["channel"], "C123");
+        let blocks = posted["blocks"].as_array().unwrap();
+        assert!(blocks[1]["text"]["text"]
+            .as_str()
+            .unwrap()
+            .contains("make &lt;all&gt;"));
+        assert_eq!(blocks[2]["elements"][0]["action_id"], ALLOW_ACTION);
+
+        let (method, updated) = &calls[1];
+        assert_eq!(method, "chat.update");
+        assert_eq!(updated["ts"], "1700000000.000100");
+        let blocks = updated["blocks"].as_array().unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[2]["elements"][0]["text"], "Allowed by slack:alice");
+    }
+}
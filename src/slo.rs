@@ -0,0 +1,318 @@
+use crate::config::SloSettings;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+/// Most samples kept per operation; older ones go first even inside the window.
+const MAX_SAMPLES: usize = 10_000;
+
+/// Operations whose latency the SLO report covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    /// Starting Claude on the backend, until it takes input
+    SessionSpawn,
+    /// Waiting for a new session's transcript to show up on disk
+    JournalReady,
+    /// From Claude asking for an approval until an answer is forwarded
+    ApprovalRoundTrip,
+    /// From reading a line of Claude's output until it is queued for a client
+    BroadcastFanOut,
+}
+
+impl Operation {
+    pub const ALL: [Self; 4] = [
+        Self::SessionSpawn,
+        Self::JournalReady,
+        Self::ApprovalRoundTrip,
+        Self::BroadcastFanOut,
+    ];
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::SessionSpawn => "session_spawn",
+            Self::JournalReady => "journal_ready",
+            Self::ApprovalRoundTrip => "approval_round_trip",
+            Self::BroadcastFanOut => "broadcast_fan_out",
+        }
+    }
+
+    const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Operation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|operation| operation.name() == s)
+            .ok_or_else(|| anyhow::anyhow!("unknown operation '{s}'"))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: Instant,
+    latency: Duration,
+    ok: bool,
+}
+
+#[derive(Debug, Default)]
+struct Series {
+    samples: VecDeque<Sample>,
+    /// Since startup, unlike the windowed counts of the report
+    total_breaches: u64,
+    total_errors: u64,
+}
+
+impl Series {
+    fn prune(&mut self, window: Duration, now: Instant) {
+        while self
+            .samples
+            .front()
+            .is_some_and(|sample| now.duration_since(sample.at) > window)
+        {
+            self.samples.pop_front();
+        }
+    }
+}
+
+/// Latency and outcome of one operation over the report window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct OperationReport {
+    pub operation: Operation,
+    /// Runs in the window, failed ones included
+    pub samples: usize,
+    pub errors: usize,
+    /// Share of runs that succeeded; absent without samples
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub availability: Option<f64>,
+    /// Percentiles of successful runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p50_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p95_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p99_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_ms: Option<u64>,
+    /// Successful runs in the window slower than the target
+    pub breaches: usize,
+    pub total_breaches: u64,
+    pub total_errors: u64,
+}
+
+/// Answer of `GET /api/v1/admin/slo`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SloReport {
+    pub window_secs: u64,
+    pub operations: Vec<OperationReport>,
+}
+
+/// Rolling latency samples of the operations in [`Operation`], checked against the
+/// configured targets.
+#[derive(Debug)]
+pub struct SloTracker {
+    settings: SloSettings,
+    series: [Mutex<Series>; Operation::ALL.len()],
+}
+
+impl SloTracker {
+    #[must_use]
+    pub fn new(settings: SloSettings) -> Self {
+        Self {
+            settings,
+            series: Default::default(),
+        }
+    }
+
+    /// Records a successful run of `operation` that took `latency`
+    pub fn record(&self, operation: Operation, latency: Duration) {
+        self.push(operation, latency, true);
+    }
+
+    /// Records a failed run of `operation`
+    pub fn record_error(&self, operation: Operation, latency: Duration) {
+        self.push(operation, latency, false);
+    }
+
+    fn push(&self, operation: Operation, latency: Duration, ok: bool) {
+        let breach = ok
+            && self
+                .settings
+                .targets
+                .get(&operation)
+                .is_some_and(|target| latency > *target);
+        let now = Instant::now();
+        let mut series = self.series[operation.index()]
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        series.prune(self.settings.window, now);
+        if series.samples.len() == MAX_SAMPLES {
+            series.samples.pop_front();
+        }
+        series.samples.push_back(Sample {
+            at: now,
+            latency,
+            ok,
+        });
+        series.total_breaches += u64::from(breach);
+        series.total_errors += u64::from(!ok);
+    }
+
+    /// Percentiles, availability and breaches of every operation over the window
+    #[must_use]
+    pub fn report(&self) -> SloReport {
+        let now = Instant::now();
+        let operations = Operation::ALL
+            .into_iter()
+            .map(|operation| {
+                let mut series = self.series[operation.index()]
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner);
+                series.prune(self.settings.window, now);
+                self.operation_report(operation, &series)
+            })
+            .collect();
+        SloReport {
+            window_secs: self.settings.window.as_secs(),
+            operations,
+        }
+    }
+
+    fn operation_report(&self, operation: Operation, series: &Series) -> OperationReport {
+        let target = self.settings.targets.get(&operation).copied();
+        let mut latencies: Vec<Duration> = series
+            .samples
+            .iter()
+            .filter(|sample| sample.ok)
+            .map(|sample| sample.latency)
+            .collect();
+        latencies.sort_unstable();
+        let samples = series.samples.len();
+        let errors = samples - latencies.len();
+        #[allow(clippy::cast_precision_loss)]
+        let availability = (samples > 0).then(|| latencies.len() as f64 / samples as f64);
+        OperationReport {
+            operation,
+            samples,
+            errors,
+            availability,
+            p50_ms: percentile(&latencies, 500),
+            p95_ms: percentile(&latencies, 950),
+            p99_ms: percentile(&latencies, 990),
+            max_ms: latencies.last().copied().map(millis),
+            target_ms: target.map(|target| u64::try_from(target.as_millis()).unwrap_or(u64::MAX)),
+            breaches: target.map_or(0, |target| {
+                latencies
+                    .iter()
+                    .filter(|latency| **latency > target)
+                    .count()
+            }),
+            total_breaches: series.total_breaches,
+            total_errors: series.total_errors,
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted `latencies`, `per_mille` of 1000
+fn percentile(latencies: &[Duration], per_mille: usize) -> Option<f64> {
+    let rank = (latencies.len() * per_mille).div_ceil(1000);
+    latencies.get(rank.saturating_sub(1)).copied().map(millis)
+}
+
+fn millis(latency: Duration) -> f64 {
+    latency.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn tracker(window: Duration) -> SloTracker {
+        SloTracker::new(SloSettings {
+            window,
+            targets: HashMap::from([(Operation::SessionSpawn, Duration::from_millis(50))]),
+        })
+    }
+
+    fn operation(report: &SloReport, operation: Operation) -> &OperationReport {
+        report
+            .operations
+            .iter()
+            .find(|report| report.operation == operation)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_percentiles_and_breaches() {
+        let tracker = tracker(Duration::from_secs(60 * 60));
+        for ms in 1..=100 {
+            tracker.record(Operation::SessionSpawn, Duration::from_millis(ms));
+        }
+        tracker.record_error(Operation::SessionSpawn, Duration::from_secs(20));
+
+        let report = tracker.report();
+        assert_eq!(report.window_secs, 3600);
+        let spawn = operation(&report, Operation::SessionSpawn);
+        assert_eq!(spawn.samples, 101);
+        assert_eq!(spawn.errors, 1);
+        assert_eq!(spawn.p50_ms, Some(50.0));
+        assert_eq!(spawn.p95_ms, Some(95.0));
+        assert_eq!(spawn.p99_ms, Some(99.0));
+        assert_eq!(spawn.max_ms, Some(100.0));
+        assert_eq!(spawn.target_ms, Some(50));
+        assert_eq!(spawn.breaches, 50);
+        assert_eq!(spawn.total_breaches, 50);
+        assert_eq!(spawn.total_errors, 1);
+        assert!((spawn.availability.unwrap() - 100.0 / 101.0).abs() < f64::EPSILON);
+
+        // Nothing recorded, nothing to report
+        let journal = operation(&report, Operation::JournalReady);
+        assert_eq!(journal.samples, 0);
+        assert_eq!(journal.p50_ms, None);
+        assert_eq!(journal.availability, None);
+        assert_eq!(journal.target_ms, None);
+    }
+
+    #[test]
+    fn test_window_rolls() {
+        let tracker = tracker(Duration::from_millis(50));
+        tracker.record(Operation::SessionSpawn, Duration::from_millis(80));
+        std::thread::sleep(Duration::from_millis(100));
+        tracker.record(Operation::SessionSpawn, Duration::from_millis(10));
+
+        let report = tracker.report();
+        let spawn = operation(&report, Operation::SessionSpawn);
+        assert_eq!(spawn.samples, 1);
+        assert_eq!(spawn.breaches, 0);
+        // Lifetime counters outlive the window
+        assert_eq!(spawn.total_breaches, 1);
+    }
+
+    #[test]
+    fn test_parse_operation() {
+        assert_eq!(
+            "approval_round_trip".parse::<Operation>().unwrap(),
+            Operation::ApprovalRoundTrip
+        );
+        assert!("spawn".parse::<Operation>().is_err());
+        assert_eq!(Operation::BroadcastFanOut.to_string(), "broadcast_fan_out");
+    }
+}
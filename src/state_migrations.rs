@@ -0,0 +1,318 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Layout version of the session state directory this build reads and writes.
+pub const STATE_VERSION: u32 = 1;
+
+const VERSION_FILE: &str = "state_version.json";
+
+/// Suffix given to state files set aside because they could not be parsed.
+const UNREADABLE_SUFFIX: &str = ".unreadable";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VersionMarker {
+    version: u32,
+    /// Release of chef-de-vibe that last migrated the directory
+    written_by: String,
+}
+
+/// One step between layout versions. Steps must be safe to repeat, since an upgrade
+/// interrupted before the version is recorded runs the step again.
+struct Migration {
+    /// Version the directory has after this step
+    to: u32,
+    description: &'static str,
+    /// Performs the step, or with `dry_run` only works out what it would do; returns
+    /// the changes either way
+    run: fn(&Path, bool) -> Result<Vec<String>>,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    to: 1,
+    description: "remove temporary files of interrupted writes and set aside state files that cannot be parsed",
+    run: tidy_state_files,
+}];
+
+/// A pending migration step and what it changes.
+#[derive(Debug)]
+pub struct PlannedStep {
+    pub to: u32,
+    pub description: &'static str,
+    pub changes: Vec<String>,
+}
+
+/// What starting up would do to a state directory.
+#[derive(Debug)]
+pub struct MigrationPlan {
+    pub dir: PathBuf,
+    /// Layout version the directory is at; 0 for directories from before versioning
+    pub version: u32,
+    pub steps: Vec<PlannedStep>,
+}
+
+impl fmt::Display for MigrationPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: state version {}, this build uses {STATE_VERSION}",
+            self.dir.display(),
+            self.version
+        )?;
+        if self.steps.is_empty() {
+            return write!(f, "\nNo migrations pending");
+        }
+        for step in &self.steps {
+            write!(f, "\nMigration to {}: {}", step.to, step.description)?;
+            if step.changes.is_empty() {
+                write!(f, "\n  nothing to change")?;
+            }
+            for change in &step.changes {
+                write!(f, "\n  {change}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Works out which migrations `dir` needs without changing anything.
+///
+/// # Errors
+///
+/// Returns an error if the directory was written by a newer release, or its version
+/// or files cannot be read.
+pub fn plan(dir: &Path) -> Result<MigrationPlan> {
+    let version = read_version(dir)?;
+    let steps = pending(version)
+        .map(|migration| {
+            Ok(PlannedStep {
+                to: migration.to,
+                description: migration.description,
+                changes: (migration.run)(dir, true)?,
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok(MigrationPlan {
+        dir: dir.to_path_buf(),
+        version,
+        steps,
+    })
+}
+
+/// Describes what starting up would do to the state directory, for `--check-migrations`.
+///
+/// # Errors
+///
+/// Returns an error if starting up would fail on the directory.
+pub fn check(dir: Option<&Path>) -> Result<String> {
+    match dir {
+        Some(dir) => Ok(plan(dir)?.to_string()),
+        None => Ok("SESSION_STATE_DIR is not set, so there is no state to migrate".to_string()),
+    }
+}
+
+/// Brings the state directory to [`STATE_VERSION`], creating it if needed. Runs before
+/// anything reads the directory.
+///
+/// # Errors
+///
+/// Returns an error if the directory was written by a newer release, or a migration
+/// step fails; steps completed before the failure stay recorded.
+pub fn migrate(dir: Option<&Path>) -> Result<()> {
+    let Some(dir) = dir else {
+        return Ok(());
+    };
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create state directory {}", dir.display()))?;
+
+    let version = read_version(dir)?;
+    for migration in pending(version) {
+        let changes = (migration.run)(dir, false)
+            .with_context(|| format!("Failed to migrate state to version {}", migration.to))?;
+        for change in &changes {
+            warn!(dir = %dir.display(), to = migration.to, change = %change, "Migrated state file");
+        }
+        write_version(dir, migration.to)?;
+        info!(
+            dir = %dir.display(),
+            to = migration.to,
+            changes = changes.len(),
+            description = migration.description,
+            "Migrated state directory"
+        );
+    }
+    Ok(())
+}
+
+fn pending(version: u32) -> impl Iterator<Item = &'static Migration> {
+    MIGRATIONS
+        .iter()
+        .filter(move |migration| migration.to > version)
+}
+
+fn read_version(dir: &Path) -> Result<u32> {
+    let path = dir.join(VERSION_FILE);
+    let version = match fs::read(&path) {
+        Ok(bytes) => {
+            serde_json::from_slice::<VersionMarker>(&bytes)
+                .with_context(|| format!("Unreadable state version file {}", path.display()))?
+                .version
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+        }
+    };
+    if version > STATE_VERSION {
+        bail!(
+            "{} has state version {version}, written by a newer chef-de-vibe; this build supports up to {STATE_VERSION}",
+            dir.display()
+        );
+    }
+    Ok(version)
+}
+
+fn write_version(dir: &Path, version: u32) -> Result<()> {
+    let marker = VersionMarker {
+        version,
+        written_by: env!("CARGO_PKG_VERSION").to_string(),
+    };
+    let path = dir.join(VERSION_FILE);
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, serde_json::to_vec(&marker)?)
+        .and_then(|()| fs::rename(&tmp_path, &path))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Files directly in the state directory, in name order.
+fn state_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to list {}", dir.display())),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Version 1: temporary files of writes cut short are removed, JSON files that do not
+/// parse are set aside, and lines of JSON Lines files that do not parse are dropped.
+fn tidy_state_files(dir: &Path, dry_run: bool) -> Result<Vec<String>> {
+    let mut changes = Vec::new();
+    for path in state_files(dir)? {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let aside = dir.join(format!("{name}{UNREADABLE_SUFFIX}"));
+        let extension = path.extension().and_then(|extension| extension.to_str());
+
+        if extension == Some("tmp") {
+            changes.push(format!("remove {name}"));
+            if !dry_run {
+                fs::remove_file(&path)?;
+            }
+        } else if extension == Some("jsonl") {
+            let content = fs::read_to_string(&path)?;
+            let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+            let readable: Vec<&str> = lines
+                .iter()
+                .copied()
+                .filter(|line| serde_json::from_str::<serde_json::Value>(line).is_ok())
+                .collect();
+            if readable.len() == lines.len() {
+                continue;
+            }
+            changes.push(format!(
+                "keep {} of {} lines of {name}, the original goes to {}",
+                readable.len(),
+                lines.len(),
+                aside.display()
+            ));
+            if !dry_run {
+                // The original is kept until the trimmed file has replaced it
+                fs::copy(&path, &aside)?;
+                let mut kept = readable.join("\n");
+                if !kept.is_empty() {
+                    kept.push('\n');
+                }
+                let tmp_path = dir.join(format!("{name}.tmp"));
+                fs::write(&tmp_path, kept)?;
+                fs::rename(&tmp_path, &path)?;
+            }
+        } else if extension == Some("json") && name != VERSION_FILE {
+            let bytes = fs::read(&path)?;
+            if let Err(e) = serde_json::from_slice::<serde_json::Value>(&bytes) {
+                changes.push(format!("move {name} to {} ({e})", aside.display()));
+                if !dry_run {
+                    fs::rename(&path, &aside)?;
+                }
+            }
+        }
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_migrates_unversioned_state() {
+        let state_dir = TempDir::new().unwrap();
+        let dir = state_dir.path();
+        fs::write(dir.join("lineage.json"), r#"{"b": "a"}"#).unwrap();
+        fs::write(dir.join("anchors.json.tmp"), "{").unwrap();
+        fs::write(dir.join("preferences.json"), r#"{"alice": {"#).unwrap();
+        fs::write(
+            dir.join("approval_audit.jsonl"),
+            "{\"approval_id\": \"1\"}\n{\"approval_id\": \"2\"}\n{\"appro",
+        )
+        .unwrap();
+
+        let plan = plan(dir).unwrap();
+        assert_eq!(plan.version, 0);
+        assert_eq!(plan.steps.len(), 1);
+        assert_eq!(plan.steps[0].changes.len(), 3);
+        // Planning changes nothing
+        assert!(dir.join("anchors.json.tmp").exists());
+
+        migrate(Some(dir)).unwrap();
+        assert!(!dir.join("anchors.json.tmp").exists());
+        assert!(!dir.join("preferences.json").exists());
+        assert!(dir.join("preferences.json.unreadable").exists());
+        assert!(dir.join("lineage.json").exists());
+        assert_eq!(
+            fs::read_to_string(dir.join("approval_audit.jsonl"))
+                .unwrap()
+                .lines()
+                .count(),
+            2
+        );
+        assert!(dir.join("approval_audit.jsonl.unreadable").exists());
+
+        let plan = super::plan(dir).unwrap();
+        assert_eq!(plan.version, STATE_VERSION);
+        assert!(plan.steps.is_empty());
+    }
+
+    #[test]
+    fn test_refuses_state_of_newer_release() {
+        let state_dir = TempDir::new().unwrap();
+        write_version(state_dir.path(), STATE_VERSION + 1).unwrap();
+        assert!(migrate(Some(state_dir.path())).is_err());
+        assert!(check(Some(state_dir.path())).is_err());
+        assert!(migrate(None).is_ok());
+    }
+}
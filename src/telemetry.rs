@@ -0,0 +1,252 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+/// How often counters are reported when telemetry is enabled.
+const REPORT_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long a single report may take before it is given up.
+const REPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Session lifecycle events that are counted.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionEvent {
+    Created,
+    Resumed,
+    FailedToStart,
+    Exited,
+}
+
+/// Calls of one API route, by outcome.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RouteCounters {
+    pub requests: u64,
+    /// Responses with a 4xx status
+    pub client_errors: u64,
+    /// Responses with a 5xx status
+    pub server_errors: u64,
+}
+
+/// Everything a telemetry report contains. Routes are keyed by method and route
+/// template (`GET /api/v1/sessions/:id`), never by the concrete path, so no session
+/// IDs, directories or message content end up in it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TelemetryReport {
+    pub version: String,
+    pub os: String,
+    pub arch: String,
+    pub period_start: chrono::DateTime<chrono::Utc>,
+    pub period_end: chrono::DateTime<chrono::Utc>,
+    pub sessions: BTreeMap<SessionEvent, u64>,
+    pub routes: BTreeMap<String, RouteCounters>,
+}
+
+#[derive(Debug)]
+struct Counters {
+    since: chrono::DateTime<chrono::Utc>,
+    sessions: BTreeMap<SessionEvent, u64>,
+    routes: BTreeMap<String, RouteCounters>,
+}
+
+impl Counters {
+    fn new() -> Self {
+        Self {
+            since: chrono::Utc::now(),
+            sessions: BTreeMap::new(),
+            routes: BTreeMap::new(),
+        }
+    }
+}
+
+/// Anonymous usage counters, kept in memory and only sent anywhere when an endpoint
+/// is configured. Counting happens either way so the preview shows what would be sent.
+pub struct Telemetry {
+    endpoint: Option<String>,
+    counters: Mutex<Counters>,
+    client: reqwest::Client,
+}
+
+impl Telemetry {
+    #[must_use]
+    pub fn new(endpoint: Option<String>) -> Self {
+        Self {
+            endpoint,
+            counters: Mutex::new(Counters::new()),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Where reports are sent; `None` means telemetry is off
+    #[must_use]
+    pub fn endpoint(&self) -> Option<&str> {
+        self.endpoint.as_deref()
+    }
+
+    fn counters(&self) -> std::sync::MutexGuard<'_, Counters> {
+        self.counters
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    pub fn record_session(&self, event: SessionEvent) {
+        *self.counters().sessions.entry(event).or_default() += 1;
+    }
+
+    /// Counts a response of the route `route` (method and route template).
+    pub fn record_request(&self, route: String, status: axum::http::StatusCode) {
+        let mut counters = self.counters();
+        let route = counters.routes.entry(route).or_default();
+        route.requests += 1;
+        if status.is_client_error() {
+            route.client_errors += 1;
+        } else if status.is_server_error() {
+            route.server_errors += 1;
+        }
+    }
+
+    /// The report that would be sent right now.
+    #[must_use]
+    pub fn report(&self) -> TelemetryReport {
+        let counters = self.counters();
+        TelemetryReport {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            period_start: counters.since,
+            period_end: chrono::Utc::now(),
+            sessions: counters.sessions.clone(),
+            routes: counters.routes.clone(),
+        }
+    }
+
+    /// Takes what was reported out of the counters, keeping anything counted while
+    /// the report was on its way.
+    fn settle(&self, report: &TelemetryReport) {
+        let mut counters = self.counters();
+        counters.since = report.period_end;
+        for (event, count) in &report.sessions {
+            if let Some(current) = counters.sessions.get_mut(event) {
+                *current -= count;
+            }
+        }
+        counters.sessions.retain(|_, count| *count > 0);
+        for (route, reported) in &report.routes {
+            if let Some(current) = counters.routes.get_mut(route) {
+                current.requests -= reported.requests;
+                current.client_errors -= reported.client_errors;
+                current.server_errors -= reported.server_errors;
+            }
+        }
+        counters.routes.retain(|_, route| route.requests > 0);
+    }
+
+    /// Sends the current report and starts a new period once it was accepted. Failed
+    /// reports are kept and go out with the next one.
+    async fn send_report(&self, endpoint: &str) {
+        let report = self.report();
+        let result = self
+            .client
+            .post(endpoint)
+            .timeout(REPORT_TIMEOUT)
+            .json(&report)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        match result {
+            Ok(_) => {
+                debug!(endpoint = %endpoint, "Telemetry report sent");
+                self.settle(&report);
+            }
+            Err(e) => warn!(endpoint = %endpoint, error = %e, "Failed to send telemetry report"),
+        }
+    }
+
+    /// Starts reporting periodically if an endpoint is configured.
+    #[must_use]
+    pub fn start_reporting(self: &Arc<Self>) -> Option<JoinHandle<()>> {
+        let endpoint = self.endpoint.clone()?;
+        info!(endpoint = %endpoint, interval_secs = REPORT_INTERVAL.as_secs(), "Telemetry enabled");
+        let telemetry = self.clone();
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval_at(
+                tokio::time::Instant::now() + REPORT_INTERVAL,
+                REPORT_INTERVAL,
+            );
+            loop {
+                interval.tick().await;
+                telemetry.send_report(&endpoint).await;
+            }
+        }))
+    }
+}
+
+/// Middleware counting responses per route; must be added with `route_layer` so the
+/// route template is known.
+pub async fn count_requests(
+    State(telemetry): State<Arc<Telemetry>>,
+    route: MatchedPath,
+    request: Request,
+    next: Next,
+) -> Response {
+    let key = format!("{} {}", request.method(), route.as_str());
+    let response = next.run(request).await;
+    telemetry.record_request(key, response.status());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    #[test]
+    fn test_report_and_settle() {
+        let telemetry = Telemetry::new(None);
+        telemetry.record_session(SessionEvent::Created);
+        telemetry.record_session(SessionEvent::Created);
+        telemetry.record_session(SessionEvent::FailedToStart);
+        telemetry.record_request("POST /api/v1/sessions".to_string(), StatusCode::OK);
+        telemetry.record_request("POST /api/v1/sessions".to_string(), StatusCode::NOT_FOUND);
+        telemetry.record_request(
+            "GET /api/v1/sessions/:id".to_string(),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        );
+
+        let report = telemetry.report();
+        assert_eq!(report.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(report.sessions[&SessionEvent::Created], 2);
+        assert_eq!(report.sessions[&SessionEvent::FailedToStart], 1);
+        assert!(!report.sessions.contains_key(&SessionEvent::Resumed));
+        assert_eq!(
+            report.routes["POST /api/v1/sessions"],
+            RouteCounters {
+                requests: 2,
+                client_errors: 1,
+                server_errors: 0,
+            }
+        );
+        assert_eq!(report.routes["GET /api/v1/sessions/:id"].server_errors, 1);
+
+        // Counted while the report was being sent, so it belongs to the next one
+        telemetry.record_session(SessionEvent::Created);
+        telemetry.settle(&report);
+
+        let next = telemetry.report();
+        assert_eq!(next.period_start, report.period_end);
+        assert_eq!(next.sessions.len(), 1);
+        assert_eq!(next.sessions[&SessionEvent::Created], 1);
+        assert!(next.routes.is_empty());
+    }
+}
@@ -0,0 +1,85 @@
+use crate::config::TlsSettings;
+use anyhow::{Context, Result};
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use axum_server::Handle;
+use std::future::Future;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+/// How long open connections may finish after shutdown begins. Unlike `axum::serve`,
+/// connections are not closed once idle, so browsers holding keep-alive connections
+/// would otherwise delay shutdown until they give up on them.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Loads the certificate and key, so unusable ones fail startup instead of every handshake.
+///
+/// # Errors
+///
+/// Returns an error if either file cannot be read or does not hold a usable PEM
+/// certificate chain or private key.
+pub async fn load(settings: &TlsSettings) -> Result<RustlsConfig> {
+    RustlsConfig::from_pem_file(&settings.cert_path, &settings.key_path)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to load TLS certificate {} and key {}",
+                settings.cert_path.display(),
+                settings.key_path.display()
+            )
+        })
+}
+
+/// Serves `app` over TLS until `shutdown` completes, then gives open connections up to
+/// [`DRAIN_TIMEOUT`] to finish.
+///
+/// # Errors
+///
+/// Returns an error if the listener cannot be handed over or the server fails.
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    tls: RustlsConfig,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<()> {
+    let handle = Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        shutdown.await;
+        shutdown_handle.graceful_shutdown(Some(DRAIN_TIMEOUT));
+    });
+
+    axum_server::from_tcp_rustls(listener.into_std()?, tls)
+        .handle(handle)
+        .serve(app.into_make_service())
+        .await?;
+    Ok(())
+}
+
+/// Rereads the certificate and key on every SIGHUP, so renewed certificates take effect
+/// without a restart. A failed reload keeps serving the previous ones.
+///
+/// # Errors
+///
+/// Returns an error if the signal handler cannot be installed.
+pub fn reload_on_hangup(settings: TlsSettings, tls: RustlsConfig) -> Result<()> {
+    let mut hangups = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .context("Failed to listen for SIGHUP")?;
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            match tls
+                .reload_from_pem_file(&settings.cert_path, &settings.key_path)
+                .await
+            {
+                Ok(()) => info!(cert = %settings.cert_path.display(), "Reloaded TLS certificate"),
+                Err(e) => warn!(
+                    cert = %settings.cert_path.display(),
+                    error = %e,
+                    "Failed to reload TLS certificate, keeping the previous one"
+                ),
+            }
+        }
+    });
+    Ok(())
+}
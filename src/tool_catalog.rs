@@ -0,0 +1,388 @@
+use crate::error::{OrchestratorError, OrchestratorResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+use utoipa::ToSchema;
+
+/// Details kept per tool; the rarest ones are dropped first.
+const MAX_DETAILS: usize = 50;
+
+/// Tools whose calls are told apart by the file they touch.
+const FILE_TOOLS: [&str; 5] = ["Read", "Edit", "MultiEdit", "Write", "NotebookEdit"];
+
+/// What tools the sessions of one project have called, most used first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ToolCatalog {
+    pub project_id: String,
+    /// Working directory of the project's sessions, when a transcript records it
+    pub working_directory: Option<String>,
+    pub sessions: usize,
+    pub calls: u64,
+    pub tools: Vec<ToolUsage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ToolUsage {
+    pub name: String,
+    pub calls: u64,
+    /// Sessions that called the tool at least once
+    pub sessions: usize,
+    pub first_used: Option<String>,
+    pub last_used: Option<String>,
+    /// Commands (`Bash`), files (file tools) or patterns (`Glob`, `Grep`), most used first
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub details: Vec<DetailUsage>,
+    /// Distinct details seen, including those cut from `details`
+    #[serde(skip_serializing_if = "is_zero", default)]
+    pub distinct_details: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct DetailUsage {
+    pub value: String,
+    pub calls: u64,
+    pub sessions: usize,
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)] // serde passes fields by reference
+const fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+#[derive(Default)]
+struct Tally {
+    calls: u64,
+    sessions: HashSet<String>,
+}
+
+impl Tally {
+    fn add(&mut self, session_id: &str) {
+        self.calls += 1;
+        if !self.sessions.contains(session_id) {
+            self.sessions.insert(session_id.to_string());
+        }
+    }
+}
+
+#[derive(Default)]
+struct ToolTally {
+    total: Tally,
+    first_used: Option<String>,
+    last_used: Option<String>,
+    details: HashMap<String, Tally>,
+}
+
+impl ToolTally {
+    fn into_usage(self, name: String) -> ToolUsage {
+        let distinct_details = self.details.len();
+        let mut details: Vec<DetailUsage> = self
+            .details
+            .into_iter()
+            .map(|(value, tally)| DetailUsage {
+                value,
+                calls: tally.calls,
+                sessions: tally.sessions.len(),
+            })
+            .collect();
+        details.sort_by(|a, b| b.calls.cmp(&a.calls).then_with(|| a.value.cmp(&b.value)));
+        details.truncate(MAX_DETAILS);
+        ToolUsage {
+            name,
+            calls: self.total.calls,
+            sessions: self.total.sessions.len(),
+            first_used: self.first_used,
+            last_used: self.last_used,
+            details,
+            distinct_details,
+        }
+    }
+}
+
+/// Directory holding the transcripts of project `project_id`, as named under the Claude
+/// projects directory (e.g. `-home-user-project`).
+///
+/// # Errors
+///
+/// Returns `InvalidRequest` for IDs that are not a single path component and
+/// `ProjectNotFound` when there is no such directory.
+pub fn project_dir(projects_dir: &Path, project_id: &str) -> OrchestratorResult<PathBuf> {
+    if project_id.is_empty() || project_id.starts_with('.') || project_id.contains(['/', '\\']) {
+        return Err(OrchestratorError::InvalidRequest(format!(
+            "Invalid project ID: {project_id}"
+        )));
+    }
+    let dir = projects_dir.join(project_id);
+    if dir.is_dir() {
+        Ok(dir)
+    } else {
+        Err(OrchestratorError::ProjectNotFound(project_id.to_string()))
+    }
+}
+
+/// Builds the catalog from every transcript in `dir`, counting only the sessions
+/// `include` accepts. Unreadable transcripts are skipped.
+#[must_use]
+pub fn scan(project_id: &str, dir: &Path, include: impl Fn(&str) -> bool) -> ToolCatalog {
+    let mut tools: HashMap<String, ToolTally> = HashMap::new();
+    let mut sessions = HashSet::new();
+    let mut working_directory = None;
+
+    let transcripts = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("jsonl"));
+    for path in transcripts {
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!(file_path = %path.display(), error = %e, "Failed to open transcript");
+                continue;
+            }
+        };
+        let file_session = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            // Most lines carry no tool call; skip them without parsing
+            if !line.contains("\"tool_use\"") {
+                continue;
+            }
+            let Ok(entry) = serde_json::from_str::<Value>(&line) else {
+                continue;
+            };
+            if entry.get("type").and_then(Value::as_str) != Some("assistant") {
+                continue;
+            }
+            let Some(blocks) = entry.pointer("/message/content").and_then(Value::as_array) else {
+                continue;
+            };
+            let session_id = entry
+                .get("sessionId")
+                .and_then(Value::as_str)
+                .unwrap_or(&file_session);
+            if !include(session_id) {
+                continue;
+            }
+            let timestamp = entry.get("timestamp").and_then(Value::as_str);
+            if working_directory.is_none() {
+                working_directory = entry.get("cwd").and_then(Value::as_str).map(String::from);
+            }
+
+            for block in blocks {
+                if block.get("type").and_then(Value::as_str) != Some("tool_use") {
+                    continue;
+                }
+                let Some(name) = block.get("name").and_then(Value::as_str) else {
+                    continue;
+                };
+                if !sessions.contains(session_id) {
+                    sessions.insert(session_id.to_string());
+                }
+                let tally = tools.entry(name.to_string()).or_default();
+                tally.total.add(session_id);
+                if let Some(timestamp) = timestamp {
+                    if tally.first_used.as_deref().is_none_or(|t| timestamp < t) {
+                        tally.first_used = Some(timestamp.to_string());
+                    }
+                    if tally.last_used.as_deref().is_none_or(|t| timestamp > t) {
+                        tally.last_used = Some(timestamp.to_string());
+                    }
+                }
+                let input = block.get("input").unwrap_or(&Value::Null);
+                if let Some(detail) = detail(name, input, working_directory.as_deref()) {
+                    tally.details.entry(detail).or_default().add(session_id);
+                }
+            }
+        }
+    }
+
+    let mut tools: Vec<ToolUsage> = tools
+        .into_iter()
+        .map(|(name, tally)| tally.into_usage(name))
+        .collect();
+    tools.sort_by(|a, b| b.calls.cmp(&a.calls).then_with(|| a.name.cmp(&b.name)));
+
+    ToolCatalog {
+        project_id: project_id.to_string(),
+        working_directory,
+        sessions: sessions.len(),
+        calls: tools.iter().map(|t| t.calls).sum(),
+        tools,
+    }
+}
+
+/// What tells one call of `tool` apart from another, as far as policies go.
+fn detail(tool: &str, input: &Value, working_dir: Option<&str>) -> Option<String> {
+    let field = |key: &str| input.get(key).and_then(Value::as_str);
+    match tool {
+        "Bash" => field("command").and_then(command_prefix),
+        _ if FILE_TOOLS.contains(&tool) => {
+            let path = field("file_path").or_else(|| field("notebook_path"))?;
+            // Paths inside the project read better relative to it
+            let relative = working_dir
+                .and_then(|dir| Path::new(path).strip_prefix(dir).ok())
+                .filter(|p| !p.as_os_str().is_empty());
+            Some(relative.map_or_else(|| path.to_string(), |p| p.display().to_string()))
+        }
+        "Glob" | "Grep" => field("pattern").map(String::from),
+        _ => None,
+    }
+}
+
+/// The program of a shell command plus its subcommand, if any: `cargo test --workspace`
+/// becomes `cargo test`, `ls -la src` becomes `ls`. Leading variable assignments are
+/// skipped.
+fn command_prefix(command: &str) -> Option<String> {
+    let mut words = command
+        .split_whitespace()
+        .skip_while(|word| word.contains('=') && !word.starts_with('='));
+    let program = words.next()?;
+    let subcommand = words.next().filter(|word| {
+        word.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ':')
+            && !word.starts_with('-')
+    });
+    Some(subcommand.map_or_else(|| program.to_string(), |sub| format!("{program} {sub}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn tool_call(session_id: &str, timestamp: &str, name: &str, input: &Value) -> String {
+        json!({
+            "type": "assistant",
+            "sessionId": session_id,
+            "cwd": "/home/user/project",
+            "timestamp": timestamp,
+            "message": {"content": [
+                {"type": "text", "text": "On it"},
+                {"type": "tool_use", "id": "t", "name": name, "input": input}
+            ]}
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_command_prefix() {
+        assert_eq!(
+            command_prefix("cargo test --workspace").as_deref(),
+            Some("cargo test")
+        );
+        assert_eq!(command_prefix("ls -la src").as_deref(), Some("ls"));
+        assert_eq!(command_prefix("cat src/main.rs").as_deref(), Some("cat"));
+        assert_eq!(
+            command_prefix("RUST_LOG=debug cargo run").as_deref(),
+            Some("cargo run")
+        );
+        assert_eq!(command_prefix("   "), None);
+    }
+
+    #[test]
+    fn test_project_dir() {
+        let projects = TempDir::new().unwrap();
+        std::fs::create_dir(projects.path().join("-home-user-project")).unwrap();
+
+        assert!(project_dir(projects.path(), "-home-user-project").is_ok());
+        assert!(matches!(
+            project_dir(projects.path(), "-home-user-other"),
+            Err(OrchestratorError::ProjectNotFound(_))
+        ));
+        for id in ["", "..", "a/b", "a\\b"] {
+            assert!(matches!(
+                project_dir(projects.path(), id),
+                Err(OrchestratorError::InvalidRequest(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_scan() {
+        let dir = TempDir::new().unwrap();
+        let first = [
+            tool_call(
+                "s1",
+                "2024-01-01T10:00:00Z",
+                "Bash",
+                &json!({"command": "cargo test --workspace"}),
+            ),
+            tool_call(
+                "s1",
+                "2024-01-01T10:01:00Z",
+                "Read",
+                &json!({"file_path": "/home/user/project/src/main.rs"}),
+            ),
+            // Tool results mention tool_use too but are not calls
+            json!({"type": "user", "sessionId": "s1", "message": {"content": [
+                {"type": "tool_result", "tool_use_id": "t", "content": "ok"}
+            ]}})
+            .to_string(),
+            "{\"type\": \"tool_use\"".to_string(),
+        ];
+        let second = [
+            tool_call(
+                "s2",
+                "2024-01-02T10:00:00Z",
+                "Bash",
+                &json!({"command": "cargo test"}),
+            ),
+            tool_call(
+                "s2",
+                "2023-12-31T10:00:00Z",
+                "Bash",
+                &json!({"command": "curl https://example.com"}),
+            ),
+            tool_call("s2", "2024-01-02T10:02:00Z", "TodoWrite", &json!({})),
+        ];
+        std::fs::write(dir.path().join("s1.jsonl"), first.join("\n")).unwrap();
+        std::fs::write(dir.path().join("s2.jsonl"), second.join("\n")).unwrap();
+        std::fs::write(
+            dir.path().join("notes.txt"),
+            tool_call("s3", "", "Bash", &json!({})),
+        )
+        .unwrap();
+
+        let catalog = scan("-home-user-project", dir.path(), |_| true);
+        assert_eq!(
+            catalog.working_directory.as_deref(),
+            Some("/home/user/project")
+        );
+        assert_eq!(catalog.sessions, 2);
+        assert_eq!(catalog.calls, 5);
+
+        let names: Vec<_> = catalog.tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["Bash", "Read", "TodoWrite"]);
+        let bash = &catalog.tools[0];
+        assert_eq!(bash.calls, 3);
+        assert_eq!(bash.sessions, 2);
+        assert_eq!(bash.first_used.as_deref(), Some("2023-12-31T10:00:00Z"));
+        assert_eq!(bash.last_used.as_deref(), Some("2024-01-02T10:00:00Z"));
+        assert_eq!(
+            bash.details,
+            vec![
+                DetailUsage {
+                    value: "cargo test".to_string(),
+                    calls: 2,
+                    sessions: 2,
+                },
+                DetailUsage {
+                    value: "curl".to_string(),
+                    calls: 1,
+                    sessions: 1,
+                },
+            ]
+        );
+        assert_eq!(catalog.tools[1].details[0].value, "src/main.rs");
+        assert!(catalog.tools[2].details.is_empty());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::config::OtlpSettings;
+use crate::models::ApprovalRequest;
+use anyhow::{Context as _, Result};
+use axum::http::HeaderMap;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::{Tracer, TracerProvider as _};
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use tracing::{warn, Span, Subscriber};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Name the server's spans are exported under, next to `OTLP_SERVICE_NAME`.
+const INSTRUMENTATION_SCOPE: &str = "chef-de-vibe";
+
+/// Exports spans to an OTLP collector until it is shut down.
+pub struct SpanExport {
+    provider: SdkTracerProvider,
+}
+
+impl SpanExport {
+    /// Sends the spans still buffered and stops exporting.
+    pub fn shutdown(self) {
+        if let Err(e) = self.provider.shutdown() {
+            warn!(error = %e, "Failed to flush spans to the OTLP collector");
+        }
+    }
+}
+
+/// A layer exporting every span to the collector `settings` point at, and the export
+/// it feeds, to be shut down when the server stops. Incoming `traceparent` headers are
+/// honored from then on, see [`continue_trace`].
+///
+/// # Errors
+///
+/// Returns an error if the exporter cannot be built, e.g. for an invalid endpoint.
+pub fn layer<S>(settings: &OtlpSettings) -> Result<(impl Layer<S>, SpanExport)>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(traces_url(&settings.endpoint))
+        .build()
+        .context("Failed to set up OTLP span export")?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        // A caller that sampled its trace gets the server's part of it as well
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            settings.sample_ratio,
+        ))))
+        .with_resource(
+            Resource::builder()
+                .with_service_name(settings.service_name.clone())
+                .build(),
+        )
+        .build();
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(provider.clone());
+    let tracer = provider.tracer(INSTRUMENTATION_SCOPE);
+    Ok((
+        tracing_opentelemetry::layer().with_tracer(tracer),
+        SpanExport { provider },
+    ))
+}
+
+/// OTLP/HTTP endpoint traces are sent to, under the collector's base URL.
+fn traces_url(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{endpoint}/v1/traces")
+    }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(axum::http::HeaderName::as_str).collect()
+    }
+}
+
+/// Makes `span` part of the trace the `traceparent` header among `headers` names, if
+/// any, so a caller's trace continues through the server.
+pub fn continue_trace(span: &Span, headers: &HeaderMap) {
+    let parent =
+        global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)));
+    // Without a tracer installed there is no trace to continue
+    let _ = span.set_parent(parent);
+}
+
+/// Exports an `approval_round_trip` span from when Claude asked for `request` until
+/// `decided_by` answered it now. Does nothing unless spans are exported.
+pub fn record_approval_round_trip(request: &ApprovalRequest, decided_by: &str) {
+    let tracer = global::tracer(INSTRUMENTATION_SCOPE);
+    let mut attributes = vec![
+        KeyValue::new("session_id", request.session_id.clone()),
+        KeyValue::new("approval_id", request.id.clone()),
+        KeyValue::new("decided_by", decided_by.to_string()),
+    ];
+    if let Some(tool) = request
+        .request
+        .get("tool_name")
+        .and_then(serde_json::Value::as_str)
+    {
+        attributes.push(KeyValue::new("tool_name", tool.to_string()));
+    }
+    let mut span = tracer
+        .span_builder("approval_round_trip")
+        .with_start_time(request.created_at)
+        .with_attributes(attributes)
+        .start_with_context(&tracer, &Span::current().context());
+    opentelemetry::trace::Span::end(&mut span);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::access_log::{self, REQUEST_ID_HEADER};
+    use axum::body::{Body, Bytes};
+    use axum::extract::Request;
+    use axum::routing::{get, post};
+    use tower::ServiceExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[tokio::test]
+    async fn test_request_spans_continue_caller_trace() {
+        // Stands in for the collector, passing on the bodies of export requests
+        let (bodies_tx, mut bodies_rx) = tokio::sync::mpsc::unbounded_channel::<Bytes>();
+        let collector = axum::Router::new().route(
+            "/v1/traces",
+            post(move |body: Bytes| async move {
+                let _ = bodies_tx.send(body);
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, collector).await.unwrap() });
+
+        // Nothing is sampled but traces callers sampled
+        let settings = OtlpSettings {
+            endpoint,
+            service_name: "chef-de-vibe-test".to_string(),
+            sample_ratio: 0.0,
+        };
+        let (layer, export) = layer(&settings).unwrap();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+        let app = axum::Router::new()
+            .route("/api/v1/ping", get(|| async { "pong" }))
+            .layer(axum::middleware::from_fn(access_log::log_requests));
+        let request = |traceparent: &str| {
+            Request::builder()
+                .uri("/api/v1/ping")
+                .header("traceparent", traceparent)
+                .header(REQUEST_ID_HEADER, "req-42")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let sampled = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let unsampled = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-00";
+        for traceparent in [sampled, unsampled] {
+            let response = app.clone().oneshot(request(traceparent)).await.unwrap();
+            assert_eq!(response.status(), 200);
+        }
+        tokio::task::spawn_blocking(move || export.shutdown())
+            .await
+            .unwrap();
+
+        let mut exported = Vec::new();
+        while let Ok(body) = bodies_rx.try_recv() {
+            exported.extend_from_slice(&body);
+        }
+        let contains = |needle: &[u8]| exported.windows(needle.len()).any(|w| w == needle);
+        // Trace IDs are exported as raw bytes
+        let trace_id = |hex: &str| -> Vec<u8> {
+            (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+                .collect()
+        };
+        assert!(contains(b"http_request"));
+        assert!(contains(b"req-42"));
+        assert!(contains(b"chef-de-vibe-test"));
+        assert!(contains(&trace_id("4bf92f3577b34da6a3ce929d0e0e4736")));
+        assert!(!contains(&trace_id("0af7651916cd43dd8448eb211c80319c")));
+    }
+
+    #[test]
+    fn test_traces_url() {
+        assert_eq!(
+            traces_url("http://localhost:4318"),
+            "http://localhost:4318/v1/traces"
+        );
+        assert_eq!(
+            traces_url("https://otel.example.com/"),
+            "https://otel.example.com/v1/traces"
+        );
+        assert_eq!(
+            traces_url("http://tempo:4318/v1/traces"),
+            "http://tempo:4318/v1/traces"
+        );
+    }
+}
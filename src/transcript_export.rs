@@ -0,0 +1,426 @@
+use crate::models::SessionInfo;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::PathBuf;
+use utoipa::ToSchema;
+
+/// Document formats a session transcript can be exported as.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Markdown,
+    /// A standalone page, with tool calls in `<details>` elements
+    Html,
+    /// The turns as [`ExportedSession`]
+    Json,
+}
+
+impl ExportFormat {
+    #[must_use]
+    pub const fn content_type(self) -> &'static str {
+        match self {
+            Self::Markdown => "text/markdown; charset=utf-8",
+            Self::Html => "text/html; charset=utf-8",
+            Self::Json => "application/json",
+        }
+    }
+
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Html => "html",
+            Self::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+/// Part of a turn.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        name: String,
+        #[schema(value_type = Object)]
+        input: serde_json::Value,
+    },
+    ToolResult {
+        content: String,
+        #[serde(default)]
+        is_error: bool,
+    },
+}
+
+/// Consecutive messages of one side of the conversation. Tool results belong to the
+/// assistant turn that made the calls.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Turn {
+    pub role: Role,
+    /// When the first message of the turn was written
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    pub blocks: Vec<Block>,
+}
+
+/// A session transcript as exported in [`ExportFormat::Json`].
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ExportedSession {
+    pub session_id: String,
+    #[schema(value_type = String)]
+    pub working_directory: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    pub turns: Vec<Turn>,
+}
+
+/// Text of a tool result's `content`, a string or a list of blocks.
+fn result_text(content: &serde_json::Value) -> String {
+    match content {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Array(blocks) => blocks
+            .iter()
+            .filter_map(|block| block["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// Blocks of a message's `content`. Thinking, images and other content without a
+/// readable form are left out.
+fn message_blocks(content: &serde_json::Value) -> Vec<Block> {
+    let Some(parts) = content.as_array() else {
+        return content
+            .as_str()
+            .filter(|text| !text.trim().is_empty())
+            .map(|text| Block::Text {
+                text: text.to_string(),
+            })
+            .into_iter()
+            .collect();
+    };
+    parts
+        .iter()
+        .filter_map(|part| match part["type"].as_str()? {
+            "text" => part["text"]
+                .as_str()
+                .filter(|text| !text.trim().is_empty())
+                .map(|text| Block::Text {
+                    text: text.to_string(),
+                }),
+            "tool_use" => Some(Block::ToolUse {
+                name: part["name"].as_str().unwrap_or("tool").to_string(),
+                input: part["input"].clone(),
+            }),
+            "tool_result" => Some(Block::ToolResult {
+                content: result_text(&part["content"]),
+                is_error: part["is_error"].as_bool().unwrap_or(false),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The conversation of a transcript as turns. Entries of subagents and entries Claude
+/// marks as meta are left out.
+#[must_use]
+pub fn turns(content: &[serde_json::Value]) -> Vec<Turn> {
+    let mut turns: Vec<Turn> = Vec::new();
+    for entry in content {
+        let role = match entry["type"].as_str() {
+            Some("user") => Role::User,
+            Some("assistant") => Role::Assistant,
+            _ => continue,
+        };
+        if entry["isSidechain"] == true || entry["isMeta"] == true {
+            continue;
+        }
+        let blocks = message_blocks(&entry["message"]["content"]);
+        if blocks.is_empty() {
+            continue;
+        }
+        let role = if blocks
+            .iter()
+            .all(|block| matches!(block, Block::ToolResult { .. }))
+        {
+            Role::Assistant
+        } else {
+            role
+        };
+
+        match turns.last_mut() {
+            Some(turn) if turn.role == role => turn.blocks.extend(blocks),
+            _ => turns.push(Turn {
+                role,
+                timestamp: entry["timestamp"].as_str().map(str::to_string),
+                blocks,
+            }),
+        }
+    }
+    turns
+}
+
+/// Renders the transcript `content` of the session `info` as a document in `format`.
+#[must_use]
+pub fn render(info: &SessionInfo, content: &[serde_json::Value], format: ExportFormat) -> String {
+    let session = ExportedSession {
+        session_id: info.session_id.clone(),
+        working_directory: info.working_directory.clone(),
+        summary: info.summary.clone(),
+        turns: turns(content),
+    };
+    match format {
+        ExportFormat::Markdown => markdown(&session),
+        ExportFormat::Html => html(&session),
+        ExportFormat::Json => serde_json::to_string_pretty(&session).unwrap_or_default(),
+    }
+}
+
+impl ExportedSession {
+    fn title(&self) -> String {
+        self.summary
+            .clone()
+            .unwrap_or_else(|| format!("Session {}", self.session_id))
+    }
+}
+
+impl Role {
+    const fn label(self) -> &'static str {
+        match self {
+            Self::User => "User",
+            Self::Assistant => "Assistant",
+        }
+    }
+}
+
+/// Heading of a turn, with its timestamp if known.
+fn turn_heading(turn: &Turn) -> String {
+    match &turn.timestamp {
+        Some(timestamp) => format!("{} · {timestamp}", turn.role.label()),
+        None => turn.role.label().to_string(),
+    }
+}
+
+/// Code fence longer than any run of backticks in `text`.
+fn fence(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn pretty_input(input: &serde_json::Value) -> String {
+    serde_json::to_string_pretty(input).unwrap_or_default()
+}
+
+fn markdown(session: &ExportedSession) -> String {
+    let mut out = format!(
+        "# {}\n\n- Session: `{}`\n- Working directory: `{}`\n",
+        session.title(),
+        session.session_id,
+        session.working_directory.display()
+    );
+    for turn in &session.turns {
+        let _ = write!(out, "\n## {}\n", turn_heading(turn));
+        for block in &turn.blocks {
+            out.push('\n');
+            match block {
+                Block::Text { text } => {
+                    let _ = writeln!(out, "{}", text.trim_end());
+                }
+                Block::ToolUse { name, input } => {
+                    let input = pretty_input(input);
+                    let fence = fence(&input);
+                    let _ = writeln!(
+                        out,
+                        "<details>\n<summary>Tool call: {name}</summary>\n\n{fence}json\n{input}\n{fence}\n\n</details>"
+                    );
+                }
+                Block::ToolResult { content, is_error } => {
+                    let label = if *is_error {
+                        "Tool error"
+                    } else {
+                        "Tool result"
+                    };
+                    let fence = fence(content);
+                    let _ = writeln!(
+                        out,
+                        "<details>\n<summary>{label}</summary>\n\n{fence}\n{}\n{fence}\n\n</details>",
+                        content.trim_end()
+                    );
+                }
+            }
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const HTML_STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:50rem;margin:2rem auto;padding:0 1rem;line-height:1.5}\
+section{border-left:3px solid #ccc;padding-left:1rem;margin:1.5rem 0}\
+section.user{border-color:#3b82f6}section.assistant{border-color:#10b981}\
+pre{background:#f5f5f5;padding:.5rem;overflow-x:auto;white-space:pre-wrap}\
+p{white-space:pre-wrap}summary{cursor:pointer;color:#555}";
+
+fn html(session: &ExportedSession) -> String {
+    let title = escape_html(&session.title());
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{HTML_STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n<p>Session <code>{}</code> in <code>{}</code></p>\n",
+        escape_html(&session.session_id),
+        escape_html(&session.working_directory.to_string_lossy())
+    );
+    for turn in &session.turns {
+        let class = match turn.role {
+            Role::User => "user",
+            Role::Assistant => "assistant",
+        };
+        let _ = writeln!(
+            out,
+            "<section class=\"{class}\">\n<h2>{}</h2>",
+            escape_html(&turn_heading(turn))
+        );
+        for block in &turn.blocks {
+            let _ = match block {
+                Block::Text { text } => writeln!(out, "<p>{}</p>", escape_html(text.trim_end())),
+                Block::ToolUse { name, input } => writeln!(
+                    out,
+                    "<details><summary>Tool call: {}</summary><pre>{}</pre></details>",
+                    escape_html(name),
+                    escape_html(&pretty_input(input))
+                ),
+                Block::ToolResult { content, is_error } => writeln!(
+                    out,
+                    "<details><summary>{}</summary><pre>{}</pre></details>",
+                    if *is_error {
+                        "Tool error"
+                    } else {
+                        "Tool result"
+                    },
+                    escape_html(content.trim_end())
+                ),
+            };
+        }
+        out.push_str("</section>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn transcript() -> Vec<serde_json::Value> {
+        vec![
+            serde_json::json!({"type": "summary", "summary": "Fix the build", "leafUuid": "u4"}),
+            serde_json::json!({"type": "user", "uuid": "u1", "timestamp": "2024-05-01T12:00:00Z",
+                "message": {"role": "user", "content": "Why does <main> fail?"}}),
+            serde_json::json!({"type": "assistant", "uuid": "u2", "timestamp": "2024-05-01T12:00:02Z",
+            "message": {"role": "assistant", "content": [
+                {"type": "thinking", "thinking": "hmm"},
+                {"type": "text", "text": "Let me check."},
+                {"type": "tool_use", "id": "t1", "name": "Bash", "input": {"command": "cargo build"}}
+            ]}}),
+            serde_json::json!({"type": "user", "uuid": "u3", "timestamp": "2024-05-01T12:00:05Z",
+            "message": {"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "t1", "is_error": true,
+                 "content": [{"type": "text", "text": "error: ```missing```"}]}
+            ]}}),
+            serde_json::json!({"type": "user", "isMeta": true, "message": {"role": "user", "content": "caveat"}}),
+            serde_json::json!({"type": "assistant", "uuid": "u4", "timestamp": "2024-05-01T12:00:09Z",
+                "message": {"role": "assistant", "content": [{"type": "text", "text": "Fixed."}]}}),
+        ]
+    }
+
+    fn info() -> SessionInfo {
+        SessionInfo {
+            session_id: "session-123".to_string(),
+            working_directory: PathBuf::from("/work/app"),
+            active: false,
+            summary: Some("Fix the build".to_string()),
+            earliest_message_date: None,
+            latest_message_date: None,
+            resumed_from: None,
+            pinned: false,
+            owner: None,
+            usage: None,
+            tags: Vec::new(),
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_turns_group_tool_results_with_the_assistant() {
+        let turns = turns(&transcript());
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[0].role, Role::User);
+        assert_eq!(turns[0].timestamp.as_deref(), Some("2024-05-01T12:00:00Z"));
+        assert_eq!(turns[1].role, Role::Assistant);
+        assert_eq!(turns[1].timestamp.as_deref(), Some("2024-05-01T12:00:02Z"));
+        assert_eq!(
+            turns[1].blocks,
+            vec![
+                Block::Text {
+                    text: "Let me check.".to_string()
+                },
+                Block::ToolUse {
+                    name: "Bash".to_string(),
+                    input: serde_json::json!({"command": "cargo build"}),
+                },
+                Block::ToolResult {
+                    content: "error: ```missing```".to_string(),
+                    is_error: true,
+                },
+                Block::Text {
+                    text: "Fixed.".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_formats() {
+        let markdown = render(&info(), &transcript(), ExportFormat::Markdown);
+        assert!(markdown.starts_with("# Fix the build\n"));
+        assert!(markdown.contains("## User · 2024-05-01T12:00:00Z\n\nWhy does <main> fail?\n"));
+        assert!(markdown.contains("<summary>Tool call: Bash</summary>"));
+        // The fence outlasts the backticks of the content
+        assert!(markdown.contains("````\nerror: ```missing```\n````"));
+        assert!(!markdown.contains("hmm"));
+        assert!(!markdown.contains("caveat"));
+
+        let html = render(&info(), &transcript(), ExportFormat::Html);
+        assert!(html.contains("<title>Fix the build</title>"));
+        assert!(html.contains("<p>Why does &lt;main&gt; fail?</p>"));
+        assert!(html.contains("<details><summary>Tool error</summary>"));
+
+        let json: ExportedSession =
+            serde_json::from_str(&render(&info(), &transcript(), ExportFormat::Json)).unwrap();
+        assert_eq!(json.session_id, "session-123");
+        assert_eq!(json.turns, turns(&transcript()));
+    }
+}
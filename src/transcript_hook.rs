@@ -0,0 +1,153 @@
+use crate::error::{OrchestratorError, OrchestratorResult};
+use async_trait::async_trait;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{debug, instrument};
+
+/// How long an external hook may take before the request fails.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Post-processing step applied to a transcript before it is served
+/// (translation, PII scrubbing, reformatting, ...).
+#[async_trait]
+pub trait TranscriptHook: Send + Sync {
+    /// Transforms the transcript entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transformation fails.
+    async fn transform(
+        &self,
+        transcript: Vec<serde_json::Value>,
+    ) -> OrchestratorResult<Vec<serde_json::Value>>;
+}
+
+/// Runs an external program that reads the transcript as JSONL on stdin
+/// and writes the transformed transcript as JSONL to stdout.
+#[derive(Debug, Clone)]
+pub struct ExternalCommandHook {
+    name: String,
+    argv: Vec<String>,
+}
+
+impl ExternalCommandHook {
+    #[must_use]
+    pub fn new(name: String, argv: Vec<String>) -> Self {
+        Self { name, argv }
+    }
+}
+
+#[async_trait]
+impl TranscriptHook for ExternalCommandHook {
+    #[instrument(skip(self, transcript), fields(hook = %self.name, entries = transcript.len()))]
+    async fn transform(
+        &self,
+        transcript: Vec<serde_json::Value>,
+    ) -> OrchestratorResult<Vec<serde_json::Value>> {
+        let hook_error = |message: String| {
+            OrchestratorError::TranscriptHookFailed(format!("{}: {message}", self.name))
+        };
+
+        let (program, args) = self
+            .argv
+            .split_first()
+            .ok_or_else(|| hook_error("empty command".to_string()))?;
+
+        let mut input = Vec::new();
+        for entry in &transcript {
+            serde_json::to_writer(&mut input, entry)?;
+            input.push(b'\n');
+        }
+
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| hook_error(format!("failed to start: {e}")))?;
+
+        // Feed stdin from a separate task so a hook that streams its output doesn't deadlock
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let writer = tokio::spawn(async move { stdin.write_all(&input).await });
+
+        let output = tokio::time::timeout(HOOK_TIMEOUT, child.wait_with_output())
+            .await
+            .map_err(|_| hook_error(format!("timed out after {}s", HOOK_TIMEOUT.as_secs())))?
+            .map_err(|e| hook_error(e.to_string()))?;
+        let _ = writer.await;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(hook_error(format!(
+                "exited with {}: {}",
+                output.status,
+                stderr.trim()
+            )));
+        }
+
+        let transformed = output
+            .stdout
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+            .map(serde_json::from_slice)
+            .collect::<Result<Vec<serde_json::Value>, _>>()
+            .map_err(|e| hook_error(format!("invalid JSONL output: {e}")))?;
+
+        debug!(
+            hook = %self.name,
+            entries_in = transcript.len(),
+            entries_out = transformed.len(),
+            "Transcript hook completed"
+        );
+
+        Ok(transformed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell_hook(script: &str) -> ExternalCommandHook {
+        ExternalCommandHook::new(
+            "test".to_string(),
+            vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_external_command_transforms_transcript() {
+        let hook = shell_hook("sed 's/secret/[scrubbed]/g'");
+        let transcript = vec![
+            serde_json::json!({"type": "user", "message": "my secret"}),
+            serde_json::json!({"type": "assistant", "message": "ok"}),
+        ];
+
+        let result = hook.transform(transcript).await.unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0]["message"], "my [scrubbed]");
+        assert_eq!(result[1]["message"], "ok");
+    }
+
+    #[tokio::test]
+    async fn test_external_command_failure() {
+        let hook = shell_hook("echo boom >&2; exit 3");
+        let err = hook
+            .transform(vec![serde_json::json!({})])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, OrchestratorError::TranscriptHookFailed(_)));
+        assert!(err.to_string().contains("boom"));
+    }
+
+    #[tokio::test]
+    async fn test_external_command_invalid_output() {
+        let hook = shell_hook("cat >/dev/null; echo 'not json'");
+        let err = hook.transform(Vec::new()).await.unwrap_err();
+        assert!(err.to_string().contains("invalid JSONL output"));
+    }
+}
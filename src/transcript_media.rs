@@ -0,0 +1,391 @@
+use crate::error::{OrchestratorError, OrchestratorResult};
+use base64::Engine;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Largest media file served from a transcript, decoded.
+pub const MAX_MEDIA_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Tools whose `file_path` input names a file Claude looked at or produced.
+const FILE_TOOLS: &[&str] = &["Read", "Write"];
+
+/// Where a media reference sits in a transcript entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MediaKind {
+    /// A content block `{"type": "image", "source": {"type": "base64", ...}}`
+    ImageBlock,
+    /// A file tool's `tool_use` block naming an image or PDF on disk
+    ToolFile,
+    /// `toolUseResult.file` of a tool that read an image, carrying it as base64
+    ToolResultFile,
+}
+
+#[derive(Debug, Clone)]
+struct MediaRef {
+    kind: MediaKind,
+    /// JSON pointer to the referencing object within the entry
+    pointer: String,
+}
+
+/// Binary content behind a media reference.
+#[derive(Debug)]
+pub struct Media {
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Path under which a session's media is served.
+#[must_use]
+pub fn media_url(session_id: &str, media_id: &str) -> String {
+    format!("/api/v1/sessions/{session_id}/media/{media_id}")
+}
+
+/// Whether a file path looks like media worth serving rather than text.
+fn is_media_path(path: &str) -> bool {
+    mime_guess::from_path(path)
+        .first_raw()
+        .is_some_and(|mime| mime.starts_with("image/") || mime == "application/pdf")
+}
+
+/// Media references of one transcript entry, in a stable order so the n-th one can be
+/// found again when it is requested.
+fn media_refs(entry: &Value) -> Vec<MediaRef> {
+    let mut refs = Vec::new();
+    if let Some(blocks) = entry.pointer("/message/content").and_then(Value::as_array) {
+        collect_blocks(blocks, "/message/content", &mut refs);
+    }
+    if entry
+        .pointer("/toolUseResult/file/base64")
+        .is_some_and(Value::is_string)
+    {
+        refs.push(MediaRef {
+            kind: MediaKind::ToolResultFile,
+            pointer: "/toolUseResult/file".to_string(),
+        });
+    }
+    refs
+}
+
+fn collect_blocks(blocks: &[Value], pointer: &str, refs: &mut Vec<MediaRef>) {
+    for (i, block) in blocks.iter().enumerate() {
+        let pointer = format!("{pointer}/{i}");
+        match block.get("type").and_then(Value::as_str) {
+            Some("image")
+                if block.pointer("/source/type").and_then(Value::as_str) == Some("base64") =>
+            {
+                refs.push(MediaRef {
+                    kind: MediaKind::ImageBlock,
+                    pointer,
+                });
+            }
+            Some("tool_use")
+                if block
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .is_some_and(|name| FILE_TOOLS.contains(&name))
+                    && block
+                        .pointer("/input/file_path")
+                        .and_then(Value::as_str)
+                        .is_some_and(is_media_path) =>
+            {
+                refs.push(MediaRef {
+                    kind: MediaKind::ToolFile,
+                    pointer,
+                });
+            }
+            _ => {
+                // Tool results nest their own content blocks
+                if let Some(nested) = block.get("content").and_then(Value::as_array) {
+                    collect_blocks(nested, &format!("{pointer}/content"), refs);
+                }
+            }
+        }
+    }
+}
+
+/// Replaces the media in `content` with URLs of the media endpoint: base64 payloads are
+/// dropped in favour of a `url`, file tools get a `media_url` next to their input.
+/// Only entries with a `uuid` are rewritten, since that is how media is looked up again.
+pub fn rewrite(session_id: &str, content: &mut [Value]) {
+    let mut rewritten = 0;
+    for entry in content.iter_mut() {
+        let Some(uuid) = entry
+            .get("uuid")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+        else {
+            continue;
+        };
+        for (n, media) in media_refs(entry).into_iter().enumerate() {
+            let url = media_url(session_id, &format!("{uuid}.{n}"));
+            let Some(node) = entry
+                .pointer_mut(&media.pointer)
+                .and_then(Value::as_object_mut)
+            else {
+                continue;
+            };
+            match media.kind {
+                MediaKind::ImageBlock => {
+                    let media_type = node
+                        .get("source")
+                        .and_then(|source| source.get("media_type"))
+                        .cloned();
+                    let mut source = serde_json::json!({"type": "url", "url": url});
+                    if let Some(media_type) = media_type {
+                        source["media_type"] = media_type;
+                    }
+                    node.insert("source".to_string(), source);
+                }
+                MediaKind::ToolFile => {
+                    node.insert("media_url".to_string(), Value::String(url));
+                }
+                MediaKind::ToolResultFile => {
+                    node.remove("base64");
+                    node.insert("url".to_string(), Value::String(url));
+                }
+            }
+            rewritten += 1;
+        }
+    }
+    if rewritten > 0 {
+        debug!(session_id = %session_id, rewritten, "Rewrote transcript media to URLs");
+    }
+}
+
+/// Loads the media `media_id` (as produced by [`rewrite`]) from a session's transcript.
+/// Files named by tools are read from disk as they are now, and only from within the
+/// session's working directory.
+///
+/// # Errors
+///
+/// Returns an error if there is no such media, the file is gone or outside the working
+/// directory, or the content is over [`MAX_MEDIA_SIZE`].
+pub async fn resolve(
+    content: &[Value],
+    working_dir: &Path,
+    media_id: &str,
+) -> OrchestratorResult<Media> {
+    let not_found = || OrchestratorError::MediaNotFound(media_id.to_string());
+    let (uuid, n) = media_id.rsplit_once('.').ok_or_else(not_found)?;
+    let n: usize = n.parse().map_err(|_| not_found())?;
+    let entry = content
+        .iter()
+        .find(|entry| entry.get("uuid").and_then(Value::as_str) == Some(uuid))
+        .ok_or_else(not_found)?;
+    let media = media_refs(entry).into_iter().nth(n).ok_or_else(not_found)?;
+    let node = entry.pointer(&media.pointer).ok_or_else(not_found)?;
+
+    let (bytes, declared, path) = match media.kind {
+        MediaKind::ImageBlock => (
+            decode(node.pointer("/source/data"))?,
+            node.pointer("/source/media_type"),
+            None,
+        ),
+        MediaKind::ToolResultFile => (decode(node.get("base64"))?, node.get("type"), None),
+        MediaKind::ToolFile => {
+            let file_path = node
+                .pointer("/input/file_path")
+                .and_then(Value::as_str)
+                .ok_or_else(not_found)?;
+            let path = contained_path(working_dir, file_path).await?;
+            (read_limited(&path).await?, None, Some(path))
+        }
+    };
+    Ok(Media {
+        content_type: detect_content_type(
+            &bytes,
+            declared.and_then(Value::as_str),
+            path.as_deref(),
+        ),
+        bytes,
+    })
+}
+
+fn decode(data: Option<&Value>) -> OrchestratorResult<Vec<u8>> {
+    let data = data.and_then(Value::as_str).unwrap_or_default();
+    // Four base64 characters carry three bytes
+    if data.len() as u64 / 4 * 3 > MAX_MEDIA_SIZE {
+        return Err(too_large());
+    }
+    base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map_err(|e| OrchestratorError::FileParseError(format!("Invalid base64 media: {e}")))
+}
+
+/// Resolves `file_path` against `working_dir`, refusing anything that ends up outside it.
+async fn contained_path(working_dir: &Path, file_path: &str) -> OrchestratorResult<PathBuf> {
+    let outside = || {
+        OrchestratorError::InvalidRequest(format!(
+            "{file_path} is outside the working directory {}",
+            working_dir.display()
+        ))
+    };
+    let root = tokio::fs::canonicalize(working_dir)
+        .await
+        .map_err(|_| OrchestratorError::MediaNotFound(file_path.to_string()))?;
+    let path = tokio::fs::canonicalize(working_dir.join(file_path))
+        .await
+        .map_err(|_| OrchestratorError::MediaNotFound(file_path.to_string()))?;
+    if path.starts_with(&root) {
+        Ok(path)
+    } else {
+        Err(outside())
+    }
+}
+
+async fn read_limited(path: &Path) -> OrchestratorResult<Vec<u8>> {
+    let metadata = tokio::fs::metadata(path).await?;
+    if !metadata.is_file() {
+        return Err(OrchestratorError::MediaNotFound(path.display().to_string()));
+    }
+    if metadata.len() > MAX_MEDIA_SIZE {
+        return Err(too_large());
+    }
+    Ok(tokio::fs::read(path).await?)
+}
+
+fn too_large() -> OrchestratorError {
+    OrchestratorError::PayloadTooLarge(format!("Media is limited to {MAX_MEDIA_SIZE} bytes"))
+}
+
+/// Content type from the bytes themselves, falling back to what the transcript declared
+/// and then to the file extension.
+fn detect_content_type(bytes: &[u8], declared: Option<&str>, path: Option<&Path>) -> String {
+    let sniffed = if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if bytes.starts_with(b"%PDF-") {
+        Some("application/pdf")
+    } else {
+        None
+    };
+    sniffed
+        .map(str::to_string)
+        .or_else(|| declared.map(str::to_string))
+        .or_else(|| {
+            path.and_then(|path| mime_guess::from_path(path).first_raw().map(str::to_string))
+        })
+        .unwrap_or_else(|| "application/octet-stream".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\nrest-of-image";
+
+    fn encoded(bytes: &[u8]) -> String {
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    }
+
+    fn transcript() -> Vec<Value> {
+        vec![
+            serde_json::json!({
+                "uuid": "u1",
+                "type": "assistant",
+                "message": {"content": [
+                    {"type": "text", "text": "Let me look"},
+                    {"type": "tool_use", "id": "t1", "name": "Read", "input": {"file_path": "shot.png"}},
+                    {"type": "tool_use", "id": "t2", "name": "Read", "input": {"file_path": "notes.md"}}
+                ]}
+            }),
+            serde_json::json!({
+                "uuid": "u2",
+                "type": "user",
+                "message": {"content": [{
+                    "type": "tool_result",
+                    "tool_use_id": "t1",
+                    "content": [{"type": "image", "source": {
+                        "type": "base64", "media_type": "image/png", "data": encoded(PNG)
+                    }}]
+                }]},
+                "toolUseResult": {"type": "image", "file": {"base64": encoded(PNG), "type": "image/png"}}
+            }),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_rewrite_and_resolve() {
+        let working_dir = TempDir::new().unwrap();
+        std::fs::write(working_dir.path().join("shot.png"), PNG).unwrap();
+        let original = transcript();
+
+        let mut content = original.clone();
+        rewrite("s1", &mut content);
+        assert_eq!(
+            content[0]["message"]["content"][1]["media_url"],
+            "/api/v1/sessions/s1/media/u1.0"
+        );
+        assert!(content[0]["message"]["content"][2]
+            .get("media_url")
+            .is_none());
+        let source = &content[1]["message"]["content"][0]["content"][0]["source"];
+        assert_eq!(source["url"], "/api/v1/sessions/s1/media/u2.0");
+        assert_eq!(source["media_type"], "image/png");
+        assert!(source.get("data").is_none());
+        assert_eq!(
+            content[1]["toolUseResult"]["file"]["url"],
+            "/api/v1/sessions/s1/media/u2.1"
+        );
+        assert!(content[1]["toolUseResult"]["file"].get("base64").is_none());
+
+        for media_id in ["u1.0", "u2.0", "u2.1"] {
+            let media = resolve(&original, working_dir.path(), media_id)
+                .await
+                .unwrap();
+            assert_eq!(media.content_type, "image/png");
+            assert_eq!(media.bytes, PNG);
+        }
+        for media_id in ["u1.1", "u3.0", "u1", "u1.x"] {
+            assert!(matches!(
+                resolve(&original, working_dir.path(), media_id).await,
+                Err(OrchestratorError::MediaNotFound(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_files_outside_working_directory_are_refused() {
+        let root = TempDir::new().unwrap();
+        let working_dir = root.path().join("project");
+        std::fs::create_dir_all(&working_dir).unwrap();
+        std::fs::write(root.path().join("secret.png"), PNG).unwrap();
+        let content = vec![serde_json::json!({
+            "uuid": "u1",
+            "message": {"content": [
+                {"type": "tool_use", "name": "Read", "input": {"file_path": "../secret.png"}}
+            ]}
+        })];
+        assert!(matches!(
+            resolve(&content, &working_dir, "u1.0").await,
+            Err(OrchestratorError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_detect_content_type() {
+        assert_eq!(
+            detect_content_type(PNG, Some("image/jpeg"), None),
+            "image/png"
+        );
+        assert_eq!(
+            detect_content_type(b"%PDF-1.7", None, Some(Path::new("a.bin"))),
+            "application/pdf"
+        );
+        assert_eq!(
+            detect_content_type(b"<svg/>", None, Some(Path::new("a.svg"))),
+            "image/svg+xml"
+        );
+        assert_eq!(
+            detect_content_type(b"????", None, None),
+            "application/octet-stream"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use crate::transcript_media;
+use axum::body::{Body, Bytes};
+use futures::{stream, StreamExt};
+use serde_json::Value;
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tracing::error;
+
+/// Content type of newline-delimited JSON
+pub const NDJSON: &str = "application/x-ndjson";
+
+/// Which lines of a journal to send, counted from 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+/// The entries of `range` in the journal at `path` as NDJSON, read as the body is sent
+/// so that memory use does not grow with the journal. Media is moved to the media
+/// endpoint as in the JSON representation unless `inline_media` is set. A line that is
+/// not JSON ends the body early with an error, which breaks the chunked encoding so the
+/// client does not mistake the content for complete.
+///
+/// # Errors
+///
+/// Returns an error if the journal cannot be opened.
+pub async fn ndjson_body(
+    path: &Path,
+    session_id: String,
+    range: LineRange,
+    inline_media: bool,
+) -> std::io::Result<Body> {
+    let file = tokio::fs::File::open(path).await?;
+    let path = path.to_path_buf();
+    let lines = stream::unfold(BufReader::new(file).lines(), |mut lines| async move {
+        let line = lines.next_line().await.transpose()?;
+        Some((line, lines))
+    });
+    let lines = lines
+        .skip(range.offset)
+        .take(range.limit.unwrap_or(usize::MAX))
+        .enumerate()
+        .map(move |(n, line)| {
+            let line_number = range.offset + n + 1;
+            line.and_then(|line: String| entry_line(&line, &session_id, inline_media))
+                .inspect_err(|e| {
+                    error!(
+                        file_path = %path.display(),
+                        line_number,
+                        error = %e,
+                        "Failed to stream session content line"
+                    );
+                })
+        });
+    Ok(Body::from_stream(lines))
+}
+
+fn entry_line(line: &str, session_id: &str, inline_media: bool) -> std::io::Result<Bytes> {
+    let mut entry: Value = serde_json::from_str(line)?;
+    if !inline_media {
+        transcript_media::rewrite(session_id, std::slice::from_mut(&mut entry));
+    }
+    let mut bytes = serde_json::to_vec(&entry)?;
+    bytes.push(b'\n');
+    Ok(Bytes::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read(body: Body) -> Result<String, axum::Error> {
+        let bytes = axum::body::to_bytes(body, usize::MAX).await?;
+        Ok(String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_body_streams_a_range_of_lines() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("session.jsonl");
+        let lines: Vec<String> = (0..5).map(|n| format!("{{\"n\": {n}}}")).collect();
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        let range = LineRange {
+            offset: 1,
+            limit: Some(2),
+        };
+        let body = ndjson_body(&path, "s".to_string(), range, false)
+            .await
+            .unwrap();
+        assert_eq!(read(body).await.unwrap(), "{\"n\":1}\n{\"n\":2}\n");
+
+        let range = LineRange {
+            offset: 3,
+            limit: None,
+        };
+        let body = ndjson_body(&path, "s".to_string(), range, false)
+            .await
+            .unwrap();
+        assert_eq!(read(body).await.unwrap(), "{\"n\":3}\n{\"n\":4}\n");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_line_fails_the_body() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("session.jsonl");
+        std::fs::write(&path, "{\"n\": 0}\nnot json\n").unwrap();
+
+        let range = LineRange {
+            offset: 0,
+            limit: None,
+        };
+        let body = ndjson_body(&path, "s".to_string(), range, false)
+            .await
+            .unwrap();
+        assert!(read(body).await.is_err());
+    }
+}
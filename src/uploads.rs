@@ -0,0 +1,259 @@
+use crate::error::{OrchestratorError, OrchestratorResult};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Largest upload accepted, across all of its chunks.
+pub const MAX_UPLOAD_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Uploads nobody wrote to or used for this long are deleted.
+const UPLOAD_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Progress of an upload, returned after every operation so clients can resume.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct UploadStatus {
+    pub upload_id: String,
+    /// Bytes received so far; the next chunk must start here
+    pub offset: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_size: Option<u64>,
+    /// Whether the upload can be referenced by a session yet
+    pub complete: bool,
+}
+
+#[derive(Debug)]
+struct Upload {
+    path: PathBuf,
+    size: u64,
+    total_size: Option<u64>,
+    updated_at: SystemTime,
+}
+
+impl Upload {
+    fn status(&self, upload_id: &str) -> UploadStatus {
+        UploadStatus {
+            upload_id: upload_id.to_string(),
+            offset: self.size,
+            total_size: self.total_size,
+            complete: self.total_size.is_none_or(|total| total == self.size),
+        }
+    }
+}
+
+/// Content uploaded ahead of a session, in chunks, so large first messages don't need
+/// multi-megabyte JSON bodies. Chunks are appended to a file per upload.
+pub struct UploadStore {
+    dir: PathBuf,
+    uploads: DashMap<String, Arc<Mutex<Upload>>>,
+}
+
+impl UploadStore {
+    #[must_use]
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            uploads: DashMap::new(),
+        }
+    }
+
+    fn get(&self, upload_id: &str) -> OrchestratorResult<Arc<Mutex<Upload>>> {
+        self.uploads
+            .get(upload_id)
+            .map(|upload| upload.clone())
+            .ok_or_else(|| OrchestratorError::UploadNotFound(upload_id.to_string()))
+    }
+
+    /// Starts an empty upload. With `total_size` the upload only counts as complete once
+    /// exactly that many bytes have arrived.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `total_size` exceeds [`MAX_UPLOAD_SIZE`] or the upload file
+    /// cannot be created.
+    pub async fn create(&self, total_size: Option<u64>) -> OrchestratorResult<UploadStatus> {
+        if total_size.is_some_and(|total| total > MAX_UPLOAD_SIZE) {
+            return Err(OrchestratorError::InvalidRequest(format!(
+                "Uploads are limited to {MAX_UPLOAD_SIZE} bytes"
+            )));
+        }
+        self.remove_expired().await;
+
+        let upload_id = Uuid::new_v4().to_string();
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.dir.join(format!("{upload_id}.upload"));
+        tokio::fs::File::create(&path).await?;
+
+        let upload = Upload {
+            path,
+            size: 0,
+            total_size,
+            updated_at: SystemTime::now(),
+        };
+        let status = upload.status(&upload_id);
+        self.uploads
+            .insert(upload_id.clone(), Arc::new(Mutex::new(upload)));
+        info!(upload_id = %upload_id, total_size = ?total_size, "Upload started");
+        Ok(status)
+    }
+
+    /// Appends a chunk that must start at the current end of the upload. A client that
+    /// lost track (e.g. after a dropped connection) asks for the status and resumes there.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the upload is unknown, `offset` is not where the upload ends,
+    /// the chunk would exceed the upload's size limit, or writing fails.
+    pub async fn append(
+        &self,
+        upload_id: &str,
+        offset: u64,
+        chunk: &[u8],
+    ) -> OrchestratorResult<UploadStatus> {
+        let upload = self.get(upload_id)?;
+        let mut upload = upload.lock().await;
+
+        if offset != upload.size {
+            return Err(OrchestratorError::InvalidRequest(format!(
+                "Chunk starts at {offset} but upload {upload_id} has {} bytes",
+                upload.size
+            )));
+        }
+        let new_size = upload.size + chunk.len() as u64;
+        let limit = upload.total_size.unwrap_or(MAX_UPLOAD_SIZE);
+        if new_size > limit {
+            return Err(OrchestratorError::InvalidRequest(format!(
+                "Chunk would grow upload {upload_id} to {new_size} bytes, more than {limit}"
+            )));
+        }
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(&upload.path)
+            .await?;
+        file.write_all(chunk).await?;
+        file.flush().await?;
+        upload.size = new_size;
+        upload.updated_at = SystemTime::now();
+        debug!(upload_id = %upload_id, offset, chunk_len = chunk.len(), "Upload chunk stored");
+        Ok(upload.status(upload_id))
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the upload is unknown.
+    pub async fn status(&self, upload_id: &str) -> OrchestratorResult<UploadStatus> {
+        let upload = self.get(upload_id)?;
+        let upload = upload.lock().await;
+        Ok(upload.status(upload_id))
+    }
+
+    /// Reads a complete upload as text.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the upload is unknown, still incomplete, or not valid UTF-8.
+    pub async fn read_text(&self, upload_id: &str) -> OrchestratorResult<String> {
+        let upload = self.get(upload_id)?;
+        let upload = upload.lock().await;
+        if !upload.status(upload_id).complete {
+            return Err(OrchestratorError::InvalidRequest(format!(
+                "Upload {upload_id} is incomplete: {} of {} bytes received",
+                upload.size,
+                upload.total_size.unwrap_or_default()
+            )));
+        }
+        let content = tokio::fs::read(&upload.path).await?;
+        String::from_utf8(content).map_err(|_| {
+            OrchestratorError::InvalidRequest(format!("Upload {upload_id} is not valid UTF-8"))
+        })
+    }
+
+    /// Deletes an upload once it has been used.
+    pub async fn remove(&self, upload_id: &str) {
+        let Some((_, upload)) = self.uploads.remove(upload_id) else {
+            return;
+        };
+        let upload = upload.lock().await;
+        if let Err(e) = tokio::fs::remove_file(&upload.path).await {
+            warn!(upload_id = %upload_id, error = %e, "Failed to delete upload file");
+        }
+    }
+
+    async fn remove_expired(&self) {
+        let now = SystemTime::now();
+        let mut expired = Vec::new();
+        for entry in &self.uploads {
+            if let Ok(upload) = entry.value().try_lock() {
+                if now
+                    .duration_since(upload.updated_at)
+                    .is_ok_and(|age| age > UPLOAD_TTL)
+                {
+                    expired.push(entry.key().clone());
+                }
+            }
+        }
+        for upload_id in expired {
+            info!(upload_id = %upload_id, "Removing expired upload");
+            self.remove(&upload_id).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_chunked_upload() {
+        let temp = TempDir::new().unwrap();
+        let store = UploadStore::new(temp.path().join("uploads"));
+
+        let status = store.create(Some(11)).await.unwrap();
+        assert_eq!(status.offset, 0);
+        assert!(!status.complete);
+        let id = status.upload_id;
+
+        store.append(&id, 0, b"hello ").await.unwrap();
+        // A retried chunk that already arrived is refused rather than duplicated
+        assert!(store.append(&id, 0, b"hello ").await.is_err());
+        assert!(store.read_text(&id).await.is_err());
+        assert!(store.append(&id, 6, b"world!").await.is_err());
+
+        let status = store.append(&id, 6, b"world").await.unwrap();
+        assert_eq!(status.offset, 11);
+        assert!(status.complete);
+        assert_eq!(store.status(&id).await.unwrap(), status);
+        assert_eq!(store.read_text(&id).await.unwrap(), "hello world");
+
+        store.remove(&id).await;
+        assert!(store.status(&id).await.is_err());
+        assert_eq!(
+            std::fs::read_dir(temp.path().join("uploads"))
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_limits() {
+        let temp = TempDir::new().unwrap();
+        let store = UploadStore::new(temp.path().to_path_buf());
+
+        assert!(store.create(Some(MAX_UPLOAD_SIZE + 1)).await.is_err());
+        assert!(store.append("missing", 0, b"x").await.is_err());
+
+        let id = store.create(None).await.unwrap().upload_id;
+        store.append(&id, 0, &[0xff, 0xfe]).await.unwrap();
+        assert!(store.status(&id).await.unwrap().complete);
+        assert!(store.read_text(&id).await.is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use crate::models::{DailyUsage, TokenUsage, UsageResponse, WorkingDirUsage};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+use utoipa::ToSchema;
+
+const USAGE_FILE: &str = "usage.json";
+
+/// Tokens and cost summed over turns, as Claude reports them in its `result` messages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct UsageTotals {
+    pub turns: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_input_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl UsageTotals {
+    fn add_turn(&mut self, usage: Option<&TokenUsage>, cost_usd: f64) {
+        self.turns += 1;
+        if let Some(usage) = usage {
+            self.input_tokens += usage.input_tokens;
+            self.output_tokens += usage.output_tokens;
+            self.cache_read_input_tokens += usage.cache_read_input_tokens;
+            self.cache_creation_input_tokens += usage.cache_creation_input_tokens;
+        }
+        self.cost_usd += cost_usd;
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.turns += other.turns;
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cost_usd += other.cost_usd;
+    }
+}
+
+/// Usage of one session on one day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UsageRecord {
+    session_id: String,
+    day: NaiveDate,
+    working_dir: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    owner: Option<String>,
+    totals: UsageTotals,
+}
+
+/// Token usage and cost of every session, by day, so it can be reported per session,
+/// per day and per working directory.
+#[derive(Debug, Default)]
+pub struct UsageLedger {
+    records: Mutex<Vec<UsageRecord>>,
+    /// Where usage is persisted, if a session state directory is configured
+    path: Option<PathBuf>,
+}
+
+impl UsageLedger {
+    /// Loads the usage persisted in `state_dir`, or starts empty.
+    #[must_use]
+    pub fn load(state_dir: Option<&Path>) -> Self {
+        let path = state_dir.map(|dir| dir.join(USAGE_FILE));
+        let mut records = Vec::new();
+
+        if let Some(path) = &path {
+            match std::fs::read(path) {
+                Ok(bytes) => match serde_json::from_slice(&bytes) {
+                    Ok(loaded) => records = loaded,
+                    Err(e) => warn!(
+                        path = %path.display(),
+                        error = %e,
+                        "Ignoring unreadable usage file"
+                    ),
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!(
+                    path = %path.display(),
+                    error = %e,
+                    "Failed to read usage file"
+                ),
+            }
+        }
+
+        Self {
+            records: Mutex::new(records),
+            path,
+        }
+    }
+
+    fn records(&self) -> std::sync::MutexGuard<'_, Vec<UsageRecord>> {
+        self.records
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Adds a turn that used `usage` and cost `cost_usd` to the usage of `session_id` today.
+    pub fn record(
+        &self,
+        session_id: &str,
+        working_dir: &Path,
+        owner: Option<&str>,
+        usage: Option<&TokenUsage>,
+        cost_usd: f64,
+    ) {
+        let day = chrono::Utc::now().date_naive();
+        let mut records = self.records();
+        let index = records
+            .iter()
+            .position(|record| record.session_id == session_id && record.day == day)
+            .unwrap_or_else(|| {
+                records.push(UsageRecord {
+                    session_id: session_id.to_string(),
+                    day,
+                    working_dir: working_dir.to_path_buf(),
+                    owner: owner.map(str::to_string),
+                    totals: UsageTotals::default(),
+                });
+                records.len() - 1
+            });
+        records[index].totals.add_turn(usage, cost_usd);
+        self.persist(&records);
+    }
+
+    /// Usage of `session_id` over all days, if it completed a turn.
+    #[must_use]
+    pub fn session_totals(&self, session_id: &str) -> Option<UsageTotals> {
+        self.records()
+            .iter()
+            .filter(|record| record.session_id == session_id)
+            .fold(None, |totals: Option<UsageTotals>, record| {
+                let mut totals = totals.unwrap_or_default();
+                totals.add(&record.totals);
+                Some(totals)
+            })
+    }
+
+    /// Usage from `since` to `until` (inclusive) of the sessions `include` accepts the
+    /// owner of, in total, per day and per working directory.
+    #[must_use]
+    pub fn report(
+        &self,
+        since: Option<NaiveDate>,
+        until: Option<NaiveDate>,
+        include: impl Fn(Option<&str>) -> bool,
+    ) -> UsageResponse {
+        let mut total = UsageTotals::default();
+        let mut by_day: BTreeMap<NaiveDate, UsageTotals> = BTreeMap::new();
+        let mut by_working_dir: BTreeMap<PathBuf, UsageTotals> = BTreeMap::new();
+
+        for record in self.records().iter().filter(|record| {
+            since.is_none_or(|since| record.day >= since)
+                && until.is_none_or(|until| record.day <= until)
+                && include(record.owner.as_deref())
+        }) {
+            total.add(&record.totals);
+            by_day.entry(record.day).or_default().add(&record.totals);
+            by_working_dir
+                .entry(record.working_dir.clone())
+                .or_default()
+                .add(&record.totals);
+        }
+
+        UsageResponse {
+            total,
+            by_day: by_day
+                .into_iter()
+                .map(|(day, usage)| DailyUsage { day, usage })
+                .collect(),
+            by_working_dir: by_working_dir
+                .into_iter()
+                .map(|(working_dir, usage)| WorkingDirUsage { working_dir, usage })
+                .collect(),
+        }
+    }
+
+    fn persist(&self, records: &[UsageRecord]) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let tmp_path = path.with_extension("json.tmp");
+        let result = serde_json::to_vec(records)
+            .map_err(std::io::Error::from)
+            .and_then(|bytes| std::fs::write(&tmp_path, bytes))
+            .and_then(|()| std::fs::rename(&tmp_path, path));
+        if let Err(e) = result {
+            warn!(path = %path.display(), error = %e, "Failed to persist usage");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn usage(input_tokens: u64, output_tokens: u64) -> TokenUsage {
+        TokenUsage {
+            input_tokens,
+            output_tokens,
+            cache_read_input_tokens: 10,
+            cache_creation_input_tokens: 0,
+        }
+    }
+
+    #[test]
+    fn test_usage_ledger() {
+        let state_dir = TempDir::new().unwrap();
+        let ledger = UsageLedger::load(Some(state_dir.path()));
+        let a = Path::new("/work/a");
+        ledger.record("s1", a, Some("alice"), Some(&usage(100, 20)), 0.5);
+        ledger.record("s1", a, Some("alice"), Some(&usage(50, 5)), 0.25);
+        ledger.record("s2", Path::new("/work/b"), Some("bob"), None, 0.0);
+
+        let s1 = ledger.session_totals("s1").unwrap();
+        assert_eq!(s1.turns, 2);
+        assert_eq!(s1.input_tokens, 150);
+        assert_eq!(s1.output_tokens, 25);
+        assert_eq!(s1.cache_read_input_tokens, 20);
+        assert_eq!(ledger.session_totals("s2").unwrap().input_tokens, 0);
+        assert!((s1.cost_usd - 0.75).abs() < f64::EPSILON);
+        assert_eq!(ledger.session_totals("s3"), None);
+
+        // Persisted usage is there after a restart
+        let ledger = UsageLedger::load(Some(state_dir.path()));
+        let report = ledger.report(None, None, |_| true);
+        assert_eq!(report.total.turns, 3);
+        assert_eq!(report.by_day.len(), 1);
+        assert_eq!(report.by_day[0].usage.turns, 3);
+        assert_eq!(report.by_working_dir.len(), 2);
+        assert_eq!(report.by_working_dir[0].working_dir, Path::new("/work/a"));
+        assert_eq!(report.by_working_dir[0].usage.input_tokens, 150);
+
+        let alice = ledger.report(None, None, |owner| owner == Some("alice"));
+        assert_eq!(alice.total.turns, 2);
+        assert_eq!(alice.by_working_dir.len(), 1);
+
+        let tomorrow = chrono::Utc::now().date_naive().succ_opt().unwrap();
+        assert_eq!(ledger.report(Some(tomorrow), None, |_| true).total.turns, 0);
+    }
+}
@@ -0,0 +1,646 @@
+use crate::config::{ApiKeys, WebPushSettings};
+use crate::error::{OrchestratorError, OrchestratorResult};
+use crate::notifications::{Notification, WatchEvent, DELIVERY_TIMEOUT};
+use anyhow::Context;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use dashmap::DashMap;
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{EcdsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+use ring::{aead, agreement, hkdf};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+const SUBSCRIPTIONS_FILE: &str = "push_subscriptions.json";
+
+/// Most browsers one identity may subscribe; the oldest subscription is dropped beyond it.
+pub const MAX_SUBSCRIPTIONS_PER_IDENTITY: usize = 20;
+
+/// How long push services hold a message for a device that is offline.
+const PUSH_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long the VAPID token of a push request stays valid; push services reject more
+/// than 24 hours.
+const VAPID_TOKEN_LIFETIME: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Notification details are cut to this many characters, keeping the encrypted payload
+/// well under the 4 KiB push services accept.
+const MAX_BODY_CHARS: usize = 1000;
+
+/// Size of the single record an encrypted payload is sent as (RFC 8188).
+const RECORD_SIZE: u32 = 4096;
+
+/// Length of an uncompressed P-256 public key.
+const P256_PUBLIC_KEY_LEN: usize = 65;
+
+/// Length of the authentication secret browsers generate per subscription.
+const AUTH_SECRET_LEN: usize = 16;
+
+/// Keys a browser generated for a subscription, as `PushSubscription.toJSON()` reports them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct PushKeys {
+    /// The browser's P-256 public key, base64url
+    pub p256dh: String,
+    /// Authentication secret, base64url
+    pub auth: String,
+}
+
+/// A browser subscribed to session notifications.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct PushSubscription {
+    /// Push service URL the browser handed out
+    pub endpoint: String,
+    pub keys: PushKeys,
+    /// Events pushed, all of them when empty
+    #[serde(default)]
+    pub events: Vec<WatchEvent>,
+    /// Sessions pushed about, every session when empty
+    #[serde(default)]
+    pub session_ids: Vec<String>,
+}
+
+impl PushSubscription {
+    fn wants(&self, notification: &Notification) -> bool {
+        (self.events.is_empty() || self.events.contains(&notification.event))
+            && (self.session_ids.is_empty() || self.session_ids.contains(&notification.session_id))
+    }
+
+    fn validate(&self) -> OrchestratorResult<()> {
+        let invalid = |message: String| Err(OrchestratorError::InvalidRequest(message));
+        match reqwest::Url::parse(&self.endpoint) {
+            Ok(url) if matches!(url.scheme(), "https" | "http") => {}
+            _ => return invalid(format!("Invalid push endpoint: {}", self.endpoint)),
+        }
+        match decode(&self.keys.p256dh) {
+            Some(key) if key.len() == P256_PUBLIC_KEY_LEN && key[0] == 0x04 => {}
+            _ => return invalid("keys.p256dh is not an uncompressed P-256 key".to_string()),
+        }
+        match decode(&self.keys.auth) {
+            Some(secret) if secret.len() == AUTH_SECRET_LEN => {}
+            _ => {
+                return invalid(format!(
+                    "keys.auth must be a {AUTH_SECRET_LEN}-byte base64url secret"
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The server's VAPID identity, which push services check push requests against.
+pub struct VapidKey {
+    key_pair: EcdsaKeyPair,
+    public_key: String,
+    subject: String,
+}
+
+impl VapidKey {
+    /// Parses the configured key pair.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a key is not valid base64url, or the keys are not a P-256
+    /// key pair.
+    pub fn new(settings: &WebPushSettings) -> anyhow::Result<Self> {
+        let public_key = decode(&settings.public_key)
+            .context("Invalid WEB_PUSH_VAPID_PUBLIC_KEY value: not base64url")?;
+        let private_key = decode(&settings.private_key)
+            .context("Invalid WEB_PUSH_VAPID_PRIVATE_KEY value: not base64url")?;
+        let key_pair = EcdsaKeyPair::from_private_key_and_public_key(
+            &ECDSA_P256_SHA256_FIXED_SIGNING,
+            &private_key,
+            &public_key,
+            &SystemRandom::new(),
+        )
+        .map_err(|e| {
+            anyhow::anyhow!("Invalid WEB_PUSH_VAPID_* keys: not a P-256 key pair ({e})")
+        })?;
+        Ok(Self {
+            key_pair,
+            public_key: URL_SAFE_NO_PAD.encode(public_key),
+            subject: settings.subject.clone(),
+        })
+    }
+
+    /// `Authorization` header value for a push request to `endpoint` (RFC 8292).
+    fn authorization(&self, endpoint: &str) -> Result<String, String> {
+        let url = reqwest::Url::parse(endpoint).map_err(|e| e.to_string())?;
+        let expires = chrono::Utc::now()
+            + chrono::Duration::from_std(VAPID_TOKEN_LIFETIME).map_err(|e| e.to_string())?;
+        let header = URL_SAFE_NO_PAD.encode(br#"{"typ":"JWT","alg":"ES256"}"#);
+        let claims = URL_SAFE_NO_PAD.encode(
+            serde_json::json!({
+                "aud": url.origin().ascii_serialization(),
+                "exp": expires.timestamp(),
+                "sub": self.subject,
+            })
+            .to_string(),
+        );
+        let signing_input = format!("{header}.{claims}");
+        let signature = self
+            .key_pair
+            .sign(&SystemRandom::new(), signing_input.as_bytes())
+            .map_err(|_| "failed to sign VAPID token".to_string())?;
+        Ok(format!(
+            "vapid t={signing_input}.{}, k={}",
+            URL_SAFE_NO_PAD.encode(signature),
+            self.public_key
+        ))
+    }
+}
+
+/// Why a push was not delivered.
+enum PushFailure {
+    /// The push service no longer knows the subscription
+    Gone,
+    Failed(String),
+}
+
+/// Browsers subscribed to push notifications, per identity, and their delivery.
+pub struct WebPush {
+    vapid: VapidKey,
+    /// Identity -> its subscriptions, oldest first
+    subscriptions: DashMap<String, Vec<PushSubscription>>,
+    /// Where subscriptions are persisted, if a session state directory is configured
+    path: Option<PathBuf>,
+    /// Tell which identities are admins, who hear about every session
+    api_keys: ApiKeys,
+    client: reqwest::Client,
+}
+
+impl WebPush {
+    /// Loads the subscriptions persisted in `state_dir`, or starts without any.
+    #[must_use]
+    pub fn load(vapid: VapidKey, state_dir: Option<&Path>, api_keys: ApiKeys) -> Self {
+        let path = state_dir.map(|dir| dir.join(SUBSCRIPTIONS_FILE));
+        let mut subscriptions = DashMap::new();
+
+        if let Some(path) = &path {
+            match std::fs::read(path) {
+                Ok(bytes) => {
+                    match serde_json::from_slice::<HashMap<String, Vec<PushSubscription>>>(&bytes) {
+                        Ok(map) => subscriptions.extend(map),
+                        Err(e) => warn!(
+                            path = %path.display(),
+                            error = %e,
+                            "Ignoring unreadable push subscriptions file"
+                        ),
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!(
+                    path = %path.display(),
+                    error = %e,
+                    "Failed to read push subscriptions file"
+                ),
+            }
+        }
+
+        Self {
+            vapid,
+            subscriptions,
+            path,
+            api_keys,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// The VAPID public key browsers subscribe with, base64url.
+    #[must_use]
+    pub fn public_key(&self) -> &str {
+        &self.vapid.public_key
+    }
+
+    /// The subscriptions of `identity`, oldest first.
+    #[must_use]
+    pub fn subscriptions(&self, identity: &str) -> Vec<PushSubscription> {
+        self.subscriptions
+            .get(identity)
+            .map(|subscriptions| subscriptions.clone())
+            .unwrap_or_default()
+    }
+
+    /// Subscribes a browser for `identity`, replacing an earlier subscription of the
+    /// same endpoint.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the endpoint is not a URL or the keys are malformed.
+    pub fn subscribe(
+        &self,
+        identity: &str,
+        mut subscription: PushSubscription,
+    ) -> OrchestratorResult<()> {
+        subscription.validate()?;
+        subscription.events.sort_unstable();
+        subscription.events.dedup();
+        {
+            let mut subscriptions = self.subscriptions.entry(identity.to_string()).or_default();
+            subscriptions.retain(|existing| existing.endpoint != subscription.endpoint);
+            subscriptions.push(subscription);
+            let excess = subscriptions
+                .len()
+                .saturating_sub(MAX_SUBSCRIPTIONS_PER_IDENTITY);
+            subscriptions.drain(..excess);
+        }
+        debug!(identity = %identity, "Stored push subscription");
+        self.persist();
+        Ok(())
+    }
+
+    /// Removes the subscription of `endpoint`; returns whether there was one.
+    pub fn unsubscribe(&self, identity: &str, endpoint: &str) -> bool {
+        let mut removed = false;
+        self.subscriptions
+            .remove_if_mut(identity, |_, subscriptions| {
+                let before = subscriptions.len();
+                subscriptions.retain(|subscription| subscription.endpoint != endpoint);
+                removed = subscriptions.len() != before;
+                subscriptions.is_empty()
+            });
+        if removed {
+            debug!(identity = %identity, "Removed push subscription");
+            self.persist();
+        }
+        removed
+    }
+
+    /// Points subscriptions limited to a session at the ID it was resumed under.
+    pub fn rename_session(&self, old_session_id: &str, new_session_id: &str) {
+        let mut renamed = false;
+        for mut subscriptions in self.subscriptions.iter_mut() {
+            for id in subscriptions
+                .iter_mut()
+                .flat_map(|subscription| subscription.session_ids.iter_mut())
+                .filter(|id| id.as_str() == old_session_id)
+            {
+                new_session_id.clone_into(id);
+                renamed = true;
+            }
+        }
+        if renamed {
+            self.persist();
+        }
+    }
+
+    /// Whether `identity` may hear about a session of `owner`, by the same rules as
+    /// [`Caller::can_access`](crate::api::auth::Caller::can_access): everyone is an admin
+    /// unless API keys belong to users.
+    fn may_access(&self, identity: &str, owner: Option<&str>) -> bool {
+        !self.api_keys.has_users() || self.api_keys.is_admin(identity) || owner == Some(identity)
+    }
+
+    /// Pushes a notification to every subscription that wants it and whose identity may
+    /// access the session. Deliveries run in the background; subscriptions the push
+    /// service no longer knows are dropped.
+    pub fn dispatch(self: &Arc<Self>, notification: &Notification) {
+        let recipients: Vec<(String, PushSubscription)> = self
+            .subscriptions
+            .iter()
+            .filter(|entry| self.may_access(entry.key(), notification.owner.as_deref()))
+            .flat_map(|entry| {
+                entry
+                    .value()
+                    .iter()
+                    .filter(|subscription| subscription.wants(notification))
+                    .map(|subscription| (entry.key().clone(), subscription.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        if recipients.is_empty() {
+            return;
+        }
+
+        let payload = Arc::new(payload(notification));
+        // Approvals block Claude until someone answers and crashes need someone to look, so
+        // they may wake the device
+        let urgency = match notification.event {
+            WatchEvent::ApprovalNeeded | WatchEvent::Crashed => "high",
+            WatchEvent::Completed | WatchEvent::Failed => "normal",
+        };
+        for (identity, subscription) in recipients {
+            let push = self.clone();
+            let payload = payload.clone();
+            let session_id = notification.session_id.clone();
+            let event = notification.event;
+            tokio::spawn(async move {
+                let delivery = tokio::time::timeout(
+                    DELIVERY_TIMEOUT,
+                    push.send(&subscription, &payload, urgency),
+                )
+                .await
+                .unwrap_or_else(|_| {
+                    Err(PushFailure::Failed(format!(
+                        "timed out after {}s",
+                        DELIVERY_TIMEOUT.as_secs()
+                    )))
+                });
+                match delivery {
+                    Ok(()) => info!(
+                        session_id = %session_id,
+                        identity = %identity,
+                        event = ?event,
+                        "Pushed session notification"
+                    ),
+                    Err(PushFailure::Gone) => {
+                        info!(
+                            identity = %identity,
+                            "Push subscription expired, removing it"
+                        );
+                        push.unsubscribe(&identity, &subscription.endpoint);
+                    }
+                    Err(PushFailure::Failed(e)) => warn!(
+                        session_id = %session_id,
+                        identity = %identity,
+                        event = ?event,
+                        error = %e,
+                        "Failed to push session notification"
+                    ),
+                }
+            });
+        }
+    }
+
+    async fn send(
+        &self,
+        subscription: &PushSubscription,
+        payload: &[u8],
+        urgency: &str,
+    ) -> Result<(), PushFailure> {
+        let (Some(ua_public), Some(auth_secret)) = (
+            decode(&subscription.keys.p256dh),
+            decode(&subscription.keys.auth),
+        ) else {
+            return Err(PushFailure::Failed(
+                "malformed subscription keys".to_string(),
+            ));
+        };
+        let body = encrypt(payload, &ua_public, &auth_secret).map_err(PushFailure::Failed)?;
+        let authorization = self
+            .vapid
+            .authorization(&subscription.endpoint)
+            .map_err(PushFailure::Failed)?;
+        let response = self
+            .client
+            .post(&subscription.endpoint)
+            .header(reqwest::header::AUTHORIZATION, authorization)
+            .header(reqwest::header::CONTENT_ENCODING, "aes128gcm")
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .header("TTL", PUSH_TTL.as_secs().to_string())
+            .header("Urgency", urgency)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| PushFailure::Failed(e.to_string()))?;
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::GONE => Err(PushFailure::Gone),
+            status => Err(PushFailure::Failed(format!(
+                "push service answered {status}"
+            ))),
+        }
+    }
+
+    fn persist(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let snapshot: HashMap<String, Vec<PushSubscription>> = self
+            .subscriptions
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        let tmp_path = path.with_extension("json.tmp");
+        let result = serde_json::to_vec(&snapshot)
+            .map_err(std::io::Error::from)
+            .and_then(|bytes| std::fs::write(&tmp_path, bytes))
+            .and_then(|()| std::fs::rename(&tmp_path, path));
+        if let Err(e) = result {
+            warn!(path = %path.display(), error = %e, "Failed to persist push subscriptions");
+        }
+    }
+}
+
+/// What the service worker receives: enough to show a notification and open the session.
+fn payload(notification: &Notification) -> Vec<u8> {
+    let body: String = notification.detail.chars().take(MAX_BODY_CHARS).collect();
+    serde_json::json!({
+        "title": notification.summary(),
+        "body": body,
+        "event": notification.event,
+        "session_id": notification.session_id,
+        "timestamp": notification.timestamp,
+    })
+    .to_string()
+    .into_bytes()
+}
+
+/// Decodes base64url, with or without padding.
+fn decode(value: &str) -> Option<Vec<u8>> {
+    URL_SAFE_NO_PAD.decode(value.trim_end_matches('=')).ok()
+}
+
+struct OutputLen(usize);
+
+impl hkdf::KeyType for OutputLen {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+fn hkdf_sha256(salt: &[u8], ikm: &[u8], info: &[u8], len: usize) -> Vec<u8> {
+    let info = [info];
+    let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, salt).extract(ikm);
+    let okm = prk
+        .expand(&info, OutputLen(len))
+        .expect("HKDF output is far below its limit");
+    let mut output = vec![0; len];
+    okm.fill(&mut output)
+        .expect("output buffer has the requested length");
+    output
+}
+
+/// Encrypts a push payload for a browser as a single `aes128gcm` record (RFC 8291).
+fn encrypt(payload: &[u8], ua_public: &[u8], auth_secret: &[u8]) -> Result<Vec<u8>, String> {
+    let rng = SystemRandom::new();
+    let as_private = agreement::EphemeralPrivateKey::generate(&agreement::ECDH_P256, &rng)
+        .map_err(|_| "failed to generate ECDH key".to_string())?;
+    let as_public = as_private
+        .compute_public_key()
+        .map_err(|_| "failed to compute ECDH public key".to_string())?;
+    let ecdh_secret = agreement::agree_ephemeral(
+        as_private,
+        &agreement::UnparsedPublicKey::new(&agreement::ECDH_P256, ua_public),
+        <[u8]>::to_vec,
+    )
+    .map_err(|_| "subscription key is not a valid P-256 point".to_string())?;
+
+    let mut key_info = b"WebPush: info\0".to_vec();
+    key_info.extend_from_slice(ua_public);
+    key_info.extend_from_slice(as_public.as_ref());
+    let ikm = hkdf_sha256(auth_secret, &ecdh_secret, &key_info, 32);
+
+    let mut salt = [0; 16];
+    rng.fill(&mut salt)
+        .map_err(|_| "failed to generate salt".to_string())?;
+    let cek = hkdf_sha256(&salt, &ikm, b"Content-Encoding: aes128gcm\0", 16);
+    let nonce = hkdf_sha256(&salt, &ikm, b"Content-Encoding: nonce\0", 12);
+
+    let key = aead::LessSafeKey::new(
+        aead::UnboundKey::new(&aead::AES_128_GCM, &cek).map_err(|_| "invalid key".to_string())?,
+    );
+    let nonce =
+        aead::Nonce::try_assume_unique_for_key(&nonce).map_err(|_| "invalid nonce".to_string())?;
+    let mut record = payload.to_vec();
+    // Padding delimiter of the last (and only) record
+    record.push(0x02);
+    key.seal_in_place_append_tag(nonce, aead::Aad::empty(), &mut record)
+        .map_err(|_| "failed to encrypt payload".to_string())?;
+
+    let as_public = as_public.as_ref();
+    let mut body = Vec::with_capacity(salt.len() + 5 + as_public.len() + record.len());
+    body.extend_from_slice(&salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(u8::try_from(as_public.len()).expect("P-256 public keys are 65 bytes"));
+    body.extend_from_slice(as_public);
+    body.extend_from_slice(&record);
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const PUBLIC_KEY: &str =
+        "BAG2w1eER_omF5yZN6dq6NKLG39fOGEaVfK1K6lcjePK6ZvNxvnp0jpKMmFYmSxSI4Fh9iffgeAJui-be11nu3k";
+    const PRIVATE_KEY: &str = "mBGjayvOqZij-n__y-Q0JhArEdEJRkr3u1K_1WBnGaA";
+
+    fn vapid() -> VapidKey {
+        VapidKey::new(&WebPushSettings {
+            public_key: PUBLIC_KEY.to_string(),
+            private_key: PRIVATE_KEY.to_string(),
+            subject: "mailto:ops@example.com".to_string(),
+        })
+        .unwrap()
+    }
+
+    fn subscription(endpoint: &str) -> PushSubscription {
+        PushSubscription {
+            endpoint: endpoint.to_string(),
+            keys: PushKeys {
+                p256dh: PUBLIC_KEY.to_string(),
+                auth: URL_SAFE_NO_PAD.encode([7; AUTH_SECRET_LEN]),
+            },
+            events: Vec::new(),
+            session_ids: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_encrypt_round_trip() {
+        let rng = SystemRandom::new();
+        let ua_private =
+            agreement::EphemeralPrivateKey::generate(&agreement::ECDH_P256, &rng).unwrap();
+        let ua_public = ua_private.compute_public_key().unwrap();
+        let auth_secret = [9; AUTH_SECRET_LEN];
+
+        let body = encrypt(b"hello", ua_public.as_ref(), &auth_secret).unwrap();
+        let (salt, rest) = body.split_at(16);
+        assert_eq!(rest[..4], RECORD_SIZE.to_be_bytes());
+        assert_eq!(usize::from(rest[4]), P256_PUBLIC_KEY_LEN);
+        let (as_public, record) = rest[5..].split_at(P256_PUBLIC_KEY_LEN);
+
+        // Decrypt the way the browser does
+        let ecdh_secret = agreement::agree_ephemeral(
+            ua_private,
+            &agreement::UnparsedPublicKey::new(&agreement::ECDH_P256, as_public),
+            <[u8]>::to_vec,
+        )
+        .unwrap();
+        let mut key_info = b"WebPush: info\0".to_vec();
+        key_info.extend_from_slice(ua_public.as_ref());
+        key_info.extend_from_slice(as_public);
+        let ikm = hkdf_sha256(&auth_secret, &ecdh_secret, &key_info, 32);
+        let cek = hkdf_sha256(salt, &ikm, b"Content-Encoding: aes128gcm\0", 16);
+        let nonce = hkdf_sha256(salt, &ikm, b"Content-Encoding: nonce\0", 12);
+        let key = aead::LessSafeKey::new(aead::UnboundKey::new(&aead::AES_128_GCM, &cek).unwrap());
+        let mut record = record.to_vec();
+        let plaintext = key
+            .open_in_place(
+                aead::Nonce::try_assume_unique_for_key(&nonce).unwrap(),
+                aead::Aad::empty(),
+                &mut record,
+            )
+            .unwrap();
+        assert_eq!(plaintext, b"hello\x02");
+    }
+
+    #[test]
+    fn test_vapid_authorization() {
+        let authorization = vapid()
+            .authorization("https://push.example.com/send/abc?x=1")
+            .unwrap();
+        let (token, key) = authorization
+            .strip_prefix("vapid t=")
+            .unwrap()
+            .split_once(", k=")
+            .unwrap();
+        assert_eq!(key, PUBLIC_KEY);
+
+        let (signing_input, signature) = token.rsplit_once('.').unwrap();
+        ring::signature::UnparsedPublicKey::new(
+            &ring::signature::ECDSA_P256_SHA256_FIXED,
+            decode(PUBLIC_KEY).unwrap(),
+        )
+        .verify(signing_input.as_bytes(), &decode(signature).unwrap())
+        .unwrap();
+        let claims: serde_json::Value =
+            serde_json::from_slice(&decode(signing_input.split_once('.').unwrap().1).unwrap())
+                .unwrap();
+        assert_eq!(claims["aud"], "https://push.example.com");
+        assert_eq!(claims["sub"], "mailto:ops@example.com");
+
+        let mismatched = WebPushSettings {
+            public_key: PUBLIC_KEY.to_string(),
+            private_key: URL_SAFE_NO_PAD.encode([1; 32]),
+            subject: "mailto:ops@example.com".to_string(),
+        };
+        assert!(VapidKey::new(&mismatched).is_err());
+    }
+
+    #[test]
+    fn test_subscriptions_persist() {
+        let state_dir = TempDir::new().unwrap();
+        let push = WebPush::load(vapid(), Some(state_dir.path()), ApiKeys::default());
+
+        let mut invalid = subscription("https://push.example.com/a");
+        invalid.keys.auth = "c2hvcnQ".to_string();
+        assert!(push.subscribe("alice", invalid).is_err());
+        assert!(push.subscribe("alice", subscription("not a url")).is_err());
+
+        push.subscribe("alice", subscription("https://push.example.com/a"))
+            .unwrap();
+        let mut limited = subscription("https://push.example.com/b");
+        limited.session_ids = vec!["s1".to_string()];
+        limited.events = vec![WatchEvent::Failed, WatchEvent::Failed];
+        push.subscribe("alice", limited).unwrap();
+        push.rename_session("s1", "s2");
+
+        let reloaded = WebPush::load(vapid(), Some(state_dir.path()), ApiKeys::default());
+        let subscriptions = reloaded.subscriptions("alice");
+        assert_eq!(subscriptions.len(), 2);
+        assert_eq!(subscriptions[1].session_ids, vec!["s2".to_string()]);
+        assert_eq!(subscriptions[1].events, vec![WatchEvent::Failed]);
+        assert!(reloaded.subscriptions("bob").is_empty());
+
+        assert!(reloaded.unsubscribe("alice", "https://push.example.com/a"));
+        assert!(!reloaded.unsubscribe("alice", "https://push.example.com/a"));
+        assert_eq!(reloaded.subscriptions("alice").len(), 1);
+    }
+}
@@ -0,0 +1,265 @@
+use crate::config::WireLogSettings;
+use crate::debug_capture::Direction;
+use crate::error::{OrchestratorError, OrchestratorResult};
+use crate::models::is_valid_session_id;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// One line relayed between clients and Claude, as written to the wire log.
+#[derive(Serialize)]
+struct WireEntry<'a> {
+    timestamp: String,
+    direction: &'static str,
+    /// Client that sent the line, or identity that answered an approval
+    #[serde(skip_serializing_if = "Option::is_none")]
+    origin: Option<&'a str>,
+    line: &'a str,
+}
+
+/// File `generation` of a session's wire log: `0` is the one being written, `1` the
+/// most recently rotated one, and so on.
+fn path(dir: &Path, session_id: &str, generation: usize) -> PathBuf {
+    if generation == 0 {
+        dir.join(format!("{session_id}.wire.jsonl"))
+    } else {
+        dir.join(format!("{session_id}.wire.jsonl.{generation}"))
+    }
+}
+
+/// Rotating log of every line a session wrote to and read from Claude.
+#[derive(Debug)]
+pub struct WireLog {
+    settings: WireLogSettings,
+    session_id: String,
+    file: File,
+    written: u64,
+}
+
+impl WireLog {
+    /// Opens the wire log of `session_id`, continuing what a previous run of the session
+    /// wrote.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ID cannot name a file or the file cannot be opened.
+    pub fn open(settings: &WireLogSettings, session_id: &str) -> std::io::Result<Self> {
+        if !is_valid_session_id(session_id) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Session ID {session_id} cannot name a wire log"),
+            ));
+        }
+        let file = Self::open_file(settings, session_id)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            settings: settings.clone(),
+            session_id: session_id.to_string(),
+            file,
+            written,
+        })
+    }
+
+    fn open_file(settings: &WireLogSettings, session_id: &str) -> std::io::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path(&settings.dir, session_id, 0))
+    }
+
+    /// Appends one relayed `line`, with where it came from when a client sent it.
+    /// Failures are logged, the traffic itself is unaffected.
+    pub fn record(&mut self, direction: Direction, origin: Option<&str>, line: &str) {
+        let entry = WireEntry {
+            timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            direction: match direction {
+                Direction::Stdin => "stdin",
+                Direction::Stdout => "stdout",
+            },
+            origin,
+            line,
+        };
+        let Ok(mut bytes) = serde_json::to_vec(&entry) else {
+            return;
+        };
+        bytes.push(b'\n');
+        let len = u64::try_from(bytes.len()).unwrap_or(u64::MAX);
+        let result = if self.written > 0
+            && self.written.saturating_add(len) > self.settings.max_file_bytes
+        {
+            self.rotate()
+        } else {
+            Ok(())
+        }
+        // One write per entry keeps a reader from seeing half an entry
+        .and_then(|()| self.file.write_all(&bytes));
+        match result {
+            Ok(()) => self.written = self.written.saturating_add(len),
+            Err(e) => warn!(
+                session_id = %self.session_id,
+                dir = %self.settings.dir.display(),
+                error = %e,
+                "Failed to write wire log"
+            ),
+        }
+    }
+
+    /// Starts a new file, dropping the oldest one once `max_files` are kept.
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let dir = &self.settings.dir;
+        let oldest = self.settings.max_files - 1;
+        match std::fs::remove_file(path(dir, &self.session_id, oldest)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        for generation in (0..oldest).rev() {
+            match std::fs::rename(
+                path(dir, &self.session_id, generation),
+                path(dir, &self.session_id, generation + 1),
+            ) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        self.file = Self::open_file(&self.settings, &self.session_id)?;
+        self.written = 0;
+        Ok(())
+    }
+
+    /// Moves the log's files to the session's new ID, where it continues.
+    pub fn rename(&mut self, session_id: &str) {
+        if !is_valid_session_id(session_id) {
+            warn!(session_id = %session_id, "Session ID cannot name a wire log, keeping the old one");
+            return;
+        }
+        let dir = &self.settings.dir;
+        for generation in 0..self.settings.max_files {
+            let from = path(dir, &self.session_id, generation);
+            match std::fs::rename(&from, path(dir, session_id, generation)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => warn!(
+                    path = %from.display(),
+                    error = %e,
+                    "Failed to move wire log to the new session ID"
+                ),
+                _ => {}
+            }
+        }
+        // The open file moved along with its name
+        self.session_id = session_id.to_string();
+    }
+}
+
+/// Everything kept of a session's wire log, oldest entries first. The last entry may
+/// be incomplete while it is being written.
+///
+/// # Errors
+///
+/// Returns an error if the ID cannot name a file, the session has no wire log, or it
+/// cannot be read.
+pub async fn read(settings: &WireLogSettings, session_id: &str) -> OrchestratorResult<Vec<u8>> {
+    if !is_valid_session_id(session_id) {
+        return Err(OrchestratorError::InvalidRequest(format!(
+            "Session ID {session_id} cannot name a wire log"
+        )));
+    }
+    let mut contents = None::<Vec<u8>>;
+    for generation in (0..settings.max_files).rev() {
+        let path = path(&settings.dir, session_id, generation);
+        match tokio::fs::read(&path).await {
+            Ok(bytes) => contents.get_or_insert_with(Vec::new).extend(bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(OrchestratorError::InternalError(format!(
+                    "Failed to read wire log {}: {e}",
+                    path.display()
+                )))
+            }
+        }
+    }
+    contents.ok_or_else(|| OrchestratorError::WireLogNotFound(session_id.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn settings(dir: &Path, max_file_bytes: u64, max_files: usize) -> WireLogSettings {
+        WireLogSettings {
+            dir: dir.to_path_buf(),
+            max_file_bytes,
+            max_files,
+        }
+    }
+
+    fn entries(contents: &[u8]) -> Vec<serde_json::Value> {
+        String::from_utf8_lossy(contents)
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_wire_log_rotates_and_reads_oldest_first() {
+        let dir = TempDir::new().unwrap();
+        // Room for about two entries per file
+        let settings = settings(dir.path(), 200, 2);
+        let mut log = WireLog::open(&settings, "s1").unwrap();
+        log.record(Direction::Stdin, Some("client-1"), r#"{"role":"user"}"#);
+        log.record(Direction::Stdout, None, "line 2");
+        log.record(Direction::Stdout, None, "line 3");
+        log.record(Direction::Stdout, None, "line 4");
+        log.record(Direction::Stdout, None, "line 5");
+
+        let first = entries(&std::fs::read(dir.path().join("s1.wire.jsonl.1")).unwrap());
+        assert_eq!(first[0]["line"], "line 3");
+        assert!(!dir.path().join("s1.wire.jsonl.2").exists());
+
+        let all = entries(&read(&settings, "s1").await.unwrap());
+        let lines: Vec<_> = all.iter().map(|entry| entry["line"].clone()).collect();
+        assert_eq!(lines, ["line 3", "line 4", "line 5"]);
+
+        // A resumed session continues its log under the new ID
+        log.rename("s2");
+        log.record(Direction::Stdin, Some("client-2"), "line 6");
+        assert!(matches!(
+            read(&settings, "s1").await,
+            Err(OrchestratorError::WireLogNotFound(_))
+        ));
+        let all = entries(&read(&settings, "s2").await.unwrap());
+        let last = all.last().unwrap();
+        assert_eq!(last["direction"], "stdin");
+        assert_eq!(last["origin"], "client-2");
+        assert_eq!(last["line"], "line 6");
+
+        assert!(matches!(
+            read(&settings, "../s2").await,
+            Err(OrchestratorError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_wire_log_keeps_direction_and_origin() {
+        let dir = TempDir::new().unwrap();
+        let settings = settings(dir.path(), 1024, 3);
+        let mut log = WireLog::open(&settings, "s1").unwrap();
+        log.record(Direction::Stdin, Some("client-1"), r#"{"role":"user"}"#);
+        log.record(Direction::Stdout, None, r#"{"type":"assistant"}"#);
+        drop(log);
+
+        // Reopening appends instead of starting over
+        let mut log = WireLog::open(&settings, "s1").unwrap();
+        log.record(Direction::Stdout, None, "not json");
+
+        let all = entries(&std::fs::read(dir.path().join("s1.wire.jsonl")).unwrap());
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0]["direction"], "stdin");
+        assert_eq!(all[0]["origin"], "client-1");
+        assert_eq!(all[0]["line"], r#"{"role":"user"}"#);
+        assert_eq!(all[1]["direction"], "stdout");
+        assert!(all[1].get("origin").is_none());
+        assert!(all[2]["timestamp"].as_str().unwrap().ends_with('Z'));
+    }
+}
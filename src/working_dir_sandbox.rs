@@ -0,0 +1,138 @@
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+/// Who tool requests the sandbox denies are attributed to.
+pub const SANDBOX_IDENTITY: &str = "working-dir-sandbox";
+
+/// Fields of a tool's input naming the file or directory it works on.
+const PATH_FIELDS: [&str; 3] = ["file_path", "notebook_path", "path"];
+
+/// What happens to tool requests whose paths resolve outside the session's working
+/// directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SandboxMode {
+    /// Nothing; approvers decide
+    #[default]
+    Off,
+    /// The request is sent to approvers with a risk reason naming the path
+    Flag,
+    /// The request is denied without asking approvers
+    Deny,
+}
+
+impl FromStr for SandboxMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "flag" => Ok(Self::Flag),
+            "deny" => Ok(Self::Deny),
+            other => anyhow::bail!("expected 'off', 'flag' or 'deny', got '{other}'"),
+        }
+    }
+}
+
+/// The first path of a `can_use_tool` request that resolves outside `working_dir`,
+/// following symlinks as far as the path exists. Relative paths are taken from
+/// `working_dir`.
+#[must_use]
+pub fn escaping_path(request: &serde_json::Value, working_dir: &Path) -> Option<PathBuf> {
+    if request.get("subtype").and_then(serde_json::Value::as_str) != Some("can_use_tool") {
+        return None;
+    }
+    let input = request.get("input")?;
+    let working_dir = resolve(working_dir, Path::new("/"));
+    PATH_FIELDS
+        .iter()
+        .filter_map(|field| input.get(field).and_then(serde_json::Value::as_str))
+        .filter(|path| !path.is_empty())
+        .map(|path| resolve(Path::new(path), &working_dir))
+        .find(|resolved| !resolved.starts_with(&working_dir))
+}
+
+/// Makes `path` absolute against `base` and resolves symlinks and `..` component by
+/// component, so parts that do not exist yet are still judged where they would land.
+fn resolve(path: &Path, base: &Path) -> PathBuf {
+    let mut resolved = if path.is_absolute() {
+        PathBuf::from("/")
+    } else {
+        base.to_path_buf()
+    };
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::CurDir => {}
+            Component::RootDir => resolved = PathBuf::from("/"),
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => {
+                resolved.push(name);
+                // Canonical up to here, so a later `..` leaves the real directory
+                if let Ok(real) = std::fs::canonicalize(&resolved) {
+                    resolved = real;
+                }
+            }
+        }
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn tool(name: &str, input: &serde_json::Value) -> serde_json::Value {
+        json!({"subtype": "can_use_tool", "tool_name": name, "input": input})
+    }
+
+    #[test]
+    fn test_escaping_path() {
+        let root = TempDir::new().unwrap();
+        let root = std::fs::canonicalize(root.path()).unwrap();
+        let work = root.join("work");
+        let outside = root.join("outside");
+        std::fs::create_dir_all(work.join("src")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, work.join("link")).unwrap();
+
+        let inside = [
+            json!({"file_path": work.join("src/main.rs")}),
+            json!({"file_path": "src/new/file.rs"}),
+            json!({"path": "./src/../src"}),
+            json!({"notebook_path": work.join("missing/../notes.ipynb")}),
+            json!({"command": "cat /etc/passwd"}),
+        ];
+        for input in inside {
+            assert_eq!(escaping_path(&tool("Edit", &input), &work), None, "{input}");
+        }
+
+        let escaping = [
+            (
+                json!({"file_path": root.join("elsewhere/file")}),
+                root.join("elsewhere/file"),
+            ),
+            (json!({"file_path": "../outside/x"}), outside.join("x")),
+            (json!({"file_path": "link/secret"}), outside.join("secret")),
+            (
+                json!({"path": work.join("new/../../outside")}),
+                outside.clone(),
+            ),
+        ];
+        for (input, expected) in escaping {
+            assert_eq!(
+                escaping_path(&tool("Write", &input), &work),
+                Some(expected),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_sandbox_mode() {
+        assert_eq!("Deny".parse::<SandboxMode>().unwrap(), SandboxMode::Deny);
+        assert!("warn".parse::<SandboxMode>().is_err());
+    }
+}
@@ -0,0 +1,91 @@
+use crate::config::WritePacing;
+use std::time::{Duration, Instant};
+
+/// Token bucket deciding when the next queued message may be written to Claude. It
+/// starts full, so a quiet session writes its first `burst` messages right away.
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    #[must_use]
+    pub fn new(pacing: WritePacing, now: Instant) -> Self {
+        let capacity = f64::from(pacing.burst);
+        Self {
+            rate: pacing.messages_per_second,
+            capacity,
+            tokens: capacity,
+            refilled_at: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        self.tokens = elapsed
+            .as_secs_f64()
+            .mul_add(self.rate, self.tokens)
+            .min(self.capacity);
+        self.refilled_at = now;
+    }
+
+    /// Takes a token for one write.
+    ///
+    /// # Errors
+    ///
+    /// Returns how long until a token is available when there is none.
+    pub fn try_take(&mut self, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+
+    /// How long until `count` more writes are allowed, if they are not allowed right away.
+    pub fn wait_for(&mut self, count: usize, now: Instant) -> Option<Duration> {
+        self.refill(now);
+        let count = f64::from(u32::try_from(count).unwrap_or(u32::MAX));
+        let missing = count - self.tokens;
+        (missing > 0.0).then(|| Duration::from_secs_f64(missing / self.rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(
+            WritePacing {
+                messages_per_second: 2.0,
+                burst: 2,
+            },
+            start,
+        );
+
+        // The burst goes through, then writes are spaced by the rate
+        assert_eq!(bucket.wait_for(2, start), None);
+        assert_eq!(bucket.wait_for(3, start), Some(Duration::from_millis(500)));
+        assert_eq!(bucket.try_take(start), Ok(()));
+        assert_eq!(bucket.try_take(start), Ok(()));
+        assert_eq!(bucket.try_take(start), Err(Duration::from_millis(500)));
+
+        let later = start + Duration::from_millis(250);
+        assert_eq!(bucket.try_take(later), Err(Duration::from_millis(250)));
+        let later = start + Duration::from_millis(500);
+        assert_eq!(bucket.try_take(later), Ok(()));
+
+        // An idle bucket refills up to its burst only
+        let idle = later + Duration::from_secs(60);
+        assert_eq!(bucket.wait_for(2, idle), None);
+        assert_eq!(bucket.wait_for(4, idle), Some(Duration::from_secs(1)));
+    }
+}
@@ -141,6 +141,7 @@ async fn test_claude_timeout_simulation_with_long_processing() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages: vec![create_file_command, sleep_command],
+        ..Default::default()
     };
 
     let response = client
@@ -203,6 +204,7 @@ async fn test_write_queue_clears_on_process_death() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages: vec![create_file_command],
+        ..Default::default()
     };
 
     let response = client
@@ -283,6 +285,7 @@ async fn test_write_queue_strict_fifo_ordering() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages: vec![create_file_command],
+        ..Default::default()
     };
 
     let response = client
@@ -409,6 +412,7 @@ async fn test_extremely_large_bootstrap_messages() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages: bootstrap_messages.clone(),
+        ..Default::default()
     };
 
     let response = client
@@ -495,6 +499,7 @@ async fn test_bootstrap_messages_with_invalid_json_handling() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages,
+        ..Default::default()
     };
 
     let response = client
@@ -540,6 +545,7 @@ async fn test_session_id_placeholder_cleanup_on_resume() {
         bootstrap_messages: vec![
             r#"{"role": "user", "content": "Resuming with: original-session"}"#.to_string(),
         ],
+        ..Default::default()
     };
 
     let response = client
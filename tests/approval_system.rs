@@ -3,7 +3,10 @@ mod helpers;
 use chef_de_vibe::{
     api::handlers::AppState,
     config::Config,
-    models::{CreateSessionRequest, CreateSessionResponse},
+    models::{
+        CreateSessionRequest, CreateSessionResponse, SessionApprovalRulesResponse,
+        SessionApprovalsResponse, SessionOptions,
+    },
     session_manager::SessionManager,
 };
 use futures_util::{SinkExt, StreamExt};
@@ -80,6 +83,27 @@ impl TestServer {
                 "/api/v1/sessions/:id",
                 axum::routing::get(chef_de_vibe::api::handlers::get_session),
             )
+            .route(
+                "/api/v1/sessions/:id/approvals",
+                axum::routing::get(chef_de_vibe::api::handlers::get_session_approvals)
+                    .post(chef_de_vibe::api::handlers::submit_approvals),
+            )
+            .route(
+                "/api/v1/sessions/:id/approvals/:approval_id",
+                axum::routing::post(chef_de_vibe::api::handlers::submit_approval),
+            )
+            .route(
+                "/api/v1/sessions/:id/approval_rules",
+                axum::routing::get(chef_de_vibe::api::handlers::get_approval_rules),
+            )
+            .route(
+                "/api/v1/sessions/:id/approval_rules/:rule_id",
+                axum::routing::delete(chef_de_vibe::api::handlers::delete_approval_rule),
+            )
+            .route(
+                "/api/v1/approvals/history",
+                axum::routing::get(chef_de_vibe::api::handlers::get_approval_history),
+            )
             .route(
                 "/api/v1/sessions/:id/claude_ws",
                 axum::routing::get(chef_de_vibe::api::websocket::websocket_handler),
@@ -187,6 +211,39 @@ async fn expect_approval_request(
     Err("Expected text message with approval request".into())
 }
 
+async fn next_json_frame(
+    ws: &mut tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >,
+) -> serde_json::Value {
+    let frame = timeout(Duration::from_secs(3), ws.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    serde_json::from_str(frame.to_text().unwrap()).unwrap()
+}
+
+/// Skips frames until one of type `wanted` arrives.
+async fn next_frame_of_type(
+    ws: &mut tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >,
+    wanted: &str,
+) -> serde_json::Value {
+    timeout(Duration::from_secs(3), async {
+        loop {
+            let frame = ws.next().await.unwrap().unwrap();
+            let value: serde_json::Value = serde_json::from_str(frame.to_text().unwrap()).unwrap();
+            if value["type"] == wanted {
+                return value;
+            }
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("Expected a {wanted} frame"))
+}
+
 async fn send_approval_response(
     ws: &mut tokio_tungstenite::WebSocketStream<
         tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
@@ -264,6 +321,7 @@ async fn test_approval_websocket_basic_connection() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages,
+        ..Default::default()
     };
 
     let create_response = client
@@ -415,6 +473,7 @@ async fn test_single_tool_approval_allow_flow() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages,
+        ..Default::default()
     };
 
     let create_response = client
@@ -608,6 +667,7 @@ async fn test_single_tool_approval_deny_flow() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages,
+        ..Default::default()
     };
 
     let create_response = client
@@ -856,6 +916,7 @@ async fn test_multiple_pending_approvals_accumulation() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages,
+        ..Default::default()
     };
 
     let create_response = client
@@ -1113,6 +1174,7 @@ async fn test_multiple_approval_clients_broadcast() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages,
+        ..Default::default()
     };
 
     let create_response = client
@@ -1338,3 +1400,1231 @@ async fn test_multiple_approval_clients_broadcast() {
     let _ = approval_ws3.close(None).await;
     let _ = main_ws.close(None).await;
 }
+
+#[tokio::test]
+#[serial]
+async fn test_bulk_approval_responses() {
+    let server = TestServer::new_with_approval_binary().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("approval_bulk_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let session_id = generate_unique_session_id("approval-bulk");
+    let session_file_path = server
+        .mock
+        .projects_dir()
+        .join(format!("{session_id}.jsonl"));
+    let escaped_content = format!(
+        r#"{{"sessionId": "{}", "cwd": "{}", "type": "start"}}"#,
+        session_id,
+        working_dir.display()
+    )
+    .replace('"', r#"\""#);
+
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: session_id.clone(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![format!(
+                r#"{{"control": "write_file", "path": "{}", "content": "{}"}}"#,
+                session_file_path.display(),
+                escaped_content
+            )],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+
+    let approval_ws_url = format!("{}{}", server.ws_url, session_data.approval_websocket_url);
+    let mut approval_ws = connect_approval_websocket(&approval_ws_url).await.unwrap();
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut main_ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while let Ok(Some(_)) = timeout(Duration::from_millis(200), main_ws.next()).await {}
+
+    let mut approval_ids = Vec::new();
+    for (request_id, tool) in [("bulk-1", "Read"), ("bulk-2", "Grep"), ("bulk-3", "Glob")] {
+        main_ws
+            .send(Message::Text(format!(
+                r#"{{"type": "control_request", "request_id": "{request_id}", "request": {{"subtype": "can_use_tool", "tool_name": "{tool}"}}}}"#
+            )))
+            .await
+            .unwrap();
+        approval_ids.push(
+            expect_approval_request(&mut approval_ws, tool)
+                .await
+                .unwrap(),
+        );
+    }
+
+    // One WebSocket message answers two approvals; bad items are reported, not fatal
+    let allow = serde_json::json!({"behavior": "allow", "updatedInput": {}});
+    let batch = serde_json::json!([
+        {"id": approval_ids[0], "response": allow},
+        {"id": approval_ids[0], "response": allow},
+        {"id": approval_ids[1], "response": allow},
+        {"id": "not-pending", "response": allow},
+        {"response": allow},
+    ]);
+    approval_ws
+        .send(Message::Text(batch.to_string()))
+        .await
+        .unwrap();
+    let reply = timeout(Duration::from_secs(3), approval_ws.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let reply: serde_json::Value = serde_json::from_str(reply.to_text().unwrap()).unwrap();
+    assert_eq!(reply["type"], "approval_results");
+    let accepted: Vec<bool> = reply["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["accepted"].as_bool().unwrap())
+        .collect();
+    assert_eq!(accepted, vec![true, false, true, false, false]);
+    assert_eq!(reply["results"][1]["error"], "duplicate id in batch");
+
+    // The same works over HTTP
+    let approvals_url = format!(
+        "{}/api/v1/sessions/{}/approvals",
+        server.base_url, session_data.session_id
+    );
+    let http_reply: serde_json::Value = client
+        .post(&approvals_url)
+        .json(&serde_json::json!([{"id": approval_ids[2], "response": allow}]))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(http_reply["results"][0]["accepted"], true);
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let session = server
+        .session_manager
+        .get_session(&session_data.session_id)
+        .unwrap();
+    assert!(session.get_pending_approvals().await.is_empty());
+
+    // Answered approvals are gone, so answering again is rejected
+    let http_reply: serde_json::Value = client
+        .post(&approvals_url)
+        .json(&serde_json::json!([{"id": approval_ids[2], "response": allow}]))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(http_reply["results"][0]["accepted"], false);
+    assert_eq!(
+        http_reply["results"][0]["error"],
+        "no pending approval with this id"
+    );
+
+    // Every answer made it into the approval history
+    let history: serde_json::Value = client
+        .get(format!(
+            "{}/api/v1/approvals/history?decision=allow&limit=2",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(history["total"], 3);
+    assert_eq!(history["next_offset"], 2);
+    assert_eq!(history["entries"].as_array().unwrap().len(), 2);
+
+    let history: serde_json::Value = client
+        .get(format!(
+            "{}/api/v1/approvals/history?tool=glob",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(history["total"], 1);
+    let entry = &history["entries"][0];
+    assert_eq!(entry["approval_id"], approval_ids[2].as_str());
+    assert_eq!(entry["session_id"], session_data.session_id.as_str());
+    assert_eq!(entry["decision"], "allow");
+    assert_eq!(entry["decided_by"], "default");
+    assert!(history.get("next_offset").is_none());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rest_approvals_without_websocket() {
+    let server = TestServer::new_with_approval_binary().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("approval_rest_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let session_id = generate_unique_session_id("approval-rest");
+    let session_file_path = server
+        .mock
+        .projects_dir()
+        .join(format!("{session_id}.jsonl"));
+    let escaped_content = format!(
+        r#"{{"sessionId": "{}", "cwd": "{}", "type": "start"}}"#,
+        session_id,
+        working_dir.display()
+    )
+    .replace('"', r#"\""#);
+
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: session_id.clone(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![format!(
+                r#"{{"control": "write_file", "path": "{}", "content": "{}"}}"#,
+                session_file_path.display(),
+                escaped_content
+            )],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+    let approvals_url = format!(
+        "{}/api/v1/sessions/{}/approvals",
+        server.base_url, session_data.session_id
+    );
+
+    // Nobody holds the approval WebSocket open
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut main_ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while let Ok(Some(_)) = timeout(Duration::from_millis(200), main_ws.next()).await {}
+    for (request_id, tool) in [("rest-1", "Read"), ("rest-2", "Grep")] {
+        main_ws
+            .send(Message::Text(format!(
+                r#"{{"type": "control_request", "request_id": "{request_id}", "request": {{"subtype": "can_use_tool", "tool_name": "{tool}"}}}}"#
+            )))
+            .await
+            .unwrap();
+    }
+
+    let list = || async {
+        client
+            .get(&approvals_url)
+            .send()
+            .await
+            .unwrap()
+            .json::<SessionApprovalsResponse>()
+            .await
+            .unwrap()
+    };
+    let mut pending = list().await;
+    for _ in 0..50 {
+        if pending.approvals.len() == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        pending = list().await;
+    }
+    assert_eq!(pending.session_id, session_data.session_id);
+    let tools: Vec<&str> = pending
+        .approvals
+        .iter()
+        .map(|approval| approval.request["tool_name"].as_str().unwrap())
+        .collect();
+    assert_eq!(tools, ["Read", "Grep"], "oldest first");
+
+    // The body is the bare response
+    let approval_url = format!("{approvals_url}/{}", pending.approvals[0].id);
+    let reply = client
+        .post(&approval_url)
+        .json(&serde_json::json!({"behavior": "deny", "message": "Not now"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reply.status(), 200);
+    let reply: serde_json::Value = reply.json().await.unwrap();
+    assert_eq!(reply["accepted"], true);
+    assert_eq!(reply["id"], pending.approvals[0].id.as_str());
+
+    let mut remaining = list().await;
+    for _ in 0..30 {
+        if remaining.approvals.len() == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        remaining = list().await;
+    }
+    assert_eq!(remaining.approvals.len(), 1);
+    assert_eq!(remaining.approvals[0].id, pending.approvals[1].id);
+
+    // Answering twice, malformed answers and unknown sessions are refused
+    let reply = client
+        .post(&approval_url)
+        .json(&serde_json::json!({"behavior": "allow", "updatedInput": {}}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reply.status(), 404);
+    let body: serde_json::Value = reply.json().await.unwrap();
+    assert_eq!(body["code"], "APPROVAL_NOT_FOUND");
+
+    let reply = client
+        .post(format!("{approvals_url}/{}", pending.approvals[1].id))
+        .json(&serde_json::json!("allow"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reply.status(), 400);
+
+    let reply = client
+        .get(format!(
+            "{}/api/v1/sessions/no-such-session/approvals",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reply.status(), 404);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_remembered_approvals_are_allowed_again() {
+    let server = TestServer::new_with_approval_binary().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("approval_rules_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let session_id = generate_unique_session_id("approval-rules");
+    let session_file_path = server
+        .mock
+        .projects_dir()
+        .join(format!("{session_id}.jsonl"));
+    let escaped_content = format!(
+        r#"{{"sessionId": "{}", "cwd": "{}", "type": "start"}}"#,
+        session_id,
+        working_dir.display()
+    )
+    .replace('"', r#"\""#);
+
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: session_id.clone(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![format!(
+                r#"{{"control": "write_file", "path": "{}", "content": "{}"}}"#,
+                session_file_path.display(),
+                escaped_content
+            )],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+    let session_url = format!(
+        "{}/api/v1/sessions/{}",
+        server.base_url, session_data.session_id
+    );
+
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut main_ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while let Ok(Some(_)) = timeout(Duration::from_millis(200), main_ws.next()).await {}
+
+    let request_test = |request_id: &str| {
+        Message::Text(format!(
+            r#"{{"type": "control_request", "request_id": "{request_id}", "request": {{"subtype": "can_use_tool", "tool_name": "Bash", "input": {{"command": "cargo test"}}}}}}"#
+        ))
+    };
+    let pending = || async {
+        let mut approvals = Vec::new();
+        for _ in 0..30 {
+            approvals = client
+                .get(format!("{session_url}/approvals"))
+                .send()
+                .await
+                .unwrap()
+                .json::<SessionApprovalsResponse>()
+                .await
+                .unwrap()
+                .approvals;
+            if !approvals.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        approvals
+    };
+
+    main_ws.send(request_test("rules-1")).await.unwrap();
+    let approvals = pending().await;
+    assert_eq!(approvals.len(), 1);
+    let reply = client
+        .post(format!("{session_url}/approvals/{}", approvals[0].id))
+        .json(&serde_json::json!({
+            "behavior": "allow",
+            "updatedInput": {"command": "cargo test"},
+            "remember": "session",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reply.status(), 200);
+
+    // The mock echoes the answers written to its stdin back to the clients
+    let response = next_frame_of_type(&mut main_ws, "control_response").await;
+    assert_eq!(response["response"]["request_id"], "rules-1");
+    assert!(
+        response["response"]["response"].get("remember").is_none(),
+        "Claude is not told about the remember scope"
+    );
+
+    let rules: SessionApprovalRulesResponse = client
+        .get(format!("{session_url}/approval_rules"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(rules.rules.len(), 1);
+    assert_eq!(rules.rules[0].tool_name, "Bash");
+    assert_eq!(rules.rules[0].input["command"], "cargo test");
+
+    // The identical request is allowed without asking
+    main_ws.send(request_test("rules-2")).await.unwrap();
+    let response = next_frame_of_type(&mut main_ws, "control_response").await;
+    assert_eq!(response["response"]["request_id"], "rules-2");
+    assert_eq!(response["response"]["response"]["behavior"], "allow");
+    let session = server
+        .session_manager
+        .get_session(&session_data.session_id)
+        .unwrap();
+    assert!(session.get_pending_approvals().await.is_empty());
+
+    // Once the rule is deleted it is asked again
+    let rules: SessionApprovalRulesResponse = client
+        .delete(format!(
+            "{session_url}/approval_rules/{}",
+            rules.rules[0].id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(rules.rules.is_empty());
+    main_ws.send(request_test("rules-3")).await.unwrap();
+    assert_eq!(pending().await.len(), 1);
+
+    let reply = client
+        .get(format!(
+            "{}/api/v1/sessions/no-such-session/approval_rules",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reply.status(), 404);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_unrouted_control_request_is_answered() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("control_route_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let session_id = generate_unique_session_id("control-route");
+    let session_file_path = server
+        .mock
+        .projects_dir()
+        .join(format!("{session_id}.jsonl"));
+    let escaped_content = format!(
+        r#"{{"sessionId": "{}", "cwd": "{}", "type": "start"}}"#,
+        session_id,
+        working_dir.display()
+    )
+    .replace('"', r#"\""#);
+
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: session_id.clone(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![format!(
+                r#"{{"control": "write_file", "path": "{}", "content": "{}"}}"#,
+                session_file_path.display(),
+                escaped_content
+            )],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut main_ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while let Ok(Some(_)) = timeout(Duration::from_millis(200), main_ws.next()).await {}
+
+    // Hook callbacks are not routed anywhere by default, so they are answered right away
+    main_ws
+        .send(Message::Text(
+            r#"{"type": "control_request", "request_id": "hook-1", "request": {"subtype": "hook_callback", "callback_id": "cb-1"}}"#
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+
+    // The mock echoes the answer written to its stdin back to the clients
+    let response = loop {
+        let message = timeout(Duration::from_secs(3), main_ws.next())
+            .await
+            .expect("Should receive the automatic control_response")
+            .unwrap()
+            .unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(message.to_text().unwrap()).unwrap();
+        if parsed["type"] == "control_response" {
+            break parsed;
+        }
+    };
+    assert_eq!(response["response"]["subtype"], "error");
+    assert_eq!(response["response"]["request_id"], "hook-1");
+
+    let session = server
+        .session_manager
+        .get_session(&session_data.session_id)
+        .unwrap();
+    assert!(session.get_pending_approvals().await.is_empty());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_approval_clarification_round_trip() {
+    let server = TestServer::new_with_approval_binary().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("approval_clarify_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let session_id = generate_unique_session_id("approval-clarify");
+    let session_file_path = server
+        .mock
+        .projects_dir()
+        .join(format!("{session_id}.jsonl"));
+    let escaped_content = format!(
+        r#"{{"sessionId": "{}", "cwd": "{}", "type": "start"}}"#,
+        session_id,
+        working_dir.display()
+    )
+    .replace('"', r#"\""#);
+
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: session_id.clone(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![format!(
+                r#"{{"control": "write_file", "path": "{}", "content": "{}"}}"#,
+                session_file_path.display(),
+                escaped_content
+            )],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+
+    let approval_ws_url = format!("{}{}", server.ws_url, session_data.approval_websocket_url);
+    let mut approval_ws = connect_approval_websocket(&approval_ws_url).await.unwrap();
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut main_ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while let Ok(Some(_)) = timeout(Duration::from_millis(200), main_ws.next()).await {}
+
+    main_ws
+        .send(Message::Text(
+            r#"{"type": "control_request", "request_id": "clarify-1", "request": {"subtype": "can_use_tool", "tool_name": "Bash", "input": {"command": "sudo ls"}}}"#
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+    let approval_id = expect_approval_request(&mut approval_ws, "Bash")
+        .await
+        .unwrap();
+
+    approval_ws
+        .send(Message::Text(
+            serde_json::json!({
+                "action": "ask_clarification",
+                "id": approval_id,
+                "question": "why do you need sudo?",
+            })
+            .to_string(),
+        ))
+        .await
+        .unwrap();
+    // The asker gets a result; every approval client gets the request with the question
+    let mut frames = [
+        next_json_frame(&mut approval_ws).await,
+        next_json_frame(&mut approval_ws).await,
+    ];
+    frames.sort_by_key(|frame| frame.get("type").is_none());
+    assert_eq!(frames[0]["type"], "approval_results");
+    assert_eq!(frames[0]["results"][0]["accepted"], true);
+    assert_eq!(frames[1]["id"], approval_id.as_str());
+    assert_eq!(
+        frames[1]["clarifications"][0]["question"],
+        "why do you need sudo?"
+    );
+    assert!(frames[1]["clarifications"][0].get("answer").is_none());
+
+    // A second question has to wait for the answer to the first
+    approval_ws
+        .send(Message::Text(
+            serde_json::json!({"action": "ask_clarification", "id": approval_id, "question": "and?"})
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+    let reply = next_json_frame(&mut approval_ws).await;
+    assert_eq!(reply["results"][0]["accepted"], false);
+
+    // The mock echoes this back, so it reads as Claude's answer
+    main_ws
+        .send(Message::Text(
+            r#"{"type": "assistant", "message": {"role": "assistant", "content": [{"type": "text", "text": "The directory is owned by root."}]}}"#
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+    let updated = next_json_frame(&mut approval_ws).await;
+    assert_eq!(updated["id"], approval_id.as_str());
+    assert_eq!(
+        updated["clarifications"][0]["answer"],
+        "The directory is owned by root."
+    );
+
+    // The request is still pending until decided
+    let session = server
+        .session_manager
+        .get_session(&session_data.session_id)
+        .unwrap();
+    let pending = session.get_pending_approvals().await;
+    assert_eq!(pending.len(), 1);
+    assert!(!pending[0].awaits_clarification());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_sandbox_denies_paths_outside_working_dir() {
+    std::env::set_var("WORKING_DIR_SANDBOX", "deny");
+    let server = TestServer::new_with_approval_binary().await;
+    std::env::remove_var("WORKING_DIR_SANDBOX");
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("approval_sandbox_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let session_id = generate_unique_session_id("approval-sandbox");
+    let session_file_path = server
+        .mock
+        .projects_dir()
+        .join(format!("{session_id}.jsonl"));
+    let escaped_content = format!(
+        r#"{{"sessionId": "{}", "cwd": "{}", "type": "start"}}"#,
+        session_id,
+        working_dir.display()
+    )
+    .replace('"', r#"\""#);
+
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: session_id.clone(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![format!(
+                r#"{{"control": "write_file", "path": "{}", "content": "{}"}}"#,
+                session_file_path.display(),
+                escaped_content
+            )],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut main_ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while let Ok(Some(_)) = timeout(Duration::from_millis(200), main_ws.next()).await {}
+
+    // Denied without asking, however the path gets out
+    main_ws
+        .send(Message::Text(
+            r#"{"type": "control_request", "request_id": "sandbox-1", "request": {"subtype": "can_use_tool", "tool_name": "Write", "input": {"file_path": "src/../../elsewhere.txt", "content": "x"}}}"#
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+    let response = next_frame_of_type(&mut main_ws, "control_response").await;
+    assert_eq!(response["response"]["request_id"], "sandbox-1");
+    assert_eq!(response["response"]["response"]["behavior"], "deny");
+    let message = response["response"]["response"]["message"]
+        .as_str()
+        .unwrap();
+    assert!(
+        message.ends_with("elsewhere.txt is outside the session's working directory"),
+        "{message}"
+    );
+
+    // Paths inside still go to approvers
+    main_ws
+        .send(Message::Text(
+            r#"{"type": "control_request", "request_id": "sandbox-2", "request": {"subtype": "can_use_tool", "tool_name": "Write", "input": {"file_path": "src/lib.rs", "content": "x"}}}"#
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+    let session = server
+        .session_manager
+        .get_session(&session_data.session_id)
+        .unwrap();
+    let mut pending = Vec::new();
+    for _ in 0..30 {
+        pending = session.get_pending_approvals().await;
+        if !pending.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].claude_request_id, "sandbox-2");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_risk_level_restricts_approvers() {
+    std::env::set_var("APPROVAL_RISK_APPROVERS", "destructive=admin");
+    let server = TestServer::new_with_approval_binary().await;
+    std::env::remove_var("APPROVAL_RISK_APPROVERS");
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("approval_risk_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let session_id = generate_unique_session_id("approval-risk");
+    let session_file_path = server
+        .mock
+        .projects_dir()
+        .join(format!("{session_id}.jsonl"));
+    let escaped_content = format!(
+        r#"{{"sessionId": "{}", "cwd": "{}", "type": "start"}}"#,
+        session_id,
+        working_dir.display()
+    )
+    .replace('"', r#"\""#);
+
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: session_id.clone(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![format!(
+                r#"{{"control": "write_file", "path": "{}", "content": "{}"}}"#,
+                session_file_path.display(),
+                escaped_content
+            )],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+
+    let approval_ws_url = format!("{}{}", server.ws_url, session_data.approval_websocket_url);
+    let mut approval_ws = connect_approval_websocket(&approval_ws_url).await.unwrap();
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut main_ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while let Ok(Some(_)) = timeout(Duration::from_millis(200), main_ws.next()).await {}
+
+    main_ws
+        .send(Message::Text(
+            r#"{"type": "control_request", "request_id": "risk-1", "request": {"subtype": "can_use_tool", "tool_name": "Bash", "input": {"command": "rm -rf build"}}}"#
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+    let request = next_json_frame(&mut approval_ws).await;
+    assert_eq!(request["risk"], "destructive");
+    assert_eq!(
+        request["risk_reasons"],
+        serde_json::json!(["deletes files recursively or without asking"])
+    );
+    let approval_id = request["id"].as_str().unwrap().to_string();
+
+    // Without an identity header everybody is `default`, who may not allow it
+    approval_ws
+        .send(Message::Text(
+            serde_json::json!({"id": approval_id, "response": {"behavior": "allow"}}).to_string(),
+        ))
+        .await
+        .unwrap();
+    let reply = next_json_frame(&mut approval_ws).await;
+    assert_eq!(reply["type"], "approval_results");
+    assert_eq!(reply["results"][0]["accepted"], false);
+    assert_eq!(
+        reply["results"][0]["error"],
+        "only admin may allow destructive requests"
+    );
+
+    let session = server
+        .session_manager
+        .get_session(&session_data.session_id)
+        .unwrap();
+    assert_eq!(session.get_pending_approvals().await.len(), 1);
+
+    // Denying is never restricted
+    approval_ws
+        .send(Message::Text(
+            serde_json::json!({"id": approval_id, "response": {"behavior": "deny", "message": "no"}})
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+    timeout(Duration::from_secs(3), async {
+        while !session.get_pending_approvals().await.is_empty() {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("The denial should have been forwarded");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_high_priority_approvals_overtake_batched_ones() {
+    std::env::set_var("APPROVAL_HIGH_PRIORITY_RISK", "destructive");
+    std::env::set_var("APPROVAL_BATCH_WINDOW_MS", "1000");
+    let server = TestServer::new_with_approval_binary().await;
+    std::env::remove_var("APPROVAL_HIGH_PRIORITY_RISK");
+    std::env::remove_var("APPROVAL_BATCH_WINDOW_MS");
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("approval_priority_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let session_id = generate_unique_session_id("approval-priority");
+    let session_file_path = server
+        .mock
+        .projects_dir()
+        .join(format!("{session_id}.jsonl"));
+    let escaped_content = format!(
+        r#"{{"sessionId": "{}", "cwd": "{}", "type": "start"}}"#,
+        session_id,
+        working_dir.display()
+    )
+    .replace('"', r#"\""#);
+
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: session_id.clone(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![format!(
+                r#"{{"control": "write_file", "path": "{}", "content": "{}"}}"#,
+                session_file_path.display(),
+                escaped_content
+            )],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+
+    let approval_ws_url = format!("{}{}", server.ws_url, session_data.approval_websocket_url);
+    let mut approval_ws = connect_approval_websocket(&approval_ws_url).await.unwrap();
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut main_ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while let Ok(Some(_)) = timeout(Duration::from_millis(200), main_ws.next()).await {}
+
+    for (request_id, command) in [
+        ("low-1", "ls"),
+        ("high-1", "rm -rf build"),
+        ("low-2", "cat a"),
+    ] {
+        main_ws
+            .send(Message::Text(
+                serde_json::json!({
+                    "type": "control_request",
+                    "request_id": request_id,
+                    "request": {"subtype": "can_use_tool", "tool_name": "Bash", "input": {"command": command}}
+                })
+                .to_string(),
+            ))
+            .await
+            .unwrap();
+    }
+
+    // The destructive request is delivered right away, ahead of the earlier read-only one
+    let first = next_json_frame(&mut approval_ws).await;
+    assert_eq!(first["request"]["input"]["command"], "rm -rf build");
+    assert_eq!(first["priority"], "high");
+    let sent = std::time::Instant::now();
+
+    // The others arrive together once the batch window is over
+    let second = next_json_frame(&mut approval_ws).await;
+    let third = next_json_frame(&mut approval_ws).await;
+    assert_eq!(second["request"]["input"]["command"], "ls");
+    assert_eq!(third["request"]["input"]["command"], "cat a");
+    assert_eq!(second["priority"], "normal");
+    assert!(sent.elapsed() >= Duration::from_millis(500));
+
+    // A client connecting later gets the high priority request first too
+    let mut late_ws = connect_approval_websocket(&approval_ws_url).await.unwrap();
+    let first = next_json_frame(&mut late_ws).await;
+    assert_eq!(first["priority"], "high");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_pending_approvals_denied_after_last_approver_leaves() {
+    let server = TestServer::new_with_approval_binary().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("approval_disconnect_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let session_id = generate_unique_session_id("approval-disconnect");
+    let session_file_path = server
+        .mock
+        .projects_dir()
+        .join(format!("{session_id}.jsonl"));
+    let escaped_content = format!(
+        r#"{{"sessionId": "{}", "cwd": "{}", "type": "start"}}"#,
+        session_id,
+        working_dir.display()
+    )
+    .replace('"', r#"\""#);
+
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: session_id.clone(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![format!(
+                r#"{{"control": "write_file", "path": "{}", "content": "{}"}}"#,
+                session_file_path.display(),
+                escaped_content
+            )],
+            options: SessionOptions {
+                approval_disconnect_deny_after_secs: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+    let session = server
+        .session_manager
+        .get_session(&session_data.session_id)
+        .unwrap();
+
+    let approval_ws_url = format!("{}{}", server.ws_url, session_data.approval_websocket_url);
+    let mut approval_ws = connect_approval_websocket(&approval_ws_url).await.unwrap();
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut main_ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while let Ok(Some(_)) = timeout(Duration::from_millis(200), main_ws.next()).await {}
+
+    main_ws
+        .send(Message::Text(
+            r#"{"type": "control_request", "request_id": "walk-away-1", "request": {"subtype": "can_use_tool", "tool_name": "Write", "input": {"file_path": "notes.txt"}}}"#
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+    next_json_frame(&mut approval_ws).await;
+
+    // Reconnecting before the countdown runs out keeps the approval pending
+    approval_ws.close(None).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let mut approval_ws = connect_approval_websocket(&approval_ws_url).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(session.get_pending_approvals().await.len(), 1);
+
+    // Walking away for good denies it once the countdown runs out
+    approval_ws.close(None).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(session.get_pending_approvals().await.len(), 1);
+    timeout(Duration::from_secs(3), async {
+        while !session.get_pending_approvals().await.is_empty() {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("The pending approval should have been denied");
+
+    // The mock echoes the control_response written to Claude
+    let denial = timeout(Duration::from_secs(3), async {
+        loop {
+            let frame = main_ws.next().await.unwrap().unwrap();
+            let value: serde_json::Value = serde_json::from_str(frame.to_text().unwrap()).unwrap();
+            if value["type"] == "control_response" {
+                return value;
+            }
+        }
+    })
+    .await
+    .expect("Claude should have been sent the denial");
+    let denial = denial.to_string();
+    assert!(denial.contains("deny"), "unexpected response: {denial}");
+    assert!(denial.contains("No approver was connected"));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_approvals_multiplexed_over_main_websocket() {
+    let server = TestServer::new_with_approval_binary().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("approval_multiplex_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let session_id = generate_unique_session_id("approval-multiplex");
+    let session_file_path = server
+        .mock
+        .projects_dir()
+        .join(format!("{session_id}.jsonl"));
+    let escaped_content = format!(
+        r#"{{"sessionId": "{}", "cwd": "{}", "type": "start"}}"#,
+        session_id,
+        working_dir.display()
+    )
+    .replace('"', r#"\""#);
+
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: session_id.clone(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![format!(
+                r#"{{"control": "write_file", "path": "{}", "content": "{}"}}"#,
+                session_file_path.display(),
+                escaped_content
+            )],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+    let session = server
+        .session_manager
+        .get_session(&session_data.session_id)
+        .unwrap();
+
+    let ws_url = format!(
+        "{}{}?channels=all",
+        server.ws_url, session_data.websocket_url
+    );
+    let (mut main_ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while let Ok(Some(_)) = timeout(Duration::from_millis(200), main_ws.next()).await {}
+
+    main_ws
+        .send(Message::Text(
+            r#"{"type": "control_request", "request_id": "multiplex-1", "request": {"subtype": "can_use_tool", "tool_name": "Write", "input": {"file_path": "notes.txt"}}}"#
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+
+    // Approval requests arrive on the main socket in an approval envelope
+    let request = next_frame_of_type(&mut main_ws, "approval").await;
+    assert_eq!(request["message"]["request"]["tool_name"], "Write");
+    let approval_id = request["message"]["id"].as_str().unwrap().to_string();
+
+    main_ws
+        .send(Message::Text(
+            serde_json::json!({
+                "type": "approval",
+                "message": [{"id": approval_id, "response": {"behavior": "allow", "updatedInput": {"file_path": "notes.txt"}}}]
+            })
+            .to_string(),
+        ))
+        .await
+        .unwrap();
+
+    // Replies to approval frames come back in the same envelope
+    let reply = next_frame_of_type(&mut main_ws, "approval").await;
+    assert_eq!(reply["message"]["type"], "approval_results");
+    assert_eq!(reply["message"]["results"][0]["accepted"], true);
+
+    // The answer reaches Claude, which the mock echoes back
+    let response = next_frame_of_type(&mut main_ws, "control_response").await;
+    assert_eq!(response["response"]["request_id"], "multiplex-1");
+    assert!(session.get_pending_approvals().await.is_empty());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_approvals_answered_through_another_instance() {
+    let sync_dir = tempfile::TempDir::new().unwrap();
+    std::env::set_var("APPROVAL_SYNC_DIR", sync_dir.path());
+    let server = TestServer::new_with_approval_binary().await;
+    // A second instance sharing the directory, which runs no sessions itself
+    let replica = TestServer::new_internal(MockClaude::new()).await;
+    std::env::remove_var("APPROVAL_SYNC_DIR");
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("approval_sync_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let session_id = generate_unique_session_id("approval-sync");
+    let session_file_path = server
+        .mock
+        .projects_dir()
+        .join(format!("{session_id}.jsonl"));
+    let escaped_content = format!(
+        r#"{{"sessionId": "{}", "cwd": "{}", "type": "start"}}"#,
+        session_id,
+        working_dir.display()
+    )
+    .replace('"', r#"\""#);
+
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: session_id.clone(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![format!(
+                r#"{{"control": "write_file", "path": "{}", "content": "{}"}}"#,
+                session_file_path.display(),
+                escaped_content
+            )],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+    let approvals_url = |base_url: &str| {
+        format!(
+            "{base_url}/api/v1/sessions/{}/approvals",
+            session_data.session_id
+        )
+    };
+
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut main_ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while let Ok(Some(_)) = timeout(Duration::from_millis(200), main_ws.next()).await {}
+    for (request_id, tool) in [("sync-1", "Read"), ("sync-2", "Grep")] {
+        main_ws
+            .send(Message::Text(format!(
+                r#"{{"type": "control_request", "request_id": "{request_id}", "request": {{"subtype": "can_use_tool", "tool_name": "{tool}"}}}}"#
+            )))
+            .await
+            .unwrap();
+    }
+
+    // The replica lists the approvals pending on the instance running the session, once
+    // they were published
+    let list = || async {
+        let reply = client
+            .get(approvals_url(&replica.base_url))
+            .send()
+            .await
+            .unwrap();
+        if reply.status() == 404 {
+            return None;
+        }
+        Some(reply.json::<SessionApprovalsResponse>().await.unwrap())
+    };
+    let mut pending = list().await;
+    for _ in 0..50 {
+        if pending
+            .as_ref()
+            .is_some_and(|pending| pending.approvals.len() == 2)
+        {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        pending = list().await;
+    }
+    let pending = pending.expect("The approvals should have been published");
+    let tools: Vec<&str> = pending
+        .approvals
+        .iter()
+        .map(|approval| approval.request["tool_name"].as_str().unwrap())
+        .collect();
+    assert_eq!(tools, ["Read", "Grep"]);
+    let (read_id, grep_id) = (&pending.approvals[0].id, &pending.approvals[1].id);
+
+    let answer = |base_url: String, id: &str, behavior: &str| {
+        client
+            .post(format!("{}/{id}", approvals_url(&base_url)))
+            .json(&serde_json::json!({"behavior": behavior, "message": "from a test"}))
+            .send()
+    };
+    let reply = answer(replica.base_url.clone(), read_id, "allow")
+        .await
+        .unwrap();
+    assert_eq!(reply.status(), 200);
+    let reply = answer(replica.base_url.clone(), grep_id, "deny")
+        .await
+        .unwrap();
+    assert_eq!(reply.status(), 200);
+
+    // Opposing answers given later, on either instance, lose to the first one
+    for base_url in [&replica.base_url, &server.base_url] {
+        let reply = answer(base_url.clone(), grep_id, "allow").await.unwrap();
+        assert_eq!(reply.status(), 409);
+        let body: serde_json::Value = reply.json().await.unwrap();
+        assert_eq!(body["code"], "APPROVAL_ALREADY_DECIDED");
+    }
+    assert!(list().await.unwrap().approvals.is_empty());
+
+    // Once polling, the instance running the session passes the answers on to Claude
+    let _approval_sync = server.session_manager.start_approval_sync().unwrap();
+    let mut behaviors = std::collections::HashMap::new();
+    for _ in 0..2 {
+        let response = next_frame_of_type(&mut main_ws, "control_response").await;
+        behaviors.insert(
+            response["response"]["request_id"]
+                .as_str()
+                .unwrap()
+                .to_string(),
+            response["response"]["response"]["behavior"].clone(),
+        );
+    }
+    assert_eq!(behaviors["sync-1"], "allow");
+    assert_eq!(behaviors["sync-2"], "deny");
+
+    // Nothing is left pending to answer through the replica
+    let session = server
+        .session_manager
+        .get_session(&session_data.session_id)
+        .unwrap();
+    assert!(session.get_pending_approvals().await.is_empty());
+    assert!(list().await.is_none());
+}
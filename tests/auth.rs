@@ -0,0 +1,539 @@
+mod helpers;
+
+use chef_de_vibe::{
+    api::{auth, handlers::AppState},
+    config::{ApiKey, ApiKeys, ApiToken, Config},
+    models::{CreateSessionRequest, CreateSessionResponse, ListSessionsResponse},
+    session_backend::BackendKind,
+    session_manager::SessionManager,
+};
+use futures_util::StreamExt;
+use helpers::logging::init_logging;
+use helpers::mock_claude::MockClaude;
+use reqwest::Client;
+use serial_test::serial;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest, http::HeaderValue};
+
+const TOKEN: &str = "test-token_0123";
+const ALICE_TOKEN: &str = "alice-token";
+const BOB_TOKEN: &str = "bob-token";
+
+fn key(token: &str, user: Option<&str>, admin: bool) -> ApiKey {
+    ApiKey {
+        token: ApiToken::new(token).unwrap(),
+        user: user.map(str::to_string),
+        admin,
+    }
+}
+
+struct TestServer {
+    pub base_url: String,
+    pub ws_url: String,
+    pub mock: MockClaude,
+    server_handle: tokio::task::JoinHandle<()>,
+}
+
+impl TestServer {
+    async fn new() -> Self {
+        Self::with_keys(vec![key(TOKEN, None, true)]).await
+    }
+
+    async fn with_keys(keys: Vec<ApiKey>) -> Self {
+        init_logging();
+        let mock = MockClaude::new();
+        mock.setup_env_vars();
+
+        let mut config = Config::from_env().expect("Failed to load config");
+        config.api_keys = ApiKeys::new(keys);
+        let api_keys = config.api_keys.clone();
+        let session_manager = Arc::new(SessionManager::new(config.clone()));
+        let state = AppState {
+            session_manager,
+            config: Arc::new(config),
+        };
+
+        let app = axum::Router::new()
+            .route(
+                "/api/v1/sessions",
+                axum::routing::get(chef_de_vibe::api::handlers::list_sessions)
+                    .post(chef_de_vibe::api::handlers::create_session),
+            )
+            .route(
+                "/api/v1/sessions/:id",
+                axum::routing::get(chef_de_vibe::api::handlers::get_session),
+            )
+            .route(
+                "/api/v1/sessions/:id/approvals",
+                axum::routing::get(chef_de_vibe::api::handlers::get_session_approvals),
+            )
+            .route(
+                "/api/v1/sessions/:id/claude_ws",
+                axum::routing::get(chef_de_vibe::api::websocket::websocket_handler),
+            )
+            .route(
+                "/api/v1/sessions/:id/claude_approvals_ws",
+                axum::routing::get(chef_de_vibe::api::websocket::approval_websocket_handler),
+            )
+            .route(
+                "/api/v1/events_ws",
+                axum::routing::get(chef_de_vibe::api::websocket::events_websocket_handler),
+            )
+            .route(
+                "/api/v1/projects/:id/tools",
+                axum::routing::get(chef_de_vibe::api::handlers::get_project_tools),
+            )
+            .route(
+                "/metrics",
+                axum::routing::get(chef_de_vibe::api::handlers::get_metrics),
+            )
+            .route_layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                auth::require_session_access,
+            ))
+            .layer(axum::middleware::from_fn_with_state(
+                api_keys,
+                auth::require_token,
+            ))
+            .route("/", axum::routing::get(|| async { "frontend" }))
+            .with_state(state);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server_handle = tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        Self {
+            base_url: format!("http://{addr}"),
+            ws_url: format!("ws://{addr}"),
+            mock,
+            server_handle,
+        }
+    }
+
+    /// Request for a new session whose transcript the mock writes as it starts
+    fn session_request(&self) -> CreateSessionRequest {
+        let session_id = format!("auth-{}", uuid::Uuid::new_v4());
+        let working_dir = self.mock.temp_dir.path().to_path_buf();
+        let session_file = self.mock.projects_dir().join(format!("{session_id}.jsonl"));
+        CreateSessionRequest {
+            session_id: session_id.clone(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![serde_json::json!({
+                "control": "write_file",
+                "path": session_file,
+                "content": serde_json::json!({"sessionId": session_id, "cwd": working_dir, "type": "start"}).to_string(),
+            })
+            .to_string()],
+            ..Default::default()
+        }
+    }
+
+    async fn create_session(&self, client: &Client, token: &str) -> CreateSessionResponse {
+        let response = client
+            .post(format!("{}/api/v1/sessions", self.base_url))
+            .bearer_auth(token)
+            .json(&self.session_request())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        response.json().await.unwrap()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server_handle.abort();
+    }
+}
+
+fn rejected_with(result: Result<impl std::fmt::Debug, tungstenite::Error>, status: u16) {
+    match result {
+        Err(tungstenite::Error::Http(response)) => assert_eq!(response.status(), status),
+        other => panic!("expected HTTP {status}, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_http_requires_token() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+    let sessions_url = format!("{}/api/v1/sessions", server.base_url);
+
+    let response = client.get(&sessions_url).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    assert_eq!(response.headers()["www-authenticate"], "Bearer");
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "UNAUTHORIZED");
+
+    let response = client
+        .get(&sessions_url)
+        .bearer_auth("wrong-token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+
+    // Only WebSocket upgrades may carry the token in the query
+    let response = client
+        .get(format!("{sessions_url}?access_token={TOKEN}"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+
+    let response = client
+        .get(&sessions_url)
+        .bearer_auth(TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // The scheme is case-insensitive
+    let response = client
+        .get(&sessions_url)
+        .header("Authorization", format!("bearer {TOKEN}"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // Routes outside the API stay open
+    let response = client
+        .get(format!("{}/", server.base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_websocket_upgrades_require_token() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+    let session = server.create_session(&client, TOKEN).await;
+    let ws_url = format!("{}{}", server.ws_url, session.websocket_url);
+    let approval_ws_url = format!("{}{}", server.ws_url, session.approval_websocket_url);
+
+    rejected_with(connect_async(ws_url.as_str()).await, 401);
+    rejected_with(
+        connect_async(format!("{ws_url}?access_token=wrong-token")).await,
+        401,
+    );
+    rejected_with(connect_async(approval_ws_url.as_str()).await, 401);
+
+    // Query parameter, next to the socket's own options
+    let (mut ws, _) = connect_async(format!("{ws_url}?ack=true&access_token={TOKEN}"))
+        .await
+        .unwrap();
+    ws.close(None).await.unwrap();
+    let (mut ws, _) = connect_async(format!("{approval_ws_url}?access_token={TOKEN}"))
+        .await
+        .unwrap();
+    ws.close(None).await.unwrap();
+
+    // Authorization header, for clients that can set one
+    let mut request = ws_url.as_str().into_client_request().unwrap();
+    request.headers_mut().insert(
+        "Authorization",
+        HeaderValue::from_str(&format!("Bearer {TOKEN}")).unwrap(),
+    );
+    let (mut ws, _) = connect_async(request).await.unwrap();
+    ws.close(None).await.unwrap();
+
+    // Subprotocol, for browsers; the server selects the marker protocol, never the token
+    let mut request = ws_url.as_str().into_client_request().unwrap();
+    request.headers_mut().insert(
+        "Sec-WebSocket-Protocol",
+        HeaderValue::from_str(&format!("{}, bearer.{TOKEN}", auth::WEBSOCKET_PROTOCOL)).unwrap(),
+    );
+    let (mut ws, response) = connect_async(request).await.unwrap();
+    assert_eq!(
+        response.headers()["sec-websocket-protocol"],
+        auth::WEBSOCKET_PROTOCOL
+    );
+    ws.close(None).await.unwrap();
+
+    let mut request = ws_url.as_str().into_client_request().unwrap();
+    request.headers_mut().insert(
+        "Sec-WebSocket-Protocol",
+        HeaderValue::from_str(&format!("{}, bearer.wrong-token", auth::WEBSOCKET_PROTOCOL))
+            .unwrap(),
+    );
+    rejected_with(connect_async(request).await, 401);
+}
+
+async fn listed_sessions(
+    server: &TestServer,
+    client: &Client,
+    token: &str,
+) -> Vec<(String, Option<String>)> {
+    let response = client
+        .get(format!("{}/api/v1/sessions", server.base_url))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let listing: ListSessionsResponse = response.json().await.unwrap();
+    listing
+        .sessions
+        .into_iter()
+        .map(|session| (session.session_id, session.owner))
+        .collect()
+}
+
+#[tokio::test]
+#[serial]
+async fn test_sessions_are_private_to_their_owner() {
+    let server = TestServer::with_keys(vec![
+        key(ALICE_TOKEN, Some("alice"), false),
+        key(BOB_TOKEN, Some("bob"), false),
+        key(TOKEN, None, true),
+    ])
+    .await;
+    let client = Client::new();
+    let session = server.create_session(&client, ALICE_TOKEN).await;
+    let session_url = format!("{}/api/v1/sessions/{}", server.base_url, session.session_id);
+    let alices = (session.session_id.clone(), Some("alice".to_string()));
+
+    assert!(listed_sessions(&server, &client, ALICE_TOKEN)
+        .await
+        .contains(&alices));
+    assert!(listed_sessions(&server, &client, TOKEN)
+        .await
+        .contains(&alices));
+    assert!(!listed_sessions(&server, &client, BOB_TOKEN)
+        .await
+        .iter()
+        .any(|(session_id, _)| *session_id == session.session_id));
+
+    for (token, status) in [(ALICE_TOKEN, 200), (TOKEN, 200), (BOB_TOKEN, 404)] {
+        for url in [session_url.clone(), format!("{session_url}/approvals")] {
+            let response = client.get(&url).bearer_auth(token).send().await.unwrap();
+            assert_eq!(response.status(), status, "{url} with {token}");
+        }
+    }
+
+    // Another user's session can neither be joined nor resumed
+    for resume in [false, true] {
+        let response = client
+            .post(format!("{}/api/v1/sessions", server.base_url))
+            .bearer_auth(BOB_TOKEN)
+            .json(&CreateSessionRequest {
+                session_id: session.session_id.clone(),
+                working_dir: server.mock.temp_dir.path().to_path_buf(),
+                resume,
+                bootstrap_messages: vec![r#"{"type":"user"}"#.to_string()],
+                ..Default::default()
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+    }
+
+    let ws_url = format!("{}{}", server.ws_url, session.websocket_url);
+    rejected_with(
+        connect_async(format!("{ws_url}?access_token={BOB_TOKEN}")).await,
+        404,
+    );
+    let (mut ws, _) = connect_async(format!("{ws_url}?access_token={ALICE_TOKEN}"))
+        .await
+        .unwrap();
+    ws.close(None).await.unwrap();
+}
+
+/// Sessions the events the socket receives within a moment are about, in the order
+/// they first appear.
+async fn event_sessions<S>(events: &mut S) -> Vec<String>
+where
+    S: futures_util::Stream<Item = Result<tungstenite::Message, tungstenite::Error>> + Unpin,
+{
+    let mut sessions = Vec::new();
+    while let Ok(Some(frame)) = tokio::time::timeout(Duration::from_secs(2), events.next()).await {
+        let Ok(tungstenite::Message::Text(text)) = frame else {
+            continue;
+        };
+        let event: serde_json::Value = serde_json::from_str(&text).unwrap();
+        let session_id = event["session_id"].as_str().unwrap().to_string();
+        if !sessions.contains(&session_id) {
+            sessions.push(session_id);
+        }
+    }
+    sessions
+}
+
+#[tokio::test]
+#[serial]
+async fn test_server_events_are_private_to_their_owner() {
+    let server = TestServer::with_keys(vec![
+        key(ALICE_TOKEN, Some("alice"), false),
+        key(BOB_TOKEN, Some("bob"), false),
+        key(TOKEN, None, true),
+    ])
+    .await;
+    let client = Client::new();
+    let events_url = format!("{}/api/v1/events_ws", server.ws_url);
+    let (mut alices_events, _) = connect_async(format!("{events_url}?access_token={ALICE_TOKEN}"))
+        .await
+        .unwrap();
+    let (mut bobs_events, _) = connect_async(format!("{events_url}?access_token={BOB_TOKEN}"))
+        .await
+        .unwrap();
+    let (mut admin_events, _) = connect_async(format!("{events_url}?access_token={TOKEN}"))
+        .await
+        .unwrap();
+
+    let alices = server.create_session(&client, ALICE_TOKEN).await.session_id;
+    let bobs = server.create_session(&client, BOB_TOKEN).await.session_id;
+
+    assert_eq!(
+        event_sessions(&mut alices_events).await,
+        std::slice::from_ref(&alices)
+    );
+    assert_eq!(
+        event_sessions(&mut bobs_events).await,
+        std::slice::from_ref(&bobs)
+    );
+    assert_eq!(event_sessions(&mut admin_events).await, [alices, bobs]);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_project_tools_only_count_own_sessions() {
+    let server = TestServer::with_keys(vec![
+        key(ALICE_TOKEN, Some("alice"), false),
+        key(BOB_TOKEN, Some("bob"), false),
+        key(TOKEN, None, true),
+    ])
+    .await;
+    let client = Client::new();
+    let alices = server.create_session(&client, ALICE_TOKEN).await.session_id;
+    let bobs = server.create_session(&client, BOB_TOKEN).await.session_id;
+
+    let project_dir = server.mock.projects_dir().join("-shared-project");
+    std::fs::create_dir_all(&project_dir).unwrap();
+    for (session_id, command) in [(&alices, "cargo test"), (&bobs, "rm -rf target")] {
+        std::fs::write(
+            project_dir.join(format!("{session_id}.jsonl")),
+            serde_json::json!({
+                "sessionId": session_id,
+                "type": "assistant",
+                "message": {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "t1", "name": "Bash", "input": {"command": command}},
+                ]},
+            })
+            .to_string(),
+        )
+        .unwrap();
+    }
+
+    for (token, sessions, commands) in [
+        (ALICE_TOKEN, 1, vec!["cargo test"]),
+        (BOB_TOKEN, 1, vec!["rm"]),
+        (TOKEN, 2, vec!["cargo test", "rm"]),
+    ] {
+        let response = client
+            .get(format!(
+                "{}/api/v1/projects/-shared-project/tools",
+                server.base_url
+            ))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let catalog: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(catalog["sessions"], sessions, "{token}");
+        let mut details: Vec<&str> = catalog["tools"][0]["details"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|detail| detail["value"].as_str().unwrap())
+            .collect();
+        details.sort_unstable();
+        assert_eq!(details, commands, "{token}");
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_metrics_only_show_own_sessions() {
+    std::env::set_var("PROCESS_SAMPLE_INTERVAL", "1");
+    let server = TestServer::with_keys(vec![
+        key(ALICE_TOKEN, Some("alice"), false),
+        key(BOB_TOKEN, Some("bob"), false),
+        key(TOKEN, None, true),
+    ])
+    .await;
+    std::env::remove_var("PROCESS_SAMPLE_INTERVAL");
+    let client = Client::new();
+    let alices = server.create_session(&client, ALICE_TOKEN).await.session_id;
+    let bobs = server.create_session(&client, BOB_TOKEN).await.session_id;
+
+    // The first sample is taken as soon as the process starts
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+
+    for (token, visible) in [
+        (ALICE_TOKEN, vec![&alices]),
+        (BOB_TOKEN, vec![&bobs]),
+        (TOKEN, vec![&alices, &bobs]),
+    ] {
+        let metrics = client
+            .get(format!("{}/metrics", server.base_url))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        for session_id in [&alices, &bobs] {
+            assert_eq!(
+                metrics.contains(&format!(
+                    r#"chef_de_vibe_session_rss_bytes{{session_id="{session_id}"}}"#
+                )),
+                visible.contains(&session_id),
+                "{session_id} with {token}"
+            );
+        }
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_only_admins_choose_the_backend() {
+    // Sessions are meant to run in containers; the CLI would run Claude on the host
+    std::env::set_var("CONTAINER_IMAGE", "claude-sandbox:latest");
+    let server = TestServer::with_keys(vec![
+        key(ALICE_TOKEN, Some("alice"), false),
+        key(TOKEN, None, true),
+    ])
+    .await;
+    std::env::remove_var("CONTAINER_IMAGE");
+    let client = Client::new();
+
+    for (token, status) in [(ALICE_TOKEN, 403), (TOKEN, 200)] {
+        let mut request = server.session_request();
+        request.options.backend = Some(BackendKind::Cli);
+        let response = client
+            .post(format!("{}/api/v1/sessions", server.base_url))
+            .bearer_auth(token)
+            .json(&request)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), status, "{token}");
+    }
+}
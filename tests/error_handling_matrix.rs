@@ -58,6 +58,9 @@ impl TestServer {
                 "/api/v1/sessions/:id/claude_ws",
                 axum::routing::get(chef_de_vibe::api::websocket::websocket_handler),
             )
+            .route_layer(axum::middleware::from_fn(
+                chef_de_vibe::api::problem::problem_details,
+            ))
             .with_state(state);
 
         let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
@@ -200,6 +203,7 @@ async fn test_write_to_dead_claude_process() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages: vec![create_file_command],
+        ..Default::default()
     };
 
     let response = client
@@ -309,6 +313,7 @@ async fn test_malformed_json_from_claude_causes_websocket_closure() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages: vec![create_file_command, malformed_trigger.to_string()],
+        ..Default::default()
     };
 
     let response = client
@@ -353,6 +358,92 @@ async fn test_malformed_json_from_claude_causes_websocket_closure() {
     }
 }
 
+#[tokio::test]
+#[serial]
+async fn test_non_json_claude_output_passes_through_in_tolerant_mode() {
+    std::env::set_var("CLAUDE_STDOUT_MODE", "tolerant");
+    let server = TestServer::new().await;
+    std::env::remove_var("CLAUDE_STDOUT_MODE");
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("tolerant_work");
+    fs::create_dir_all(&working_dir).unwrap();
+
+    let session_file_path = server.mock.projects_dir.join("tolerant-session.jsonl");
+    let create_file_command = serde_json::json!({
+        "control": "write_file",
+        "path": session_file_path.to_string_lossy(),
+        "content": format!(
+            r#"{{"sessionId": "tolerant-session", "cwd": "{}", "type": "start"}}"#,
+            working_dir.display()
+        )
+    })
+    .to_string();
+
+    let request = CreateSessionRequest {
+        session_id: "tolerant-session".to_string(),
+        working_dir: working_dir.clone(),
+        resume: false,
+        bootstrap_messages: vec![create_file_command],
+        ..Default::default()
+    };
+
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let session_data: CreateSessionResponse = response.json().await.unwrap();
+
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while timeout(Duration::from_millis(200), ws.next()).await.is_ok() {}
+
+    ws.send(Message::Text(
+        r#"{"control": "print_raw", "text": "warning: stray CLI output"}"#.to_string(),
+    ))
+    .await
+    .unwrap();
+    ws.send(Message::Text(
+        r#"{"role": "user", "content": "still alive"}"#.to_string(),
+    ))
+    .await
+    .unwrap();
+
+    // The raw line arrives wrapped and the stream keeps going afterwards
+    let mut raw_event = None;
+    let mut echoed_after = false;
+    while let Ok(Some(Ok(msg))) = timeout(Duration::from_secs(2), ws.next()).await {
+        let Message::Text(text) = msg else {
+            panic!("Connection should stay open in tolerant mode, got: {msg:?}");
+        };
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        if value["type"] == "raw" {
+            raw_event = Some(value);
+        } else if raw_event.is_some() && value["content"] == "still alive" {
+            echoed_after = true;
+            break;
+        }
+    }
+
+    let raw_event = raw_event.expect("Should receive the wrapped raw line");
+    assert_eq!(raw_event["text"], "warning: stray CLI output");
+    assert!(
+        echoed_after,
+        "Claude output should continue after a raw line"
+    );
+
+    let session = server
+        .session_manager
+        .get_session(&session_data.session_id)
+        .unwrap();
+    assert_eq!(session.get_raw_output_lines(), 1);
+
+    let _ = ws.close(None).await;
+}
+
 #[tokio::test]
 #[serial]
 async fn test_websocket_to_nonexistent_session() {
@@ -420,6 +511,7 @@ async fn test_client_sends_invalid_json_via_websocket() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages: vec![create_file_command],
+        ..Default::default()
     };
 
     let response = client
@@ -515,6 +607,7 @@ async fn test_concurrent_writes_to_same_session() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages: vec![create_file_command],
+        ..Default::default()
     };
 
     let response = client
@@ -609,6 +702,7 @@ async fn test_session_file_corruption_during_active_session() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages: vec![],
+        ..Default::default()
     };
 
     let response = client
@@ -700,6 +794,14 @@ async fn test_missing_required_fields_in_request() {
         422,
         "Should return 422 for missing session_id"
     );
+    assert_eq!(
+        response.headers()["content-type"],
+        "application/problem+json"
+    );
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "INVALID_REQUEST");
+    assert_eq!(body["status"], 422);
+    assert!(body["detail"].as_str().unwrap().contains("session_id"));
 
     // Test missing working_dir
     let invalid_request = serde_json::json!({
@@ -737,3 +839,56 @@ async fn test_missing_required_fields_in_request() {
         "Should return 400 for malformed JSON"
     );
 }
+
+#[tokio::test]
+#[serial]
+async fn test_session_id_that_is_not_a_file_name_is_rejected() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    for session_id in ["../escape", "nested/session", "..", "/absolute"] {
+        let response = client
+            .post(format!("{}/api/v1/sessions", server.base_url))
+            .json(&serde_json::json!({
+                "session_id": session_id,
+                "working_dir": server.mock.temp_dir.path(),
+                "resume": false,
+                "bootstrap_messages": [r#"{"role": "user", "content": "Hello"}"#]
+            }))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 400, "{session_id}");
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["code"], "INVALID_REQUEST");
+        assert!(server.session_manager.get_session(session_id).is_none());
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_unknown_session_is_problem_document() {
+    let server = TestServer::new().await;
+
+    let response = Client::new()
+        .get(format!(
+            "{}/api/v1/sessions/no-such-session",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 404);
+    assert_eq!(
+        response.headers()["content-type"],
+        "application/problem+json"
+    );
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "SESSION_NOT_FOUND");
+    assert_eq!(body["type"], "urn:chef-de-vibe:SESSION_NOT_FOUND");
+    assert_eq!(body["title"], "Not Found");
+    assert_eq!(body["status"], 404);
+    assert_eq!(body["detail"], body["error"]);
+}
@@ -0,0 +1,280 @@
+#![cfg(feature = "testing")]
+
+mod helpers;
+
+use chef_de_vibe::{
+    api::handlers::AppState,
+    config::Config,
+    fault_injection::FaultsResponse,
+    models::{CreateSessionRequest, CreateSessionResponse},
+    session_manager::SessionManager,
+};
+use futures_util::{SinkExt, StreamExt};
+use helpers::logging::init_logging;
+use helpers::mock_claude::MockClaude;
+use reqwest::Client;
+use serial_test::serial;
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::time::timeout;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use url::Url;
+
+type WsStream =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+struct TestServer {
+    pub base_url: String,
+    pub ws_url: String,
+    pub mock: MockClaude,
+    server_handle: tokio::task::JoinHandle<()>,
+    session_manager: Arc<SessionManager>,
+}
+
+impl TestServer {
+    async fn new() -> Self {
+        init_logging();
+        let mock = MockClaude::new();
+        mock.setup_env_vars();
+
+        let config = Config::from_env().expect("Failed to load config");
+        let session_manager = Arc::new(SessionManager::new(config.clone()));
+        let state = AppState {
+            session_manager: session_manager.clone(),
+            config: Arc::new(config),
+        };
+
+        let app = axum::Router::new()
+            .route(
+                "/api/v1/sessions",
+                axum::routing::post(chef_de_vibe::api::handlers::create_session),
+            )
+            .route(
+                "/api/v1/sessions/:id/claude_ws",
+                axum::routing::get(chef_de_vibe::api::websocket::websocket_handler),
+            )
+            .route(
+                "/api/v1/admin/sessions/:id/faults",
+                axum::routing::get(chef_de_vibe::api::faults::get_faults)
+                    .post(chef_de_vibe::api::faults::inject_faults),
+            )
+            .with_state(state);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server_handle = tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        Self {
+            base_url: format!("http://127.0.0.1:{port}"),
+            ws_url: format!("ws://127.0.0.1:{port}"),
+            mock,
+            server_handle,
+            session_manager,
+        }
+    }
+
+    /// Creates a session and connects a client to it, past the bootstrap output.
+    async fn start_session(&self, name: &str) -> (String, WsStream) {
+        let working_dir = self.mock.temp_dir.path().join(name);
+        fs::create_dir_all(&working_dir).unwrap();
+        let session_id = format!("{name}-{}", std::process::id());
+        let session_file_path = self.mock.projects_dir.join(format!("{session_id}.jsonl"));
+        let create_file_command = serde_json::json!({
+            "control": "write_file",
+            "path": session_file_path.to_string_lossy(),
+            "content": format!(
+                r#"{{"sessionId": "{}", "cwd": "{}", "type": "start"}}"#,
+                session_id,
+                working_dir.display()
+            )
+        })
+        .to_string();
+
+        let response = Client::new()
+            .post(format!("{}/api/v1/sessions", self.base_url))
+            .json(&CreateSessionRequest {
+                session_id,
+                working_dir,
+                resume: false,
+                bootstrap_messages: vec![create_file_command],
+                ..Default::default()
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let session: CreateSessionResponse = response.json().await.unwrap();
+
+        let ws_url = format!("{}{}", self.ws_url, session.websocket_url);
+        let (mut ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+        while let Ok(Some(_)) = timeout(Duration::from_millis(300), ws.next()).await {}
+        (session.session_id, ws)
+    }
+
+    async fn inject(&self, session_id: &str, faults: serde_json::Value) -> FaultsResponse {
+        let response = Client::new()
+            .post(format!(
+                "{}/api/v1/admin/sessions/{session_id}/faults",
+                self.base_url
+            ))
+            .json(&faults)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        response.json().await.unwrap()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server_handle.abort();
+
+        // Use thread-based cleanup to avoid runtime nesting issues
+        let session_manager = self.session_manager.clone();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            rt.block_on(async {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                session_manager.shutdown().await;
+                tokio::time::sleep(Duration::from_millis(300)).await;
+            });
+        });
+    }
+}
+
+async fn send_text(ws: &mut WsStream, text: &str) {
+    let message = serde_json::json!({"role": "user", "content": text}).to_string();
+    ws.send(Message::Text(message)).await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_dropped_broadcasts() {
+    let server = TestServer::new().await;
+    let (session_id, mut ws) = server.start_session("fault-drop").await;
+
+    let status = server
+        .inject(&session_id, serde_json::json!({"drop_broadcasts": 2}))
+        .await;
+    assert_eq!(status.drop_broadcasts, 2);
+
+    // Both the relayed input and Claude's echo of the first message are dropped,
+    // the second message gets through
+    send_text(&mut ws, "lost").await;
+    assert!(timeout(Duration::from_millis(700), ws.next())
+        .await
+        .is_err());
+    send_text(&mut ws, "delivered").await;
+    let frame = timeout(Duration::from_secs(3), ws.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(frame.to_text().unwrap().contains("delivered"));
+
+    let status: FaultsResponse = Client::new()
+        .get(format!(
+            "{}/api/v1/admin/sessions/{session_id}/faults",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status.drop_broadcasts, 0);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_delayed_stdin_writes() {
+    let server = TestServer::new().await;
+    let (session_id, mut ws) = server.start_session("fault-delay").await;
+
+    server
+        .inject(&session_id, serde_json::json!({"stdin_delay_ms": 1000}))
+        .await;
+    send_text(&mut ws, "slow").await;
+    // The relayed input arrives straight away, Claude's echo only after the delay
+    let relayed = timeout(Duration::from_millis(500), ws.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(relayed.to_text().unwrap().contains("slow"));
+    assert!(timeout(Duration::from_millis(500), ws.next())
+        .await
+        .is_err());
+    let frame = timeout(Duration::from_secs(3), ws.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(frame.to_text().unwrap().contains("slow"));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_corrupted_stdout_line_disconnects_clients() {
+    let server = TestServer::new().await;
+    let (session_id, mut ws) = server.start_session("fault-corrupt").await;
+
+    server
+        .inject(&session_id, serde_json::json!({"corrupt_stdout_lines": 1}))
+        .await;
+    send_text(&mut ws, "torn").await;
+
+    // Strict stdout handling gives up on the session at the first torn line
+    let closed = timeout(Duration::from_secs(3), async {
+        loop {
+            match ws.next().await {
+                None | Some(Ok(Message::Close(_)) | Err(_)) => return,
+                Some(Ok(_)) => {}
+            }
+        }
+    })
+    .await;
+    assert!(closed.is_ok(), "The client should have been disconnected");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_simulated_process_exit() {
+    let server = TestServer::new().await;
+    let (session_id, _ws) = server.start_session("fault-exit").await;
+    let session = server.session_manager.get_session(&session_id).unwrap();
+    assert!(session.is_active().await);
+
+    server
+        .inject(&session_id, serde_json::json!({"exit_process": true}))
+        .await;
+    timeout(Duration::from_secs(3), async {
+        while session.is_active().await {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("The session should have noticed its process exit");
+
+    // There is nothing left to kill
+    let response = Client::new()
+        .post(format!(
+            "{}/api/v1/admin/sessions/{session_id}/faults",
+            server.base_url
+        ))
+        .json(&serde_json::json!({"exit_process": true}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
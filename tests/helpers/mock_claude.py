@@ -6,7 +6,14 @@ By default, echoes back any JSON input it receives.
 Supports control commands in JSON format:
   - {"control": "exit", "code": 1}: Exit with specified code
   - {"control": "sleep", "duration": 1.5}: Sleep for specified duration
-  - {"control": "write_file", "path": "/path/to/file", "content": "data"}: Write content to file
+  - {"control": "write_file", "path": "/path/to/file", "content": "data"}: Write content to file;
+    environment variables in the path are expanded
+  - {"control": "print_raw", "text": "data"}: Print text verbatim (not JSON) to stdout
+  - {"control": "spin"}: Burn CPU until killed
+
+With MOCK_CLAUDE_REPLY set, stream-json user messages ({"type": "user", ...}) are
+answered with an assistant message and a result instead of being echoed, and the
+session transcript is written under CLAUDE_PROJECTS_DIR like Claude would.
 """
 
 import sys
@@ -43,6 +50,8 @@ def main():
                     
                     elif control == "write_file":
                         file_path = data.get("path")
+                        if file_path:
+                            file_path = os.path.expandvars(file_path)
                         content = data.get("content", "")
                         if file_path:
                             try:
@@ -53,7 +62,37 @@ def main():
                             except Exception as e:
                                 print(json.dumps({"error": f"Failed to write file: {e}"}), flush=True)
                         continue
+
+                    elif control == "print_raw":
+                        print(data.get("text", ""), flush=True)
+                        continue
+
+                    elif control == "spin":
+                        while True:
+                            pass
                 
+                if os.environ.get("MOCK_CLAUDE_REPLY") and isinstance(data, dict) and data.get("type") == "user":
+                    content = data.get("message", {}).get("content", "")
+                    reply = f"You said: {content}"
+                    if "--session-id" in sys.argv and os.environ.get("CLAUDE_PROJECTS_DIR"):
+                        session_id = sys.argv[sys.argv.index("--session-id") + 1]
+                        transcript = Path(os.environ["CLAUDE_PROJECTS_DIR"]) / "mock" / f"{session_id}.jsonl"
+                        transcript.parent.mkdir(parents=True, exist_ok=True)
+                        with open(transcript, "a") as f:
+                            f.write(json.dumps({"sessionId": session_id, "cwd": os.getcwd(), **data}) + "\n")
+                    print(json.dumps({
+                        "type": "assistant",
+                        "message": {"role": "assistant", "content": [{"type": "text", "text": reply}]},
+                    }), flush=True)
+                    print(json.dumps({
+                        "type": "result",
+                        "subtype": "success",
+                        "is_error": False,
+                        "result": reply,
+                        "usage": {"input_tokens": len(content.split()), "output_tokens": len(reply.split())},
+                    }), flush=True)
+                    continue
+
                 # Echo back the JSON
                 print(json.dumps(data), flush=True)
                 
@@ -230,6 +230,7 @@ async fn test_create_session_empty_bootstrap_messages_validation() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages: vec![], // Empty bootstrap_messages should be rejected
+        ..Default::default()
     };
 
     let response = client
@@ -309,6 +310,7 @@ async fn test_bootstrap_messages_triggers_claude_response() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages,
+        ..Default::default()
     };
 
     let create_response = client
@@ -411,6 +413,7 @@ async fn test_resume_session_with_bootstrap_messages() {
         working_dir: working_dir.clone(),
         resume: true,
         bootstrap_messages,
+        ..Default::default()
     };
 
     let response = client
@@ -522,6 +525,7 @@ async fn test_multiline_bootstrap_messages_compaction() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages,
+        ..Default::default()
     };
 
     let response = client
@@ -582,6 +586,7 @@ async fn test_invalid_json_bootstrap_messages_rejection() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages: vec!["{ invalid json }".to_string()], // Invalid JSON
+        ..Default::default()
     };
 
     let response = client
@@ -640,6 +645,7 @@ async fn test_message_queue_json_compaction() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages,
+        ..Default::default()
     };
 
     let create_response = client
@@ -768,6 +774,7 @@ async fn test_mixed_json_formats_consistency() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages,
+        ..Default::default()
     };
 
     let create_response = client
@@ -0,0 +1,192 @@
+mod helpers;
+
+use chef_de_vibe::{api::handlers::AppState, config::Config, session_manager::SessionManager};
+use helpers::logging::init_logging;
+use helpers::mock_claude::MockClaude;
+use reqwest::Client;
+use serial_test::serial;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+struct TestServer {
+    pub base_url: String,
+    pub mock: MockClaude,
+    server_handle: tokio::task::JoinHandle<()>,
+    session_manager: Arc<SessionManager>,
+}
+
+impl TestServer {
+    async fn new() -> Self {
+        init_logging();
+        let mock = MockClaude::new();
+        mock.setup_env_vars();
+        // Make the mock answer user messages like Claude would
+        std::env::set_var("MOCK_CLAUDE_REPLY", "1");
+
+        let config = Config::from_env().expect("Failed to load config");
+        let session_manager = Arc::new(SessionManager::new(config.clone()));
+        let state = AppState {
+            session_manager: session_manager.clone(),
+            config: Arc::new(config),
+        };
+
+        let app = axum::Router::new()
+            .route(
+                "/v1/chat/completions",
+                axum::routing::post(chef_de_vibe::api::openai::chat_completions),
+            )
+            .with_state(state);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server_handle = tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        Self {
+            base_url,
+            mock,
+            server_handle,
+            session_manager,
+        }
+    }
+
+    async fn wait_for_sessions_to_stop(&self) {
+        for _ in 0..50 {
+            if self.session_manager.get_active_sessions().await.is_empty() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("Chat completion session was not stopped");
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server_handle.abort();
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_chat_completion() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let response = client
+        .post(format!("{}/v1/chat/completions", server.base_url))
+        .json(&serde_json::json!({
+            "model": "claude-sonnet",
+            "messages": [
+                {"role": "system", "content": "Be brief."},
+                {"role": "user", "content": "ping"}
+            ],
+            "working_dir": server.mock.temp_dir.path(),
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let completion: serde_json::Value = response.json().await.unwrap();
+
+    assert_eq!(completion["object"], "chat.completion");
+    assert_eq!(completion["model"], "claude-sonnet");
+    assert!(completion["id"].as_str().unwrap().starts_with("chatcmpl-"));
+    assert_eq!(completion["choices"][0]["message"]["role"], "assistant");
+    assert_eq!(
+        completion["choices"][0]["message"]["content"],
+        "You said: ping"
+    );
+    assert_eq!(completion["choices"][0]["finish_reason"], "stop");
+    assert_eq!(completion["usage"]["prompt_tokens"], 1);
+    assert_eq!(completion["usage"]["completion_tokens"], 3);
+    assert_eq!(completion["usage"]["total_tokens"], 4);
+
+    server.wait_for_sessions_to_stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_chat_completion_stream() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let response = client
+        .post(format!("{}/v1/chat/completions", server.base_url))
+        .json(&serde_json::json!({
+            "messages": [
+                {"role": "user", "content": "first"},
+                {"role": "assistant", "content": "ok"},
+                {"role": "user", "content": "second"}
+            ],
+            "stream": true,
+            "working_dir": server.mock.temp_dir.path(),
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/event-stream"));
+
+    let body = tokio::time::timeout(Duration::from_secs(10), response.text())
+        .await
+        .expect("Stream should end after the result")
+        .unwrap();
+    let data: Vec<&str> = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .collect();
+    assert_eq!(data.last(), Some(&"[DONE]"));
+
+    let chunks: Vec<serde_json::Value> = data[..data.len() - 1]
+        .iter()
+        .map(|chunk| serde_json::from_str(chunk).unwrap())
+        .collect();
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0]["object"], "chat.completion.chunk");
+    assert_eq!(chunks[0]["model"], "claude-code");
+    assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+    let content = chunks[0]["choices"][0]["delta"]["content"]
+        .as_str()
+        .unwrap();
+    // Earlier turns are replayed ahead of the final user message
+    assert!(content.starts_with("You said: Conversation so far:"));
+    assert!(content.contains("User: first"));
+    assert!(content.contains("Assistant: ok"));
+    assert!(content.ends_with("User: second"));
+    assert_eq!(chunks[1]["choices"][0]["finish_reason"], "stop");
+    assert_eq!(chunks[0]["id"], chunks[1]["id"]);
+
+    server.wait_for_sessions_to_stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_chat_completion_rejects_bad_conversation() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let response = client
+        .post(format!("{}/v1/chat/completions", server.base_url))
+        .json(&serde_json::json!({
+            "messages": [{"role": "assistant", "content": "hello"}],
+            "working_dir": server.mock.temp_dir.path(),
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let error: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(error["code"], "INVALID_REQUEST");
+    assert!(server
+        .session_manager
+        .get_active_sessions()
+        .await
+        .is_empty());
+}
@@ -0,0 +1,271 @@
+mod helpers;
+
+use chef_de_vibe::{
+    api::handlers::AppState,
+    config::Config,
+    models::{ListSessionsResponse, PinnedSessionsResponse, PreferencesResponse},
+    session_manager::SessionManager,
+};
+use helpers::logging::init_logging;
+use helpers::mock_claude::MockClaude;
+use reqwest::Client;
+use serial_test::serial;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+const IDENTITY_HEADER: &str = "X-Forwarded-User";
+
+struct TestServer {
+    pub base_url: String,
+    mock: MockClaude,
+    server_handle: tokio::task::JoinHandle<()>,
+}
+
+impl TestServer {
+    async fn new() -> Self {
+        init_logging();
+        let mock = MockClaude::new();
+        mock.setup_env_vars();
+        std::env::set_var("IDENTITY_HEADER", IDENTITY_HEADER);
+
+        let config = Config::from_env().expect("Failed to load config");
+        std::env::remove_var("IDENTITY_HEADER");
+        let session_manager = Arc::new(SessionManager::new(config.clone()));
+        let state = AppState {
+            session_manager,
+            config: Arc::new(config),
+        };
+
+        let app = axum::Router::new()
+            .route(
+                "/api/v1/preferences",
+                axum::routing::get(chef_de_vibe::api::handlers::get_preferences)
+                    .put(chef_de_vibe::api::handlers::set_preferences),
+            )
+            .route(
+                "/api/v1/sessions",
+                axum::routing::get(chef_de_vibe::api::handlers::list_sessions),
+            )
+            .route(
+                "/api/v1/sessions/:id/pin",
+                axum::routing::put(chef_de_vibe::api::handlers::pin_session)
+                    .delete(chef_de_vibe::api::handlers::unpin_session),
+            )
+            .with_state(state);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server_handle = tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        Self {
+            base_url,
+            mock,
+            server_handle,
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server_handle.abort();
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_preferences_per_identity() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+    let url = format!("{}/api/v1/preferences", server.base_url);
+
+    // Without the proxy's header there is nobody to store preferences for
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    let error: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(error["code"], "UNAUTHORIZED");
+
+    let preferences = serde_json::json!({
+        "theme": "dark",
+        "notifications": {"sound": "chime", "volume": 0.4},
+        "pinned_sessions": ["session-a"],
+    });
+    let response = client
+        .put(&url)
+        .header(IDENTITY_HEADER, "alice")
+        .json(&preferences)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let stored: PreferencesResponse = client
+        .get(&url)
+        .header(IDENTITY_HEADER, "alice")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(stored.identity, "alice");
+    assert_eq!(serde_json::Value::Object(stored.preferences), preferences);
+
+    let other: PreferencesResponse = client
+        .get(&url)
+        .header(IDENTITY_HEADER, "bob")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(other.preferences.is_empty());
+
+    let response = client
+        .put(&url)
+        .header(IDENTITY_HEADER, "alice")
+        .json(&serde_json::json!(["not", "an", "object"]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_pinned_sessions_in_list() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    // Sessions on disk, the latest messages of `middle` being the most recent
+    let project_dir = server.mock.projects_dir.join("pins-project");
+    std::fs::create_dir_all(&project_dir).unwrap();
+    for (session_id, created, updated) in [
+        ("pin-old", "2025-01-01T00:00:00Z", "2025-01-02T00:00:00Z"),
+        ("pin-middle", "2025-02-01T00:00:00Z", "2025-04-01T00:00:00Z"),
+        ("pin-new", "2025-03-01T00:00:00Z", "2025-03-02T00:00:00Z"),
+    ] {
+        let lines: Vec<String> = [created, updated]
+            .iter()
+            .map(|timestamp| {
+                serde_json::json!({
+                    "sessionId": session_id,
+                    "cwd": "/tmp/pins",
+                    "type": "user",
+                    "timestamp": timestamp,
+                    "message": {"role": "user", "content": "hello"}
+                })
+                .to_string()
+            })
+            .collect();
+        std::fs::write(
+            project_dir.join(format!("{session_id}.jsonl")),
+            lines.join("\n") + "\n",
+        )
+        .unwrap();
+    }
+
+    for session_id in ["pin-old", "pin-new"] {
+        let response = client
+            .put(format!(
+                "{}/api/v1/sessions/{session_id}/pin",
+                server.base_url
+            ))
+            .header(IDENTITY_HEADER, "alice")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+    let pins: PinnedSessionsResponse = client
+        .delete(format!("{}/api/v1/sessions/pin-old/pin", server.base_url))
+        .header(IDENTITY_HEADER, "alice")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(pins.pinned_sessions, ["pin-new"]);
+    client
+        .put(format!("{}/api/v1/sessions/pin-old/pin", server.base_url))
+        .header(IDENTITY_HEADER, "alice")
+        .send()
+        .await
+        .unwrap();
+
+    // Pins are stored with the rest of the preferences
+    let stored: PreferencesResponse = client
+        .get(format!("{}/api/v1/preferences", server.base_url))
+        .header(IDENTITY_HEADER, "alice")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        stored.preferences["pinned_sessions"],
+        serde_json::json!(["pin-new", "pin-old"])
+    );
+
+    let list = |sort: &'static str, user: &'static str| {
+        let client = client.clone();
+        let url = format!("{}/api/v1/sessions?sort={sort}", server.base_url);
+        async move {
+            let response: ListSessionsResponse = client
+                .get(url)
+                .header(IDENTITY_HEADER, user)
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            response
+                .sessions
+                .into_iter()
+                .map(|session| (session.session_id, session.pinned))
+                .collect::<Vec<_>>()
+        }
+    };
+
+    assert_eq!(
+        list("pinned_first", "alice").await,
+        [
+            ("pin-new".to_string(), true),
+            ("pin-old".to_string(), true),
+            ("pin-middle".to_string(), false)
+        ]
+    );
+    assert_eq!(
+        list("recent", "alice").await,
+        [
+            ("pin-middle".to_string(), false),
+            ("pin-new".to_string(), true),
+            ("pin-old".to_string(), true)
+        ]
+    );
+    assert_eq!(
+        list("created", "bob").await,
+        [
+            ("pin-new".to_string(), false),
+            ("pin-middle".to_string(), false),
+            ("pin-old".to_string(), false)
+        ]
+    );
+
+    let response = client
+        .get(format!(
+            "{}/api/v1/sessions?sort=alphabetical",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
@@ -58,6 +58,10 @@ impl TestServer {
                 "/api/v1/sessions/:id",
                 axum::routing::get(chef_de_vibe::api::handlers::get_session),
             )
+            .route(
+                "/metrics",
+                axum::routing::get(chef_de_vibe::api::handlers::get_metrics),
+            )
             .route(
                 "/api/v1/sessions/:id/claude_ws",
                 axum::routing::get(chef_de_vibe::api::websocket::websocket_handler),
@@ -150,6 +154,7 @@ async fn test_write_queue_fifo_ordering() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages: vec![create_file_command],
+        ..Default::default()
     };
 
     let create_response = client
@@ -226,6 +231,7 @@ async fn test_websocket_close_code_1011_on_process_death() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages: vec![create_file_command],
+        ..Default::default()
     };
 
     let response = client
@@ -327,6 +333,7 @@ async fn test_claude_process_death_complete_journey() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages: vec![create_file_command],
+        ..Default::default()
     };
 
     let response = client
@@ -444,3 +451,271 @@ async fn test_claude_process_death_complete_journey() {
         "New WebSocket connection should fail after process death"
     );
 }
+
+// A deadlocked map shard blocks its worker thread; a second one lets the timeout fire
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[serial]
+async fn test_resume_during_dead_session_grace_period() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("grace_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let write_session_file = |session_id: &str| {
+        serde_json::json!({
+            "control": "write_file",
+            "path": server.mock.projects_dir.join(format!("{session_id}.jsonl")),
+            "content": format!(
+                r#"{{"sessionId": "{session_id}", "cwd": "{}", "type": "start"}}"#,
+                working_dir.display()
+            ),
+        })
+        .to_string()
+    };
+
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: "grace-session".to_string(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![write_session_file("grace-session")],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let session_data: CreateSessionResponse = response.json().await.unwrap();
+
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    ws.send(Message::Text(
+        r#"{"control": "exit", "code": 1}"#.to_string(),
+    ))
+    .await
+    .unwrap();
+    let _ = timeout(Duration::from_secs(5), async {
+        while let Some(Ok(message)) = ws.next().await {
+            if matches!(message, Message::Close(_)) {
+                break;
+            }
+        }
+    })
+    .await;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // Claude is gone, but the session waits out its grace period in the map
+    let exited = server.session_manager.get_session("grace-session").unwrap();
+    assert!(!exited.is_active().await);
+
+    let resumed = timeout(
+        Duration::from_secs(30),
+        client
+            .post(format!("{}/api/v1/sessions", server.base_url))
+            .json(&CreateSessionRequest {
+                session_id: "grace-session".to_string(),
+                working_dir: working_dir.clone(),
+                resume: true,
+                bootstrap_messages: vec![
+                    write_session_file("grace-session-resumed"),
+                    serde_json::json!({"session_id": "grace-session-resumed", "type": "start"})
+                        .to_string(),
+                ],
+                ..Default::default()
+            })
+            .send(),
+    )
+    .await
+    .expect("resuming a session in its grace period should not hang")
+    .unwrap();
+    assert_eq!(resumed.status(), 200);
+    let resumed: CreateSessionResponse = resumed.json().await.unwrap();
+    assert_eq!(resumed.session_id, "grace-session-resumed");
+    assert!(server
+        .session_manager
+        .get_session("grace-session")
+        .is_none());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_abandoned_create_session_is_cleaned_up() {
+    let server = TestServer::new().await;
+
+    let working_dir = server.mock.temp_dir.path().join("abandoned_work");
+    fs::create_dir_all(&working_dir).unwrap();
+
+    // Without a session file the server keeps waiting for Claude's journal
+    let request = CreateSessionRequest {
+        session_id: "abandoned-session".to_string(),
+        working_dir,
+        resume: false,
+        bootstrap_messages: vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+        ..Default::default()
+    };
+    let url = format!("{}/api/v1/sessions", server.base_url);
+    let create = tokio::spawn(async move { Client::new().post(url).json(&request).send().await });
+
+    // Wait for Claude to be running, then give up on the request
+    let session = timeout(Duration::from_secs(5), async {
+        loop {
+            if let Some(session) = server.session_manager.get_session("abandoned-session") {
+                if session.get_process_id().await.is_some() {
+                    return session;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("Claude should have been spawned");
+    create.abort();
+
+    timeout(Duration::from_secs(5), async {
+        while session.get_process_id().await.is_some() {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("Claude should be killed once the client is gone");
+    assert!(server
+        .session_manager
+        .get_session("abandoned-session")
+        .is_none());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_process_resource_sampling() {
+    std::env::set_var("PROCESS_SAMPLE_INTERVAL", "1");
+    let server = TestServer::new().await;
+    std::env::remove_var("PROCESS_SAMPLE_INTERVAL");
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("sampling_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let session_file_path = server.mock.projects_dir.join("sampling-session.jsonl");
+    let create_file_command = serde_json::json!({
+        "control": "write_file",
+        "path": session_file_path.to_string_lossy(),
+        "content": format!(
+            r#"{{"sessionId": "sampling-session", "cwd": "{}", "type": "start"}}"#,
+            working_dir.display()
+        ),
+    })
+    .to_string();
+
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: "sampling-session".to_string(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![create_file_command],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(create_response.status(), 200);
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+
+    // The first sample is taken as soon as the process starts
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+
+    let session: serde_json::Value = client
+        .get(format!(
+            "{}/api/v1/sessions/{}",
+            server.base_url, session_data.session_id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(session["resources"]["rss_bytes"].as_u64().unwrap() > 1024 * 1024);
+
+    let metrics = client
+        .get(format!("{}/metrics", server.base_url))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(metrics.contains(r#"chef_de_vibe_session_rss_bytes{session_id="sampling-session"}"#));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_cpu_limit_kills_claude() {
+    std::env::set_var("CLAUDE_CPU_LIMIT_SECS", "1");
+    let server = TestServer::new().await;
+    std::env::remove_var("CLAUDE_CPU_LIMIT_SECS");
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("cpu_limit_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let session_file_path = server.mock.projects_dir.join("cpu-limit-session.jsonl");
+    let create_file_command = serde_json::json!({
+        "control": "write_file",
+        "path": session_file_path.to_string_lossy(),
+        "content": format!(
+            r#"{{"sessionId": "cpu-limit-session", "cwd": "{}", "type": "start"}}"#,
+            working_dir.display()
+        ),
+    })
+    .to_string();
+
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: "cpu-limit-session".to_string(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![create_file_command],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(create_response.status(), 200);
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut ws_stream, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    ws_stream
+        .send(Message::Text(r#"{"control": "spin"}"#.to_string()))
+        .await
+        .unwrap();
+
+    let notice = timeout(Duration::from_secs(10), async {
+        while let Some(Ok(msg)) = ws_stream.next().await {
+            if let Message::Text(text) = msg {
+                if text.contains("resource_limit_exceeded") {
+                    return text;
+                }
+            }
+        }
+        panic!("WebSocket closed before the resource limit notice");
+    })
+    .await
+    .expect("Claude should be killed for using too much CPU");
+    assert!(notice.contains(r#""resource":"cpu""#), "Got: {notice}");
+
+    let session: serde_json::Value = client
+        .get(format!(
+            "{}/api/v1/sessions/{}",
+            server.base_url, session_data.session_id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(session["status"], "resource_limit_exceeded");
+    assert_eq!(session["resource_limit"], "cpu");
+}
@@ -39,6 +39,10 @@ impl TestServer {
                 "/api/v1/sessions",
                 axum::routing::get(chef_de_vibe::api::handlers::list_sessions),
             )
+            .route(
+                "/api/v1/projects/:id/tools",
+                axum::routing::get(chef_de_vibe::api::handlers::get_project_tools),
+            )
             .with_state(state);
 
         let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
@@ -457,3 +461,148 @@ async fn test_summary_without_uuid_field() {
 
     assert!(session_ids.contains(&"incomplete-session".to_string()));
 }
+
+#[tokio::test]
+#[serial]
+async fn test_project_tool_catalog() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let project_path = server.mock.projects_dir.join("-home-user-project");
+    fs::create_dir_all(&project_path).unwrap();
+    fs::write(
+        project_path.join("first.jsonl"),
+        r#"{"uuid":"u1","sessionId":"first","type":"user","message":{"role":"user","content":"Run the tests"},"cwd":"/home/user/project"}
+{"uuid":"a1","sessionId":"first","type":"assistant","timestamp":"2024-01-01T10:00:00Z","cwd":"/home/user/project","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"Bash","input":{"command":"cargo test --workspace"}}]}}
+{"uuid":"a2","sessionId":"first","type":"assistant","timestamp":"2024-01-01T10:05:00Z","cwd":"/home/user/project","message":{"role":"assistant","content":[{"type":"tool_use","id":"t2","name":"Edit","input":{"file_path":"/home/user/project/src/lib.rs"}}]}}"#,
+    )
+    .unwrap();
+    fs::write(
+        project_path.join("second.jsonl"),
+        r#"{"uuid":"a3","sessionId":"second","type":"assistant","timestamp":"2024-01-02T09:00:00Z","cwd":"/home/user/project","message":{"role":"assistant","content":[{"type":"tool_use","id":"t3","name":"Bash","input":{"command":"cargo test"}},{"type":"tool_use","id":"t4","name":"Bash","input":{"command":"rm -rf target"}}]}}"#,
+    )
+    .unwrap();
+
+    let response = client
+        .get(format!(
+            "{}/api/v1/projects/-home-user-project/tools",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let catalog: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(catalog["working_directory"], "/home/user/project");
+    assert_eq!(catalog["sessions"], 2);
+    assert_eq!(catalog["calls"], 4);
+
+    let bash = &catalog["tools"][0];
+    assert_eq!(bash["name"], "Bash");
+    assert_eq!(bash["calls"], 3);
+    assert_eq!(bash["sessions"], 2);
+    assert_eq!(bash["first_used"], "2024-01-01T10:00:00Z");
+    assert_eq!(bash["last_used"], "2024-01-02T09:00:00Z");
+    // The outlier shows up as a command used once, in a single session
+    assert_eq!(bash["details"][0]["value"], "cargo test");
+    assert_eq!(bash["details"][0]["sessions"], 2);
+    assert_eq!(bash["details"][1]["value"], "rm");
+    assert_eq!(bash["details"][1]["calls"], 1);
+
+    let edit = &catalog["tools"][1];
+    assert_eq!(edit["name"], "Edit");
+    assert_eq!(edit["details"][0]["value"], "src/lib.rs");
+
+    let response = client
+        .get(format!(
+            "{}/api/v1/projects/-home-user-missing/tools",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    let error: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(error["code"], "PROJECT_NOT_FOUND");
+
+    let response = client
+        .get(format!(
+            "{}/api/v1/projects/..%2Fetc/tools",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_list_sessions_filters() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let project_path = server.mock.projects_dir.join("filters");
+    fs::create_dir_all(&project_path).unwrap();
+    let journals = [
+        ("old-app", "/work/app", "2024-01-10T12:00:00.000Z"),
+        ("new-app", "/work/app/backend", "2024-03-10T12:00:00.000Z"),
+        ("apple", "/work/apple", "2024-03-12T12:00:00.000Z"),
+    ];
+    for (session_id, cwd, timestamp) in journals {
+        fs::write(
+            project_path.join(format!("{session_id}.jsonl")),
+            format!(
+                r#"{{"uuid":"{session_id}-1","sessionId":"{session_id}","type":"user","message":{{"role":"user","content":"Hi"}},"cwd":"{cwd}","timestamp":"{timestamp}"}}"#
+            ),
+        )
+        .unwrap();
+    }
+    // No timestamps, so never within a date range
+    fs::write(
+        project_path.join("undated.jsonl"),
+        r#"{"uuid":"undated-1","sessionId":"undated","type":"user","message":{"role":"user","content":"Hi"},"cwd":"/work/app"}"#,
+    )
+    .unwrap();
+
+    let list = |query: &'static str| {
+        let client = client.clone();
+        let url = format!("{}/api/v1/sessions?{query}", server.base_url);
+        async move {
+            let response = client.get(url).send().await.unwrap();
+            assert_eq!(response.status(), 200, "{query}");
+            let body: ListSessionsResponse = response.json().await.unwrap();
+            let mut ids: Vec<String> = body.sessions.into_iter().map(|s| s.session_id).collect();
+            ids.sort();
+            ids
+        }
+    };
+
+    assert_eq!(
+        list("working_dir=/work/app").await,
+        ["new-app", "old-app", "undated"]
+    );
+    assert_eq!(
+        list("since=2024-03-01T00:00:00Z").await,
+        ["apple", "new-app"]
+    );
+    assert_eq!(
+        list("until=2024-03-11T00:00:00Z&working_dir=/work").await,
+        ["new-app", "old-app"]
+    );
+    assert_eq!(
+        list("active=false&working_dir=/work/apple").await,
+        ["apple"]
+    );
+    assert!(list("active=true").await.is_empty());
+
+    let response = client
+        .get(format!(
+            "{}/api/v1/sessions?since=2024-03-01T00:00:00Z&until=2024-01-01T00:00:00Z",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
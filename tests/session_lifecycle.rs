@@ -3,10 +3,12 @@ mod helpers;
 use chef_de_vibe::{
     api::handlers::AppState,
     config::Config,
-    models::{CreateSessionRequest, GetSessionResponse, ListSessionsResponse},
+    models::{
+        CreateSessionRequest, GetSessionResponse, ListSessionsResponse, TerminateSessionResponse,
+    },
     session_manager::SessionManager,
 };
-use futures_util::SinkExt;
+use futures_util::{SinkExt, StreamExt};
 use helpers::logging::init_logging;
 use helpers::mock_claude::MockClaude;
 use reqwest::Client;
@@ -76,7 +78,8 @@ impl TestServer {
             )
             .route(
                 "/api/v1/sessions/:id",
-                axum::routing::get(chef_de_vibe::api::handlers::get_session),
+                axum::routing::get(chef_de_vibe::api::handlers::get_session)
+                    .delete(chef_de_vibe::api::handlers::delete_session),
             )
             .route(
                 "/api/v1/sessions/:id/claude_ws",
@@ -182,6 +185,7 @@ async fn test_session_lifecycle() {
             create_file_command,
             r#"{"role": "user", "content": "Hello"}"#.to_string(),
         ],
+        ..Default::default()
     };
 
     let response = client
@@ -233,6 +237,78 @@ async fn test_session_lifecycle() {
     let _ = ws_stream.close(None).await;
 }
 
+#[tokio::test]
+#[serial]
+async fn test_delete_session_terminates_claude() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("delete_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let create_file_command = serde_json::json!({
+        "control": "write_file",
+        "path": server.mock.projects_dir.join("delete-session.jsonl"),
+        "content": serde_json::json!({
+            "sessionId": "delete-session", "cwd": working_dir, "type": "start",
+        })
+        .to_string(),
+    });
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: "delete-session".to_string(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![create_file_command.to_string()],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let url = Url::parse(&format!(
+        "{}/api/v1/sessions/delete-session/claude_ws",
+        server.ws_url
+    ))
+    .unwrap();
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+
+    let session_url = format!("{}/api/v1/sessions/delete-session", server.base_url);
+    let response = client.delete(&session_url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: TerminateSessionResponse = response.json().await.unwrap();
+    assert_eq!(body.session_id, "delete-session");
+    assert!(body.process_terminated);
+    assert!(!body.killed, "The mock exits on SIGTERM");
+
+    // Connected clients are told to go away
+    let closed = tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(message) = ws_stream.next().await {
+            if matches!(message, Ok(Message::Close(_)) | Err(_)) {
+                return;
+            }
+        }
+    })
+    .await;
+    assert!(closed.is_ok(), "Expected the WebSocket to be closed");
+
+    assert!(server
+        .session_manager
+        .get_session("delete-session")
+        .is_none());
+    let response = client
+        .get(format!("{}/api/v1/sessions", server.base_url))
+        .send()
+        .await
+        .unwrap();
+    let body: ListSessionsResponse = response.json().await.unwrap();
+    assert!(body.sessions.iter().all(|session| !session.active));
+
+    let response = client.delete(&session_url).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
 #[tokio::test]
 #[serial]
 async fn test_corrupted_session_file() {
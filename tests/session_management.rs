@@ -1,11 +1,13 @@
 mod helpers;
 
 use chef_de_vibe::{
-    api::handlers::AppState,
+    api::{body_limit::BodyLimit, handlers::AppState},
     config::Config,
     models::{
-        CreateSessionRequest, CreateSessionResponse, GetSessionResponse, ListSessionsResponse,
+        CreateSessionRequest, CreateSessionResponse, GetSessionResponse, ImportSessionsResponse,
+        ListSessionsResponse, SessionAnchorsResponse, SessionMetadataResponse, SessionOptions,
     },
+    session_backend::BackendKind,
     session_manager::SessionManager,
 };
 use helpers::logging::init_logging;
@@ -59,6 +61,7 @@ impl TestServer {
     async fn new_internal(mock: MockClaude) -> Self {
         let config = Config::from_env().expect("Failed to load config");
         let session_manager = Arc::new(SessionManager::new(config.clone()));
+        let create_session_limit = BodyLimit::create_session(&config.body_limits);
         let state = AppState {
             session_manager: session_manager.clone(),
             config: Arc::new(config),
@@ -72,11 +75,65 @@ impl TestServer {
             )
             .route(
                 "/api/v1/sessions",
-                axum::routing::post(chef_de_vibe::api::handlers::create_session),
+                create_session_limit.apply(axum::routing::post(
+                    chef_de_vibe::api::handlers::create_session,
+                )),
+            )
+            .route(
+                "/api/v1/sessions/import",
+                axum::routing::post(chef_de_vibe::api::handlers::import_sessions),
             )
             .route(
                 "/api/v1/sessions/:id",
-                axum::routing::get(chef_de_vibe::api::handlers::get_session),
+                axum::routing::get(chef_de_vibe::api::handlers::get_session)
+                    .route_layer(axum::middleware::from_fn(
+                        chef_de_vibe::api::caching::conditional_get,
+                    ))
+                    .route_layer(chef_de_vibe::api::caching::compression()),
+            )
+            .route(
+                "/api/v1/sessions/:id/anchors",
+                axum::routing::post(chef_de_vibe::api::handlers::create_anchor),
+            )
+            .route(
+                "/api/v1/sessions/:id/anchors/:name",
+                axum::routing::delete(chef_de_vibe::api::handlers::delete_anchor),
+            )
+            .route(
+                "/api/v1/sessions/:id/metadata",
+                axum::routing::patch(chef_de_vibe::api::handlers::update_session_metadata),
+            )
+            .route(
+                "/api/v1/sessions/:id/files",
+                axum::routing::get(chef_de_vibe::api::handlers::get_session_files),
+            )
+            .route(
+                "/api/v1/sessions/:id/export",
+                axum::routing::get(chef_de_vibe::api::handlers::export_session),
+            )
+            .route(
+                "/api/v1/sessions/:id/media/:media_id",
+                axum::routing::get(chef_de_vibe::api::handlers::get_session_media),
+            )
+            .route(
+                "/api/v1/sessions/:id/scratch",
+                axum::routing::get(chef_de_vibe::api::handlers::get_session_scratch),
+            )
+            .route(
+                "/api/v1/sessions/:id/scratch/*path",
+                axum::routing::get(chef_de_vibe::api::handlers::get_session_scratch_file),
+            )
+            .route(
+                "/api/v1/sessions/:id/wirelog",
+                axum::routing::get(chef_de_vibe::api::handlers::get_session_wirelog),
+            )
+            .route(
+                "/api/v1/sessions/:id/content",
+                axum::routing::get(chef_de_vibe::api::handlers::get_session_content_since),
+            )
+            .route(
+                "/api/v1/sessions/:id/context",
+                axum::routing::get(chef_de_vibe::api::handlers::get_session_context),
             )
             .route(
                 "/api/v1/sessions/:id/claude_ws",
@@ -86,6 +143,22 @@ impl TestServer {
                 "/api/v1/sessions/:id/claude_approvals_ws",
                 axum::routing::get(chef_de_vibe::api::websocket::approval_websocket_handler),
             )
+            .route(
+                "/api/v1/admin/status",
+                axum::routing::get(chef_de_vibe::api::admin::get_admin_status),
+            )
+            .route(
+                "/api/v1/admin/draining",
+                axum::routing::put(chef_de_vibe::api::admin::set_draining),
+            )
+            .route(
+                "/api/v1/admin/rescan",
+                axum::routing::post(chef_de_vibe::api::admin::rescan_sessions),
+            )
+            .route(
+                "/api/v1/admin/sessions/:id/kill",
+                axum::routing::post(chef_de_vibe::api::admin::kill_session),
+            )
             .with_state(state);
 
         // Find a free port
@@ -313,6 +386,7 @@ async fn test_active_sessions_always_listed() {
         working_dir: active_work_dir.clone(),
         resume: false,
         bootstrap_messages: vec![write_command],
+        ..Default::default()
     };
 
     let response = client
@@ -412,6 +486,7 @@ async fn test_create_new_session() {
             create_file_command,
             r#"{"role": "user", "content": "Hello"}"#.to_string(),
         ],
+        ..Default::default()
     };
 
     let response = client
@@ -447,6 +522,60 @@ async fn test_create_new_session() {
     assert!(list_body.sessions[0].active);
 }
 
+#[tokio::test]
+#[serial]
+async fn test_create_session_with_legacy_first_message() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("legacy_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let session_file_path = server.mock.projects_dir.join("legacy-session.jsonl");
+    let create_file_command = serde_json::json!({
+        "control": "write_file",
+        "path": session_file_path.to_string_lossy(),
+        "content": format!(
+            r#"{{"sessionId": "legacy-session", "cwd": "{}", "type": "start"}}"#,
+            working_dir.display()
+        ),
+    })
+    .to_string();
+
+    // Older frontends sent the first message on its own
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&serde_json::json!({
+            "session_id": "legacy-session",
+            "working_dir": working_dir,
+            "resume": false,
+            "first_message": create_file_command,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: CreateSessionResponse = response.json().await.unwrap();
+    assert_eq!(body.session_id, "legacy-session");
+    let deprecation = body.deprecation.expect("Legacy shape should be flagged");
+    assert_eq!(deprecation.field, "first_message");
+    assert_eq!(deprecation.replacement, "bootstrap_messages");
+    assert!(session_file_path.exists());
+
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&serde_json::json!({
+            "session_id": "conflicting-session",
+            "working_dir": working_dir,
+            "resume": false,
+            "first_message": ["a"],
+            "bootstrap_messages": ["b"],
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_client_error());
+}
+
 #[tokio::test]
 #[serial]
 async fn test_create_session_invalid_working_dir() {
@@ -458,6 +587,7 @@ async fn test_create_session_invalid_working_dir() {
         working_dir: server.mock.temp_dir.path().join("non-existent"),
         resume: false,
         bootstrap_messages: vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+        ..Default::default()
     };
 
     let response = client
@@ -525,6 +655,7 @@ async fn test_resume_session() {
             session_init_response,
             r#"{"role": "user", "content": "Resume session"}"#.to_string(),
         ],
+        ..Default::default()
     };
 
     let response = client
@@ -543,6 +674,90 @@ async fn test_resume_session() {
     assert!(body.approval_websocket_url.contains(&body.session_id));
 }
 
+#[tokio::test]
+#[serial]
+async fn test_list_sessions_collapses_resumed_chain() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("work");
+    fs::create_dir_all(&working_dir).unwrap();
+
+    create_test_session_file(
+        &server.mock.projects_dir,
+        "work",
+        "chain-old",
+        working_dir.to_str().unwrap(),
+    );
+
+    // Resume hands back a new session ID, leaving the old transcript on disk
+    let new_session_id = "chain-new";
+    let create_file_command = serde_json::json!({
+        "control": "write_file",
+        "path": server.mock.projects_dir.join(format!("{new_session_id}.jsonl")).to_string_lossy(),
+        "content": format!(
+            r#"{{"sessionId": "{new_session_id}", "cwd": "{}", "type": "user", "message": {{"role": "user", "content": "Resumed"}}}}"#,
+            working_dir.display()
+        )
+    })
+    .to_string();
+    let session_init_response =
+        serde_json::json!({"session_id": new_session_id, "type": "start"}).to_string();
+
+    let request = CreateSessionRequest {
+        session_id: "chain-old".to_string(),
+        working_dir: working_dir.clone(),
+        resume: true,
+        bootstrap_messages: vec![create_file_command, session_init_response],
+        ..Default::default()
+    };
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: CreateSessionResponse = response.json().await.unwrap();
+    assert_eq!(body.session_id, new_session_id);
+
+    let listed: ListSessionsResponse = client
+        .get(format!("{}/api/v1/sessions", server.base_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(!listed.sessions.iter().any(|s| s.session_id == "chain-old"));
+    let head = listed
+        .sessions
+        .iter()
+        .find(|s| s.session_id == new_session_id)
+        .expect("Latest segment should be listed");
+    assert_eq!(head.resumed_from.as_deref(), Some("chain-old"));
+
+    let expanded: ListSessionsResponse = client
+        .get(format!(
+            "{}/api/v1/sessions?expand_lineage=true",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(expanded
+        .sessions
+        .iter()
+        .any(|s| s.session_id == "chain-old"));
+    assert!(expanded
+        .sessions
+        .iter()
+        .any(|s| s.session_id == new_session_id));
+}
+
 #[tokio::test]
 #[serial]
 async fn test_get_session_active() {
@@ -559,6 +774,7 @@ async fn test_get_session_active() {
         working_dir: working_dir.clone(),
         resume: false,
         bootstrap_messages: vec![r#"{"role": "user", "content": "Hello"}"#.to_string()],
+        ..Default::default()
     };
 
     client
@@ -589,6 +805,85 @@ async fn test_get_session_active() {
     );
 }
 
+#[tokio::test]
+#[serial]
+async fn test_import_claude_desktop_conversation() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+    let working_dir = server.mock.temp_dir.path().join("imported_work");
+    fs::create_dir_all(&working_dir).unwrap();
+
+    let export = serde_json::json!([{
+        "uuid": "desktop-conversation",
+        "name": "Plan the migration",
+        "chat_messages": [
+            {"sender": "human", "text": "How should we migrate?", "created_at": "2024-05-01T12:00:00Z"},
+            {"sender": "assistant", "text": "In three steps.", "created_at": "2024-05-01T12:00:05Z"}
+        ]
+    }]);
+    let import_url = format!("{}/api/v1/sessions/import", server.base_url);
+    let response = client
+        .post(&import_url)
+        .query(&[
+            ("format", "claude_desktop"),
+            ("working_dir", working_dir.to_str().unwrap()),
+        ])
+        .json(&export)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let imported: ImportSessionsResponse = response.json().await.unwrap();
+    assert_eq!(imported.sessions.len(), 1);
+    assert_eq!(imported.sessions[0].messages, 2);
+
+    // The imported session is listed with its title and can be read like any other
+    let sessions: ListSessionsResponse = client
+        .get(format!("{}/api/v1/sessions", server.base_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let listed = sessions
+        .sessions
+        .iter()
+        .find(|s| s.session_id == imported.sessions[0].session_id)
+        .expect("Imported session should be listed");
+    assert_eq!(listed.summary.as_deref(), Some("Plan the migration"));
+    assert_eq!(listed.working_directory, working_dir);
+
+    let session: GetSessionResponse = client
+        .get(format!(
+            "{}/api/v1/sessions/{}",
+            server.base_url, imported.sessions[0].session_id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        session.content[1]["message"]["content"][0]["text"],
+        "In three steps."
+    );
+
+    // Importing into a directory that does not exist is refused
+    let response = client
+        .post(&import_url)
+        .query(&[
+            ("format", "claude_desktop"),
+            ("working_dir", "/nonexistent/dir"),
+        ])
+        .json(&export)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
 #[tokio::test]
 #[serial]
 async fn test_get_session_from_disk() {
@@ -620,106 +915,800 @@ async fn test_get_session_from_disk() {
 
 #[tokio::test]
 #[serial]
-async fn test_get_session_not_found() {
+async fn test_get_session_as_ndjson() {
     let server = TestServer::new().await;
     let client = Client::new();
 
+    create_test_session_file(
+        &server.mock.projects_dir,
+        "project1",
+        "streamed-session",
+        "/home/user/project1",
+    );
+    let url = format!("{}/api/v1/sessions/streamed-session", server.base_url);
+
     let response = client
-        .get(format!("{}/api/v1/sessions/non-existent", server.base_url))
+        .get(format!("{url}?format=ndjson&offset=1&limit=2"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    assert!(!response.headers().contains_key("etag"));
+    let body = response.text().await.unwrap();
+    let entries: Vec<serde_json::Value> = body
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["message"]["content"], "Hello Claude");
+    assert_eq!(entries[1]["type"], "assistant");
+
+    // The whole journal without a range
+    let body = client
+        .get(format!("{url}?format=ndjson"))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(body.lines().count(), 5);
+
+    let response = client
+        .get(format!("{url}?format=ndjson&hook=anything"))
         .send()
         .await
         .unwrap();
+    assert_eq!(response.status(), 400);
 
+    let response = client
+        .get(format!(
+            "{}/api/v1/sessions/missing-session?format=ndjson",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
     assert_eq!(response.status(), 404);
 }
 
 #[tokio::test]
 #[serial]
-async fn test_ping_pong_active_session_first_user_message_as_summary() {
+async fn test_get_session_content_since() {
     let server = TestServer::new().await;
     let client = Client::new();
 
-    // Create a temporary work directory
-    let work_dir = server.mock.temp_dir.path().join("ping_pong_test");
-    fs::create_dir_all(&work_dir).unwrap();
+    create_test_session_file(
+        &server.mock.projects_dir,
+        "project1",
+        "synced-session",
+        "/home/user/project1",
+    );
+    let url = format!("{}/api/v1/sessions/synced-session/content", server.base_url);
+    let get = |query: &str| {
+        let request = client.get(format!("{url}{query}"));
+        async move {
+            let response = request.send().await.unwrap();
+            let status = response.status();
+            (status, response.json::<serde_json::Value>().await.unwrap())
+        }
+    };
 
-    let session_id = "ping-pong-session-test";
+    let (status, body) = get("").await;
+    assert_eq!(status, 200);
+    assert_eq!(body["content"].as_array().unwrap().len(), 5);
+    assert_eq!(body["first_line"], 0);
+    assert_eq!(body["next_line"], 5);
 
-    // Calculate the project folder path that Claude will use
-    let project_folder = format!("-{}", work_dir.display().to_string().replace('/', "-"));
-    let project_path = server.mock.projects_dir.join(&project_folder);
-    fs::create_dir_all(&project_path).unwrap();
+    let journal = server
+        .mock
+        .projects_dir
+        .join("project1/synced-session.jsonl");
+    let mut file = fs::OpenOptions::new().append(true).open(&journal).unwrap();
+    std::io::Write::write_all(
+        &mut file,
+        b"{\"sessionId\": \"synced-session\", \"uuid\": \"u-6\", \"type\": \"user\"}\n\
+          {\"sessionId\": \"synced-session\", \"uuid\": \"u-7\", \"type\": \"assistant\"}\n",
+    )
+    .unwrap();
+
+    let (_, body) = get("?after_line=5").await;
+    assert_eq!(body["first_line"], 5);
+    assert_eq!(body["next_line"], 7);
+    assert_eq!(body["content"][0]["uuid"], "u-6");
+    assert_eq!(body["reset"], false);
+
+    let (_, body) = get("?after_uuid=u-6").await;
+    assert_eq!(body["first_line"], 6);
+    assert_eq!(body["content"].as_array().unwrap().len(), 1);
+    assert_eq!(body["content"][0]["uuid"], "u-7");
+
+    let (_, body) = get("?after_line=7").await;
+    assert!(body["content"].as_array().unwrap().is_empty());
+
+    // A position the journal never reached tells the client to start over
+    let (_, body) = get("?after_line=40").await;
+    assert_eq!(body["reset"], true);
+
+    let (status, body) = get("?after_uuid=missing").await;
+    assert_eq!(status, 400);
+    assert_eq!(body["code"], "INVALID_REQUEST");
+    let (status, _) = get("?after_line=1&after_uuid=u-6").await;
+    assert_eq!(status, 400);
+}
 
-    // Path where the session file should be written
-    let session_file_path = project_path.join(format!("{session_id}.jsonl"));
+#[tokio::test]
+#[serial]
+async fn test_get_session_is_conditional_and_compressed() {
+    let server = TestServer::new().await;
+    let client = Client::new();
 
-    // Create the session content that simulates the ping-pong conversation
-    let session_content = format!(
-        r#"{{"parentUuid":null,"isSidechain":false,"userType":"external","cwd":"{}","sessionId":"{}","version":"1.0.108","gitBranch":"master","type":"user","message":{{"role":"user","content":"ping"}},"uuid":"0136315c-067d-410c-abdd-95aeadcf7e82","timestamp":"2025-09-20T09:39:07.234Z"}}
-{{"parentUuid":"0136315c-067d-410c-abdd-95aeadcf7e82","isSidechain":false,"userType":"external","cwd":"{}","sessionId":"{}","version":"1.0.108","gitBranch":"master","message":{{"id":"msg_01N1Xnc57sfsbeiXaf4aGdXK","type":"message","role":"assistant","model":"claude-opus-4-1-20250805","content":[{{"type":"text","text":"pong"}}],"stop_reason":null,"stop_sequence":null,"usage":{{"input_tokens":4,"cache_creation_input_tokens":19557,"cache_read_input_tokens":0,"cache_creation":{{"ephemeral_5m_input_tokens":19557,"ephemeral_1h_input_tokens":0}},"output_tokens":5,"service_tier":"standard"}}}},"requestId":"req_011CTKRuD4pxvgMZSghPgRxH","type":"assistant","uuid":"8d68843d-4578-471c-aeaa-83a47083329b","timestamp":"2025-09-20T09:39:10.179Z"}}"#,
-        work_dir.display(),
-        session_id,
-        work_dir.display(),
-        session_id
+    create_test_session_file(
+        &server.mock.projects_dir,
+        "project1",
+        "cached-session",
+        "/home/user/project1",
     );
-
-    // Create bootstrap messages that will:
-    // 1. First respond with the session ID to complete the handshake
-    // 2. Then use write_file control command to create the journal file
-    let session_id_response = format!(r#"{{"sessionId": "{session_id}"}}"#);
-
-    let write_command = serde_json::json!({
-        "control": "write_file",
-        "path": session_file_path.to_string_lossy(),
-        "content": session_content
-    })
-    .to_string();
-
-    // Create the active session via the API
-    let create_request = CreateSessionRequest {
-        session_id: session_id.to_string(),
-        working_dir: work_dir.clone(),
-        resume: false,
-        bootstrap_messages: vec![
-            session_id_response, // First, respond with session ID for handshake
-            write_command,       // Then, write the journal file
-        ],
-    };
+    let url = format!("{}/api/v1/sessions/cached-session", server.base_url);
 
     let response = client
-        .post(format!("{}/api/v1/sessions", server.base_url))
-        .json(&create_request)
+        .get(&url)
+        .header("accept-encoding", "gzip")
         .send()
         .await
         .unwrap();
-
     assert_eq!(response.status(), 200);
-    let create_response: CreateSessionResponse = response.json().await.unwrap();
-    assert!(create_response.websocket_url.contains(session_id));
-
-    // Give a moment for the file to be written
-    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    assert_eq!(response.headers()["content-encoding"], "gzip");
+    assert_eq!(response.headers()["cache-control"], "no-cache");
+    let etag = response.headers()["etag"].clone();
+    assert!(etag.to_str().unwrap().starts_with("W/\""));
 
-    // Now list sessions - the active session should appear with "ping" as summary
     let response = client
-        .get(format!("{}/api/v1/sessions", server.base_url))
+        .get(&url)
+        .header("if-none-match", etag.clone())
         .send()
         .await
         .unwrap();
+    assert_eq!(response.status(), 304);
+    assert_eq!(response.headers()["etag"], etag);
+    assert!(response.bytes().await.unwrap().is_empty());
 
+    // Without Accept-Encoding the JSON is sent as it is
+    let response = client
+        .get(&url)
+        .header("if-none-match", "W/\"stale\"")
+        .send()
+        .await
+        .unwrap();
     assert_eq!(response.status(), 200);
-    let list_response: ListSessionsResponse = response.json().await.unwrap();
+    assert!(!response.headers().contains_key("content-encoding"));
+    let body: GetSessionResponse = response.json().await.unwrap();
+    assert_eq!(body.session_id, "cached-session");
+}
 
-    // Find our ping-pong session
-    let ping_pong_session = list_response
-        .sessions
-        .iter()
-        .find(|s| s.session_id == session_id)
-        .expect("Ping-pong session should be in the list");
+#[tokio::test]
+#[serial]
+async fn test_export_session() {
+    let server = TestServer::new().await;
+    let client = Client::new();
 
-    // Verify the session is active
-    assert!(
-        ping_pong_session.active,
+    create_test_session_file(
+        &server.mock.projects_dir,
+        "project1",
+        "exported-session",
+        "/home/user/project1",
+    );
+    let export_url = format!(
+        "{}/api/v1/sessions/exported-session/export",
+        server.base_url
+    );
+
+    let response = client.get(&export_url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.headers()["content-type"],
+        "text/markdown; charset=utf-8"
+    );
+    let markdown = response.text().await.unwrap();
+    assert!(markdown.starts_with("# Session exported-session\n"));
+    assert!(markdown.contains("## User\n\nWhat's 2+2?\n"));
+    assert!(markdown.contains("## Assistant\n\n2 + 2 equals 4.\n"));
+
+    let response = client
+        .get(format!("{export_url}?format=html"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()["content-type"],
+        "text/html; charset=utf-8"
+    );
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("<p>Hello Claude</p>"));
+
+    let response = client
+        .get(format!("{export_url}?format=json"))
+        .send()
+        .await
+        .unwrap();
+    let exported: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(exported["turns"].as_array().unwrap().len(), 4);
+
+    let response = client
+        .get(format!(
+            "{}/api/v1/sessions/missing-session/export",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_get_session_over_size_limit_is_paged() {
+    std::env::set_var("SESSION_RESPONSE_MAX_BYTES", "100");
+    let server = TestServer::new().await;
+    std::env::remove_var("SESSION_RESPONSE_MAX_BYTES");
+    let client = Client::new();
+
+    create_test_session_file(
+        &server.mock.projects_dir,
+        "project1",
+        "large-session",
+        "/home/user/project1",
+    );
+    let get = |query: &'static str| {
+        let request = client.get(format!(
+            "{}/api/v1/sessions/large-session{query}",
+            server.base_url
+        ));
+        async move {
+            let response = request.send().await.unwrap();
+            assert_eq!(response.status(), 200);
+            response.json::<GetSessionResponse>().await.unwrap()
+        }
+    };
+
+    // Over the limit only the paging descriptor comes back
+    let body = get("").await;
+    assert!(body.content.is_empty());
+    let paging = body.paging.unwrap();
+    assert!(paging.omitted);
+    assert_eq!(paging.total_entries, 5);
+    assert!(paging.total_bytes > 100);
+    assert_eq!(paging.next_offset, Some(0));
+
+    let body = get("?full=true").await;
+    assert_eq!(body.content.len(), 5);
+    assert!(body.paging.is_none());
+
+    let body = get("?offset=1&limit=2").await;
+    assert_eq!(body.content.len(), 2);
+    let paging = body.paging.unwrap();
+    assert!(!paging.omitted);
+    assert_eq!(paging.next_offset, Some(3));
+
+    // The tail of the transcript is the last page
+    let body = get("?offset=4&limit=2").await;
+    assert_eq!(body.content.len(), 1);
+    assert_eq!(body.paging.unwrap().next_offset, None);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_create_session_body_limit() {
+    std::env::set_var("CREATE_SESSION_BODY_MAX_BYTES", "4096");
+    let server = TestServer::new().await;
+    std::env::remove_var("CREATE_SESSION_BODY_MAX_BYTES");
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("body_limit_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let request = CreateSessionRequest {
+        session_id: "too-large-session".to_string(),
+        working_dir,
+        resume: false,
+        bootstrap_messages: vec![format!(
+            r#"{{"role": "user", "content": "{}"}}"#,
+            "x".repeat(8192)
+        )],
+        ..Default::default()
+    };
+
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 413);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "PAYLOAD_TOO_LARGE");
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("CREATE_SESSION_BODY_MAX_BYTES"));
+    assert!(server
+        .session_manager
+        .get_session("too-large-session")
+        .is_none());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_session_anchors() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let project_path = server.mock.projects_dir.join("anchored");
+    fs::create_dir_all(&project_path).unwrap();
+    fs::write(
+        project_path.join("anchored-session.jsonl"),
+        r#"{"sessionId": "anchored-session", "cwd": "/home/user/anchored", "type": "start"}
+{"sessionId": "anchored-session", "type": "user", "uuid": "u1", "message": {"role": "user", "content": "Let's design it"}}
+{"sessionId": "anchored-session", "type": "assistant", "uuid": "u2", "message": {"role": "assistant", "content": [{"type": "text", "text": "Here's a design"}]}}
+{"sessionId": "anchored-session", "type": "user", "uuid": "u3", "message": {"role": "user", "content": "Write the patch"}}
+"#,
+    )
+    .unwrap();
+    let anchors_url = format!(
+        "{}/api/v1/sessions/anchored-session/anchors",
+        server.base_url
+    );
+
+    for (name, uuid) in [("final patch", "u3"), ("design discussion", "u1")] {
+        let response = client
+            .post(&anchors_url)
+            .json(&serde_json::json!({"name": name, "message_uuid": uuid}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+    let response = client
+        .post(&anchors_url)
+        .json(&serde_json::json!({"name": "nowhere", "message_uuid": "missing"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    // Listed in conversation order, with where to page to
+    let session: GetSessionResponse = client
+        .get(format!(
+            "{}/api/v1/sessions/anchored-session",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let anchors: Vec<_> = session
+        .anchors
+        .iter()
+        .map(|a| (a.name.as_str(), a.message_uuid.as_str(), a.entry_index))
+        .collect();
+    assert_eq!(
+        anchors,
+        vec![
+            ("design discussion", "u1", Some(1)),
+            ("final patch", "u3", Some(3)),
+        ]
+    );
+    assert_eq!(session.anchors[0].created_by, "default");
+
+    let remaining: SessionAnchorsResponse = client
+        .delete(format!("{anchors_url}/final%20patch"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(remaining.anchors.len(), 1);
+    assert_eq!(remaining.anchors[0].name, "design discussion");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_session_tags_and_metadata() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    create_test_session_file(
+        &server.mock.projects_dir,
+        "project1",
+        "tagged-session",
+        "/home/user/project1",
+    );
+    create_test_session_file(
+        &server.mock.projects_dir,
+        "project2",
+        "untagged-session",
+        "/home/user/project2",
+    );
+    let metadata_url = format!(
+        "{}/api/v1/sessions/tagged-session/metadata",
+        server.base_url
+    );
+
+    let updated: SessionMetadataResponse = client
+        .patch(&metadata_url)
+        .json(&serde_json::json!({
+            "add_tags": ["bugfix", "backend"],
+            "metadata": {"ticket": "PROJ-42", "reviewer": "sam"}
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(updated.tags, ["backend", "bugfix"]);
+    let updated: SessionMetadataResponse = client
+        .patch(&metadata_url)
+        .json(&serde_json::json!({
+            "remove_tags": ["backend"],
+            "metadata": {"reviewer": null}
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(updated.tags, ["bugfix"]);
+    assert_eq!(updated.metadata.len(), 1);
+
+    let tagged: ListSessionsResponse = client
+        .get(format!("{}/api/v1/sessions?tag=bugfix", server.base_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(tagged.sessions.len(), 1);
+    let session = &tagged.sessions[0];
+    assert_eq!(session.session_id, "tagged-session");
+    assert_eq!(session.tags, ["bugfix"]);
+    assert_eq!(session.metadata["ticket"], "PROJ-42");
+
+    let response = client
+        .patch(&metadata_url)
+        .json(&serde_json::json!({"add_tags": ["two words"]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let response = client
+        .patch(format!(
+            "{}/api/v1/sessions/missing-session/metadata",
+            server.base_url
+        ))
+        .json(&serde_json::json!({"add_tags": ["bugfix"]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_get_session_with_transcript_hook() {
+    std::env::set_var(
+        "TRANSCRIPT_HOOKS",
+        r#"drop_assistant=grep -v "type":"assistant""#,
+    );
+    let server = TestServer::new().await;
+    std::env::remove_var("TRANSCRIPT_HOOKS");
+    let client = Client::new();
+
+    create_test_session_file(
+        &server.mock.projects_dir,
+        "project1",
+        "hooked-session",
+        "/home/user/project1",
+    );
+
+    let response = client
+        .get(format!(
+            "{}/api/v1/sessions/hooked-session?hook=drop_assistant",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    let body: GetSessionResponse = response.json().await.unwrap();
+    assert_eq!(body.content.len(), 3);
+    assert!(body
+        .content
+        .iter()
+        .all(|entry| entry["type"] != "assistant"));
+
+    // Only hooks configured on the server can be requested
+    let response = client
+        .get(format!(
+            "{}/api/v1/sessions/hooked-session?hook=rm",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    // Exports run the hook before rendering
+    let export_url = format!("{}/api/v1/sessions/hooked-session/export", server.base_url);
+    let response = client
+        .get(format!("{export_url}?format=json&hook=drop_assistant"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let exported: serde_json::Value = response.json().await.unwrap();
+    let turns = exported["turns"].as_array().unwrap();
+    assert!(!turns.is_empty());
+    assert!(turns.iter().all(|turn| turn["role"] == "user"));
+    let response = client
+        .get(format!("{export_url}?hook=rm"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_get_session_files_at_message() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let repo = server.mock.temp_dir.path().join("time_travel_repo");
+    fs::create_dir_all(&repo).unwrap();
+    let git = |args: &[&str], date: &str| {
+        let status = std::process::Command::new("git")
+            .arg("-C")
+            .arg(&repo)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .env("GIT_AUTHOR_DATE", date)
+            .env("GIT_COMMITTER_DATE", date)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    git(&["init", "-q"], "2025-01-01T09:00:00Z");
+    fs::write(repo.join("main.rs"), "fn main() {}").unwrap();
+    git(&["add", "."], "2025-01-01T10:00:00Z");
+    git(&["commit", "-qm", "first"], "2025-01-01T10:00:00Z");
+    fs::write(repo.join("main.rs"), "fn main() { lib::run() }").unwrap();
+    fs::write(repo.join("lib.rs"), "pub fn run() {}").unwrap();
+    git(&["add", "."], "2025-01-01T12:00:00Z");
+    git(&["commit", "-qm", "second"], "2025-01-01T12:00:00Z");
+
+    let project_path = server.mock.projects_dir.join("time_travel");
+    fs::create_dir_all(&project_path).unwrap();
+    fs::write(
+        project_path.join("time-travel.jsonl"),
+        format!(
+            r#"{{"sessionId": "time-travel", "cwd": "{}", "type": "start"}}
+{{"sessionId": "time-travel", "type": "assistant", "uuid": "early", "timestamp": "2025-01-01T11:00:00.000Z", "message": {{"role": "assistant", "content": "Created main.rs"}}}}
+{{"sessionId": "time-travel", "type": "assistant", "uuid": "late", "timestamp": "2025-01-01T13:00:00.000Z", "message": {{"role": "assistant", "content": "Added lib.rs"}}}}
+"#,
+            repo.display()
+        ),
+    )
+    .unwrap();
+
+    let files_url = format!("{}/api/v1/sessions/time-travel/files", server.base_url);
+    let early: serde_json::Value = client
+        .get(format!("{files_url}?at_uuid=early&path=main.rs"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(early["files"], serde_json::json!(["main.rs"]));
+    assert_eq!(early["content"], "fn main() {}");
+
+    let late: serde_json::Value = client
+        .get(format!("{files_url}?at_uuid=late"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(late["files"], serde_json::json!(["lib.rs", "main.rs"]));
+    assert_ne!(late["commit"], early["commit"]);
+
+    let response = client
+        .get(format!("{files_url}?at_uuid=missing"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_get_session_context() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    // The mock's projects directory sits in the temp dir, which plays the user's ~/.claude
+    let user_dir = server.mock.temp_dir.path();
+    fs::write(
+        user_dir.join("settings.json"),
+        r#"{"permissions": {"deny": ["Bash(rm:*)"]}}"#,
+    )
+    .unwrap();
+    let project = user_dir.join("context_project");
+    fs::create_dir_all(project.join(".claude")).unwrap();
+    fs::write(project.join("CLAUDE.md"), "Never touch prod.").unwrap();
+    fs::write(
+        project.join(".mcp.json"),
+        r#"{"mcpServers": {"github": {"command": "gh-mcp", "env": {"GITHUB_TOKEN": "secret"}}}}"#,
+    )
+    .unwrap();
+    create_test_session_file(
+        &server.mock.projects_dir,
+        "context",
+        "context-session",
+        &project.display().to_string(),
+    );
+
+    let context: serde_json::Value = client
+        .get(format!(
+            "{}/api/v1/sessions/context-session/context",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(context["session_id"], "context-session");
+    assert_eq!(context["instructions"].as_array().unwrap().len(), 1);
+    assert_eq!(context["instructions"][0]["scope"], "project");
+    assert_eq!(context["instructions"][0]["content"], "Never touch prod.");
+    assert_eq!(context["settings"][0]["scope"], "user");
+    assert_eq!(
+        context["settings"][0]["permissions"]["deny"],
+        serde_json::json!(["Bash(rm:*)"])
+    );
+    assert_eq!(context["mcp_servers"][0]["name"], "github");
+    assert_eq!(
+        context["mcp_servers"][0]["config"]["env"]["GITHUB_TOKEN"],
+        "[redacted]"
+    );
+
+    let response = client
+        .get(format!(
+            "{}/api/v1/sessions/non-existent/context",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_get_session_not_found() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let response = client
+        .get(format!("{}/api/v1/sessions/non-existent", server.base_url))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_ping_pong_active_session_first_user_message_as_summary() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    // Create a temporary work directory
+    let work_dir = server.mock.temp_dir.path().join("ping_pong_test");
+    fs::create_dir_all(&work_dir).unwrap();
+
+    let session_id = "ping-pong-session-test";
+
+    // Calculate the project folder path that Claude will use
+    let project_folder = format!("-{}", work_dir.display().to_string().replace('/', "-"));
+    let project_path = server.mock.projects_dir.join(&project_folder);
+    fs::create_dir_all(&project_path).unwrap();
+
+    // Path where the session file should be written
+    let session_file_path = project_path.join(format!("{session_id}.jsonl"));
+
+    // Create the session content that simulates the ping-pong conversation
+    let session_content = format!(
+        r#"{{"parentUuid":null,"isSidechain":false,"userType":"external","cwd":"{}","sessionId":"{}","version":"1.0.108","gitBranch":"master","type":"user","message":{{"role":"user","content":"ping"}},"uuid":"0136315c-067d-410c-abdd-95aeadcf7e82","timestamp":"2025-09-20T09:39:07.234Z"}}
+{{"parentUuid":"0136315c-067d-410c-abdd-95aeadcf7e82","isSidechain":false,"userType":"external","cwd":"{}","sessionId":"{}","version":"1.0.108","gitBranch":"master","message":{{"id":"msg_01N1Xnc57sfsbeiXaf4aGdXK","type":"message","role":"assistant","model":"claude-opus-4-1-20250805","content":[{{"type":"text","text":"pong"}}],"stop_reason":null,"stop_sequence":null,"usage":{{"input_tokens":4,"cache_creation_input_tokens":19557,"cache_read_input_tokens":0,"cache_creation":{{"ephemeral_5m_input_tokens":19557,"ephemeral_1h_input_tokens":0}},"output_tokens":5,"service_tier":"standard"}}}},"requestId":"req_011CTKRuD4pxvgMZSghPgRxH","type":"assistant","uuid":"8d68843d-4578-471c-aeaa-83a47083329b","timestamp":"2025-09-20T09:39:10.179Z"}}"#,
+        work_dir.display(),
+        session_id,
+        work_dir.display(),
+        session_id
+    );
+
+    // Create bootstrap messages that will:
+    // 1. First respond with the session ID to complete the handshake
+    // 2. Then use write_file control command to create the journal file
+    let session_id_response = format!(r#"{{"sessionId": "{session_id}"}}"#);
+
+    let write_command = serde_json::json!({
+        "control": "write_file",
+        "path": session_file_path.to_string_lossy(),
+        "content": session_content
+    })
+    .to_string();
+
+    // Create the active session via the API
+    let create_request = CreateSessionRequest {
+        session_id: session_id.to_string(),
+        working_dir: work_dir.clone(),
+        resume: false,
+        bootstrap_messages: vec![
+            session_id_response, // First, respond with session ID for handshake
+            write_command,       // Then, write the journal file
+        ],
+        ..Default::default()
+    };
+
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&create_request)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    let create_response: CreateSessionResponse = response.json().await.unwrap();
+    assert!(create_response.websocket_url.contains(session_id));
+
+    // Give a moment for the file to be written
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    // Now list sessions - the active session should appear with "ping" as summary
+    let response = client
+        .get(format!("{}/api/v1/sessions", server.base_url))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    let list_response: ListSessionsResponse = response.json().await.unwrap();
+
+    // Find our ping-pong session
+    let ping_pong_session = list_response
+        .sessions
+        .iter()
+        .find(|s| s.session_id == session_id)
+        .expect("Ping-pong session should be in the list");
+
+    // Verify the session is active
+    assert!(
+        ping_pong_session.active,
         "Session should be marked as active"
     );
 
@@ -1140,3 +2129,566 @@ async fn test_list_sessions_large_scale() {
         "Large scale list should complete within 5 seconds, took {duration:?}"
     );
 }
+
+#[tokio::test]
+#[serial]
+async fn test_create_session_with_system_prompts() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("prompt_work");
+    fs::create_dir_all(&working_dir).unwrap();
+
+    let session_file_path = server.mock.projects_dir.join("prompt-session.jsonl");
+    let session_content = format!(
+        r#"{{"sessionId": "prompt-session", "cwd": "{}", "type": "start"}}"#,
+        working_dir.display()
+    );
+    let create_file_command = serde_json::json!({
+        "control": "write_file",
+        "path": session_file_path.to_string_lossy(),
+        "content": session_content
+    })
+    .to_string();
+
+    let request = CreateSessionRequest {
+        session_id: "prompt-session".to_string(),
+        working_dir: working_dir.clone(),
+        resume: false,
+        bootstrap_messages: vec![create_file_command],
+        first_message_upload: None,
+        options: SessionOptions {
+            system_prompt: None,
+            append_system_prompt: Some("Never push to main".to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client
+        .get(format!(
+            "{}/api/v1/sessions/prompt-session",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let body: GetSessionResponse = response.json().await.unwrap();
+    assert_eq!(body.options.system_prompt, None);
+    assert_eq!(
+        body.options.append_system_prompt.as_deref(),
+        Some("Never push to main")
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_create_session_on_chosen_backend() {
+    use std::os::unix::fs::PermissionsExt;
+
+    init_logging();
+    let mock = MockClaude::new();
+    mock.setup_env_vars();
+    // Fake runtime that records how it was run and starts the mock Claude on the host
+    let runtime = mock.temp_dir.path().join("runtime");
+    let runtime_log = mock.temp_dir.path().join("runtime.log");
+    fs::write(
+        &runtime,
+        format!(
+            "#!/bin/sh\necho \"$@\" >> '{}'\nexec '{}'\n",
+            runtime_log.display(),
+            mock.binary_path().display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&runtime, fs::Permissions::from_mode(0o755)).unwrap();
+    std::env::set_var("CONTAINER_IMAGE", "claude-sandbox:latest");
+    std::env::set_var("CONTAINER_RUNTIME", &runtime);
+    std::env::set_var("SESSION_BACKEND", "cli");
+    let server = TestServer::new_internal(mock).await;
+    std::env::remove_var("CONTAINER_IMAGE");
+    std::env::remove_var("CONTAINER_RUNTIME");
+    std::env::remove_var("SESSION_BACKEND");
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("backend_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let create = |session_id: &str, backend: Option<BackendKind>| {
+        let create_file_command = serde_json::json!({
+            "control": "write_file",
+            "path": server.mock.projects_dir.join(format!("{session_id}.jsonl")).to_string_lossy(),
+            "content": format!(
+                r#"{{"sessionId": "{session_id}", "cwd": "{}", "type": "start"}}"#,
+                working_dir.display()
+            ),
+        })
+        .to_string();
+        client
+            .post(format!("{}/api/v1/sessions", server.base_url))
+            .json(&CreateSessionRequest {
+                session_id: session_id.to_string(),
+                working_dir: working_dir.clone(),
+                bootstrap_messages: vec![create_file_command],
+                options: SessionOptions {
+                    backend,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .send()
+    };
+
+    // SESSION_BACKEND keeps sessions on the host unless they ask for a container
+    let response = create("host-session", None).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(!runtime_log.exists());
+
+    let response = create("boxed-session", Some(BackendKind::Container))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let runtime_args = fs::read_to_string(&runtime_log).unwrap();
+    assert!(runtime_args.contains("--label=chef-de-vibe.session=boxed-session"));
+    assert!(runtime_args.contains("claude-sandbox:latest"));
+
+    let body: GetSessionResponse = client
+        .get(format!("{}/api/v1/sessions/boxed-session", server.base_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body.options.backend, Some(BackendKind::Container));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_create_session_on_unconfigured_backend() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("unboxed_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&serde_json::json!({
+            "session_id": "unboxed-session",
+            "working_dir": working_dir,
+            "resume": false,
+            "bootstrap_messages": [],
+            "backend": "container",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    assert!(server
+        .session_manager
+        .get_session("unboxed-session")
+        .is_none());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_transcript_media() {
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\nfake-pixels";
+    const PNG_BASE64: &str = "iVBORw0KGgpmYWtlLXBpeGVscw==";
+
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("media-work");
+    fs::create_dir_all(&working_dir).unwrap();
+    fs::write(working_dir.join("diagram.png"), PNG).unwrap();
+
+    let project_path = server.mock.projects_dir.join("media-project");
+    fs::create_dir_all(&project_path).unwrap();
+    let entries = [
+        serde_json::json!({
+            "sessionId": "media-session", "cwd": working_dir, "type": "assistant", "uuid": "a1",
+            "message": {"role": "assistant", "content": [
+                {"type": "tool_use", "id": "t1", "name": "Read", "input": {"file_path": "diagram.png"}}
+            ]}
+        }),
+        serde_json::json!({
+            "sessionId": "media-session", "cwd": working_dir, "type": "user", "uuid": "u1",
+            "message": {"role": "user", "content": [{
+                "type": "tool_result", "tool_use_id": "t1",
+                "content": [{"type": "image", "source": {
+                    "type": "base64", "media_type": "image/png", "data": PNG_BASE64
+                }}]
+            }]}
+        }),
+    ];
+    let lines: Vec<String> = entries.iter().map(ToString::to_string).collect();
+    fs::write(
+        project_path.join("media-session.jsonl"),
+        lines.join("\n") + "\n",
+    )
+    .unwrap();
+
+    let body: GetSessionResponse = client
+        .get(format!("{}/api/v1/sessions/media-session", server.base_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let file_url = body.content[0]["message"]["content"][0]["media_url"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let source = &body.content[1]["message"]["content"][0]["content"][0]["source"];
+    assert_eq!(source["type"], "url");
+    assert!(source.get("data").is_none());
+    let image_url = source["url"].as_str().unwrap().to_string();
+    assert_eq!(image_url, "/api/v1/sessions/media-session/media/u1.0");
+
+    for url in [file_url, image_url] {
+        let response = client
+            .get(format!("{}{url}", server.base_url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-type"], "image/png");
+        assert_eq!(response.bytes().await.unwrap().as_ref(), PNG);
+    }
+
+    // The original content is still available on request
+    let body: GetSessionResponse = client
+        .get(format!(
+            "{}/api/v1/sessions/media-session?inline_media=true",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        body.content[1]["message"]["content"][0]["content"][0]["source"]["data"],
+        PNG_BASE64
+    );
+
+    let response = client
+        .get(format!(
+            "{}/api/v1/sessions/media-session/media/u1.5",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    let error: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(error["code"], "MEDIA_NOT_FOUND");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_session_scratch_directory() {
+    let scratch_root = tempfile::TempDir::new().unwrap();
+    std::env::set_var("SCRATCH_DIR", scratch_root.path());
+    let server = TestServer::new().await;
+    std::env::remove_var("SCRATCH_DIR");
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("scratch-work");
+    fs::create_dir_all(&working_dir).unwrap();
+
+    let create_session_file = serde_json::json!({
+        "control": "write_file",
+        "path": server.mock.projects_dir.join("scratch-session.jsonl"),
+        "content": serde_json::json!({
+            "sessionId": "scratch-session", "cwd": working_dir, "type": "start",
+        })
+        .to_string(),
+    });
+    // Claude finds its scratch directory in the environment
+    let write_report = serde_json::json!({
+        "control": "write_file",
+        "path": "$CHEF_DE_VIBE_SCRATCH_DIR/reports/summary.md",
+        "content": "# Summary",
+    });
+    let request = CreateSessionRequest {
+        session_id: "scratch-session".to_string(),
+        working_dir: working_dir.clone(),
+        resume: false,
+        bootstrap_messages: vec![create_session_file.to_string(), write_report.to_string()],
+        ..Default::default()
+    };
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let scratch_url = format!(
+        "{}/api/v1/sessions/scratch-session/scratch",
+        server.base_url
+    );
+    let mut listing = serde_json::Value::Null;
+    for _ in 0..50 {
+        listing = client
+            .get(&scratch_url)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if listing["files"]
+            .as_array()
+            .is_some_and(|files| !files.is_empty())
+        {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(
+        listing["directory"],
+        serde_json::json!(scratch_root.path().join("scratch-session"))
+    );
+    assert_eq!(listing["files"][0]["path"], "reports/summary.md");
+    assert_eq!(listing["files"][0]["size"], 9);
+    assert!(
+        !working_dir.join("reports").exists(),
+        "The working tree stays clean"
+    );
+
+    let response = client
+        .get(format!("{scratch_url}/reports/summary.md"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "text/markdown");
+    assert_eq!(response.text().await.unwrap(), "# Summary");
+
+    let response = client
+        .get(format!("{scratch_url}/missing.txt"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    let error: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(error["code"], "SCRATCH_FILE_NOT_FOUND");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_session_wire_log() {
+    let wire_log_dir = tempfile::TempDir::new().unwrap();
+    std::env::set_var("WIRE_LOG_DIR", wire_log_dir.path());
+    let server = TestServer::new().await;
+    std::env::remove_var("WIRE_LOG_DIR");
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("wire-work");
+    fs::create_dir_all(&working_dir).unwrap();
+
+    let create_session_file = serde_json::json!({
+        "control": "write_file",
+        "path": server.mock.projects_dir.join("wire-session.jsonl"),
+        "content": serde_json::json!({
+            "sessionId": "wire-session", "cwd": working_dir, "type": "start",
+        })
+        .to_string(),
+    });
+    // The mock echoes it back on stdout
+    let probe = serde_json::json!({"type": "wire_probe"});
+    let request = CreateSessionRequest {
+        session_id: "wire-session".to_string(),
+        working_dir: working_dir.clone(),
+        resume: false,
+        bootstrap_messages: vec![create_session_file.to_string(), probe.to_string()],
+        ..Default::default()
+    };
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let wire_log_url = format!("{}/api/v1/sessions/wire-session/wirelog", server.base_url);
+    let mut entries = Vec::new();
+    for _ in 0..50 {
+        let response = client.get(&wire_log_url).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+        entries = response
+            .text()
+            .await
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect();
+        if entries.iter().any(|entry| entry["direction"] == "stdout") {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(entries[0]["direction"], "stdin");
+    assert_eq!(entries[0]["line"], create_session_file.to_string());
+    let echoed = entries
+        .iter()
+        .find(|entry| entry["direction"] == "stdout")
+        .expect("The echoed probe is logged");
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(echoed["line"].as_str().unwrap()).unwrap(),
+        probe
+    );
+
+    let response = client
+        .get(format!(
+            "{}/api/v1/sessions/unknown-session/wirelog",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    let error: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(error["code"], "WIRE_LOG_NOT_FOUND");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_admin_api() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+    let working_dir = server.mock.temp_dir.path().join("work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let create = |session_id: &str| {
+        let create_file_command = serde_json::json!({
+            "control": "write_file",
+            "path": server.mock.projects_dir.join(format!("{session_id}.jsonl")),
+            "content": format!(
+                r#"{{"sessionId": "{session_id}", "cwd": "{}", "type": "start"}}"#,
+                working_dir.display()
+            )
+        })
+        .to_string();
+        client
+            .post(format!("{}/api/v1/sessions", server.base_url))
+            .json(&CreateSessionRequest {
+                session_id: session_id.to_string(),
+                working_dir: working_dir.clone(),
+                resume: false,
+                bootstrap_messages: vec![
+                    create_file_command,
+                    r#"{"role": "user", "content": "Hello"}"#.to_string(),
+                ],
+                ..Default::default()
+            })
+            .send()
+    };
+    assert_eq!(create("admin-session").await.unwrap().status(), 200);
+
+    let status: serde_json::Value = client
+        .get(format!("{}/api/v1/admin/status", server.base_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["draining"], false);
+    let session = &status["sessions"][0];
+    assert_eq!(session["session_id"], "admin-session");
+    assert_eq!(session["status"], "ready");
+    assert_eq!(session["clients"], 0);
+    assert_eq!(session["pending_approvals"], 0);
+    let pid = session["process_id"].as_u64().unwrap();
+    assert!(std::path::Path::new(&format!("/proc/{pid}")).exists());
+
+    // Draining refuses new sessions until it is switched off again
+    let draining: serde_json::Value = client
+        .put(format!("{}/api/v1/admin/draining", server.base_url))
+        .json(&serde_json::json!({"draining": true}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(draining["draining"], true);
+    assert_eq!(create("refused-session").await.unwrap().status(), 503);
+    client
+        .put(format!("{}/api/v1/admin/draining", server.base_url))
+        .json(&serde_json::json!({"draining": false}))
+        .send()
+        .await
+        .unwrap();
+    assert!(!server.session_manager.is_draining());
+
+    create_test_session_file(
+        &server.mock.projects_dir,
+        "project1",
+        "disk-session",
+        "/home/user/project1",
+    );
+    let rescan: serde_json::Value = client
+        .post(format!("{}/api/v1/admin/rescan", server.base_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(rescan["rescanned"].as_u64().unwrap() >= 1);
+
+    let killed: serde_json::Value = client
+        .post(format!(
+            "{}/api/v1/admin/sessions/admin-session/kill",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(killed["process_terminated"], true);
+    assert_eq!(killed["killed"], true);
+    assert!(server
+        .session_manager
+        .get_session("admin-session")
+        .is_none());
+    let mut gone = false;
+    for _ in 0..50 {
+        if !std::path::Path::new(&format!("/proc/{pid}")).exists() {
+            gone = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(gone, "Claude process {pid} is still running");
+
+    let response = client
+        .post(format!(
+            "{}/api/v1/admin/sessions/admin-session/kill",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
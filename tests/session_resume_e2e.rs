@@ -175,6 +175,7 @@ async fn test_session_resume_with_session_id_in_first_message() {
             session_response, // Session ID in first message
             r#"{"role": "user", "content": "Resume this session"}"#.to_string(),
         ],
+        ..Default::default()
     };
 
     let response = client
@@ -283,6 +284,7 @@ async fn test_session_resume_with_session_id_in_second_message() {
             session_response, // Second message - contains session ID
             r#"{"role": "user", "content": "Resume this session"}"#.to_string(),
         ],
+        ..Default::default()
     };
 
     let response = client
@@ -406,6 +408,7 @@ async fn test_session_resume_with_multiple_messages_before_session_id() {
             session_response,  // Message 4 - contains session ID
             r#"{"role": "user", "content": "Resume this session"}"#.to_string(),
         ],
+        ..Default::default()
     };
 
     let response = client
@@ -499,6 +502,7 @@ async fn test_session_resume_fails_when_no_session_id_found() {
             status_response,
             exit_command, // This will make the process exit without sending session_id
         ],
+        ..Default::default()
     };
 
     let response = client
@@ -573,6 +577,7 @@ async fn test_non_resume_session_creation() {
             ready_response, // No session_id needed
             r#"{"role": "user", "content": "Start new session"}"#.to_string(),
         ],
+        ..Default::default()
     };
 
     let response = client
@@ -0,0 +1,615 @@
+mod helpers;
+
+use axum::{extract::State, Json};
+use chef_de_vibe::{
+    api::{auth, handlers::AppState},
+    config::{ApiKey, ApiKeys, ApiToken, Config},
+    models::{
+        CreateSessionRequest, PushSubscriptionsResponse, SessionWatchesResponse,
+        WebPushKeyResponse, WriteMessage,
+    },
+    session_manager::SessionManager,
+};
+use helpers::logging::init_logging;
+use helpers::mock_claude::MockClaude;
+use reqwest::Client;
+use serial_test::serial;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+/// Collects whatever is posted to it, tagged with the path it was posted to
+async fn start_receiver() -> (String, mpsc::UnboundedReceiver<(String, serde_json::Value)>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let app = axum::Router::new()
+        .route(
+            "/*path",
+            axum::routing::post(
+                |State(tx): State<mpsc::UnboundedSender<(String, serde_json::Value)>>,
+                 uri: axum::http::Uri,
+                 Json(body): Json<serde_json::Value>| async move {
+                    let _ = tx.send((uri.path().to_string(), body));
+                },
+            ),
+        )
+        .with_state(tx);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (base_url, rx)
+}
+
+const VAPID_PUBLIC_KEY: &str =
+    "BAG2w1eER_omF5yZN6dq6NKLG39fOGEaVfK1K6lcjePK6ZvNxvnp0jpKMmFYmSxSI4Fh9iffgeAJui-be11nu3k";
+const VAPID_PRIVATE_KEY: &str = "mBGjayvOqZij-n__y-Q0JhArEdEJRkr3u1K_1WBnGaA";
+
+/// A push request as the push service received it
+struct PushRequest {
+    path: String,
+    headers: axum::http::HeaderMap,
+    body_len: usize,
+}
+
+/// Stands in for a browser vendor's push service; endpoints under `/gone` answer as if
+/// the subscription had expired
+async fn start_push_service() -> (String, mpsc::UnboundedReceiver<PushRequest>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let app = axum::Router::new()
+        .route(
+            "/*path",
+            axum::routing::post(
+                |State(tx): State<mpsc::UnboundedSender<PushRequest>>,
+                 uri: axum::http::Uri,
+                 headers: axum::http::HeaderMap,
+                 body: axum::body::Bytes| async move {
+                    let path = uri.path().to_string();
+                    let status = if path.starts_with("/gone") {
+                        axum::http::StatusCode::GONE
+                    } else {
+                        axum::http::StatusCode::CREATED
+                    };
+                    let _ = tx.send(PushRequest {
+                        path,
+                        headers,
+                        body_len: body.len(),
+                    });
+                    status
+                },
+            ),
+        )
+        .with_state(tx);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (base_url, rx)
+}
+
+struct TestServer {
+    pub base_url: String,
+    pub mock: MockClaude,
+    server_handle: tokio::task::JoinHandle<()>,
+    session_manager: Arc<SessionManager>,
+}
+
+impl TestServer {
+    async fn new(receiver_url: &str) -> Self {
+        Self::with_keys(receiver_url, Vec::new()).await
+    }
+
+    async fn with_keys(receiver_url: &str, keys: Vec<ApiKey>) -> Self {
+        init_logging();
+        let mock = MockClaude::new();
+        mock.setup_env_vars();
+        std::env::set_var(
+            "NOTIFICATION_TARGETS",
+            format!("hook=webhook:{receiver_url}/hook;team=slack:{receiver_url}/slack"),
+        );
+
+        let mut config = Config::from_env().expect("Failed to load config");
+        std::env::remove_var("NOTIFICATION_TARGETS");
+        config.api_keys = ApiKeys::new(keys);
+        let api_keys = config.api_keys.clone();
+        let session_manager = Arc::new(SessionManager::new(config.clone()));
+        let state = AppState {
+            session_manager: session_manager.clone(),
+            config: Arc::new(config),
+        };
+
+        let app = axum::Router::new()
+            .route(
+                "/api/v1/sessions",
+                axum::routing::post(chef_de_vibe::api::handlers::create_session),
+            )
+            .route(
+                "/api/v1/sessions/:id/watch",
+                axum::routing::get(chef_de_vibe::api::handlers::get_session_watches)
+                    .put(chef_de_vibe::api::handlers::watch_session)
+                    .delete(chef_de_vibe::api::handlers::unwatch_session),
+            )
+            .route(
+                "/api/v1/push/vapid_public_key",
+                axum::routing::get(chef_de_vibe::api::handlers::get_web_push_key),
+            )
+            .route(
+                "/api/v1/push/subscriptions",
+                axum::routing::get(chef_de_vibe::api::handlers::list_push_subscriptions)
+                    .post(chef_de_vibe::api::handlers::subscribe_push)
+                    .delete(chef_de_vibe::api::handlers::unsubscribe_push),
+            )
+            .with_state(state);
+        let app = if api_keys.is_empty() {
+            app
+        } else {
+            app.layer(axum::middleware::from_fn_with_state(
+                api_keys,
+                auth::require_token,
+            ))
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server_handle = tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        Self {
+            base_url,
+            mock,
+            server_handle,
+            session_manager,
+        }
+    }
+
+    async fn send_to_claude(&self, session_id: &str, message: serde_json::Value) {
+        self.session_manager
+            .enqueue_message(
+                session_id,
+                WriteMessage {
+                    content: message.to_string(),
+                    sender_client_id: "test".to_string(),
+                    timestamp: std::time::SystemTime::now(),
+                },
+            )
+            .await
+            .unwrap();
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server_handle.abort();
+    }
+}
+
+async fn next_delivery(
+    rx: &mut mpsc::UnboundedReceiver<(String, serde_json::Value)>,
+) -> (String, serde_json::Value) {
+    tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("Notification should be delivered")
+        .unwrap()
+}
+
+#[tokio::test]
+#[serial]
+async fn test_watched_session_notifications() {
+    let (receiver_url, mut deliveries) = start_receiver().await;
+    let server = TestServer::new(&receiver_url).await;
+    let client = Client::new();
+
+    let session_id = format!("watch-{}", uuid::Uuid::new_v4());
+    let working_dir = server.mock.temp_dir.path().to_path_buf();
+    let session_file = server
+        .mock
+        .projects_dir()
+        .join(format!("{session_id}.jsonl"));
+    let transcript =
+        serde_json::json!({"sessionId": session_id, "cwd": working_dir, "type": "start"});
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: session_id.clone(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![serde_json::json!({
+                "control": "write_file",
+                "path": session_file,
+                "content": transcript.to_string(),
+            })
+            .to_string()],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let watch_url = format!("{}/api/v1/sessions/{session_id}/watch", server.base_url);
+    let response = client
+        .put(&watch_url)
+        .json(&serde_json::json!({"target": "hook"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client
+        .put(&watch_url)
+        .json(&serde_json::json!({"target": "team", "events": ["approval_needed"]}))
+        .send()
+        .await
+        .unwrap();
+    let watches: SessionWatchesResponse = response.json().await.unwrap();
+    assert_eq!(watches.watches.len(), 2);
+    assert_eq!(watches.watches[0].target, "hook");
+    assert_eq!(watches.watches[0].events.len(), 4);
+
+    // The mock echoes these back as if Claude had produced them
+    server
+        .send_to_claude(
+            &session_id,
+            serde_json::json!({"type": "result", "subtype": "success", "is_error": false, "result": "All done"}),
+        )
+        .await;
+    let (path, body) = next_delivery(&mut deliveries).await;
+    assert_eq!(path, "/hook");
+    assert_eq!(body["event"], "completed");
+    assert_eq!(body["session_id"], session_id.as_str());
+    assert_eq!(body["detail"], "All done");
+
+    server
+        .send_to_claude(
+            &session_id,
+            serde_json::json!({
+                "type": "control_request",
+                "request_id": "req-1",
+                "request": {"subtype": "can_use_tool", "tool_name": "Bash", "input": {}}
+            }),
+        )
+        .await;
+    let mut approval_deliveries = [
+        next_delivery(&mut deliveries).await,
+        next_delivery(&mut deliveries).await,
+    ];
+    approval_deliveries.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(approval_deliveries[0].0, "/hook");
+    assert_eq!(approval_deliveries[0].1["event"], "approval_needed");
+    assert_eq!(approval_deliveries[1].0, "/slack");
+    let text = approval_deliveries[1].1["text"].as_str().unwrap();
+    assert!(text.contains(&session_id));
+    assert!(text.contains("Bash"));
+
+    let response = client
+        .delete(format!("{watch_url}?target=hook"))
+        .send()
+        .await
+        .unwrap();
+    let watches: SessionWatchesResponse = response.json().await.unwrap();
+    assert_eq!(watches.watches.len(), 1);
+    assert_eq!(watches.watches[0].target, "team");
+
+    // Only approvals are watched now, so a completed turn goes unannounced
+    server
+        .send_to_claude(
+            &session_id,
+            serde_json::json!({"type": "result", "subtype": "success", "is_error": false, "result": "Again"}),
+        )
+        .await;
+    assert!(
+        tokio::time::timeout(Duration::from_millis(500), deliveries.recv())
+            .await
+            .is_err(),
+        "Unwatched events should not be delivered"
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_watch_rejects_unknown_target_and_session() {
+    let (receiver_url, _deliveries) = start_receiver().await;
+    let server = TestServer::new(&receiver_url).await;
+    let client = Client::new();
+
+    // Web Push is off unless VAPID keys are configured
+    let response = client
+        .get(format!("{}/api/v1/push/vapid_public_key", server.base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    let response = client
+        .put(format!(
+            "{}/api/v1/sessions/no-such-session/watch",
+            server.base_url
+        ))
+        .json(&serde_json::json!({"target": "hook"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    let session_id = "watch-on-disk";
+    let project_dir = server.mock.projects_dir().join("project");
+    std::fs::create_dir_all(&project_dir).unwrap();
+    std::fs::write(
+        project_dir.join(format!("{session_id}.jsonl")),
+        format!(r#"{{"sessionId": "{session_id}", "cwd": "/tmp", "type": "start"}}"#),
+    )
+    .unwrap();
+
+    let response = client
+        .put(format!(
+            "{}/api/v1/sessions/{session_id}/watch",
+            server.base_url
+        ))
+        .json(&serde_json::json!({"target": "pager"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let error: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(error["code"], "INVALID_REQUEST");
+
+    // Sessions that are only on disk can be watched ahead of being resumed
+    let response = client
+        .put(format!(
+            "{}/api/v1/sessions/{session_id}/watch",
+            server.base_url
+        ))
+        .json(&serde_json::json!({"target": "hook", "events": ["failed"]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client
+        .get(format!(
+            "{}/api/v1/sessions/{session_id}/watch",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    let watches: SessionWatchesResponse = response.json().await.unwrap();
+    assert_eq!(watches.session_id, session_id);
+    assert_eq!(watches.watches.len(), 1);
+    assert_eq!(
+        serde_json::to_value(&watches.watches[0].events).unwrap(),
+        serde_json::json!(["failed"])
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_web_push_notifications() {
+    let (receiver_url, _deliveries) = start_receiver().await;
+    let (push_url, mut pushes) = start_push_service().await;
+    std::env::set_var("WEB_PUSH_VAPID_PUBLIC_KEY", VAPID_PUBLIC_KEY);
+    std::env::set_var("WEB_PUSH_VAPID_PRIVATE_KEY", VAPID_PRIVATE_KEY);
+    std::env::set_var("WEB_PUSH_SUBJECT", "mailto:ops@example.com");
+    let server = TestServer::new(&receiver_url).await;
+    std::env::remove_var("WEB_PUSH_VAPID_PUBLIC_KEY");
+    std::env::remove_var("WEB_PUSH_VAPID_PRIVATE_KEY");
+    std::env::remove_var("WEB_PUSH_SUBJECT");
+    let client = Client::new();
+
+    let response = client
+        .get(format!("{}/api/v1/push/vapid_public_key", server.base_url))
+        .send()
+        .await
+        .unwrap();
+    let key: WebPushKeyResponse = response.json().await.unwrap();
+    assert_eq!(key.public_key, VAPID_PUBLIC_KEY);
+
+    let subscriptions_url = format!("{}/api/v1/push/subscriptions", server.base_url);
+    let subscribe = |endpoint: String, events: serde_json::Value| {
+        client.post(&subscriptions_url).json(&serde_json::json!({
+            "endpoint": endpoint,
+            "expirationTime": null,
+            // Any uncompressed P-256 key will do for a browser that never decrypts
+            "keys": {"p256dh": VAPID_PUBLIC_KEY, "auth": "AAECAwQFBgcICQoLDA0ODw"},
+            "events": events,
+        }))
+    };
+    let response = subscribe(
+        format!("{push_url}/live/1"),
+        serde_json::json!(["completed"]),
+    )
+    .send()
+    .await
+    .unwrap();
+    assert_eq!(response.status(), 200);
+    let response = subscribe(format!("{push_url}/gone/1"), serde_json::json!([]))
+        .send()
+        .await
+        .unwrap();
+    let subscriptions: PushSubscriptionsResponse = response.json().await.unwrap();
+    assert_eq!(subscriptions.identity, "default");
+    assert_eq!(subscriptions.subscriptions.len(), 2);
+
+    let response = client
+        .post(&subscriptions_url)
+        .json(&serde_json::json!({
+            "endpoint": format!("{push_url}/live/2"),
+            "keys": {"p256dh": "short", "auth": "AAECAwQFBgcICQoLDA0ODw"},
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    let session_id = format!("push-{}", uuid::Uuid::new_v4());
+    let working_dir = server.mock.temp_dir.path().to_path_buf();
+    let session_file = server
+        .mock
+        .projects_dir()
+        .join(format!("{session_id}.jsonl"));
+    let transcript =
+        serde_json::json!({"sessionId": session_id, "cwd": working_dir, "type": "start"});
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: session_id.clone(),
+            working_dir,
+            resume: false,
+            bootstrap_messages: vec![serde_json::json!({
+                "control": "write_file",
+                "path": session_file,
+                "content": transcript.to_string(),
+            })
+            .to_string()],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // Pushed to every subscribed browser without the session being watched
+    server
+        .send_to_claude(
+            &session_id,
+            serde_json::json!({"type": "result", "subtype": "success", "is_error": false, "result": "All done"}),
+        )
+        .await;
+    let mut requests = Vec::new();
+    for _ in 0..2 {
+        requests.push(
+            tokio::time::timeout(Duration::from_secs(5), pushes.recv())
+                .await
+                .expect("Notification should be pushed")
+                .unwrap(),
+        );
+    }
+    requests.sort_by(|a, b| a.path.cmp(&b.path));
+    assert_eq!(requests[0].path, "/gone/1");
+    let live = &requests[1];
+    assert_eq!(live.path, "/live/1");
+    assert_eq!(live.headers["content-encoding"], "aes128gcm");
+    assert_eq!(live.headers["urgency"], "normal");
+    assert!(live.headers.contains_key("ttl"));
+    let authorization = live.headers["authorization"].to_str().unwrap();
+    assert!(authorization.starts_with("vapid t="));
+    assert!(authorization.ends_with(&format!(", k={VAPID_PUBLIC_KEY}")));
+    // Salt, record size, key and authentication tag surround the ciphertext
+    assert!(live.body_len > 16 + 4 + 1 + 65 + 16);
+
+    // The push service said the other subscription is gone
+    let mut remaining = Vec::new();
+    for _ in 0..50 {
+        let response = client.get(&subscriptions_url).send().await.unwrap();
+        let subscriptions: PushSubscriptionsResponse = response.json().await.unwrap();
+        remaining = subscriptions.subscriptions;
+        if remaining.len() == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].endpoint, format!("{push_url}/live/1"));
+
+    let response = client
+        .delete(&subscriptions_url)
+        .query(&[("endpoint", format!("{push_url}/live/1"))])
+        .send()
+        .await
+        .unwrap();
+    let subscriptions: PushSubscriptionsResponse = response.json().await.unwrap();
+    assert!(subscriptions.subscriptions.is_empty());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_web_push_only_reaches_session_owners() {
+    let (receiver_url, _deliveries) = start_receiver().await;
+    let (push_url, mut pushes) = start_push_service().await;
+    std::env::set_var("WEB_PUSH_VAPID_PUBLIC_KEY", VAPID_PUBLIC_KEY);
+    std::env::set_var("WEB_PUSH_VAPID_PRIVATE_KEY", VAPID_PRIVATE_KEY);
+    std::env::set_var("WEB_PUSH_SUBJECT", "mailto:ops@example.com");
+    let key = |token: &str, user: &str| ApiKey {
+        token: ApiToken::new(token).unwrap(),
+        user: Some(user.to_string()),
+        admin: false,
+    };
+    let server = TestServer::with_keys(
+        &receiver_url,
+        vec![key("alice-token", "alice"), key("bob-token", "bob")],
+    )
+    .await;
+    std::env::remove_var("WEB_PUSH_VAPID_PUBLIC_KEY");
+    std::env::remove_var("WEB_PUSH_VAPID_PRIVATE_KEY");
+    std::env::remove_var("WEB_PUSH_SUBJECT");
+    let client = Client::new();
+
+    let session_id = format!("push-{}", uuid::Uuid::new_v4());
+    let working_dir = server.mock.temp_dir.path().to_path_buf();
+    let session_file = server
+        .mock
+        .projects_dir()
+        .join(format!("{session_id}.jsonl"));
+    let transcript =
+        serde_json::json!({"sessionId": session_id, "cwd": working_dir, "type": "start"});
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .bearer_auth("bob-token")
+        .json(&CreateSessionRequest {
+            session_id: session_id.clone(),
+            working_dir,
+            resume: false,
+            bootstrap_messages: vec![serde_json::json!({
+                "control": "write_file",
+                "path": session_file,
+                "content": transcript.to_string(),
+            })
+            .to_string()],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let subscribe = |token: &str, user: &str, session_ids: serde_json::Value| {
+        client
+            .post(format!("{}/api/v1/push/subscriptions", server.base_url))
+            .bearer_auth(token)
+            .json(&serde_json::json!({
+                "endpoint": format!("{push_url}/live/{user}"),
+                "keys": {"p256dh": VAPID_PUBLIC_KEY, "auth": "AAECAwQFBgcICQoLDA0ODw"},
+                "session_ids": session_ids,
+            }))
+            .send()
+    };
+    // Bob's session cannot be subscribed to by name, nor reached through a catch-all
+    let response = subscribe("alice-token", "alice", serde_json::json!([session_id]))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    for (token, user) in [("alice-token", "alice"), ("bob-token", "bob")] {
+        let response = subscribe(token, user, serde_json::json!([])).await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    server
+        .send_to_claude(
+            &session_id,
+            serde_json::json!({"type": "result", "subtype": "success", "is_error": false, "result": "All done"}),
+        )
+        .await;
+    let push = tokio::time::timeout(Duration::from_secs(5), pushes.recv())
+        .await
+        .expect("Notification should be pushed")
+        .unwrap();
+    assert_eq!(push.path, "/live/bob");
+    assert!(
+        tokio::time::timeout(Duration::from_secs(1), pushes.recv())
+            .await
+            .is_err(),
+        "Alice should not be notified about Bob's session"
+    );
+}
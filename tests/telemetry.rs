@@ -0,0 +1,191 @@
+mod helpers;
+
+use chef_de_vibe::{
+    api::handlers::AppState, config::Config, models::CreateSessionRequest,
+    session_manager::SessionManager, telemetry,
+};
+use helpers::logging::init_logging;
+use helpers::mock_claude::MockClaude;
+use reqwest::Client;
+use serial_test::serial;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+struct TestServer {
+    pub base_url: String,
+    pub mock: MockClaude,
+    server_handle: tokio::task::JoinHandle<()>,
+}
+
+impl TestServer {
+    async fn new() -> Self {
+        init_logging();
+        let mock = MockClaude::new();
+        mock.setup_env_vars();
+
+        let config = Config::from_env().expect("Failed to load config");
+        let session_manager = Arc::new(SessionManager::new(config.clone()));
+        let state = AppState {
+            session_manager: session_manager.clone(),
+            config: Arc::new(config),
+        };
+
+        let app = axum::Router::new()
+            .route(
+                "/api/v1/sessions",
+                axum::routing::post(chef_de_vibe::api::handlers::create_session),
+            )
+            .route(
+                "/api/v1/sessions/:id",
+                axum::routing::get(chef_de_vibe::api::handlers::get_session),
+            )
+            .route(
+                "/api/v1/telemetry",
+                axum::routing::get(chef_de_vibe::api::handlers::get_telemetry_preview),
+            )
+            .route(
+                "/api/v1/admin/slo",
+                axum::routing::get(chef_de_vibe::api::handlers::get_slo_report),
+            )
+            .route_layer(axum::middleware::from_fn_with_state(
+                session_manager.telemetry().clone(),
+                telemetry::count_requests,
+            ))
+            .with_state(state);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server_handle = tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        Self {
+            base_url,
+            mock,
+            server_handle,
+        }
+    }
+}
+
+impl TestServer {
+    async fn create_session(&self, client: &Client) -> (String, std::path::PathBuf) {
+        let session_id = format!("telemetry-{}", uuid::Uuid::new_v4());
+        let working_dir = self.mock.temp_dir.path().to_path_buf();
+        let session_file = self.mock.projects_dir().join(format!("{session_id}.jsonl"));
+        let response = client
+            .post(format!("{}/api/v1/sessions", self.base_url))
+            .json(&CreateSessionRequest {
+                session_id: session_id.clone(),
+                working_dir: working_dir.clone(),
+                resume: false,
+                bootstrap_messages: vec![serde_json::json!({
+                    "control": "write_file",
+                    "path": session_file,
+                    "content": serde_json::json!({"sessionId": session_id, "cwd": working_dir, "type": "start"}).to_string(),
+                })
+                .to_string()],
+                ..Default::default()
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        (session_id, working_dir)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server_handle.abort();
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_telemetry_preview() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let (session_id, working_dir) = server.create_session(&client).await;
+
+    let response = client
+        .get(format!(
+            "{}/api/v1/sessions/no-such-session",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    let preview: serde_json::Value = client
+        .get(format!("{}/api/v1/telemetry", server.base_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(preview["enabled"], false);
+    assert!(preview.get("endpoint").is_none());
+
+    let report = &preview["report"];
+    assert_eq!(report["sessions"]["created"], 1);
+    assert_eq!(report["routes"]["POST /api/v1/sessions"]["requests"], 1);
+    assert_eq!(
+        report["routes"]["GET /api/v1/sessions/:id"],
+        serde_json::json!({"requests": 1, "client_errors": 1, "server_errors": 0})
+    );
+
+    // Nothing identifying a session or directory makes it into the report
+    let serialized = report.to_string();
+    assert!(!serialized.contains(&session_id));
+    assert!(!serialized.contains("no-such-session"));
+    assert!(!serialized.contains(working_dir.to_str().unwrap()));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_slo_report() {
+    std::env::set_var("SLO_TARGETS_MS", "session_spawn=1");
+    let server = TestServer::new().await;
+    std::env::remove_var("SLO_TARGETS_MS");
+    let client = Client::new();
+    server.create_session(&client).await;
+
+    let report: serde_json::Value = client
+        .get(format!("{}/api/v1/admin/slo", server.base_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["window_secs"], 3600);
+    let operation = |name: &str| {
+        report["operations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|operation| operation["operation"] == name)
+            .unwrap()
+            .clone()
+    };
+
+    // Starting the mock takes longer than a millisecond
+    let spawn = operation("session_spawn");
+    assert_eq!(spawn["samples"], 1);
+    assert_eq!(spawn["availability"], 1.0);
+    assert_eq!(spawn["target_ms"], 1);
+    assert_eq!(spawn["breaches"], 1);
+    assert!(spawn["p99_ms"].as_f64().unwrap() > 1.0);
+
+    let journal = operation("journal_ready");
+    assert_eq!(journal["samples"], 1);
+    assert!(journal.get("target_ms").is_none());
+    assert_eq!(journal["breaches"], 0);
+
+    assert_eq!(operation("approval_round_trip")["samples"], 0);
+}
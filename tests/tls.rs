@@ -0,0 +1,67 @@
+use chef_de_vibe::{config::TlsSettings, tls};
+use std::path::PathBuf;
+use tokio::net::TcpListener;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+fn settings() -> TlsSettings {
+    TlsSettings {
+        cert_path: fixture("tls_cert.pem"),
+        key_path: fixture("tls_key.pem"),
+    }
+}
+
+#[tokio::test]
+async fn test_serves_https() {
+    let rustls_config = tls::load(&settings()).await.unwrap();
+    let app = axum::Router::new().route("/", axum::routing::get(|| async { "frontend" }));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(tls::serve(listener, app, rustls_config, async {
+        let _ = shutdown_rx.await;
+    }));
+
+    let cert =
+        reqwest::Certificate::from_pem(&std::fs::read(fixture("tls_cert.pem")).unwrap()).unwrap();
+    let client = reqwest::Client::builder()
+        .add_root_certificate(cert)
+        .build()
+        .unwrap();
+    let response = client
+        .get(format!("https://localhost:{}/", addr.port()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "frontend");
+
+    // Plain HTTP gets no answer it can read
+    assert!(reqwest::get(format!("http://{addr}/")).await.is_err());
+
+    // Idle keep-alive connections would hold up shutdown until the drain timeout
+    drop(client);
+    shutdown_tx.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_unusable_certificate_fails_loading() {
+    let missing = TlsSettings {
+        cert_path: fixture("missing_cert.pem"),
+        key_path: fixture("tls_key.pem"),
+    };
+    assert!(tls::load(&missing).await.is_err());
+
+    // The key where the certificate belongs
+    let swapped = TlsSettings {
+        cert_path: fixture("tls_key.pem"),
+        key_path: fixture("tls_cert.pem"),
+    };
+    assert!(tls::load(&swapped).await.is_err());
+}
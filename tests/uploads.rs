@@ -0,0 +1,228 @@
+mod helpers;
+
+use chef_de_vibe::{
+    api::handlers::AppState, config::Config, models::CreateSessionRequest,
+    session_manager::SessionManager,
+};
+use helpers::logging::init_logging;
+use helpers::mock_claude::MockClaude;
+use reqwest::Client;
+use serial_test::serial;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+struct TestServer {
+    pub base_url: String,
+    pub mock: MockClaude,
+    server_handle: tokio::task::JoinHandle<()>,
+}
+
+impl TestServer {
+    async fn new() -> Self {
+        init_logging();
+        let mock = MockClaude::new();
+        mock.setup_env_vars();
+        // Make the mock record user messages in its transcript like Claude would
+        std::env::set_var("MOCK_CLAUDE_REPLY", "1");
+
+        let config = Config::from_env().expect("Failed to load config");
+        let session_manager = Arc::new(SessionManager::new(config.clone()));
+        let state = AppState {
+            session_manager,
+            config: Arc::new(config),
+        };
+
+        let app = axum::Router::new()
+            .route(
+                "/api/v1/sessions",
+                axum::routing::post(chef_de_vibe::api::handlers::create_session),
+            )
+            .route(
+                "/api/v1/sessions/:id",
+                axum::routing::get(chef_de_vibe::api::handlers::get_session),
+            )
+            .route(
+                "/api/v1/uploads",
+                axum::routing::post(chef_de_vibe::api::handlers::create_upload),
+            )
+            .route(
+                "/api/v1/uploads/:id",
+                axum::routing::get(chef_de_vibe::api::handlers::get_upload)
+                    .put(chef_de_vibe::api::handlers::append_upload),
+            )
+            .with_state(state);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server_handle = tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        Self {
+            base_url,
+            mock,
+            server_handle,
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server_handle.abort();
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_session_with_uploaded_first_message() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let pasted = "fn main() {}\n".repeat(1000);
+    let (first, second) = pasted.split_at(5000);
+
+    let upload: serde_json::Value = client
+        .post(format!("{}/api/v1/uploads", server.base_url))
+        .json(&serde_json::json!({"total_size": pasted.len()}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let upload_id = upload["upload_id"].as_str().unwrap().to_string();
+    let upload_url = format!("{}/api/v1/uploads/{upload_id}", server.base_url);
+    assert_eq!(upload["offset"], 0);
+    assert_eq!(upload["complete"], false);
+
+    let status: serde_json::Value = client
+        .put(format!("{upload_url}?offset=0"))
+        .body(first.to_string())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["offset"], 5000);
+
+    // A chunk sent again after a lost response is refused; the status says where to resume
+    let response = client
+        .put(format!("{upload_url}?offset=0"))
+        .body(first.to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let status: serde_json::Value = client
+        .get(&upload_url)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["offset"], 5000);
+
+    let session_id = format!("upload-{}", uuid::Uuid::new_v4());
+    let request = CreateSessionRequest {
+        session_id: session_id.clone(),
+        working_dir: server.mock.temp_dir.path().to_path_buf(),
+        resume: false,
+        bootstrap_messages: vec![],
+        first_message_upload: Some(upload_id.clone()),
+        ..Default::default()
+    };
+
+    // Incomplete uploads cannot be used yet
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    let status: serde_json::Value = client
+        .put(format!("{upload_url}?offset=5000"))
+        .body(second.to_string())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["complete"], true);
+
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let session: serde_json::Value = client
+        .get(format!("{}/api/v1/sessions/{session_id}", server.base_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let first_message = session["content"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["type"] == "user")
+        .expect("Uploaded message should reach Claude");
+    assert_eq!(first_message["message"]["content"], pasted.as_str());
+
+    // The upload is used up once the session has it
+    let response = client.get(&upload_url).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+    let error: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(error["code"], "UPLOAD_NOT_FOUND");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_upload_limits() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let response = client
+        .post(format!("{}/api/v1/uploads", server.base_url))
+        .json(&serde_json::json!({"total_size": 1u64 << 40}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    let response = client
+        .put(format!(
+            "{}/api/v1/uploads/no-such-upload?offset=0",
+            server.base_url
+        ))
+        .body("data")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    let response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: "upload-missing".to_string(),
+            working_dir: server.mock.temp_dir.path().to_path_buf(),
+            bootstrap_messages: vec![],
+            first_message_upload: Some("no-such-upload".to_string()),
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
@@ -58,6 +58,7 @@ fn create_session_request_with_file(
             create_file_command,
             r#"{"role": "assistant", "content": "Session started successfully"}"#.to_string(),
         ],
+        ..Default::default()
     }
 }
 
@@ -99,14 +100,46 @@ impl TestServer {
                 "/api/v1/sessions/:id",
                 axum::routing::get(chef_de_vibe::api::handlers::get_session),
             )
+            .route(
+                "/api/v1/usage",
+                axum::routing::get(chef_de_vibe::api::handlers::get_usage),
+            )
+            .route(
+                "/api/v1/sessions/:id/debug",
+                axum::routing::put(chef_de_vibe::api::handlers::set_session_debug),
+            )
+            .route(
+                "/api/v1/sessions/:id/queue",
+                axum::routing::get(chef_de_vibe::api::handlers::get_session_queue),
+            )
+            .route(
+                "/api/v1/sessions/:id/clients",
+                axum::routing::get(chef_de_vibe::api::handlers::get_session_clients),
+            )
+            .route(
+                "/api/v1/sessions/:id/queue/:client_id",
+                axum::routing::post(chef_de_vibe::api::handlers::resolve_held_messages),
+            )
             .route(
                 "/api/v1/sessions/:id/claude_ws",
                 axum::routing::get(chef_de_vibe::api::websocket::websocket_handler),
             )
+            .route(
+                "/api/v1/sessions/:id/events",
+                axum::routing::get(chef_de_vibe::api::sse::session_events),
+            )
+            .route(
+                "/api/v1/sessions/:id/messages",
+                axum::routing::post(chef_de_vibe::api::sse::send_message),
+            )
             .route(
                 "/api/v1/sessions/:id/claude_approvals_ws",
                 axum::routing::get(chef_de_vibe::api::websocket::approval_websocket_handler),
             )
+            .route(
+                "/api/v1/events_ws",
+                axum::routing::get(chef_de_vibe::api::websocket::events_websocket_handler),
+            )
             .with_state(state);
 
         // Find a free port
@@ -302,6 +335,473 @@ async fn test_multiple_websocket_clients() {
     let _ = ws2.close(None).await;
 }
 
+#[tokio::test]
+#[serial]
+async fn test_websocket_presence_relay() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("presence_work");
+    fs::create_dir_all(&working_dir).unwrap();
+
+    let request = create_session_request_with_file(
+        "presence-session",
+        &working_dir,
+        &server.mock.projects_dir,
+    );
+
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(create_response.status(), 200);
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut ws1, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    let (mut ws2, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+
+    // Drain initial messages
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    while timeout(Duration::from_millis(100), ws1.next())
+        .await
+        .is_ok()
+    {}
+    while timeout(Duration::from_millis(100), ws2.next())
+        .await
+        .is_ok()
+    {}
+
+    let presence = r#"{"type": "presence", "state": {"viewing": "msg-1", "selection": [3, 9]}}"#;
+    ws1.send(Message::Text(presence.to_string())).await.unwrap();
+
+    // The other client receives the state tagged with the sender's ID
+    let frame = match timeout(Duration::from_secs(2), ws2.next()).await {
+        Ok(Some(Ok(Message::Text(text)))) => {
+            serde_json::from_str::<serde_json::Value>(&text).unwrap()
+        }
+        other => panic!("Client 2 should have received a presence frame, got: {other:?}"),
+    };
+    assert_eq!(frame["type"], "presence");
+    assert_eq!(frame["state"]["viewing"], "msg-1");
+    let sender_id = frame["client_id"].as_str().unwrap().to_string();
+
+    // Presence is neither echoed to the sender nor written to Claude
+    assert!(
+        timeout(Duration::from_millis(500), ws1.next())
+            .await
+            .is_err(),
+        "Sender should not receive its own presence or a Claude echo"
+    );
+
+    // A late joiner gets the current presence snapshot
+    let (mut ws3, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    let mut snapshot = None;
+    while let Ok(Some(Ok(Message::Text(text)))) = timeout(Duration::from_secs(1), ws3.next()).await
+    {
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        if value["type"] == "presence" {
+            snapshot = Some(value);
+            break;
+        }
+    }
+    let snapshot = snapshot.expect("Late joiner should receive existing presence");
+    assert_eq!(snapshot["client_id"], sender_id.as_str());
+    assert_eq!(snapshot["state"]["selection"][1], 9);
+
+    // Disconnecting clears the client's presence for everyone else
+    let _ = ws1.close(None).await;
+    let frame = match timeout(Duration::from_secs(2), ws2.next()).await {
+        Ok(Some(Ok(Message::Text(text)))) => {
+            serde_json::from_str::<serde_json::Value>(&text).unwrap()
+        }
+        other => panic!("Client 2 should have been told client 1 left, got: {other:?}"),
+    };
+    assert_eq!(frame["client_id"], sender_id.as_str());
+    assert!(frame["state"].is_null());
+
+    let _ = ws2.close(None).await;
+    let _ = ws3.close(None).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_websocket_ack_frames() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("ack_work");
+    fs::create_dir_all(&working_dir).unwrap();
+
+    let request =
+        create_session_request_with_file("ack-session", &working_dir, &server.mock.projects_dir);
+
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(create_response.status(), 200);
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+
+    let ws_url = format!("{}{}?ack=true", server.ws_url, session_data.websocket_url);
+    let (mut ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while timeout(Duration::from_millis(200), ws.next()).await.is_ok() {}
+
+    ws.send(Message::Text(
+        r#"{"role": "user", "content": "count me"}"#.to_string(),
+    ))
+    .await
+    .unwrap();
+
+    // Input echo and Claude's reply both carry increasing sequence numbers
+    let mut seqs = Vec::new();
+    while let Ok(Some(Ok(Message::Text(text)))) = timeout(Duration::from_secs(2), ws.next()).await {
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        seqs.push(
+            value["seq"]
+                .as_u64()
+                .expect("Frame should be tagged with seq"),
+        );
+        if seqs.len() == 2 {
+            break;
+        }
+    }
+    assert_eq!(seqs.len(), 2);
+    assert!(seqs[0] < seqs[1]);
+
+    let session = server
+        .session_manager
+        .get_session(&session_data.session_id)
+        .unwrap();
+    let lag = session.get_delivery_lag().await;
+    assert_eq!(lag.len(), 1);
+    assert!(lag[0].1 >= 2);
+
+    // Acks are consumed by the server and never reach Claude
+    ws.send(Message::Text(format!(r#"{{"ack": {}}}"#, seqs[1])))
+        .await
+        .unwrap();
+    assert!(
+        timeout(Duration::from_millis(500), ws.next())
+            .await
+            .is_err(),
+        "Ack frames should not be echoed"
+    );
+    let lag = session.get_delivery_lag().await;
+    assert_eq!(lag[0].1, 0);
+
+    let _ = ws.close(None).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_websocket_stream_events_opt_in() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("stream_work");
+    fs::create_dir_all(&working_dir).unwrap();
+
+    let request =
+        create_session_request_with_file("stream-session", &working_dir, &server.mock.projects_dir);
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(create_response.status(), 200);
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut streaming, _) = connect_async(Url::parse(&format!("{ws_url}?stream=true")).unwrap())
+        .await
+        .unwrap();
+    let (mut plain, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while timeout(Duration::from_millis(300), streaming.next())
+        .await
+        .is_ok()
+    {}
+    while timeout(Duration::from_millis(300), plain.next())
+        .await
+        .is_ok()
+    {}
+
+    // The mock echoes this back, so it reads as partial output from Claude
+    streaming
+        .send(Message::Text(
+            r#"{"type": "stream_event", "event": {"type": "content_block_delta", "delta": {"type": "text_delta", "text": "Hel"}}}"#
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+
+    // Both clients see the input echo; only the streaming one also gets Claude's event
+    let mut counts = Vec::new();
+    for ws in [&mut streaming, &mut plain] {
+        let mut stream_events = 0;
+        while let Ok(Some(Ok(msg))) = timeout(Duration::from_secs(1), ws.next()).await {
+            if let Message::Text(text) = msg {
+                if text.contains("text_delta") {
+                    stream_events += 1;
+                }
+            }
+        }
+        counts.push(stream_events);
+    }
+    assert_eq!(counts, vec![2, 1]);
+
+    let _ = streaming.close(None).await;
+    let _ = plain.close(None).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_websocket_timestamps() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("timestamps_work");
+    fs::create_dir_all(&working_dir).unwrap();
+
+    let request = create_session_request_with_file(
+        "timestamps-session",
+        &working_dir,
+        &server.mock.projects_dir,
+    );
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(create_response.status(), 200);
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+
+    let ws_url = format!(
+        "{}{}?timestamps=true",
+        server.ws_url, session_data.websocket_url
+    );
+    let (mut ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while timeout(Duration::from_millis(200), ws.next()).await.is_ok() {}
+
+    ws.send(Message::Text(
+        r#"{"role": "user", "content": "when?"}"#.to_string(),
+    ))
+    .await
+    .unwrap();
+
+    // Both the input echo and Claude's reply say when the server got and relayed them
+    let mut frames = 0;
+    while let Ok(Some(Ok(Message::Text(text)))) = timeout(Duration::from_secs(2), ws.next()).await {
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        let received =
+            chrono::DateTime::parse_from_rfc3339(value["server_received_at"].as_str().unwrap())
+                .unwrap();
+        let sent = chrono::DateTime::parse_from_rfc3339(value["server_sent_at"].as_str().unwrap())
+            .unwrap();
+        assert!(received <= sent);
+        assert!(value.get("seq").is_none());
+        frames += 1;
+        if frames == 2 {
+            break;
+        }
+    }
+    assert_eq!(frames, 2);
+
+    let _ = ws.close(None).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_session_debug_capture() {
+    let state_dir = tempfile::TempDir::new().unwrap();
+    std::env::set_var("SESSION_STATE_DIR", state_dir.path());
+    let server = TestServer::new().await;
+    std::env::remove_var("SESSION_STATE_DIR");
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("debug_work");
+    fs::create_dir_all(&working_dir).unwrap();
+
+    let request =
+        create_session_request_with_file("debug-session", &working_dir, &server.mock.projects_dir);
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(create_response.status(), 200);
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+    let debug_url = format!(
+        "{}/api/v1/sessions/{}/debug",
+        server.base_url, session_data.session_id
+    );
+
+    let enable_response = client
+        .put(&debug_url)
+        .json(&serde_json::json!({"enabled": true, "duration_secs": 60}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(enable_response.status(), 200);
+    let enabled: serde_json::Value = enable_response.json().await.unwrap();
+    assert_eq!(enabled["enabled"], true);
+    assert_eq!(enabled["expires_in_secs"], 60);
+    let debug_file = std::path::PathBuf::from(enabled["debug_file"].as_str().unwrap());
+    assert!(debug_file.starts_with(state_dir.path()));
+
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while timeout(Duration::from_millis(200), ws.next()).await.is_ok() {}
+    ws.send(Message::Text(
+        r#"{"role": "user", "content": "trace me"}"#.to_string(),
+    ))
+    .await
+    .unwrap();
+    let _ = timeout(Duration::from_secs(2), ws.next()).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // Both directions of the exchange end up in the capture file
+    let capture = fs::read_to_string(&debug_file).unwrap();
+    assert!(capture
+        .lines()
+        .any(|line| line.contains(">> ") && line.contains("trace me")));
+    assert!(capture
+        .lines()
+        .any(|line| line.contains("<< ") && line.contains("trace me")));
+
+    let disable_response = client
+        .put(&debug_url)
+        .json(&serde_json::json!({"enabled": false}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(disable_response.status(), 200);
+    let session = server
+        .session_manager
+        .get_session(&session_data.session_id)
+        .unwrap();
+    assert!(session.debug_capture.lock().await.is_none());
+
+    let missing_response = client
+        .put(format!("{}/api/v1/sessions/nope/debug", server.base_url))
+        .json(&serde_json::json!({"enabled": true}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing_response.status(), 404);
+
+    let _ = ws.close(None).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_session_through_sdk_socket_bridge() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let bridge_dir = tempfile::TempDir::new().unwrap();
+    let socket_path = bridge_dir.path().join("bridge.sock");
+    let listener = tokio::net::UnixListener::bind(&socket_path).unwrap();
+    std::env::set_var("CLAUDE_SDK_SOCKET", &socket_path);
+    let server = TestServer::new().await;
+    std::env::remove_var("CLAUDE_SDK_SOCKET");
+    let client = Client::new();
+
+    // Bridge that writes the transcript like the SDK would and echoes every message
+    let projects_dir = server.mock.projects_dir.clone();
+    let (hang_up_tx, hang_up_rx) = tokio::sync::oneshot::channel::<()>();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let start: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        let session_id = start["session_id"].as_str().unwrap().to_string();
+        fs::write(
+            projects_dir.join(format!("{session_id}.jsonl")),
+            format!(r#"{{"sessionId": "{session_id}", "cwd": "/tmp", "type": "start"}}"#),
+        )
+        .unwrap();
+        writer
+            .write_all(
+                format!("{{\"type\":\"started\",\"session_id\":\"{session_id}\"}}\n").as_bytes(),
+            )
+            .await
+            .unwrap();
+        let echo = async {
+            while let Ok(Some(line)) = lines.next_line().await {
+                writer
+                    .write_all(format!("{line}\n").as_bytes())
+                    .await
+                    .unwrap();
+            }
+        };
+        tokio::select! {
+            () = echo => {}
+            _ = hang_up_rx => {}
+        }
+    });
+
+    let working_dir = server.mock.temp_dir.path().join("bridge_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&CreateSessionRequest {
+            session_id: "bridged-session".to_string(),
+            working_dir: working_dir.clone(),
+            resume: false,
+            bootstrap_messages: vec![r#"{"role": "user", "content": "hello"}"#.to_string()],
+            ..Default::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(create_response.status(), 200);
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+
+    let session = server
+        .session_manager
+        .get_session(&session_data.session_id)
+        .unwrap();
+    assert!(session.is_active().await);
+    assert_eq!(session.get_process_id().await, None);
+
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while timeout(Duration::from_millis(200), ws.next()).await.is_ok() {}
+    ws.send(Message::Text(
+        r#"{"role": "user", "content": "over the bridge"}"#.to_string(),
+    ))
+    .await
+    .unwrap();
+    let echoed = timeout(Duration::from_secs(2), ws.next())
+        .await
+        .expect("Bridge should echo the message")
+        .unwrap()
+        .unwrap();
+    assert!(echoed.to_text().unwrap().contains("over the bridge"));
+
+    // The bridge hanging up ends the session
+    hang_up_tx.send(()).unwrap();
+    let mut closed = false;
+    for _ in 0..20 {
+        if !session.is_active().await {
+            closed = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(closed, "Session should end when the bridge hangs up");
+}
+
 #[tokio::test]
 #[serial]
 async fn test_websocket_message_broadcasting() {
@@ -1014,7 +1514,7 @@ async fn test_multiple_client_message_broadcasting_sequence() {
     );
 
     // Longer delay before next message to ensure proper processing
-    tokio::time::sleep(Duration::from_millis(1000)).await;
+    tokio::time::sleep(Duration::from_secs(1)).await;
 
     // Client 2 sends message
     let msg2 = r#"{"role": "user", "content": "Message from client 2"}"#;
@@ -1092,3 +1592,777 @@ async fn test_multiple_client_message_broadcasting_sequence() {
     let _ = ws2.close(None).await;
     let _ = ws3.close(None).await;
 }
+
+#[tokio::test]
+#[serial]
+async fn test_held_messages_released_through_queue_api() {
+    std::env::set_var("DISCONNECT_QUEUE_POLICY", "hold");
+    let server = TestServer::new().await;
+    std::env::remove_var("DISCONNECT_QUEUE_POLICY");
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("queue_work");
+    fs::create_dir_all(&working_dir).unwrap();
+
+    let request =
+        create_session_request_with_file("queue-session", &working_dir, &server.mock.projects_dir);
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(create_response.status(), 200);
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+    let queue_url = format!(
+        "{}/api/v1/sessions/{}/queue",
+        server.base_url, session_data.session_id
+    );
+
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while timeout(Duration::from_millis(200), ws.next()).await.is_ok() {}
+
+    // What a client that went away under the hold policy leaves behind
+    let session = server
+        .session_manager
+        .get_session(&session_data.session_id)
+        .unwrap();
+    session
+        .held_messages
+        .lock()
+        .await
+        .push(chef_de_vibe::models::WriteMessage {
+            content: r#"{"role": "user", "content": "held back"}"#.to_string(),
+            sender_client_id: "gone-client".to_string(),
+            timestamp: std::time::SystemTime::now(),
+        });
+
+    let queue: serde_json::Value = client
+        .get(&queue_url)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(queue["disconnect_policy"], "hold");
+    assert_eq!(queue["clients"][0]["client_id"], "gone-client");
+    assert_eq!(queue["clients"][0]["connected"], false);
+    assert_eq!(queue["clients"][0]["queued"], serde_json::json!([]));
+    assert_eq!(
+        queue["clients"][0]["held"][0]["content"],
+        r#"{"role": "user", "content": "held back"}"#
+    );
+
+    let release_response = client
+        .post(format!("{queue_url}/gone-client"))
+        .json(&serde_json::json!({"action": "release"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(release_response.status(), 200);
+    let released: serde_json::Value = release_response.json().await.unwrap();
+    assert_eq!(released["action"], "release");
+    assert_eq!(released["count"], 1);
+
+    // Once released the message reaches Claude, which echoes it back
+    let echoed = timeout(Duration::from_secs(5), async {
+        while let Some(Ok(Message::Text(text))) = ws.next().await {
+            if text.contains("held back") {
+                return true;
+            }
+        }
+        false
+    })
+    .await
+    .unwrap_or(false);
+    assert!(echoed);
+
+    let queue: serde_json::Value = client
+        .get(&queue_url)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(queue["clients"], serde_json::json!([]));
+
+    let missing_response = client
+        .get(format!("{}/api/v1/sessions/nope/queue", server.base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing_response.status(), 404);
+
+    let _ = ws.close(None).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_session_turn_limit() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("turn_limit_work");
+    fs::create_dir_all(&working_dir).unwrap();
+
+    let mut request = create_session_request_with_file(
+        "turn-limit-session",
+        &working_dir,
+        &server.mock.projects_dir,
+    );
+    request.options.max_turns = Some(1);
+
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(create_response.status(), 200);
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut ws_stream, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while timeout(Duration::from_millis(300), ws_stream.next())
+        .await
+        .is_ok()
+    {}
+
+    // The mock echoes this back, so it reads as Claude finishing a turn
+    ws_stream
+        .send(Message::Text(
+            r#"{"type": "result", "subtype": "success"}"#.to_string(),
+        ))
+        .await
+        .unwrap();
+
+    let notice = timeout(Duration::from_secs(5), async {
+        while let Some(Ok(msg)) = ws_stream.next().await {
+            if let Message::Text(text) = msg {
+                if text.contains("limit_reached") {
+                    return text;
+                }
+            }
+        }
+        panic!("WebSocket closed before the limit notice");
+    })
+    .await
+    .expect("Should have received the limit notice");
+    assert!(notice.contains(r#""limit":"turns""#), "Got: {notice}");
+
+    let session: serde_json::Value = client
+        .get(format!(
+            "{}/api/v1/sessions/{}",
+            server.base_url, session_data.session_id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(session["status"], "limit_reached");
+    assert_eq!(session["max_turns"], 1);
+
+    // Input past the limit is neither written to Claude nor relayed to clients
+    ws_stream
+        .send(Message::Text(
+            r#"{"role": "user", "content": "one more thing"}"#.to_string(),
+        ))
+        .await
+        .unwrap();
+    while let Ok(Some(Ok(msg))) = timeout(Duration::from_secs(1), ws_stream.next()).await {
+        if let Message::Text(text) = msg {
+            assert!(!text.contains("one more thing"), "Got: {text}");
+        }
+    }
+
+    let _ = ws_stream.close(None).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_session_usage_accounting() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("usage_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let request =
+        create_session_request_with_file("usage-session", &working_dir, &server.mock.projects_dir);
+
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(create_response.status(), 200);
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut ws_stream, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+
+    // The mock echoes these back, so they read as Claude finishing two turns; the cost
+    // Claude reports is that of its whole run so far
+    for (input_tokens, total_cost_usd) in [(100, 0.5), (40, 0.75)] {
+        let result = serde_json::json!({
+            "type": "result",
+            "subtype": "success",
+            "total_cost_usd": total_cost_usd,
+            "usage": {"input_tokens": input_tokens, "output_tokens": 10, "cache_read_input_tokens": 5},
+        });
+        ws_stream
+            .send(Message::Text(result.to_string()))
+            .await
+            .unwrap();
+    }
+
+    let session_url = format!(
+        "{}/api/v1/sessions/{}",
+        server.base_url, session_data.session_id
+    );
+    let usage = timeout(Duration::from_secs(5), async {
+        loop {
+            let session: serde_json::Value = client
+                .get(&session_url)
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            if session["usage"]["turns"] == 2 {
+                return session["usage"].clone();
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("Both turns should have been accounted");
+    assert_eq!(usage["input_tokens"], 140);
+    assert_eq!(usage["output_tokens"], 20);
+    assert_eq!(usage["cache_read_input_tokens"], 10);
+    assert_eq!(usage["cost_usd"], 0.75);
+
+    let report: serde_json::Value = client
+        .get(format!("{}/api/v1/usage", server.base_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["total"]["turns"], 2);
+    assert_eq!(report["by_day"].as_array().unwrap().len(), 1);
+    assert_eq!(report["by_working_dir"][0]["input_tokens"], 140);
+
+    let response = client
+        .get(format!(
+            "{}/api/v1/usage?since=2024-05-02&until=2024-05-01",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_client_inputs_recorded_to_sidecar() {
+    let state_dir = tempfile::TempDir::new().unwrap();
+    std::env::set_var("SESSION_STATE_DIR", state_dir.path());
+    std::env::set_var("RECORD_CLIENT_INPUTS", "true");
+    let server = TestServer::new().await;
+    std::env::remove_var("SESSION_STATE_DIR");
+    std::env::remove_var("RECORD_CLIENT_INPUTS");
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("record_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let request =
+        create_session_request_with_file("record-session", &working_dir, &server.mock.projects_dir);
+    let session_data: CreateSessionResponse = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    while timeout(Duration::from_millis(200), ws.next()).await.is_ok() {}
+    ws.send(Message::Text(
+        r#"{"role": "user", "content": "remember me"}"#.to_string(),
+    ))
+    .await
+    .unwrap();
+    let _ = timeout(Duration::from_secs(2), ws.next()).await;
+
+    let sidecar = state_dir
+        .path()
+        .join(format!("{}.inputs.jsonl", session_data.session_id));
+    let lines: Vec<serde_json::Value> = fs::read_to_string(&sidecar)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["type"], "orchestrator_input");
+    assert_eq!(lines[0]["sessionId"], session_data.session_id.as_str());
+    assert_eq!(lines[0]["content"]["content"], "remember me");
+
+    let session: serde_json::Value = client
+        .get(format!(
+            "{}/api/v1/sessions/{}",
+            server.base_url, session_data.session_id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        session["recorded_inputs"][0]["content"]["content"],
+        "remember me"
+    );
+
+    let _ = ws.close(None).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_write_pacing_reports_queue_position() {
+    std::env::set_var("STDIN_MESSAGES_PER_SECOND", "2");
+    std::env::set_var("STDIN_BURST", "1");
+    let server = TestServer::new().await;
+    std::env::remove_var("STDIN_MESSAGES_PER_SECOND");
+    std::env::remove_var("STDIN_BURST");
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("pacing_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let request =
+        create_session_request_with_file("pacing-session", &working_dir, &server.mock.projects_dir);
+    let session_data: CreateSessionResponse = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut ws, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    // Let the bootstrap write use up the burst and the bucket refill
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    while timeout(Duration::from_millis(200), ws.next()).await.is_ok() {}
+
+    for n in 1..=3 {
+        ws.send(Message::Text(format!(
+            r#"{{"role": "user", "content": "burst {n}"}}"#
+        )))
+        .await
+        .unwrap();
+    }
+
+    // The burst of one goes through, the rest wait their turn
+    let queue: serde_json::Value = client
+        .get(format!(
+            "{}/api/v1/sessions/{}/queue",
+            server.base_url, session_data.session_id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let queued = queue["clients"][0]["queued"].as_array().unwrap();
+    assert!(
+        !queued.is_empty(),
+        "Paced messages should wait in the queue"
+    );
+
+    let mut positions = Vec::new();
+    let mut echoed = 0;
+    while echoed < 6 {
+        let Ok(Some(Ok(Message::Text(frame)))) = timeout(Duration::from_secs(3), ws.next()).await
+        else {
+            panic!("Paced messages should all be written, got {echoed} echoes");
+        };
+        let frame: serde_json::Value = serde_json::from_str(&frame).unwrap();
+        if frame["type"] == "queued" {
+            assert!(frame["estimated_wait_ms"].as_u64().unwrap() > 0);
+            positions.push(frame["position"].as_u64().unwrap());
+        } else if frame["content"]
+            .as_str()
+            .is_some_and(|content| content.starts_with("burst"))
+        {
+            // Each message comes back once as client input and once from Claude
+            echoed += 1;
+        }
+    }
+    assert_eq!(
+        positions.len(),
+        2,
+        "Only the held back messages are reported"
+    );
+    assert!(positions
+        .iter()
+        .all(|&position| (1..=3).contains(&position)));
+
+    let _ = ws.close(None).await;
+}
+
+/// Reads the event stream until an event's `data` satisfies `matches`, returning it.
+async fn next_sse_data(
+    response: &mut reqwest::Response,
+    buffer: &mut String,
+    matches: impl Fn(&str, &str) -> bool,
+) -> String {
+    loop {
+        while let Some(end) = buffer.find("\n\n") {
+            let event: String = buffer.drain(..end + 2).collect();
+            let name = event
+                .lines()
+                .find_map(|line| line.strip_prefix("event: "))
+                .unwrap_or("message");
+            let data = event
+                .lines()
+                .filter_map(|line| line.strip_prefix("data: "))
+                .collect::<Vec<_>>()
+                .join("\n");
+            if matches(name, &data) {
+                return data;
+            }
+        }
+        let chunk = response.chunk().await.unwrap().expect("Event stream ended");
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_session_events_and_messages() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("sse_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let request =
+        create_session_request_with_file("sse-session", &working_dir, &server.mock.projects_dir);
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(create_response.status(), 200);
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+    let session_url = format!(
+        "{}/api/v1/sessions/{}",
+        server.base_url, session_data.session_id
+    );
+
+    let mut events = client
+        .get(format!("{session_url}/events"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(events.status(), 200);
+    assert_eq!(events.headers()["content-type"], "text/event-stream");
+    let mut buffer = String::new();
+    let connected = timeout(
+        Duration::from_secs(5),
+        next_sse_data(&mut events, &mut buffer, |name, _| name == "connected"),
+    )
+    .await
+    .expect("The stream should announce the client");
+    let client_id = serde_json::from_str::<serde_json::Value>(&connected).unwrap()["client_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let message =
+        serde_json::json!({"type": "user", "message": {"role": "user", "content": "over sse"}});
+    let response = client
+        .post(format!("{session_url}/messages?client_id={client_id}"))
+        .json(&message)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+    let sent: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(sent["client_id"], client_id.as_str());
+
+    // The input is broadcast back, then echoed by the mock as Claude output
+    for _ in 0..2 {
+        let data = timeout(
+            Duration::from_secs(5),
+            next_sse_data(&mut events, &mut buffer, |name, data| {
+                name == "message" && data.contains("over sse")
+            }),
+        )
+        .await
+        .expect("The message should come back on the stream");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&data).unwrap(),
+            message
+        );
+    }
+
+    let missing = client
+        .post(format!(
+            "{}/api/v1/sessions/missing/messages",
+            server.base_url
+        ))
+        .json(&message)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), 404);
+    let missing = client
+        .get(format!(
+            "{}/api/v1/sessions/missing/events",
+            server.base_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), 404);
+}
+
+/// Reads text frames until one contains `needle`.
+async fn next_frame_containing(
+    ws_stream: &mut tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >,
+    needle: &str,
+) -> serde_json::Value {
+    timeout(Duration::from_secs(5), async {
+        loop {
+            if let Some(Ok(Message::Text(text))) = ws_stream.next().await {
+                if text.contains(needle) {
+                    return serde_json::from_str(&text).unwrap();
+                }
+            }
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("No frame containing {needle}"))
+}
+
+#[tokio::test]
+#[serial]
+async fn test_websocket_resume_after_seq() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("resume_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let request =
+        create_session_request_with_file("resume-session", &working_dir, &server.mock.projects_dir);
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(create_response.status(), 200);
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+
+    let (mut ws_stream, _) =
+        connect_async(Url::parse(&format!("{ws_url}?resumable=true")).unwrap())
+            .await
+            .unwrap();
+    let first =
+        serde_json::json!({"type": "user", "message": {"role": "user", "content": "first"}});
+    ws_stream
+        .send(Message::Text(first.to_string()))
+        .await
+        .unwrap();
+    // The input is broadcast, then echoed back by the mock
+    let input = next_frame_containing(&mut ws_stream, "first").await;
+    let echo = next_frame_containing(&mut ws_stream, "first").await;
+    let seen = echo["session_seq"].as_u64().unwrap();
+    assert!(input["session_seq"].as_u64().unwrap() < seen);
+    ws_stream.close(None).await.unwrap();
+
+    let session_url = format!(
+        "{}/api/v1/sessions/{}",
+        server.base_url, session_data.session_id
+    );
+    let second =
+        serde_json::json!({"type": "user", "message": {"role": "user", "content": "second"}});
+    let response = client
+        .post(format!("{session_url}/messages"))
+        .json(&second)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+    // Let the mock echo it while nobody listens
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let (mut ws_stream, _) =
+        connect_async(Url::parse(&format!("{ws_url}?after_seq={seen}")).unwrap())
+            .await
+            .unwrap();
+    let replayed = next_frame_containing(&mut ws_stream, "second").await;
+    assert_eq!(replayed["session_seq"], seen + 1);
+    let replayed = next_frame_containing(&mut ws_stream, "second").await;
+    assert_eq!(replayed["session_seq"], seen + 2);
+
+    // A sequence number the session never reached is reported as a gap
+    let (mut ws_stream, _) =
+        connect_async(Url::parse(&format!("{ws_url}?after_seq=100000")).unwrap())
+            .await
+            .unwrap();
+    let gap = next_frame_containing(&mut ws_stream, "replay_gap").await;
+    assert_eq!(gap["after_seq"], 100_000);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_clients_that_miss_pongs_are_dropped() {
+    std::env::set_var("WEBSOCKET_PING_INTERVAL", "1");
+    std::env::set_var("WEBSOCKET_MAX_MISSED_PONGS", "1");
+    let server = TestServer::new().await;
+    std::env::remove_var("WEBSOCKET_PING_INTERVAL");
+    std::env::remove_var("WEBSOCKET_MAX_MISSED_PONGS");
+    let client = Client::new();
+
+    let working_dir = server.mock.temp_dir.path().join("heartbeat_work");
+    fs::create_dir_all(&working_dir).unwrap();
+
+    let request = create_session_request_with_file(
+        "heartbeat-session",
+        &working_dir,
+        &server.mock.projects_dir,
+    );
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(create_response.status(), 200);
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+    let clients_url = format!(
+        "{}/api/v1/sessions/{}/clients",
+        server.base_url, session_data.session_id
+    );
+
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut alive, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    // Never read from, so its pings go unanswered
+    let (_silent, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+
+    let clients: serde_json::Value = client
+        .get(&clients_url)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(clients["clients"].as_array().unwrap().len(), 2);
+
+    // Reading answers the pings
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(3500);
+    while tokio::time::Instant::now() < deadline {
+        let _ = timeout(Duration::from_millis(100), alive.next()).await;
+    }
+
+    let clients: serde_json::Value = client
+        .get(&clients_url)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let clients = clients["clients"].as_array().unwrap();
+    assert_eq!(
+        clients.len(),
+        1,
+        "silent client should be dropped: {clients:?}"
+    );
+    assert_eq!(clients[0]["channel"], "main");
+    let connected_at: chrono::DateTime<chrono::Utc> =
+        serde_json::from_value(clients[0]["connected_at"].clone()).unwrap();
+    let last_seen_at: chrono::DateTime<chrono::Utc> =
+        serde_json::from_value(clients[0]["last_seen_at"].clone()).unwrap();
+    assert!(last_seen_at > connected_at);
+
+    let missing = client
+        .get(format!("{}/api/v1/sessions/nope/clients", server.base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), 404);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_events_websocket_reports_session_lifecycle() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+
+    let events_url = format!("{}/api/v1/events_ws", server.ws_url);
+    let (mut events, _) = connect_async(Url::parse(&events_url).unwrap())
+        .await
+        .unwrap();
+
+    let working_dir = server.mock.temp_dir.path().join("events_work");
+    fs::create_dir_all(&working_dir).unwrap();
+    let session_id = generate_unique_session_id("events-session");
+    let request =
+        create_session_request_with_file(&session_id, &working_dir, &server.mock.projects_dir);
+    let create_response = client
+        .post(format!("{}/api/v1/sessions", server.base_url))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(create_response.status(), 200);
+    let session_data: CreateSessionResponse = create_response.json().await.unwrap();
+
+    let created = next_frame_containing(&mut events, "session_created").await;
+    assert_eq!(created["session_id"], session_id.as_str());
+    assert_eq!(
+        created["working_directory"],
+        working_dir.to_string_lossy().as_ref()
+    );
+    let active = next_frame_containing(&mut events, "session_active").await;
+    assert_eq!(active["session_id"], session_id.as_str());
+
+    // Make Claude die underneath the session
+    let ws_url = format!("{}{}", server.ws_url, session_data.websocket_url);
+    let (mut ws_stream, _) = connect_async(Url::parse(&ws_url).unwrap()).await.unwrap();
+    ws_stream
+        .send(Message::Text(
+            serde_json::json!({"control": "exit", "code": 1}).to_string(),
+        ))
+        .await
+        .unwrap();
+
+    let inactive = next_frame_containing(&mut events, "session_inactive").await;
+    assert_eq!(inactive["session_id"], session_id.as_str());
+    let exited = next_frame_containing(&mut events, "claude_exited").await;
+    assert_eq!(exited["session_id"], session_id.as_str());
+    assert_eq!(exited["crashed"], true);
+    assert_eq!(exited["restarting"], false);
+}
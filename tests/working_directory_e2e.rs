@@ -177,6 +177,7 @@ async fn test_active_session_working_directory() {
             create_file_command1,
             r#"{"role": "user", "content": "Test message 1"}"#.to_string(),
         ],
+        ..Default::default()
     };
 
     let response1 = client
@@ -210,6 +211,7 @@ async fn test_active_session_working_directory() {
             create_file_command2,
             r#"{"role": "user", "content": "Test message 2"}"#.to_string(),
         ],
+        ..Default::default()
     };
 
     let response2 = client
@@ -442,6 +444,7 @@ async fn test_session_resume_working_directory() {
             session_init_response,
             r#"{"role": "user", "content": "Resume this session"}"#.to_string(),
         ],
+        ..Default::default()
     };
 
     let resume_response = client
@@ -512,6 +515,7 @@ async fn test_list_sessions_working_directories() {
             create_file_command,
             r#"{"role": "user", "content": "Active session"}"#.to_string(),
         ],
+        ..Default::default()
     };
 
     client
@@ -655,6 +659,7 @@ async fn test_mixed_active_inactive_working_directories() {
             web_create_file_command,
             r#"{"role": "user", "content": "Working on web frontend"}"#.to_string(),
         ],
+        ..Default::default()
     };
 
     let api_request = CreateSessionRequest {
@@ -665,6 +670,7 @@ async fn test_mixed_active_inactive_working_directories() {
             api_create_file_command,
             r#"{"role": "user", "content": "Working on backend API"}"#.to_string(),
         ],
+        ..Default::default()
     };
 
     // Create the active sessions
@@ -797,6 +803,7 @@ async fn test_working_directory_consistency_across_lifecycle() {
             create_file_command,
             r#"{"role": "user", "content": "Starting lifecycle test"}"#.to_string(),
         ],
+        ..Default::default()
     };
 
     let create_response = client